@@ -0,0 +1,69 @@
+//! Volume and mixing kernels on 8-channel 48kHz audio
+//!
+//! Every render thread scales what it plays by the volume and the capture
+//! thread adds mixed sources to what it captured; this measures how many
+//! times faster than real time each instruction set does both:
+//!
+//! ```bash
+//! cargo bench --bench mixing
+//! ```
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use wemux::audio::SimdLevel;
+
+/// Length of each measurement
+const RUN_TIME: Duration = Duration::from_secs(2);
+
+/// Audio processed per pass: 10ms of 8 channels at 48kHz
+const FRAMES: usize = 480;
+const CHANNELS: usize = 8;
+const PASS: Duration = Duration::from_millis(10);
+
+/// Times faster than real time `kernel` processes a pass of audio
+fn realtime_factor(mut kernel: impl FnMut()) -> f64 {
+    let start = Instant::now();
+    let mut passes = 0u32;
+    while start.elapsed() < RUN_TIME {
+        for _ in 0..100 {
+            kernel();
+        }
+        passes += 100;
+    }
+    PASS.as_secs_f64() * passes as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let samples = |scale: f32| -> Vec<u8> {
+        (0..FRAMES * CHANNELS)
+            .flat_map(|n| (((n % 200) as f32 / 100.0 - 1.0) * scale).to_le_bytes())
+            .collect()
+    };
+    let source = samples(0.5);
+
+    println!("kernel  volume x realtime  mix x realtime");
+    let mut scalar = None;
+    for level in SimdLevel::available() {
+        let mut data = samples(1.0);
+        // Halving and doubling keeps the samples in range over millions of passes
+        let mut gain = 0.5;
+        let volume = realtime_factor(|| {
+            gain = 1.0 / gain;
+            level.scale_f32(black_box(&mut data), gain);
+        });
+        let mut data = samples(0.5);
+        let mix = realtime_factor(|| {
+            level.add_scaled_f32(black_box(&mut data), black_box(&source), -0.5);
+        });
+
+        let (base_volume, base_mix) = *scalar.get_or_insert((volume, mix));
+        println!(
+            "{:>6}  {:>10.0} ({:.1}x)  {:>7.0} ({:.1}x)",
+            level.to_string(),
+            volume,
+            volume / base_volume,
+            mix,
+            mix / base_mix
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Ring buffer throughput with one writer and several readers
+//!
+//! The capture thread writes while every render thread reads; this measures
+//! how long the readers take to get through a render pass each as their
+//! number grows:
+//!
+//! ```bash
+//! cargo bench --bench ring_buffer
+//! ```
+//!
+//! `wemux bench` measures the same setup for its number of outputs.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+use wemux::audio::{ReaderState, RingBuffer};
+
+/// 10ms of 48kHz stereo float, a typical capture packet
+const PACKET_BYTES: usize = 3840;
+
+/// Read size of a render pass
+const READ_BYTES: usize = 1920;
+
+/// Time for `readers` readers to each copy `passes` passes of audio while
+/// one writer fills the buffer
+fn read_passes(readers: usize, passes: u64) -> Duration {
+    let buffer = Arc::new(RingBuffer::new(PACKET_BYTES * 64));
+    let stop = Arc::new(AtomicBool::new(false));
+    let ready = Arc::new(Barrier::new(readers + 1));
+
+    let writer = {
+        let buffer = buffer.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let packet = vec![0x55u8; PACKET_BYTES];
+            while !stop.load(Ordering::Relaxed) {
+                buffer.write(&packet);
+            }
+        })
+    };
+
+    let handles: Vec<_> = (0..readers)
+        .map(|_| {
+            let buffer = buffer.clone();
+            let ready = ready.clone();
+            thread::spawn(move || {
+                let mut reader = ReaderState::new(&buffer);
+                let mut data = vec![0u8; READ_BYTES];
+                ready.wait();
+                // Overwritten audio is read all the same; only the copying counts
+                let mut left = passes * READ_BYTES as u64;
+                while left > 0 {
+                    left = left.saturating_sub(reader.read(&buffer, &mut data) as u64);
+                }
+            })
+        })
+        .collect();
+
+    ready.wait();
+    let start = Instant::now();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    stop.store(true, Ordering::Relaxed);
+    let _ = writer.join();
+    elapsed
+}
+
+fn ring_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ring_buffer");
+    for readers in [1, 2, 4, 8] {
+        group.throughput(Throughput::Bytes((READ_BYTES * readers) as u64));
+        group.bench_with_input(
+            BenchmarkId::new("readers", readers),
+            &readers,
+            |b, &readers| b.iter_custom(|passes| read_passes(readers, passes)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ring_buffer);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+//! Generates `wemux.h`, the C header for the `ffi` feature, into `OUT_DIR`
+//!
+//! Set `WEMUX_HEADER_DIR` to also copy it there, e.g. `WEMUX_HEADER_DIR=include`
+//! to refresh the committed header after changing `src/ffi.rs`.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    use cbindgen::{Builder, Config, EnumConfig, Language, RenameRule};
+    use std::path::{Path, PathBuf};
+
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-env-changed=WEMUX_HEADER_DIR");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    let header = PathBuf::from(out_dir).join("wemux.h");
+    // Variants as WEMUX_EVENT_KIND_..., so they can't clash in C
+    let mut config = Config::default();
+    config.language = Language::C;
+    config.include_guard = Some("WEMUX_H".to_string());
+    config.header = Some("/* Generated from src/ffi.rs by build.rs; do not edit */".to_string());
+    config.cpp_compat = true;
+    config.enumeration = EnumConfig {
+        rename_variants: RenameRule::ScreamingSnakeCase,
+        prefix_with_name: true,
+        ..Default::default()
+    };
+    Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", crate_dir))
+        .generate()
+        .expect("Failed to generate the C header")
+        .write_to_file(&header);
+
+    // A relative directory is taken from the crate's root
+    if let Some(dir) = std::env::var_os("WEMUX_HEADER_DIR") {
+        let dir = Path::new(&crate_dir).join(dir);
+        std::fs::create_dir_all(&dir).expect("Failed to create WEMUX_HEADER_DIR");
+        std::fs::copy(&header, dir.join("wemux.h")).expect("Failed to copy the C header");
+    }
+}
@@ -0,0 +1,29 @@
+//! Run the wemux engine from another program
+//!
+//! Duplicates the default output to every HDMI device until Ctrl+C:
+//!
+//! ```bash
+//! cargo run --example embed
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wemux::audio::{AudioEngine, EngineConfig};
+
+fn main() -> wemux::Result<()> {
+    tracing_subscriber::fmt().init();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))
+        .expect("Failed to set Ctrl+C handler");
+
+    let config = EngineConfig {
+        buffer_ms: 60,
+        ..Default::default()
+    };
+    let mut engine = AudioEngine::new(config);
+
+    println!("Playing to all HDMI outputs, press Ctrl+C to stop");
+    engine.run_blocking(&stop)
+}
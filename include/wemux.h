@@ -0,0 +1,217 @@
+/* Generated from src/ffi.rs by build.rs; do not edit */
+
+#ifndef WEMUX_H
+#define WEMUX_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded
+ */
+#define WEMUX_OK 0
+
+/**
+ * The call failed; `wemux_last_error` says why
+ */
+#define WEMUX_ERROR -1
+
+/**
+ * What an event passed to a `WemuxEventCallback` is about
+ */
+typedef enum WemuxEventKind {
+  /**
+   * The default output changed
+   */
+  WEMUX_EVENT_KIND_DEFAULT_DEVICE_CHANGED = 0,
+  /**
+   * A device was added, removed, enabled or disabled
+   */
+  WEMUX_EVENT_KIND_DEVICE_LIST_CHANGED = 1,
+  /**
+   * An output kept underrunning and got a larger buffer
+   */
+  WEMUX_EVENT_KIND_BUFFER_TUNED = 2,
+  /**
+   * An output receives a Dolby/DTS bitstream and passes it on untouched
+   */
+  WEMUX_EVENT_KIND_PASSTHROUGH = 3,
+  /**
+   * An output stopped responding and was reopened
+   */
+  WEMUX_EVENT_KIND_RECOVERED = 4,
+  /**
+   * An output reaches the same speakers as another one and was paused
+   */
+  WEMUX_EVENT_KIND_DUPLICATE_SUPPRESSED = 5,
+  /**
+   * An output failed to start or to take audio
+   */
+  WEMUX_EVENT_KIND_RENDERER_FAILED = 6,
+  /**
+   * An output fell behind and skipped audio
+   */
+  WEMUX_EVENT_KIND_BUFFER_OVERRUN = 7,
+  /**
+   * The fallback source started (message "fallback") or stopped ("source")
+   */
+  WEMUX_EVENT_KIND_SOURCE_SWITCHED = 8,
+  /**
+   * An announcement started (message "active") or ended ("inactive")
+   */
+  WEMUX_EVENT_KIND_DUCKING = 9,
+  /**
+   * Capturing the source failed
+   */
+  WEMUX_EVENT_KIND_CAPTURE_FAILED = 10,
+  /**
+   * The engine can't continue and should be restarted
+   */
+  WEMUX_EVENT_KIND_FATAL = 11,
+  /**
+   * The clock master's device was removed; message is the output that
+   * took over, NULL if none is left
+   */
+  WEMUX_EVENT_KIND_MASTER_CHANGED = 12,
+  /**
+   * An output that failed (`RendererFailed`) plays again
+   */
+  WEMUX_EVENT_KIND_RENDERER_WORKING = 13,
+  /**
+   * Capturing the source works again after `CaptureFailed`
+   */
+  WEMUX_EVENT_KIND_CAPTURE_WORKING = 14,
+} WemuxEventKind;
+
+/**
+ * An engine, the thread it runs on and the callback its events go to
+ */
+typedef struct WemuxEngine WemuxEngine;
+
+/**
+ * Called on a thread of the library for every engine event
+ *
+ * `device_id` and `message` may be NULL and are only valid during the call.
+ */
+typedef void (*WemuxEventCallback)(enum WemuxEventKind kind,
+                                   const char *device_id,
+                                   const char *message,
+                                   void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Version of the library, e.g. "0.2.1"
+ */
+const char *wemux_version(void);
+
+/**
+ * Description of the last error on the calling thread, or NULL
+ *
+ * Valid until the next failing call on the same thread.
+ */
+const char *wemux_last_error(void);
+
+/**
+ * Release a string returned by the library
+ *
+ * # Safety
+ *
+ * `value` must be NULL or a string returned by the library, not yet released.
+ */
+void wemux_string_free(char *value);
+
+/**
+ * Create an engine from a configuration in the service config's TOML
+ * format (NULL = defaults: the default output to every HDMI output)
+ *
+ * Returns NULL on error. Release the engine with `wemux_engine_free`.
+ *
+ * # Safety
+ *
+ * `config` must be NULL or a NUL-terminated string.
+ */
+struct WemuxEngine *wemux_engine_new(const char *config);
+
+/**
+ * Stop and release an engine
+ *
+ * # Safety
+ *
+ * `engine` must be NULL or an engine from `wemux_engine_new`, not yet released.
+ */
+void wemux_engine_free(struct WemuxEngine *engine);
+
+/**
+ * Send the engine's events to `callback` (NULL = stop sending them)
+ *
+ * # Safety
+ *
+ * `engine` must be a live engine. `callback` must be safe to call from
+ * another thread with `user_data` until it is replaced or the engine released.
+ */
+int32_t wemux_engine_set_event_callback(struct WemuxEngine *engine,
+                                        WemuxEventCallback callback,
+                                        void *user_data);
+
+/**
+ * Start capturing and playing to the outputs
+ *
+ * # Safety
+ *
+ * `engine` must be a live engine.
+ */
+int32_t wemux_engine_start(struct WemuxEngine *engine);
+
+/**
+ * Stop the engine; it can be started again
+ *
+ * # Safety
+ *
+ * `engine` must be a live engine.
+ */
+int32_t wemux_engine_stop(struct WemuxEngine *engine);
+
+/**
+ * Whether the engine is running
+ *
+ * # Safety
+ *
+ * `engine` must be NULL or a live engine.
+ */
+bool wemux_engine_is_running(const struct WemuxEngine *engine);
+
+/**
+ * Pause or resume one output of the running engine
+ *
+ * # Safety
+ *
+ * `engine` must be a live engine and `device_id` a NUL-terminated string.
+ */
+int32_t wemux_engine_set_device_paused(struct WemuxEngine *engine,
+                                       const char *device_id,
+                                       bool paused);
+
+/**
+ * The outputs as a JSON array of objects with `id`, `name`, `is_enabled`,
+ * `is_paused`, `is_system_default` and, if set, `alias`
+ *
+ * While running these are the engine's outputs; while stopped, every
+ * output device, none enabled. Returns NULL on error; release the string
+ * with `wemux_string_free`.
+ *
+ * # Safety
+ *
+ * `engine` must be a live engine.
+ */
+char *wemux_engine_list_devices(const struct WemuxEngine *engine);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* WEMUX_H */
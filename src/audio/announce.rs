@@ -0,0 +1,250 @@
+//! Announcements played over the source, which is ducked meanwhile
+//!
+//! A microphone, or an output a paging or file player app plays to, is
+//! captured alongside the main source. While it is audible the main source is
+//! turned down on every output so the announcement can be heard; a moment
+//! after it goes quiet the source fades back to full volume.
+
+use crate::audio::fallback::is_audible;
+use crate::audio::{AudioFormat, ReaderState, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How far the source is turned down during an announcement, unless configured
+pub const DEFAULT_DUCK_DB: f32 = 15.0;
+
+/// Time the source takes to duck once an announcement starts
+const ATTACK: Duration = Duration::from_millis(50);
+
+/// Time the source takes to return to full volume
+const RELEASE: Duration = Duration::from_millis(500);
+
+/// Silence within an announcement that doesn't end it, e.g. pauses in speech
+const HOLD: Duration = Duration::from_secs(1);
+
+/// Source played over the main one, ducking it while audible
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    /// Device ID, alias or part of a device name; outputs are looked up
+    /// first, then inputs
+    pub device: String,
+    /// Decibels the main source is turned down by while the announcement plays
+    #[serde(default = "default_duck_db")]
+    pub duck_db: f32,
+}
+
+fn default_duck_db() -> f32 {
+    DEFAULT_DUCK_DB
+}
+
+impl FromStr for Announcement {
+    type Err = String;
+
+    /// Parse "DEVICE" or "DEVICE@DB"
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (device, duck_db) = match text.rsplit_once('@') {
+            Some((device, db)) => {
+                let db = db
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid number of decibels '{}'", db))?;
+                (device, db)
+            }
+            None => (text, default_duck_db()),
+        };
+
+        let device = device.trim();
+        if device.is_empty() {
+            return Err(format!("no device in '{}'", text));
+        }
+        if !(0.0..=60.0).contains(&duck_db) {
+            return Err(format!("ducking {}dB out of range 0 - 60", duck_db));
+        }
+        Ok(Self {
+            device: device.to_string(),
+            duck_db,
+        })
+    }
+}
+
+/// Mixes an announcement's 32-bit float audio over captured audio, ducking
+/// the captured audio while the announcement is audible
+pub(crate) struct Ducker {
+    buffer: Arc<RingBuffer>,
+    reader: ReaderState,
+    /// Most audio the announcement may have waiting beyond what one mix consumes
+    max_backlog: usize,
+    /// Bytes per frame
+    frame_bytes: usize,
+    /// Gain of the source during an announcement
+    ducked_gain: f32,
+    /// Gain change per frame while ducking and while restoring
+    attack_step: f32,
+    release_step: f32,
+    gain: f32,
+    /// When the announcement was last audible
+    last_audible: Option<Instant>,
+    active: bool,
+    scratch: Vec<u8>,
+}
+
+impl Ducker {
+    /// Play audio written to `buffer` from now on over audio in `format`
+    pub(crate) fn new(
+        buffer: Arc<RingBuffer>,
+        duck_db: f32,
+        format: &AudioFormat,
+        max_backlog: usize,
+    ) -> Self {
+        let frames = |duration: Duration| {
+            (format.sample_rate as f32 * duration.as_secs_f32())
+                .round()
+                .max(1.0)
+        };
+        let ducked_gain = 10f32.powf(-duck_db.abs() / 20.0);
+        Self {
+            reader: ReaderState::new(&buffer),
+            buffer,
+            max_backlog,
+            frame_bytes: format.block_align as usize,
+            ducked_gain,
+            attack_step: (1.0 - ducked_gain) / frames(ATTACK),
+            release_step: (1.0 - ducked_gain) / frames(RELEASE),
+            gain: 1.0,
+            last_audible: None,
+            active: false,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Bytes of whole frames the announcement has waiting
+    pub(crate) fn pending(&self) -> usize {
+        let available = self.reader.available(&self.buffer);
+        available - available % self.frame_bytes
+    }
+
+    /// Mix the announcement's waiting audio into `data` at `now`, ducking
+    /// what `data` held while the announcement plays
+    ///
+    /// Returns the new state when an announcement starts (true) or ends (false).
+    pub(crate) fn process(&mut self, data: &mut [u8], now: Instant) -> Option<bool> {
+        if self.reader.is_lagging(&self.buffer) {
+            self.reader.catch_up(&self.buffer);
+        }
+
+        // Drop what the announcement got ahead, so its latency stays bounded
+        let available = self.reader.available(&self.buffer);
+        let excess = available.saturating_sub(data.len() + self.max_backlog);
+        if excess > 0 {
+            self.scratch.resize(excess, 0);
+            self.reader.read(&self.buffer, &mut self.scratch);
+        }
+        self.scratch.resize(data.len(), 0);
+        let read = self.reader.read(&self.buffer, &mut self.scratch);
+        self.scratch[read..].fill(0);
+
+        let change = if is_audible(&self.scratch[..read]) {
+            self.last_audible = Some(now);
+            (!self.active).then(|| {
+                self.active = true;
+                true
+            })
+        } else if self.active
+            && self
+                .last_audible
+                .is_none_or(|at| now.duration_since(at) >= HOLD)
+        {
+            self.active = false;
+            Some(false)
+        } else {
+            None
+        };
+
+        let target = if self.active { self.ducked_gain } else { 1.0 };
+        for (frame, announced) in data
+            .chunks_exact_mut(self.frame_bytes)
+            .zip(self.scratch.chunks_exact(self.frame_bytes))
+        {
+            self.gain = if self.gain > target {
+                (self.gain - self.attack_step).max(target)
+            } else {
+                (self.gain + self.release_step).min(target)
+            };
+            for (d, s) in frame.chunks_exact_mut(4).zip(announced.chunks_exact(4)) {
+                let source = f32::from_le_bytes([d[0], d[1], d[2], d[3]]);
+                let announcement = f32::from_le_bytes([s[0], s[1], s[2], s[3]]);
+                let mixed = (source * self.gain + announcement).clamp(-1.0, 1.0);
+                d.copy_from_slice(&mixed.to_le_bytes());
+            }
+        }
+        change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{default_channel_mask, SampleEncoding};
+
+    fn samples(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn values(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_announcement_ducks_source() {
+        assert_eq!(
+            "Mic@20".parse::<Announcement>().unwrap(),
+            Announcement {
+                device: "Mic".to_string(),
+                duck_db: 20.0
+            }
+        );
+        assert_eq!("Mic".parse::<Announcement>().unwrap().duck_db, 15.0);
+        assert!("Mic@-3".parse::<Announcement>().is_err());
+
+        // Mono at 80Hz: the source ducks in 4 frames and comes back in 40
+        let format = AudioFormat {
+            sample_rate: 80,
+            channels: 1,
+            bits_per_sample: 32,
+            block_align: 4,
+            encoding: SampleEncoding::Float,
+            channel_mask: default_channel_mask(1),
+        };
+        let announcements = Arc::new(RingBuffer::new(1024));
+        let mut ducker = Ducker::new(announcements.clone(), 20.0, &format, 1024);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut data = samples(&[1.0; 4]);
+        assert_eq!(ducker.process(&mut data, at(0)), None);
+        assert_eq!(values(&data), [1.0; 4]);
+
+        announcements.write(&samples(&[0.5; 6]));
+        assert_eq!(ducker.pending(), 24);
+        let mut data = samples(&[0.4; 6]);
+        assert_eq!(ducker.process(&mut data, at(50)), Some(true));
+        let mixed = values(&data);
+        for (value, expected) in mixed.iter().zip([0.81, 0.72, 0.63, 0.54, 0.54, 0.54]) {
+            assert!((value - expected).abs() < 1e-5, "{:?}", mixed);
+        }
+
+        // A pause shorter than the hold keeps the source ducked
+        let mut data = samples(&[1.0; 2]);
+        assert_eq!(ducker.process(&mut data, at(500)), None);
+        assert!(values(&data).iter().all(|v| (v - 0.1).abs() < 1e-5));
+
+        let mut data = samples(&[1.0; 2]);
+        assert_eq!(ducker.process(&mut data, at(1050)), Some(false));
+        let restored = values(&data);
+        assert!(restored[0] > 0.1 && restored[1] > restored[0] && restored[1] < 1.0);
+    }
+}
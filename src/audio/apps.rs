@@ -0,0 +1,521 @@
+//! Loopback capture of an output's apps, leaving some of them out
+//!
+//! A loopback capture records everything an output plays. To keep apps such
+//! as a voice chat off the mirrored outputs, each other app playing on the
+//! output is captured on its own (process loopback) and the captures are
+//! mixed. The output's audio sessions are checked again every
+//! [`RESCAN_INTERVAL`], so apps that start playing later are picked up.
+//!
+//! A process capture includes the process's children, so an app playing
+//! from several processes (a browser and its audio process) is captured once,
+//! from its topmost playing process. An app with an excluded app among its
+//! children isn't captured as a whole; its other children are captured on
+//! their own.
+//!
+//! Windows' own sounds have no process of their own to capture and are left
+//! out as well.
+
+use crate::audio::simd::add_scaled_f32;
+use crate::audio::{AudioFormat, CaptureBackend, LoopbackCapture};
+use crate::error::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+use windows::{
+    core::{Interface, PWSTR},
+    Win32::{
+        Foundation::{CloseHandle, S_OK},
+        Media::Audio::{
+            AudioSessionStateExpired, IAudioClient, IAudioSessionControl2, IAudioSessionManager2,
+            IMMDevice,
+        },
+        System::{
+            Com::CLSCTX_ALL,
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+                TH32CS_SNAPPROCESS,
+            },
+            Threading::{
+                OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        },
+    },
+};
+
+/// Time between checks for apps that started or stopped playing
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Audio an app may have waiting beyond one read, in milliseconds; more is
+/// dropped so its latency stays bounded
+const MAX_BACKLOG_MS: u32 = 50;
+
+/// Longest wait for audio in one read, in milliseconds
+const PERIOD_MS: u32 = 10;
+
+/// Ancestors followed up from a process; guards against cycles through
+/// reused process IDs
+const MAX_TREE_DEPTH: usize = 64;
+
+/// One app's capture and the audio it delivered but wasn't mixed yet
+struct AppStream {
+    process_id: u32,
+    name: String,
+    capture: LoopbackCapture,
+    pending: Vec<u8>,
+}
+
+/// Mix of the apps playing on an output, except the excluded ones
+pub struct AppCapture {
+    device: IMMDevice,
+    format: AudioFormat,
+    /// Excluded executable names, lowercase and without ".exe"
+    exclude: Vec<String>,
+    streams: Vec<AppStream>,
+    /// Processes failing to open, not tried again while they play
+    skipped: HashSet<u32>,
+    /// Processes left out on purpose, logged once while they play
+    left_out: HashSet<u32>,
+    last_scan: Option<Instant>,
+    /// When capturing started and the frames delivered since, which pace
+    /// the mix
+    started: Option<Instant>,
+    frames_delivered: u64,
+}
+
+// SAFETY: like LoopbackCapture, used from MTA threads only
+unsafe impl Send for AppCapture {}
+
+impl AppCapture {
+    /// Capture the apps playing on `device` except those named in
+    /// `exclude_apps` (executable names, ".exe" optional)
+    pub fn new(device: IMMDevice, exclude_apps: &[String]) -> Result<Self> {
+        let format = unsafe {
+            let client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
+            let format_ptr = client.GetMixFormat()?;
+            let format = AudioFormat::from_wave_format(format_ptr);
+            windows::Win32::System::Com::CoTaskMemFree(Some(format_ptr as *const _));
+            format
+        };
+        info!("Capturing apps except: {}", exclude_apps.join(", "));
+        Ok(Self {
+            device,
+            // Every app is captured in float at the output's rate and channels
+            format: AudioFormat {
+                bits_per_sample: 32,
+                block_align: format.channels * 4,
+                encoding: Default::default(),
+                ..format
+            },
+            exclude: exclude_apps.iter().map(|app| app_key(app)).collect(),
+            streams: Vec::new(),
+            skipped: HashSet::new(),
+            left_out: HashSet::new(),
+            last_scan: None,
+            started: None,
+            frames_delivered: 0,
+        })
+    }
+
+    /// Capture apps that started playing and drop those that stopped
+    fn rescan(&mut self) -> Result<()> {
+        self.last_scan = Some(Instant::now());
+        let mut playing = self.playing_processes()?;
+        let parents = process_parents();
+        let excluded: HashSet<u32> = playing
+            .iter()
+            .filter(|(_, name)| is_excluded(name, &self.exclude))
+            .map(|(pid, _)| *pid)
+            .collect();
+        let tree = ProcessTree::new(&parents, &excluded, playing.keys().copied());
+        let roots = tree.roots();
+
+        self.streams.retain(|stream| {
+            let keep = roots.contains(&stream.process_id);
+            if !playing.contains_key(&stream.process_id) {
+                info!("App stopped playing: {}", stream.name);
+            } else if !keep {
+                debug!("Capturing {} as part of another app", stream.name);
+            }
+            keep
+        });
+        self.skipped.retain(|pid| playing.contains_key(pid));
+        self.left_out.retain(|pid| playing.contains_key(pid));
+
+        for (pid, name) in &playing {
+            if tree.is_excluded(*pid) {
+                if self.left_out.insert(*pid) {
+                    debug!("Leaving out app: {} ({})", name, pid);
+                }
+            } else if tree.is_blocked(*pid) && self.left_out.insert(*pid) {
+                info!(
+                    "Leaving out app {} ({}): an excluded app plays from it",
+                    name, pid
+                );
+            }
+        }
+
+        for pid in roots {
+            if self.streams.iter().any(|s| s.process_id == pid) || self.skipped.contains(&pid) {
+                continue;
+            }
+            let name = playing.remove(&pid).unwrap_or_default();
+            let capture = LoopbackCapture::from_process(pid, &self.format).and_then(|mut c| {
+                if self.started.is_some() {
+                    c.start()?;
+                }
+                Ok(c)
+            });
+            match capture {
+                Ok(capture) => {
+                    info!("Capturing app: {} ({})", name, pid);
+                    self.streams.push(AppStream {
+                        process_id: pid,
+                        name,
+                        capture,
+                        pending: Vec::new(),
+                    });
+                }
+                Err(e) => {
+                    warn!("Failed to capture app {} ({}): {}", name, pid, e);
+                    self.skipped.insert(pid);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executable names of the processes with an audio session on the
+    /// output, by process ID
+    fn playing_processes(&self) -> Result<HashMap<u32, String>> {
+        let own_pid = std::process::id();
+        let mut processes = HashMap::new();
+        unsafe {
+            let manager: IAudioSessionManager2 = self.device.Activate(CLSCTX_ALL, None)?;
+            let sessions = manager.GetSessionEnumerator()?;
+            for i in 0..sessions.GetCount()? {
+                let Ok(session) = sessions
+                    .GetSession(i)
+                    .and_then(|s| s.cast::<IAudioSessionControl2>())
+                else {
+                    continue;
+                };
+                if session
+                    .GetState()
+                    .is_ok_and(|s| s == AudioSessionStateExpired)
+                    || session.IsSystemSoundsSession() == S_OK
+                {
+                    continue;
+                }
+                let Ok(pid) = session.GetProcessId() else {
+                    continue;
+                };
+                if pid == 0 || pid == own_pid || processes.contains_key(&pid) {
+                    continue;
+                }
+                processes.insert(pid, process_name(pid).unwrap_or_default());
+            }
+        }
+        Ok(processes)
+    }
+}
+
+impl CaptureBackend for AppCapture {
+    fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.started.is_some() {
+            return Ok(());
+        }
+        self.rescan()?;
+        for stream in &mut self.streams {
+            stream.capture.start()?;
+        }
+        self.started = Some(Instant::now());
+        self.frames_delivered = 0;
+        info!("App capture started");
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if self.started.take().is_none() {
+            return Ok(());
+        }
+        for stream in &mut self.streams {
+            let _ = stream.capture.stop();
+            stream.pending.clear();
+        }
+        info!("App capture stopped");
+        Ok(())
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        let Some(started) = self.started else {
+            return Ok(0);
+        };
+        if self
+            .last_scan
+            .is_none_or(|last| last.elapsed() >= RESCAN_INTERVAL)
+        {
+            if let Err(e) = self.rescan() {
+                warn!("Failed to list the apps playing: {}", e);
+            }
+        }
+
+        // The mix runs on the clock: apps come and go, and some deliver
+        // nothing while silent
+        let rate = self.format.sample_rate as u64;
+        let period_frames = rate * PERIOD_MS as u64 / 1000;
+        let due = |delivered: u64| {
+            ((started.elapsed().as_secs_f64() * rate as f64) as u64).saturating_sub(delivered)
+        };
+        let mut due_frames = due(self.frames_delivered);
+        if due_frames < period_frames && timeout_ms > 0 {
+            let wait = (period_frames - due_frames) * 1000 / rate;
+            thread::sleep(Duration::from_millis(wait.min(timeout_ms as u64)));
+            due_frames = due(self.frames_delivered);
+        }
+        // After a stall, start over from now rather than rush to catch up
+        if due_frames > rate {
+            self.frames_delivered += due_frames - period_frames;
+            due_frames = period_frames;
+        }
+
+        for stream in &mut self.streams {
+            loop {
+                let frames = stream.capture.read_frames(0)?;
+                if frames.is_empty() {
+                    break;
+                }
+                let start = stream.pending.len();
+                stream.pending.resize(start + frames.byte_count(), 0);
+                frames.copy_to(&mut stream.pending[start..]);
+            }
+        }
+
+        let block = self.format.block_align as usize;
+        let bytes = (due_frames as usize * block).min(dest.len() / block * block);
+        if bytes == 0 {
+            return Ok(0);
+        }
+        let dest = &mut dest[..bytes];
+        dest.fill(0);
+        let max_backlog = self.format.buffer_size_for_ms(MAX_BACKLOG_MS);
+        for stream in &mut self.streams {
+            mix_pending(&mut stream.pending, dest, max_backlog);
+        }
+        self.frames_delivered += (bytes / block) as u64;
+        Ok(bytes)
+    }
+}
+
+/// Add `pending` audio to `dest` and remove what was used, first dropping
+/// all but `max_backlog` bytes beyond what `dest` takes
+fn mix_pending(pending: &mut Vec<u8>, dest: &mut [u8], max_backlog: usize) {
+    let excess = pending.len().saturating_sub(dest.len() + max_backlog);
+    // Whole samples only
+    pending.drain(..excess / 4 * 4);
+    let used = pending.len().min(dest.len());
+    add_scaled_f32(&mut dest[..used], &pending[..used], 1.0);
+    pending.drain(..used);
+}
+
+/// Executable name without directory and ".exe", lowercase
+fn app_key(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.rsplit(['\\', '/']).next().unwrap_or_default();
+    name.strip_suffix(".exe").unwrap_or(name).to_string()
+}
+
+/// Whether the executable `name` is one of the `exclude` keys
+fn is_excluded(name: &str, exclude: &[String]) -> bool {
+    let key = app_key(name);
+    exclude.contains(&key)
+}
+
+/// The playing processes and how they descend from each other
+struct ProcessTree<'a> {
+    parents: &'a HashMap<u32, u32>,
+    playing: HashSet<u32>,
+    /// Excluded processes and their playing descendants
+    excluded: HashSet<u32>,
+    /// Playing processes with an excluded process among their descendants
+    blocked: HashSet<u32>,
+}
+
+impl<'a> ProcessTree<'a> {
+    fn new(
+        parents: &'a HashMap<u32, u32>,
+        excluded: &HashSet<u32>,
+        playing: impl Iterator<Item = u32>,
+    ) -> Self {
+        let playing: HashSet<u32> = playing.collect();
+        let excluded: HashSet<u32> = playing
+            .iter()
+            .copied()
+            .filter(|&pid| {
+                excluded.contains(&pid) || ancestors(pid, parents).any(|a| excluded.contains(&a))
+            })
+            .collect();
+        let blocked = excluded
+            .iter()
+            .flat_map(|&pid| ancestors(pid, parents))
+            .filter(|pid| playing.contains(pid) && !excluded.contains(pid))
+            .collect();
+        Self {
+            parents,
+            playing,
+            excluded,
+            blocked,
+        }
+    }
+
+    fn is_excluded(&self, pid: u32) -> bool {
+        self.excluded.contains(&pid)
+    }
+
+    fn is_blocked(&self, pid: u32) -> bool {
+        self.blocked.contains(&pid)
+    }
+
+    /// Whether capturing `pid` with its children leaves out no excluded app
+    fn capturable(&self, pid: u32) -> bool {
+        self.playing.contains(&pid) && !self.is_excluded(pid) && !self.is_blocked(pid)
+    }
+
+    /// Whether a capturable ancestor's capture includes `pid`
+    ///
+    /// Of processes that are each other's ancestors through reused process
+    /// IDs, the lowest one covers the others.
+    fn covered(&self, pid: u32) -> bool {
+        ancestors(pid, self.parents).any(|ancestor| {
+            self.capturable(ancestor)
+                && (ancestor < pid || !ancestors(ancestor, self.parents).any(|a| a == pid))
+        })
+    }
+
+    /// Processes to capture: the capturable ones no ancestor already
+    /// covers, in ascending order
+    fn roots(&self) -> Vec<u32> {
+        let mut roots: Vec<u32> = self
+            .playing
+            .iter()
+            .copied()
+            .filter(|&pid| self.capturable(pid) && !self.covered(pid))
+            .collect();
+        roots.sort_unstable();
+        roots
+    }
+}
+
+/// Parent, grandparent and so on of `pid`
+fn ancestors(pid: u32, parents: &HashMap<u32, u32>) -> impl Iterator<Item = u32> + '_ {
+    std::iter::successors(parents.get(&pid).copied(), |parent| {
+        parents.get(parent).copied()
+    })
+    .take_while(move |&parent| parent != 0 && parent != pid)
+    .take(MAX_TREE_DEPTH)
+}
+
+/// Parent process ID of every running process
+fn process_parents() -> HashMap<u32, u32> {
+    let mut parents = HashMap::new();
+    unsafe {
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+            return parents;
+        };
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut found = Process32FirstW(snapshot, &mut entry);
+        while found.is_ok() {
+            parents.insert(entry.th32ProcessID, entry.th32ParentProcessID);
+            found = Process32NextW(snapshot, &mut entry);
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    parents
+}
+
+/// Executable file name of a process
+fn process_name(process_id: u32) -> Option<String> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+        let mut path = [0u16; 1024];
+        let mut len = path.len() as u32;
+        let result = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(path.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        result.ok()?;
+        let path = String::from_utf16_lossy(&path[..len as usize]);
+        Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_excluded_apps_and_mix() {
+        let exclude = vec![app_key("Discord"), app_key("C:\\Apps\\Teams.EXE")];
+        assert!(is_excluded("Discord.exe", &exclude));
+        assert!(is_excluded("teams.exe", &exclude));
+        assert!(!is_excluded("spotify.exe", &exclude));
+
+        // Two samples fit and one more may wait; the oldest two are dropped
+        let mut pending = samples(&[0.125, 0.25, 0.375, 0.5, 0.625]);
+        let mut dest = samples(&[0.25, 0.25]);
+        mix_pending(&mut pending, &mut dest, 4);
+        assert_eq!(dest, samples(&[0.625, 0.75]));
+        assert_eq!(pending, samples(&[0.625]));
+
+        // Less pending than asked for leaves the rest as it was
+        let mut dest = samples(&[0.0, 0.0]);
+        mix_pending(&mut pending, &mut dest, 4);
+        assert_eq!(dest, samples(&[0.625, 0.0]));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_process_tree_is_captured_once() {
+        // explorer (1) started a browser (10) with an audio process (11), and
+        // a launcher (20) that plays itself and runs a game (21) and a voice
+        // chat (22) with a helper (23)
+        let parents = HashMap::from([(10, 1), (11, 10), (20, 1), (21, 20), (22, 20), (23, 22)]);
+        let excluded = HashSet::from([22]);
+        let playing = [10, 11, 20, 21, 22, 23];
+        let tree = ProcessTree::new(&parents, &excluded, playing.into_iter());
+
+        // The browser's tree is captured from its top; the launcher would
+        // bring the voice chat along, so only the game is captured
+        assert_eq!(tree.roots(), vec![10, 21]);
+        assert!(tree.is_blocked(20));
+        assert!(tree.is_excluded(22) && tree.is_excluded(23));
+
+        // Once the browser's main process goes quiet, its audio process is
+        // captured on its own
+        let tree = ProcessTree::new(&parents, &HashSet::new(), [11, 21].into_iter());
+        assert_eq!(tree.roots(), vec![11, 21]);
+
+        // Of a cycle through reused process IDs, one process is captured
+        let parents = HashMap::from([(5, 6), (6, 7), (7, 5)]);
+        let tree = ProcessTree::new(&parents, &HashSet::new(), [5, 6, 7].into_iter());
+        assert_eq!(tree.roots(), vec![5]);
+    }
+}
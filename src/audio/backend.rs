@@ -0,0 +1,142 @@
+//! Audio backend abstraction
+//!
+//! [`AudioEngine`](crate::audio::AudioEngine) reaches the audio system only
+//! through these traits. [`WasapiBackend`](crate::audio::WasapiBackend) is the
+//! production implementation; [`MockBackend`](crate::audio::MockBackend)
+//! generates synthetic audio so the engine can be exercised without devices.
+
+use crate::audio::{AudioFormat, BufferTuning, DeviceLatency};
+use crate::device::{DeviceEvent, DeviceInfo};
+use crate::error::Result;
+use crate::sync::DevicePosition;
+use crossbeam_channel::Sender;
+use std::time::Duration;
+
+/// Source of captured audio (loopback of an output device, or an input)
+pub trait CaptureBackend: Send {
+    /// Format of the captured audio
+    fn format(&self) -> &AudioFormat;
+
+    /// Start capturing
+    fn start(&mut self) -> Result<()>;
+
+    /// Stop capturing
+    fn stop(&mut self) -> Result<()>;
+
+    /// Wait up to `timeout_ms` for audio and copy it into `dest`
+    ///
+    /// Returns the number of bytes copied, 0 if nothing was captured in time.
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize>;
+
+    /// How long before the last read its audio was captured, zero when the
+    /// source can't tell
+    fn capture_delay(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Output device receiving duplicated audio
+pub trait RenderBackend: Send {
+    /// Device ID
+    fn device_id(&self) -> &str;
+
+    /// Human-readable device name
+    fn device_name(&self) -> &str;
+
+    /// Format the device renders in
+    fn format(&self) -> &AudioFormat;
+
+    /// Device capabilities and the buffer it was opened with
+    fn latency(&self) -> DeviceLatency;
+
+    /// Start rendering
+    fn start(&mut self) -> Result<()>;
+
+    /// Stop rendering
+    fn stop(&mut self) -> Result<()>;
+
+    /// Wait up to `timeout_ms` for buffer space and write frames
+    ///
+    /// Returns the number of frames written
+    fn write_frames(&mut self, data: &[u8], timeout_ms: u32) -> Result<u32>;
+
+    /// Write up to `frames` frames of silence
+    fn write_silence(&mut self, frames: u32) -> Result<()>;
+
+    /// Frames queued in the device buffer and not yet played
+    ///
+    /// 0 while running with audio flowing means the device underran.
+    fn padding_frames(&self) -> Result<u32>;
+
+    /// Frames the device has played and when, for clock synchronization
+    fn device_position(&self) -> Result<DevicePosition>;
+
+    /// Record a write failure
+    fn set_error(&mut self, message: &str);
+}
+
+/// Volume of the captured output, followed by the renderers
+pub trait VolumeSource: Send {
+    /// Effective volume (0.0 when muted)
+    fn effective_volume(&self) -> f32;
+}
+
+/// Factory for everything the engine needs from the audio system
+///
+/// The backend is shared with the engine's worker threads, which open their
+/// own captures and volume sources (e.g. after a default device change).
+pub trait AudioBackend: Send + Sync + 'static {
+    /// Capture type
+    type Capture: CaptureBackend;
+    /// Output renderer type
+    type Renderer: RenderBackend;
+    /// Volume source type
+    type Volume: VolumeSource;
+    /// Device change subscription; events stop when it is dropped
+    type Monitor;
+
+    /// Open a loopback capture of the output device with the given ID, or of
+    /// the current default output if `source_id` is None; an input device's
+    /// ID opens a capture of what it records
+    fn open_capture(&self, source_id: Option<&str>) -> Result<Self::Capture>;
+
+    /// Open a capture of the apps playing on the output device with the
+    /// given ID, or on the current default output if `source_id` is None,
+    /// without the apps named in `exclude_apps`
+    fn open_app_capture(
+        &self,
+        source_id: Option<&str>,
+        exclude_apps: &[String],
+    ) -> Result<Self::Capture>;
+
+    /// Open a renderer for the output device with the given ID
+    fn open_renderer(&self, device_id: &str, tuning: &BufferTuning) -> Result<Self::Renderer>;
+
+    /// Open a volume source for the output device with the given ID, or for
+    /// the current default output if `source_id` is None
+    fn open_volume(&self, source_id: Option<&str>) -> Result<Self::Volume>;
+
+    /// Start sending device change events to `event_tx`
+    fn watch_devices(&self, event_tx: Sender<DeviceEvent>) -> Result<Self::Monitor>;
+
+    /// List all active output devices
+    fn output_devices(&self) -> Result<Vec<DeviceInfo>>;
+
+    /// List active HDMI output devices
+    fn hdmi_devices(&self) -> Result<Vec<DeviceInfo>> {
+        Ok(self
+            .output_devices()?
+            .into_iter()
+            .filter(|d| d.is_hdmi)
+            .collect())
+    }
+
+    /// ID of the current default output device
+    fn default_output_id(&self) -> Option<String>;
+
+    /// List all active input devices (microphones, line-in)
+    fn input_devices(&self) -> Result<Vec<DeviceInfo>>;
+
+    /// ID of the current default input device
+    fn default_input_id(&self) -> Option<String>;
+}
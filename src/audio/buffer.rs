@@ -2,16 +2,26 @@
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-/// Lock-free single-producer single-consumer ring buffer
+/// Keeps a value on a cache line of its own
+///
+/// 128 bytes covers CPUs that prefetch cache lines in pairs.
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+/// Lock-free single-producer multi-consumer ring buffer
 ///
 /// Used to decouple the capture thread from render threads.
 /// Each renderer should have its own read position tracked externally.
+///
+/// The write position changes with every capture packet, so it sits on its
+/// own cache line: otherwise each write would evict the fields readers only
+/// look at from every render thread's cache.
 pub struct RingBuffer {
     buffer: Box<[u8]>,
     capacity: usize,
-    write_pos: AtomicUsize,
     /// Mask for fast modulo operation (only works when capacity is power of 2)
     mask: usize,
+    write_pos: CachePadded<AtomicUsize>,
 }
 
 impl RingBuffer {
@@ -25,8 +35,8 @@ impl RingBuffer {
         Self {
             buffer: vec![0u8; capacity].into_boxed_slice(),
             capacity,
-            write_pos: AtomicUsize::new(0),
             mask,
+            write_pos: CachePadded(AtomicUsize::new(0)),
         }
     }
 
@@ -37,7 +47,7 @@ impl RingBuffer {
 
     /// Get the current write position
     pub fn write_position(&self) -> usize {
-        self.write_pos.load(Ordering::Acquire)
+        self.write_pos.0.load(Ordering::Acquire)
     }
 
     /// Write data to the buffer (single producer)
@@ -49,7 +59,7 @@ impl RingBuffer {
             return 0;
         }
 
-        let write_pos = self.write_pos.load(Ordering::Relaxed);
+        let write_pos = self.write_pos.0.load(Ordering::Relaxed);
         let start_pos = write_pos & self.mask;
 
         // Calculate contiguous space before wrap-around
@@ -76,7 +86,7 @@ impl RingBuffer {
 
         // Update write position
         let new_pos = write_pos.wrapping_add(data.len());
-        self.write_pos.store(new_pos, Ordering::Release);
+        self.write_pos.0.store(new_pos, Ordering::Release);
 
         data.len()
     }
@@ -86,11 +96,15 @@ impl RingBuffer {
     /// Returns the number of bytes read and updates the read position.
     /// The reader is responsible for tracking their own read position.
     pub fn read(&self, buf: &mut [u8], read_pos: &mut usize) -> usize {
+        self.read_until(buf, read_pos, self.write_position())
+    }
+
+    /// Read data written before `write_pos`, a position loaded earlier
+    fn read_until(&self, buf: &mut [u8], read_pos: &mut usize, write_pos: usize) -> usize {
         if buf.is_empty() {
             return 0;
         }
 
-        let write_pos = self.write_pos.load(Ordering::Acquire);
         let available = write_pos.wrapping_sub(*read_pos);
 
         // Don't read more than available or more than buffer size
@@ -128,39 +142,47 @@ impl RingBuffer {
 
     /// Calculate available bytes to read from a given read position
     pub fn available(&self, read_pos: usize) -> usize {
-        let write_pos = self.write_pos.load(Ordering::Acquire);
+        let write_pos = self.write_position();
         let available = write_pos.wrapping_sub(read_pos);
         available.min(self.capacity)
     }
 
     /// Check if reader is lagging behind (data was overwritten)
     pub fn is_lagging(&self, read_pos: usize) -> bool {
-        let write_pos = self.write_pos.load(Ordering::Acquire);
+        let write_pos = self.write_position();
         write_pos.wrapping_sub(read_pos) > self.capacity
     }
 
     /// Reset reader position to current write position (catch up)
     pub fn catch_up(&self, read_pos: &mut usize) {
-        *read_pos = self.write_pos.load(Ordering::Acquire);
+        *read_pos = self.write_position();
     }
 }
 
 /// Per-renderer read state for the shared ring buffer
 pub struct ReaderState {
     read_pos: usize,
+    /// Write position last loaded; loaded again only when it holds less
+    /// than a read asks for, so readers rarely touch the shared cache line
+    cached_write_pos: usize,
 }
 
 impl ReaderState {
     /// Create a new reader state starting from the current write position
     pub fn new(buffer: &RingBuffer) -> Self {
+        let write_pos = buffer.write_position();
         Self {
-            read_pos: buffer.write_position(),
+            read_pos: write_pos,
+            cached_write_pos: write_pos,
         }
     }
 
     /// Read data from the shared buffer
     pub fn read(&mut self, buffer: &RingBuffer, buf: &mut [u8]) -> usize {
-        buffer.read(buf, &mut self.read_pos)
+        if self.cached_write_pos.wrapping_sub(self.read_pos) < buf.len() {
+            self.cached_write_pos = buffer.write_position();
+        }
+        buffer.read_until(buf, &mut self.read_pos, self.cached_write_pos)
     }
 
     /// Get available bytes to read
@@ -175,11 +197,11 @@ impl ReaderState {
 
     /// Catch up to current write position (skip data)
     pub fn catch_up(&mut self, buffer: &RingBuffer) {
-        buffer.catch_up(&mut self.read_pos)
+        buffer.catch_up(&mut self.read_pos);
+        self.cached_write_pos = self.read_pos;
     }
 
     /// Get current read position
-    #[allow(dead_code)]
     pub fn position(&self) -> usize {
         self.read_pos
     }
@@ -202,6 +224,16 @@ mod tests {
 
         assert_eq!(read, 5);
         assert_eq!(read_buf, data);
+
+        // Audio written after the last read is found once the cached
+        // position runs short
+        buffer.write(&[6, 7, 8]);
+        let mut read_buf = [0u8; 2];
+        assert_eq!(reader.read(&buffer, &mut read_buf), 2);
+        buffer.write(&[9]);
+        let mut read_buf = [0u8; 2];
+        assert_eq!(reader.read(&buffer, &mut read_buf), 2);
+        assert_eq!(read_buf, [8, 9]);
     }
 
     #[test]
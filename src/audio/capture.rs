@@ -1,25 +1,46 @@
 //! WASAPI loopback capture from system audio output
-
-use crate::audio::AudioFormat;
-use crate::error::Result;
+//!
+//! Input devices (microphones, line-in) are captured directly instead.
+
+use crate::audio::{AudioFormat, SampleEncoding, WAVE_FORMAT_IEEE_FLOAT};
+use crate::error::{Result, WemuxError};
+use crossbeam_channel::{bounded, Sender};
+use std::cell::Cell;
+use std::mem::ManuallyDrop;
 use std::ptr;
+use std::time::Duration;
 use tracing::{debug, info, trace};
 use windows::{
-    core::PCWSTR,
+    core::{implement, IUnknown, Interface, HRESULT, PCWSTR, PROPVARIANT},
     Win32::{
         Foundation::{HANDLE, WAIT_OBJECT_0},
         Media::Audio::{
-            eConsole, eRender, IAudioCaptureClient, IAudioClient, IMMDevice, IMMDeviceEnumerator,
-            MMDeviceEnumerator, AUDCLNT_BUFFERFLAGS_SILENT, AUDCLNT_SHAREMODE_SHARED,
-            AUDCLNT_STREAMFLAGS_EVENTCALLBACK, AUDCLNT_STREAMFLAGS_LOOPBACK,
+            eCapture, eConsole, eRender, ActivateAudioInterfaceAsync,
+            IActivateAudioInterfaceAsyncOperation, IActivateAudioInterfaceCompletionHandler,
+            IActivateAudioInterfaceCompletionHandler_Impl, IAudioCaptureClient, IAudioClient,
+            IMMDevice, IMMDeviceEnumerator, IMMEndpoint, MMDeviceEnumerator,
+            AUDCLNT_BUFFERFLAGS_SILENT, AUDCLNT_SHAREMODE_SHARED,
+            AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM, AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+            AUDCLNT_STREAMFLAGS_LOOPBACK, AUDIOCLIENT_ACTIVATION_PARAMS,
+            AUDIOCLIENT_ACTIVATION_PARAMS_0, AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+            AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS, PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+            VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK, WAVEFORMATEX,
         },
         System::{
-            Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED},
+            Com::{
+                CoCreateInstance, CoInitializeEx, IAgileObject, IAgileObject_Impl, CLSCTX_ALL,
+                COINIT_MULTITHREADED,
+            },
+            Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
             Threading::{CreateEventW, WaitForSingleObject},
+            Variant::VT_BLOB,
         },
     },
 };
 
+/// Longest wait for a process loopback client to be activated
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// WASAPI loopback capture for capturing system audio output
 pub struct LoopbackCapture {
     audio_client: IAudioClient,
@@ -28,6 +49,8 @@ pub struct LoopbackCapture {
     event: HANDLE,
     buffer_frames: u32,
     started: bool,
+    /// How long before the last read its first frame was captured
+    delay: Cell<Duration>,
 }
 
 // SAFETY: LoopbackCapture is Send because WASAPI uses MTA (Multi-Threaded Apartment)
@@ -49,7 +72,8 @@ impl LoopbackCapture {
         }
     }
 
-    /// Create a loopback capture from a specific device
+    /// Create a capture from a specific device: loopback for an output,
+    /// direct for an input such as a microphone
     pub fn from_device(device: &IMMDevice) -> Result<Self> {
         unsafe {
             // Get device ID for logging
@@ -59,21 +83,22 @@ impl LoopbackCapture {
                 windows::Win32::System::Com::CoTaskMemFree(Some(id_ptr.0 as *const _));
                 id
             };
-            debug!("Creating loopback capture for device: {}", device_id);
+            let is_input = device
+                .cast::<IMMEndpoint>()
+                .and_then(|endpoint| endpoint.GetDataFlow())
+                .is_ok_and(|flow| flow == eCapture);
+            debug!(
+                "Creating {} capture for device: {}",
+                if is_input { "input" } else { "loopback" },
+                device_id
+            );
 
             // Activate audio client
             let audio_client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
 
             // Get mix format
             let format_ptr = audio_client.GetMixFormat()?;
-            let format_ref = &*format_ptr;
-
-            let format = AudioFormat {
-                sample_rate: format_ref.nSamplesPerSec,
-                channels: format_ref.nChannels,
-                bits_per_sample: format_ref.wBitsPerSample,
-                block_align: format_ref.nBlockAlign,
-            };
+            let format = AudioFormat::from_wave_format(format_ptr);
 
             info!("Capture format: {}", format);
 
@@ -88,9 +113,14 @@ impl LoopbackCapture {
                     350_000i64 // 35ms fallback
                 });
 
+            let flags = if is_input {
+                AUDCLNT_STREAMFLAGS_EVENTCALLBACK
+            } else {
+                AUDCLNT_STREAMFLAGS_LOOPBACK | AUDCLNT_STREAMFLAGS_EVENTCALLBACK
+            };
             audio_client.Initialize(
                 AUDCLNT_SHAREMODE_SHARED,
-                AUDCLNT_STREAMFLAGS_LOOPBACK | AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+                flags,
                 buffer_duration,
                 0,
                 format_ptr,
@@ -117,6 +147,66 @@ impl LoopbackCapture {
                 event,
                 buffer_frames,
                 started: false,
+                delay: Cell::new(Duration::ZERO),
+            })
+        }
+    }
+
+    /// Create a loopback capture of one process and its children, in
+    /// 32-bit float at the rate and channels of `format`
+    ///
+    /// Needs Windows 10 version 2004 or later.
+    pub(crate) fn from_process(process_id: u32, format: &AudioFormat) -> Result<Self> {
+        unsafe {
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            debug!("Creating loopback capture for process: {}", process_id);
+
+            let audio_client = activate_process_loopback(process_id)?;
+
+            // Process loopback has no mix format; the stream is converted to ours
+            let format = AudioFormat {
+                sample_rate: format.sample_rate,
+                channels: format.channels,
+                bits_per_sample: 32,
+                block_align: format.channels * 4,
+                encoding: SampleEncoding::Float,
+                channel_mask: format.channel_mask,
+            };
+            let wave_format = WAVEFORMATEX {
+                wFormatTag: WAVE_FORMAT_IEEE_FLOAT,
+                nChannels: format.channels,
+                nSamplesPerSec: format.sample_rate,
+                nAvgBytesPerSec: format.bytes_per_second(),
+                nBlockAlign: format.block_align,
+                wBitsPerSample: 32,
+                cbSize: 0,
+            };
+
+            let event = CreateEventW(None, false, false, None)?;
+            audio_client.Initialize(
+                AUDCLNT_SHAREMODE_SHARED,
+                AUDCLNT_STREAMFLAGS_LOOPBACK
+                    | AUDCLNT_STREAMFLAGS_EVENTCALLBACK
+                    | AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM,
+                350_000,
+                0,
+                &wave_format,
+                None,
+            )?;
+            audio_client.SetEventHandle(event)?;
+
+            // Process loopback reports no buffer size
+            let buffer_frames = audio_client.GetBufferSize().unwrap_or(0);
+            let capture_client: IAudioCaptureClient = audio_client.GetService()?;
+
+            Ok(Self {
+                audio_client,
+                capture_client,
+                format,
+                event,
+                buffer_frames,
+                started: false,
+                delay: Cell::new(Duration::ZERO),
             })
         }
     }
@@ -190,6 +280,11 @@ impl LoopbackCapture {
                 return Ok(CapturedFrames::empty());
             }
 
+            if let Some(now) = qpc_now() {
+                let captured = Duration::from_nanos(qpc_position.saturating_mul(100));
+                self.delay.set(now.saturating_sub(captured));
+            }
+
             let is_silent = (flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32) != 0;
             let byte_count = num_frames as usize * self.format.block_align as usize;
 
@@ -218,6 +313,92 @@ impl LoopbackCapture {
     pub fn is_running(&self) -> bool {
         self.started
     }
+
+    /// How long before the last read its audio was captured
+    pub fn delay(&self) -> Duration {
+        self.delay.get()
+    }
+}
+
+/// Current performance counter time, in the units WASAPI timestamps packets in
+fn qpc_now() -> Option<Duration> {
+    let (mut counter, mut frequency) = (0i64, 0i64);
+    unsafe {
+        QueryPerformanceCounter(&mut counter).ok()?;
+        QueryPerformanceFrequency(&mut frequency).ok()?;
+    }
+    if frequency <= 0 {
+        return None;
+    }
+    let nanos = counter as u128 * 1_000_000_000 / frequency as u128;
+    Some(Duration::from_nanos(nanos as u64))
+}
+
+/// Signals that an asynchronous audio interface activation completed
+#[implement(IActivateAudioInterfaceCompletionHandler, IAgileObject)]
+struct ActivationHandler {
+    done: Sender<()>,
+}
+
+impl IActivateAudioInterfaceCompletionHandler_Impl for ActivationHandler_Impl {
+    fn ActivateCompleted(
+        &self,
+        _operation: Option<&IActivateAudioInterfaceAsyncOperation>,
+    ) -> windows::core::Result<()> {
+        let _ = self.done.try_send(());
+        Ok(())
+    }
+}
+
+impl IAgileObject_Impl for ActivationHandler_Impl {}
+
+/// Activate an audio client capturing what `process_id` and its children play
+unsafe fn activate_process_loopback(process_id: u32) -> Result<IAudioClient> {
+    let params = AUDIOCLIENT_ACTIVATION_PARAMS {
+        ActivationType: AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+        Anonymous: AUDIOCLIENT_ACTIVATION_PARAMS_0 {
+            ProcessLoopbackParams: AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS {
+                TargetProcessId: process_id,
+                ProcessLoopbackMode: PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+            },
+        },
+    };
+    // The blob points at `params`; it must not be freed as a PROPVARIANT
+    let mut raw: windows::core::imp::PROPVARIANT = std::mem::zeroed();
+    raw.Anonymous.Anonymous.vt = VT_BLOB.0;
+    raw.Anonymous.Anonymous.Anonymous.blob = windows::core::imp::BLOB {
+        cbSize: std::mem::size_of::<AUDIOCLIENT_ACTIVATION_PARAMS>() as u32,
+        pBlobData: &params as *const _ as *mut u8,
+    };
+    let activation = ManuallyDrop::new(PROPVARIANT::from_raw(raw));
+
+    let (done_tx, done_rx) = bounded(1);
+    let handler: IActivateAudioInterfaceCompletionHandler =
+        ActivationHandler { done: done_tx }.into();
+    let operation = ActivateAudioInterfaceAsync(
+        VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK,
+        &IAudioClient::IID,
+        Some(&*activation as *const PROPVARIANT),
+        &handler,
+    )?;
+    if done_rx.recv_timeout(ACTIVATION_TIMEOUT).is_err() {
+        return Err(WemuxError::DeviceError {
+            device_id: format!("process {}", process_id),
+            message: "loopback activation timed out".to_string(),
+        });
+    }
+
+    let mut result = HRESULT(0);
+    let mut interface: Option<IUnknown> = None;
+    operation.GetActivateResult(&mut result, &mut interface)?;
+    result.ok()?;
+    interface
+        .ok_or_else(|| WemuxError::DeviceError {
+            device_id: format!("process {}", process_id),
+            message: "loopback activation returned no client".to_string(),
+        })?
+        .cast::<IAudioClient>()
+        .map_err(Into::into)
 }
 
 impl Drop for LoopbackCapture {
@@ -0,0 +1,224 @@
+//! Speaker layouts and channel mapping between them
+//!
+//! A WAVEFORMATEXTENSIBLE names the speaker each channel is for in its
+//! channel mask. When an output's layout differs from the captured audio's -
+//! 7.1 captured, a stereo TV as output - [`ChannelMap`] routes each captured
+//! channel to the output channel for the same speaker, folding speakers the
+//! output lacks into the nearest ones it has.
+
+use crate::audio::{AudioFormat, SampleEncoding};
+
+/// `SPEAKER_*` bits from ksmedia.h
+const FRONT_LEFT: u32 = 0x1;
+const FRONT_RIGHT: u32 = 0x2;
+const FRONT_CENTER: u32 = 0x4;
+const LOW_FREQUENCY: u32 = 0x8;
+const BACK_LEFT: u32 = 0x10;
+const BACK_RIGHT: u32 = 0x20;
+const FRONT_LEFT_OF_CENTER: u32 = 0x40;
+const FRONT_RIGHT_OF_CENTER: u32 = 0x80;
+const BACK_CENTER: u32 = 0x100;
+const SIDE_LEFT: u32 = 0x200;
+const SIDE_RIGHT: u32 = 0x400;
+
+/// Gain of a speaker folded into another one (-3 dB)
+const FOLD_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Speakers a stream with `channels` channels and no channel mask is for,
+/// following the `KSAUDIO_SPEAKER_*` layouts
+pub fn default_channel_mask(channels: u16) -> u32 {
+    match channels {
+        1 => FRONT_CENTER,
+        2 => FRONT_LEFT | FRONT_RIGHT,
+        3 => FRONT_LEFT | FRONT_RIGHT | FRONT_CENTER,
+        4 => FRONT_LEFT | FRONT_RIGHT | BACK_LEFT | BACK_RIGHT,
+        5 => FRONT_LEFT | FRONT_RIGHT | FRONT_CENTER | BACK_LEFT | BACK_RIGHT,
+        6 => FRONT_LEFT | FRONT_RIGHT | FRONT_CENTER | LOW_FREQUENCY | BACK_LEFT | BACK_RIGHT,
+        8 => {
+            FRONT_LEFT
+                | FRONT_RIGHT
+                | FRONT_CENTER
+                | LOW_FREQUENCY
+                | BACK_LEFT
+                | BACK_RIGHT
+                | SIDE_LEFT
+                | SIDE_RIGHT
+        }
+        n => (1u32 << n.min(18)) - 1,
+    }
+}
+
+/// Common name of a speaker layout, e.g. "5.1"
+pub fn layout_name(channel_mask: u32) -> Option<&'static str> {
+    let name = match channel_mask {
+        FRONT_CENTER => "mono",
+        0x3 => "stereo",
+        0xB => "2.1",
+        0x33 => "quad",
+        0x3F => "5.1",
+        0x60F => "5.1 side",
+        0x63F => "7.1",
+        0xFF => "7.1 wide",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Where a speaker the output lacks is played instead, in order of preference
+fn fold_targets(speaker: u32) -> &'static [(&'static [u32], f32)] {
+    match speaker {
+        FRONT_LEFT => &[(&[FRONT_CENTER], FOLD_GAIN)],
+        FRONT_RIGHT => &[(&[FRONT_CENTER], FOLD_GAIN)],
+        FRONT_CENTER => &[(&[FRONT_LEFT, FRONT_RIGHT], FOLD_GAIN)],
+        FRONT_LEFT_OF_CENTER => &[(&[FRONT_LEFT], 1.0), (&[FRONT_CENTER], FOLD_GAIN)],
+        FRONT_RIGHT_OF_CENTER => &[(&[FRONT_RIGHT], 1.0), (&[FRONT_CENTER], FOLD_GAIN)],
+        BACK_LEFT => &[
+            (&[SIDE_LEFT], 1.0),
+            (&[FRONT_LEFT], FOLD_GAIN),
+            (&[FRONT_CENTER], FOLD_GAIN),
+        ],
+        BACK_RIGHT => &[
+            (&[SIDE_RIGHT], 1.0),
+            (&[FRONT_RIGHT], FOLD_GAIN),
+            (&[FRONT_CENTER], FOLD_GAIN),
+        ],
+        SIDE_LEFT => &[
+            (&[BACK_LEFT], 1.0),
+            (&[FRONT_LEFT], FOLD_GAIN),
+            (&[FRONT_CENTER], FOLD_GAIN),
+        ],
+        SIDE_RIGHT => &[
+            (&[BACK_RIGHT], 1.0),
+            (&[FRONT_RIGHT], FOLD_GAIN),
+            (&[FRONT_CENTER], FOLD_GAIN),
+        ],
+        BACK_CENTER => &[
+            (&[BACK_LEFT, BACK_RIGHT], FOLD_GAIN),
+            (&[SIDE_LEFT, SIDE_RIGHT], FOLD_GAIN),
+            (&[FRONT_LEFT, FRONT_RIGHT], FOLD_GAIN),
+            (&[FRONT_CENTER], FOLD_GAIN),
+        ],
+        // The subwoofer and height speakers are left out of a fold-down
+        _ => &[],
+    }
+}
+
+/// Speaker of each channel, in channel order; channels beyond the mask have none
+fn speakers(channels: u16, channel_mask: u32) -> Vec<Option<u32>> {
+    let mut bits = (0..32)
+        .map(|bit| 1u32 << bit)
+        .filter(|b| channel_mask & b != 0);
+    (0..channels).map(|_| bits.next()).collect()
+}
+
+/// Routes the channels of one 32-bit float layout to another
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChannelMap {
+    source_channels: usize,
+    /// Source channels and gains mixed into each output channel
+    routes: Vec<Vec<(usize, f32)>>,
+}
+
+impl ChannelMap {
+    /// Map from `source` to `dest`; None if both have the same layout or
+    /// either isn't 32-bit float, which can't be mapped
+    pub(crate) fn between(source: &AudioFormat, dest: &AudioFormat) -> Option<Self> {
+        let float =
+            |f: &AudioFormat| f.encoding == SampleEncoding::Float && f.bits_per_sample == 32;
+        if !float(source) || !float(dest) {
+            return None;
+        }
+        if source.channels == dest.channels && source.channel_mask == dest.channel_mask {
+            return None;
+        }
+
+        let dest_speakers = speakers(dest.channels, dest.channel_mask);
+        let dest_channel = |speaker: u32| dest_speakers.iter().position(|s| *s == Some(speaker));
+        let mut routes = vec![Vec::new(); dest.channels as usize];
+        for (index, speaker) in speakers(source.channels, source.channel_mask)
+            .into_iter()
+            .enumerate()
+        {
+            let Some(speaker) = speaker else { continue };
+            if let Some(channel) = dest_channel(speaker) {
+                routes[channel].push((index, 1.0));
+                continue;
+            }
+            let fold = fold_targets(speaker).iter().find_map(|(targets, gain)| {
+                let channels: Option<Vec<usize>> =
+                    targets.iter().map(|t| dest_channel(*t)).collect();
+                channels.map(|channels| (channels, *gain))
+            });
+            if let Some((channels, gain)) = fold {
+                for channel in channels {
+                    routes[channel].push((index, gain));
+                }
+            }
+        }
+
+        Some(Self {
+            source_channels: source.channels as usize,
+            routes,
+        })
+    }
+
+    /// Map the frames in `data` into `out`, replacing its contents
+    pub(crate) fn map(&self, data: &[u8], out: &mut Vec<u8>) {
+        out.clear();
+        for frame in data.chunks_exact(self.source_channels * 4) {
+            let sample = |channel: usize| {
+                let bytes = &frame[channel * 4..channel * 4 + 4];
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            };
+            for route in &self.routes {
+                let value: f32 = route
+                    .iter()
+                    .map(|(channel, gain)| sample(*channel) * gain)
+                    .sum();
+                out.extend_from_slice(&value.clamp(-1.0, 1.0).to_le_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(channels: u16, channel_mask: u32) -> AudioFormat {
+        AudioFormat {
+            sample_rate: 48000,
+            channels,
+            bits_per_sample: 32,
+            block_align: channels * 4,
+            encoding: SampleEncoding::Float,
+            channel_mask,
+        }
+    }
+
+    #[test]
+    fn test_surround_folds_down_to_stereo() {
+        let surround = format(8, default_channel_mask(8));
+        let stereo = format(2, default_channel_mask(2));
+        assert_eq!(layout_name(surround.channel_mask), Some("7.1"));
+        assert!(ChannelMap::between(&stereo, &stereo).is_none());
+
+        // FL FR FC LFE BL BR SL SR
+        let frame: Vec<u8> = [0.1f32, 0.2, 0.4, 1.0, 0.0, 0.0, 0.3, 0.0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let map = ChannelMap::between(&surround, &stereo).unwrap();
+        let mut out = Vec::new();
+        map.map(&frame, &mut out);
+
+        let samples: Vec<f32> = out
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        // Center and surround at -3 dB, the subwoofer left out
+        assert_eq!(samples.len(), 2);
+        assert!((samples[0] - (0.1 + (0.4 + 0.3) * FOLD_GAIN)).abs() < 1e-6);
+        assert!((samples[1] - (0.2 + 0.4 * FOLD_GAIN)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,69 @@
+//! Fixed delay for outputs that play sooner than others
+//!
+//! TVs add their own processing latency, and it differs from model to model.
+//! Delaying the outputs that are early by the difference lines them all up
+//! with the slowest one.
+
+/// Delays a byte stream by a fixed amount
+pub(crate) struct DelayLine {
+    /// Audio not played yet, starting as silence
+    line: Vec<u8>,
+    /// Oldest byte in `line`
+    pos: usize,
+}
+
+impl DelayLine {
+    /// Delay by `bytes`, a whole number of frames (0 passes audio unchanged)
+    pub(crate) fn new(bytes: usize) -> Self {
+        Self {
+            line: vec![0; bytes],
+            pos: 0,
+        }
+    }
+
+    /// Replace `data` with the audio written `bytes` earlier
+    pub(crate) fn process(&mut self, data: &mut [u8]) {
+        if self.line.is_empty() {
+            return;
+        }
+        let mut done = 0;
+        while done < data.len() {
+            let n = (self.line.len() - self.pos).min(data.len() - done);
+            data[done..done + n].swap_with_slice(&mut self.line[self.pos..self.pos + n]);
+            self.pos = (self.pos + n) % self.line.len();
+            done += n;
+        }
+    }
+
+    /// Forget the audio in the line, so stale audio isn't played after a pause
+    pub(crate) fn clear(&mut self) {
+        self.line.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_line() {
+        let mut delay = DelayLine::new(3);
+        let mut data = [1, 2, 3, 4, 5];
+        delay.process(&mut data);
+        assert_eq!(data, [0, 0, 0, 1, 2]);
+
+        let mut data = [6, 7];
+        delay.process(&mut data);
+        assert_eq!(data, [3, 4]);
+
+        delay.clear();
+        let mut data = [8, 9, 10, 11];
+        delay.process(&mut data);
+        assert_eq!(data, [0, 0, 0, 8]);
+
+        let mut unchanged = DelayLine::new(0);
+        let mut data = [1, 2];
+        unchanged.process(&mut data);
+        assert_eq!(data, [1, 2]);
+    }
+}
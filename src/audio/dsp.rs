@@ -0,0 +1,439 @@
+//! Per-output processing chain
+//!
+//! After the volume and fades, each output runs what it plays through a
+//! [`DspChain`]: its equalizer, the stages configured for it (`[dsp]` in the
+//! service config, `dsp` in a device's tray settings), its delay, the
+//! mapping to its speakers and resampling to its rate. Every step is a [`DspStage`]; a new kind of
+//! processing implements the trait and gets a [`DspStageConfig`] variant.
+//!
+//! Stages work on interleaved 32-bit float samples in the captured format.
+//! A bitstream output only gets its delay, which passes bytes unchanged.
+
+use crate::audio::channels::ChannelMap;
+use crate::audio::delay::DelayLine;
+use crate::audio::eq::{EqSettings, Equalizer};
+use crate::audio::resample::Resampler;
+use crate::audio::{simd, AudioFormat};
+use crate::error::{Result, WemuxError};
+use crossbeam_channel::Receiver;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Default ceiling of a limiter in dBFS
+const LIMITER_THRESHOLD_DB: f32 = -1.0;
+
+/// Default time a limiter takes to let go after a peak
+const LIMITER_RELEASE_MS: u32 = 100;
+
+/// One step of an output's processing
+pub trait DspStage: Send {
+    /// Short name for logs, e.g. "limiter"
+    fn name(&self) -> &'static str;
+
+    /// Process `data` in place; a stage that changes the number of channels
+    /// replaces its contents
+    fn process(&mut self, data: &mut Vec<u8>);
+
+    /// Forget the audio processed so far, e.g. filter state after a pause
+    fn reset(&mut self) {}
+}
+
+/// A configured stage, as written in the service config or tray settings
+///
+/// ```toml
+/// [dsp]
+/// patio = [
+///     { type = "gain", db = -6.0 },
+///     { type = "limiter", threshold_db = -3.0 },
+/// ]
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DspStageConfig {
+    /// Fixed gain in dB
+    Gain { db: f32 },
+    /// 10-band equalizer, gains from 31Hz to 16kHz in dB
+    Eq { gains_db: [f32; 10] },
+    /// Fixed delay in milliseconds
+    Delay { ms: u32 },
+    /// Peak limiter keeping the level below `threshold_db` dBFS
+    Limiter {
+        #[serde(default = "limiter_threshold_db")]
+        threshold_db: f32,
+        #[serde(default = "limiter_release_ms")]
+        release_ms: u32,
+    },
+    /// Source channel played on each channel, e.g. `[1, 0]` swaps left and right
+    Channels { map: Vec<usize> },
+}
+
+fn limiter_threshold_db() -> f32 {
+    LIMITER_THRESHOLD_DB
+}
+
+fn limiter_release_ms() -> u32 {
+    LIMITER_RELEASE_MS
+}
+
+impl DspStageConfig {
+    /// Build the stage for audio in `format`
+    pub fn build(&self, format: &AudioFormat) -> Result<Box<dyn DspStage>> {
+        let stage: Box<dyn DspStage> = match self {
+            Self::Gain { db } => Box::new(Gain(10f32.powf(db / 20.0))),
+            Self::Eq { gains_db } => {
+                let settings = EqSettings {
+                    gains_db: *gains_db,
+                };
+                match Equalizer::new(&settings, format.sample_rate, format.channels) {
+                    Some(equalizer) => Box::new(equalizer),
+                    // A flat equalizer leaves the audio as it is
+                    None => Box::new(Gain(1.0)),
+                }
+            }
+            Self::Delay { ms } => {
+                let frames = (format.sample_rate as u64 * *ms as u64 / 1000) as u32;
+                Box::new(DelayLine::new(format.frames_to_bytes(frames)))
+            }
+            Self::Limiter {
+                threshold_db,
+                release_ms,
+            } => Box::new(Limiter::new(*threshold_db, *release_ms, format)),
+            Self::Channels { map } => {
+                let channels = format.channels as usize;
+                if map.len() != channels || map.iter().any(|&c| c >= channels) {
+                    return Err(WemuxError::InvalidConfig(format!(
+                        "Channel map {:?} doesn't fit {} channels",
+                        map, channels
+                    )));
+                }
+                Box::new(ChannelRoute {
+                    map: map.clone(),
+                    out: Vec::new(),
+                })
+            }
+        };
+        Ok(stage)
+    }
+}
+
+impl fmt::Display for DspStageConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gain { db } => write!(f, "gain {:+.1}dB", db),
+            Self::Eq { .. } => write!(f, "eq"),
+            Self::Delay { ms } => write!(f, "delay {}ms", ms),
+            Self::Limiter { threshold_db, .. } => write!(f, "limiter {:.1}dB", threshold_db),
+            Self::Channels { map } => write!(f, "channels {:?}", map),
+        }
+    }
+}
+
+/// Stages an output runs in order
+#[derive(Default)]
+pub struct DspChain {
+    stages: Vec<Box<dyn DspStage>>,
+    /// Audio being processed; stages may change its length
+    work: Vec<u8>,
+}
+
+impl DspChain {
+    /// Append a stage
+    pub fn push(&mut self, stage: Box<dyn DspStage>) {
+        self.stages.push(stage);
+    }
+
+    /// Run `data` through every stage, returning the result
+    pub fn process(&mut self, data: &[u8]) -> &[u8] {
+        self.work.clear();
+        self.work.extend_from_slice(data);
+        for stage in &mut self.stages {
+            stage.process(&mut self.work);
+        }
+        &self.work
+    }
+
+    /// Forget the audio processed so far in every stage
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    /// Names of the stages in order, for logs
+    pub fn names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+}
+
+/// Fixed linear gain
+struct Gain(f32);
+
+impl DspStage for Gain {
+    fn name(&self) -> &'static str {
+        "gain"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        if self.0 != 1.0 {
+            simd::scale_f32(data, self.0);
+        }
+    }
+}
+
+impl DspStage for Equalizer {
+    fn name(&self) -> &'static str {
+        "eq"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        self.process_f32(data);
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// An output's equalizer, rebuilt when the tray or IPC changes its settings
+pub(crate) struct LiveEqualizer {
+    settings_rx: Receiver<EqSettings>,
+    settings: EqSettings,
+    equalizer: Option<Equalizer>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl LiveEqualizer {
+    pub(crate) fn new(settings_rx: Receiver<EqSettings>, format: &AudioFormat) -> Self {
+        Self {
+            settings_rx,
+            settings: EqSettings::default(),
+            equalizer: None,
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+        }
+    }
+}
+
+impl DspStage for LiveEqualizer {
+    fn name(&self) -> &'static str {
+        "eq"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        // Rebuilding the filters resets their state, so only do it on a change
+        if let Some(settings) = self.settings_rx.try_iter().last() {
+            if settings != self.settings {
+                self.settings = settings;
+                self.equalizer = Equalizer::new(&settings, self.sample_rate, self.channels);
+            }
+        }
+        if let Some(equalizer) = &mut self.equalizer {
+            equalizer.process_f32(data);
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Some(equalizer) = &mut self.equalizer {
+            equalizer.clear();
+        }
+    }
+}
+
+impl DspStage for DelayLine {
+    fn name(&self) -> &'static str {
+        "delay"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        DelayLine::process(self, data);
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// Mapping from the captured speaker layout to the output's
+pub(crate) struct SpeakerMap {
+    map: ChannelMap,
+    out: Vec<u8>,
+}
+
+impl SpeakerMap {
+    pub(crate) fn new(map: ChannelMap) -> Self {
+        Self {
+            map,
+            out: Vec::new(),
+        }
+    }
+}
+
+impl DspStage for SpeakerMap {
+    fn name(&self) -> &'static str {
+        "speakers"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        self.map.map(data, &mut self.out);
+        std::mem::swap(data, &mut self.out);
+    }
+}
+
+/// Resampling from the engine's rate to the rate the output mixes at
+pub(crate) struct OutputResampler {
+    resampler: Resampler,
+    out: Vec<u8>,
+}
+
+impl OutputResampler {
+    pub(crate) fn new(channels: u16, from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            resampler: Resampler::new(channels, from_rate, to_rate),
+            out: Vec::new(),
+        }
+    }
+}
+
+impl DspStage for OutputResampler {
+    fn name(&self) -> &'static str {
+        "resample"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        self.resampler.process(data, &mut self.out);
+        std::mem::swap(data, &mut self.out);
+    }
+}
+
+/// User-chosen source channel per channel
+struct ChannelRoute {
+    map: Vec<usize>,
+    out: Vec<u8>,
+}
+
+impl DspStage for ChannelRoute {
+    fn name(&self) -> &'static str {
+        "channels"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        self.out.clear();
+        for frame in data.chunks_exact(self.map.len() * 4) {
+            for &source in &self.map {
+                self.out
+                    .extend_from_slice(&frame[source * 4..source * 4 + 4]);
+            }
+        }
+        std::mem::swap(data, &mut self.out);
+    }
+}
+
+/// Peak limiter: the gain drops at once to keep a peak at the threshold and
+/// recovers over the release time; all channels share one gain
+struct Limiter {
+    threshold: f32,
+    /// Factor the held peak decays by per frame
+    release: f32,
+    /// Peak held from earlier frames
+    envelope: f32,
+    channels: usize,
+}
+
+impl Limiter {
+    fn new(threshold_db: f32, release_ms: u32, format: &AudioFormat) -> Self {
+        let release_frames = format.sample_rate as f32 * release_ms.max(1) as f32 / 1000.0;
+        Self {
+            threshold: 10f32.powf(threshold_db.min(0.0) / 20.0),
+            release: (-1.0 / release_frames).exp(),
+            envelope: 0.0,
+            channels: format.channels.max(1) as usize,
+        }
+    }
+}
+
+impl DspStage for Limiter {
+    fn name(&self) -> &'static str {
+        "limiter"
+    }
+
+    fn process(&mut self, data: &mut Vec<u8>) {
+        for frame in data.chunks_exact_mut(self.channels * 4) {
+            let peak = frame
+                .chunks_exact(4)
+                .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]).abs())
+                .fold(0.0, f32::max);
+            self.envelope = peak.max(self.envelope * self.release);
+            if self.envelope <= self.threshold {
+                continue;
+            }
+            let gain = self.threshold / self.envelope;
+            for sample in frame.chunks_exact_mut(4) {
+                let value = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                sample.copy_from_slice(&(value * gain).to_le_bytes());
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::SampleEncoding;
+
+    fn samples(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn values(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_configured_chain() {
+        let stereo = AudioFormat {
+            sample_rate: 1000,
+            channels: 2,
+            bits_per_sample: 32,
+            block_align: 8,
+            encoding: SampleEncoding::Float,
+            channel_mask: 0x3,
+        };
+        let stages: Vec<DspStageConfig> = toml::from_str::<toml::Table>(
+            r#"stages = [
+                { type = "channels", map = [1, 0] },
+                { type = "gain", db = 6.0 },
+                { type = "limiter", threshold_db = -6.0 },
+                { type = "delay", ms = 1 },
+            ]"#,
+        )
+        .unwrap()["stages"]
+            .clone()
+            .try_into()
+            .unwrap();
+        assert_eq!(stages[2].to_string(), "limiter -6.0dB");
+
+        let mut chain = DspChain::default();
+        for stage in &stages {
+            chain.push(stage.build(&stereo).unwrap());
+        }
+        assert_eq!(chain.names(), ["channels", "gain", "limiter", "delay"]);
+
+        // Swapped, doubled, the 1.6 peak limited to -6dB, one frame late
+        let out = values(chain.process(&samples(&[0.1, 0.2, 0.05, 0.8, 0.0, 0.0])));
+        let ceiling = 10f32.powf(-6.0 / 20.0);
+        let expected = [0.0, 0.0, 0.4, 0.2, ceiling, 0.1 * ceiling / 1.6];
+        for (out, expected) in out.iter().zip(expected) {
+            assert!((out - expected).abs() < 1e-3, "{:?}", out);
+        }
+
+        let route = DspStageConfig::Channels { map: vec![0, 2] };
+        assert!(route.build(&stereo).is_err());
+    }
+}
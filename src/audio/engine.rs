@@ -1,22 +1,97 @@
 //! Audio engine - main controller coordinating capture and renderers
 
+use crate::audio::announce::Ducker;
+use crate::audio::backend::{AudioBackend, CaptureBackend, RenderBackend, VolumeSource};
 use crate::audio::buffer::ReaderState;
-use crate::audio::volume::{apply_volume_f32, VolumeLevel, VolumeTracker};
-use crate::audio::{AudioFormat, HardwareCapabilities, HdmiRenderer, LoopbackCapture, RingBuffer};
-use crate::device::{DeviceEnumerator, DeviceEvent, DeviceInfo, DeviceMonitor};
+use crate::audio::channels::ChannelMap;
+use crate::audio::delay::DelayLine;
+use crate::audio::dsp::{DspChain, DspStageConfig, LiveEqualizer, OutputResampler, SpeakerMap};
+use crate::audio::fade::{CutFade, Envelope, Transition, FADE_DURATION, PAUSE_FADE_MS};
+use crate::audio::fallback::SourceSwitch;
+use crate::audio::meter::{LevelCounters, LevelMeter, SignalLevel};
+use crate::audio::mixer::Mixer;
+use crate::audio::player::FilePlayer;
+use crate::audio::realtime::{prioritize_render_thread, PerformanceMode, ThreadPriority};
+use crate::audio::resample::{can_convert, internal_format, ConvertedCapture};
+use crate::audio::stats::{
+    CaptureClock, Heartbeat, RendererCounters, ThreadHealth, THREAD_STALL_TIMEOUT,
+};
+use crate::audio::volume::{apply_volume_f32, VolumeLevel, VolumeMode};
+use crate::audio::{
+    Announcement, AudioFormat, BufferPlan, BufferTuning, DeviceLatency, EngineStats, EqSettings,
+    FallbackSource, HardwareCapabilities, MixSource, RendererStats, RingBuffer, SampleEncoding,
+    ThreadState, UnderrunTracker, WasapiBackend, Zone, ZoneStatus,
+};
+use crate::device::{
+    find_device, find_duplicates, DeviceAliases, DeviceEvent, DeviceInfo, DeviceKind, DeviceState,
+    MatchRank,
+};
 use crate::error::{Result, WemuxError};
-use crate::sync::ClockSync;
-use crossbeam_channel::{bounded, Receiver, Sender};
+use crate::ipc::{InstanceLock, ENGINE_INSTANCE_NAME};
+use crate::sync::{
+    ClockReference, ClockSync, DevicePosition, DEFAULT_DRIFT_THRESHOLD_MS,
+    DEFAULT_MAX_CORRECTION_MS,
+};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
 use parking_lot::Mutex;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+/// Consecutive capture read failures before the engine is considered dead (~1s of retries)
+const MAX_CAPTURE_ERRORS: u32 = 100;
+
+/// Capture reinitialization attempts before giving up on a default device change
+const MAX_REINIT_ATTEMPTS: u32 = 5;
+
+/// Engines started in this process; hosts replace their engine on config
+/// changes, so restarts are counted across instances
+static ENGINE_STARTS: AtomicU64 = AtomicU64::new(0);
+
+/// How often the watchdog checks the worker threads' heartbeats
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Render thread replacements per device before the engine is given up on
+const MAX_RENDERER_RESTARTS: u32 = 3;
+
+/// Time `stop` waits for all worker threads before leaving the rest behind
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Latency assumed for Bluetooth outputs unless configured otherwise
+pub const BLUETOOTH_LATENCY_MS: u32 = 200;
+
+/// Outputs that got frames this recently count as receiving audio
+const AUDIO_ACTIVITY_WINDOW: Duration = Duration::from_secs(1);
+
+/// Size of each mixed source's own buffer in milliseconds
+const MIX_BUFFER_MS: u32 = 500;
+
+/// Audio a mixed source may get ahead of the main capture before it is trimmed
+const MIX_MAX_BACKLOG_MS: u32 = 40;
+
+/// How long the capture waits for audio while the fallback source plays, so
+/// the fallback's audio is passed on steadily when the main source sends none
+const FALLBACK_POLL_MS: u32 = 10;
+
+/// Time without audio from the main source after which announcements and
+/// mixed-in sources are passed on by themselves
+const SOURCE_IDLE: Duration = Duration::from_millis(50);
+
+/// How often a render thread retries opening its device once Windows
+/// reports it back, while the device isn't ready yet
+const DEVICE_REOPEN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Weight of each new measurement in the reported end-to-end latency, which
+/// jumps by a period with every write
+const LATENCY_SMOOTHING: f64 = 0.05;
+
 /// Device status for external control
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DeviceStatus {
     /// Device ID
     pub id: String,
@@ -28,34 +103,200 @@ pub struct DeviceStatus {
     pub is_paused: bool,
     /// Whether this device is the current system default output (auto-paused, cannot be controlled)
     pub is_system_default: bool,
+    /// Whether this device is the output the engine captures (auto-paused, cannot be controlled)
+    #[serde(default)]
+    pub is_capture_source: bool,
+    /// User-assigned alias
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Device period, latency class and buffer, once the engine has opened the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<DeviceLatency>,
+    /// Output reaching the same sink, if this one started paused as its duplicate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+    /// Whether frames were written to the device in the last second
+    #[serde(default)]
+    pub receiving_audio: bool,
+    /// Time from capturing audio to the device playing it, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_to_end_ms: Option<u32>,
+    /// State of the device while Windows reports it disabled or unplugged;
+    /// its output waits for it to come back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inactive: Option<DeviceState>,
+}
+
+/// What [`AudioEngine::start`] would do, worked out without opening any device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartPlan {
+    /// Zone the plan is for, when zones are configured
+    pub zone: Option<String>,
+    /// What would be captured, e.g. "default output (Speakers)"
+    pub source: String,
+    /// Buffer sizes derived from `buffer_ms` and the number of outputs
+    pub buffers: BufferPlan,
+    /// Outputs that would be opened, in order
+    pub outputs: Vec<PlannedOutput>,
+}
+
+/// An output as [`AudioEngine::start`] would set it up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedOutput {
+    pub id: String,
+    /// Alias if one is assigned, else the device name
+    pub name: String,
+    /// Whether the other outputs would be kept in step with this one
+    pub master: bool,
+    /// Why the output would start paused, if it would
+    pub paused: Option<String>,
+    /// Extra delay in milliseconds
+    pub delay_ms: u32,
+}
+
+impl DeviceStatus {
+    /// Name to show the user: the alias if one is assigned, else the device name
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether the engine paused the device to prevent feedback, so it
+    /// can't be resumed
+    pub fn is_auto_paused(&self) -> bool {
+        self.is_system_default || self.is_capture_source
+    }
+}
+
+/// Kind of device the engine captures from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SourceType {
+    /// Loopback of an output device: whatever the computer plays
+    #[default]
+    Loopback,
+    /// An input device such as line-in or a microphone, e.g. a turntable
+    /// or mixer plugged into the sound card
+    Input,
+    /// An audio file (WAV, FLAC, MP3 or Ogg Vorbis) played in a loop, e.g.
+    /// background music
+    File,
 }
 
 /// Engine configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EngineConfig {
-    /// Buffer size in milliseconds
+    /// Audio each renderer moves to its device per write, in milliseconds;
+    /// the ring buffer and pre-fill scale with it (see [`BufferPlan`])
     pub buffer_ms: u32,
+    /// Buffer in milliseconds per device ID or alias, for outputs that need
+    /// more (or less) than `buffer_ms`; see [`BufferPlan::initial_tuning_for`]
+    pub device_buffer_ms: HashMap<String, u32>,
     /// Specific device IDs to use (None = auto-detect all output devices)
     pub device_ids: Option<Vec<String>>,
     /// Device IDs to exclude (system default will be auto-excluded)
     pub exclude_ids: Option<Vec<String>>,
     /// Source device ID for loopback (None = system default)
     pub source_device_id: Option<String>,
+    /// Whether `source_device_id` names an output to loop back, an input
+    /// to record or an audio file to play; with [`SourceType::Input`] None means
+    /// the default input
+    pub source_type: SourceType,
+    /// Apps (executable names, ".exe" optional) left out of a loopback
+    /// source, e.g. a voice chat; each other app is captured on its own
+    pub exclude_apps: Vec<String>,
     /// Device IDs that should start paused (disabled in settings)
     pub paused_device_ids: Option<Vec<String>>,
     /// Use all output devices instead of HDMI only
     pub use_all_devices: bool,
+    /// Refuse to start while another process runs a wemux engine
+    pub exclusive: bool,
+    /// User-assigned device aliases, usable in `device_ids` and `exclude_ids`
+    pub aliases: DeviceAliases,
+    /// Extra sources mixed into the captured audio
+    pub mix_sources: Vec<MixSource>,
+    /// Source played instead of the captured audio while that is silent
+    pub fallback_source: Option<FallbackSource>,
+    /// Source played over the captured audio, which is ducked while it is audible
+    pub announcement: Option<Announcement>,
+    /// Pace renderers by the audio the capture delivers instead of a master
+    /// renderer's clock (for variable-rate sources such as virtual cables)
+    pub follow_source: bool,
+    /// Drift in milliseconds before a renderer is corrected
+    pub drift_threshold_ms: f64,
+    /// Most audio in milliseconds skipped or repeated per correction
+    pub max_correction_ms: f64,
+    /// Run the capture thread as a critical MMCSS task on a core of its own,
+    /// polling for audio instead of sleeping; lowest latency, one busy core
+    pub performance_capture: bool,
+    /// Scheduling priority of the render threads
+    pub render_priority: ThreadPriority,
+    /// Core to pin the render thread to per device ID or alias; threads of
+    /// other devices run on any core
+    pub render_cores: HashMap<String, usize>,
+    /// Equalizer per device ID; devices without an entry are not equalized
+    pub equalizers: HashMap<String, EqSettings>,
+    /// Extra delay in milliseconds per device ID or alias, to line up outputs
+    /// that play sooner than others (see `wemux calibrate`)
+    pub delays: HashMap<String, u32>,
+    /// Processing stages per device ID or alias, run after the equalizer
+    /// and before the delay
+    pub dsp: HashMap<String, Vec<DspStageConfig>>,
+    /// Latency assumed for Bluetooth outputs: while one plays, the outputs
+    /// without an entry in `delays` are delayed by this much (0 = off)
+    pub bluetooth_latency_ms: u32,
+    /// Time in milliseconds an output fades out when paused and in when
+    /// resumed (0 = switch at once)
+    pub pause_fade_ms: u32,
+    /// Whether the outputs follow the source's volume, play at
+    /// `mirror_volume` or follow it scaled by `mirror_volume`
+    pub volume_mode: VolumeMode,
+    /// Volume of the outputs (0.0 - 1.0) with [`VolumeMode::Fixed`] and
+    /// [`VolumeMode::Scaled`], independent of the main output's volume
+    pub mirror_volume: f32,
+    /// Groups of outputs, each with its own capture and clock sync; when
+    /// set, only the zones' devices play and `device_ids` is not used
+    pub zones: Vec<Zone>,
+    /// Start outputs paused that reach the same physical sink as another
+    /// output (same hardware container), e.g. an AV receiver exposed twice
+    pub suppress_duplicates: bool,
+    /// Device IDs or aliases that play even when they duplicate another output
+    pub allow_duplicates: Vec<String>,
 }
 
 impl Default for EngineConfig {
     fn default() -> Self {
         Self {
             buffer_ms: 50,
+            device_buffer_ms: HashMap::new(),
             device_ids: None,
             exclude_ids: None,
             source_device_id: None,
+            source_type: SourceType::default(),
+            exclude_apps: Vec::new(),
             paused_device_ids: None,
             use_all_devices: false,
+            exclusive: false,
+            aliases: DeviceAliases::default(),
+            mix_sources: Vec::new(),
+            fallback_source: None,
+            announcement: None,
+            follow_source: false,
+            drift_threshold_ms: DEFAULT_DRIFT_THRESHOLD_MS,
+            max_correction_ms: DEFAULT_MAX_CORRECTION_MS,
+            performance_capture: false,
+            render_priority: ThreadPriority::default(),
+            render_cores: HashMap::new(),
+            equalizers: HashMap::new(),
+            delays: HashMap::new(),
+            dsp: HashMap::new(),
+            bluetooth_latency_ms: BLUETOOTH_LATENCY_MS,
+            pause_fade_ms: PAUSE_FADE_MS,
+            volume_mode: VolumeMode::default(),
+            mirror_volume: 1.0,
+            zones: Vec::new(),
+            suppress_duplicates: false,
+            allow_duplicates: Vec::new(),
         }
     }
 }
@@ -89,6 +330,25 @@ enum CaptureCommand {
 struct RendererControl {
     /// Flag to pause this renderer (keeps thread alive but silent)
     paused: Arc<AtomicBool>,
+    /// Flag pausing all renderers of the engine, shared by their controls
+    all_paused: Arc<AtomicBool>,
+    /// Counters published by the render thread for monitoring
+    stats: Arc<RendererCounters>,
+    /// Equalizer changes, picked up by the render thread between passes
+    eq_tx: Sender<EqSettings>,
+    eq_rx: Receiver<EqSettings>,
+    /// Last equalizer sent, handed again to a replacement render thread
+    eq_current: Arc<Mutex<EqSettings>>,
+    /// Extra delay applied to this output in milliseconds
+    delay_ms: u32,
+    /// Processing stages configured for this output
+    dsp: Vec<DspStageConfig>,
+    /// Core the render thread is pinned to, if configured
+    core: Option<usize>,
+    /// Set when the thread was replaced or its output deselected; it exits on its next pass
+    retired: Arc<AtomicBool>,
+    /// Output this one duplicates, if it started paused for that reason
+    duplicate_of: Option<String>,
 }
 
 /// Events from the engine that external controllers might care about
@@ -96,53 +356,160 @@ struct RendererControl {
 pub enum EngineEvent {
     /// Default audio device changed - UI should refresh
     DefaultDeviceChanged,
+    /// A device was added, removed, enabled or disabled (e.g. a TV powered
+    /// off) - UI should refresh
+    DeviceListChanged,
+    /// A renderer kept underrunning and was reopened with a larger buffer
+    BufferTuned {
+        device_id: String,
+        /// WASAPI buffer added on top of the hardware-derived size
+        extra_buffer_ms: u32,
+        /// Silence written before the first audio
+        prefill_ms: u32,
+    },
+    /// A renderer receives a compressed bitstream (AC3/DTS passthrough) and
+    /// passes it on without volume scaling or drift correction
+    Passthrough { device_id: String },
+    /// A render thread stopped responding and the watchdog replaced it
+    Recovered { device_id: String, reason: String },
+    /// An output reaches the same sink as another and started paused
+    /// ([`EngineConfig::suppress_duplicates`])
+    DuplicateSuppressed {
+        device_id: String,
+        duplicate_of: String,
+    },
+    /// An output could not be started or its writes started failing; sent
+    /// once per run of failures, the engine keeps retrying
+    RendererFailed { device_id: String, error: String },
+    /// An output's writes succeed again after [`RendererFailed`](Self::RendererFailed)
+    RendererWorking { device_id: String },
+    /// Capturing started failing; sent once per run of failures. A capture
+    /// that can't recover is reported as [`Fatal`](Self::Fatal)
+    CaptureFailed { error: String },
+    /// Capturing works again after [`CaptureFailed`](Self::CaptureFailed)
+    CaptureWorking,
+    /// An output fell so far behind the capture that audio was skipped
+    BufferOverrun { device_id: String },
+    /// The clock master's device was removed and `master` took over its
+    /// role, or no output is left to (`None`)
+    MasterChanged {
+        removed: String,
+        master: Option<String>,
+    },
+    /// The fallback source took over from the silent capture (true) or
+    /// yielded back to it once it was audible again (false)
+    SourceSwitched { fallback: bool },
+    /// An announcement started playing and the captured audio was ducked
+    /// (true), or it ended and the captured audio was restored (false)
+    Ducking { active: bool },
+    /// Unrecoverable failure (e.g. the Windows audio service restarted).
+    /// The engine no longer produces audio and should be stopped and restarted by its owner.
+    Fatal(String),
+}
+
+/// A zone's engine, started and stopped with the zone
+struct ZoneEngine<B: AudioBackend> {
+    name: String,
+    engine: AudioEngine<B>,
 }
 
 /// Audio engine coordinating capture and multiple renderers
-pub struct AudioEngine {
+///
+/// Generic over the [`AudioBackend`] it drives; [`AudioEngine::new`] uses WASAPI.
+pub struct AudioEngine<B: AudioBackend = WasapiBackend> {
     config: EngineConfig,
+    backend: Arc<B>,
     state: Arc<Mutex<EngineState>>,
     stop_flag: Arc<AtomicBool>,
     capture_handle: Option<JoinHandle<()>>,
-    render_handles: Vec<JoinHandle<()>>,
+    mix_handles: Vec<JoinHandle<()>>,
+    render_handles: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+    watchdog_handle: Option<JoinHandle<()>>,
     command_tx: Option<Sender<EngineCommand>>,
     buffer: Option<Arc<RingBuffer>>,
     format: Option<AudioFormat>,
+    ring_buffer_ms: u32,
     volume_level: Arc<VolumeLevel>,
     volume_handle: Option<JoinHandle<()>>,
+    // Pauses every renderer at once, kept across restarts like the mute
+    all_paused: Arc<AtomicBool>,
     // Device monitoring
-    device_monitor: Option<DeviceMonitor>,
+    device_monitor: Option<B::Monitor>,
     monitor_handle: Option<JoinHandle<()>>,
     renderer_controls: Arc<Mutex<HashMap<String, RendererControl>>>,
     capture_cmd_tx: Option<Sender<CaptureCommand>>,
     // Track current default device and device names for external control
     current_default_id: Arc<Mutex<Option<String>>>,
+    // Output captured when it isn't the default, paused like the default
+    current_source_id: Option<String>,
     device_names: Arc<Mutex<HashMap<String, String>>>,
+    // Aliases of the active renderers, keyed by device ID
+    device_aliases: HashMap<String, String>,
+    // Buffer tuning learned per device, kept across restarts
+    buffer_tunings: Arc<Mutex<HashMap<String, BufferTuning>>>,
     // Event notification channel for external listeners
     event_tx: Option<Sender<EngineEvent>>,
+    // Machine-wide engine lock, held while running in exclusive mode
+    instance_lock: Option<InstanceLock>,
+    // One engine per configured zone while running with zones
+    zones: Vec<ZoneEngine<B>>,
+    // Shared with the watchdog, to add renderers while running
+    render_context: Option<Arc<RenderContext<B>>>,
+    // Heartbeats of the capture and mixed source threads, for stats
+    thread_heartbeats: Vec<(String, Arc<Heartbeat>)>,
+    // Level of the audio the capture thread passes on, for stats
+    level: Arc<LevelCounters>,
 }
 
 impl AudioEngine {
     /// Create a new audio engine with the given configuration
     pub fn new(config: EngineConfig) -> Self {
+        Self::with_backend(config, WasapiBackend)
+    }
+}
+
+impl<B: AudioBackend> AudioEngine<B> {
+    /// Create an audio engine driving the given backend
+    pub fn with_backend(config: EngineConfig, backend: B) -> Self {
+        Self::with_shared_backend(config, Arc::new(backend))
+    }
+
+    /// Create an audio engine driving a backend shared with other engines
+    fn with_shared_backend(config: EngineConfig, backend: Arc<B>) -> Self {
+        let volume_level = Arc::new(VolumeLevel::new());
+        volume_level.set_mode(config.volume_mode);
+        volume_level.set_mirror(config.mirror_volume);
         Self {
             config,
+            backend,
             state: Arc::new(Mutex::new(EngineState::Uninitialized)),
             stop_flag: Arc::new(AtomicBool::new(false)),
             capture_handle: None,
-            render_handles: Vec::new(),
+            mix_handles: Vec::new(),
+            render_handles: Arc::new(Mutex::new(HashMap::new())),
+            watchdog_handle: None,
             command_tx: None,
             buffer: None,
             format: None,
-            volume_level: Arc::new(VolumeLevel::new()),
+            ring_buffer_ms: 0,
+            volume_level,
             volume_handle: None,
+            all_paused: Arc::new(AtomicBool::new(false)),
             device_monitor: None,
             monitor_handle: None,
             renderer_controls: Arc::new(Mutex::new(HashMap::new())),
             capture_cmd_tx: None,
             current_default_id: Arc::new(Mutex::new(None)),
+            current_source_id: None,
             device_names: Arc::new(Mutex::new(HashMap::new())),
+            device_aliases: HashMap::new(),
+            buffer_tunings: Arc::new(Mutex::new(HashMap::new())),
             event_tx: None,
+            instance_lock: None,
+            zones: Vec::new(),
+            render_context: None,
+            thread_heartbeats: Vec::new(),
+            level: Arc::new(LevelCounters::default()),
         }
     }
 
@@ -159,7 +526,130 @@ impl AudioEngine {
 
     /// Get the audio format (available after initialization)
     pub fn format(&self) -> Option<&AudioFormat> {
-        self.format.as_ref()
+        self.format
+            .as_ref()
+            .or_else(|| self.zones.iter().find_map(|zone| zone.engine.format()))
+    }
+
+    /// Work out what [`start`](Self::start) would do without opening any
+    /// audio client: one plan, or one per zone
+    ///
+    /// Fails where starting would fail on the configuration, e.g. a source or
+    /// device selection that matches nothing.
+    pub fn plan(&self) -> Result<Vec<StartPlan>> {
+        let caps = HardwareCapabilities::default();
+        self.validate_buffers(&caps)?;
+
+        if !self.config.zones.is_empty() {
+            let mut plans = Vec::with_capacity(self.config.zones.len());
+            for zone in &self.config.zones {
+                let mut config = self.config.clone();
+                config.device_ids = Some(zone.devices.clone());
+                config.source_device_id = zone.source.clone().or(config.source_device_id);
+                config.zones = Vec::new();
+                let engine = AudioEngine::with_shared_backend(config, self.backend.clone());
+                for mut plan in engine.plan()? {
+                    plan.zone = Some(zone.name.clone());
+                    plans.push(plan);
+                }
+            }
+            return Ok(plans);
+        }
+
+        let outputs = self.backend.output_devices()?;
+        let name_of = |id: &str| {
+            outputs
+                .iter()
+                .chain(&self.backend.input_devices().unwrap_or_default())
+                .find(|d| d.id == id)
+                .map_or_else(|| id.to_string(), |d| d.name.clone())
+        };
+        let source_id = match self.config.source_type {
+            SourceType::File => None,
+            _ => self.resolve_source()?,
+        };
+        let source = match self.config.source_type {
+            SourceType::File => match &self.config.source_device_id {
+                Some(path) => format!("file {}", path),
+                None => {
+                    return Err(WemuxError::InvalidConfig(
+                        "A file source needs the path of an audio file".to_string(),
+                    ))
+                }
+            },
+            _ => match &source_id {
+                Some(id) => name_of(id),
+                None => match self.backend.default_output_id() {
+                    Some(id) => format!("default output ({})", name_of(&id)),
+                    None => "default output (none)".to_string(),
+                },
+            },
+        };
+        let captured_output = source_id.filter(|_| self.config.source_type == SourceType::Loopback);
+
+        let known_devices = self.alias_candidates();
+        let target_devices = self.get_target_devices(&known_devices)?;
+        if target_devices.is_empty() {
+            return Err(WemuxError::NoHdmiDevices);
+        }
+        let aliases = self.config.aliases.assignments(&known_devices);
+
+        // The same decisions start() makes, in the same order
+        let default_device_id = if self.config.source_type == SourceType::Loopback {
+            self.backend.default_output_id()
+        } else {
+            None
+        };
+        let bluetooth_playing = target_devices.iter().any(|device| {
+            device.kind() == DeviceKind::Bluetooth
+                && default_device_id.as_ref() != Some(&device.id)
+                && captured_output.as_ref() != Some(&device.id)
+                && !self.should_device_start_paused(&device.id)
+        });
+        let duplicates = if self.config.suppress_duplicates {
+            self.duplicate_outputs(
+                &target_devices,
+                default_device_id.as_deref(),
+                &known_devices,
+            )
+        } else {
+            HashMap::new()
+        };
+
+        let planned = target_devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| {
+                let paused = if default_device_id.as_ref() == Some(&device.id) {
+                    Some("default output".to_string())
+                } else if captured_output.as_ref() == Some(&device.id) {
+                    Some("capture source".to_string())
+                } else if self.should_device_start_paused(&device.id) {
+                    Some("disabled in settings".to_string())
+                } else {
+                    duplicates
+                        .get(&device.id)
+                        .map(|other| format!("duplicate of {}", name_of(other)))
+                };
+                PlannedOutput {
+                    id: device.id.clone(),
+                    name: aliases
+                        .get(&device.id)
+                        .cloned()
+                        .unwrap_or_else(|| device.name.clone()),
+                    master: index == 0 && !self.config.follow_source,
+                    paused,
+                    delay_ms: self.delay_with(&aliases, device, bluetooth_playing),
+                }
+            })
+            .collect();
+
+        Ok(vec![StartPlan {
+            zone: None,
+            source,
+            buffers: BufferPlan::new(self.config.buffer_ms, &caps, target_devices.len()),
+            outputs: planned,
+        }])
     }
 
     /// Initialize and start the engine
@@ -173,20 +663,80 @@ impl AudioEngine {
 
         info!("Starting audio engine...");
 
+        let result = self.start_threads();
+        if result.is_err() {
+            // Threads started before the failure would keep the devices
+            // open, and every retry would add another set
+            let stuck = self.teardown();
+            if !stuck.is_empty() {
+                warn!(
+                    "Threads of the failed start left behind: {}",
+                    stuck.join(", ")
+                );
+            }
+        }
+        result
+    }
+
+    /// The part of [`start`](Self::start) that opens the devices and starts
+    /// the threads; what it started is left to [`teardown`](Self::teardown)
+    /// if it fails
+    fn start_threads(&mut self) -> Result<()> {
+        // Use Standard latency class as hardware detection happens per renderer
+        let caps = HardwareCapabilities::default();
+        self.validate_buffers(&caps)?;
+
+        // Claim the devices before touching them; released again if startup fails
+        let instance_lock = if self.config.exclusive {
+            match InstanceLock::acquire(ENGINE_INSTANCE_NAME)? {
+                Some(lock) => Some(lock),
+                None => return Err(WemuxError::EngineInUse),
+            }
+        } else {
+            None
+        };
+
         // Reset stop flag
         self.stop_flag.store(false, Ordering::SeqCst);
 
+        if !self.config.zones.is_empty() {
+            self.zones = self.start_zones()?;
+            self.instance_lock = instance_lock;
+            *self.state.lock() = EngineState::Running;
+            info!("Audio engine started with {} zones", self.zones.len());
+            return Ok(());
+        }
+
+        // Capture a fixed source if configured, else follow the default output
+        let target = self.capture_target()?;
+        let source_id = match &target {
+            CaptureTarget::Device(id) | CaptureTarget::Apps(Some(id), _) => Some(id.clone()),
+            _ => None,
+        };
+        // Playing to the captured output would feed its audio back into it
+        let captured_output = source_id
+            .clone()
+            .filter(|_| self.config.source_type == SourceType::Loopback);
+
         // Create loopback capture (just to get format, will be recreated in thread)
-        let capture = LoopbackCapture::from_default_device()?;
-        let format = capture.format().clone();
+        let capture = open_capture_target(&*self.backend, &target)?;
+        let format = internal_format(capture.format());
         self.format = Some(format.clone());
+        if *capture.format() != format {
+            info!(
+                "Capture format: {}, passed on as {}",
+                capture.format(),
+                format
+            );
+        } else {
+            info!("Capture format: {}", format);
+        }
         drop(capture); // Release the capture, thread will create its own
 
-        info!("Capture format: {}", format);
-
         // Enumerate and create renderers
-        let enumerator = DeviceEnumerator::new()?;
-        let target_devices = self.get_target_devices(&enumerator)?;
+        let known_devices = self.alias_candidates();
+        let target_devices = self.get_target_devices(&known_devices)?;
+        self.device_aliases = self.config.aliases.assignments(&known_devices);
 
         if target_devices.is_empty() {
             return Err(WemuxError::NoHdmiDevices);
@@ -202,77 +752,249 @@ impl AudioEngine {
             info!("  - {}", device.name);
         }
 
-        // Auto-calculate optimal ring buffer size based on number of renderers
-        // Use Standard latency class as default if hardware detection fails
-        let ring_buffer_ms = HardwareCapabilities::default()
-            .optimal_ring_buffer_ms(target_devices.len());
-        let buffer_size = format.buffer_size_for_ms(ring_buffer_ms);
+        // Size the buffers from buffer_ms and the number of renderers
+        let plan = BufferPlan::new(self.config.buffer_ms, &caps, target_devices.len());
+        let buffer_size = format.buffer_size_for_ms(plan.ring_buffer_ms);
         let buffer = Arc::new(RingBuffer::new(buffer_size));
         self.buffer = Some(buffer.clone());
-        info!("Ring buffer: {}ms ({} bytes)", ring_buffer_ms, buffer_size);
+        self.ring_buffer_ms = plan.ring_buffer_ms;
+        info!(
+            "Ring buffer: {}ms ({} bytes), {}ms chunks, {}ms pre-fill",
+            plan.ring_buffer_ms, buffer_size, plan.chunk_ms, plan.prefill_ms
+        );
 
         // Create clock sync
-        let clock_sync = Arc::new(Mutex::new(ClockSync::new(format.sample_rate)));
+        let reference = if self.config.follow_source {
+            info!("Renderers follow the source clock");
+            ClockReference::Source
+        } else {
+            ClockReference::WallClock
+        };
+        let clock_sync = Arc::new(Mutex::new(
+            ClockSync::with_reference(format.sample_rate, reference).with_drift_limits(
+                self.config.drift_threshold_ms,
+                self.config.max_correction_ms,
+            ),
+        ));
+        // Lets the renderers fade around a capture restart
+        let transition = Arc::new(Transition::default());
 
         // Create command channel
-        let (cmd_tx, _cmd_rx) = bounded::<EngineCommand>(16);
+        let (cmd_tx, cmd_rx) = bounded::<EngineCommand>(16);
         self.command_tx = Some(cmd_tx);
 
         // Create capture command channel
         let (capture_cmd_tx, capture_cmd_rx) = bounded::<CaptureCommand>(16);
         self.capture_cmd_tx = Some(capture_cmd_tx.clone());
 
+        // Capture the extra sources to mix in; a source that can't be used is skipped
+        let mut mixer = Mixer::new(
+            format.buffer_size_for_ms(MIX_MAX_BACKLOG_MS),
+            format.block_align as usize,
+        );
+        let mix_sources = if format.is_bitstream() && !self.config.mix_sources.is_empty() {
+            warn!("Captured audio is a bitstream, not mixing other sources into it");
+            Vec::new()
+        } else {
+            self.config.mix_sources.clone()
+        };
+        for source in mix_sources {
+            match self.start_mix_source(&source, &format) {
+                Ok(source_buffer) => mixer.add_input(source_buffer, source.gain),
+                Err(e) => warn!("Not mixing source {}: {}", source.device, e),
+            }
+        }
+
+        // The fallback source is captured all along, ready to take over
+        let fallback = match self.config.fallback_source.clone() {
+            Some(source) if format.is_bitstream() => {
+                warn!(
+                    "Captured audio is a bitstream, not falling back to {}",
+                    source.device
+                );
+                None
+            }
+            Some(source) => match self.start_fallback_source(&source, &format) {
+                Ok(source_buffer) => Some(SourceSwitch::new(
+                    source_buffer,
+                    Duration::from_secs(source.after_secs as u64),
+                    format.buffer_size_for_ms(MIX_MAX_BACKLOG_MS),
+                    Instant::now(),
+                )),
+                Err(e) => {
+                    warn!("Not falling back to {}: {}", source.device, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // So is the announcement source, ready to be played over the capture
+        let announcement = match self.config.announcement.clone() {
+            Some(source) if format.is_bitstream() => {
+                warn!(
+                    "Captured audio is a bitstream, not playing announcements from {}",
+                    source.device
+                );
+                None
+            }
+            Some(source) => match self.start_announcement_source(&source, &format) {
+                Ok(source_buffer) => Some(Ducker::new(
+                    source_buffer,
+                    source.duck_db,
+                    &format,
+                    format.buffer_size_for_ms(MIX_MAX_BACKLOG_MS),
+                )),
+                Err(e) => {
+                    warn!("Not playing announcements from {}: {}", source.device, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
         // Start capture thread
-        let capture_buffer = buffer.clone();
+        let capture_backend = self.backend.clone();
+        let capture_clock = Arc::new(CaptureClock::default());
+        let capture_output = CaptureOutput {
+            buffer: buffer.clone(),
+            format: format.clone(),
+            mixer,
+            fallback,
+            announcement,
+            heartbeat: Arc::new(Heartbeat::default()),
+            capture_clock: capture_clock.clone(),
+            transition: transition.clone(),
+            // Only float samples can be measured
+            meter: (format.encoding == SampleEncoding::Float && format.bits_per_sample == 32)
+                .then(|| LevelMeter::new(self.level.clone(), format.sample_rate, format.channels)),
+        };
+        let capture_heartbeat = capture_output.heartbeat.clone();
+        self.thread_heartbeats
+            .push(("capture".to_string(), capture_heartbeat.clone()));
         let capture_stop = self.stop_flag.clone();
+        let capture_event_tx = self.event_tx.clone();
+        let capture_target = target;
+        let performance = self.config.performance_capture;
+        if performance {
+            info!("Capturing in performance mode");
+        }
 
         self.capture_handle = Some(thread::spawn(move || {
-            capture_thread(capture_buffer, capture_stop, capture_cmd_rx);
+            capture_thread(
+                capture_backend,
+                capture_target,
+                capture_output,
+                capture_stop,
+                capture_cmd_rx,
+                capture_event_tx,
+                performance,
+            );
         }));
 
         // Create device monitor
         let (device_event_tx, device_event_rx) = bounded::<DeviceEvent>(64);
-        self.device_monitor = Some(DeviceMonitor::new(device_event_tx)?);
+        self.device_monitor = Some(self.backend.watch_devices(device_event_tx)?);
         info!("Device enumerator initialized");
 
         // Create channel for volume tracker device events
         let (volume_event_tx, volume_event_rx) = bounded::<DeviceEvent>(16);
 
-        // Start volume tracking thread
-        let volume_level = self.volume_level.clone();
-        let volume_stop = self.stop_flag.clone();
+        // Start volume tracking thread; an input's level is already applied
+        // to what it records, so its audio plays at full volume
+        let loopback = self.config.source_type == SourceType::Loopback;
+        if loopback {
+            let volume_backend = self.backend.clone();
+            let volume_level = self.volume_level.clone();
+            let volume_stop = self.stop_flag.clone();
 
-        self.volume_handle = Some(thread::spawn(move || {
-            volume_tracking_thread(volume_level, volume_stop, volume_event_rx);
-        }));
+            self.volume_handle = Some(thread::spawn(move || {
+                volume_tracking_thread(
+                    volume_backend,
+                    source_id,
+                    volume_level,
+                    volume_stop,
+                    volume_event_rx,
+                );
+            }));
+        } else {
+            self.volume_level.set(1.0);
+        }
 
         // Clear renderer controls and device names
         self.renderer_controls.lock().clear();
         self.device_names.lock().clear();
 
-        // Get current default device ID for checking during renderer setup
-        let default_device_id = enumerator
-            .get_default_render_device()
-            .ok()
-            .and_then(|d| unsafe {
-                d.GetId().ok().and_then(|id_ptr| {
-                    let id = windows::core::PCWSTR(id_ptr.0).to_string().ok();
-                    windows::Win32::System::Com::CoTaskMemFree(Some(id_ptr.0 as *const _));
-                    id
-                })
-            });
+        // Get current default device ID for checking during renderer setup;
+        // the default output doesn't play an input source by itself, so
+        // it isn't paused then
+        let default_device_id = if loopback {
+            self.backend.default_output_id()
+        } else {
+            None
+        };
 
-        // Store current default device ID
+        // Store current default device ID, and the captured output when
+        // that is another one
         *self.current_default_id.lock() = default_device_id.clone();
+        self.current_source_id = captured_output;
 
         // Start renderer threads
+        let render_context = Arc::new(RenderContext {
+            backend: self.backend.clone(),
+            buffer: buffer.clone(),
+            stop_flag: self.stop_flag.clone(),
+            clock_sync: clock_sync.clone(),
+            format: format.clone(),
+            volume_level: self.volume_level.clone(),
+            transition,
+            pause_fade: Duration::from_millis(self.config.pause_fade_ms as u64),
+            priority: self.config.render_priority,
+            buffer_tunings: self.buffer_tunings.clone(),
+            plan,
+            device_buffer_ms: self.device_buffers(),
+            capture_clock,
+            event_tx: self.event_tx.clone(),
+        });
+        self.render_context = Some(render_context.clone());
+        // Bluetooth outputs play far behind wired ones; the others wait for
+        // them unless their delay is configured
+        let bluetooth_playing = target_devices.iter().any(|device| {
+            device.kind() == DeviceKind::Bluetooth
+                && default_device_id.as_ref() != Some(&device.id)
+                && !self.is_capture_source(&device.id)
+                && !self.should_device_start_paused(&device.id)
+        });
+        if bluetooth_playing && self.config.bluetooth_latency_ms > 0 {
+            info!(
+                "Bluetooth output playing, delaying the others by {}ms",
+                self.config.bluetooth_latency_ms
+            );
+        }
+        let duplicates = if self.config.suppress_duplicates {
+            self.duplicate_outputs(
+                &target_devices,
+                default_device_id.as_deref(),
+                &known_devices,
+            )
+        } else {
+            HashMap::new()
+        };
+
         let mut first_device = true;
         for device_info in target_devices {
-            let device = enumerator.get_device_by_id(&device_info.id)?;
-            let renderer = HdmiRenderer::new(&device)?;
+            // A device Windows reports disabled or unplugged, e.g. a TV in
+            // standby, gets an output that waits for it
+            let output = render_context.open_output(&device_info.id)?;
 
-            // Set first device as master
-            if first_device {
+            // Set first device as master; when following the source all are
+            // slaves. A waiting output joins once its device is opened
+            if let RenderOutput::Inactive(state) = output {
+                warn!(
+                    "Device {} is {}, its output waits for it",
+                    device_info.name, state
+                );
+            } else if first_device && !self.config.follow_source {
                 clock_sync.lock().set_master(&device_info.id);
                 first_device = false;
             } else {
@@ -280,64 +1002,62 @@ impl AudioEngine {
             }
 
             // Create renderer control - start paused if:
-            // 1. This device is the default output (to prevent feedback)
+            // 1. This device is the default output or the captured one (to prevent feedback)
             // 2. This device is in the paused_device_ids list (from settings)
+            // 3. Another output already reaches the same sink
             let is_default = default_device_id
                 .as_ref()
                 .map(|id| id == &device_info.id)
                 .unwrap_or(false);
+            let is_source = self.is_capture_source(&device_info.id);
 
             let should_pause_from_config = self.should_device_start_paused(&device_info.id);
-            let should_start_paused = is_default || should_pause_from_config;
+            let duplicate_of = duplicates.get(&device_info.id).cloned();
+            let should_start_paused =
+                is_default || is_source || should_pause_from_config || duplicate_of.is_some();
 
             if is_default {
                 info!(
                     "Device {} is the default output, starting paused",
                     device_info.name
                 );
+            } else if is_source {
+                info!(
+                    "Device {} is the capture source, starting paused",
+                    device_info.name
+                );
             } else if should_pause_from_config {
                 info!(
                     "Device {} is disabled in settings, starting paused",
                     device_info.name
                 );
-            }
-
-            let paused_flag = Arc::new(AtomicBool::new(should_start_paused));
-            let renderer_control = RendererControl {
-                paused: paused_flag.clone(),
-            };
-            self.renderer_controls
-                .lock()
-                .insert(device_info.id.clone(), renderer_control);
-
-            // Store device name for external control
-            self.device_names
-                .lock()
-                .insert(device_info.id.clone(), device_info.name.clone());
-
-            let render_buffer = buffer.clone();
-            let render_stop = self.stop_flag.clone();
-            let render_clock = clock_sync.clone();
-            let render_format = format.clone();
-            let render_volume = self.volume_level.clone();
-
-            let handle = thread::spawn(move || {
-                render_thread(
-                    renderer,
-                    render_buffer,
-                    render_stop,
-                    paused_flag,
-                    render_clock,
-                    render_format,
-                    render_volume,
+            } else if let Some(other) = &duplicate_of {
+                info!(
+                    "Device {} reaches the same sink as {}, starting paused",
+                    device_info.name, other
                 );
-            });
+                if let Some(tx) = &self.event_tx {
+                    let _ = tx.send(EngineEvent::DuplicateSuppressed {
+                        device_id: device_info.id.clone(),
+                        duplicate_of: other.clone(),
+                    });
+                }
+            }
 
-            self.render_handles.push(handle);
+            let delay_ms = self.delay_for(&device_info, bluetooth_playing);
+            self.spawn_renderer(
+                &render_context,
+                output,
+                &device_info,
+                should_start_paused,
+                delay_ms,
+                duplicate_of,
+            );
         }
 
         // Start device monitor thread
         let monitor_controls = self.renderer_controls.clone();
+        let monitor_clock_sync = clock_sync.clone();
         let monitor_stop = self.stop_flag.clone();
         let monitor_default_id = self.current_default_id.clone();
         let monitor_event_tx = self.event_tx.clone();
@@ -346,21 +1066,43 @@ impl AudioEngine {
             device_monitor_thread(
                 device_event_rx,
                 monitor_controls,
+                monitor_clock_sync,
                 capture_cmd_tx,
                 volume_event_tx,
                 monitor_stop,
                 monitor_default_id,
+                loopback,
                 monitor_event_tx,
             );
         }));
 
+        // Start watchdog thread
+        let watchdog_controls = self.renderer_controls.clone();
+        let watchdog_handles = self.render_handles.clone();
+
+        self.watchdog_handle = Some(thread::spawn(move || {
+            watchdog_thread(
+                render_context,
+                watchdog_controls,
+                watchdog_handles,
+                capture_heartbeat,
+                cmd_rx,
+            );
+        }));
+
+        self.instance_lock = instance_lock;
         *self.state.lock() = EngineState::Running;
+        ENGINE_STARTS.fetch_add(1, Ordering::Relaxed);
         info!("Audio engine started");
 
         Ok(())
     }
 
     /// Stop the engine
+    ///
+    /// Threads that haven't exited within a few seconds, e.g. stuck in a
+    /// driver call, are left behind and reported as `ShutdownTimeout`; the
+    /// engine is stopped either way and can be started again.
     pub fn stop(&mut self) -> Result<()> {
         {
             let mut state = self.state.lock();
@@ -372,6 +1114,29 @@ impl AudioEngine {
 
         info!("Stopping audio engine...");
 
+        let stuck = self.teardown();
+
+        *self.state.lock() = EngineState::Stopped;
+        info!("Audio engine stopped");
+
+        if stuck.is_empty() {
+            Ok(())
+        } else {
+            Err(WemuxError::ShutdownTimeout(stuck))
+        }
+    }
+
+    /// Stop every thread the engine started and release what it holds
+    ///
+    /// Returns the threads that didn't exit in time.
+    fn teardown(&mut self) -> Vec<String> {
+        let mut stuck = Vec::new();
+        for mut zone in self.zones.drain(..) {
+            if let Err(WemuxError::ShutdownTimeout(threads)) = zone.engine.stop() {
+                stuck.extend(threads.into_iter().map(|t| format!("{}/{}", zone.name, t)));
+            }
+        }
+
         // Signal threads to stop
         self.stop_flag.store(true, Ordering::SeqCst);
 
@@ -384,65 +1149,270 @@ impl AudioEngine {
         // This must happen before waiting for monitor thread
         self.device_monitor = None;
 
+        // All threads share one deadline, so a wedged device can't hold up
+        // the caller (e.g. the tray's Exit) for long
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        let mut join = |handle: JoinHandle<()>, name: &str| {
+            if !join_until(handle, name, deadline) {
+                stuck.push(name.to_string());
+            }
+        };
+
+        // Wait for the watchdog first, so it can't start render threads any more
+        if let Some(handle) = self.watchdog_handle.take() {
+            join(handle, "watchdog");
+        }
+
         // Wait for capture thread
         if let Some(handle) = self.capture_handle.take() {
-            let _ = handle.join();
+            join(handle, "capture");
+        }
+
+        // Wait for mixed source threads
+        for handle in self.mix_handles.drain(..) {
+            join(handle, "mix source");
         }
+        self.thread_heartbeats.clear();
 
         // Wait for volume tracking thread
         if let Some(handle) = self.volume_handle.take() {
-            let _ = handle.join();
+            join(handle, "volume");
         }
 
         // Wait for device monitor thread
         if let Some(handle) = self.monitor_handle.take() {
-            let _ = handle.join();
+            join(handle, "device monitor");
         }
 
         // Wait for render threads
-        for handle in self.render_handles.drain(..) {
-            let _ = handle.join();
+        let render_handles: Vec<_> = self.render_handles.lock().drain().collect();
+        for (device_id, handle) in render_handles {
+            let name = self.device_names.lock().get(&device_id).cloned();
+            join(handle, name.as_deref().unwrap_or(&device_id));
         }
 
         // Clear renderer controls and device names
         self.renderer_controls.lock().clear();
         self.device_names.lock().clear();
+        self.device_aliases.clear();
 
         // Clear channels
         self.command_tx = None;
         self.capture_cmd_tx = None;
+        self.render_context = None;
 
         // Clear buffer and format
         self.buffer = None;
         self.format = None;
+        self.ring_buffer_ms = 0;
 
-        // Clear current default device
+        // Clear current default device and source
         *self.current_default_id.lock() = None;
+        self.current_source_id = None;
 
-        *self.state.lock() = EngineState::Stopped;
-        info!("Audio engine stopped");
+        // Let another process run its engine
+        self.instance_lock = None;
 
-        Ok(())
+        if !stuck.is_empty() {
+            // Threads left behind keep the old flag; restarting must not
+            // clear it under them
+            self.stop_flag = Arc::new(AtomicBool::new(false));
+        }
+        stuck
+    }
+
+    /// Apply a new configuration, restarting the engine only when needed
+    ///
+    /// Changes to which outputs play, their pause state, aliases, delays,
+    /// equalizers and duplicate suppression are applied while running:
+    /// deselected outputs stop, newly selected ones join and the others keep
+    /// playing without a gap. Other changes (buffer size, sources, zones, ...)
+    /// restart the engine, as does deselecting the output the others keep in
+    /// sync with or changing the delay of a running one. A stopped engine
+    /// keeps the configuration for its next start.
+    pub fn reconfigure(&mut self, config: EngineConfig) -> Result<()> {
+        let in_place = self.config.zones.is_empty() && self.differs_in_outputs_only(&config);
+        // A volume set from the tray or over IPC since is kept unless the
+        // configuration changes it too
+        if config.volume_mode != self.config.volume_mode {
+            self.set_volume_mode(config.volume_mode);
+        }
+        if config.mirror_volume != self.config.mirror_volume {
+            self.set_mirror_volume(config.mirror_volume);
+        }
+        self.config = config;
+        if !self.is_running() {
+            return Ok(());
+        }
+
+        if let Some(context) = self.render_context.clone().filter(|_| in_place) {
+            let known_devices = self.alias_candidates();
+            // A selection that matches nothing fails the same way on restart
+            if let Ok(target_devices) = self.get_target_devices(&known_devices) {
+                if self.update_outputs(&context, &target_devices, &known_devices) {
+                    info!("Audio engine reconfigured");
+                    return Ok(());
+                }
+            }
+        }
+
+        info!("Restarting audio engine with the new configuration");
+        self.restart()
+    }
+
+    /// Stop the engine if it runs and start it again
+    ///
+    /// Threads the stop leaves behind (`ShutdownTimeout`) are logged rather
+    /// than failing the restart, since the engine is stopped either way.
+    pub fn restart(&mut self) -> Result<()> {
+        match self.stop() {
+            Ok(()) => {}
+            Err(WemuxError::ShutdownTimeout(threads)) => {
+                warn!(
+                    "Restarting without waiting for stuck threads: {}",
+                    threads.join(", ")
+                );
+            }
+            Err(e) => return Err(e),
+        }
+        self.start()
+    }
+
+    /// Run the engine until `stop_signal` is set, then shut it down
+    ///
+    /// Starts the engine unless it is already running. Also returns once the
+    /// engine stops by other means (e.g. [`stop`](Self::stop) from an event
+    /// handler). Meant for hosts without a loop of their own:
+    ///
+    /// ```no_run
+    /// use std::sync::atomic::AtomicBool;
+    /// use wemux::audio::{AudioEngine, EngineConfig};
+    ///
+    /// static STOP: AtomicBool = AtomicBool::new(false);
+    ///
+    /// let mut engine = AudioEngine::new(EngineConfig::default());
+    /// // Set STOP from a signal handler or another thread to shut down
+    /// engine.run_blocking(&STOP).expect("Audio engine failed");
+    /// ```
+    pub fn run_blocking(&mut self, stop_signal: &AtomicBool) -> Result<()> {
+        if !self.is_running() {
+            self.start()?;
+        }
+
+        while !stop_signal.load(Ordering::SeqCst) && self.is_running() {
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        self.stop()
+    }
+
+    /// Create an engine for each zone and start the ones not paused
+    ///
+    /// Zone engines share this engine's backend, events and buffer tunings.
+    /// Zones started before a failure are stopped again when dropped.
+    fn start_zones(&self) -> Result<Vec<ZoneEngine<B>>> {
+        let mut zones: Vec<ZoneEngine<B>> = Vec::with_capacity(self.config.zones.len());
+        for zone in &self.config.zones {
+            if zone.devices.is_empty() {
+                return Err(WemuxError::InvalidConfig(format!(
+                    "Zone '{}' has no devices",
+                    zone.name
+                )));
+            }
+            if zones.iter().any(|z| z.name == zone.name) {
+                return Err(WemuxError::InvalidConfig(format!(
+                    "Zone '{}' is defined twice",
+                    zone.name
+                )));
+            }
+
+            let mut config = self.config.clone();
+            config.device_ids = Some(zone.devices.clone());
+            config.source_device_id = zone.source.clone().or(config.source_device_id);
+            config.exclusive = false;
+            config.zones = Vec::new();
+
+            let mut engine = AudioEngine::with_shared_backend(config, self.backend.clone());
+            engine.buffer_tunings = self.buffer_tunings.clone();
+            engine.event_tx = self.event_tx.clone();
+            engine.set_muted(self.is_muted());
+            engine.all_paused = self.all_paused.clone();
+            engine.set_volume_mode(self.volume_mode());
+            engine.set_mirror_volume(self.mirror_volume());
+            if zone.paused {
+                info!("Zone {} is paused", zone.name);
+            } else {
+                info!("Starting zone {}", zone.name);
+                engine.start()?;
+            }
+            zones.push(ZoneEngine {
+                name: zone.name.clone(),
+                engine,
+            });
+        }
+        Ok(zones)
+    }
+
+    /// Whether each zone of the running engine is playing
+    pub fn zone_statuses(&self) -> Vec<ZoneStatus> {
+        self.zones
+            .iter()
+            .map(|zone| ZoneStatus {
+                name: zone.name.clone(),
+                paused: !zone.engine.is_running(),
+            })
+            .collect()
+    }
+
+    /// Pause or resume a zone of the running engine
+    ///
+    /// A paused zone releases its devices and source; resuming starts it again.
+    pub fn set_zone_paused(&mut self, name: &str, paused: bool) -> Result<()> {
+        let muted = self.is_muted();
+        let zone = self
+            .zones
+            .iter_mut()
+            .find(|zone| zone.name == name)
+            .ok_or_else(|| WemuxError::InvalidConfig(format!("No zone named '{}'", name)))?;
+
+        if paused {
+            info!("Pausing zone {}", name);
+            zone.engine.stop()
+        } else if zone.engine.is_running() {
+            Ok(())
+        } else {
+            info!("Resuming zone {}", name);
+            zone.engine.set_muted(muted);
+            zone.engine.start()
+        }
+    }
+
+    /// Engine of the zone playing a device
+    fn zone_engine(&self, device_id: &str) -> Option<&AudioEngine<B>> {
+        self.zones
+            .iter()
+            .map(|zone| &zone.engine)
+            .find(|engine| engine.renderer_controls.lock().contains_key(device_id))
     }
 
     /// Get target devices based on configuration
-    fn get_target_devices(&self, enumerator: &DeviceEnumerator) -> Result<Vec<DeviceInfo>> {
+    fn get_target_devices(&self, known_devices: &[DeviceInfo]) -> Result<Vec<DeviceInfo>> {
         let mut devices = if let Some(ids) = &self.config.device_ids {
             // Use specified devices
-            let all_devices = enumerator.enumerate_all_devices()?;
+            let all_devices = self.backend.output_devices()?;
             all_devices
                 .into_iter()
                 .filter(|d| {
                     ids.iter()
-                        .any(|id| d.id.contains(id) || d.name.contains(id))
+                        .any(|id| self.matches_selector(d, id, known_devices))
                 })
                 .collect()
         } else if self.config.use_all_devices {
             // Use all output devices
-            enumerator.enumerate_all_devices()?
+            self.backend.output_devices()?
         } else {
             // Auto-detect HDMI devices only (legacy behavior)
-            enumerator.enumerate_hdmi_devices().unwrap_or_default()
+            self.backend.hdmi_devices().unwrap_or_default()
         };
 
         // Apply exclusions
@@ -450,62 +1420,665 @@ impl AudioEngine {
             devices.retain(|d| {
                 !excludes
                     .iter()
-                    .any(|ex| d.id.contains(ex) || d.name.contains(ex))
+                    .any(|ex| self.matches_selector(d, ex, known_devices))
             });
         }
 
         Ok(devices)
     }
 
-    /// Check if a device should start paused based on config
-    fn should_device_start_paused(&self, device_id: &str) -> bool {
-        if let Some(paused_ids) = &self.config.paused_device_ids {
-            paused_ids.iter().any(|id| id == device_id)
-        } else {
-            false
+    /// What the capture thread records
+    fn capture_target(&self) -> Result<CaptureTarget> {
+        if self.config.source_type != SourceType::File {
+            let source = self.resolve_source()?;
+            if !self.config.exclude_apps.is_empty() {
+                if self.config.source_type == SourceType::Loopback {
+                    return Ok(CaptureTarget::Apps(
+                        source,
+                        self.config.exclude_apps.clone(),
+                    ));
+                }
+                warn!("Apps can only be left out of a loopback source, capturing everything");
+            }
+            return Ok(match source {
+                Some(id) => CaptureTarget::Device(id),
+                None => CaptureTarget::DefaultOutput,
+            });
         }
+
+        let path = self.config.source_device_id.as_ref().ok_or_else(|| {
+            WemuxError::InvalidConfig("A file source needs the path of an audio file".to_string())
+        })?;
+        // Played in the format the default output's capture is passed on in
+        let format = internal_format(self.backend.open_capture(None)?.format());
+        info!("Playing file: {}", path);
+        Ok(CaptureTarget::File(PathBuf::from(path), format))
     }
 
-    /// Check if engine is running
-    pub fn is_running(&self) -> bool {
-        *self.state.lock() == EngineState::Running
+    /// Device ID of the configured capture source (None = follow the default output)
+    ///
+    /// An input source is always fixed: without a selector the default input
+    /// at start is used, and a later default change doesn't move the capture.
+    fn resolve_source(&self) -> Result<Option<String>> {
+        if self.config.source_type == SourceType::Input {
+            let inputs = self.backend.input_devices()?;
+            let source = match &self.config.source_device_id {
+                Some(selector) => find_device(selector, &inputs, &self.config.aliases)?,
+                None => {
+                    let default_id = self.backend.default_input_id();
+                    inputs
+                        .iter()
+                        .find(|d| default_id.as_ref() == Some(&d.id))
+                        .ok_or_else(|| WemuxError::DeviceNotFound("default input".to_string()))?
+                }
+            };
+            info!("Capturing from input: {}", source.name);
+            return Ok(Some(source.id.clone()));
+        }
+
+        let Some(selector) = &self.config.source_device_id else {
+            return Ok(None);
+        };
+
+        let source = self.resolve_device(selector)?;
+        info!("Capturing from fixed source: {}", source.name);
+        Ok(Some(source.id))
     }
 
-    /// Get status of all active renderers
-    pub fn get_device_statuses(&self) -> Vec<DeviceStatus> {
-        let controls = self.renderer_controls.lock();
-        let names = self.device_names.lock();
-        let current_default = self.current_default_id.lock();
+    /// Find the output device a source is given as
+    ///
+    /// Sources are given like a `device_ids` entry: an exact ID, an alias, or
+    /// part of a device ID or name; the best match wins (see [`find_device`]).
+    fn resolve_device(&self, selector: &str) -> Result<DeviceInfo> {
+        let devices = self.backend.output_devices()?;
+        find_device(selector, &devices, &self.config.aliases).cloned()
+    }
 
-        controls
-            .iter()
-            .map(|(id, control)| {
-                let is_system_default = current_default.as_ref().map(|d| d == id).unwrap_or(false);
-                DeviceStatus {
-                    id: id.clone(),
-                    name: names.get(id).cloned().unwrap_or_else(|| id.clone()),
-                    is_enabled: true, // In active renderers = enabled
-                    is_paused: control.paused.load(Ordering::Relaxed),
-                    is_system_default,
-                }
-            })
-            .collect()
+    /// Start capturing an extra source into its own buffer for the mixer
+    fn start_mix_source(
+        &mut self,
+        source: &MixSource,
+        format: &AudioFormat,
+    ) -> Result<Arc<RingBuffer>> {
+        let device = self.resolve_device(&source.device)?;
+        let buffer = self.start_extra_capture(&device, "mix", format)?;
+        info!("Mixing source {} (gain {})", device.name, source.gain);
+        Ok(buffer)
     }
 
-    /// Pause a specific renderer
-    pub fn pause_renderer(&self, device_id: &str) -> Result<()> {
-        let controls = self.renderer_controls.lock();
-        if let Some(control) = controls.get(device_id) {
-            control.paused.store(true, Ordering::SeqCst);
-            debug!("Paused renderer: {}", device_id);
-            Ok(())
-        } else {
-            Err(WemuxError::DeviceNotFound(device_id.to_string()))
+    /// Start capturing the fallback source into its own buffer
+    ///
+    /// The fallback may be an output or, e.g. a microphone, an input.
+    fn start_fallback_source(
+        &mut self,
+        source: &FallbackSource,
+        format: &AudioFormat,
+    ) -> Result<Arc<RingBuffer>> {
+        let device = self.resolve_output_or_input(&source.device)?;
+        let buffer = self.start_extra_capture(&device, "fallback", format)?;
+        info!(
+            "Falling back to {} after {}s of silence",
+            device.name, source.after_secs
+        );
+        Ok(buffer)
+    }
+
+    /// Start capturing the announcement source into its own buffer
+    fn start_announcement_source(
+        &mut self,
+        source: &Announcement,
+        format: &AudioFormat,
+    ) -> Result<Arc<RingBuffer>> {
+        let device = self.resolve_output_or_input(&source.device)?;
+        let buffer = self.start_extra_capture(&device, "announcements", format)?;
+        info!(
+            "Playing announcements from {}, ducking the source by {}dB",
+            device.name, source.duck_db
+        );
+        Ok(buffer)
+    }
+
+    /// Find an extra source among the outputs, then among the inputs
+    fn resolve_output_or_input(&self, selector: &str) -> Result<DeviceInfo> {
+        match self.resolve_device(selector) {
+            Err(WemuxError::DeviceNotFound(_)) => {
+                let inputs = self.backend.input_devices()?;
+                Ok(find_device(selector, &inputs, &self.config.aliases)?.clone())
+            }
+            result => result,
         }
     }
 
-    /// Resume a specific renderer
+    /// Capture `device` on a thread of its own into a new buffer, labelled
+    /// `role` in the thread health
+    fn start_extra_capture(
+        &mut self,
+        device: &DeviceInfo,
+        role: &str,
+        format: &AudioFormat,
+    ) -> Result<Arc<RingBuffer>> {
+        // The source is converted to the format of the main capture
+        let source_format = self
+            .backend
+            .open_capture(Some(&device.id))?
+            .format()
+            .clone();
+        if !can_convert(&source_format, format) {
+            return Err(WemuxError::FormatMismatch {
+                expected: format.to_string(),
+                actual: source_format.to_string(),
+            });
+        }
+
+        let buffer = Arc::new(RingBuffer::new(format.buffer_size_for_ms(MIX_BUFFER_MS)));
+        let source_backend = self.backend.clone();
+        let source_id = device.id.clone();
+        let source_buffer = buffer.clone();
+        let source_format = format.clone();
+        let source_stop = self.stop_flag.clone();
+        let heartbeat = Arc::new(Heartbeat::default());
+        self.thread_heartbeats
+            .push((format!("{}: {}", role, device.name), heartbeat.clone()));
+
+        self.mix_handles.push(thread::spawn(move || {
+            mix_source_thread(
+                source_backend,
+                source_id,
+                source_format,
+                source_buffer,
+                source_stop,
+                heartbeat,
+            );
+        }));
+        Ok(buffer)
+    }
+
+    /// Devices aliases are resolved against (empty when no aliases are configured)
+    fn alias_candidates(&self) -> Vec<DeviceInfo> {
+        if self.config.aliases.is_empty() {
+            return Vec::new();
+        }
+        self.backend.output_devices().unwrap_or_default()
+    }
+
+    /// Check a device against a selector from `device_ids`/`exclude_ids`
+    ///
+    /// Alias names select exactly the device they resolve to; anything else
+    /// matches as part of the device ID or name, ignoring case.
+    fn matches_selector(&self, device: &DeviceInfo, selector: &str, known: &[DeviceInfo]) -> bool {
+        match self.config.aliases.get(selector) {
+            Some(alias) => alias
+                .resolve(known)
+                .is_some_and(|resolved| resolved.id == device.id),
+            None => MatchRank::of(selector, &device.id, &device.name, None).is_some(),
+        }
+    }
+
+    /// Outputs to start paused because another output reaches the same sink,
+    /// mapped to the ID of that output
+    ///
+    /// The default output, which Windows plays itself, is kept over its
+    /// duplicates, then the first output not disabled in the settings.
+    fn duplicate_outputs(
+        &self,
+        devices: &[DeviceInfo],
+        default_id: Option<&str>,
+        known: &[DeviceInfo],
+    ) -> HashMap<String, String> {
+        let is_default = |device: &DeviceInfo| default_id == Some(device.id.as_str());
+        let mut candidates: Vec<&DeviceInfo> = devices
+            .iter()
+            .filter(|d| is_default(d) || !self.should_device_start_paused(&d.id))
+            .collect();
+        candidates.sort_by_key(|d| !is_default(d));
+
+        find_duplicates(candidates)
+            .into_iter()
+            .filter(|(duplicate, _)| {
+                !self
+                    .config
+                    .allow_duplicates
+                    .iter()
+                    .any(|allowed| self.matches_selector(duplicate, allowed, known))
+            })
+            .map(|(duplicate, first)| (duplicate.id.clone(), first.id.clone()))
+            .collect()
+    }
+
+    /// Check whether `config` only changes what [`reconfigure`](Self::reconfigure)
+    /// applies while running
+    fn differs_in_outputs_only(&self, config: &EngineConfig) -> bool {
+        let current = &self.config;
+        let outputs_kept = EngineConfig {
+            device_ids: current.device_ids.clone(),
+            exclude_ids: current.exclude_ids.clone(),
+            paused_device_ids: current.paused_device_ids.clone(),
+            use_all_devices: current.use_all_devices,
+            aliases: current.aliases.clone(),
+            equalizers: current.equalizers.clone(),
+            delays: current.delays.clone(),
+            dsp: current.dsp.clone(),
+            bluetooth_latency_ms: current.bluetooth_latency_ms,
+            suppress_duplicates: current.suppress_duplicates,
+            allow_duplicates: current.allow_duplicates.clone(),
+            volume_mode: current.volume_mode,
+            mirror_volume: current.mirror_volume,
+            ..config.clone()
+        };
+        outputs_kept == *current
+    }
+
+    /// Bring the running renderers in line with `target_devices` and the config
+    ///
+    /// Returns false without changing anything if that needs a restart: no
+    /// outputs are left, the clock master is deselected or the delay or
+    /// processing stages of a running output change.
+    fn update_outputs(
+        &mut self,
+        context: &Arc<RenderContext<B>>,
+        target_devices: &[DeviceInfo],
+        known_devices: &[DeviceInfo],
+    ) -> bool {
+        let default_id = self.current_default_id.lock().clone();
+        let is_default = |id: &str| default_id.as_deref() == Some(id);
+        let bluetooth_playing = target_devices.iter().any(|device| {
+            device.kind() == DeviceKind::Bluetooth
+                && !is_default(&device.id)
+                && !self.is_capture_source(&device.id)
+                && !self.should_device_start_paused(&device.id)
+        });
+        self.device_aliases = self.config.aliases.assignments(known_devices);
+
+        let removed: Vec<String> = self
+            .renderer_controls
+            .lock()
+            .keys()
+            .filter(|id| !target_devices.iter().any(|d| &d.id == *id))
+            .cloned()
+            .collect();
+        let master_removed = {
+            let clock_sync = context.clock_sync.lock();
+            removed.iter().any(|id| clock_sync.is_master(id))
+        };
+        let delay_changed = {
+            let controls = self.renderer_controls.lock();
+            target_devices.iter().any(|device| {
+                controls.get(&device.id).is_some_and(|c| {
+                    c.delay_ms != self.delay_for(device, bluetooth_playing)
+                        || c.dsp != self.dsp_for(&device.id)
+                })
+            })
+        };
+        if target_devices.is_empty() || master_removed || delay_changed {
+            return false;
+        }
+
+        for device_id in removed {
+            let name = self.device_names.lock().remove(&device_id);
+            info!(
+                "Device {} is no longer selected, stopping it",
+                name.as_deref().unwrap_or(&device_id)
+            );
+            if let Some(control) = self.renderer_controls.lock().remove(&device_id) {
+                control.retired.store(true, Ordering::SeqCst);
+            }
+            context.clock_sync.lock().remove_slave(&device_id);
+            let handle = self.render_handles.lock().remove(&device_id);
+            if let Some(handle) = handle {
+                let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+                join_until(handle, name.as_deref().unwrap_or(&device_id), deadline);
+            }
+        }
+
+        let duplicates = if self.config.suppress_duplicates {
+            self.duplicate_outputs(target_devices, default_id.as_deref(), known_devices)
+        } else {
+            HashMap::new()
+        };
+        for device in target_devices {
+            let duplicate_of = duplicates.get(&device.id).cloned();
+            let paused = is_default(&device.id)
+                || self.is_capture_source(&device.id)
+                || self.should_device_start_paused(&device.id)
+                || duplicate_of.is_some();
+            let eq = self
+                .config
+                .equalizers
+                .get(&device.id)
+                .copied()
+                .unwrap_or_default();
+
+            let was_duplicate = self
+                .renderer_controls
+                .lock()
+                .get(&device.id)
+                .is_some_and(|c| c.duplicate_of.is_some());
+            if let Some(other) = duplicate_of.as_ref().filter(|_| !was_duplicate) {
+                info!(
+                    "Device {} reaches the same sink as {}, pausing it",
+                    device.name, other
+                );
+                if let Some(tx) = &self.event_tx {
+                    let _ = tx.send(EngineEvent::DuplicateSuppressed {
+                        device_id: device.id.clone(),
+                        duplicate_of: other.clone(),
+                    });
+                }
+            }
+
+            if let Some(control) = self.renderer_controls.lock().get_mut(&device.id) {
+                control.paused.store(paused, Ordering::SeqCst);
+                control.duplicate_of = duplicate_of;
+                let mut eq_current = control.eq_current.lock();
+                if *eq_current != eq {
+                    *eq_current = eq;
+                    let _ = control.eq_tx.send(eq);
+                }
+                continue;
+            }
+
+            match context.open_output(&device.id) {
+                Ok(output) => {
+                    info!("Device {} is now selected, starting it", device.name);
+                    if let RenderOutput::Open(_) = output {
+                        context.clock_sync.lock().register_slave(&device.id);
+                    }
+                    let delay_ms = self.delay_for(device, bluetooth_playing);
+                    self.spawn_renderer(context, output, device, paused, delay_ms, duplicate_of);
+                }
+                Err(e) => warn!("Failed to open {}: {}", device.name, e),
+            }
+        }
+        true
+    }
+
+    /// Start a render thread for a device and track its control
+    fn spawn_renderer(
+        &self,
+        context: &Arc<RenderContext<B>>,
+        output: RenderOutput<B>,
+        device: &DeviceInfo,
+        paused: bool,
+        delay_ms: u32,
+        duplicate_of: Option<String>,
+    ) {
+        let (eq_tx, eq_rx) = unbounded();
+        let eq = self
+            .config
+            .equalizers
+            .get(&device.id)
+            .copied()
+            .unwrap_or_default();
+        let _ = eq_tx.send(eq);
+        let control = RendererControl {
+            paused: Arc::new(AtomicBool::new(paused)),
+            all_paused: self.all_paused.clone(),
+            stats: Arc::new(RendererCounters::default()),
+            eq_tx,
+            eq_rx,
+            eq_current: Arc::new(Mutex::new(eq)),
+            delay_ms,
+            dsp: self.dsp_for(&device.id),
+            core: self.render_core(&device.id),
+            retired: Arc::new(AtomicBool::new(false)),
+            duplicate_of,
+        };
+        self.renderer_controls
+            .lock()
+            .insert(device.id.clone(), control.clone());
+
+        // Store device name for external control
+        self.device_names
+            .lock()
+            .insert(device.id.clone(), device.name.clone());
+
+        let handle = match output {
+            RenderOutput::Open(renderer) => context.spawn(renderer, control),
+            RenderOutput::Inactive(state) => {
+                control.stats.set_device_state(state);
+                context.spawn_waiting(&device.id, control)
+            }
+        };
+        self.render_handles.lock().insert(device.id.clone(), handle);
+    }
+
+    /// Check if a device is the output captured in place of the default one
+    fn is_capture_source(&self, device_id: &str) -> bool {
+        self.current_source_id.as_deref() == Some(device_id)
+    }
+
+    /// Check if a device should start paused based on config
+    fn should_device_start_paused(&self, device_id: &str) -> bool {
+        if let Some(paused_ids) = &self.config.paused_device_ids {
+            paused_ids.iter().any(|id| id == device_id)
+        } else {
+            false
+        }
+    }
+
+    /// Check if engine is running
+    pub fn is_running(&self) -> bool {
+        *self.state.lock() == EngineState::Running
+    }
+
+    /// Get status of all active renderers
+    pub fn get_device_statuses(&self) -> Vec<DeviceStatus> {
+        if !self.zones.is_empty() {
+            return self
+                .zones
+                .iter()
+                .flat_map(|zone| zone.engine.get_device_statuses())
+                .collect();
+        }
+
+        let controls = self.renderer_controls.lock();
+        let names = self.device_names.lock();
+        let current_default = self.current_default_id.lock();
+
+        controls
+            .iter()
+            .map(|(id, control)| {
+                let is_system_default = current_default.as_ref().map(|d| d == id).unwrap_or(false);
+                DeviceStatus {
+                    id: id.clone(),
+                    name: names.get(id).cloned().unwrap_or_else(|| id.clone()),
+                    is_enabled: true, // In active renderers = enabled
+                    is_paused: control.paused.load(Ordering::Relaxed),
+                    is_system_default,
+                    is_capture_source: self.is_capture_source(id),
+                    alias: self.device_aliases.get(id).cloned(),
+                    latency: control.stats.latency(),
+                    duplicate_of: control.duplicate_of.clone(),
+                    receiving_audio: control.stats.wrote_within(AUDIO_ACTIVITY_WINDOW),
+                    end_to_end_ms: control
+                        .stats
+                        .end_to_end()
+                        .map(|latency| latency.as_millis() as u32),
+                    inactive: Some(control.stats.device_state()).filter(|s| !s.is_active()),
+                }
+            })
+            .collect()
+    }
+
+    /// Get live statistics of the engine and all active renderers
+    pub fn get_stats(&self) -> EngineStats {
+        if !self.zones.is_empty() {
+            return self.zone_stats();
+        }
+
+        let Some(format) = &self.format else {
+            return EngineStats::default();
+        };
+        // Sync state comes from what the render threads publish: locking the
+        // clock sync here could stall them behind a lower-priority caller
+        let controls = self.renderer_controls.lock();
+        let names = self.device_names.lock();
+        let bytes_per_ms = format.bytes_per_second() as f64 / 1000.0;
+
+        let mut renderers: Vec<RendererStats> = controls
+            .iter()
+            .map(|(id, control)| RendererStats {
+                id: id.clone(),
+                name: names.get(id).cloned().unwrap_or_else(|| id.clone()),
+                alias: self.device_aliases.get(id).cloned(),
+                state: control.stats.state(),
+                is_master: control.stats.is_master(),
+                drift_ms: control.stats.drift_ms(),
+                buffered_ms: control.stats.buffered() as f64 / bytes_per_ms,
+                underruns: control.stats.underruns(),
+                extra_buffer_ms: control.stats.extra_buffer_ms(),
+                frames_written: control.stats.frames(),
+                longest_pass_ms: control.stats.longest_pass().as_secs_f64() * 1000.0,
+                heartbeat_age_ms: control.stats.heartbeat().age().as_millis() as u64,
+                stalled: control.stats.state() != ThreadState::Stopped
+                    && control.stats.heartbeat().age() > THREAD_STALL_TIMEOUT,
+                end_to_end_ms: control
+                    .stats
+                    .end_to_end()
+                    .map(|latency| latency.as_secs_f64() * 1000.0),
+            })
+            .collect();
+        renderers.sort_by(|a, b| a.id.cmp(&b.id));
+
+        EngineStats {
+            running: self.is_running(),
+            volume: self.volume_level.get(),
+            muted: self.volume_level.is_muted(),
+            volume_mode: self.volume_level.mode(),
+            mirror_volume: self.volume_level.mirror(),
+            ring_buffer_ms: self.ring_buffer_ms,
+            restarts: ENGINE_STARTS.load(Ordering::Relaxed).saturating_sub(1),
+            renderers,
+            threads: self
+                .thread_heartbeats
+                .iter()
+                .map(|(name, heartbeat)| ThreadHealth::of(name, heartbeat))
+                .collect(),
+            level: self.level.level(),
+        }
+    }
+
+    /// Peak and RMS level of the audio passed to the outputs, the loudest
+    /// zone's with zones
+    pub fn signal_level(&self) -> SignalLevel {
+        self.zones
+            .iter()
+            .map(|zone| zone.engine.signal_level())
+            .fold(self.level.level(), SignalLevel::max)
+    }
+
+    /// Statistics of all zones together
+    fn zone_stats(&self) -> EngineStats {
+        let mut stats = EngineStats {
+            running: self.is_running(),
+            muted: self.is_muted(),
+            volume_mode: self.volume_mode(),
+            mirror_volume: self.mirror_volume(),
+            restarts: ENGINE_STARTS.load(Ordering::Relaxed).saturating_sub(1),
+            ..EngineStats::default()
+        };
+        for zone in &self.zones {
+            let zone_stats = zone.engine.get_stats();
+            stats.volume = stats.volume.max(zone_stats.volume);
+            stats.ring_buffer_ms = stats.ring_buffer_ms.max(zone_stats.ring_buffer_ms);
+            stats.level = stats.level.max(zone_stats.level);
+            stats.renderers.extend(zone_stats.renderers);
+            stats
+                .threads
+                .extend(zone_stats.threads.into_iter().map(|thread| ThreadHealth {
+                    name: format!("{}/{}", zone.name, thread.name),
+                    ..thread
+                }));
+        }
+        stats.renderers.sort_by(|a, b| a.id.cmp(&b.id));
+        stats
+    }
+
+    /// Mute or unmute all renderers; they keep running and stay in sync
+    pub fn set_muted(&self, muted: bool) {
+        for zone in &self.zones {
+            zone.engine.set_muted(muted);
+        }
+        self.volume_level.set_muted(muted);
+        info!("Output {}", if muted { "muted" } else { "unmuted" });
+    }
+
+    /// Whether all renderers are muted
+    pub fn is_muted(&self) -> bool {
+        self.volume_level.is_muted()
+    }
+
+    /// Pause all renderers: capture and every thread keep running, the
+    /// outputs fade out and play silence until [`AudioEngine::resume_all`]
+    ///
+    /// Unlike muting, the renderers skip their processing while paused and
+    /// resume at the newest audio; unlike stopping, the devices stay open.
+    pub fn pause_all(&self) {
+        self.all_paused.store(true, Ordering::SeqCst);
+        info!("All outputs paused");
+    }
+
+    /// Resume the renderers paused by [`AudioEngine::pause_all`]; outputs
+    /// paused on their own stay paused
+    pub fn resume_all(&self) {
+        self.all_paused.store(false, Ordering::SeqCst);
+        info!("All outputs resumed");
+    }
+
+    /// Whether all renderers are paused by [`AudioEngine::pause_all`]
+    pub fn is_paused_all(&self) -> bool {
+        self.all_paused.load(Ordering::SeqCst)
+    }
+
+    /// Set the volume of all renderers (0.0 - 1.0) for
+    /// [`VolumeMode::Fixed`] and [`VolumeMode::Scaled`]; the source's
+    /// volume is left alone
+    pub fn set_mirror_volume(&self, volume: f32) {
+        for zone in &self.zones {
+            zone.engine.set_mirror_volume(volume);
+        }
+        self.volume_level.set_mirror(volume);
+        info!("Mirror volume set to {:.0}%", self.mirror_volume() * 100.0);
+    }
+
+    /// Volume of the renderers for [`VolumeMode::Fixed`] and [`VolumeMode::Scaled`]
+    pub fn mirror_volume(&self) -> f32 {
+        self.volume_level.mirror()
+    }
+
+    /// Choose how the volume of all renderers is set
+    pub fn set_volume_mode(&self, mode: VolumeMode) {
+        for zone in &self.zones {
+            zone.engine.set_volume_mode(mode);
+        }
+        self.volume_level.set_mode(mode);
+        info!("Volume mode set to {:?}", mode);
+    }
+
+    pub fn volume_mode(&self) -> VolumeMode {
+        self.volume_level.mode()
+    }
+
+    /// Pause a specific renderer
+    pub fn pause_renderer(&self, device_id: &str) -> Result<()> {
+        if let Some(engine) = self.zone_engine(device_id) {
+            return engine.pause_renderer(device_id);
+        }
+        let controls = self.renderer_controls.lock();
+        if let Some(control) = controls.get(device_id) {
+            control.paused.store(true, Ordering::SeqCst);
+            debug!("Paused renderer: {}", device_id);
+            Ok(())
+        } else {
+            Err(WemuxError::DeviceNotFound(device_id.to_string()))
+        }
+    }
+
+    /// Resume a specific renderer
     pub fn resume_renderer(&self, device_id: &str) -> Result<()> {
+        if let Some(engine) = self.zone_engine(device_id) {
+            return engine.resume_renderer(device_id);
+        }
         let controls = self.renderer_controls.lock();
         if let Some(control) = controls.get(device_id) {
             control.paused.store(false, Ordering::SeqCst);
@@ -516,8 +2089,107 @@ impl AudioEngine {
         }
     }
 
+    /// Change a renderer's equalizer while it runs (flat settings turn it off)
+    ///
+    /// Lasts until the engine restarts; [`EngineConfig::equalizers`] sets the
+    /// equalizers it starts with.
+    pub fn set_equalizer(&self, device_id: &str, settings: EqSettings) -> Result<()> {
+        if let Some(engine) = self.zone_engine(device_id) {
+            return engine.set_equalizer(device_id, settings);
+        }
+        let controls = self.renderer_controls.lock();
+        if let Some(control) = controls.get(device_id) {
+            *control.eq_current.lock() = settings;
+            let _ = control.eq_tx.send(settings);
+            debug!(
+                "Equalizer of {} set to {:?} dB",
+                device_id, settings.gains_db
+            );
+            Ok(())
+        } else {
+            Err(WemuxError::DeviceNotFound(device_id.to_string()))
+        }
+    }
+
+    /// Delay of a device: configured by ID or alias, else compensation for
+    /// Bluetooth outputs playing alongside it
+    fn delay_for(&self, device: &DeviceInfo, bluetooth_playing: bool) -> u32 {
+        self.delay_with(&self.device_aliases, device, bluetooth_playing)
+    }
+
+    /// [`delay_for`](Self::delay_for) with aliases keyed by device ID
+    fn delay_with(
+        &self,
+        aliases: &HashMap<String, String>,
+        device: &DeviceInfo,
+        bluetooth_playing: bool,
+    ) -> u32 {
+        let configured = self.config.delays.get(&device.id).or_else(|| {
+            let alias = aliases.get(&device.id)?;
+            self.config.delays.get(alias)
+        });
+        match configured {
+            Some(&delay) => delay,
+            None if bluetooth_playing && device.kind() != DeviceKind::Bluetooth => {
+                self.config.bluetooth_latency_ms
+            }
+            None => 0,
+        }
+    }
+
+    /// Processing stages configured for a device, by ID or alias
+    fn dsp_for(&self, device_id: &str) -> Vec<DspStageConfig> {
+        let dsp = &self.config.dsp;
+        dsp.get(device_id)
+            .or_else(|| {
+                let alias = self.device_aliases.get(device_id)?;
+                dsp.get(alias)
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Check `buffer_ms` and every device's own buffer against the accepted range
+    fn validate_buffers(&self, caps: &HardwareCapabilities) -> Result<()> {
+        BufferPlan::validate(self.config.buffer_ms, caps)?;
+        for (device, &buffer_ms) in &self.config.device_buffer_ms {
+            BufferPlan::validate(buffer_ms, caps).map_err(|e| match e {
+                WemuxError::InvalidConfig(reason) => {
+                    WemuxError::InvalidConfig(format!("{}: {}", device, reason))
+                }
+                e => e,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// `device_buffer_ms` by device ID, with aliases resolved; an entry for
+    /// the ID wins over one for its alias
+    fn device_buffers(&self) -> HashMap<String, u32> {
+        let buffers = &self.config.device_buffer_ms;
+        let mut by_id = buffers.clone();
+        for (id, alias) in &self.device_aliases {
+            if let Some(&buffer_ms) = buffers.get(alias) {
+                by_id.entry(id.clone()).or_insert(buffer_ms);
+            }
+        }
+        by_id
+    }
+
+    /// Core configured for a device's render thread, by ID or alias
+    fn render_core(&self, device_id: &str) -> Option<usize> {
+        let cores = &self.config.render_cores;
+        cores.get(device_id).copied().or_else(|| {
+            let alias = self.device_aliases.get(device_id)?;
+            cores.get(alias).copied()
+        })
+    }
+
     /// Check if a device is the current default output
     pub fn is_device_default(&self, device_id: &str) -> bool {
+        if let Some(engine) = self.zone_engine(device_id) {
+            return engine.is_device_default(device_id);
+        }
         self.current_default_id
             .lock()
             .as_ref()
@@ -526,97 +2198,551 @@ impl AudioEngine {
     }
 }
 
-impl Drop for AudioEngine {
+impl<B: AudioBackend> Drop for AudioEngine<B> {
     fn drop(&mut self) {
         let _ = self.stop();
     }
 }
 
-/// Capture thread function
-fn capture_thread(
+/// Notify external listeners that the engine can no longer recover by itself
+fn report_fatal(event_tx: &Option<Sender<EngineEvent>>, reason: String) {
+    error!("Fatal engine error: {}", reason);
+    if let Some(tx) = event_tx {
+        let _ = tx.send(EngineEvent::Fatal(reason));
+    }
+}
+
+/// Notify external listeners without ever blocking the audio threads
+///
+/// An event a listener is too slow to take is dropped.
+fn notify(event_tx: &Option<Sender<EngineEvent>>, event: EngineEvent) {
+    if let Some(tx) = event_tx {
+        let _ = tx.try_send(event);
+    }
+}
+
+/// Where the capture thread puts what it captured
+struct CaptureOutput {
     buffer: Arc<RingBuffer>,
+    /// Format the audio is passed on in
+    format: AudioFormat,
+    /// Extra sources added before the audio reaches the ring buffer
+    mixer: Mixer,
+    /// Source that replaces the captured audio while it is silent
+    fallback: Option<SourceSwitch>,
+    /// Source played over the captured audio, ducking it
+    announcement: Option<Ducker>,
+    /// Beaten on every read so the watchdog can tell a stuck capture
+    heartbeat: Arc<Heartbeat>,
+    /// When the newest audio passed on was captured
+    capture_clock: Arc<CaptureClock>,
+    /// Fades the outputs while the capture is reopened
+    transition: Arc<Transition>,
+    /// Measures the level of what is passed on, None for other than float audio
+    meter: Option<LevelMeter>,
+}
+
+/// What the capture thread records
+enum CaptureTarget {
+    /// Loopback of the default output, reopened when the default changes
+    DefaultOutput,
+    /// A fixed output (loopback) or input device
+    Device(String),
+    /// Loopback of the apps playing on an output (None = the default output,
+    /// followed like `DefaultOutput`), without the excluded apps
+    Apps(Option<String>, Vec<String>),
+    /// An audio file played in a loop in the given format
+    File(PathBuf, AudioFormat),
+}
+
+/// Capture of a [`CaptureTarget`]
+enum TargetCapture<C> {
+    Device(C),
+    File(FilePlayer),
+}
+
+impl<C: CaptureBackend> CaptureBackend for TargetCapture<C> {
+    fn format(&self) -> &AudioFormat {
+        match self {
+            TargetCapture::Device(capture) => capture.format(),
+            TargetCapture::File(player) => player.format(),
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        match self {
+            TargetCapture::Device(capture) => capture.start(),
+            TargetCapture::File(player) => player.start(),
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        match self {
+            TargetCapture::Device(capture) => capture.stop(),
+            TargetCapture::File(player) => player.stop(),
+        }
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        match self {
+            TargetCapture::Device(capture) => capture.read_into(dest, timeout_ms),
+            TargetCapture::File(player) => player.read_into(dest, timeout_ms),
+        }
+    }
+
+    fn capture_delay(&self) -> Duration {
+        match self {
+            TargetCapture::Device(capture) => capture.capture_delay(),
+            TargetCapture::File(player) => player.capture_delay(),
+        }
+    }
+}
+
+/// Open a capture of `target`
+fn open_capture_target<B: AudioBackend>(
+    backend: &B,
+    target: &CaptureTarget,
+) -> Result<TargetCapture<B::Capture>> {
+    match target {
+        CaptureTarget::DefaultOutput => backend.open_capture(None).map(TargetCapture::Device),
+        CaptureTarget::Device(id) => backend.open_capture(Some(id)).map(TargetCapture::Device),
+        CaptureTarget::Apps(id, exclude_apps) => backend
+            .open_app_capture(id.as_deref(), exclude_apps)
+            .map(TargetCapture::Device),
+        CaptureTarget::File(path, format) => {
+            FilePlayer::open(path, format).map(TargetCapture::File)
+        }
+    }
+}
+
+/// Capture thread function
+///
+/// A fixed device or a file stays the source; the default output is
+/// followed and its capture reopened when the default changes. Every
+/// capture is converted to the format of `output`, so a new default output
+/// mixing at another rate doesn't change what the renderers get.
+fn capture_thread<B: AudioBackend>(
+    backend: Arc<B>,
+    target: CaptureTarget,
+    output: CaptureOutput,
     stop_flag: Arc<AtomicBool>,
     command_rx: Receiver<CaptureCommand>,
+    event_tx: Option<Sender<EngineEvent>>,
+    performance: bool,
 ) {
     info!("Capture thread started");
+    // Kept for the life of the thread
+    let _performance_mode = performance.then(PerformanceMode::enter);
+    // Polling never blocks in performance mode
+    let wait_ms = if performance { 0 } else { 100 };
+    let CaptureOutput {
+        buffer,
+        format,
+        mut mixer,
+        mut fallback,
+        mut announcement,
+        heartbeat,
+        capture_clock,
+        transition,
+        mut meter,
+    } = output;
+    let mut pass_on = |data: &[u8]| {
+        if let Some(meter) = &mut meter {
+            meter.measure(data);
+        }
+        buffer.write(data);
+    };
 
-    let mut capture = match LoopbackCapture::from_default_device() {
-        Ok(c) => c,
+    // Failures are reported here; the watchdog only looks for a capture that
+    // stopped without saying so
+    let mut capture = match open_capture_target(&*backend, &target) {
+        Ok(c) => ConvertedCapture::new(c, &format),
         Err(e) => {
-            error!("Failed to create capture: {}", e);
+            report_fatal(&event_tx, format!("Failed to create capture: {}", e));
+            heartbeat.finish();
             return;
         }
     };
 
     if let Err(e) = capture.start() {
-        error!("Failed to start capture: {}", e);
+        report_fatal(&event_tx, format!("Failed to start capture: {}", e));
+        heartbeat.finish();
         return;
     }
 
+    let (follows_default, exclude_apps) = match &target {
+        CaptureTarget::DefaultOutput => (true, Vec::new()),
+        CaptureTarget::Apps(None, exclude_apps) => (true, exclude_apps.clone()),
+        _ => (false, Vec::new()),
+    };
     let mut temp_buffer = vec![0u8; 4096];
+    let mut consecutive_errors = 0u32;
+    // Capture of the new default output being opened while the old one plays
+    let mut preopen: Option<Receiver<Result<B::Capture>>> = None;
+    let mut pending_reinit = false;
+    let mut reinit_failures = 0u32;
+    // Whether a failure was reported that hasn't ended yet
+    let mut capture_failing = false;
+    // When the main source last sent audio
+    let mut last_packet = Instant::now();
 
     while !stop_flag.load(Ordering::Relaxed) {
+        heartbeat.beat();
+
         // Check for commands (non-blocking)
         if let Ok(cmd) = command_rx.try_recv() {
             match cmd {
+                CaptureCommand::Reinitialize if !follows_default => {
+                    debug!("Capturing a fixed source, ignoring default device change");
+                }
                 CaptureCommand::Reinitialize => {
-                    info!("Reinitializing capture for new default device...");
-                    let _ = capture.stop();
+                    // Replaces a capture still opening for an earlier default
+                    info!("Opening capture of the new default device...");
+                    preopen = Some(preopen_capture(backend.clone(), exclude_apps.clone()));
+                }
+            }
+        }
 
-                    // Small delay to let Windows settle
-                    thread::sleep(Duration::from_millis(100));
-
-                    match LoopbackCapture::from_default_device() {
-                        Ok(new_capture) => {
-                            capture = new_capture;
-                            if let Err(e) = capture.start() {
-                                error!("Failed to start new capture: {}", e);
-                                // Try to recover by sleeping and retrying
-                                thread::sleep(Duration::from_millis(500));
-                                continue;
+        if let Some(opening) = &preopen {
+            match opening.try_recv() {
+                Ok(Ok(new_capture)) => {
+                    preopen = None;
+                    // Pass on what the old device still holds, then switch
+                    // over without stopping the audio
+                    loop {
+                        match capture.read_into(&mut temp_buffer, 0) {
+                            Ok(bytes) if bytes > 0 => {
+                                capture_clock.record(capture.capture_delay());
+                                mixer.mix_into(&mut temp_buffer[..bytes]);
+                                announce(&mut announcement, &mut temp_buffer[..bytes], &event_tx);
+                                pass_on(&temp_buffer[..bytes]);
                             }
-                            info!("Capture reinitialized successfully");
-                        }
-                        Err(e) => {
-                            error!("Failed to reinitialize capture: {}", e);
-                            // Try to recover by recreating with old device
-                            thread::sleep(Duration::from_millis(500));
-                            continue;
+                            _ => break,
                         }
                     }
+                    let _ = capture.stop();
+                    capture = ConvertedCapture::new(TargetCapture::Device(new_capture), &format);
+                    pending_reinit = false;
+                    consecutive_errors = 0;
+                    info!("Capture switched to the new default device");
+                }
+                Ok(Err(e)) => {
+                    warn!("Failed to open capture of the new default device: {}", e);
+                    preopen = None;
+                    pending_reinit = true;
+                    reinit_failures = 0;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    preopen = None;
+                    pending_reinit = true;
+                    reinit_failures = 0;
                 }
             }
         }
 
-        match capture.read_frames(100) {
-            Ok(frames) => {
-                if !frames.is_empty() {
-                    let bytes = frames.copy_to(&mut temp_buffer);
-                    buffer.write(&temp_buffer[..bytes]);
+        // Reopening in place, with the outputs faded out meanwhile, is the
+        // fallback when opening alongside the old capture failed
+        if pending_reinit {
+            info!("Reinitializing capture for new default device...");
+            // Mark where the old capture's audio ends and pass it on up to
+            // there, so the outputs fade out over it and in over the new
+            // capture's audio
+            if reinit_failures == 0 {
+                let block = format.block_align as usize;
+                let fade = format.buffer_size_for_ms(FADE_DURATION.as_millis() as u32);
+                let cut = buffer.write_position().wrapping_add(fade / block * block);
+                transition.cut_at(cut);
+                let deadline = Instant::now() + FADE_DURATION * 2;
+                let room = temp_buffer.len() / block * block;
+                loop {
+                    let left = cut.wrapping_sub(buffer.write_position()).min(room);
+                    if left == 0 {
+                        break;
+                    }
+                    let bytes = match capture.read_into(&mut temp_buffer, FALLBACK_POLL_MS) {
+                        Ok(bytes) if bytes > 0 => bytes.min(left),
+                        Ok(_) if Instant::now() < deadline => continue,
+                        // Silence makes up for audio the old capture no
+                        // longer delivers
+                        _ => {
+                            temp_buffer[..left].fill(0);
+                            left
+                        }
+                    };
+                    mixer.mix_into(&mut temp_buffer[..bytes]);
+                    announce(&mut announcement, &mut temp_buffer[..bytes], &event_tx);
+                    pass_on(&temp_buffer[..bytes]);
                 }
             }
-            Err(e) => {
-                warn!("Capture error: {}", e);
-                // Brief pause before retry
-                thread::sleep(Duration::from_millis(10));
-            }
-        }
-    }
+            let _ = capture.stop();
 
-    let _ = capture.stop();
-    info!("Capture thread stopped");
-}
+            // Small delay to let Windows settle
+            thread::sleep(Duration::from_millis(100));
 
-/// Volume tracking thread function
-fn volume_tracking_thread(
-    volume_level: Arc<VolumeLevel>,
+            let new_capture = open_default_capture(&*backend, &exclude_apps).and_then(|c| {
+                let mut c = ConvertedCapture::new(TargetCapture::Device(c), &format);
+                c.start()?;
+                Ok(c)
+            });
+
+            match new_capture {
+                Ok(new_capture) => {
+                    capture = new_capture;
+                    pending_reinit = false;
+                    consecutive_errors = 0;
+                    info!("Capture reinitialized successfully");
+                }
+                Err(e) => {
+                    reinit_failures += 1;
+                    if reinit_failures == 1 {
+                        capture_failing = true;
+                        notify(
+                            &event_tx,
+                            EngineEvent::CaptureFailed {
+                                error: e.to_string(),
+                            },
+                        );
+                    }
+                    if reinit_failures >= MAX_REINIT_ATTEMPTS {
+                        report_fatal(
+                            &event_tx,
+                            format!(
+                                "Failed to reinitialize capture after {} attempts: {}",
+                                reinit_failures, e
+                            ),
+                        );
+                        break;
+                    }
+                    error!(
+                        "Failed to reinitialize capture (attempt {}/{}): {}",
+                        reinit_failures, MAX_REINIT_ATTEMPTS, e
+                    );
+                    // Give the audio service time to recover before retrying
+                    thread::sleep(Duration::from_millis(500));
+                    continue;
+                }
+            }
+        }
+
+        // Announcements and mixed-in sources play even while the main source
+        // sends nothing
+        let wait_ms = match &fallback {
+            Some(switch) if switch.is_active() => wait_ms.min(FALLBACK_POLL_MS),
+            _ if announcement.is_some() || mixer.has_inputs() => wait_ms.min(FALLBACK_POLL_MS),
+            _ => wait_ms,
+        };
+        match capture.read_into(&mut temp_buffer, wait_ms) {
+            Ok(bytes) => {
+                consecutive_errors = 0;
+                if capture_failing {
+                    capture_failing = false;
+                    notify(&event_tx, EngineEvent::CaptureWorking);
+                }
+                if let Some(switch) = &mut fallback {
+                    if let Some(active) = switch.update(&temp_buffer[..bytes], Instant::now()) {
+                        if active {
+                            info!("Source silent, the fallback source takes over");
+                        } else {
+                            info!("Source audible again, the fallback source yields");
+                        }
+                        notify(&event_tx, EngineEvent::SourceSwitched { fallback: active });
+                    }
+                    if switch.is_active() {
+                        if bytes > 0 {
+                            switch.replace(&mut temp_buffer[..bytes]);
+                        } else {
+                            // The source sends nothing at all, so pass on
+                            // whatever the fallback has
+                            loop {
+                                let read = switch.read(&mut temp_buffer);
+                                if read == 0 {
+                                    break;
+                                }
+                                capture_clock.record(Duration::ZERO);
+                                mixer.mix_into(&mut temp_buffer[..read]);
+                                announce(&mut announcement, &mut temp_buffer[..read], &event_tx);
+                                pass_on(&temp_buffer[..read]);
+                            }
+                            continue;
+                        }
+                    }
+                }
+                if bytes > 0 {
+                    last_packet = Instant::now();
+                    capture_clock.record(capture.capture_delay());
+                    mixer.mix_into(&mut temp_buffer[..bytes]);
+                    announce(&mut announcement, &mut temp_buffer[..bytes], &event_tx);
+                    pass_on(&temp_buffer[..bytes]);
+                } else if last_packet.elapsed() >= SOURCE_IDLE
+                    && (announcement.is_some() || mixer.has_inputs())
+                {
+                    // The source sends nothing at all, so pass on the
+                    // mixed-in sources and the announcement by themselves
+                    let announced = announcement.as_ref().map_or(0, |ducker| ducker.pending());
+                    let room = temp_buffer.len() - temp_buffer.len() % format.block_align as usize;
+                    let pending = mixer.pending().max(announced).min(room);
+                    if pending > 0 {
+                        capture_clock.record(Duration::ZERO);
+                        temp_buffer[..pending].fill(0);
+                        mixer.mix_into(&mut temp_buffer[..pending]);
+                        announce(&mut announcement, &mut temp_buffer[..pending], &event_tx);
+                        pass_on(&temp_buffer[..pending]);
+                    }
+                } else if performance {
+                    std::hint::spin_loop();
+                }
+            }
+            Err(e) => {
+                consecutive_errors += 1;
+                if consecutive_errors == 1 {
+                    capture_failing = true;
+                    notify(
+                        &event_tx,
+                        EngineEvent::CaptureFailed {
+                            error: e.to_string(),
+                        },
+                    );
+                }
+                if consecutive_errors >= MAX_CAPTURE_ERRORS {
+                    report_fatal(&event_tx, format!("Capture failed repeatedly: {}", e));
+                    break;
+                }
+                warn!("Capture error: {}", e);
+                // Brief pause before retry
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    let _ = capture.stop();
+    heartbeat.finish();
+    info!("Capture thread stopped");
+}
+
+/// Open a capture of the current default output, of all its apps but
+/// `exclude_apps` if there are any
+fn open_default_capture<B: AudioBackend>(
+    backend: &B,
+    exclude_apps: &[String],
+) -> Result<B::Capture> {
+    if exclude_apps.is_empty() {
+        backend.open_capture(None)
+    } else {
+        backend.open_app_capture(None, exclude_apps)
+    }
+}
+
+/// Open and start a capture of the current default output on another thread
+///
+/// Opening takes long enough to be heard as a gap, so the old capture keeps
+/// playing meanwhile. Dropping the receiver discards the capture.
+fn preopen_capture<B: AudioBackend>(
+    backend: Arc<B>,
+    exclude_apps: Vec<String>,
+) -> Receiver<Result<B::Capture>> {
+    let (tx, rx) = bounded(1);
+    thread::spawn(move || {
+        let capture = open_default_capture(&*backend, &exclude_apps).and_then(|mut c| {
+            c.start()?;
+            Ok(c)
+        });
+        let _ = tx.send(capture);
+    });
+    rx
+}
+
+/// Play the announcement over `data` and report when ducking starts or ends
+fn announce(
+    announcement: &mut Option<Ducker>,
+    data: &mut [u8],
+    event_tx: &Option<Sender<EngineEvent>>,
+) {
+    let Some(ducker) = announcement else {
+        return;
+    };
+    if let Some(active) = ducker.process(data, Instant::now()) {
+        if active {
+            info!("Announcement playing, ducking the source");
+        } else {
+            info!("Announcement over, restoring the source");
+        }
+        notify(event_tx, EngineEvent::Ducking { active });
+    }
+}
+
+/// Capture thread of an extra source, mixed into the main capture or
+/// standing in for it
+///
+/// Failures only silence this source; the engine keeps running without it.
+fn mix_source_thread<B: AudioBackend>(
+    backend: Arc<B>,
+    device_id: String,
+    format: AudioFormat,
+    buffer: Arc<RingBuffer>,
+    stop_flag: Arc<AtomicBool>,
+    heartbeat: Arc<Heartbeat>,
+) {
+    info!("Extra source thread started for: {}", device_id);
+
+    let capture = backend.open_capture(Some(&device_id)).and_then(|c| {
+        let mut c = ConvertedCapture::new(c, &format);
+        c.start()?;
+        Ok(c)
+    });
+    let mut capture = match capture {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to start extra source {}: {}", device_id, e);
+            heartbeat.finish();
+            return;
+        }
+    };
+
+    let mut temp_buffer = vec![0u8; 4096];
+    let mut consecutive_errors = 0u32;
+
+    while !stop_flag.load(Ordering::Relaxed) {
+        heartbeat.beat();
+        match capture.read_into(&mut temp_buffer, 100) {
+            Ok(bytes) => {
+                consecutive_errors = 0;
+                buffer.write(&temp_buffer[..bytes]);
+            }
+            Err(e) => {
+                consecutive_errors += 1;
+                if consecutive_errors >= MAX_CAPTURE_ERRORS {
+                    error!(
+                        "Extra source {} failed repeatedly, no longer using it: {}",
+                        device_id, e
+                    );
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    let _ = capture.stop();
+    heartbeat.finish();
+    info!("Extra source thread stopped for: {}", device_id);
+}
+
+/// Volume tracking thread function
+///
+/// Follows the volume of the captured device: the fixed source if set, else
+/// the default output.
+fn volume_tracking_thread<B: AudioBackend>(
+    backend: Arc<B>,
+    source_id: Option<String>,
+    volume_level: Arc<VolumeLevel>,
     stop_flag: Arc<AtomicBool>,
     device_event_rx: Receiver<DeviceEvent>,
 ) {
     info!("Volume tracking thread started");
 
     // Initialize volume tracker
-    let mut tracker = match VolumeTracker::from_default_device() {
+    let mut tracker = match backend.open_volume(source_id.as_deref()) {
         Ok(t) => t,
         Err(e) => {
             error!("Failed to initialize volume tracker: {}", e);
@@ -626,11 +2752,15 @@ fn volume_tracking_thread(
 
     while !stop_flag.load(Ordering::Relaxed) {
         // Check for device change events (non-blocking)
-        if let Ok(DeviceEvent::DefaultChanged { .. }) = device_event_rx.try_recv() {
+        let default_changed = matches!(
+            device_event_rx.try_recv(),
+            Ok(DeviceEvent::DefaultChanged { .. })
+        );
+        if default_changed && source_id.is_none() {
             info!("Reinitializing volume tracker for new default device...");
             // Small delay to let Windows settle
             thread::sleep(Duration::from_millis(100));
-            match VolumeTracker::from_default_device() {
+            match backend.open_volume(None) {
                 Ok(new_tracker) => {
                     tracker = new_tracker;
                     info!("Volume tracker reinitialized successfully");
@@ -641,7 +2771,7 @@ fn volume_tracking_thread(
             }
         }
 
-        let volume = tracker.get_effective_volume();
+        let volume = tracker.effective_volume();
         volume_level.set(volume);
 
         // Poll every 100ms
@@ -651,14 +2781,97 @@ fn volume_tracking_thread(
     info!("Volume tracking thread stopped");
 }
 
+/// Take a removed output out of clock sync, handing the master role on if
+/// it had it
+///
+/// A playing output is preferred as the new master, then the lowest ID, so
+/// the choice doesn't depend on map order. Returns the event to report a
+/// master change.
+fn leave_clock_sync(
+    clock_sync: &Mutex<ClockSync>,
+    renderer_controls: &Mutex<HashMap<String, RendererControl>>,
+    removed: &str,
+) -> Option<EngineEvent> {
+    let controls = renderer_controls.lock();
+    let removed_control = controls.get(removed)?;
+    let mut sync = clock_sync.lock();
+    if !sync.is_master(removed) {
+        sync.remove_slave(removed);
+        return None;
+    }
+
+    let master = controls
+        .iter()
+        .filter(|(id, control)| {
+            id.as_str() != removed
+                && !control.retired.load(Ordering::SeqCst)
+                && control.stats.device_state().is_active()
+        })
+        .min_by_key(|(id, control)| (control.paused.load(Ordering::SeqCst), id.as_str()))
+        .map(|(id, _)| id.clone())
+        .filter(|id| sync.promote(id));
+    removed_control.stats.set_sync(false, 0.0);
+    match &master {
+        Some(id) => {
+            info!("Clock master {} was removed, {} takes over", removed, id);
+            controls[id].stats.set_sync(true, 0.0);
+        }
+        None => warn!(
+            "Clock master {} was removed, no output left to take over",
+            removed
+        ),
+    }
+    Some(EngineEvent::MasterChanged {
+        removed: removed.to_string(),
+        master,
+    })
+}
+
+/// Record a new state of an output's device, taking the output out of clock
+/// sync while Windows reports the device disabled or unplugged
+///
+/// The output's render thread waits while the device is inactive and opens
+/// it again once it is back. Returns the event to report a master change.
+fn track_device_state(
+    clock_sync: &Mutex<ClockSync>,
+    renderer_controls: &Mutex<HashMap<String, RendererControl>>,
+    device_id: &str,
+    state: DeviceState,
+) -> Option<EngineEvent> {
+    let was_active = {
+        let controls = renderer_controls.lock();
+        let stats = &controls.get(device_id)?.stats;
+        let was_active = stats.device_state().is_active();
+        stats.set_device_state(state);
+        was_active
+    };
+    if state.is_active() {
+        if !was_active {
+            info!("Device {} is back, reopening its output", device_id);
+        }
+        return None;
+    }
+    if !was_active {
+        return None;
+    }
+    info!("Device {} is {}, its output waits for it", device_id, state);
+    leave_clock_sync(clock_sync, renderer_controls, device_id)
+}
+
 /// Device monitor thread function
+///
+/// Default output changes are only followed with `follow_default`, i.e. when
+/// capturing a loopback source.
+#[allow(clippy::too_many_arguments)]
 fn device_monitor_thread(
     event_rx: Receiver<DeviceEvent>,
     renderer_controls: Arc<Mutex<HashMap<String, RendererControl>>>,
+    clock_sync: Arc<Mutex<ClockSync>>,
     capture_cmd_tx: Sender<CaptureCommand>,
     volume_event_tx: Sender<DeviceEvent>,
     stop_flag: Arc<AtomicBool>,
     current_default_id: Arc<Mutex<Option<String>>>,
+    follow_default: bool,
     engine_event_tx: Option<Sender<EngineEvent>>,
 ) {
     info!("Device monitor thread started");
@@ -666,14 +2879,40 @@ fn device_monitor_thread(
     while !stop_flag.load(Ordering::Relaxed) {
         match event_rx.recv_timeout(Duration::from_millis(100)) {
             Ok(event) => {
-                if let DeviceEvent::DefaultChanged {
+                if matches!(
+                    event,
+                    DeviceEvent::Added(_)
+                        | DeviceEvent::Removed(_)
+                        | DeviceEvent::StateChanged { .. }
+                ) {
+                    debug!("Device list changed: {:?}", event);
+                    let master_change = match &event {
+                        DeviceEvent::Removed(device_id) => {
+                            leave_clock_sync(&clock_sync, &renderer_controls, device_id)
+                        }
+                        DeviceEvent::StateChanged {
+                            device_id,
+                            new_state,
+                        } => track_device_state(
+                            &clock_sync,
+                            &renderer_controls,
+                            device_id,
+                            *new_state,
+                        ),
+                        _ => None,
+                    };
+                    if let Some(event) = master_change {
+                        notify(&engine_event_tx, event);
+                    }
+                    notify(&engine_event_tx, EngineEvent::DeviceListChanged);
+                } else if let DeviceEvent::DefaultChanged {
                     data_flow,
                     device_id,
                     ..
                 } = &event
                 {
                     // Only care about render devices (data_flow = 0 = eRender)
-                    if *data_flow == 0 {
+                    if *data_flow == 0 && follow_default {
                         info!("Default render device changed to: {}", device_id);
 
                         // Update current default device ID
@@ -730,52 +2969,323 @@ fn device_monitor_thread(
     info!("Device monitor thread stopped");
 }
 
+/// Renderer that reopens itself with a larger buffer when it keeps underrunning
+struct AdaptiveRenderer<B: AudioBackend> {
+    renderer: B::Renderer,
+    backend: Arc<B>,
+    tuning: BufferTuning,
+    /// Audio moved to the device per write, in milliseconds
+    chunk_ms: u32,
+    underruns: UnderrunTracker,
+    // Shared with the engine so the next start reuses the tuning
+    learned: Arc<Mutex<HashMap<String, BufferTuning>>>,
+    event_tx: Option<Sender<EngineEvent>>,
+}
+
+impl<B: AudioBackend> AdaptiveRenderer<B> {
+    /// Write the pre-fill silence that establishes the latency buffer
+    fn prefill(&mut self) {
+        let format = self.renderer.format();
+        let frames =
+            format.buffer_size_for_ms(self.tuning.prefill_ms) / format.block_align as usize;
+        let _ = self.renderer.write_silence(frames as u32);
+    }
+
+    /// Record an underrun, growing the buffer once they become frequent
+    ///
+    /// Returns true if the renderer was replaced (and needs pre-filling).
+    fn record_underrun(&mut self) -> bool {
+        let device_id = self.renderer.device_id().to_string();
+        debug!(
+            "Renderer {} underrun ({} total)",
+            self.renderer.device_name(),
+            self.underruns.total() + 1
+        );
+
+        if !self.underruns.record() {
+            return false;
+        }
+        let Some(next) = self.tuning.grown() else {
+            return false;
+        };
+
+        // Open the replacement before letting go of the working renderer
+        let _ = self.renderer.stop();
+        let grown = self
+            .backend
+            .open_renderer(&device_id, &next)
+            .and_then(|mut renderer| renderer.start().map(|()| renderer));
+        let grown = match grown {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                warn!("Failed to grow buffer for {}: {}", device_id, e);
+                let _ = self.renderer.start();
+                return false;
+            }
+        };
+
+        self.renderer = grown;
+        self.tuning = next;
+        self.learned.lock().insert(device_id.clone(), next);
+        info!(
+            "Renderer {} keeps underrunning, buffer grown by {}ms with {}ms pre-fill",
+            self.renderer.device_name(),
+            next.extra_buffer_ms,
+            next.prefill_ms
+        );
+
+        if let Some(tx) = &self.event_tx {
+            let _ = tx.send(EngineEvent::BufferTuned {
+                device_id,
+                extra_buffer_ms: next.extra_buffer_ms,
+                prefill_ms: next.prefill_ms,
+            });
+        }
+        true
+    }
+
+    /// Open the device again once it is back from being disabled or
+    /// unplugged, which invalidated the old stream
+    fn reopen(&mut self) -> Result<()> {
+        let device_id = self.renderer.device_id().to_string();
+        let mut renderer = self.backend.open_renderer(&device_id, &self.tuning)?;
+        renderer.start()?;
+        self.renderer = renderer;
+        self.prefill();
+        Ok(())
+    }
+}
+
+/// What a render thread reports to the clock sync after a write
+enum ClockReading {
+    /// Captured frames waiting for the renderer, when following the source
+    Backlog(u64),
+    /// Frames the device played and when
+    Position(DevicePosition),
+}
+
 /// Render thread function
-fn render_thread(
-    mut renderer: HdmiRenderer,
+#[allow(clippy::too_many_arguments)]
+fn render_thread<B: AudioBackend>(
+    mut output: AdaptiveRenderer<B>,
     buffer: Arc<RingBuffer>,
     stop_flag: Arc<AtomicBool>,
-    paused_flag: Arc<AtomicBool>,
+    control: RendererControl,
     clock_sync: Arc<Mutex<ClockSync>>,
     format: AudioFormat,
     volume_level: Arc<VolumeLevel>,
+    transition: Arc<Transition>,
+    pause_fade: Duration,
+    priority: ThreadPriority,
+    capture_clock: Arc<CaptureClock>,
 ) {
-    let device_name = renderer.device_name().to_string();
-    let device_id = renderer.device_id().to_string();
+    let device_name = output.renderer.device_name().to_string();
+    let device_id = output.renderer.device_id().to_string();
+    let stats = control.stats;
+    let retired = control.retired;
     info!("Render thread started for: {}", device_name);
+    prioritize_render_thread(&device_name, priority, control.core);
 
-    if let Err(e) = renderer.start() {
+    if let Err(e) = output.renderer.start() {
         error!("Failed to start renderer {}: {}", device_name, e);
+        notify(
+            &output.event_tx,
+            EngineEvent::RendererFailed {
+                device_id,
+                error: e.to_string(),
+            },
+        );
+        stats.set_state(ThreadState::Stopped);
         return;
     }
 
     // Create reader state for this renderer
     let mut reader = ReaderState::new(&buffer);
-    let mut render_buffer = vec![0u8; format.buffer_size_for_ms(50)];
+    let mut render_buffer = vec![0u8; format.buffer_size_for_ms(output.chunk_ms)];
+    let mut cut_fade = CutFade::new(format.sample_rate);
+    let mut pause_envelope = Envelope::with_duration(format.sample_rate, pause_fade);
+    // An output starting paused has no audio to fade out of
+    if control.paused.load(Ordering::Relaxed) || control.all_paused.load(Ordering::Relaxed) {
+        pause_envelope.mute();
+    }
 
     // Pre-fill with silence to establish latency buffer
-    let _ =
-        renderer.write_silence(format.buffer_size_for_ms(20) as u32 / format.block_align as u32);
+    output.prefill();
 
-    while !stop_flag.load(Ordering::Relaxed) {
-        // Check if paused (when this device is the default output)
-        if paused_flag.load(Ordering::Relaxed) {
+    // Whether audio is flowing, so an empty device buffer means an underrun
+    let mut primed = false;
+    // Whether the last write failed, so a run of failures is reported once
+    let mut failing = false;
+    // Whether the device was disabled or unplugged, so it has to be reopened
+    let mut device_lost = false;
+
+    // The master is looked up again on every pass, as it changes when the
+    // master's device is removed
+    let (follows_source, is_master) = {
+        let sync = clock_sync.lock();
+        (sync.follows_source(), sync.is_master(&device_id))
+    };
+    stats.set_sync(is_master, 0.0);
+    stats.set_extra_buffer_ms(output.tuning.extra_buffer_ms);
+
+    // Correction computed after the previous write, so each pass takes the
+    // clock sync lock only once
+    let mut correction = 0;
+    // Smoothed time from capture to playback in seconds
+    let mut end_to_end: Option<f64> = None;
+
+    let delay_frames = (format.sample_rate as u64 * control.delay_ms as u64 / 1000) as u32;
+    let delay = DelayLine::new(format.frames_to_bytes(delay_frames));
+    if control.delay_ms > 0 {
+        info!("Delaying {} by {}ms", device_name, control.delay_ms);
+    }
+
+    // A bitstream has to reach the receiver bit-exact: scaling samples or
+    // skipping frames to correct drift would break its framing
+    let passthrough = format.is_bitstream() || output.renderer.format().is_bitstream();
+    if passthrough {
+        warn!(
+            "Renderer {} receives a bitstream, passing it through without volume or drift correction",
+            device_name
+        );
+        if let Some(tx) = &output.event_tx {
+            let _ = tx.send(EngineEvent::Passthrough {
+                device_id: device_id.clone(),
+            });
+        }
+    }
+
+    // The equalizer, configured stages, delay and speaker mapping, in order;
+    // a bitstream only gets the delay
+    let mut chain = DspChain::default();
+    if !passthrough {
+        chain.push(Box::new(LiveEqualizer::new(control.eq_rx.clone(), &format)));
+        for stage in &control.dsp {
+            match stage.build(&format) {
+                Ok(built) => chain.push(built),
+                Err(e) => warn!("Skipping {} on {}: {}", stage, device_name, e),
+            }
+        }
+        if !control.dsp.is_empty() {
+            info!("Processing {}: {}", device_name, chain.names().join(" > "));
+        }
+    }
+    chain.push(Box::new(delay));
+
+    // An output with other speakers than the source gets each channel on the
+    // same speaker, or folded into the nearest one it has
+    let device_format = output.renderer.format().clone();
+    let channel_map = if passthrough {
+        None
+    } else {
+        ChannelMap::between(&format, &device_format)
+    };
+    if let Some(map) = channel_map {
+        info!(
+            "Mapping channels for {}: {} to {}",
+            device_name, format, device_format
+        );
+        chain.push(Box::new(SpeakerMap::new(map)));
+    } else if !passthrough && device_format.block_align != format.block_align {
+        warn!(
+            "Renderer {} plays {}, which the captured {} can't be mapped to",
+            device_name, device_format, format
+        );
+    }
+    // An output mixing at another rate gets the audio resampled to it, last,
+    // so it plays at the right pitch
+    let device_rate = device_format.sample_rate;
+    if !passthrough && device_rate != format.sample_rate && can_convert(&format, &device_format) {
+        info!(
+            "Resampling for {}: {}Hz to {}Hz",
+            device_name, format.sample_rate, device_rate
+        );
+        chain.push(Box::new(OutputResampler::new(
+            device_format.channels,
+            format.sample_rate,
+            device_rate,
+        )));
+    }
+    // The device counts frames at its own rate, the clock sync at the engine's
+    let to_engine_frames = |frames: u64| frames * format.sample_rate as u64 / device_rate as u64;
+
+    while !stop_flag.load(Ordering::Relaxed) && !retired.load(Ordering::Relaxed) {
+        let pass_started = Instant::now();
+        stats.heartbeat().beat();
+
+        // Nothing can be played to a disabled or unplugged device: wait for
+        // it to come back, then open it again
+        if !stats.device_state().is_active() {
+            if !device_lost {
+                device_lost = true;
+                let _ = output.renderer.stop();
+            }
+            stats.set_state(ThreadState::Paused);
+            stats.set_buffered(0);
+            thread::sleep(Duration::from_millis(50));
+            reader.catch_up(&buffer);
+            chain.reset();
+            primed = false;
+            continue;
+        }
+        if device_lost {
+            if let Err(e) = output.reopen() {
+                debug!("Renderer {} not ready yet: {}", device_name, e);
+                thread::sleep(DEVICE_REOPEN_INTERVAL);
+                continue;
+            }
+            device_lost = false;
+            // Fade in, and sync the new device clock from zero
+            pause_envelope.mute();
+            stats.set_latency(&output.renderer.latency());
+            {
+                let mut sync = clock_sync.lock();
+                if sync.is_master(&device_id) {
+                    sync.set_master(&device_id);
+                } else {
+                    sync.register_slave(&device_id);
+                }
+            }
+            info!("Renderer {} reopened", device_name);
+        }
+
+        let renderer = &mut output.renderer;
+
+        // Check if paused (when this device is the default output, or all
+        // are); audio keeps playing until it has faded out
+        let paused =
+            control.paused.load(Ordering::Relaxed) || control.all_paused.load(Ordering::Relaxed);
+        if paused && (passthrough || pause_envelope.is_silent() || reader.available(&buffer) == 0) {
+            // Resuming fades in from silence
+            pause_envelope.mute();
+            stats.set_state(ThreadState::Paused);
+            stats.set_buffered(0);
             // Write silence to keep device happy, but don't read from buffer
             let _ = renderer.write_silence(480); // 10ms of silence
             thread::sleep(Duration::from_millis(50));
             // Keep reader caught up to avoid buffer overrun when resuming
             reader.catch_up(&buffer);
+            chain.reset();
+            primed = false;
             continue;
         }
+        stats.set_state(ThreadState::Running);
 
         // Check for buffer underrun/overrun
         if reader.is_lagging(&buffer) {
             warn!("Renderer {} buffer overrun, catching up", device_name);
             reader.catch_up(&buffer);
+            notify(
+                &output.event_tx,
+                EngineEvent::BufferOverrun {
+                    device_id: device_id.clone(),
+                },
+            );
         }
 
         // Read available data
         let available = reader.available(&buffer);
+        stats.set_buffered(available);
         if available == 0 {
             // No data available, write silence
             let _ = renderer.write_silence(480); // 10ms of silence
@@ -783,19 +3293,25 @@ fn render_thread(
             continue;
         }
 
+        // The device played everything it had: grow its buffer if this keeps happening
+        if primed
+            && renderer.padding_frames().is_ok_and(|padding| padding == 0)
+            && output.record_underrun()
+        {
+            output.prefill();
+            stats.set_extra_buffer_ms(output.tuning.extra_buffer_ms);
+            stats.set_latency(&output.renderer.latency());
+            primed = false;
+        }
+        stats.set_underruns(output.underruns.total());
+        let renderer = &mut output.renderer;
+
         // Read and write
         let to_read = available.min(render_buffer.len());
+        let position = reader.position();
         let read = reader.read(&buffer, &mut render_buffer[..to_read]);
 
         if read > 0 {
-            // Apply clock sync correction (use readonly to avoid locking)
-            let (correction, is_master) = {
-                let sync = clock_sync.lock();
-                let correction = sync.get_correction_readonly(&device_id);
-                let is_master = sync.is_master(&device_id);
-                (correction, is_master)
-            };
-
             // For now, skip samples if ahead (positive correction)
             // In a more sophisticated implementation, we'd do sample rate conversion
             let (start, end) = if correction > 0 {
@@ -805,28 +3321,113 @@ fn render_thread(
                 (0, read)
             };
 
-            // Apply volume scaling
-            let volume = volume_level.get();
-            apply_volume_f32(&mut render_buffer[start..end], volume);
+            // Apply volume scaling; a bitstream can only be passed or silenced
+            let volume = volume_level.gain();
+            if !passthrough {
+                apply_volume_f32(&mut render_buffer[start..end], volume);
+                cut_fade.apply(
+                    &mut render_buffer[start..end],
+                    format.block_align as usize,
+                    position.wrapping_add(start),
+                    transition.cut(),
+                );
+                pause_envelope.apply(
+                    &mut render_buffer[start..end],
+                    format.channels as usize,
+                    if paused { 0.0 } else { 1.0 },
+                );
+            } else if volume == 0.0 {
+                render_buffer[start..end].fill(0);
+            }
+
+            let frames = chain.process(&render_buffer[start..end]);
+            match renderer.write_frames(frames, output.chunk_ms) {
+                Ok(frames) => {
+                    primed = true;
+                    if failing {
+                        failing = false;
+                        notify(
+                            &output.event_tx,
+                            EngineEvent::RendererWorking {
+                                device_id: device_id.clone(),
+                            },
+                        );
+                    }
+                    stats.add_frames(frames);
+
+                    // Query the device before locking, so the lock is held
+                    // only for the bookkeeping
+                    let waiting = format.bytes_to_frames(reader.available(&buffer)) as u64;
+                    let padding = renderer
+                        .padding_frames()
+                        .map(|padding| to_engine_frames(padding as u64));
 
-            match renderer.write_frames(&render_buffer[start..end], 50) {
-                Ok(_frames) => {
-                    // Update clock sync position and apply correction
-                    if let Ok(pos) = renderer.get_buffer_position() {
+                    // What was just written was captured before the audio
+                    // still waiting, and plays once the device played the
+                    // rest of its buffer
+                    if let (Ok(padding), Some(age)) = (&padding, capture_clock.age()) {
+                        let queued = (waiting + *padding) as f64 / format.sample_rate as f64;
+                        let measured =
+                            age.as_secs_f64() + queued + control.delay_ms as f64 / 1000.0;
+                        let smoothed = end_to_end.map_or(measured, |last| {
+                            last + (measured - last) * LATENCY_SMOOTHING
+                        });
+                        end_to_end = Some(smoothed);
+                        stats.set_end_to_end(Duration::from_secs_f64(smoothed));
+                    }
+
+                    let position = if follows_source {
+                        padding.map(|padding| ClockReading::Backlog(waiting + padding))
+                    } else {
+                        renderer.device_position().map(|position| {
+                            ClockReading::Position(DevicePosition {
+                                frames: to_engine_frames(position.frames),
+                                ..position
+                            })
+                        })
+                    };
+
+                    // Update clock sync position, apply this pass's correction
+                    // and fetch the next one
+                    if let Ok(position) = position {
                         let mut sync = clock_sync.lock();
-                        if is_master {
-                            sync.update_master(pos);
-                        } else {
-                            sync.update_slave(&device_id, pos);
-                            if correction != 0 {
-                                sync.apply_correction(&device_id);
+                        let is_master = sync.is_master(&device_id);
+                        match position {
+                            ClockReading::Backlog(backlog) => {
+                                sync.update_backlog(&device_id, backlog)
                             }
+                            ClockReading::Position(position) if is_master => {
+                                sync.update_master(position)
+                            }
+                            ClockReading::Position(position) => {
+                                sync.update_slave(&device_id, position)
+                            }
+                        }
+                        if correction != 0 {
+                            sync.apply_correction(&device_id);
                         }
+                        if !passthrough {
+                            correction = sync.get_correction_readonly(&device_id);
+                        }
+                        let drift_ms = sync.get_drift_ms(&device_id).unwrap_or(0.0);
+                        drop(sync);
+                        stats.set_sync(is_master, drift_ms);
                     }
+                    stats.record_pass(pass_started.elapsed());
                 }
                 Err(e) => {
                     warn!("Renderer {} write error: {}", device_name, e);
                     renderer.set_error(&e.to_string());
+                    if !failing {
+                        failing = true;
+                        notify(
+                            &output.event_tx,
+                            EngineEvent::RendererFailed {
+                                device_id: device_id.clone(),
+                                error: e.to_string(),
+                            },
+                        );
+                    }
                     // Brief pause before retry
                     thread::sleep(Duration::from_millis(10));
                 }
@@ -834,6 +3435,1409 @@ fn render_thread(
         }
     }
 
-    let _ = renderer.stop();
+    let _ = output.renderer.stop();
+    // A replacement thread owns the counters now
+    if retired.load(Ordering::Relaxed) {
+        info!("Replaced render thread exited for: {}", device_name);
+        return;
+    }
+    stats.set_state(ThreadState::Stopped);
     info!("Render thread stopped for: {}", device_name);
 }
+
+/// Record an opened device's timing, warning if its period is longer than
+/// the chunks it is sent
+fn report_latency<B: AudioBackend>(renderer: &AdaptiveRenderer<B>, control: &RendererControl) {
+    let latency = renderer.renderer.latency();
+    if latency.capabilities.default_period_ms() > renderer.chunk_ms as f64 {
+        warn!(
+            "{} has a {:.0}ms period, longer than the {}ms buffer; raise the buffer if it crackles",
+            renderer.renderer.device_name(),
+            latency.capabilities.default_period_ms(),
+            renderer.chunk_ms
+        );
+    }
+    control.stats.set_latency(&latency);
+}
+
+/// A device opened for its render thread, or the state keeping it closed
+enum RenderOutput<B: AudioBackend> {
+    Open(AdaptiveRenderer<B>),
+    /// Disabled or unplugged; the render thread opens it once it is back
+    Inactive(DeviceState),
+}
+
+/// Everything needed to start render threads, kept by the watchdog to
+/// replace stalled ones
+struct RenderContext<B: AudioBackend> {
+    backend: Arc<B>,
+    buffer: Arc<RingBuffer>,
+    stop_flag: Arc<AtomicBool>,
+    clock_sync: Arc<Mutex<ClockSync>>,
+    format: AudioFormat,
+    volume_level: Arc<VolumeLevel>,
+    transition: Arc<Transition>,
+    /// Fade applied when a renderer is paused or resumed
+    pause_fade: Duration,
+    priority: ThreadPriority,
+    buffer_tunings: Arc<Mutex<HashMap<String, BufferTuning>>>,
+    plan: BufferPlan,
+    /// Buffers configured per device ID, in place of the plan's
+    device_buffer_ms: HashMap<String, u32>,
+    /// When the newest audio in the ring buffer was captured
+    capture_clock: Arc<CaptureClock>,
+    event_tx: Option<Sender<EngineEvent>>,
+}
+
+impl<B: AudioBackend> RenderContext<B> {
+    /// Open a device with the buffer tuning learned for it, or else the one
+    /// its configured buffer gives
+    fn open(&self, device_id: &str) -> Result<AdaptiveRenderer<B>> {
+        let learned = self.buffer_tunings.lock().get(device_id).copied();
+        let tuning = learned.unwrap_or_else(|| match self.device_buffer_ms.get(device_id) {
+            Some(&buffer_ms) => {
+                debug!("{} uses its own {}ms buffer", device_id, buffer_ms);
+                self.plan.initial_tuning_for(buffer_ms)
+            }
+            None => self.plan.initial_tuning(),
+        });
+        Ok(AdaptiveRenderer {
+            renderer: self.backend.open_renderer(device_id, &tuning)?,
+            backend: self.backend.clone(),
+            tuning,
+            chunk_ms: self.plan.chunk_ms,
+            underruns: UnderrunTracker::new(),
+            learned: self.buffer_tunings.clone(),
+            event_tx: self.event_tx.clone(),
+        })
+    }
+
+    /// Open a device like [`RenderContext::open`], unless Windows reports it
+    /// inactive
+    fn open_output(&self, device_id: &str) -> Result<RenderOutput<B>> {
+        match self.open(device_id) {
+            Ok(renderer) => Ok(RenderOutput::Open(renderer)),
+            Err(WemuxError::DeviceInactive { state, .. }) => Ok(RenderOutput::Inactive(state)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Start a render thread for an opened device
+    fn spawn(
+        self: &Arc<Self>,
+        renderer: AdaptiveRenderer<B>,
+        control: RendererControl,
+    ) -> JoinHandle<()> {
+        report_latency(&renderer, &control);
+        let context = self.clone();
+        thread::spawn(move || context.render(renderer, control))
+    }
+
+    /// Start a render thread for an inactive device, which waits until the
+    /// device monitor reports it active and opens it then
+    fn spawn_waiting(
+        self: &Arc<Self>,
+        device_id: &str,
+        control: RendererControl,
+    ) -> JoinHandle<()> {
+        let context = self.clone();
+        let device_id = device_id.to_string();
+        thread::spawn(move || {
+            let stats = control.stats.clone();
+            stats.set_state(ThreadState::Paused);
+            let renderer = loop {
+                if context.stop_flag.load(Ordering::Relaxed)
+                    || control.retired.load(Ordering::Relaxed)
+                {
+                    stats.set_state(ThreadState::Stopped);
+                    return;
+                }
+                stats.heartbeat().beat();
+                if !stats.device_state().is_active() {
+                    thread::sleep(Duration::from_millis(50));
+                    continue;
+                }
+                match context.open(&device_id) {
+                    Ok(renderer) => break renderer,
+                    Err(e) => {
+                        debug!("Renderer {} not ready yet: {}", device_id, e);
+                        thread::sleep(DEVICE_REOPEN_INTERVAL);
+                    }
+                }
+            };
+            {
+                let mut sync = context.clock_sync.lock();
+                if sync.master_id().is_none() && !sync.follows_source() {
+                    sync.set_master(&device_id);
+                } else {
+                    sync.register_slave(&device_id);
+                }
+            }
+            info!("Device {} is back, starting its output", device_id);
+            report_latency(&renderer, &control);
+            context.render(renderer, control);
+        })
+    }
+
+    /// Run the render thread for an opened device on the current thread
+    fn render(&self, renderer: AdaptiveRenderer<B>, control: RendererControl) {
+        render_thread(
+            renderer,
+            self.buffer.clone(),
+            self.stop_flag.clone(),
+            control,
+            self.clock_sync.clone(),
+            self.format.clone(),
+            self.volume_level.clone(),
+            self.transition.clone(),
+            self.pause_fade,
+            self.priority,
+            self.capture_clock.clone(),
+        );
+    }
+
+    /// Retire a stalled render thread and start a new one in its place
+    ///
+    /// The old thread can't be interrupted inside a driver call; it exits on
+    /// its own if the call ever returns. Returns the new thread's control.
+    fn replace(
+        self: &Arc<Self>,
+        device_id: &str,
+        control: &RendererControl,
+    ) -> Result<(RendererControl, JoinHandle<()>)> {
+        control.retired.store(true, Ordering::SeqCst);
+        // A failed attempt is retried only once the timeout passed again
+        control.stats.heartbeat().beat();
+
+        let renderer = self.open(device_id)?;
+        let control = RendererControl {
+            retired: Arc::new(AtomicBool::new(false)),
+            ..control.clone()
+        };
+        let _ = control.eq_tx.send(*control.eq_current.lock());
+
+        // The new device clock starts from zero
+        {
+            let mut sync = self.clock_sync.lock();
+            if sync.is_master(device_id) {
+                sync.set_master(device_id);
+            } else {
+                sync.register_slave(device_id);
+            }
+        }
+
+        Ok((control.clone(), self.spawn(renderer, control)))
+    }
+}
+
+/// Wait for a thread to exit until `deadline`, leaving it running if it doesn't
+///
+/// Returns whether the thread exited.
+fn join_until(handle: JoinHandle<()>, name: &str, deadline: Instant) -> bool {
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            error!("{} thread did not stop in time, leaving it behind", name);
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let _ = handle.join();
+    true
+}
+
+/// Watchdog thread function
+///
+/// Replaces render threads whose heartbeat went stale - stuck in a driver
+/// call, exited or panicked - and hands the engine back to its owner (as
+/// [`EngineEvent::Fatal`]) when the capture stalls or a device keeps stalling.
+fn watchdog_thread<B: AudioBackend>(
+    context: Arc<RenderContext<B>>,
+    renderer_controls: Arc<Mutex<HashMap<String, RendererControl>>>,
+    render_handles: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+    capture_heartbeat: Arc<Heartbeat>,
+    command_rx: Receiver<EngineCommand>,
+) {
+    info!("Watchdog thread started");
+
+    let mut restarts: HashMap<String, u32> = HashMap::new();
+    let mut given_up = HashSet::new();
+    let mut capture_reported = false;
+
+    while !context.stop_flag.load(Ordering::Relaxed) {
+        // Wakes up right away when the engine stops
+        match command_rx.recv_timeout(WATCHDOG_INTERVAL) {
+            Ok(EngineCommand::Stop) => break,
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let capture_age = capture_heartbeat.age();
+        if !capture_reported
+            && !capture_heartbeat.is_finished()
+            && capture_age > THREAD_STALL_TIMEOUT
+        {
+            capture_reported = true;
+            report_fatal(
+                &context.event_tx,
+                format!(
+                    "Capture thread stopped responding for {:.1}s",
+                    capture_age.as_secs_f64()
+                ),
+            );
+        }
+
+        let stalled: Vec<(String, RendererControl, Duration)> = renderer_controls
+            .lock()
+            .iter()
+            .map(|(id, control)| (id.clone(), control.clone(), control.stats.heartbeat().age()))
+            .filter(|(id, _, age)| *age > THREAD_STALL_TIMEOUT && !given_up.contains(id))
+            .collect();
+
+        for (device_id, control, age) in stalled {
+            if context.stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let attempts = restarts.entry(device_id.clone()).or_insert(0);
+            if *attempts >= MAX_RENDERER_RESTARTS {
+                given_up.insert(device_id.clone());
+                report_fatal(
+                    &context.event_tx,
+                    format!(
+                        "Renderer {} stalled again after {} restarts",
+                        device_id, MAX_RENDERER_RESTARTS
+                    ),
+                );
+                continue;
+            }
+            *attempts += 1;
+
+            let reason = format!("no progress for {:.1}s", age.as_secs_f64());
+            warn!(
+                "Render thread for {} stalled ({}), replacing it ({}/{})",
+                device_id, reason, attempts, MAX_RENDERER_RESTARTS
+            );
+            match context.replace(&device_id, &control) {
+                Ok((control, handle)) => {
+                    let mut controls = renderer_controls.lock();
+                    if !controls.contains_key(&device_id) {
+                        // Deselected by reconfigure() meanwhile
+                        control.retired.store(true, Ordering::SeqCst);
+                        continue;
+                    }
+                    controls.insert(device_id.clone(), control);
+                    drop(controls);
+                    // The stalled thread's handle is dropped, detaching it
+                    render_handles.lock().insert(device_id.clone(), handle);
+                    if let Some(tx) = &context.event_tx {
+                        let _ = tx.send(EngineEvent::Recovered { device_id, reason });
+                    }
+                }
+                Err(e) => warn!("Failed to restart renderer {}: {}", device_id, e),
+            }
+        }
+    }
+
+    info!("Watchdog thread stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{MockBackend, MockSignal, SampleEncoding};
+    use std::time::Instant;
+
+    fn mock_backend() -> MockBackend {
+        MockBackend::new(MockSignal::Sine {
+            frequency: 440.0,
+            amplitude: 0.5,
+        })
+        .with_device("hdmi-1", "HDMI 1")
+        .with_device("hdmi-2", "HDMI 2")
+        .with_device("speakers", "Speakers")
+    }
+
+    /// Poll `condition` for up to two seconds
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        condition()
+    }
+
+    fn is_paused(engine: &AudioEngine<MockBackend>, device_id: &str) -> bool {
+        engine
+            .get_device_statuses()
+            .iter()
+            .any(|s| s.id == device_id && s.is_paused)
+    }
+
+    #[test]
+    fn test_duplicates_audio_to_hdmi_devices() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.start().unwrap();
+        assert!(engine.is_running());
+
+        // 100ms of audio reached every HDMI renderer
+        let received = |id: &str| backend.output(id).is_some_and(|o| o.frames_written >= 4800);
+        assert!(wait_for(|| received("hdmi-1") && received("hdmi-2")));
+        // HDMI-only mode leaves other outputs alone
+        assert!(backend.output("speakers").is_none());
+
+        let peak = backend.output("hdmi-1").unwrap().peak;
+        assert!(peak > 0.4 && peak <= 0.5 + f32::EPSILON);
+
+        engine.stop().unwrap();
+        assert_eq!(engine.state(), EngineState::Stopped);
+        assert!(!backend.output("hdmi-1").unwrap().running);
+        assert!(engine.get_device_statuses().is_empty());
+    }
+
+    #[test]
+    fn test_output_at_another_rate_keeps_its_pitch() {
+        let backend = mock_backend().with_device_rate("hdmi-2", 44_100);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.start().unwrap();
+
+        // The 440Hz tone, half a period in 54.5 frames at 48kHz and 50.1 at 44.1kHz
+        let half_period = |id: &str| {
+            let mut half_periods = backend.output(id).unwrap_or_default().half_periods;
+            half_periods.sort_unstable();
+            half_periods.get(half_periods.len() / 2).copied()
+        };
+        assert!(wait_for(|| backend
+            .output("hdmi-2")
+            .is_some_and(|o| o.half_periods.len() >= 100)));
+        assert!(matches!(half_period("hdmi-1"), Some(54 | 55)));
+        assert!(matches!(half_period("hdmi-2"), Some(50 | 51)));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_performance_capture_delivers_audio() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            performance_capture: true,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        let received = |id: &str| backend.output(id).is_some_and(|o| o.frames_written >= 4800);
+        assert!(wait_for(|| received("hdmi-1") && received("hdmi-2")));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_all_devices_and_exclusions() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            use_all_devices: true,
+            exclude_ids: Some(vec!["hdmi-2".to_string()]),
+            paused_device_ids: Some(vec!["speakers".to_string()]),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        let mut ids: Vec<String> = engine
+            .get_device_statuses()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["hdmi-1", "speakers"]);
+        assert!(is_paused(&engine, "speakers"));
+        assert!(!is_paused(&engine, "hdmi-1"));
+    }
+
+    #[test]
+    fn test_reconfigure_keeps_untouched_outputs_playing() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            use_all_devices: true,
+            exclude_ids: Some(vec!["speakers".to_string()]),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config.clone(), backend.clone());
+        engine.start().unwrap();
+        let buffer = engine.buffer.clone().unwrap();
+        assert!(wait_for(|| backend
+            .output("hdmi-2")
+            .is_some_and(|o| o.running)));
+
+        let config = EngineConfig {
+            exclude_ids: Some(vec!["hdmi-2".to_string()]),
+            paused_device_ids: Some(vec!["hdmi-1".to_string()]),
+            ..config
+        };
+        engine.reconfigure(config.clone()).unwrap();
+        let mut ids: Vec<String> = engine
+            .get_device_statuses()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["hdmi-1", "speakers"]);
+        assert!(is_paused(&engine, "hdmi-1"));
+        assert!(!backend.output("hdmi-2").unwrap().running);
+        assert!(Arc::ptr_eq(&buffer, engine.buffer.as_ref().unwrap()));
+
+        // A new buffer size needs a restart
+        engine
+            .reconfigure(EngineConfig {
+                buffer_ms: 80,
+                ..config
+            })
+            .unwrap();
+        assert!(engine.is_running());
+        assert!(!Arc::ptr_eq(&buffer, engine.buffer.as_ref().unwrap()));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_bluetooth_output_delays_the_others() {
+        let backend = mock_backend().with_device("bt", "Headphones (Bluetooth)");
+        let config = EngineConfig {
+            use_all_devices: true,
+            delays: HashMap::from([("hdmi-2".to_string(), 30)]),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend);
+        engine.start().unwrap();
+
+        let delay = |id: &str| engine.renderer_controls.lock()[id].delay_ms;
+        assert_eq!(delay("hdmi-1"), BLUETOOTH_LATENCY_MS);
+        assert_eq!(delay("hdmi-2"), 30);
+        assert_eq!(delay("bt"), 0);
+        engine.stop().unwrap();
+
+        // A paused Bluetooth output delays nothing
+        engine.config.paused_device_ids = Some(vec!["bt".to_string()]);
+        engine.start().unwrap();
+        assert_eq!(engine.renderer_controls.lock()["hdmi-1"].delay_ms, 0);
+    }
+
+    #[test]
+    fn test_zones_play_and_pause_independently() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            zones: vec![
+                Zone {
+                    name: "Downstairs".to_string(),
+                    devices: vec!["hdmi-1".to_string()],
+                    source: None,
+                    paused: false,
+                },
+                Zone {
+                    name: "Office".to_string(),
+                    devices: vec!["hdmi-2".to_string()],
+                    source: Some("Speakers".to_string()),
+                    paused: true,
+                },
+            ],
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        let ids = |engine: &AudioEngine<MockBackend>| {
+            let mut ids: Vec<_> = engine
+                .get_device_statuses()
+                .into_iter()
+                .map(|s| s.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&engine), ["hdmi-1"]);
+        assert!(engine.zone_statuses()[1].paused);
+
+        engine.set_zone_paused("Office", false).unwrap();
+        assert_eq!(ids(&engine), ["hdmi-1", "hdmi-2"]);
+        assert!(wait_for(|| backend
+            .capture_sources()
+            .contains(&Some("speakers".to_string()))));
+        engine.pause_renderer("hdmi-2").unwrap();
+        assert!(is_paused(&engine, "hdmi-2"));
+        assert_eq!(engine.get_stats().renderers.len(), 2);
+        assert!(engine.set_zone_paused("Attic", false).is_err());
+
+        engine.stop().unwrap();
+        assert!(engine.get_device_statuses().is_empty());
+    }
+
+    #[test]
+    fn test_alias_selects_device_after_id_change() {
+        let mut aliases = DeviceAliases::default();
+        aliases.assign(
+            "tv",
+            &DeviceInfo {
+                id: "hdmi-2-before-driver-update".to_string(),
+                name: "HDMI 2".to_string(),
+                is_hdmi: true,
+                is_default: false,
+                container_id: None,
+                is_bluetooth: false,
+            },
+        );
+        let config = EngineConfig {
+            device_ids: Some(vec!["tv".to_string()]),
+            aliases,
+            render_cores: HashMap::from([("tv".to_string(), 1)]),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, mock_backend());
+        engine.start().unwrap();
+
+        let statuses = engine.get_device_statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].id, "hdmi-2");
+        assert_eq!(statuses[0].display_name(), "tv");
+        // Settings keyed by the alias follow it too
+        assert_eq!(engine.renderer_controls.lock()["hdmi-2"].core, Some(1));
+    }
+
+    #[test]
+    fn test_underruns_grow_renderer_buffer() {
+        let backend = mock_backend();
+        backend.set_starving("hdmi-1", true);
+        let (event_tx, event_rx) = crossbeam_channel::unbounded();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        match event_rx.recv_timeout(Duration::from_secs(2)).unwrap() {
+            EngineEvent::BufferTuned {
+                device_id,
+                extra_buffer_ms,
+                prefill_ms,
+            } => {
+                assert_eq!(device_id, "hdmi-1");
+                assert!(extra_buffer_ms > 0);
+                assert!(prefill_ms > BufferTuning::default().prefill_ms);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        let tuned = |id: &str| {
+            backend
+                .output(id)
+                .is_some_and(|o| o.tuning.extra_buffer_ms > 0)
+        };
+        assert!(wait_for(|| tuned("hdmi-1")));
+        assert!(!tuned("hdmi-2"));
+
+        // The learned tuning is reused when the engine restarts
+        backend.set_starving("hdmi-1", false);
+        engine.stop().unwrap();
+        engine.start().unwrap();
+        assert!(tuned("hdmi-1"));
+    }
+
+    #[test]
+    fn test_pause_and_resume_renderer() {
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), mock_backend());
+        engine.start().unwrap();
+
+        // Device timing is known as soon as the renderers are open
+        assert!(engine
+            .get_device_statuses()
+            .iter()
+            .all(|d| d.latency.as_ref().is_some_and(|l| l.buffer_ms == 100)));
+
+        engine.pause_renderer("hdmi-2").unwrap();
+        assert!(is_paused(&engine, "hdmi-2"));
+        engine.resume_renderer("hdmi-2").unwrap();
+        assert!(!is_paused(&engine, "hdmi-2"));
+
+        assert!(matches!(
+            engine.pause_renderer("missing"),
+            Err(WemuxError::DeviceNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_pause_all_silences_every_output() {
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), mock_backend());
+        engine.start().unwrap();
+        engine.pause_renderer("hdmi-2").unwrap();
+
+        let state = |engine: &AudioEngine<MockBackend>, id: &str| {
+            engine
+                .get_stats()
+                .renderers
+                .iter()
+                .find(|r| r.id == id)
+                .map(|r| r.state)
+        };
+        engine.pause_all();
+        assert!(engine.is_paused_all());
+        assert!(wait_for(
+            || state(&engine, "hdmi-1") == Some(ThreadState::Paused)
+        ));
+        assert!(engine.is_running());
+
+        // Resuming leaves the output paused on its own paused
+        engine.resume_all();
+        assert!(wait_for(
+            || state(&engine, "hdmi-1") == Some(ThreadState::Running)
+        ));
+        assert!(is_paused(&engine, "hdmi-2"));
+        assert_eq!(state(&engine, "hdmi-2"), Some(ThreadState::Paused));
+    }
+
+    #[test]
+    fn test_device_status_shows_audio_activity() {
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), mock_backend());
+        engine.start().unwrap();
+
+        let receiving = |engine: &AudioEngine<MockBackend>, id: &str| {
+            engine
+                .get_device_statuses()
+                .iter()
+                .any(|d| d.id == id && d.receiving_audio)
+        };
+        assert!(wait_for(|| receiving(&engine, "hdmi-2")));
+
+        // A paused output only gets silence, which isn't counted
+        engine.pause_renderer("hdmi-2").unwrap();
+        assert!(wait_for(|| !receiving(&engine, "hdmi-2")));
+        assert!(receiving(&engine, "hdmi-1"));
+    }
+
+    #[test]
+    fn test_stats_reflect_render_threads() {
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), mock_backend());
+        engine.start().unwrap();
+        engine.pause_renderer("hdmi-2").unwrap();
+
+        let state = |id: &str| {
+            engine
+                .get_stats()
+                .renderers
+                .iter()
+                .find(|r| r.id == id)
+                .map(|r| r.state)
+        };
+        assert!(wait_for(|| state("hdmi-1") == Some(ThreadState::Running)));
+        assert!(wait_for(|| state("hdmi-2") == Some(ThreadState::Paused)));
+
+        // A renderer that wrote audio knows how late it plays
+        assert!(wait_for(|| {
+            engine
+                .get_device_statuses()
+                .iter()
+                .any(|d| d.id == "hdmi-1" && d.end_to_end_ms.is_some())
+        }));
+
+        let stats = engine.get_stats();
+        assert!(stats.running);
+        assert!(stats.ring_buffer_ms > 0);
+        assert_eq!(stats.renderers.iter().filter(|r| r.is_master).count(), 1);
+        assert!(stats.renderers.iter().all(|r| !r.stalled));
+        let capture = stats.threads.iter().find(|t| t.name == "capture").unwrap();
+        assert!(!capture.finished && !capture.stalled);
+
+        engine.stop().unwrap();
+        assert_eq!(engine.get_stats(), EngineStats::default());
+    }
+
+    #[test]
+    fn test_default_device_change_pauses_renderer() {
+        let backend = mock_backend();
+        backend.set_default_device("speakers");
+
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+        assert!(!is_paused(&engine, "hdmi-1"));
+
+        backend.set_default_device("hdmi-1");
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(2)),
+            Ok(EngineEvent::DefaultDeviceChanged)
+        ));
+        assert!(is_paused(&engine, "hdmi-1"));
+        assert!(engine.is_device_default("hdmi-1"));
+    }
+
+    #[test]
+    fn test_default_change_opens_capture_alongside() {
+        let backend = mock_backend();
+        backend.set_default_device("speakers");
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.start().unwrap();
+        // The format is read from a capture before the capture thread opens its own
+        assert!(wait_for(|| backend.capture_sources().len() == 2));
+
+        backend.set_default_device("hdmi-1");
+        assert!(wait_for(|| backend.capture_sources().len() == 3));
+        assert_eq!(backend.capture_sources(), [None, None, None]);
+
+        // The other output keeps getting audio across the switch
+        let written = || {
+            engine
+                .get_stats()
+                .renderers
+                .iter()
+                .find(|r| r.id == "hdmi-2")
+                .map_or(0, |r| r.frames_written)
+        };
+        let before = written();
+        assert!(wait_for(|| written() > before));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_excluded_apps_follow_the_default() {
+        let backend = mock_backend();
+        backend.set_default_device("speakers");
+        let config = EngineConfig {
+            exclude_apps: vec!["Discord".to_string()],
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+        assert!(wait_for(|| backend.excluded_apps().len() == 2));
+
+        // The capture of the new default leaves the same apps out
+        backend.set_default_device("hdmi-1");
+        assert!(wait_for(|| backend.excluded_apps().len() == 3));
+        assert!(backend
+            .excluded_apps()
+            .iter()
+            .all(|apps| apps == &["Discord"]));
+        assert_eq!(backend.capture_sources(), [None, None, None]);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_plan_opens_nothing() {
+        let backend = mock_backend();
+        backend.set_default_device("hdmi-1");
+        let engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+
+        let plans = engine.plan().unwrap();
+        assert_eq!(plans.len(), 1);
+        let plan = &plans[0];
+        assert_eq!(plan.source, "default output (HDMI 1)");
+        assert_eq!(plan.buffers, BufferPlan::new(50, &Default::default(), 2));
+        let outputs: Vec<_> = plan
+            .outputs
+            .iter()
+            .map(|o| (o.id.as_str(), o.master, o.paused.as_deref()))
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                ("hdmi-1", true, Some("default output")),
+                ("hdmi-2", false, None)
+            ]
+        );
+
+        assert!(backend.capture_sources().is_empty());
+        assert!(backend.output("hdmi-1").is_none() && backend.output("hdmi-2").is_none());
+        assert_eq!(engine.state(), EngineState::Uninitialized);
+    }
+
+    #[test]
+    fn test_device_list_change_is_forwarded() {
+        let backend = mock_backend();
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        // A TV powering off turns its output inactive
+        backend.send_device_event(DeviceEvent::StateChanged {
+            device_id: "hdmi-2".to_string(),
+            new_state: DeviceState::NotPresent,
+        });
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(2)),
+            Ok(EngineEvent::DeviceListChanged)
+        ));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_inactive_device_waits_and_is_reopened() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        let inactive = |engine: &AudioEngine<MockBackend>| {
+            engine
+                .get_device_statuses()
+                .into_iter()
+                .find(|status| status.id == "hdmi-2")
+                .expect("hdmi-2 listed")
+                .inactive
+        };
+        let playing = |id: &str| {
+            backend
+                .output(id)
+                .is_some_and(|output| output.running && output.frames_written > 0)
+        };
+
+        // Unplugged after being listed, like a TV in standby: its output is
+        // listed and waits for it
+        backend.set_device_state("hdmi-2", DeviceState::Unplugged);
+        engine.start().unwrap();
+        assert_eq!(inactive(&engine), Some(DeviceState::Unplugged));
+        assert!(backend.output("hdmi-2").is_none());
+
+        // Plugged in: opened and playing
+        backend.set_device_state("hdmi-2", DeviceState::Active);
+        assert!(wait_for(|| playing("hdmi-2")));
+        assert_eq!(inactive(&engine), None);
+
+        // Disabled while playing: its output waits with the device closed
+        backend.set_device_state("hdmi-2", DeviceState::Disabled);
+        assert!(wait_for(|| !backend.output("hdmi-2").unwrap().running));
+        assert_eq!(inactive(&engine), Some(DeviceState::Disabled));
+        assert!(backend.output("hdmi-1").unwrap().running);
+
+        // Enabled again: opened anew and playing
+        backend.set_device_state("hdmi-2", DeviceState::Active);
+        assert!(wait_for(|| playing("hdmi-2")));
+        assert_eq!(inactive(&engine), None);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_removed_master_hands_over_clock() {
+        let backend = mock_backend();
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        backend.send_device_event(DeviceEvent::Removed("hdmi-1".to_string()));
+        let Ok(EngineEvent::MasterChanged { removed, master }) =
+            event_rx.recv_timeout(Duration::from_secs(2))
+        else {
+            panic!("expected a master change");
+        };
+        assert_eq!(removed, "hdmi-1");
+        let master = master.expect("another output takes over");
+        assert!(wait_for(|| engine
+            .get_stats()
+            .renderers
+            .iter()
+            .any(|r| r.id == master && r.is_master)));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_fixed_source_ignores_default_change() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            source_device_id: Some("Speakers".to_string()),
+            ..Default::default()
+        };
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+        assert!(wait_for(|| backend.capture_sources().len() == 2));
+
+        backend.set_default_device("hdmi-1");
+        assert!(event_rx.recv_timeout(Duration::from_secs(2)).is_ok());
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(
+            backend.capture_sources(),
+            vec![Some("speakers".to_string()); 2]
+        );
+
+        let config = EngineConfig {
+            source_device_id: Some("missing".to_string()),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, mock_backend());
+        assert!(matches!(engine.start(), Err(WemuxError::DeviceNotFound(_))));
+    }
+
+    #[test]
+    fn test_captured_output_starts_paused() {
+        let backend = mock_backend();
+        backend.set_default_device("speakers");
+        let config = EngineConfig {
+            source_device_id: Some("HDMI 2".to_string()),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend);
+        let plans = engine.plan().unwrap();
+        let paused = |id: &str| {
+            let output = plans[0].outputs.iter().find(|o| o.id == id).unwrap();
+            output.paused.clone()
+        };
+        assert_eq!(paused("hdmi-2").as_deref(), Some("capture source"));
+        assert_eq!(paused("hdmi-1"), None);
+
+        engine.start().unwrap();
+        assert!(is_paused(&engine, "hdmi-2"));
+        assert!(!is_paused(&engine, "hdmi-1"));
+        let statuses = engine.get_device_statuses();
+        let source = statuses.iter().find(|d| d.id == "hdmi-2").unwrap();
+        assert!(source.is_capture_source && source.is_auto_paused());
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_input_source_plays_on_default_output() {
+        let backend = mock_backend()
+            .with_input("line-in", "Line In")
+            .with_input("mic", "Microphone");
+        backend.set_default_device("hdmi-1");
+        let config = EngineConfig {
+            source_type: SourceType::Input,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        // Without a source the default input is recorded, and the default
+        // output plays it like the others
+        assert!(wait_for(|| backend.capture_sources().len() == 2));
+        assert_eq!(
+            backend.capture_sources(),
+            vec![Some("line-in".to_string()); 2]
+        );
+        assert!(!is_paused(&engine, "hdmi-1"));
+        engine.stop().unwrap();
+
+        // Outputs aren't inputs
+        let config = EngineConfig {
+            source_device_id: Some("micro".to_string()),
+            source_type: SourceType::Input,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+        assert!(wait_for(|| backend.capture_sources().len() == 4));
+        assert_eq!(backend.capture_sources()[3], Some("mic".to_string()));
+        engine.stop().unwrap();
+
+        let config = EngineConfig {
+            source_device_id: Some("Speakers".to_string()),
+            source_type: SourceType::Input,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend);
+        assert!(matches!(engine.start(), Err(WemuxError::DeviceNotFound(_))));
+    }
+
+    #[test]
+    fn test_file_source_plays_on_all_outputs() {
+        // One second of mono 16-bit PCM at half scale
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0");
+        wav.extend_from_slice(&48_000u32.to_le_bytes());
+        wav.extend_from_slice(&96_000u32.to_le_bytes());
+        wav.extend_from_slice(b"\x02\0\x10\0data");
+        wav.extend_from_slice(&96_000u32.to_le_bytes());
+        for _ in 0..48_000 {
+            wav.extend_from_slice(&16384i16.to_le_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("wemux-file-{}.wav", std::process::id()));
+        std::fs::write(&path, wav).unwrap();
+
+        let backend = mock_backend();
+        backend.set_default_device("hdmi-1");
+        let config = EngineConfig {
+            source_device_id: Some(path.display().to_string()),
+            source_type: SourceType::File,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        for id in ["hdmi-1", "hdmi-2"] {
+            assert!(wait_for(|| backend
+                .output(id)
+                .is_some_and(|output| output.peak == 0.5)));
+        }
+        engine.stop().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let config = EngineConfig {
+            source_type: SourceType::File,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, mock_backend());
+        assert!(matches!(engine.start(), Err(WemuxError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_fallback_source_takes_over_silent_capture() {
+        let backend = MockBackend::new(MockSignal::Silence)
+            .with_device("hdmi-1", "HDMI 1")
+            .with_input("mic", "Microphone");
+        let config = EngineConfig {
+            fallback_source: Some("Microphone@0".parse().unwrap()),
+            ..Default::default()
+        };
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        assert!(backend.capture_sources().contains(&Some("mic".to_string())));
+        let switched = std::iter::from_fn(|| event_rx.recv_timeout(Duration::from_secs(2)).ok())
+            .find(|event| matches!(event, EngineEvent::SourceSwitched { .. }));
+        assert!(matches!(
+            switched,
+            Some(EngineEvent::SourceSwitched { fallback: true })
+        ));
+        assert!(engine
+            .get_stats()
+            .threads
+            .iter()
+            .any(|thread| thread.name == "fallback: Microphone"));
+    }
+
+    #[test]
+    fn test_announcement_ducks_capture() {
+        let backend = mock_backend().with_input("mic", "Microphone");
+        let config = EngineConfig {
+            announcement: Some("Microphone@20".parse().unwrap()),
+            ..Default::default()
+        };
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        assert!(backend.capture_sources().contains(&Some("mic".to_string())));
+        let ducking = std::iter::from_fn(|| event_rx.recv_timeout(Duration::from_secs(2)).ok())
+            .find(|event| matches!(event, EngineEvent::Ducking { .. }));
+        assert!(matches!(
+            ducking,
+            Some(EngineEvent::Ducking { active: true })
+        ));
+        assert!(engine
+            .get_stats()
+            .threads
+            .iter()
+            .any(|thread| thread.name == "announcements: Microphone"));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_mix_sources_are_captured() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            mix_sources: vec!["Speakers@0.5".parse().unwrap(), "missing".parse().unwrap()],
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        // A source that can't be found is skipped
+        engine.start().unwrap();
+
+        let speakers = Some("speakers".to_string());
+        assert!(wait_for(|| {
+            backend
+                .capture_sources()
+                .iter()
+                .filter(|source| **source == speakers)
+                .count()
+                == 2
+        }));
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.frames_written > 0)));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_mix_sources_play_while_the_source_is_idle() {
+        let backend = mock_backend();
+        backend.set_source_idle(None, true);
+        let config = EngineConfig {
+            mix_sources: vec!["Speakers@0.5".parse().unwrap()],
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        // The loopback sends nothing, yet the mixed-in source is heard
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.peak > 0.1)));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_follow_source_has_no_master() {
+        let backend = mock_backend();
+        let config = EngineConfig {
+            follow_source: true,
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        let received = |id: &str| backend.output(id).is_some_and(|o| o.frames_written >= 4800);
+        assert!(wait_for(|| received("hdmi-1") && received("hdmi-2")));
+        let stats = engine.get_stats();
+        assert!(stats.renderers.iter().all(|r| !r.is_master));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_bitstream_passes_through_unscaled() {
+        let backend = mock_backend().with_encoding(SampleEncoding::Bitstream);
+        backend.set_volume(0.5);
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(2)),
+            Ok(EngineEvent::Passthrough { .. })
+        ));
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.frames_written >= 4800)));
+        // The samples reach the device as captured, not at half volume
+        let peak = backend.output("hdmi-1").unwrap().peak;
+        assert!(peak > 0.4);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_capture_failure_is_reported() {
+        let backend = mock_backend();
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        backend.set_capture_fails(true);
+        // The first failure is reported right away, giving up only later
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(5)),
+            Ok(EngineEvent::CaptureFailed { .. })
+        ));
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(5)),
+            Ok(EngineEvent::Fatal(_))
+        ));
+    }
+
+    #[test]
+    fn test_capture_recovery_is_reported() {
+        let backend = mock_backend();
+        let (event_tx, event_rx) = bounded(16);
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        backend.set_capture_fails(true);
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(5)),
+            Ok(EngineEvent::CaptureFailed { .. })
+        ));
+        backend.set_capture_fails(false);
+        assert!(matches!(
+            event_rx.recv_timeout(Duration::from_secs(5)),
+            Ok(EngineEvent::CaptureWorking)
+        ));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_watchdog_replaces_stalled_renderer() {
+        let backend = mock_backend();
+        let (event_tx, event_rx) = unbounded();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.set_event_channel(event_tx);
+        engine.start().unwrap();
+
+        backend.set_hanging("hdmi-1", true);
+        let recovered = event_rx
+            .iter()
+            .find_map(|event| match event {
+                EngineEvent::Recovered { device_id, .. } => Some(device_id),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(recovered, "hdmi-1");
+
+        // The replacement renders once the device responds again
+        backend.set_hanging("hdmi-1", false);
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.frames_written > 0)));
+        assert!(engine.is_running());
+
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_reconfigure_restarts_past_wedged_threads() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.start().unwrap();
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.frames_written > 0)));
+
+        backend.set_hanging("hdmi-1", true);
+        thread::sleep(Duration::from_millis(200));
+        // A new buffer size needs a restart, which the stuck thread doesn't fail
+        let config = EngineConfig {
+            buffer_ms: 80,
+            ..Default::default()
+        };
+        engine.reconfigure(config).unwrap();
+        assert!(engine.is_running());
+
+        backend.set_hanging("hdmi-1", false);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_stop_leaves_wedged_threads_behind() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.start().unwrap();
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.frames_written > 0)));
+
+        // Let the render thread get stuck in its next write
+        backend.set_hanging("hdmi-1", true);
+        thread::sleep(Duration::from_millis(200));
+        let started = Instant::now();
+        let result = engine.stop();
+        assert!(started.elapsed() < SHUTDOWN_TIMEOUT + Duration::from_secs(1));
+        assert!(matches!(
+            result,
+            Err(WemuxError::ShutdownTimeout(threads)) if threads == ["HDMI 1"]
+        ));
+        assert_eq!(engine.state(), EngineState::Stopped);
+
+        // The engine starts again; the thread left behind exits once the device responds
+        engine.start().unwrap();
+        backend.set_hanging("hdmi-1", false);
+        assert!(wait_for(|| backend
+            .output("hdmi-1")
+            .is_some_and(|o| o.frames_written >= 4800)));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_run_blocking_until_stop_signal() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        let stop = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let received =
+                    |id: &str| backend.output(id).is_some_and(|o| o.frames_written >= 4800);
+                assert!(wait_for(|| received("hdmi-1")));
+                stop.store(true, Ordering::SeqCst);
+            });
+            engine.run_blocking(&stop).unwrap();
+        });
+
+        assert_eq!(engine.state(), EngineState::Stopped);
+        assert!(!backend.output("hdmi-1").unwrap().running);
+    }
+
+    #[test]
+    fn test_equalizer_per_device() {
+        let backend = mock_backend();
+        let mut cut = EqSettings::default();
+        cut.gains_db[3] = -12.0;
+        cut.gains_db[4] = -12.0;
+        let config = EngineConfig {
+            equalizers: HashMap::from([("hdmi-1".to_string(), cut)]),
+            ..Default::default()
+        };
+        let mut engine = AudioEngine::with_backend(config, backend.clone());
+        engine.start().unwrap();
+
+        // The 440Hz tone is cut on hdmi-1 only
+        let received = |id: &str| backend.output(id).is_some_and(|o| o.frames_written >= 4800);
+        assert!(wait_for(|| received("hdmi-1") && received("hdmi-2")));
+        assert!(backend.output("hdmi-1").unwrap().peak < 0.3);
+        assert!(backend.output("hdmi-2").unwrap().peak > 0.4);
+
+        assert!(engine.set_equalizer("hdmi-2", cut).is_ok());
+        assert!(engine.set_equalizer("speakers", cut).is_err());
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_start_without_devices_fails() {
+        let backend = MockBackend::new(MockSignal::Silence).with_device("speakers", "Speakers");
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend);
+
+        assert!(matches!(engine.start(), Err(WemuxError::NoHdmiDevices)));
+        assert!(!engine.is_running());
+    }
+
+    #[test]
+    fn test_failed_start_stops_its_threads() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+
+        // hdmi-1 is already playing when opening hdmi-2 fails
+        backend.set_open_fails("hdmi-2", true);
+        assert!(engine.start().is_err());
+        assert!(!engine.is_running());
+        assert!(!backend.output("hdmi-1").is_some_and(|o| o.running));
+        assert_eq!(backend.open_captures(), 0);
+
+        // A retry starts cleanly
+        backend.set_open_fails("hdmi-2", false);
+        engine.start().unwrap();
+        assert!(wait_for(|| backend.open_captures() == 1));
+        engine.stop().unwrap();
+        assert_eq!(backend.open_captures(), 0);
+    }
+
+    #[test]
+    fn test_monitoring_does_not_stall_render_threads() {
+        let backend = mock_backend();
+        let mut engine = AudioEngine::with_backend(EngineConfig::default(), backend.clone());
+        engine.start().unwrap();
+
+        // Poll as hard as a misbehaving UI could while audio flows (the
+        // engine isn't Sync, so the pollers share it behind a mutex)
+        let engine = Mutex::new(engine);
+        let deadline = Instant::now() + Duration::from_millis(500);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while Instant::now() < deadline {
+                        let stats = engine.lock().get_stats();
+                        assert_eq!(stats.renderers.iter().filter(|r| r.is_master).count(), 1);
+                        engine.lock().get_device_statuses();
+                    }
+                });
+            }
+        });
+        let mut engine = engine.into_inner();
+
+        for (id, control) in engine.renderer_controls.lock().iter() {
+            let longest = control.stats.longest_pass();
+            assert!(
+                longest < Duration::from_millis(20),
+                "{} stalled for {:?}",
+                id,
+                longest
+            );
+        }
+        engine.stop().unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+//! Per-device 10-band graphic equalizer
+//!
+//! Each band is a peaking biquad (RBJ audio EQ cookbook) one octave wide,
+//! centred from 31Hz to 16kHz. Bands left at 0dB are skipped, the rest run in
+//! series on 32-bit float samples with separate state per channel.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::{PI, SQRT_2};
+
+/// Centre frequencies of the bands in Hz
+pub const EQ_BANDS_HZ: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Largest boost or cut of a band in dB
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+/// Q of a one-octave band
+const BAND_Q: f64 = SQRT_2;
+
+/// Equalizer settings of one device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EqSettings {
+    /// Gain per band in dB, from 31Hz to 16kHz (limited to ±12dB)
+    pub gains_db: [f32; 10],
+}
+
+impl EqSettings {
+    /// Whether every band is at 0dB, leaving the audio untouched
+    pub fn is_flat(&self) -> bool {
+        self.gains_db.iter().all(|gain| *gain == 0.0)
+    }
+}
+
+/// Normalized biquad coefficients
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl Biquad {
+    /// Peaking filter boosting or cutting around `freq`
+    fn peaking(freq: f64, gain_db: f64, sample_rate: f64) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * BAND_Q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha / a;
+
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * cos / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+
+    fn process(&self, state: &mut BiquadState, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * state.x1 + self.b2 * state.x2
+            - self.a1 * state.y1
+            - self.a2 * state.y2;
+        state.x2 = state.x1;
+        state.x1 = x;
+        state.y2 = state.y1;
+        state.y1 = y;
+        y
+    }
+}
+
+/// Previous inputs and outputs of one filter on one channel
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+/// Equalizer applied to one renderer's audio
+pub(crate) struct Equalizer {
+    filters: Vec<Biquad>,
+    /// State of filter `i` on channel `c` at `i * channels + c`
+    states: Vec<BiquadState>,
+    channels: usize,
+}
+
+impl Equalizer {
+    /// Build the filters for `settings`, or None if they leave the audio untouched
+    ///
+    /// Bands too close to the Nyquist frequency of `sample_rate` are left out.
+    pub(crate) fn new(settings: &EqSettings, sample_rate: u32, channels: u16) -> Option<Self> {
+        let filters: Vec<Biquad> = EQ_BANDS_HZ
+            .iter()
+            .zip(settings.gains_db)
+            .filter(|(freq, gain)| *gain != 0.0 && **freq < sample_rate as f32 * 0.45)
+            .map(|(freq, gain)| {
+                let gain = gain.clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+                Biquad::peaking(*freq as f64, gain as f64, sample_rate as f64)
+            })
+            .collect();
+
+        if filters.is_empty() || channels == 0 {
+            return None;
+        }
+        let channels = channels as usize;
+        Some(Self {
+            states: vec![BiquadState::default(); filters.len() * channels],
+            filters,
+            channels,
+        })
+    }
+
+    /// Filter interleaved 32-bit float samples in place, clamping to -1.0 - 1.0
+    pub(crate) fn process_f32(&mut self, data: &mut [u8]) {
+        for frame in data.chunks_exact_mut(4 * self.channels) {
+            for (channel, bytes) in frame.chunks_exact_mut(4).enumerate() {
+                let mut sample =
+                    f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+                for (i, filter) in self.filters.iter().enumerate() {
+                    sample = filter.process(&mut self.states[i * self.channels + channel], sample);
+                }
+                bytes.copy_from_slice(&(sample.clamp(-1.0, 1.0) as f32).to_le_bytes());
+            }
+        }
+    }
+
+    /// Forget the filter state, so stale audio doesn't ring on after a pause
+    pub(crate) fn clear(&mut self) {
+        self.states.fill(BiquadState::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak of a sine after the equalizer has settled
+    fn filtered_peak(settings: &EqSettings, frequency: f32) -> f32 {
+        let mut eq = Equalizer::new(settings, 48000, 1).unwrap();
+        let mut data: Vec<u8> = (0..48000)
+            .flat_map(|i| {
+                let t = i as f32 / 48000.0;
+                (0.25 * (2.0 * std::f32::consts::PI * frequency * t).sin()).to_le_bytes()
+            })
+            .collect();
+        eq.process_f32(&mut data);
+
+        data[24000 * 4..]
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_equalizer_bands() {
+        assert!(Equalizer::new(&EqSettings::default(), 48000, 2).is_none());
+
+        // +6dB at 1kHz doubles a 1kHz tone
+        let mut boost = EqSettings::default();
+        boost.gains_db[5] = 6.0;
+        assert!((filtered_peak(&boost, 1000.0) - 0.5).abs() < 0.01);
+
+        // A bass cut leaves the midrange alone
+        let mut bass_cut = EqSettings::default();
+        bass_cut.gains_db[0] = -12.0;
+        bass_cut.gains_db[1] = -12.0;
+        assert!((filtered_peak(&bass_cut, 1000.0) - 0.25).abs() < 0.01);
+        assert!(filtered_peak(&bass_cut, 40.0) < 0.1);
+
+        // Bands near the Nyquist frequency are left out
+        let mut treble = EqSettings::default();
+        treble.gains_db[9] = 6.0;
+        assert!(Equalizer::new(&treble, 32000, 2).is_none());
+    }
+}
@@ -0,0 +1,232 @@
+//! Fading the outputs out and back in around a capture restart
+//!
+//! When the default device changes and the new device's capture can't be
+//! opened alongside the old one, the loopback capture is stopped and opened
+//! again. Cutting the audio off mid-waveform and starting it again
+//! mid-waveform pops; instead the capture thread marks the ring buffer
+//! position where the old capture's audio ends and fills the buffer up to
+//! it before stopping. Each render thread fades out over the audio before
+//! that cut and back in over the audio after it, going by its own read
+//! position, so every output fades at the same point of the audio however
+//! far behind the capture it plays.
+//!
+//! Pausing and resuming an output is faded the same way, over
+//! `EngineConfig::pause_fade_ms`, as some receivers click when audio stops
+//! or starts abruptly.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Time the outputs take to fade out or back in
+pub(crate) const FADE_DURATION: Duration = Duration::from_millis(30);
+
+/// Time an output takes to fade out when paused and in when resumed, unless
+/// configured
+pub const PAUSE_FADE_MS: u32 = 20;
+
+/// No cut marked yet
+const NO_CUT: usize = usize::MAX;
+
+/// Where the latest capture restart cuts the audio, shared by all threads
+#[derive(Debug)]
+pub(crate) struct Transition {
+    cut: AtomicUsize,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            cut: AtomicUsize::new(NO_CUT),
+        }
+    }
+}
+
+impl Transition {
+    /// Mark the ring buffer position where the old capture's audio ends
+    pub(crate) fn cut_at(&self, position: usize) {
+        self.cut.store(position, Ordering::Release);
+    }
+
+    /// Position of the latest cut, if there was one
+    pub(crate) fn cut(&self) -> Option<usize> {
+        Some(self.cut.load(Ordering::Acquire)).filter(|&cut| cut != NO_CUT)
+    }
+}
+
+/// A render thread's gain around the [`Transition`] cut
+#[derive(Debug, Clone)]
+pub(crate) struct CutFade {
+    gain: f32,
+    /// Frames the fade out and the fade in take each
+    frames: usize,
+}
+
+impl CutFade {
+    /// Fade at full gain for audio at `sample_rate`
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        let frames = (sample_rate as f32 * FADE_DURATION.as_secs_f32()).round();
+        Self {
+            gain: 1.0,
+            frames: (frames as usize).max(1),
+        }
+    }
+
+    /// Scale 32-bit float frames of `frame_bytes` read from ring buffer
+    /// position `position` on
+    ///
+    /// The gain falls to zero at `cut` and rises back over the frames after
+    /// it. A render thread that learns of the cut late fades out over the
+    /// frames it has left.
+    pub(crate) fn apply(
+        &mut self,
+        data: &mut [u8],
+        frame_bytes: usize,
+        position: usize,
+        cut: Option<usize>,
+    ) {
+        let Some(cut) = cut else {
+            return;
+        };
+        let since_cut = position.wrapping_sub(cut) as isize;
+        if self.gain == 1.0 && since_cut >= (self.frames * frame_bytes) as isize {
+            // Long past the cut
+            return;
+        }
+        for (i, frame) in data.chunks_exact_mut(frame_bytes).enumerate() {
+            let at = position.wrapping_add(i * frame_bytes);
+            let ahead = cut.wrapping_sub(at) as isize / frame_bytes as isize;
+            if ahead > 0 {
+                if ahead as usize <= self.frames {
+                    // Linear to zero at the cut from wherever the gain is
+                    self.gain -= self.gain / ahead as f32;
+                }
+            } else {
+                let since = ahead.unsigned_abs();
+                self.gain = ((since + 1) as f32 / self.frames as f32).min(1.0);
+            }
+            if self.gain == 1.0 {
+                continue;
+            }
+            for sample in frame.chunks_exact_mut(4) {
+                let value = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                sample.copy_from_slice(&(value * self.gain).to_le_bytes());
+            }
+        }
+    }
+}
+
+/// A render thread's gain ramping towards a target, for pausing
+#[derive(Debug, Clone)]
+pub(crate) struct Envelope {
+    gain: f32,
+    /// Gain change per frame
+    step: f32,
+}
+
+impl Envelope {
+    /// Envelope at full gain for audio at `sample_rate`, fading over `duration`
+    ///
+    /// A zero duration switches within one frame.
+    pub(crate) fn with_duration(sample_rate: u32, duration: Duration) -> Self {
+        let frames = (sample_rate as f32 * duration.as_secs_f32())
+            .round()
+            .max(1.0);
+        Self {
+            gain: 1.0,
+            step: 1.0 / frames,
+        }
+    }
+
+    /// Whether the gain reached zero
+    pub(crate) fn is_silent(&self) -> bool {
+        self.gain == 0.0
+    }
+
+    /// Drop to zero gain at once, e.g. when there is no audio left to fade
+    pub(crate) fn mute(&mut self) {
+        self.gain = 0.0;
+    }
+
+    /// Scale 32-bit float frames of `channels` samples, moving the gain
+    /// towards `target` one step per frame
+    pub(crate) fn apply(&mut self, data: &mut [u8], channels: usize, target: f32) {
+        if self.gain == target && target == 1.0 {
+            return;
+        }
+        for frame in data.chunks_exact_mut(channels * 4) {
+            self.gain = if self.gain < target {
+                (self.gain + self.step).min(target)
+            } else {
+                (self.gain - self.step).max(target)
+            };
+            for sample in frame.chunks_exact_mut(4) {
+                let value = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                sample.copy_from_slice(&(value * self.gain).to_le_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gains(envelope: &mut Envelope, frames: usize, target: f32) -> Vec<f32> {
+        let mut data: Vec<u8> = (0..frames).flat_map(|_| 1.0f32.to_le_bytes()).collect();
+        envelope.apply(&mut data, 1, target);
+        data.chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    fn cut_gains(fade: &mut CutFade, from: usize, frames: usize, cut: Option<usize>) -> Vec<f32> {
+        let mut data: Vec<u8> = (0..frames).flat_map(|_| 1.0f32.to_le_bytes()).collect();
+        fade.apply(&mut data, 4, from * 4, cut);
+        data.chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_cut_fades_out_and_in_at_the_position() {
+        let transition = Transition::default();
+        // Four frames per fade
+        let rate = (4.0 / FADE_DURATION.as_secs_f32()).round() as u32;
+        let mut fade = CutFade::new(rate);
+        assert_eq!(cut_gains(&mut fade, 0, 2, transition.cut()), [1.0, 1.0]);
+
+        // The cut after frame 7, split across reads
+        transition.cut_at(8 * 4);
+        assert_eq!(
+            cut_gains(&mut fade, 2, 4, transition.cut()),
+            [1.0, 1.0, 0.75, 0.5]
+        );
+        assert_eq!(
+            cut_gains(&mut fade, 6, 6, transition.cut()),
+            [0.25, 0.0, 0.25, 0.5, 0.75, 1.0]
+        );
+        assert_eq!(cut_gains(&mut fade, 12, 2, transition.cut()), [1.0, 1.0]);
+
+        // A reader seeing the cut two frames ahead still reaches zero there
+        let mut late = CutFade::new(rate);
+        assert_eq!(
+            cut_gains(&mut late, 6, 3, transition.cut()),
+            [0.5, 0.0, 0.25]
+        );
+    }
+
+    #[test]
+    fn test_pause_envelope() {
+        // Two frames per fade
+        let mut envelope = Envelope::with_duration(1000, Duration::from_millis(2));
+        assert_eq!(gains(&mut envelope, 3, 0.0), [0.5, 0.0, 0.0]);
+        assert!(envelope.is_silent());
+        assert_eq!(gains(&mut envelope, 3, 1.0), [0.5, 1.0, 1.0]);
+
+        envelope.mute();
+        assert_eq!(gains(&mut envelope, 1, 0.0), [0.0]);
+
+        let mut instant = Envelope::with_duration(1000, Duration::ZERO);
+        assert_eq!(gains(&mut instant, 2, 0.0), [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,194 @@
+//! A second source that takes over while the main one is silent
+//!
+//! A network receiver or a microphone can stand in for the loopback: once
+//! the main capture has been silent for a while, the fallback's audio is
+//! played instead, and as soon as the main capture is audible again it
+//! plays on its own.
+
+use crate::audio::{ReaderState, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Seconds of silence before the fallback takes over, unless configured
+pub const DEFAULT_FALLBACK_AFTER_SECS: u32 = 5;
+
+/// Samples quieter than this count as silence (about -80 dBFS)
+const SILENCE_THRESHOLD: f32 = 1e-4;
+
+/// Source played while the main capture is silent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FallbackSource {
+    /// Device ID, alias or part of a device name; outputs are looked up
+    /// first, then inputs
+    pub device: String,
+    /// Seconds the main capture must be silent before the fallback plays
+    #[serde(default = "default_after_secs")]
+    pub after_secs: u32,
+}
+
+fn default_after_secs() -> u32 {
+    DEFAULT_FALLBACK_AFTER_SECS
+}
+
+impl FromStr for FallbackSource {
+    type Err = String;
+
+    /// Parse "DEVICE" or "DEVICE@SECONDS"
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (device, after_secs) = match text.rsplit_once('@') {
+            Some((device, secs)) => {
+                let secs = secs
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid number of seconds '{}'", secs))?;
+                (device, secs)
+            }
+            None => (text, default_after_secs()),
+        };
+
+        let device = device.trim();
+        if device.is_empty() {
+            return Err(format!("no device in '{}'", text));
+        }
+        Ok(Self {
+            device: device.to_string(),
+            after_secs,
+        })
+    }
+}
+
+/// Switches captured 32-bit float audio over to a fallback source's buffer
+pub(crate) struct SourceSwitch {
+    buffer: Arc<RingBuffer>,
+    reader: ReaderState,
+    after: Duration,
+    /// Most audio the fallback may have waiting beyond what one read consumes
+    max_backlog: usize,
+    /// When the main capture last delivered audible audio
+    last_audible: Instant,
+    active: bool,
+    scratch: Vec<u8>,
+}
+
+impl SourceSwitch {
+    /// Switch to audio written to `buffer` after `after` of silence from `now` on
+    pub(crate) fn new(
+        buffer: Arc<RingBuffer>,
+        after: Duration,
+        max_backlog: usize,
+        now: Instant,
+    ) -> Self {
+        Self {
+            reader: ReaderState::new(&buffer),
+            buffer,
+            after,
+            max_backlog,
+            last_audible: now,
+            active: false,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Whether the fallback is playing instead of the main capture
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Note what the main capture delivered at `now`; nothing counts as silence
+    ///
+    /// Returns the new state when the fallback takes over (true) or yields
+    /// back to the main capture (false).
+    pub(crate) fn update(&mut self, captured: &[u8], now: Instant) -> Option<bool> {
+        if is_audible(captured) {
+            self.last_audible = now;
+            if self.active {
+                self.active = false;
+                return Some(false);
+            }
+        } else if !self.active && now.duration_since(self.last_audible) >= self.after {
+            // Start from the fallback's newest audio, not what piled up before
+            self.reader = ReaderState::new(&self.buffer);
+            self.active = true;
+            return Some(true);
+        }
+        None
+    }
+
+    /// Replace `data` with the fallback's audio, silence where it has none yet
+    pub(crate) fn replace(&mut self, data: &mut [u8]) {
+        let read = self.read(data);
+        data[read..].fill(0);
+    }
+
+    /// Copy up to `dest.len()` bytes of the fallback's waiting audio into
+    /// `dest`, returning how many were copied
+    pub(crate) fn read(&mut self, dest: &mut [u8]) -> usize {
+        if self.reader.is_lagging(&self.buffer) {
+            self.reader.catch_up(&self.buffer);
+        }
+
+        // Drop what the fallback got ahead, so its latency stays bounded
+        let available = self.reader.available(&self.buffer);
+        let excess = available.saturating_sub(dest.len() + self.max_backlog);
+        if excess > 0 {
+            self.scratch.resize(excess, 0);
+            self.reader.read(&self.buffer, &mut self.scratch);
+        }
+
+        self.reader.read(&self.buffer, dest)
+    }
+}
+
+/// Whether any 32-bit float sample in `data` is above the silence threshold
+pub(crate) fn is_audible(data: &[u8]) -> bool {
+    data.chunks_exact(4)
+        .any(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]).abs() > SILENCE_THRESHOLD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_fallback_takes_over_during_silence() {
+        assert_eq!(
+            "Line In@10".parse::<FallbackSource>().unwrap(),
+            FallbackSource {
+                device: "Line In".to_string(),
+                after_secs: 10
+            }
+        );
+        assert_eq!("Mic".parse::<FallbackSource>().unwrap().after_secs, 5);
+        assert!("Mic@soon".parse::<FallbackSource>().is_err());
+
+        let fallback = Arc::new(RingBuffer::new(64));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut switch = SourceSwitch::new(fallback.clone(), Duration::from_secs(5), 64, start);
+        let silence = samples(&[0.0, 0.0]);
+        let music = samples(&[0.5, -0.5]);
+
+        assert_eq!(switch.update(&silence, at(4)), None);
+        // Audio from before the switch is not played
+        fallback.write(&samples(&[0.9, 0.9]));
+        assert_eq!(switch.update(&[], at(5)), Some(true));
+        assert!(switch.is_active());
+
+        fallback.write(&samples(&[0.25, 0.25]));
+        let mut data = samples(&[0.0, 0.0, 0.0, 0.0]);
+        switch.replace(&mut data);
+        assert_eq!(data, samples(&[0.25, 0.25, 0.0, 0.0]));
+
+        assert_eq!(switch.update(&silence, at(9)), None);
+        assert_eq!(switch.update(&music, at(10)), Some(false));
+        assert!(!switch.is_active());
+        // Silence counts from the last audible audio
+        assert_eq!(switch.update(&silence, at(14)), None);
+    }
+}
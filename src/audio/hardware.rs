@@ -1,11 +1,13 @@
 //! Hardware capability detection for auto-calculating optimal buffer sizes
 
 use crate::error::Result;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
 use windows::Win32::Media::Audio::IAudioClient;
 
 /// Latency classification based on device characteristics
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LatencyClass {
     /// Low latency devices (professional/gaming cards): 20-30ms buffer
     LowLatency,
@@ -35,8 +37,19 @@ impl LatencyClass {
     }
 }
 
+impl std::fmt::Display for LatencyClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LatencyClass::LowLatency => "low latency",
+            LatencyClass::Standard => "standard",
+            LatencyClass::HighLatency => "high latency",
+        };
+        f.write_str(name)
+    }
+}
+
 /// Hardware capabilities queried from WASAPI
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HardwareCapabilities {
     /// Minimum period supported by device (100-nanosecond units)
     pub min_period: i64,
@@ -109,6 +122,14 @@ impl HardwareCapabilities {
         optimal
     }
 
+    /// Get the WASAPI buffer duration in 100-nanosecond units after auto-tuning
+    ///
+    /// Adds `extra_ms` on top of [`optimal_buffer_duration`](Self::optimal_buffer_duration)
+    /// for devices that underran with the hardware-derived size.
+    pub fn tuned_buffer_duration(&self, extra_ms: u32) -> i64 {
+        self.optimal_buffer_duration() + (extra_ms as i64) * 10_000
+    }
+
     /// Calculate optimal ring buffer size in milliseconds
     ///
     /// Takes into account the number of renderers to add safety margin.
@@ -137,6 +158,18 @@ impl HardwareCapabilities {
     }
 }
 
+/// What an opened output reported about its timing, and the buffer it got
+///
+/// Shows why wemux picked a buffer; the output with the largest buffer sets
+/// the latency of all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceLatency {
+    /// Capabilities queried from the device (defaults if the query failed)
+    pub capabilities: HardwareCapabilities,
+    /// WASAPI buffer the device was opened with, in milliseconds
+    pub buffer_ms: u32,
+}
+
 impl Default for HardwareCapabilities {
     /// Default capabilities (conservative values for when detection fails)
     fn default() -> Self {
@@ -0,0 +1,168 @@
+//! Signal level of the captured audio for VU meters
+//!
+//! The capture thread measures the peak and RMS of the audio it passes to the
+//! outputs over short windows and publishes the last complete window without
+//! a lock. [`EngineStats::level`](crate::audio::EngineStats) carries it to the
+//! tray tooltip, `wemux monitor` and anything else reading stats over IPC, to
+//! show that audio is flowing.
+
+use crate::audio::stats::Heartbeat;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Length of one measured window
+const WINDOW: Duration = Duration::from_millis(50);
+
+/// A level not updated for this long reads as silence, as the capture
+/// delivers nothing while no app plays
+const LEVEL_HOLD: Duration = Duration::from_millis(250);
+
+/// Level in dBFS reported for silence
+pub const SILENCE_DB: f32 = -96.0;
+
+/// Peak and RMS level of a window of audio, linear (1.0 = full scale)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignalLevel {
+    /// Largest absolute sample
+    pub peak: f32,
+    /// Root mean square of the samples
+    pub rms: f32,
+}
+
+impl SignalLevel {
+    /// Peak in dBFS, [`SILENCE_DB`] at the lowest
+    pub fn peak_db(&self) -> f32 {
+        to_db(self.peak)
+    }
+
+    /// RMS in dBFS, [`SILENCE_DB`] at the lowest
+    pub fn rms_db(&self) -> f32 {
+        to_db(self.rms)
+    }
+
+    /// Whether the peak is at or below [`SILENCE_DB`]
+    pub fn is_silent(&self) -> bool {
+        self.peak_db() <= SILENCE_DB
+    }
+
+    /// The louder of two levels, e.g. of several zones
+    pub fn max(self, other: Self) -> Self {
+        Self {
+            peak: self.peak.max(other.peak),
+            rms: self.rms.max(other.rms),
+        }
+    }
+}
+
+fn to_db(linear: f32) -> f32 {
+    if linear > 0.0 {
+        (20.0 * linear.log10()).max(SILENCE_DB)
+    } else {
+        SILENCE_DB
+    }
+}
+
+/// Last measured level, shared between the capture thread and readers
+#[derive(Debug, Default)]
+pub(crate) struct LevelCounters {
+    /// Peak as `f32` bits
+    peak: AtomicU32,
+    /// RMS as `f32` bits
+    rms: AtomicU32,
+    /// Beats whenever a window is published
+    updated: Heartbeat,
+}
+
+impl LevelCounters {
+    fn publish(&self, level: SignalLevel) {
+        self.peak.store(level.peak.to_bits(), Ordering::Relaxed);
+        self.rms.store(level.rms.to_bits(), Ordering::Relaxed);
+        self.updated.beat();
+    }
+
+    /// Level of the last window, silence if none was measured lately
+    pub(crate) fn level(&self) -> SignalLevel {
+        if self.updated.age() > LEVEL_HOLD {
+            return SignalLevel::default();
+        }
+        SignalLevel {
+            peak: f32::from_bits(self.peak.load(Ordering::Relaxed)),
+            rms: f32::from_bits(self.rms.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Measures 32-bit float audio in windows of [`WINDOW`], owned by the
+/// capture thread
+#[derive(Debug)]
+pub(crate) struct LevelMeter {
+    counters: Arc<LevelCounters>,
+    window_samples: usize,
+    samples: usize,
+    peak: f32,
+    sum_squares: f64,
+}
+
+impl LevelMeter {
+    pub(crate) fn new(counters: Arc<LevelCounters>, sample_rate: u32, channels: u16) -> Self {
+        let frames = (sample_rate as u128 * WINDOW.as_millis() / 1000) as usize;
+        Self {
+            counters,
+            window_samples: (frames * channels as usize).max(1),
+            samples: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+
+    /// Add `data` to the current window, publishing each window completed
+    pub(crate) fn measure(&mut self, data: &[u8]) {
+        for sample in data.chunks_exact(4) {
+            let value = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+            self.peak = self.peak.max(value.abs());
+            self.sum_squares += value as f64 * value as f64;
+            self.samples += 1;
+            if self.samples == self.window_samples {
+                self.counters.publish(SignalLevel {
+                    peak: self.peak,
+                    rms: (self.sum_squares / self.samples as f64).sqrt() as f32,
+                });
+                self.samples = 0;
+                self.peak = 0.0;
+                self.sum_squares = 0.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_meter_publishes_full_windows() {
+        let counters = Arc::new(LevelCounters::default());
+        // Two channels at 1kHz: 50 frames, 100 samples per window
+        let mut meter = LevelMeter::new(counters.clone(), 1000, 2);
+        let square: Vec<u8> = (0..100)
+            .flat_map(|n| if n % 2 == 0 { 0.5f32 } else { -0.5 }.to_le_bytes())
+            .collect();
+
+        meter.measure(&square[..200]);
+        assert!(counters.level().is_silent());
+
+        meter.measure(&square[200..]);
+        let level = counters.level();
+        assert_eq!(level.peak, 0.5);
+        assert!((level.rms - 0.5).abs() < 1e-6);
+        assert!((level.peak_db() + 6.02).abs() < 0.01);
+
+        // Nothing captured since: silence
+        thread::sleep(LEVEL_HOLD + Duration::from_millis(50));
+        assert_eq!(counters.level(), SignalLevel::default());
+        assert_eq!(SignalLevel::default().rms_db(), SILENCE_DB);
+    }
+}
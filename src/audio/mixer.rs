@@ -0,0 +1,182 @@
+//! Mixing of additional capture sources into the main capture
+//!
+//! Every extra source is captured on its own thread into a private ring
+//! buffer. The main capture thread pulls as many bytes from each source as it
+//! captured itself and adds them, scaled by the source's gain, before the
+//! result goes to the shared ring buffer. The main capture sets the pace: a
+//! source that runs ahead has its backlog trimmed, one that falls behind
+//! contributes silence for the missing part. While the main capture sends
+//! nothing at all (a loopback of an output playing nothing), the sources are
+//! mixed into silence as their audio arrives.
+
+use crate::audio::buffer::{ReaderState, RingBuffer};
+use crate::audio::simd::add_scaled_f32;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// An extra capture source mixed into the main one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MixSource {
+    /// Device ID, alias or part of a device name, like `device_ids` entries
+    pub device: String,
+    /// Linear gain applied to the source (1.0 = unchanged)
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+impl FromStr for MixSource {
+    type Err = String;
+
+    /// Parse "DEVICE" or "DEVICE@GAIN"
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (device, gain) = match text.rsplit_once('@') {
+            Some((device, gain)) => {
+                let gain = gain
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid gain '{}'", gain))?;
+                (device, gain)
+            }
+            None => (text, default_gain()),
+        };
+
+        let device = device.trim();
+        if device.is_empty() {
+            return Err(format!("no device in '{}'", text));
+        }
+        if !(0.0..=4.0).contains(&gain) {
+            return Err(format!("gain {} out of range 0.0 - 4.0", gain));
+        }
+        Ok(Self {
+            device: device.to_string(),
+            gain,
+        })
+    }
+}
+
+/// One source's buffer as seen by the mixer
+struct MixInput {
+    buffer: Arc<RingBuffer>,
+    reader: ReaderState,
+    gain: f32,
+}
+
+/// Adds extra sources to captured 32-bit float audio
+pub(crate) struct Mixer {
+    inputs: Vec<MixInput>,
+    /// Most audio a source may have waiting beyond what one mix consumes
+    max_backlog: usize,
+    frame_bytes: usize,
+    scratch: Vec<u8>,
+}
+
+impl Mixer {
+    /// Create a mixer without inputs; `max_backlog` bounds the latency a
+    /// source that runs ahead can build up, in bytes, and `frame_bytes` is
+    /// the size of one frame
+    pub(crate) fn new(max_backlog: usize, frame_bytes: usize) -> Self {
+        Self {
+            inputs: Vec::new(),
+            max_backlog,
+            frame_bytes: frame_bytes.max(1),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub(crate) fn has_inputs(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+
+    /// Whole frames the input furthest ahead has waiting, in bytes
+    pub(crate) fn pending(&self) -> usize {
+        let available = self
+            .inputs
+            .iter()
+            .map(|input| input.reader.available(&input.buffer))
+            .max()
+            .unwrap_or(0);
+        available - available % self.frame_bytes
+    }
+
+    /// Mix audio written to `buffer` from now on
+    pub(crate) fn add_input(&mut self, buffer: Arc<RingBuffer>, gain: f32) {
+        self.inputs.push(MixInput {
+            reader: ReaderState::new(&buffer),
+            buffer,
+            gain,
+        });
+    }
+
+    /// Add every input's pending audio to `data`
+    pub(crate) fn mix_into(&mut self, data: &mut [u8]) {
+        for input in &mut self.inputs {
+            if input.reader.is_lagging(&input.buffer) {
+                input.reader.catch_up(&input.buffer);
+            }
+
+            // Drop what the source got ahead, so its latency stays bounded
+            let available = input.reader.available(&input.buffer);
+            let excess = available.saturating_sub(data.len() + self.max_backlog);
+            if excess > 0 {
+                self.scratch.resize(excess, 0);
+                input.reader.read(&input.buffer, &mut self.scratch);
+            }
+
+            self.scratch.resize(data.len(), 0);
+            let read = input.reader.read(&input.buffer, &mut self.scratch);
+            add_scaled_f32(&mut data[..read], &self.scratch[..read], input.gain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_mix_sources() {
+        assert_eq!(
+            "Line In@0.5".parse::<MixSource>().unwrap(),
+            MixSource {
+                device: "Line In".to_string(),
+                gain: 0.5
+            }
+        );
+        assert_eq!("Mic".parse::<MixSource>().unwrap().gain, 1.0);
+        assert!("Mic@loud".parse::<MixSource>().is_err());
+        assert!("@0.5".parse::<MixSource>().is_err());
+
+        let source = Arc::new(RingBuffer::new(64));
+        let mut mixer = Mixer::new(8, 4);
+        assert!(!mixer.has_inputs());
+        mixer.add_input(source.clone(), 0.5);
+
+        // A source that has nothing yet leaves the capture unchanged
+        let mut data = samples(&[0.25, 0.25]);
+        mixer.mix_into(&mut data);
+        assert_eq!(data, samples(&[0.25, 0.25]));
+
+        source.write(&samples(&[0.5, 1.0]));
+        assert_eq!(mixer.pending(), 8);
+        mixer.mix_into(&mut data);
+        assert_eq!(data, samples(&[0.5, 0.75]));
+
+        // A backlog beyond 8 bytes is skipped, keeping the newest audio
+        source.write(&samples(&[0.1, 0.1, 0.1, 0.1, 0.2, 0.2]));
+        let mut data = samples(&[0.0, 0.0]);
+        mixer.mix_into(&mut data);
+        assert_eq!(data, samples(&[0.05, 0.05]));
+        let mut data = samples(&[0.0, 0.0]);
+        mixer.mix_into(&mut data);
+        assert_eq!(data, samples(&[0.1, 0.1]));
+    }
+}
@@ -0,0 +1,841 @@
+//! Synthetic audio backend for testing without audio devices
+//!
+//! [`MockBackend`] paces capture and rendering against the wall clock like real
+//! hardware, so an [`AudioEngine`](crate::audio::AudioEngine) driven by it runs
+//! its normal threads, ring buffer and clock sync. Tests inspect what each
+//! renderer received through [`MockBackend::output`].
+
+use crate::audio::backend::{AudioBackend, CaptureBackend, RenderBackend, VolumeSource};
+use crate::audio::{
+    default_channel_mask, AudioFormat, BufferTuning, DeviceLatency, HardwareCapabilities,
+    SampleEncoding,
+};
+use crate::device::{DeviceEvent, DeviceInfo, DeviceState, HdmiFilter};
+use crate::error::{Result, WemuxError};
+use crate::sync::DevicePosition;
+use crossbeam_channel::Sender;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::TAU;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Sample rate of the mock devices
+const MOCK_SAMPLE_RATE: u32 = 48000;
+
+/// Capture delivery period in frames (10ms, like a WASAPI period)
+const MOCK_PERIOD_FRAMES: u64 = 480;
+
+/// Renderer buffer size in frames (100ms)
+const MOCK_BUFFER_FRAMES: u64 = 4800;
+
+/// Signal produced by the mock capture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MockSignal {
+    /// Sine wave on all channels
+    Sine { frequency: f32, amplitude: f32 },
+    /// Uniform white noise
+    Noise { amplitude: f32 },
+    /// Digital silence
+    Silence,
+}
+
+/// Audio received by a mock renderer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockOutput {
+    /// Frames of audio written
+    pub frames_written: u64,
+    /// Frames of silence written
+    pub silence_frames: u64,
+    /// Largest absolute sample value written
+    pub peak: f32,
+    /// Whether the renderer is started
+    pub running: bool,
+    /// Buffer tuning the renderer was last opened with
+    pub tuning: BufferTuning,
+    /// Frames between sign changes of the first channel, which give the
+    /// pitch played
+    pub half_periods: Vec<u32>,
+}
+
+/// State shared between the backend handle and the devices it opened
+#[derive(Debug)]
+struct MockState {
+    default_id: Option<String>,
+    volume: f32,
+    capture_fails: bool,
+    /// Sources whose captures deliver nothing (None = default output)
+    idle_sources: HashSet<Option<String>>,
+    capture_sources: Vec<Option<String>>,
+    /// Captures opened and not yet dropped
+    open_captures: usize,
+    excluded_apps: Vec<Vec<String>>,
+    starving: HashSet<String>,
+    hanging: HashSet<String>,
+    open_fails: HashSet<String>,
+    inactive: HashMap<String, DeviceState>,
+    outputs: HashMap<String, MockOutput>,
+    watchers: HashMap<u64, Sender<DeviceEvent>>,
+    next_watcher: u64,
+}
+
+/// Audio backend generating synthetic audio
+///
+/// Clones share their state, so a test can keep a clone to drive device events
+/// and inspect outputs while the engine owns the original.
+#[derive(Debug, Clone)]
+pub struct MockBackend {
+    format: AudioFormat,
+    signal: MockSignal,
+    devices: Vec<(String, String)>,
+    inputs: Vec<(String, String)>,
+    /// Sample rates of outputs mixing at another rate than the capture
+    device_rates: HashMap<String, u32>,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBackend {
+    /// Create a backend without output devices, capturing `signal`
+    ///
+    /// Audio is 48kHz stereo 32-bit float, the usual loopback mix format.
+    pub fn new(signal: MockSignal) -> Self {
+        Self {
+            format: AudioFormat {
+                sample_rate: MOCK_SAMPLE_RATE,
+                channels: 2,
+                bits_per_sample: 32,
+                block_align: 8,
+                encoding: SampleEncoding::Float,
+                channel_mask: default_channel_mask(2),
+            },
+            signal,
+            devices: Vec::new(),
+            inputs: Vec::new(),
+            device_rates: HashMap::new(),
+            state: Arc::new(Mutex::new(MockState {
+                default_id: None,
+                volume: 1.0,
+                capture_fails: false,
+                idle_sources: HashSet::new(),
+                capture_sources: Vec::new(),
+                open_captures: 0,
+                excluded_apps: Vec::new(),
+                starving: HashSet::new(),
+                hanging: HashSet::new(),
+                open_fails: HashSet::new(),
+                inactive: HashMap::new(),
+                outputs: HashMap::new(),
+                watchers: HashMap::new(),
+                next_watcher: 0,
+            })),
+        }
+    }
+
+    /// Add an output device
+    pub fn with_device(mut self, id: &str, name: &str) -> Self {
+        self.devices.push((id.to_string(), name.to_string()));
+        self
+    }
+
+    /// Make an output mix at `sample_rate` instead of the capture's rate
+    pub fn with_device_rate(mut self, id: &str, sample_rate: u32) -> Self {
+        self.device_rates.insert(id.to_string(), sample_rate);
+        self
+    }
+
+    /// Add an input device; the first one added is the default input
+    pub fn with_input(mut self, id: &str, name: &str) -> Self {
+        self.inputs.push((id.to_string(), name.to_string()));
+        self
+    }
+
+    /// Label captured and rendered audio with another encoding, e.g. to
+    /// simulate a bitstream (the generated samples stay the same)
+    pub fn with_encoding(mut self, encoding: SampleEncoding) -> Self {
+        self.format.encoding = encoding;
+        self
+    }
+
+    /// Capture format
+    pub fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    /// Change the default output and notify device watchers
+    pub fn set_default_device(&self, device_id: &str) {
+        self.state.lock().default_id = Some(device_id.to_string());
+        self.send_device_event(DeviceEvent::DefaultChanged {
+            data_flow: 0, // eRender
+            role: 0,      // eConsole
+            device_id: device_id.to_string(),
+        });
+    }
+
+    /// Notify device watchers of `event`, e.g. a device being unplugged
+    pub fn send_device_event(&self, event: DeviceEvent) {
+        self.state
+            .lock()
+            .watchers
+            .retain(|_, tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Set the volume reported for the default output
+    pub fn set_volume(&self, volume: f32) {
+        self.state.lock().volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Make captures of `source` (None = default output) deliver nothing, like
+    /// a loopback of an output playing nothing
+    pub fn set_source_idle(&self, source: Option<&str>, idle: bool) {
+        let source = source.map(str::to_string);
+        let mut state = self.state.lock();
+        if idle {
+            state.idle_sources.insert(source);
+        } else {
+            state.idle_sources.remove(&source);
+        }
+    }
+
+    /// Make every capture read fail (e.g. to simulate an audio service restart)
+    pub fn set_capture_fails(&self, fails: bool) {
+        self.state.lock().capture_fails = fails;
+    }
+
+    /// Sources of the captures opened so far (None = default output)
+    pub fn capture_sources(&self) -> Vec<Option<String>> {
+        self.state.lock().capture_sources.clone()
+    }
+
+    /// Number of captures opened and not yet dropped
+    pub fn open_captures(&self) -> usize {
+        self.state.lock().open_captures
+    }
+
+    /// Apps left out of each app capture opened so far, in order
+    pub fn excluded_apps(&self) -> Vec<Vec<String>> {
+        self.state.lock().excluded_apps.clone()
+    }
+
+    /// Make a device report an empty buffer on every check, like a device
+    /// that keeps underrunning
+    pub fn set_starving(&self, device_id: &str, starving: bool) {
+        let mut state = self.state.lock();
+        if starving {
+            state.starving.insert(device_id.to_string());
+        } else {
+            state.starving.remove(device_id);
+        }
+    }
+
+    /// Make every write to a device block until cleared, like a driver call
+    /// that never returns
+    pub fn set_hanging(&self, device_id: &str, hanging: bool) {
+        let mut state = self.state.lock();
+        if hanging {
+            state.hanging.insert(device_id.to_string());
+        } else {
+            state.hanging.remove(device_id);
+        }
+    }
+
+    /// Make opening a device fail with a device error, like a driver
+    /// rejecting the stream
+    pub fn set_open_fails(&self, device_id: &str, fails: bool) {
+        let mut state = self.state.lock();
+        if fails {
+            state.open_fails.insert(device_id.to_string());
+        } else {
+            state.open_fails.remove(device_id);
+        }
+    }
+
+    /// Change a device's state and notify device watchers
+    ///
+    /// An inactive device stays listed, like one unplugged between listing
+    /// and opening, but can't be opened.
+    pub fn set_device_state(&self, device_id: &str, state: DeviceState) {
+        {
+            let mut shared = self.state.lock();
+            if state.is_active() {
+                shared.inactive.remove(device_id);
+            } else {
+                shared.inactive.insert(device_id.to_string(), state);
+            }
+        }
+        self.send_device_event(DeviceEvent::StateChanged {
+            device_id: device_id.to_string(),
+            new_state: state,
+        });
+    }
+
+    /// Audio received so far by the renderer for `device_id`
+    pub fn output(&self, device_id: &str) -> Option<MockOutput> {
+        self.state.lock().outputs.get(device_id).cloned()
+    }
+}
+
+impl AudioBackend for MockBackend {
+    type Capture = MockCapture;
+    type Renderer = MockRenderer;
+    type Volume = MockVolume;
+    type Monitor = MockMonitor;
+
+    fn open_capture(&self, source_id: Option<&str>) -> Result<Self::Capture> {
+        if let Some(id) = source_id {
+            if !self
+                .devices
+                .iter()
+                .chain(&self.inputs)
+                .any(|(device_id, _)| device_id == id)
+            {
+                return Err(WemuxError::DeviceNotFound(id.to_string()));
+            }
+        }
+        {
+            let mut state = self.state.lock();
+            state.capture_sources.push(source_id.map(str::to_string));
+            state.open_captures += 1;
+        }
+
+        Ok(MockCapture {
+            source: source_id.map(str::to_string),
+            format: self.format.clone(),
+            signal: self.signal,
+            state: self.state.clone(),
+            started_at: None,
+            frames_produced: 0,
+            phase: 0.0,
+            noise_seed: 0x2545_f491,
+        })
+    }
+
+    /// Generates the same signal as [`open_capture`](Self::open_capture);
+    /// the excluded apps are only recorded
+    fn open_app_capture(
+        &self,
+        source_id: Option<&str>,
+        exclude_apps: &[String],
+    ) -> Result<Self::Capture> {
+        let capture = self.open_capture(source_id)?;
+        self.state.lock().excluded_apps.push(exclude_apps.to_vec());
+        Ok(capture)
+    }
+
+    fn open_renderer(&self, device_id: &str, tuning: &BufferTuning) -> Result<Self::Renderer> {
+        let (id, name) = self
+            .devices
+            .iter()
+            .find(|(id, _)| id == device_id)
+            .cloned()
+            .ok_or_else(|| WemuxError::DeviceNotFound(device_id.to_string()))?;
+
+        let mut state = self.state.lock();
+        if let Some(&inactive) = state.inactive.get(&id) {
+            return Err(WemuxError::DeviceInactive {
+                device_id: id,
+                state: inactive,
+            });
+        }
+        if state.open_fails.contains(&id) {
+            return Err(WemuxError::device_error(id, "Simulated open failure"));
+        }
+        state.outputs.insert(
+            id.clone(),
+            MockOutput {
+                tuning: *tuning,
+                ..Default::default()
+            },
+        );
+
+        let format = AudioFormat {
+            sample_rate: self
+                .device_rates
+                .get(&id)
+                .copied()
+                .unwrap_or(self.format.sample_rate),
+            ..self.format.clone()
+        };
+        Ok(MockRenderer {
+            id,
+            name,
+            format,
+            state: self.state.clone(),
+            started_at: None,
+            frames_queued: 0,
+            positive: false,
+            since_crossing: 0,
+        })
+    }
+
+    fn open_volume(&self, _source_id: Option<&str>) -> Result<Self::Volume> {
+        Ok(MockVolume {
+            state: self.state.clone(),
+        })
+    }
+
+    fn watch_devices(&self, event_tx: Sender<DeviceEvent>) -> Result<Self::Monitor> {
+        let mut state = self.state.lock();
+        let id = state.next_watcher;
+        state.next_watcher += 1;
+        state.watchers.insert(id, event_tx);
+
+        Ok(MockMonitor {
+            id,
+            state: self.state.clone(),
+        })
+    }
+
+    fn output_devices(&self) -> Result<Vec<DeviceInfo>> {
+        let default_id = self.state.lock().default_id.clone();
+
+        Ok(self
+            .devices
+            .iter()
+            .map(|(id, name)| DeviceInfo {
+                id: id.clone(),
+                name: name.clone(),
+                is_hdmi: HdmiFilter::is_hdmi_device(name) || HdmiFilter::is_hdmi_device_id(id),
+                is_default: default_id.as_deref() == Some(id.as_str()),
+                container_id: None,
+                is_bluetooth: false,
+            })
+            .collect())
+    }
+
+    fn default_output_id(&self) -> Option<String> {
+        self.state.lock().default_id.clone()
+    }
+
+    fn input_devices(&self) -> Result<Vec<DeviceInfo>> {
+        Ok(self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, (id, name))| DeviceInfo {
+                id: id.clone(),
+                name: name.clone(),
+                is_hdmi: false,
+                is_default: i == 0,
+                container_id: None,
+                is_bluetooth: false,
+            })
+            .collect())
+    }
+
+    fn default_input_id(&self) -> Option<String> {
+        self.inputs.first().map(|(id, _)| id.clone())
+    }
+}
+
+/// Frames a clock started at `started_at` has advanced by now
+fn elapsed_frames(started_at: Instant, sample_rate: u32) -> u64 {
+    (started_at.elapsed().as_secs_f64() * sample_rate as f64) as u64
+}
+
+/// Capture producing [`MockSignal`] samples in real time
+pub struct MockCapture {
+    source: Option<String>,
+    format: AudioFormat,
+    signal: MockSignal,
+    state: Arc<Mutex<MockState>>,
+    started_at: Option<Instant>,
+    frames_produced: u64,
+    phase: f32,
+    noise_seed: u32,
+}
+
+impl MockCapture {
+    /// Write `frames` frames of the signal into `dest` as f32 samples
+    fn generate(&mut self, dest: &mut [u8], frames: usize) {
+        let channels = self.format.channels as usize;
+        let step = match self.signal {
+            MockSignal::Sine { frequency, .. } => TAU * frequency / self.format.sample_rate as f32,
+            _ => 0.0,
+        };
+
+        for frame in dest
+            .chunks_exact_mut(self.format.block_align as usize)
+            .take(frames)
+        {
+            let value = match self.signal {
+                MockSignal::Sine { amplitude, .. } => {
+                    let value = amplitude * self.phase.sin();
+                    self.phase = (self.phase + step) % TAU;
+                    value
+                }
+                MockSignal::Noise { amplitude } => {
+                    // xorshift32 mapped to [-1.0, 1.0)
+                    self.noise_seed ^= self.noise_seed << 13;
+                    self.noise_seed ^= self.noise_seed >> 17;
+                    self.noise_seed ^= self.noise_seed << 5;
+                    amplitude * (self.noise_seed as f32 / u32::MAX as f32 * 2.0 - 1.0)
+                }
+                MockSignal::Silence => 0.0,
+            };
+
+            for sample in frame.chunks_exact_mut(4).take(channels) {
+                sample.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
+
+impl CaptureBackend for MockCapture {
+    fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.started_at.is_none() {
+            self.started_at = Some(Instant::now());
+            self.frames_produced = 0;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.started_at = None;
+        Ok(())
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        if self.state.lock().capture_fails {
+            return Err(WemuxError::device_error(
+                "mock-capture",
+                "Simulated failure",
+            ));
+        }
+
+        let idle = self.state.lock().idle_sources.contains(&self.source);
+        let Some(started_at) = self.started_at.filter(|_| !idle) else {
+            thread::sleep(Duration::from_millis(timeout_ms as u64));
+            return Ok(0);
+        };
+
+        // Deliver audio in whole periods, waiting for the next one if needed
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+        let due = loop {
+            let due = elapsed_frames(started_at, self.format.sample_rate) - self.frames_produced;
+            if due >= MOCK_PERIOD_FRAMES {
+                break due;
+            }
+            if Instant::now() >= deadline {
+                return Ok(0);
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        let frames = (due as usize).min(dest.len() / self.format.block_align as usize);
+        self.generate(dest, frames);
+        self.frames_produced += frames as u64;
+
+        Ok(self.format.frames_to_bytes(frames as u32))
+    }
+}
+
+/// Renderer consuming audio in real time and recording what it received
+pub struct MockRenderer {
+    id: String,
+    name: String,
+    format: AudioFormat,
+    state: Arc<Mutex<MockState>>,
+    started_at: Option<Instant>,
+    /// Frames handed to the device since start, including underrun gaps
+    frames_queued: u64,
+    /// Whether the last nonzero sample of the first channel was positive
+    positive: bool,
+    /// Frames written since its sign last changed
+    since_crossing: u32,
+}
+
+impl MockRenderer {
+    /// Frames queued but not yet played
+    fn padding(&mut self) -> u64 {
+        let played = self
+            .started_at
+            .map(|t| elapsed_frames(t, self.format.sample_rate))
+            .unwrap_or(0);
+        // An underrun plays silence, so the queue never lags the play position
+        self.frames_queued = self.frames_queued.max(played);
+        self.frames_queued - played
+    }
+
+    /// Block while the test marks the device as hanging
+    fn wait_while_hanging(&self) {
+        while self.state.lock().hanging.contains(&self.id) {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn update_output(&self, update: impl FnOnce(&mut MockOutput)) {
+        if let Some(output) = self.state.lock().outputs.get_mut(&self.id) {
+            update(output);
+        }
+    }
+}
+
+impl RenderBackend for MockRenderer {
+    fn device_id(&self) -> &str {
+        &self.id
+    }
+
+    fn device_name(&self) -> &str {
+        &self.name
+    }
+
+    fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    fn latency(&self) -> DeviceLatency {
+        DeviceLatency {
+            capabilities: HardwareCapabilities::default(),
+            buffer_ms: (MOCK_BUFFER_FRAMES * 1000 / MOCK_SAMPLE_RATE as u64) as u32,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.started_at.is_none() {
+            self.started_at = Some(Instant::now());
+            self.frames_queued = 0;
+            self.update_output(|o| o.running = true);
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if self.started_at.take().is_some() {
+            self.update_output(|o| o.running = false);
+        }
+        Ok(())
+    }
+
+    fn write_frames(&mut self, data: &[u8], timeout_ms: u32) -> Result<u32> {
+        self.wait_while_hanging();
+        if self.started_at.is_none() {
+            return Err(WemuxError::device_error(&self.id, "Renderer not running"));
+        }
+
+        let mut space = MOCK_BUFFER_FRAMES - self.padding();
+        if space == 0 {
+            // Wait for the device to play a period, like waiting on the buffer event
+            thread::sleep(Duration::from_millis((timeout_ms as u64).min(10)));
+            space = MOCK_BUFFER_FRAMES - self.padding();
+            if space == 0 {
+                return Ok(0);
+            }
+        }
+
+        let frames = (self.format.bytes_to_frames(data.len()) as u64).min(space) as u32;
+        let bytes = self.format.frames_to_bytes(frames);
+        let sample = |s: &[u8]| f32::from_le_bytes([s[0], s[1], s[2], s[3]]);
+        let peak = data[..bytes]
+            .chunks_exact(4)
+            .map(|s| sample(s).abs())
+            .fold(0.0f32, f32::max);
+        let mut half_periods = Vec::new();
+        for frame in data[..bytes].chunks_exact(self.format.block_align as usize) {
+            let value = sample(frame);
+            self.since_crossing += 1;
+            if value != 0.0 && (value > 0.0) != self.positive {
+                self.positive = value > 0.0;
+                half_periods.push(self.since_crossing);
+                self.since_crossing = 0;
+            }
+        }
+
+        self.frames_queued += frames as u64;
+        self.update_output(|o| {
+            o.frames_written += frames as u64;
+            o.peak = o.peak.max(peak);
+            o.half_periods.extend(half_periods);
+        });
+
+        Ok(frames)
+    }
+
+    fn write_silence(&mut self, frames: u32) -> Result<()> {
+        self.wait_while_hanging();
+        if self.started_at.is_none() {
+            return Ok(());
+        }
+
+        let frames = (frames as u64).min(MOCK_BUFFER_FRAMES - self.padding());
+        self.frames_queued += frames;
+        self.update_output(|o| o.silence_frames += frames);
+
+        Ok(())
+    }
+
+    fn padding_frames(&self) -> Result<u32> {
+        if self.state.lock().starving.contains(&self.id) {
+            return Ok(0);
+        }
+        let played = self
+            .started_at
+            .map(|t| elapsed_frames(t, self.format.sample_rate))
+            .unwrap_or(0);
+        Ok(self.frames_queued.saturating_sub(played) as u32)
+    }
+
+    fn device_position(&self) -> Result<DevicePosition> {
+        // Every mock device plays at exactly its rate, against a clock
+        // shared by all of them like the performance counter
+        static EPOCH: OnceLock<Instant> = OnceLock::new();
+        let epoch = *EPOCH.get_or_init(Instant::now);
+        let now = Instant::now();
+        let frames = self.started_at.map_or(0, |started| {
+            (now.saturating_duration_since(started).as_secs_f64() * self.format.sample_rate as f64)
+                as u64
+        });
+        Ok(DevicePosition {
+            frames,
+            time: now.duration_since(epoch),
+        })
+    }
+
+    fn set_error(&mut self, _message: &str) {}
+}
+
+impl Drop for MockCapture {
+    fn drop(&mut self) {
+        self.state.lock().open_captures -= 1;
+    }
+}
+
+impl Drop for MockRenderer {
+    fn drop(&mut self) {
+        let _ = RenderBackend::stop(self);
+    }
+}
+
+/// Volume source reporting the level set with [`MockBackend::set_volume`]
+pub struct MockVolume {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl VolumeSource for MockVolume {
+    fn effective_volume(&self) -> f32 {
+        self.state.lock().volume
+    }
+}
+
+/// Device event subscription, removed when dropped
+pub struct MockMonitor {
+    id: u64,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Drop for MockMonitor {
+    fn drop(&mut self) {
+        self.state.lock().watchers.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_generation() {
+        let backend = MockBackend::new(MockSignal::Sine {
+            frequency: 1000.0,
+            amplitude: 0.5,
+        });
+        let mut capture = backend.open_capture(None).unwrap();
+        let mut buf = vec![0u8; 48 * 8];
+        capture.generate(&mut buf, 48);
+
+        let samples = samples(&buf);
+        // Both channels carry the same value
+        assert!(samples.chunks(2).all(|f| f[0] == f[1]));
+        // One full 1kHz cycle in 48 frames, peaking at the amplitude
+        let peak = samples.iter().fold(0.0f32, |a, s| a.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.01);
+        assert!(samples[0].abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_noise_generation() {
+        let backend = MockBackend::new(MockSignal::Noise { amplitude: 0.25 });
+        let mut capture = backend.open_capture(None).unwrap();
+        let mut buf = vec![0u8; 1000 * 8];
+        capture.generate(&mut buf, 1000);
+
+        let samples = samples(&buf);
+        assert!(samples.iter().all(|s| s.abs() <= 0.25));
+        assert!(samples.iter().any(|s| *s != samples[0]));
+    }
+
+    #[test]
+    fn test_capture_paced_in_real_time() {
+        let backend = MockBackend::new(MockSignal::Silence);
+        let mut capture = backend.open_capture(None).unwrap();
+        let mut buf = vec![0u8; 4096];
+
+        // Not started: nothing to read
+        assert_eq!(capture.read_into(&mut buf, 1).unwrap(), 0);
+
+        capture.start().unwrap();
+        let read = capture.read_into(&mut buf, 100).unwrap();
+        assert!(read >= MOCK_PERIOD_FRAMES as usize * 8);
+
+        backend.set_capture_fails(true);
+        assert!(capture.read_into(&mut buf, 1).is_err());
+    }
+
+    #[test]
+    fn test_renderer_records_output() {
+        let backend = MockBackend::new(MockSignal::Silence).with_device("hdmi-1", "HDMI 1");
+        let tuning = BufferTuning::default();
+        let mut renderer = backend.open_renderer("hdmi-1", &tuning).unwrap();
+        assert!(backend.open_renderer("missing", &tuning).is_err());
+
+        let data: Vec<u8> = [0.75f32, -0.5]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert!(renderer.write_frames(&data, 10).is_err());
+
+        renderer.start().unwrap();
+        assert_eq!(renderer.write_frames(&data, 10).unwrap(), 1);
+        renderer.write_silence(10).unwrap();
+
+        let output = backend.output("hdmi-1").unwrap();
+        assert!(output.running);
+        assert_eq!(output.frames_written, 1);
+        assert_eq!(output.silence_frames, 10);
+        assert_eq!(output.peak, 0.75);
+    }
+
+    #[test]
+    fn test_default_device_events() {
+        let backend = MockBackend::new(MockSignal::Silence)
+            .with_device("hdmi-1", "HDMI 1")
+            .with_device("speakers", "Speakers");
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let monitor = backend.watch_devices(tx).unwrap();
+
+        backend.set_default_device("speakers");
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            DeviceEvent::DefaultChanged { device_id, .. } if device_id == "speakers"
+        ));
+
+        let devices = backend.output_devices().unwrap();
+        assert!(devices[0].is_hdmi && !devices[0].is_default);
+        assert!(!devices[1].is_hdmi && devices[1].is_default);
+
+        drop(monitor);
+        backend.set_default_device("hdmi-1");
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,29 +1,152 @@
 //! Audio capture, rendering, and synchronization
 
+mod announce;
+mod apps;
+mod backend;
 mod buffer;
 mod capture;
+mod channels;
+mod delay;
+mod dsp;
 mod engine;
+mod eq;
+mod fade;
+mod fallback;
 mod hardware;
+mod meter;
+mod mixer;
+mod mock;
+mod player;
+mod realtime;
 mod renderer;
+mod resample;
+mod simd;
+mod stats;
+mod tuning;
 mod volume;
+mod wasapi;
+mod wav;
+mod zone;
 
-pub use buffer::RingBuffer;
+pub use announce::{Announcement, DEFAULT_DUCK_DB};
+pub use apps::AppCapture;
+pub use backend::{AudioBackend, CaptureBackend, RenderBackend, VolumeSource};
+pub use buffer::{ReaderState, RingBuffer};
 pub use capture::LoopbackCapture;
-pub use engine::{AudioEngine, DeviceStatus, EngineConfig, EngineEvent, EngineState};
-pub use hardware::{HardwareCapabilities, LatencyClass};
+pub use channels::{default_channel_mask, layout_name};
+pub use dsp::{DspChain, DspStage, DspStageConfig};
+pub use engine::{
+    AudioEngine, DeviceStatus, EngineConfig, EngineEvent, EngineState, PlannedOutput, SourceType,
+    StartPlan, BLUETOOTH_LATENCY_MS,
+};
+pub use eq::{EqSettings, EQ_BANDS_HZ, MAX_GAIN_DB};
+pub use fade::PAUSE_FADE_MS;
+pub use fallback::{FallbackSource, DEFAULT_FALLBACK_AFTER_SECS};
+pub use hardware::{DeviceLatency, HardwareCapabilities, LatencyClass};
+pub use meter::{SignalLevel, SILENCE_DB};
+pub use mixer::MixSource;
+pub use mock::{
+    MockBackend, MockCapture, MockMonitor, MockOutput, MockRenderer, MockSignal, MockVolume,
+};
+pub use player::FilePlayer;
+pub use realtime::ThreadPriority;
 pub use renderer::{HdmiRenderer, RendererState};
-pub use volume::{apply_volume_f32, VolumeLevel, VolumeTracker};
+pub use resample::{internal_format, INTERNAL_SAMPLE_RATE};
+pub use simd::SimdLevel;
+pub use stats::{EngineStats, RendererStats, ThreadHealth, ThreadState};
+pub use tuning::{BufferPlan, BufferTuning, UnderrunTracker, MAX_BUFFER_MS, MIN_BUFFER_MS};
+pub use volume::{apply_volume_f32, VolumeLevel, VolumeMode, VolumeTracker};
+pub use wasapi::{WasapiBackend, WasapiCapture};
+pub use wav::WavAudio;
+pub use zone::{Zone, ZoneStatus};
+
+use windows::core::GUID;
+use windows::Win32::Media::Audio::{WAVEFORMATEX, WAVEFORMATEXTENSIBLE, WAVE_FORMAT_PCM};
+
+/// `WAVE_FORMAT_IEEE_FLOAT` from mmreg.h
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+/// `WAVE_FORMAT_EXTENSIBLE` from mmreg.h
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// `KSDATAFORMAT_SUBTYPE_PCM` from ksmedia.h
+const SUBTYPE_PCM: GUID = GUID::from_u128(0x00000001_0000_0010_8000_00aa00389b71);
+/// `KSDATAFORMAT_SUBTYPE_IEEE_FLOAT` from ksmedia.h
+const SUBTYPE_IEEE_FLOAT: GUID = GUID::from_u128(0x00000003_0000_0010_8000_00aa00389b71);
+/// Bytes `cbSize` covers in a WAVEFORMATEXTENSIBLE
+const EXTENSIBLE_EXTRA_SIZE: u16 = 22;
+
+/// How the samples of a stream are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleEncoding {
+    /// 32-bit float, the shared-mode mix format
+    #[default]
+    Float,
+    /// Integer PCM
+    Pcm,
+    /// Compressed audio (AC3, DTS, ...) framed as PCM for S/PDIF or HDMI.
+    /// Any change to the bytes, even dropping a frame, corrupts the stream.
+    Bitstream,
+}
 
 /// Audio format information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AudioFormat {
     pub sample_rate: u32,
     pub channels: u16,
     pub bits_per_sample: u16,
     pub block_align: u16,
+    pub encoding: SampleEncoding,
+    /// Speakers of the channels in channel order (`SPEAKER_*` bits from
+    /// ksmedia.h), the default layout for the channel count when the format
+    /// doesn't name them
+    pub channel_mask: u32,
 }
 
 impl AudioFormat {
+    /// Read a format returned by WASAPI
+    ///
+    /// # Safety
+    ///
+    /// `format` must point to a valid WAVEFORMATEX, extended to a
+    /// WAVEFORMATEXTENSIBLE when its tag and size say so.
+    pub(crate) unsafe fn from_wave_format(format: *const WAVEFORMATEX) -> Self {
+        let wave = *format;
+        let tag = wave.wFormatTag;
+        let mut channel_mask = 0;
+        let encoding = match tag {
+            WAVE_FORMAT_EXTENSIBLE if wave.cbSize >= EXTENSIBLE_EXTRA_SIZE => {
+                let extensible = *(format as *const WAVEFORMATEXTENSIBLE);
+                channel_mask = extensible.dwChannelMask;
+                match extensible.SubFormat {
+                    SUBTYPE_IEEE_FLOAT => SampleEncoding::Float,
+                    SUBTYPE_PCM => SampleEncoding::Pcm,
+                    _ => SampleEncoding::Bitstream,
+                }
+            }
+            WAVE_FORMAT_IEEE_FLOAT => SampleEncoding::Float,
+            _ if tag as u32 == WAVE_FORMAT_PCM => SampleEncoding::Pcm,
+            // WAVE_FORMAT_DOLBY_AC3_SPDIF, WAVE_FORMAT_DTS and other codecs
+            _ => SampleEncoding::Bitstream,
+        };
+        // No mask (or 0, "not specified") means the default speakers
+        if channel_mask == 0 {
+            channel_mask = default_channel_mask(wave.nChannels);
+        }
+
+        Self {
+            sample_rate: wave.nSamplesPerSec,
+            channels: wave.nChannels,
+            bits_per_sample: wave.wBitsPerSample,
+            block_align: wave.nBlockAlign,
+            encoding,
+            channel_mask,
+        }
+    }
+
+    /// Whether the stream carries compressed audio that must pass through unchanged
+    pub fn is_bitstream(&self) -> bool {
+        self.encoding == SampleEncoding::Bitstream
+    }
+
     /// Calculate bytes per second
     pub fn bytes_per_second(&self) -> u32 {
         self.sample_rate * self.block_align as u32
@@ -51,6 +174,12 @@ impl std::fmt::Display for AudioFormat {
             f,
             "{}Hz {}ch {}bit",
             self.sample_rate, self.channels, self.bits_per_sample
-        )
+        )?;
+        if self.is_bitstream() {
+            f.write_str(" bitstream")?;
+        } else if let Some(layout) = layout_name(self.channel_mask) {
+            write!(f, " {}", layout)?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,304 @@
+//! Playing an audio file in a loop as the source
+//!
+//! Background music for a multi-room setup needs no application playing it:
+//! the file is decoded into memory, converted to the outputs' format and
+//! handed to the engine in real time like captured audio, starting over at
+//! the end. WAV files are read by `WavAudio`, other WAV files and FLAC, MP3
+//! and Ogg Vorbis are decoded with symphonia.
+
+use crate::audio::backend::CaptureBackend;
+use crate::audio::{AudioFormat, SampleEncoding, WavAudio};
+use crate::error::{Result, WemuxError};
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Audio handed out per read while due, in milliseconds (like a WASAPI period)
+const PERIOD_MS: u64 = 10;
+
+/// An audio file played in a loop in real time
+pub struct FilePlayer {
+    format: AudioFormat,
+    /// The whole file in `format`, interleaved
+    samples: Vec<f32>,
+    /// Next frame to play
+    position: usize,
+    started_at: Option<Instant>,
+    frames_produced: u64,
+}
+
+impl FilePlayer {
+    /// Load an audio file, converted to `format`
+    ///
+    /// `format` must be 32-bit float, the shared-mode mix format. A mono
+    /// file plays on every channel; channels the outputs don't have are
+    /// dropped, and those the file doesn't have stay silent.
+    pub fn open(path: &Path, format: &AudioFormat) -> Result<Self> {
+        if format.encoding != SampleEncoding::Float || format.bits_per_sample != 32 {
+            return Err(WemuxError::FormatMismatch {
+                expected: "32-bit float".to_string(),
+                actual: format.to_string(),
+            });
+        }
+        let data = std::fs::read(path).map_err(|e| {
+            WemuxError::InvalidConfig(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        // The own parser also takes WAV files of recorders that never fill
+        // in the RIFF size, which symphonia rejects
+        let wav = match WavAudio::parse(&data) {
+            Ok(wav) => wav,
+            Err(_) => decode(data, path.extension().and_then(|e| e.to_str()))
+                .map_err(|e| WemuxError::InvalidConfig(format!("{}: {}", path.display(), e)))?,
+        };
+        if wav.frames() == 0 {
+            return Err(WemuxError::InvalidConfig(format!(
+                "{}: no audio data",
+                path.display()
+            )));
+        }
+
+        Ok(Self {
+            format: format.clone(),
+            samples: convert(&wav, format),
+            position: 0,
+            started_at: None,
+            frames_produced: 0,
+        })
+    }
+
+    /// Number of frames in one pass through the file
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.format.channels as usize
+    }
+}
+
+/// Decode the first audio track of a file's contents to float samples
+///
+/// `extension` helps to tell the container apart. Packets that fail to
+/// decode are skipped, as players do.
+fn decode(data: Vec<u8>, extension: Option<&str>) -> std::result::Result<WavAudio, String> {
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("unsupported file: {}", e))?;
+    let mut reader = probed.format;
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("no audio track")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("unsupported codec: {}", e))?;
+
+    let mut samples = Vec::new();
+    let mut spec = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        let decoded_spec = *decoded.spec();
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, decoded_spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+        spec = Some(decoded_spec);
+    }
+    let spec = spec.ok_or("no audio data")?;
+    Ok(WavAudio {
+        samples,
+        channels: spec.channels.count() as u16,
+        sample_rate: spec.rate,
+    })
+}
+
+/// Resample `wav` to the rate of `format` (linearly, wrapping around at the
+/// end so the loop has no seam) and map its channels
+fn convert(wav: &WavAudio, format: &AudioFormat) -> Vec<f32> {
+    let in_channels = wav.channels as usize;
+    let out_channels = format.channels as usize;
+    let in_frames = wav.frames();
+    let ratio = wav.sample_rate as f64 / format.sample_rate as f64;
+    let out_frames = ((in_frames as f64 / ratio).round() as usize).max(1);
+
+    let mut samples = Vec::with_capacity(out_frames * out_channels);
+    for frame in 0..out_frames {
+        let source = frame as f64 * ratio;
+        let first = source as usize % in_frames;
+        let next = (first + 1) % in_frames;
+        let fraction = source.fract() as f32;
+        for channel in 0..out_channels {
+            let in_channel = match in_channels {
+                1 => 0,
+                _ if channel < in_channels => channel,
+                _ => {
+                    samples.push(0.0);
+                    continue;
+                }
+            };
+            let a = wav.samples[first * in_channels + in_channel];
+            let b = wav.samples[next * in_channels + in_channel];
+            samples.push(a + (b - a) * fraction);
+        }
+    }
+    samples
+}
+
+impl CaptureBackend for FilePlayer {
+    fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.started_at.is_none() {
+            self.started_at = Some(Instant::now());
+            self.frames_produced = 0;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.started_at = None;
+        Ok(())
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        let Some(started_at) = self.started_at else {
+            thread::sleep(Duration::from_millis(timeout_ms as u64));
+            return Ok(0);
+        };
+
+        // Hand out audio in whole periods, waiting for the next one if needed
+        let period = self.format.sample_rate as u64 * PERIOD_MS / 1000;
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+        let due = loop {
+            let elapsed =
+                (started_at.elapsed().as_secs_f64() * self.format.sample_rate as f64) as u64;
+            let due = elapsed.saturating_sub(self.frames_produced);
+            if due >= period {
+                break due;
+            }
+            if Instant::now() >= deadline {
+                return Ok(0);
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        let channels = self.format.channels as usize;
+        let frames = (due as usize).min(dest.len() / self.format.block_align as usize);
+        for frame in dest
+            .chunks_exact_mut(self.format.block_align as usize)
+            .take(frames)
+        {
+            let start = self.position * channels;
+            for (sample, value) in frame
+                .chunks_exact_mut(4)
+                .zip(&self.samples[start..start + channels])
+            {
+                sample.copy_from_slice(&value.to_le_bytes());
+            }
+            self.position = (self.position + 1) % self.frames();
+        }
+        self.frames_produced += frames as u64;
+
+        Ok(self.format.frames_to_bytes(frames as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::default_channel_mask;
+
+    fn format(sample_rate: u32, channels: u16) -> AudioFormat {
+        AudioFormat {
+            sample_rate,
+            channels,
+            bits_per_sample: 32,
+            block_align: channels * 4,
+            encoding: SampleEncoding::Float,
+            channel_mask: default_channel_mask(channels),
+        }
+    }
+
+    #[test]
+    fn test_convert_rate_and_channels() {
+        let wav = WavAudio {
+            samples: vec![0.0, 0.5, 1.0, 0.5],
+            channels: 1,
+            sample_rate: 24_000,
+        };
+
+        // Twice the rate: every other frame is interpolated, the last one
+        // towards the first frame of the next pass
+        assert_eq!(
+            convert(&wav, &format(48_000, 2)),
+            [
+                0.0, 0.0, 0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0, 1.0, 0.75, 0.75, 0.5, 0.5, 0.25,
+                0.25
+            ]
+        );
+
+        let stereo = WavAudio {
+            samples: vec![0.1, 0.2, 0.3, 0.4],
+            channels: 2,
+            sample_rate: 48_000,
+        };
+        assert_eq!(
+            convert(&stereo, &format(48_000, 3)),
+            [0.1, 0.2, 0.0, 0.3, 0.4, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_decode_wav() {
+        // 16-bit stereo PCM, two frames
+        let pcm: Vec<u8> = [0i16, 16384, -16384, 32767]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&(36 + pcm.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        for field in [16u32, 0x0002_0001, 44_100, 44_100 * 4, 0x0010_0004] {
+            wav.extend_from_slice(&field.to_le_bytes());
+        }
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&pcm);
+
+        let audio = decode(wav.clone(), None).unwrap();
+        assert_eq!(audio, WavAudio::parse(&wav).unwrap());
+        assert_eq!(audio.channels, 2);
+        assert_eq!(audio.sample_rate, 44_100);
+
+        assert!(decode(b"not audio".to_vec(), Some("mp3")).is_err());
+    }
+}
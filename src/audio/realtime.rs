@@ -0,0 +1,175 @@
+//! Performance capture mode (`EngineConfig::performance_capture`)
+//!
+//! Normally the capture thread sleeps until WASAPI signals a packet, and the
+//! scheduler can take a millisecond or more to wake it on a busy machine. In
+//! performance mode the thread runs as an MMCSS "Pro Audio" task at critical
+//! priority, pinned to a core of its own counted from the last one, and polls
+//! the capture event instead of sleeping on it. Audio moves on the moment it
+//! arrives, at the price of keeping that core fully busy.
+//!
+//! Render threads can be given a higher priority and a core of their own
+//! (`EngineConfig::render_priority` and `render_cores`) on machines where
+//! background work delays them into dropouts.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{info, warn};
+use windows::core::w;
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::System::Threading::{
+    AvRevertMmThreadCharacteristics, AvSetMmThreadCharacteristicsW, AvSetMmThreadPriority,
+    GetCurrentThread, SetThreadAffinityMask, SetThreadPriority, AVRT_PRIORITY_CRITICAL,
+    THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_TIME_CRITICAL,
+};
+
+/// Scheduling priority of the render threads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadPriority {
+    /// Like any other thread
+    #[default]
+    Normal,
+    /// Ahead of normal threads
+    High,
+    /// Ahead of everything but other time-critical threads
+    TimeCritical,
+}
+
+/// Raise the calling render thread's priority and pin it to `core`
+///
+/// Steps that fail are logged and skipped. The settings end with the thread.
+pub(crate) fn prioritize_render_thread(
+    device_name: &str,
+    priority: ThreadPriority,
+    core: Option<usize>,
+) {
+    let level = match priority {
+        ThreadPriority::Normal => None,
+        ThreadPriority::High => Some(THREAD_PRIORITY_HIGHEST),
+        ThreadPriority::TimeCritical => Some(THREAD_PRIORITY_TIME_CRITICAL),
+    };
+    if let Some(level) = level {
+        if let Err(e) = unsafe { SetThreadPriority(GetCurrentThread(), level) } {
+            warn!("Failed to raise priority of {}: {}", device_name, e);
+        }
+    }
+
+    let Some(core) = core else {
+        return;
+    };
+    let cores = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(usize::BITS as usize);
+    if core >= cores {
+        warn!(
+            "Core {} for {} doesn't exist (cores 0 - {}), not pinning",
+            core,
+            device_name,
+            cores - 1
+        );
+    } else if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << core) } == 0 {
+        warn!("Failed to pin {} to core {}", device_name, core);
+    } else {
+        info!("Rendering {} on core {}", device_name, core);
+    }
+}
+
+/// Cores taken by threads in performance mode, one bit each
+static PINNED_CORES: AtomicUsize = AtomicUsize::new(0);
+
+/// Take the highest core of `cores` not marked in `taken`, never core 0
+fn claim_core(taken: &AtomicUsize, cores: usize) -> Option<usize> {
+    (1..cores).rev().find(|&core| {
+        let bit = 1 << core;
+        taken.fetch_or(bit, Ordering::Relaxed) & bit == 0
+    })
+}
+
+/// Hand a core taken with `claim_core` back
+fn release_core(taken: &AtomicUsize, core: usize) {
+    taken.fetch_and(!(1 << core), Ordering::Relaxed);
+}
+
+/// Performance mode of the calling thread, left again on drop
+pub(crate) struct PerformanceMode {
+    mmcss: Option<HANDLE>,
+    core: Option<usize>,
+}
+
+impl PerformanceMode {
+    /// Register the calling thread with MMCSS and pin it to a core
+    ///
+    /// Steps that fail are logged and skipped; the thread still polls.
+    pub(crate) fn enter() -> Self {
+        let mmcss = unsafe {
+            let mut task_index = 0u32;
+            match AvSetMmThreadCharacteristicsW(w!("Pro Audio"), &mut task_index) {
+                Ok(handle) => {
+                    if let Err(e) = AvSetMmThreadPriority(handle, AVRT_PRIORITY_CRITICAL) {
+                        warn!("Failed to raise MMCSS priority: {}", e);
+                    }
+                    Some(handle)
+                }
+                Err(e) => {
+                    warn!("Failed to register with MMCSS: {}", e);
+                    None
+                }
+            }
+        };
+
+        // Core 0 handles most interrupts, so it is never taken
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(usize::BITS as usize);
+        let core = claim_core(&PINNED_CORES, cores);
+        let core = match core {
+            Some(core) if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << core) } == 0 => {
+                warn!("Failed to pin capture thread to core {}", core);
+                release_core(&PINNED_CORES, core);
+                None
+            }
+            Some(core) => {
+                info!("Performance capture on core {}", core);
+                Some(core)
+            }
+            None => {
+                warn!("No free core for performance capture, not pinning");
+                None
+            }
+        };
+
+        Self { mmcss, core }
+    }
+}
+
+impl Drop for PerformanceMode {
+    fn drop(&mut self) {
+        if let Some(core) = self.core {
+            release_core(&PINNED_CORES, core);
+        }
+        if let Some(handle) = self.mmcss {
+            unsafe {
+                let _ = AvRevertMmThreadCharacteristics(handle);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_released_core_is_handed_out_again() {
+        let taken = AtomicUsize::new(0);
+        assert_eq!(claim_core(&taken, 4), Some(3));
+        assert_eq!(claim_core(&taken, 4), Some(2));
+        release_core(&taken, 3);
+        // A count would hand out core 2 a second time here
+        assert_eq!(claim_core(&taken, 4), Some(3));
+        assert_eq!(claim_core(&taken, 4), Some(1));
+        assert_eq!(claim_core(&taken, 4), None);
+    }
+}
@@ -1,8 +1,11 @@
 //! WASAPI render client for audio output to HDMI devices
 
-use crate::audio::AudioFormat;
+use crate::audio::{AudioFormat, BufferTuning, DeviceLatency, HardwareCapabilities, LatencyClass};
+use crate::device::DeviceEnumerator;
 use crate::error::{Result, WemuxError};
+use crate::sync::DevicePosition;
 use std::ptr;
+use std::time::Duration;
 use tracing::{debug, info, trace, warn};
 use windows::{
     core::PCWSTR,
@@ -10,7 +13,7 @@ use windows::{
         Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
         Foundation::{HANDLE, WAIT_OBJECT_0},
         Media::Audio::{
-            IAudioClient, IAudioRenderClient, IMMDevice, AUDCLNT_SHAREMODE_SHARED,
+            IAudioClient, IAudioClock, IAudioRenderClient, IMMDevice, AUDCLNT_SHAREMODE_SHARED,
             AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
         },
         System::{
@@ -42,9 +45,13 @@ pub struct HdmiRenderer {
     device_name: String,
     audio_client: IAudioClient,
     render_client: IAudioRenderClient,
+    /// Position of the stream, in `clock_frequency` units per second
+    clock: IAudioClock,
+    clock_frequency: u64,
     format: AudioFormat,
     event: HANDLE,
     buffer_frames: u32,
+    capabilities: HardwareCapabilities,
     state: RendererState,
 }
 
@@ -55,6 +62,11 @@ unsafe impl Send for HdmiRenderer {}
 impl HdmiRenderer {
     /// Create a new renderer for the given device
     pub fn new(device: &IMMDevice) -> Result<Self> {
+        Self::with_tuning(device, &BufferTuning::default())
+    }
+
+    /// Create a renderer whose WASAPI buffer is grown by `tuning`
+    pub fn with_tuning(device: &IMMDevice, tuning: &BufferTuning) -> Result<Self> {
         unsafe {
             // Get device ID
             let device_id = {
@@ -76,14 +88,7 @@ impl HdmiRenderer {
 
             // Get mix format
             let format_ptr = audio_client.GetMixFormat()?;
-            let format_ref = &*format_ptr;
-
-            let format = AudioFormat {
-                sample_rate: format_ref.nSamplesPerSec,
-                channels: format_ref.nChannels,
-                bits_per_sample: format_ref.wBitsPerSample,
-                block_align: format_ref.nBlockAlign,
-            };
+            let format = AudioFormat::from_wave_format(format_ptr);
 
             info!("Renderer format for {}: {}", device_name, format);
 
@@ -91,12 +96,15 @@ impl HdmiRenderer {
             let event = CreateEventW(None, false, false, None)?;
 
             // Auto-calculate optimal buffer duration based on hardware capabilities
-            let buffer_duration = crate::audio::HardwareCapabilities::query(&audio_client)
-                .map(|caps| caps.optimal_buffer_duration())
-                .unwrap_or_else(|e| {
-                    debug!("Failed to query hardware capabilities: {}, using default 35ms", e);
-                    350_000i64 // 35ms fallback
-                });
+            let mut capabilities = HardwareCapabilities::query(&audio_client).unwrap_or_else(|e| {
+                debug!("Failed to query capabilities: {}, using defaults", e);
+                HardwareCapabilities::default()
+            });
+            if DeviceEnumerator::is_bluetooth(device) {
+                info!("{} is a Bluetooth device, using high latency", device_name);
+                capabilities.latency_class = LatencyClass::HighLatency;
+            }
+            let buffer_duration = capabilities.tuned_buffer_duration(tuning.extra_buffer_ms);
 
             audio_client.Initialize(
                 AUDCLNT_SHAREMODE_SHARED,
@@ -119,6 +127,8 @@ impl HdmiRenderer {
 
             // Get render client
             let render_client: IAudioRenderClient = audio_client.GetService()?;
+            let clock: IAudioClock = audio_client.GetService()?;
+            let clock_frequency = clock.GetFrequency()?;
 
             // Free format memory
             windows::Win32::System::Com::CoTaskMemFree(Some(format_ptr as *const _ as *const _));
@@ -128,9 +138,12 @@ impl HdmiRenderer {
                 device_name,
                 audio_client,
                 render_client,
+                clock,
+                clock_frequency,
                 format,
                 event,
                 buffer_frames,
+                capabilities,
                 state: RendererState::Idle,
             })
         }
@@ -184,6 +197,14 @@ impl HdmiRenderer {
         self.buffer_frames
     }
 
+    /// Get the device's capabilities and the buffer it was opened with
+    pub fn latency(&self) -> DeviceLatency {
+        DeviceLatency {
+            capabilities: self.capabilities.clone(),
+            buffer_ms: (self.buffer_frames as u64 * 1000 / self.format.sample_rate as u64) as u32,
+        }
+    }
+
     /// Start rendering
     pub fn start(&mut self) -> Result<()> {
         if self.state == RendererState::Running {
@@ -293,17 +314,24 @@ impl HdmiRenderer {
         }
     }
 
-    /// Get current buffer position for synchronization
-    pub fn get_buffer_position(&self) -> Result<u64> {
-        unsafe {
-            let mut _position: u64 = 0;
-            let mut _qpc: u64 = 0;
+    /// Get frames queued in the device buffer and not yet played
+    pub fn padding_frames(&self) -> Result<u32> {
+        unsafe { Ok(self.audio_client.GetCurrentPadding()?) }
+    }
 
-            // Note: This requires AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM to be useful
-            // For now, we use padding as a proxy
-            let padding = self.audio_client.GetCurrentPadding()?;
-            Ok(padding as u64)
-        }
+    /// Frames the device has played since the stream started, with the
+    /// performance counter time the position was read at
+    pub fn device_position(&self) -> Result<DevicePosition> {
+        let mut position = 0u64;
+        // In 100ns units
+        let mut qpc = 0u64;
+        unsafe { self.clock.GetPosition(&mut position, Some(&mut qpc))? };
+        let frames = position as u128 * self.format.sample_rate as u128
+            / self.clock_frequency.max(1) as u128;
+        Ok(DevicePosition {
+            frames: frames as u64,
+            time: Duration::from_nanos(qpc.saturating_mul(100)),
+        })
     }
 
     /// Set error state
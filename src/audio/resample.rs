@@ -0,0 +1,241 @@
+//! Conversion of captured audio to the engine's internal format
+//!
+//! The engine passes audio on at one fixed rate, [`INTERNAL_SAMPLE_RATE`],
+//! whatever rate the captured device mixes at. Every capture is converted as
+//! it is read: resampled, and its channels mapped when a new default output
+//! has other speakers than the one the engine started with. Switching the
+//! default output to a 44.1kHz device then leaves the ring buffer and every
+//! renderer with the format they were started with. An output mixing at
+//! another rate resamples the audio once more, as the last stage of its
+//! [`DspChain`](crate::audio::DspChain).
+//!
+//! Only 32-bit float audio can be converted; PCM and bitstreams are passed
+//! on in the format they are captured in.
+
+use crate::audio::backend::CaptureBackend;
+use crate::audio::channels::ChannelMap;
+use crate::audio::{AudioFormat, SampleEncoding};
+use crate::error::Result;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Sample rate the engine passes audio on at
+pub const INTERNAL_SAMPLE_RATE: u32 = 48_000;
+
+fn is_float(format: &AudioFormat) -> bool {
+    format.encoding == SampleEncoding::Float && format.bits_per_sample == 32
+}
+
+/// Format the engine passes audio captured in `source` on in
+pub fn internal_format(source: &AudioFormat) -> AudioFormat {
+    if !is_float(source) {
+        return source.clone();
+    }
+    AudioFormat {
+        sample_rate: INTERNAL_SAMPLE_RATE,
+        ..source.clone()
+    }
+}
+
+/// Whether audio captured in `source` can be read in `dest`
+pub(crate) fn can_convert(source: &AudioFormat, dest: &AudioFormat) -> bool {
+    source == dest || (is_float(source) && is_float(dest))
+}
+
+/// Linear resampler for a stream of interleaved 32-bit float frames
+///
+/// Blocks are resampled as one continuous stream: the last frame of a block
+/// is kept to interpolate towards the first frame of the next.
+pub(crate) struct Resampler {
+    channels: usize,
+    /// Source frames per resampled frame
+    step: f64,
+    /// Position of the next resampled frame, in source frames from `last`
+    position: f64,
+    /// Last frame of the previous block
+    last: Vec<f32>,
+}
+
+impl Resampler {
+    pub(crate) fn new(channels: u16, from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            channels: channels as usize,
+            step: from_rate as f64 / to_rate as f64,
+            position: 0.0,
+            last: vec![0.0; channels as usize],
+        }
+    }
+
+    /// Resample the frames in `data` into `out`, replacing its contents
+    pub(crate) fn process(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        out.clear();
+        let frames = data.len() / (self.channels * 4);
+        if frames == 0 {
+            return;
+        }
+        // Frame 0 is the last one of the previous block, 1 the first of this one
+        let sample = |frame: usize, channel: usize| {
+            if frame == 0 {
+                return self.last[channel];
+            }
+            let at = ((frame - 1) * self.channels + channel) * 4;
+            f32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+        };
+
+        while self.position < frames as f64 {
+            let frame = self.position as usize;
+            let fraction = (self.position - frame as f64) as f32;
+            for channel in 0..self.channels {
+                let a = sample(frame, channel);
+                let b = sample(frame + 1, channel);
+                out.extend_from_slice(&(a + (b - a) * fraction).to_le_bytes());
+            }
+            self.position += self.step;
+        }
+        self.position -= frames as f64;
+        let last: Vec<f32> = (0..self.channels).map(|c| sample(frames, c)).collect();
+        self.last = last;
+    }
+}
+
+/// A capture read in another format than it records in
+pub(crate) struct ConvertedCapture<C> {
+    capture: C,
+    format: AudioFormat,
+    channel_map: Option<ChannelMap>,
+    resampler: Option<Resampler>,
+    /// Audio as captured
+    raw: Vec<u8>,
+    /// Captured audio with its channels mapped
+    mapped: Vec<u8>,
+    /// Converted audio not read yet
+    pending: Vec<u8>,
+}
+
+impl<C: CaptureBackend> ConvertedCapture<C> {
+    /// Read `capture` in `format`; audio that can't be converted is read as
+    /// it is captured
+    pub(crate) fn new(capture: C, format: &AudioFormat) -> Self {
+        let source = capture.format().clone();
+        let converts = source != *format && can_convert(&source, format);
+        if converts {
+            info!("Converting captured {} to {}", source, format);
+        } else if source != *format {
+            warn!("Captured {} can't be converted to {}", source, format);
+        }
+        let channel_map = converts
+            .then(|| ChannelMap::between(&source, format))
+            .flatten();
+        let resampler = (converts && source.sample_rate != format.sample_rate)
+            .then(|| Resampler::new(format.channels, source.sample_rate, format.sample_rate));
+        Self {
+            format: if converts { format.clone() } else { source },
+            capture,
+            channel_map,
+            resampler,
+            raw: Vec::new(),
+            mapped: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<C: CaptureBackend> CaptureBackend for ConvertedCapture<C> {
+    fn format(&self) -> &AudioFormat {
+        &self.format
+    }
+
+    fn start(&mut self) -> Result<()> {
+        self.capture.start()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.capture.stop()
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        if self.channel_map.is_none() && self.resampler.is_none() {
+            return self.capture.read_into(dest, timeout_ms);
+        }
+
+        // Resampling up leaves more than fits; it is handed out first
+        if self.pending.is_empty() {
+            self.raw.resize(dest.len(), 0);
+            let bytes = self.capture.read_into(&mut self.raw, timeout_ms)?;
+            let mut data = &self.raw[..bytes];
+            if let Some(map) = &self.channel_map {
+                map.map(data, &mut self.mapped);
+                data = &self.mapped;
+            }
+            match &mut self.resampler {
+                Some(resampler) => resampler.process(data, &mut self.pending),
+                None => self.pending.extend_from_slice(data),
+            }
+        }
+
+        let block_align = self.format.block_align as usize;
+        let len = self.pending.len().min(dest.len()) / block_align * block_align;
+        dest[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        Ok(len)
+    }
+
+    fn capture_delay(&self) -> Duration {
+        self.capture.capture_delay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_resampling_is_continuous_across_blocks() {
+        // A stereo ramp at 44.1kHz, resampled to 48kHz in uneven blocks
+        let ramp: Vec<u8> = (0..441)
+            .flat_map(|n| [n as f32, -(n as f32)])
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mut resampler = Resampler::new(2, 44_100, INTERNAL_SAMPLE_RATE);
+        let mut resampled = Vec::new();
+        let mut out = Vec::new();
+        for block in ramp.chunks(8 * 37) {
+            resampler.process(block, &mut out);
+            resampled.extend(samples(&out));
+        }
+
+        // 10ms in, 10ms out, each frame on the ramp (the first one rises
+        // from the silence before the stream)
+        assert_eq!(resampled.len(), 480 * 2);
+        let step = 44_100.0 / 48_000.0;
+        for (frame, pair) in resampled.chunks_exact(2).enumerate().skip(2) {
+            let expected = frame as f32 * step - 1.0;
+            assert!((pair[0] - expected).abs() < 1e-3, "frame {}", frame);
+            assert_eq!(pair[1], -pair[0]);
+        }
+
+        let format = AudioFormat {
+            sample_rate: 44_100,
+            channels: 2,
+            bits_per_sample: 32,
+            block_align: 8,
+            encoding: SampleEncoding::Float,
+            channel_mask: 0x3,
+        };
+        assert_eq!(internal_format(&format).sample_rate, INTERNAL_SAMPLE_RATE);
+        let pcm = AudioFormat {
+            encoding: SampleEncoding::Pcm,
+            bits_per_sample: 16,
+            block_align: 4,
+            ..format.clone()
+        };
+        assert_eq!(internal_format(&pcm), pcm);
+        assert!(!can_convert(&pcm, &internal_format(&format)));
+    }
+}
@@ -0,0 +1,215 @@
+//! Vectorized kernels for 32-bit float audio
+//!
+//! Scaling by the volume and adding scaled sources run on every sample the
+//! engine passes on, once per output for the volume. On x86_64 they process
+//! eight samples at a time with AVX or four with SSE2, picked at runtime by
+//! [`SimdLevel::detect`]; other targets and the tail shorter than a vector use
+//! the scalar loop. Samples are read unaligned, so any byte slice works.
+//!
+//! `cargo bench --bench mixing` compares the levels on 8-channel 48kHz audio.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Instruction set the kernels use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    /// One sample at a time
+    Scalar,
+    /// Four samples at a time (always present on x86_64)
+    Sse2,
+    /// Eight samples at a time
+    Avx,
+}
+
+impl SimdLevel {
+    /// Best level this CPU supports, detected once
+    pub fn detect() -> Self {
+        static LEVEL: OnceLock<SimdLevel> = OnceLock::new();
+        *LEVEL.get_or_init(|| Self::available().into_iter().max().unwrap_or(Self::Scalar))
+    }
+
+    /// All levels this CPU supports, [`SimdLevel::Scalar`] first
+    pub fn available() -> Vec<Self> {
+        let mut levels = vec![Self::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            levels.push(Self::Sse2);
+            if is_x86_feature_detected!("avx") {
+                levels.push(Self::Avx);
+            }
+        }
+        levels
+    }
+
+    /// Multiply every sample in `data` by `gain`
+    pub fn scale_f32(self, data: &mut [u8], gain: f32) {
+        let done = match self {
+            Self::Scalar => 0,
+            // SAFETY: levels other than Scalar are only reported by
+            // `available` when the CPU supports them
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => unsafe { x86::scale_sse2(data, gain) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx => unsafe { x86::scale_avx(data, gain) },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => 0,
+        };
+        scale_scalar(&mut data[done..], gain);
+    }
+
+    /// Add `src` scaled by `gain` to `dest`, clamping to -1.0 - 1.0
+    pub fn add_scaled_f32(self, dest: &mut [u8], src: &[u8], gain: f32) {
+        let len = dest.len().min(src.len());
+        let (dest, src) = (&mut dest[..len], &src[..len]);
+        let done = match self {
+            Self::Scalar => 0,
+            // SAFETY: as in `scale_f32`
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => unsafe { x86::add_scaled_sse2(dest, src, gain) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx => unsafe { x86::add_scaled_avx(dest, src, gain) },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => 0,
+        };
+        add_scaled_scalar(&mut dest[done..], &src[done..], gain);
+    }
+}
+
+impl fmt::Display for SimdLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Scalar => "scalar",
+            Self::Sse2 => "SSE2",
+            Self::Avx => "AVX",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Multiply every 32-bit float sample in `data` by `gain`
+#[inline]
+pub(crate) fn scale_f32(data: &mut [u8], gain: f32) {
+    SimdLevel::detect().scale_f32(data, gain);
+}
+
+/// Add `src` scaled by `gain` to `dest`, both 32-bit float samples, clamping to -1.0 - 1.0
+#[inline]
+pub(crate) fn add_scaled_f32(dest: &mut [u8], src: &[u8], gain: f32) {
+    SimdLevel::detect().add_scaled_f32(dest, src, gain);
+}
+
+fn scale_scalar(data: &mut [u8], gain: f32) {
+    for sample in data.chunks_exact_mut(4) {
+        let value = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+        sample.copy_from_slice(&(value * gain).to_le_bytes());
+    }
+}
+
+fn add_scaled_scalar(dest: &mut [u8], src: &[u8], gain: f32) {
+    for (d, s) in dest.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        let a = f32::from_le_bytes([d[0], d[1], d[2], d[3]]);
+        let b = f32::from_le_bytes([s[0], s[1], s[2], s[3]]);
+        let mixed = (a + b * gain).clamp(-1.0, 1.0);
+        d.copy_from_slice(&mixed.to_le_bytes());
+    }
+}
+
+/// x86_64 kernels; each returns the number of bytes it processed, a multiple
+/// of its vector size, leaving the rest to the scalar loop
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn scale_sse2(data: &mut [u8], gain: f32) -> usize {
+        let vectors = data.len() / 16;
+        let ptr = data.as_mut_ptr() as *mut f32;
+        let gain = _mm_set1_ps(gain);
+        for i in 0..vectors {
+            let p = ptr.add(i * 4);
+            _mm_storeu_ps(p, _mm_mul_ps(_mm_loadu_ps(p), gain));
+        }
+        vectors * 16
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn scale_avx(data: &mut [u8], gain: f32) -> usize {
+        let vectors = data.len() / 32;
+        let ptr = data.as_mut_ptr() as *mut f32;
+        let gain = _mm256_set1_ps(gain);
+        for i in 0..vectors {
+            let p = ptr.add(i * 8);
+            _mm256_storeu_ps(p, _mm256_mul_ps(_mm256_loadu_ps(p), gain));
+        }
+        vectors * 32
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn add_scaled_sse2(dest: &mut [u8], src: &[u8], gain: f32) -> usize {
+        let vectors = dest.len() / 16;
+        let (d, s) = (dest.as_mut_ptr() as *mut f32, src.as_ptr() as *const f32);
+        let (gain, low, high) = (_mm_set1_ps(gain), _mm_set1_ps(-1.0), _mm_set1_ps(1.0));
+        for i in 0..vectors {
+            let (dp, sp) = (d.add(i * 4), s.add(i * 4));
+            let mixed = _mm_add_ps(_mm_loadu_ps(dp), _mm_mul_ps(_mm_loadu_ps(sp), gain));
+            _mm_storeu_ps(dp, _mm_min_ps(_mm_max_ps(mixed, low), high));
+        }
+        vectors * 16
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn add_scaled_avx(dest: &mut [u8], src: &[u8], gain: f32) -> usize {
+        let vectors = dest.len() / 32;
+        let (d, s) = (dest.as_mut_ptr() as *mut f32, src.as_ptr() as *const f32);
+        let (gain, low, high) = (
+            _mm256_set1_ps(gain),
+            _mm256_set1_ps(-1.0),
+            _mm256_set1_ps(1.0),
+        );
+        for i in 0..vectors {
+            let (dp, sp) = (d.add(i * 8), s.add(i * 8));
+            let mixed = _mm256_add_ps(
+                _mm256_loadu_ps(dp),
+                _mm256_mul_ps(_mm256_loadu_ps(sp), gain),
+            );
+            _mm256_storeu_ps(dp, _mm256_min_ps(_mm256_max_ps(mixed, low), high));
+        }
+        vectors * 32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: impl Iterator<Item = f32>) -> Vec<u8> {
+        values.flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_levels_match_scalar() {
+        // 37 samples: whole vectors plus a tail, read one byte off alignment
+        let mut source = vec![0u8];
+        source.extend(samples((0..37).map(|n| (n as f32 - 18.0) / 10.0)));
+        let other = samples((0..37).map(|n| (n % 5) as f32 / 4.0));
+
+        let mut scaled = source[1..].to_vec();
+        SimdLevel::Scalar.scale_f32(&mut scaled, 0.3);
+        let mut mixed = source[1..].to_vec();
+        SimdLevel::Scalar.add_scaled_f32(&mut mixed, &other, 0.5);
+        // -1.8 + 0.0 clamped
+        assert_eq!(mixed[..4], (-1.0f32).to_le_bytes());
+
+        for level in SimdLevel::available() {
+            let mut data = source.clone();
+            level.scale_f32(&mut data[1..], 0.3);
+            assert_eq!(&data[1..], &scaled[..], "{}", level);
+
+            let mut data = source.clone();
+            level.add_scaled_f32(&mut data[1..], &other, 0.5);
+            assert_eq!(&data[1..], &mixed[..], "{}", level);
+        }
+        assert!(SimdLevel::available().contains(&SimdLevel::detect()));
+    }
+}
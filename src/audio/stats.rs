@@ -0,0 +1,421 @@
+//! Live engine statistics for monitoring
+//!
+//! Render threads publish their state through lock-free counters; the engine
+//! combines them with clock sync and volume state into an [`EngineStats`]
+//! snapshot that can be sent over IPC (see `wemux monitor`), along with the
+//! captured signal level from [`meter`](crate::audio::SignalLevel).
+
+use crate::audio::{DeviceLatency, HardwareCapabilities, LatencyClass, SignalLevel, VolumeMode};
+use crate::device::DeviceState;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{
+    AtomicBool, AtomicI64, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
+use std::time::{Duration, Instant};
+
+/// A worker thread without a heartbeat for this long is considered stalled
+pub(crate) const THREAD_STALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What a render thread is currently doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadState {
+    /// Thread spawned, renderer not started yet
+    Starting,
+    /// Rendering audio
+    Running,
+    /// Writing silence while paused
+    Paused,
+    /// Thread exited
+    Stopped,
+}
+
+impl ThreadState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => ThreadState::Starting,
+            1 => ThreadState::Running,
+            2 => ThreadState::Paused,
+            _ => ThreadState::Stopped,
+        }
+    }
+}
+
+impl std::fmt::Display for ThreadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ThreadState::Starting => "starting",
+            ThreadState::Running => "running",
+            ThreadState::Paused => "paused",
+            ThreadState::Stopped => "stopped",
+        };
+        f.write_str(name)
+    }
+}
+
+/// When a worker thread last made progress
+///
+/// Kept as milliseconds since the heartbeat was created so it fits an atomic.
+#[derive(Debug)]
+pub(crate) struct Heartbeat {
+    created: Instant,
+    last_beat_ms: AtomicU64,
+    /// Set by a thread that exits on purpose, so its silence isn't a stall
+    finished: AtomicBool,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            created: Instant::now(),
+            last_beat_ms: AtomicU64::new(0),
+            finished: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Heartbeat {
+    pub(crate) fn beat(&self) {
+        self.last_beat_ms
+            .store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Time since the last beat
+    pub(crate) fn age(&self) -> Duration {
+        let last = Duration::from_millis(self.last_beat_ms.load(Ordering::Relaxed));
+        self.created.elapsed().saturating_sub(last)
+    }
+
+    pub(crate) fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// Whether the thread stopped making progress without exiting
+    pub(crate) fn is_stalled(&self) -> bool {
+        !self.is_finished() && self.age() > THREAD_STALL_TIMEOUT
+    }
+}
+
+/// When the newest audio in the ring buffer was captured
+///
+/// Published by the capture thread so render threads can tell how long ago
+/// what they write was captured. Kept as microseconds since the clock was
+/// created so it fits an atomic.
+#[derive(Debug)]
+pub(crate) struct CaptureClock {
+    created: Instant,
+    /// `u64::MAX` until audio was captured
+    captured_us: AtomicU64,
+}
+
+impl Default for CaptureClock {
+    fn default() -> Self {
+        Self {
+            created: Instant::now(),
+            captured_us: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl CaptureClock {
+    /// Record that audio captured `delay` ago was written to the ring buffer
+    pub(crate) fn record(&self, delay: Duration) {
+        let captured = self.created.elapsed().saturating_sub(delay);
+        self.captured_us
+            .store(captured.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Time since the newest audio in the ring buffer was captured, None
+    /// until audio was captured
+    pub(crate) fn age(&self) -> Option<Duration> {
+        let captured = self.captured_us.load(Ordering::Relaxed);
+        (captured != u64::MAX).then(|| {
+            self.created
+                .elapsed()
+                .saturating_sub(Duration::from_micros(captured))
+        })
+    }
+}
+
+/// Counters a render thread updates while running
+///
+/// Readers never take a lock the render thread needs, so monitoring can't
+/// hold up audio.
+#[derive(Debug, Default)]
+pub(crate) struct RendererCounters {
+    state: AtomicU8,
+    buffered_bytes: AtomicUsize,
+    underruns: AtomicU64,
+    frames: AtomicU64,
+    is_master: AtomicBool,
+    /// Drift in milliseconds as `f64` bits
+    drift_ms: AtomicU64,
+    extra_buffer_ms: AtomicU32,
+    /// Longest render pass in microseconds
+    longest_pass_us: AtomicU64,
+    /// Device timing, published when the device is opened (0 = not yet)
+    device_buffer_ms: AtomicU32,
+    min_period: AtomicI64,
+    default_period: AtomicI64,
+    latency_class: AtomicU8,
+    /// Smoothed time from capture to playback in microseconds (0 = not yet)
+    end_to_end_us: AtomicU64,
+    /// State of the device as Windows last reported it (0 = active)
+    device_state: AtomicU8,
+    heartbeat: Heartbeat,
+    /// Beats whenever frames are written, unlike the heartbeat which also
+    /// beats while paused or waiting for audio
+    last_frames: Heartbeat,
+}
+
+impl RendererCounters {
+    pub(crate) fn set_state(&self, state: ThreadState) {
+        self.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    pub(crate) fn state(&self) -> ThreadState {
+        ThreadState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    /// Record how much captured audio is waiting for this renderer
+    pub(crate) fn set_buffered(&self, bytes: usize) {
+        self.buffered_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn buffered(&self) -> usize {
+        self.buffered_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_underruns(&self, total: u64) {
+        self.underruns.store(total, Ordering::Relaxed);
+    }
+
+    pub(crate) fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Count frames written to the device
+    pub(crate) fn add_frames(&self, frames: u32) {
+        self.frames.fetch_add(frames as u64, Ordering::Relaxed);
+        self.last_frames.beat();
+    }
+
+    pub(crate) fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Whether frames were written to the device within `window`
+    pub(crate) fn wrote_within(&self, window: Duration) -> bool {
+        self.frames() > 0 && self.last_frames.age() <= window
+    }
+
+    /// Record the renderer's clock sync role and drift
+    pub(crate) fn set_sync(&self, is_master: bool, drift_ms: f64) {
+        self.is_master.store(is_master, Ordering::Relaxed);
+        self.drift_ms.store(drift_ms.to_bits(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_master(&self) -> bool {
+        self.is_master.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn drift_ms(&self) -> f64 {
+        f64::from_bits(self.drift_ms.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_extra_buffer_ms(&self, ms: u32) {
+        self.extra_buffer_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub(crate) fn extra_buffer_ms(&self) -> u32 {
+        self.extra_buffer_ms.load(Ordering::Relaxed)
+    }
+
+    /// Record how long one pass of reading, syncing and writing audio took
+    pub(crate) fn record_pass(&self, duration: Duration) {
+        self.longest_pass_us
+            .fetch_max(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Longest render pass so far
+    pub(crate) fn longest_pass(&self) -> Duration {
+        Duration::from_micros(self.longest_pass_us.load(Ordering::Relaxed))
+    }
+
+    /// Record the timing of the device the thread renders to
+    pub(crate) fn set_latency(&self, latency: &DeviceLatency) {
+        let caps = &latency.capabilities;
+        self.min_period.store(caps.min_period, Ordering::Relaxed);
+        self.default_period
+            .store(caps.default_period, Ordering::Relaxed);
+        self.latency_class
+            .store(caps.latency_class as u8, Ordering::Relaxed);
+        self.device_buffer_ms
+            .store(latency.buffer_ms, Ordering::Relaxed);
+    }
+
+    /// Timing of the device, None until it has been opened
+    pub(crate) fn latency(&self) -> Option<DeviceLatency> {
+        let buffer_ms = self.device_buffer_ms.load(Ordering::Relaxed);
+        if buffer_ms == 0 {
+            return None;
+        }
+        let latency_class = match self.latency_class.load(Ordering::Relaxed) {
+            0 => LatencyClass::LowLatency,
+            1 => LatencyClass::Standard,
+            _ => LatencyClass::HighLatency,
+        };
+        Some(DeviceLatency {
+            capabilities: HardwareCapabilities {
+                min_period: self.min_period.load(Ordering::Relaxed),
+                default_period: self.default_period.load(Ordering::Relaxed),
+                latency_class,
+            },
+            buffer_ms,
+        })
+    }
+
+    /// Record the time from capturing audio to the device playing it
+    pub(crate) fn set_end_to_end(&self, latency: Duration) {
+        self.end_to_end_us
+            .store((latency.as_micros() as u64).max(1), Ordering::Relaxed);
+    }
+
+    /// Time from capturing audio to the device playing it, None until the
+    /// renderer has written audio
+    pub(crate) fn end_to_end(&self) -> Option<Duration> {
+        match self.end_to_end_us.load(Ordering::Relaxed) {
+            0 => None,
+            us => Some(Duration::from_micros(us)),
+        }
+    }
+
+    /// Record that Windows reported the device disabled, unplugged or back
+    pub(crate) fn set_device_state(&self, state: DeviceState) {
+        self.device_state.store(state as u8, Ordering::Relaxed);
+    }
+
+    pub(crate) fn device_state(&self) -> DeviceState {
+        match self.device_state.load(Ordering::Relaxed) {
+            1 => DeviceState::Disabled,
+            2 => DeviceState::NotPresent,
+            3 => DeviceState::Unplugged,
+            _ => DeviceState::Active,
+        }
+    }
+
+    /// Beaten by the render thread on every pass, paused or not
+    pub(crate) fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+}
+
+/// Snapshot of one renderer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RendererStats {
+    /// Device ID
+    pub id: String,
+    /// Device name
+    pub name: String,
+    /// User-assigned alias
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Render thread state
+    pub state: ThreadState,
+    /// Whether this renderer is the clock sync master
+    pub is_master: bool,
+    /// Drift from the master in milliseconds (positive = ahead, 0 for the master)
+    pub drift_ms: f64,
+    /// Captured audio waiting in the ring buffer for this renderer, in milliseconds
+    pub buffered_ms: f64,
+    /// Underruns since the renderer started
+    pub underruns: u64,
+    /// Audio frames written to the device since the renderer started
+    #[serde(default)]
+    pub frames_written: u64,
+    /// WASAPI buffer added by auto-tuning, in milliseconds
+    pub extra_buffer_ms: u32,
+    /// Longest time one pass of reading, syncing and writing audio took, in milliseconds
+    #[serde(default)]
+    pub longest_pass_ms: f64,
+    /// Time since the render thread last made progress, in milliseconds
+    #[serde(default)]
+    pub heartbeat_age_ms: u64,
+    /// Whether the render thread stopped making progress without exiting
+    #[serde(default)]
+    pub stalled: bool,
+    /// Time from capturing audio to the device playing it, in milliseconds;
+    /// the delay to set in the display's lip-sync setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_to_end_ms: Option<f64>,
+}
+
+impl RendererStats {
+    /// Name to show the user: the alias if one is assigned, else the device name
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Liveness of a thread feeding the ring buffer (the capture or a mixed source)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadHealth {
+    /// What the thread does, e.g. "capture" or "mix: Microphone"
+    pub name: String,
+    /// Time since the thread last made progress, in milliseconds
+    pub heartbeat_age_ms: u64,
+    /// Whether the thread exited
+    pub finished: bool,
+    /// Whether the thread stopped making progress without exiting
+    pub stalled: bool,
+}
+
+impl ThreadHealth {
+    pub(crate) fn of(name: &str, heartbeat: &Heartbeat) -> Self {
+        Self {
+            name: name.to_string(),
+            heartbeat_age_ms: heartbeat.age().as_millis() as u64,
+            finished: heartbeat.is_finished(),
+            stalled: heartbeat.is_stalled(),
+        }
+    }
+}
+
+/// Snapshot of the whole engine
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineStats {
+    /// Whether the engine is running
+    pub running: bool,
+    /// Effective source volume (0.0 - 1.0, 0 when the system is muted)
+    pub volume: f32,
+    /// Whether wemux output is muted
+    #[serde(default)]
+    pub muted: bool,
+    /// How the volume of the outputs is set
+    #[serde(default)]
+    pub volume_mode: VolumeMode,
+    /// wemux's own volume of the outputs (0.0 - 1.0), see [`VolumeMode`]
+    #[serde(default = "full_volume")]
+    pub mirror_volume: f32,
+    /// Ring buffer capacity in milliseconds
+    pub ring_buffer_ms: u32,
+    /// Times an engine was started again in the hosting process
+    #[serde(default)]
+    pub restarts: u64,
+    /// Per-renderer statistics
+    pub renderers: Vec<RendererStats>,
+    /// Capture and mixed source threads
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub threads: Vec<ThreadHealth>,
+    /// Level of the audio passed to the outputs, silent while none is captured
+    #[serde(default)]
+    pub level: SignalLevel,
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
@@ -0,0 +1,257 @@
+//! Runtime buffer auto-tuning for struggling renderers
+//!
+//! [`HardwareCapabilities`](crate::audio::HardwareCapabilities) picks a WASAPI
+//! buffer from the device period, which is not enough for devices that stall
+//! irregularly (Bluetooth, flaky HDMI links). Render threads count underruns
+//! and grow the buffer and pre-fill of such a device step by step.
+//!
+//! The starting point is a [`BufferPlan`] derived from `EngineConfig::buffer_ms`,
+//! or for a device with an entry in `EngineConfig::device_buffer_ms`, a tuning
+//! derived from that.
+
+use crate::audio::HardwareCapabilities;
+use crate::error::{Result, WemuxError};
+use std::time::{Duration, Instant};
+
+/// WASAPI buffer added per tuning step
+const BUFFER_STEP_MS: u32 = 20;
+
+/// Pre-fill added per tuning step
+const PREFILL_STEP_MS: u32 = 10;
+
+/// Largest extra WASAPI buffer auto-tuning will add
+const MAX_EXTRA_BUFFER_MS: u32 = 200;
+
+/// Largest pre-fill auto-tuning will use
+const MAX_PREFILL_MS: u32 = 100;
+
+/// Pre-fill used before any tuning with the default 50ms buffer
+const DEFAULT_PREFILL_MS: u32 = 20;
+
+/// Smallest render chunk, so a tiny `buffer_ms` can't spin the render threads
+const MIN_CHUNK_MS: u32 = 5;
+
+/// Render chunks the ring buffer holds at least
+const RING_BUFFER_CHUNKS: u32 = 6;
+
+/// Smallest `buffer_ms` accepted: chunks shorter than a device period leave
+/// the device starved between writes
+pub const MIN_BUFFER_MS: u32 = 10;
+
+/// Largest `buffer_ms` accepted: the ring buffer holds several chunks, so
+/// larger values only add seconds of latency
+pub const MAX_BUFFER_MS: u32 = 500;
+
+/// Buffer sizes the engine derives from `EngineConfig::buffer_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferPlan {
+    /// Ring buffer capacity in milliseconds
+    pub ring_buffer_ms: u32,
+    /// Audio a render thread moves to its device per write, in milliseconds
+    pub chunk_ms: u32,
+    /// Silence written before the first audio, before any tuning
+    pub prefill_ms: u32,
+}
+
+impl BufferPlan {
+    /// Plan for `buffer_ms` and `num_renderers` outputs
+    ///
+    /// Renderers write `buffer_ms` chunks and pre-fill 40% of that; the ring
+    /// buffer is the hardware-derived size, grown to hold several chunks.
+    pub fn new(buffer_ms: u32, caps: &HardwareCapabilities, num_renderers: usize) -> Self {
+        let chunk_ms = buffer_ms.max(MIN_CHUNK_MS);
+        Self {
+            ring_buffer_ms: caps
+                .optimal_ring_buffer_ms(num_renderers)
+                .max(chunk_ms * RING_BUFFER_CHUNKS),
+            chunk_ms,
+            prefill_ms: (chunk_ms * 2 / 5).min(MAX_PREFILL_MS),
+        }
+    }
+
+    /// Check `buffer_ms` against the device period and the accepted range
+    pub fn validate(buffer_ms: u32, caps: &HardwareCapabilities) -> Result<()> {
+        let min = MIN_BUFFER_MS.max(caps.default_period_ms().ceil() as u32);
+        if (min..=MAX_BUFFER_MS).contains(&buffer_ms) {
+            return Ok(());
+        }
+        let effect = if buffer_ms < min {
+            "smaller buffers make outputs crackle"
+        } else {
+            "larger buffers delay audio by seconds"
+        };
+        Err(WemuxError::InvalidConfig(format!(
+            "Buffer of {}ms is out of range: use {} to {}ms ({})",
+            buffer_ms, min, MAX_BUFFER_MS, effect
+        )))
+    }
+
+    /// Tuning of a device nothing has been learned about yet
+    pub fn initial_tuning(&self) -> BufferTuning {
+        self.initial_tuning_for(self.chunk_ms)
+    }
+
+    /// Tuning of a device configured with a `buffer_ms` of its own
+    ///
+    /// What the device's buffer exceeds the engine's by is added to its
+    /// WASAPI buffer, and it pre-fills 40% of its buffer; a smaller buffer
+    /// only pre-fills less.
+    pub fn initial_tuning_for(&self, buffer_ms: u32) -> BufferTuning {
+        BufferTuning {
+            extra_buffer_ms: buffer_ms
+                .saturating_sub(self.chunk_ms)
+                .min(MAX_EXTRA_BUFFER_MS),
+            prefill_ms: (buffer_ms.max(MIN_CHUNK_MS) * 2 / 5).min(MAX_PREFILL_MS),
+        }
+    }
+}
+
+/// Underruns within [`UNDERRUN_WINDOW`] that trigger a tuning step
+const UNDERRUN_THRESHOLD: u32 = 3;
+
+/// Window in which underruns are counted
+const UNDERRUN_WINDOW: Duration = Duration::from_secs(10);
+
+/// Buffering applied to one renderer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferTuning {
+    /// WASAPI buffer added on top of the hardware-derived size, in milliseconds
+    pub extra_buffer_ms: u32,
+    /// Silence written before the first audio, in milliseconds
+    pub prefill_ms: u32,
+}
+
+impl Default for BufferTuning {
+    fn default() -> Self {
+        Self {
+            extra_buffer_ms: 0,
+            prefill_ms: DEFAULT_PREFILL_MS,
+        }
+    }
+}
+
+impl BufferTuning {
+    /// Next larger tuning step, None once both limits are reached
+    pub fn grown(&self) -> Option<Self> {
+        let next = Self {
+            extra_buffer_ms: (self.extra_buffer_ms + BUFFER_STEP_MS).min(MAX_EXTRA_BUFFER_MS),
+            prefill_ms: (self.prefill_ms + PREFILL_STEP_MS).min(MAX_PREFILL_MS),
+        };
+        (next != *self).then_some(next)
+    }
+}
+
+/// Counts a renderer's underruns and decides when its buffer should grow
+#[derive(Debug)]
+pub struct UnderrunTracker {
+    window_start: Instant,
+    window_count: u32,
+    total: u64,
+}
+
+impl UnderrunTracker {
+    /// Create a tracker with no underruns recorded
+    pub fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            window_count: 0,
+            total: 0,
+        }
+    }
+
+    /// Record an underrun, returns true when the buffer should grow
+    pub fn record(&mut self) -> bool {
+        self.total += 1;
+
+        if self.window_start.elapsed() > UNDERRUN_WINDOW {
+            self.window_start = Instant::now();
+            self.window_count = 0;
+        }
+        self.window_count += 1;
+
+        if self.window_count >= UNDERRUN_THRESHOLD {
+            // Start over so the grown buffer gets a fresh window
+            self.window_start = Instant::now();
+            self.window_count = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Underruns recorded since creation
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+impl Default for UnderrunTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuning_grows_to_limits() {
+        let mut tuning = BufferTuning::default();
+        let mut steps = 0;
+        while let Some(next) = tuning.grown() {
+            assert!(next.extra_buffer_ms >= tuning.extra_buffer_ms);
+            assert!(next.prefill_ms >= tuning.prefill_ms);
+            tuning = next;
+            steps += 1;
+        }
+
+        assert_eq!(steps, MAX_EXTRA_BUFFER_MS / BUFFER_STEP_MS);
+        assert_eq!(tuning.extra_buffer_ms, MAX_EXTRA_BUFFER_MS);
+        assert_eq!(tuning.prefill_ms, MAX_PREFILL_MS);
+    }
+
+    #[test]
+    fn test_buffer_plan() {
+        let caps = HardwareCapabilities::default();
+
+        // The default buffer keeps the previous fixed sizes
+        let plan = BufferPlan::new(50, &caps, 2);
+        assert_eq!(plan.chunk_ms, 50);
+        assert_eq!(plan.initial_tuning(), BufferTuning::default());
+        assert_eq!(plan.ring_buffer_ms, caps.optimal_ring_buffer_ms(2));
+
+        let large = BufferPlan::new(200, &caps, 2);
+        assert_eq!(large.ring_buffer_ms, 1200);
+        assert_eq!(large.prefill_ms, 80);
+
+        assert_eq!(BufferPlan::new(0, &caps, 1).chunk_ms, MIN_CHUNK_MS);
+
+        // A device with its own buffer: the excess goes to its WASAPI buffer
+        let tuning = plan.initial_tuning_for(120);
+        assert_eq!(tuning.extra_buffer_ms, 70);
+        assert_eq!(tuning.prefill_ms, 48);
+        assert_eq!(plan.initial_tuning_for(20).extra_buffer_ms, 0);
+        assert_eq!(plan.initial_tuning_for(20).prefill_ms, 8);
+
+        assert!(BufferPlan::validate(50, &caps).is_ok());
+        assert!(BufferPlan::validate(5, &caps).is_err());
+        assert!(BufferPlan::validate(5000, &caps).is_err());
+        let slow = HardwareCapabilities {
+            default_period: 200_000, // 20ms
+            ..caps
+        };
+        assert!(BufferPlan::validate(15, &slow).is_err());
+    }
+
+    #[test]
+    fn test_underrun_threshold() {
+        let mut tracker = UnderrunTracker::new();
+        assert!(!tracker.record());
+        assert!(!tracker.record());
+        assert!(tracker.record());
+        // Counting restarts after a tuning step
+        assert!(!tracker.record());
+        assert_eq!(tracker.total(), 4);
+    }
+}
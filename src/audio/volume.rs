@@ -1,35 +1,129 @@
 //! System volume tracking for volume-following feature
+//!
+//! The outputs follow the captured device's volume by default. A mirror
+//! volume of wemux's own can replace or scale it ([`VolumeMode`]), so the
+//! mirrored outputs can be turned down without touching the main output.
 
+use crate::audio::simd;
 use crate::error::Result;
-use std::sync::atomic::{AtomicU32, Ordering};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 use tracing::{debug, warn};
 use windows::Win32::{
     Media::Audio::Endpoints::IAudioEndpointVolume,
-    Media::Audio::{eConsole, eRender, IMMDeviceEnumerator, MMDeviceEnumerator},
+    Media::Audio::{eConsole, eRender, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator},
     System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED},
 };
 
+/// How the volume of the outputs is set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeMode {
+    /// Follow the volume of the captured device
+    #[default]
+    Follow,
+    /// Play at the mirror volume, whatever the captured device's volume
+    Fixed,
+    /// Follow the captured device's volume, scaled by the mirror volume
+    Scaled,
+}
+
+impl fmt::Display for VolumeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VolumeMode::Follow => "follow",
+            VolumeMode::Fixed => "fixed",
+            VolumeMode::Scaled => "scaled",
+        })
+    }
+}
+
+impl VolumeMode {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => VolumeMode::Follow,
+            1 => VolumeMode::Fixed,
+            _ => VolumeMode::Scaled,
+        }
+    }
+}
+
 /// Atomic volume level stored as u32 bits of an f32 (0.0-1.0)
 ///
 /// Using AtomicU32 with f32 bit representation for lock-free sharing
-/// between the volume polling thread and render threads.
-pub struct VolumeLevel(AtomicU32);
+/// between the volume polling thread and render threads. The mute flag and
+/// the mirror volume are wemux's own and independent of the system's.
+pub struct VolumeLevel {
+    volume: AtomicU32,
+    muted: AtomicBool,
+    /// Mirror volume as `f32` bits
+    mirror: AtomicU32,
+    mode: AtomicU8,
+}
 
 impl VolumeLevel {
     /// Create with default volume of 1.0 (full)
     pub fn new() -> Self {
-        Self(AtomicU32::new(1.0f32.to_bits()))
+        Self {
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            muted: AtomicBool::new(false),
+            mirror: AtomicU32::new(1.0f32.to_bits()),
+            mode: AtomicU8::new(VolumeMode::Follow as u8),
+        }
     }
 
     /// Get current volume level (0.0 - 1.0)
     pub fn get(&self) -> f32 {
-        f32::from_bits(self.0.load(Ordering::Relaxed))
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
     }
 
     /// Set volume level (0.0 - 1.0)
     pub fn set(&self, volume: f32) {
         let clamped = volume.clamp(0.0, 1.0);
-        self.0.store(clamped.to_bits(), Ordering::Relaxed);
+        self.volume.store(clamped.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Silence all renderers without stopping them
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Whether renderers are muted
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    /// Set the mirror volume (0.0 - 1.0)
+    pub fn set_mirror(&self, volume: f32) {
+        let clamped = volume.clamp(0.0, 1.0);
+        self.mirror.store(clamped.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Mirror volume (0.0 - 1.0)
+    pub fn mirror(&self) -> f32 {
+        f32::from_bits(self.mirror.load(Ordering::Relaxed))
+    }
+
+    pub fn set_mode(&self, mode: VolumeMode) {
+        self.mode.store(mode as u8, Ordering::Relaxed);
+    }
+
+    pub fn mode(&self) -> VolumeMode {
+        VolumeMode::from_u8(self.mode.load(Ordering::Relaxed))
+    }
+
+    /// Scale factor to apply to rendered samples (0 while muted)
+    pub fn gain(&self) -> f32 {
+        if self.is_muted() {
+            return 0.0;
+        }
+        match self.mode() {
+            VolumeMode::Follow => self.get(),
+            VolumeMode::Fixed => self.mirror(),
+            VolumeMode::Scaled => self.get() * self.mirror(),
+        }
     }
 }
 
@@ -39,7 +133,7 @@ impl Default for VolumeLevel {
     }
 }
 
-/// Tracks system volume from the default render device
+/// Tracks system volume of a render device
 pub struct VolumeTracker {
     endpoint_volume: IAudioEndpointVolume,
 }
@@ -55,9 +149,16 @@ impl VolumeTracker {
 
             let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?;
 
+            Self::from_device(&device)
+        }
+    }
+
+    /// Create a volume tracker for a specific render device
+    pub fn from_device(device: &IMMDevice) -> Result<Self> {
+        unsafe {
             let endpoint_volume: IAudioEndpointVolume = device.Activate(CLSCTX_ALL, None)?;
 
-            debug!("Volume tracker initialized for default device");
+            debug!("Volume tracker initialized");
 
             Ok(Self { endpoint_volume })
         }
@@ -103,7 +204,7 @@ unsafe impl Send for VolumeTracker {}
 /// Apply volume scaling to 32-bit float audio samples in-place
 ///
 /// # Arguments
-/// * `data` - Byte slice containing f32 samples (must be aligned to 4 bytes)
+/// * `data` - Byte slice containing f32 samples
 /// * `volume` - Volume level 0.0 - 1.0
 #[inline]
 pub fn apply_volume_f32(data: &mut [u8], volume: f32) {
@@ -112,13 +213,27 @@ pub fn apply_volume_f32(data: &mut [u8], volume: f32) {
         return;
     }
 
-    // Process as f32 samples
-    // SAFETY: Audio data is always 4-byte aligned (32-bit float format)
-    let samples =
-        unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut f32, data.len() / 4) };
+    simd::scale_f32(data, volume);
+}
 
-    // Apply volume with SIMD-friendly loop
-    for sample in samples.iter_mut() {
-        *sample *= volume;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain_by_volume_mode() {
+        let level = VolumeLevel::new();
+        level.set(0.5);
+        level.set_mirror(0.4);
+        assert_eq!(level.gain(), 0.5);
+
+        level.set_mode(VolumeMode::Fixed);
+        assert_eq!(level.gain(), 0.4);
+        level.set_mode(VolumeMode::Scaled);
+        assert_eq!(level.mode(), VolumeMode::Scaled);
+        assert!((level.gain() - 0.2).abs() < f32::EPSILON);
+
+        level.set_muted(true);
+        assert_eq!(level.gain(), 0.0);
     }
 }
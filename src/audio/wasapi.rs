@@ -0,0 +1,215 @@
+//! WASAPI implementation of the audio backend traits
+
+use crate::audio::backend::{AudioBackend, CaptureBackend, RenderBackend, VolumeSource};
+use crate::audio::{
+    AppCapture, AudioFormat, BufferTuning, DeviceLatency, HdmiRenderer, LoopbackCapture,
+    VolumeTracker,
+};
+use crate::device::{DeviceEnumerator, DeviceEvent, DeviceInfo, DeviceMonitor};
+use crate::error::Result;
+use crate::sync::DevicePosition;
+use crossbeam_channel::Sender;
+use std::time::Duration;
+use windows::Win32::Media::Audio::IMMDevice;
+
+/// Audio backend using the Windows Core Audio APIs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WasapiBackend;
+
+/// Capture opened by [`WasapiBackend`]
+pub enum WasapiCapture {
+    /// Everything a device plays or records
+    Device(LoopbackCapture),
+    /// The apps playing on an output, some left out
+    Apps(AppCapture),
+}
+
+impl AudioBackend for WasapiBackend {
+    type Capture = WasapiCapture;
+    type Renderer = HdmiRenderer;
+    type Volume = VolumeTracker;
+    type Monitor = DeviceMonitor;
+
+    fn open_capture(&self, source_id: Option<&str>) -> Result<Self::Capture> {
+        match source_id {
+            Some(id) => {
+                LoopbackCapture::from_device(&DeviceEnumerator::new()?.get_device_by_id(id)?)
+            }
+            None => LoopbackCapture::from_default_device(),
+        }
+        .map(WasapiCapture::Device)
+    }
+
+    fn open_app_capture(
+        &self,
+        source_id: Option<&str>,
+        exclude_apps: &[String],
+    ) -> Result<Self::Capture> {
+        let enumerator = DeviceEnumerator::new()?;
+        let device = match source_id {
+            Some(id) => enumerator.get_device_by_id(id)?,
+            None => enumerator.get_default_render_device()?,
+        };
+        AppCapture::new(device, exclude_apps).map(WasapiCapture::Apps)
+    }
+
+    fn open_renderer(&self, device_id: &str, tuning: &BufferTuning) -> Result<Self::Renderer> {
+        let enumerator = DeviceEnumerator::new()?;
+        let device = enumerator.get_active_device_by_id(device_id)?;
+        HdmiRenderer::with_tuning(&device, tuning)
+    }
+
+    fn open_volume(&self, source_id: Option<&str>) -> Result<Self::Volume> {
+        match source_id {
+            Some(id) => VolumeTracker::from_device(&DeviceEnumerator::new()?.get_device_by_id(id)?),
+            None => VolumeTracker::from_default_device(),
+        }
+    }
+
+    fn watch_devices(&self, event_tx: Sender<DeviceEvent>) -> Result<Self::Monitor> {
+        DeviceMonitor::new(event_tx)
+    }
+
+    fn output_devices(&self) -> Result<Vec<DeviceInfo>> {
+        DeviceEnumerator::new()?.enumerate_all_devices()
+    }
+
+    fn default_output_id(&self) -> Option<String> {
+        let enumerator = DeviceEnumerator::new().ok()?;
+        endpoint_id(&enumerator.get_default_render_device().ok()?)
+    }
+
+    fn input_devices(&self) -> Result<Vec<DeviceInfo>> {
+        DeviceEnumerator::new()?.enumerate_input_devices()
+    }
+
+    fn default_input_id(&self) -> Option<String> {
+        let enumerator = DeviceEnumerator::new().ok()?;
+        endpoint_id(&enumerator.get_default_input_device().ok()?)
+    }
+}
+
+/// ID of an endpoint
+fn endpoint_id(device: &IMMDevice) -> Option<String> {
+    unsafe {
+        let id_ptr = device.GetId().ok()?;
+        let id = windows::core::PCWSTR(id_ptr.0).to_string().ok();
+        windows::Win32::System::Com::CoTaskMemFree(Some(id_ptr.0 as *const _));
+        id
+    }
+}
+
+impl CaptureBackend for LoopbackCapture {
+    fn format(&self) -> &AudioFormat {
+        LoopbackCapture::format(self)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        LoopbackCapture::start(self)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        LoopbackCapture::stop(self)
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        let frames = self.read_frames(timeout_ms)?;
+        if frames.is_empty() {
+            return Ok(0);
+        }
+        Ok(frames.copy_to(dest))
+    }
+
+    fn capture_delay(&self) -> Duration {
+        self.delay()
+    }
+}
+
+impl CaptureBackend for WasapiCapture {
+    fn format(&self) -> &AudioFormat {
+        match self {
+            WasapiCapture::Device(capture) => CaptureBackend::format(capture),
+            WasapiCapture::Apps(capture) => capture.format(),
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        match self {
+            WasapiCapture::Device(capture) => CaptureBackend::start(capture),
+            WasapiCapture::Apps(capture) => capture.start(),
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        match self {
+            WasapiCapture::Device(capture) => CaptureBackend::stop(capture),
+            WasapiCapture::Apps(capture) => capture.stop(),
+        }
+    }
+
+    fn read_into(&mut self, dest: &mut [u8], timeout_ms: u32) -> Result<usize> {
+        match self {
+            WasapiCapture::Device(capture) => capture.read_into(dest, timeout_ms),
+            WasapiCapture::Apps(capture) => capture.read_into(dest, timeout_ms),
+        }
+    }
+
+    fn capture_delay(&self) -> Duration {
+        match self {
+            WasapiCapture::Device(capture) => CaptureBackend::capture_delay(capture),
+            WasapiCapture::Apps(capture) => capture.capture_delay(),
+        }
+    }
+}
+
+impl RenderBackend for HdmiRenderer {
+    fn device_id(&self) -> &str {
+        HdmiRenderer::device_id(self)
+    }
+
+    fn device_name(&self) -> &str {
+        HdmiRenderer::device_name(self)
+    }
+
+    fn format(&self) -> &AudioFormat {
+        HdmiRenderer::format(self)
+    }
+
+    fn latency(&self) -> DeviceLatency {
+        HdmiRenderer::latency(self)
+    }
+
+    fn start(&mut self) -> Result<()> {
+        HdmiRenderer::start(self)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        HdmiRenderer::stop(self)
+    }
+
+    fn write_frames(&mut self, data: &[u8], timeout_ms: u32) -> Result<u32> {
+        HdmiRenderer::write_frames(self, data, timeout_ms)
+    }
+
+    fn write_silence(&mut self, frames: u32) -> Result<()> {
+        HdmiRenderer::write_silence(self, frames)
+    }
+
+    fn padding_frames(&self) -> Result<u32> {
+        HdmiRenderer::padding_frames(self)
+    }
+
+    fn device_position(&self) -> Result<DevicePosition> {
+        HdmiRenderer::device_position(self)
+    }
+
+    fn set_error(&mut self, message: &str) {
+        HdmiRenderer::set_error(self, message)
+    }
+}
+
+impl VolumeSource for VolumeTracker {
+    fn effective_volume(&self) -> f32 {
+        self.get_effective_volume()
+    }
+}
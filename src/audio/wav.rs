@@ -0,0 +1,99 @@
+//! Reading WAV files
+
+/// `WAVE_FORMAT_PCM`, `WAVE_FORMAT_IEEE_FLOAT` and `WAVE_FORMAT_EXTENSIBLE`
+const FORMAT_PCM: u16 = 0x0001;
+const FORMAT_FLOAT: u16 = 0x0003;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Audio read from a WAV file, as float samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavAudio {
+    /// Samples of all channels, interleaved
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+impl WavAudio {
+    /// Parse the contents of a WAV file
+    ///
+    /// Integer PCM of 16, 24 or 32 bits and 32-bit float are supported.
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let u16_at = |pos: usize| u16::from_le_bytes([data[pos], data[pos + 1]]);
+        let u32_at = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err("not a WAV file (convert other formats to WAV first)".to_string());
+        }
+
+        let mut format = None;
+        let mut pos = 12;
+        while pos + 8 <= data.len() {
+            let id = &data[pos..pos + 4];
+            let size = u32_at(pos + 4) as usize;
+            let body = pos + 8;
+            let end = (body + size).min(data.len());
+
+            if id == b"fmt " && end - body >= 16 {
+                let mut tag = u16_at(body);
+                if tag == FORMAT_EXTENSIBLE && end - body >= 26 {
+                    // The sub-format GUID starts with the actual format tag
+                    tag = u16_at(body + 24);
+                }
+                format = Some((tag, u16_at(body + 2), u32_at(body + 4), u16_at(body + 14)));
+            } else if id == b"data" {
+                let (tag, channels, sample_rate, bits) = format.ok_or("data before format")?;
+                if channels == 0 {
+                    return Err("no channels".to_string());
+                }
+                let sample = |bytes: &[u8]| -> Option<f32> {
+                    match (tag, bits) {
+                        (FORMAT_PCM, 16) => {
+                            Some(i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0)
+                        }
+                        (FORMAT_PCM, 24) => Some(
+                            i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32
+                                / 2_147_483_648.0,
+                        ),
+                        (FORMAT_PCM, 32) => Some(
+                            i32::from_le_bytes(bytes.try_into().ok()?) as f32 / 2_147_483_648.0,
+                        ),
+                        (FORMAT_FLOAT, 32) => Some(f32::from_le_bytes(bytes.try_into().ok()?)),
+                        _ => None,
+                    }
+                };
+                let width = bits as usize / 8;
+                let frame = width * channels as usize;
+                if sample(&vec![0; width]).is_none() {
+                    return Err(format!("unsupported format {} with {} bits", tag, bits));
+                }
+
+                let samples = data[body..end]
+                    .chunks_exact(frame)
+                    .flat_map(|frame| frame.chunks_exact(width).filter_map(sample))
+                    .collect();
+                return Ok(Self {
+                    samples,
+                    channels,
+                    sample_rate,
+                });
+            }
+            // Chunks are padded to an even size
+            pos = body + size + (size & 1);
+        }
+        Err("no audio data".to_string())
+    }
+
+    /// Number of frames
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// The audio with its channels averaged
+    pub fn to_mono(&self) -> Vec<f32> {
+        self.samples
+            .chunks_exact(self.channels as usize)
+            .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+            .collect()
+    }
+}
@@ -0,0 +1,30 @@
+//! Zones: groups of outputs that play and pause together
+//!
+//! Each zone runs its own capture and clock sync, so one zone can mirror the
+//! system audio while another plays a different source or stays paused.
+
+use serde::{Deserialize, Serialize};
+
+/// A named group of outputs with its own source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Zone {
+    /// Name shown in the tray and used to pause or resume the zone
+    pub name: String,
+    /// Device IDs, aliases or parts of device names, like `device_ids` entries
+    pub devices: Vec<String>,
+    /// Output to capture for this zone (None = the engine's source)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Whether the zone stays silent when the engine starts
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Whether a zone of a running engine is playing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZoneStatus {
+    /// Zone name
+    pub name: String,
+    /// Whether the zone is paused
+    pub paused: bool,
+}
@@ -0,0 +1,178 @@
+//! Synthetic benchmarks (`wemux bench`)
+//!
+//! Measures how this machine copes with a buffer setting before it goes
+//! live: the engine runs on the mock backend, capturing generated audio and
+//! playing it to fake outputs that consume it in real time, so no audio
+//! device is opened. The ring buffer is also measured on its own, with one
+//! writer and as many readers as outputs copying as fast as they can.
+
+use crate::audio::{AudioEngine, EngineConfig, MockBackend, MockSignal, ReaderState, RingBuffer};
+use crate::error::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use windows::Win32::Foundation::FILETIME;
+use windows::Win32::System::Threading::{GetCurrentProcess, GetProcessTimes};
+
+/// 10ms of 48kHz stereo float, a typical capture packet
+const PACKET_BYTES: usize = 3840;
+
+/// Read size of a render pass
+const READ_BYTES: usize = 1920;
+
+/// Outcome of running the engine on fake outputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineResult {
+    pub outputs: usize,
+    pub buffer_ms: u32,
+    /// Time the engine ran
+    pub elapsed: Duration,
+    /// Audio the slowest output got, as a share of the time it ran (1.0 = real time)
+    pub delivered: f64,
+    /// Longest render pass of any output in milliseconds: reading, syncing and
+    /// writing one chunk
+    pub longest_pass_ms: f64,
+    /// Underruns of all outputs together
+    pub underruns: u64,
+    /// CPU time the process used, as a share of all cores (None if unavailable)
+    pub cpu_usage: Option<f64>,
+}
+
+/// Run the engine with `outputs` fake outputs and `buffer_ms` chunks for
+/// `duration`
+pub fn run_pipeline(outputs: usize, buffer_ms: u32, duration: Duration) -> Result<PipelineResult> {
+    let mut backend = MockBackend::new(MockSignal::Sine {
+        frequency: 440.0,
+        amplitude: 0.5,
+    });
+    for n in 1..=outputs {
+        backend = backend.with_device(&format!("bench-{}", n), &format!("Bench {}", n));
+    }
+    let sample_rate = backend.format().sample_rate;
+    let config = EngineConfig {
+        buffer_ms,
+        use_all_devices: true,
+        ..Default::default()
+    };
+    let mut engine = AudioEngine::with_backend(config, backend);
+
+    let cpu_before = process_cpu_time();
+    let started = Instant::now();
+    engine.start()?;
+    thread::sleep(duration);
+    // Stats are cleared when the engine stops
+    let stats = engine.get_stats();
+    let elapsed = started.elapsed();
+    let cpu_after = process_cpu_time();
+    engine.stop()?;
+
+    let expected = elapsed.as_secs_f64() * sample_rate as f64;
+    let delivered = stats
+        .renderers
+        .iter()
+        .map(|r| r.frames_written as f64 / expected)
+        .fold(f64::INFINITY, f64::min);
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+
+    Ok(PipelineResult {
+        outputs,
+        buffer_ms,
+        elapsed,
+        delivered: if stats.renderers.is_empty() {
+            0.0
+        } else {
+            delivered
+        },
+        longest_pass_ms: stats
+            .renderers
+            .iter()
+            .map(|r| r.longest_pass_ms)
+            .fold(0.0, f64::max),
+        underruns: stats.renderers.iter().map(|r| r.underruns).sum(),
+        cpu_usage: cpu_before.zip(cpu_after).map(|(before, after)| {
+            (after - before).as_secs_f64() / elapsed.as_secs_f64() / cores as f64
+        }),
+    })
+}
+
+/// Bytes per second `readers` readers get through a ring buffer together
+/// while one writer fills it, measured for `duration`
+pub fn ring_buffer_throughput(readers: usize, duration: Duration) -> f64 {
+    let buffer = Arc::new(RingBuffer::new(PACKET_BYTES * 64));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let writer = {
+        let buffer = buffer.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let packet = vec![0x55u8; PACKET_BYTES];
+            while !stop.load(Ordering::Relaxed) {
+                buffer.write(&packet);
+            }
+        })
+    };
+
+    let handles: Vec<_> = (0..readers)
+        .map(|_| {
+            let buffer = buffer.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut reader = ReaderState::new(&buffer);
+                let mut data = vec![0u8; READ_BYTES];
+                let mut total = 0u64;
+                // Overwritten audio is read all the same; only the copying counts
+                while !stop.load(Ordering::Relaxed) {
+                    total += reader.read(&buffer, &mut data) as u64;
+                }
+                total
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    thread::sleep(duration);
+    stop.store(true, Ordering::Relaxed);
+    let total: u64 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    let elapsed = start.elapsed().as_secs_f64();
+    let _ = writer.join();
+
+    total as f64 / elapsed
+}
+
+/// CPU time the process used so far, in user and kernel mode
+fn process_cpu_time() -> Option<Duration> {
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    unsafe {
+        GetProcessTimes(
+            GetCurrentProcess(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        )
+        .ok()?;
+    }
+    // FILETIMEs count 100ns intervals
+    let ticks = |t: FILETIME| ((t.dwHighDateTime as u64) << 32) | t.dwLowDateTime as u64;
+    Some(Duration::from_nanos((ticks(kernel) + ticks(user)) * 100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_runs_in_real_time() {
+        let result = run_pipeline(2, 50, Duration::from_millis(500)).unwrap();
+        assert_eq!(result.outputs, 2);
+        // Starting up takes part of the time
+        assert!(result.delivered > 0.5, "{:?}", result);
+        assert!(result.longest_pass_ms > 0.0);
+
+        assert!(ring_buffer_throughput(2, Duration::from_millis(50)) > 0.0);
+    }
+}
@@ -16,10 +16,16 @@
 //!
 //! To uninstall the service:
 //!   wemux service uninstall
+//!
+//! A service installed with `wemux service install --name <name> --config <file>`
+//! is started with `--name <name> --config <file>`.
+
+use wemux::service::ServiceInstance;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let instance = ServiceInstance::from_args(std::env::args_os().skip(1))?;
     // This binary should only be started by the Windows Service Control Manager
-    wemux::service::run_service().map_err(|e| {
+    wemux::service::run_service(instance).map_err(|e| {
         eprintln!("Failed to run service: {}", e);
         e
     })?;
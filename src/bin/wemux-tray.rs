@@ -3,24 +3,83 @@
 //! Windows system tray application for controlling audio synchronization
 //! to multiple HDMI devices.
 //!
-//! Run with `--debug` to show console window and enable stdout/stderr output.
+//! Logs to %LOCALAPPDATA%\wemux\tray.log; the menu shows its last lines and
+//! opens its folder. Run with `--debug` to also show a console window with
+//! stdout/stderr output.
+//! `--no-autostart` leaves audio sync stopped until it is started from the
+//! menu, and `--settings <path>` uses another settings file than the
+//! `wemux-tray.toml` next to the executable. `--profile <name>` picks
+//! `wemux-tray-<name>.toml` next to the executable instead, and
+//! `--minimized-notifications` keeps errors out of balloon notifications
+//! (they still show in the icon, its tooltip and "Recent Errors...").
 
 // Hide console window in release mode unless --debug is passed
 // This is handled at runtime via Windows API
 #![windows_subsystem = "windows"]
 
-use anyhow::Result;
-use std::env;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use tracing_subscriber::EnvFilter;
-use wemux::tray::{TrayApp, TrayConfig};
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use wemux::ipc::{self, InstanceLock, Request, TRAY_INSTANCE_NAME, TRAY_PIPE_NAME};
+use wemux::logging::{LogRing, RollingFile, RotationPolicy};
+use wemux::tray::{self, TrayApp, TrayConfig, TraySettings};
 
 // Global flag for console control handler
 static CONSOLE_EXIT_FLAG: AtomicBool = AtomicBool::new(false);
 
+/// wemux system tray
+#[derive(Parser, Debug)]
+#[command(name = "wemux-tray")]
+#[command(version, about, long_about = None)]
+struct TrayArgs {
+    /// Show a console window with log output
+    #[arg(short, long)]
+    debug: bool,
+
+    /// Leave audio sync stopped until it is started from the menu
+    #[arg(long)]
+    no_autostart: bool,
+
+    /// Use the settings file wemux-tray-<NAME>.toml next to the executable
+    #[arg(long, value_name = "NAME", conflicts_with = "settings")]
+    profile: Option<String>,
+
+    /// Use this settings file instead of wemux-tray.toml next to the executable
+    #[arg(long, value_name = "PATH")]
+    settings: Option<PathBuf>,
+
+    /// Don't show errors as balloon notifications
+    #[arg(long)]
+    minimized_notifications: bool,
+}
+
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    let debug_mode = args.iter().any(|arg| arg == "--debug" || arg == "-d");
+    let args = match TrayArgs::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // Without a console, help and usage errors go to a message box
+            show_message(&e.to_string(), e.use_stderr());
+            std::process::exit(e.exit_code());
+        }
+    };
+    let settings_path = match &args.profile {
+        Some(name) => Some(
+            TraySettings::profile_path(name)
+                .ok_or_else(|| anyhow!("Invalid profile name: {:?}", name))?,
+        ),
+        // Absolute, so the command registered to start at logon finds it too
+        None => args
+            .settings
+            .as_deref()
+            .map(std::path::absolute)
+            .transpose()?,
+    };
+    if let Some(path) = settings_path {
+        TraySettings::use_path(path);
+    }
+    let debug_mode = args.debug;
 
     // In debug mode, allocate a console window for stdout/stderr
     if debug_mode {
@@ -48,19 +107,48 @@ fn main() -> Result<()> {
 
             let _ = SetConsoleCtrlHandler(Some(console_ctrl_handler), true);
         }
+    }
 
-        // Initialize logging only in debug mode
-        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
-        tracing_subscriber::fmt()
-            .with_env_filter(filter)
-            .with_target(false)
-            .init();
+    // Log to the file and the ring "View Log..." shows, and the console in
+    // debug mode; the Logging menu changes the level
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let log_file = tray::default_log_path().and_then(|path| {
+        std::fs::create_dir_all(path.parent()?).ok()?;
+        RollingFile::open(path, RotationPolicy::default()).ok()
+    });
+    tracing_subscriber::registry()
+        .with(wemux::logging::reloadable_filter(filter))
+        .with(
+            fmt::layer()
+                .with_writer(LogRing::global)
+                .with_target(false)
+                .with_ansi(false),
+        )
+        .with(log_file.map(|file| {
+            fmt::layer()
+                .with_writer(std::sync::Mutex::new(file))
+                .with_target(false)
+                .with_ansi(false)
+        }))
+        .with(debug_mode.then(|| fmt::layer().with_target(false)))
+        .init();
 
+    if debug_mode {
         println!("Starting wemux system tray application (debug mode)...");
         println!("Use the system tray Exit menu or Ctrl+C to exit cleanly.");
     }
 
+    // Only one tray per session - hand off to the running instance and exit
+    let _instance_lock = match InstanceLock::acquire(TRAY_INSTANCE_NAME)? {
+        Some(lock) => lock,
+        None => {
+            if let Err(e) = ipc::send_request(TRAY_PIPE_NAME, &Request::Activate) {
+                eprintln!("wemux-tray is already running but did not respond: {}", e);
+            }
+            return Ok(());
+        }
+    };
+
     // Initialize COM (required for Windows audio)
     unsafe {
         windows::Win32::System::Com::CoInitializeEx(
@@ -72,8 +160,8 @@ fn main() -> Result<()> {
 
     // Create and run tray app
     let config = TrayConfig {
-        auto_start: true,
-        show_notifications: true,
+        auto_start: !args.no_autostart,
+        show_notifications: !args.minimized_notifications,
     };
 
     let mut app = TrayApp::new(config)?;
@@ -85,3 +173,25 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Show `text` in a message box, as an error if `error`
+fn show_message(text: &str, error: bool) {
+    use windows::core::HSTRING;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        MessageBoxW, MB_ICONERROR, MB_ICONINFORMATION, MB_OK,
+    };
+
+    let icon = if error {
+        MB_ICONERROR
+    } else {
+        MB_ICONINFORMATION
+    };
+    unsafe {
+        MessageBoxW(
+            None,
+            &HSTRING::from(text.trim_end()),
+            &HSTRING::from("wemux-tray"),
+            MB_OK | icon,
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Measuring how late each output plays (`wemux calibrate`)
+//!
+//! TVs delay their audio by different amounts, so outputs that leave the PC
+//! in sync can still be audibly apart in the room. Calibration plays a click
+//! pattern on every output and finds the clicks in a recording of the room,
+//! made by a microphone on this machine or by a phone, to get each output's
+//! latency relative to the others. The resulting delays go into
+//! [`EngineConfig::delays`](crate::audio::EngineConfig::delays).
+//!
+//! `wemux test` plays a test tone to outputs the same way, to check by ear
+//! that each one works and how far apart they are.
+
+mod pattern;
+mod tone;
+mod wav;
+
+pub use pattern::{analyze, delays, duration_secs, MAX_DEVICES};
+pub use tone::render_test_tone;
+pub use wav::read_wav;
+
+use crate::audio::{HdmiRenderer, LoopbackCapture, SampleEncoding};
+use crate::device::DeviceEnumerator;
+use crate::error::{Result, WemuxError};
+use std::thread;
+use std::time::Duration;
+use tracing::info;
+
+/// Audio kept queued on each output; the same small amount everywhere, so
+/// only the outputs themselves differ
+const QUEUED_MS: u32 = 30;
+
+/// How often outputs are fed and the recording drained
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// One output playing its part of the pattern
+struct Output {
+    renderer: HdmiRenderer,
+    data: Vec<u8>,
+    written: usize,
+}
+
+/// Play the pattern on `device_ids`, in this order, to record it elsewhere
+///
+/// A recording analyzed later must use the same devices in the same order.
+pub fn play(device_ids: &[String]) -> Result<()> {
+    play_pattern(device_ids, || Ok(()))
+}
+
+/// Play the pattern while recording it with `input_id` (None = the default
+/// microphone) and return the latencies [`analyze`] finds
+pub fn measure(device_ids: &[String], input_id: Option<&str>) -> Result<Vec<Option<f64>>> {
+    let enumerator = DeviceEnumerator::new()?;
+    let input = match input_id {
+        Some(id) => enumerator.get_device_by_id(id)?,
+        None => enumerator.get_default_input_device()?,
+    };
+    let mut capture = LoopbackCapture::from_device(&input)?;
+    let format = capture.format().clone();
+    if format.encoding != SampleEncoding::Float || format.bits_per_sample != 32 {
+        return Err(WemuxError::Calibration(format!(
+            "Unsupported microphone format {}",
+            format
+        )));
+    }
+    capture.start()?;
+
+    let mut samples = Vec::new();
+    play_pattern(device_ids, || loop {
+        let frames = capture.read_frames(0)?;
+        if frames.is_empty() {
+            return Ok(());
+        }
+        match frames.data() {
+            Some(data) => {
+                samples.extend(data.chunks_exact(format.block_align as usize).map(|frame| {
+                    let sum: f32 = frame
+                        .chunks_exact(4)
+                        .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+                        .sum();
+                    sum / format.channels as f32
+                }))
+            }
+            None => samples.resize(samples.len() + frames.num_frames() as usize, 0.0),
+        }
+    })?;
+    capture.stop()?;
+
+    analyze(&samples, format.sample_rate, device_ids.len())
+}
+
+/// Play the test tone on `device_ids` at once for `seconds`, straight to
+/// the devices without capturing anything
+pub fn play_test_tone(device_ids: &[String], seconds: f64) -> Result<()> {
+    if device_ids.is_empty() {
+        return Err(WemuxError::Calibration("No devices to test".to_string()));
+    }
+    info!(
+        "Playing test tone on {} devices for {:.0}s",
+        device_ids.len(),
+        seconds
+    );
+    play_rendered(
+        device_ids,
+        |_, sample_rate, channels| render_test_tone(sample_rate, channels, seconds),
+        || Ok(()),
+    )
+}
+
+/// Play the pattern on every device, calling `tick` between writes
+fn play_pattern(device_ids: &[String], tick: impl FnMut() -> Result<()>) -> Result<()> {
+    if device_ids.is_empty() || device_ids.len() > MAX_DEVICES {
+        return Err(WemuxError::Calibration(format!(
+            "Calibration needs 1 to {} devices, got {}",
+            MAX_DEVICES,
+            device_ids.len()
+        )));
+    }
+
+    info!(
+        "Playing calibration pattern on {} devices for {:.0}s",
+        device_ids.len(),
+        duration_secs(device_ids.len())
+    );
+    play_rendered(
+        device_ids,
+        |index, sample_rate, channels| {
+            pattern::render(index, device_ids.len(), sample_rate, channels)
+        },
+        tick,
+    )
+}
+
+/// Play what `render` returns for each device (given its index, sample rate
+/// and channels) on all of them at once, calling `tick` between writes
+fn play_rendered(
+    device_ids: &[String],
+    render: impl Fn(usize, u32, u16) -> Vec<f32>,
+    mut tick: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let enumerator = DeviceEnumerator::new()?;
+    let mut outputs = Vec::with_capacity(device_ids.len());
+    for (index, id) in device_ids.iter().enumerate() {
+        let renderer = HdmiRenderer::new(&enumerator.get_device_by_id(id)?)?;
+        let format = renderer.format();
+        if format.encoding != SampleEncoding::Float || format.bits_per_sample != 32 {
+            return Err(WemuxError::Calibration(format!(
+                "Unsupported format {} on {}",
+                format,
+                renderer.device_name()
+            )));
+        }
+        let data = render(index, format.sample_rate, format.channels)
+            .into_iter()
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        outputs.push(Output {
+            renderer,
+            data,
+            written: 0,
+        });
+    }
+
+    for output in &mut outputs {
+        output.renderer.start()?;
+    }
+
+    loop {
+        tick()?;
+
+        let mut playing = false;
+        for output in &mut outputs {
+            let format = output.renderer.format().clone();
+            let padding = output.renderer.padding_frames()?;
+            if output.written < output.data.len() {
+                let room = format
+                    .buffer_size_for_ms(QUEUED_MS)
+                    .saturating_sub(format.frames_to_bytes(padding));
+                let end = (output.written + room - room % format.block_align as usize)
+                    .min(output.data.len());
+                let frames = output
+                    .renderer
+                    .write_frames(&output.data[output.written..end], 0)?;
+                output.written += format.frames_to_bytes(frames);
+                playing = true;
+            } else if padding > 0 {
+                playing = true;
+            }
+        }
+        if !playing {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    for output in &mut outputs {
+        output.renderer.stop()?;
+    }
+    Ok(())
+}
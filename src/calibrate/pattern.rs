@@ -0,0 +1,187 @@
+//! Click pattern played for calibration, and finding it in a recording
+//!
+//! Device `i` (0-based) plays `i + 1` clicks at the start of its own slot,
+//! one slot per device and round. The number of clicks in a burst tells
+//! which device played it, so the recording needs no common start time with
+//! the playback: each device's latency relative to the others is where its
+//! bursts land compared to where they were scheduled.
+
+use crate::error::{Result, WemuxError};
+
+/// Most devices one pattern can tell apart
+pub const MAX_DEVICES: usize = 6;
+
+/// Silence before the first click, covering the start of playback
+const LEAD_IN_SECS: f64 = 0.5;
+
+/// Time given to each device in a round
+const SLOT_SECS: f64 = 1.2;
+
+/// Time between the clicks of one burst
+const CLICK_SPACING_SECS: f64 = 0.08;
+
+/// Rounds played; the median of all rounds is kept
+const ROUNDS: usize = 3;
+
+/// Silence after the last click, covering the slowest output
+const TAIL_SECS: f64 = 1.0;
+
+/// Length and pitch of a click
+const CLICK_SECS: f64 = 0.004;
+const CLICK_HZ: f64 = 3000.0;
+
+/// Time after an onset in which no other onset is counted (room echo)
+const REFRACTORY_SECS: f64 = 0.04;
+
+/// Length of the pattern for `devices` outputs in seconds
+pub fn duration_secs(devices: usize) -> f64 {
+    LEAD_IN_SECS + (ROUNDS * devices) as f64 * SLOT_SECS + TAIL_SECS
+}
+
+/// Interleaved 32-bit float samples device `device` of `devices` plays
+pub fn render(device: usize, devices: usize, sample_rate: u32, channels: u16) -> Vec<f32> {
+    let rate = sample_rate as f64;
+    let channels = channels as usize;
+    let frames = (duration_secs(devices) * rate) as usize;
+    let mut samples = vec![0.0f32; frames * channels];
+
+    let click_frames = (CLICK_SECS * rate) as usize;
+    for round in 0..ROUNDS {
+        let slot_start = LEAD_IN_SECS + (round * devices + device) as f64 * SLOT_SECS;
+        for click in 0..=device {
+            let start = ((slot_start + click as f64 * CLICK_SPACING_SECS) * rate) as usize;
+            for n in 0..click_frames.min(frames.saturating_sub(start)) {
+                let decay = 1.0 - n as f64 / click_frames as f64;
+                let value = (std::f64::consts::TAU * CLICK_HZ * n as f64 / rate).sin() * decay;
+                let frame = &mut samples[(start + n) * channels..][..channels];
+                frame.fill(value as f32 * 0.8);
+            }
+        }
+    }
+    samples
+}
+
+/// Latency of each device in milliseconds, relative to the earliest one
+///
+/// `samples` is a mono recording of the pattern played on `devices` outputs.
+/// Devices whose clicks weren't heard are None.
+pub fn analyze(samples: &[f32], sample_rate: u32, devices: usize) -> Result<Vec<Option<f64>>> {
+    let rate = sample_rate as f64;
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak < 0.001 {
+        return Err(WemuxError::Calibration(
+            "The recording is silent".to_string(),
+        ));
+    }
+
+    // Clicks stand out from the background, quieter outputs included
+    let mut background: Vec<f32> = samples.iter().step_by(16).map(|s| s.abs()).collect();
+    background.sort_by(f32::total_cmp);
+    let noise = background[background.len() / 2];
+    let threshold = (peak * 0.15).max(noise * 8.0);
+
+    let refractory = (REFRACTORY_SECS * rate) as usize;
+    let mut onsets: Vec<f64> = Vec::new();
+    let mut last = None;
+    for (i, sample) in samples.iter().enumerate() {
+        if sample.abs() >= threshold && last.is_none_or(|last| i >= last + refractory) {
+            onsets.push(i as f64 / rate);
+            last = Some(i);
+        }
+    }
+
+    // Bursts as (time of the first click, time of the last click, number of clicks)
+    let mut bursts: Vec<(f64, f64, usize)> = Vec::new();
+    for onset in onsets {
+        match bursts.last_mut() {
+            Some((_, last, count)) if onset - *last <= CLICK_SPACING_SECS * 1.5 => {
+                *last = onset;
+                *count += 1;
+            }
+            _ => bursts.push((onset, onset, 1)),
+        }
+    }
+
+    // Where each burst landed compared to its slot, folded into one round
+    let period = devices as f64 * SLOT_SECS;
+    let mut offsets: Vec<Vec<f64>> = vec![Vec::new(); devices];
+    let mut reference = None;
+    for (time, _, count) in bursts {
+        if count > devices {
+            continue;
+        }
+        let device = count - 1;
+        let residual = time - device as f64 * SLOT_SECS;
+        let reference = *reference.get_or_insert(residual);
+        let mut offset = (residual - reference).rem_euclid(period);
+        if offset > period / 2.0 {
+            offset -= period;
+        }
+        offsets[device].push(offset);
+    }
+
+    let medians: Vec<Option<f64>> = offsets
+        .into_iter()
+        .map(|mut offsets| {
+            offsets.sort_by(f64::total_cmp);
+            offsets.get(offsets.len() / 2).copied()
+        })
+        .collect();
+    let Some(earliest) = medians.iter().flatten().copied().reduce(f64::min) else {
+        return Err(WemuxError::Calibration(
+            "No clicks found in the recording".to_string(),
+        ));
+    };
+    Ok(medians
+        .into_iter()
+        .map(|median| median.map(|m| (m - earliest) * 1000.0))
+        .collect())
+}
+
+/// Delay in milliseconds that lines each device up with the latest one
+pub fn delays(latencies: &[Option<f64>]) -> Vec<Option<u32>> {
+    let latest = latencies.iter().flatten().copied().fold(0.0, f64::max);
+    latencies
+        .iter()
+        .map(|latency| latency.map(|l| (latest - l).round() as u32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latencies_found() {
+        let rate = 48_000;
+        let latencies_ms = [0.0, 40.0, 120.0];
+        let devices = latencies_ms.len();
+
+        // The recording starts 300ms before playback, with a quiet hiss
+        let start = (0.3 * rate as f64) as usize;
+        let mut recording =
+            vec![0.0f32; start + (duration_secs(devices) * 1.5 * rate as f64) as usize];
+        for (i, sample) in recording.iter_mut().enumerate() {
+            *sample = ((i * 7919) % 101) as f32 / 101.0 * 0.01 - 0.005;
+        }
+        for (device, latency) in latencies_ms.iter().enumerate() {
+            let offset = start + (latency / 1000.0 * rate as f64) as usize;
+            // Farther outputs are quieter
+            let gain = 1.0 / (device + 1) as f32;
+            for (i, sample) in render(device, devices, rate, 1).into_iter().enumerate() {
+                recording[offset + i] += sample * gain;
+            }
+        }
+
+        let measured = analyze(&recording, rate, devices).unwrap();
+        for (measured, expected) in measured.iter().zip(latencies_ms) {
+            assert!((measured.unwrap() - expected).abs() < 2.0, "{:?}", measured);
+        }
+        assert_eq!(
+            delays(&[Some(0.0), Some(40.2), Some(120.0), None]),
+            [Some(120), Some(80), Some(0), None]
+        );
+
+        assert!(analyze(&vec![0.0; 48_000], rate, devices).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! Test signal of `wemux test`
+//!
+//! Beeps move through the channels in device order (front left, front right,
+//! center...), each channel at a higher pitch, so a missing or swapped
+//! speaker is easy to spot. Every output starts the signal at the same time,
+//! so on several outputs the beeps sound as one only if they are in step.
+
+/// Length of one beep
+const BEEP_SECS: f64 = 0.3;
+
+/// Time from one beep to the next
+const STEP_SECS: f64 = 0.75;
+
+/// Pitch of the first channel; each further channel is a major third higher
+const BASE_HZ: f64 = 440.0;
+
+/// Fade in and out of each beep, so it doesn't click
+const RAMP_SECS: f64 = 0.01;
+
+/// Interleaved 32-bit float samples of `seconds` of the test signal
+pub fn render_test_tone(sample_rate: u32, channels: u16, seconds: f64) -> Vec<f32> {
+    let rate = sample_rate as f64;
+    let channels = channels.max(1) as usize;
+    let frames = (seconds * rate) as usize;
+    let mut samples = vec![0.0f32; frames * channels];
+
+    let beep_frames = (BEEP_SECS * rate) as usize;
+    let ramp_frames = (RAMP_SECS * rate).max(1.0);
+    let mut beep = 0;
+    loop {
+        let start = (beep as f64 * STEP_SECS * rate) as usize;
+        if start >= frames {
+            break;
+        }
+        let channel = beep % channels;
+        let hz = BASE_HZ * 2f64.powf(channel as f64 * 4.0 / 12.0);
+        for n in 0..beep_frames.min(frames - start) {
+            let ramp = (n as f64 / ramp_frames)
+                .min((beep_frames - n) as f64 / ramp_frames)
+                .min(1.0);
+            let value = (std::f64::consts::TAU * hz * n as f64 / rate).sin() * ramp * 0.5;
+            samples[(start + n) * channels + channel] = value as f32;
+        }
+        beep += 1;
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beeps_walk_the_channels() {
+        let rate = 1000;
+        let samples = render_test_tone(rate, 2, 2.0);
+        assert_eq!(samples.len(), 4000);
+
+        let loud = |frame: usize, channel: usize| samples[frame * 2 + channel].abs() > 0.1;
+        let beeping = |start: usize, channel: usize| (start..start + 300).any(|f| loud(f, channel));
+        // First beep on the left, the next on the right, then left again
+        assert!(beeping(0, 0) && !beeping(0, 1));
+        assert!(beeping(750, 1) && !beeping(750, 0));
+        assert!(beeping(1500, 0) && !beeping(1500, 1));
+        // Silence between beeps
+        assert!(!(300..750).any(|f| loud(f, 0) || loud(f, 1)));
+    }
+}
@@ -0,0 +1,52 @@
+//! Reading recordings from WAV files
+
+use crate::audio::WavAudio;
+use crate::error::{Result, WemuxError};
+use std::path::Path;
+
+/// Read a WAV file as mono samples and its sample rate
+///
+/// Integer PCM of 16, 24 or 32 bits and 32-bit float are supported; channels
+/// are averaged.
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let data = std::fs::read(path).map_err(|e| {
+        WemuxError::Calibration(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    parse(&data)
+        .map_err(|message| WemuxError::Calibration(format!("{}: {}", path.display(), message)))
+}
+
+fn parse(data: &[u8]) -> std::result::Result<(Vec<f32>, u32), String> {
+    let wav = WavAudio::parse(data)?;
+    Ok((wav.to_mono(), wav.sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `WAVE_FORMAT_PCM`
+    const FORMAT_PCM: u16 = 0x0001;
+
+    #[test]
+    fn test_parse_pcm16_stereo() {
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+        wav.extend_from_slice(b"fmt \x10\0\0\0");
+        wav.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&44_100u32.to_le_bytes());
+        wav.extend_from_slice(&(44_100u32 * 4).to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data\x08\0\0\0");
+        for sample in [16384i16, 0, -32768, -32768] {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let (samples, rate) = parse(&wav).unwrap();
+        assert_eq!(rate, 44_100);
+        assert_eq!(samples, [0.25, -1.0]);
+        assert!(parse(b"ID3\x04 an mp3").is_err());
+    }
+}
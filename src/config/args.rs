@@ -1,6 +1,16 @@
 //! CLI argument parsing using clap
 
+use crate::audio::{Announcement, FallbackSource, MixSource, SourceType, VolumeMode};
+use crate::health::parse_period;
+use crate::logging::{
+    LogLevel, LogRotation, RotationPolicy, DEFAULT_LOG_KEEP, DEFAULT_LOG_MAX_SIZE_MB,
+};
+use crate::service::config::ServiceMode;
+use crate::service::template::ConfigLanguage;
+use crate::service::SERVICE_NAME;
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// wemux - Windows Multi-HDMI Audio Sync
 ///
@@ -24,6 +34,18 @@ pub struct Args {
     /// Log output to file
     #[arg(long, global = true)]
     pub log: Option<String>,
+
+    /// When to start a new log file
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub log_rotation: LogRotation,
+
+    /// Size limit of a log file in MB with --log-rotation size
+    #[arg(long, default_value_t = DEFAULT_LOG_MAX_SIZE_MB, global = true)]
+    pub log_max_size_mb: u64,
+
+    /// Rotated log files to keep
+    #[arg(long, default_value_t = DEFAULT_LOG_KEEP, global = true)]
+    pub log_keep: usize,
 }
 
 /// Available commands
@@ -38,35 +60,262 @@ pub enum Command {
         /// Show device IDs (useful for scripting)
         #[arg(long)]
         show_ids: bool,
+
+        /// Show each device's mix format and latency class
+        #[arg(long)]
+        detailed: bool,
+
+        /// Keep running and print devices as they are plugged in, removed,
+        /// renamed or made the default
+        #[arg(long)]
+        watch: bool,
     },
 
     /// Start audio synchronization
     Start {
-        /// Specify HDMI device IDs to use (comma-separated)
+        /// Specify HDMI device IDs or aliases to use (comma-separated)
         /// If not specified, all HDMI devices will be used
         #[arg(short, long, value_delimiter = ',')]
         devices: Option<Vec<String>>,
 
-        /// Exclude specific device IDs (comma-separated)
+        /// Exclude specific device IDs or aliases (comma-separated)
         #[arg(short, long, value_delimiter = ',')]
         exclude: Option<Vec<String>>,
 
         /// Buffer size in milliseconds (default: 50)
+        ///
+        /// Audio is written to each device in chunks of this size; the ring
+        /// buffer and pre-fill grow with it. 10 to 500ms. Same as buffer_ms in
+        /// the service configuration
         #[arg(short, long, default_value = "50")]
         buffer: u32,
 
-        /// Source device ID for loopback capture
+        /// Source device for loopback capture (ID, alias or name), or the
+        /// file to play with --source-type file
         /// If not specified, uses system default output
         #[arg(long)]
         source: Option<String>,
+
+        /// Whether --source is an output to loop back, an input to record
+        /// (line-in, microphone; without --source the default input) or a
+        /// audio file (WAV, FLAC, MP3, Ogg Vorbis) to play in a loop
+        #[arg(long, value_enum, default_value_t = SourceType::Loopback)]
+        source_type: SourceType,
+
+        /// App to leave out of the captured audio, by executable name, e.g.
+        /// Discord (repeatable or comma-separated; default: `exclude_apps`
+        /// in the service config)
+        #[arg(long = "exclude-app", value_name = "APP", value_delimiter = ',')]
+        exclude_apps: Vec<String>,
+
+        /// Extra source to mix in, as DEVICE or DEVICE@GAIN (repeatable)
+        #[arg(long, value_name = "DEVICE[@GAIN]")]
+        mix: Vec<MixSource>,
+
+        /// Output or input to play instead of the source after it has been
+        /// silent for SECONDS (default: 5), until the source is audible again
+        #[arg(long, value_name = "DEVICE[@SECONDS]")]
+        fallback: Option<FallbackSource>,
+
+        /// Output or input played over the source, e.g. a paging microphone;
+        /// the source is turned down by DB decibels (default: 15) while it is
+        /// audible
+        #[arg(long, value_name = "DEVICE[@DB]")]
+        announce: Option<Announcement>,
+
+        /// Pace outputs by the audio the source delivers instead of the first
+        /// output's clock (for variable-rate sources such as VB-Cable)
+        #[arg(long)]
+        follow_source: bool,
+
+        /// Capture on a dedicated core with MMCSS, polling instead of sleeping
+        /// (lowest latency, keeps one core busy)
+        #[arg(long)]
+        performance: bool,
+
+        /// Print the source, outputs, master and buffer sizes that would be
+        /// used, without opening any device
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Keep running in the background after the console closes; writes
+        /// its PID to %LOCALAPPDATA%\wemux\daemon.pid and logs to
+        /// daemon.log next to it unless --log is given
+        #[arg(long, conflicts_with = "dry_run")]
+        daemon: bool,
+
+        /// Run as the background copy started by --daemon
+        #[arg(long, hide = true)]
+        detached: bool,
     },
 
+    /// Stop audio synchronization of the running service, daemon or tray
+    Stop,
+
+    /// Restart audio synchronization of the running service, daemon or tray
+    Restart,
+
     /// Show detailed device information
     Info {
-        /// Device ID to show info for
+        /// Device ID, name or alias to show info for
         device_id: String,
     },
 
+    /// Live dashboard of the engine run by the wemux service
+    Monitor {
+        /// Refresh interval in milliseconds
+        #[arg(short, long, default_value = "250")]
+        interval: u64,
+
+        /// Watch the service on another machine (its remote_listen address, e.g. htpc:9465)
+        #[arg(long, requires = "token")]
+        host: Option<String>,
+
+        /// Token matching the remote service's remote_token
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    /// Underruns, reconnects and drift per output recorded by the wemux service
+    ///
+    /// The service keeps health_history_days days of history (default: 7);
+    /// outputs with the most underruns per hour are listed first.
+    Stats {
+        /// Only show this output (ID, name or alias)
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Period to add up, e.g. 30m, 24h or 7d
+        #[arg(long, default_value = "24h", value_parser = parse_period)]
+        since: Duration,
+
+        /// Service instance whose history to read
+        #[arg(long, default_value = SERVICE_NAME)]
+        name: String,
+    },
+
+    /// Check the audio setup and print a pass/fail report
+    Doctor,
+
+    /// Measure how this machine handles a buffer setting, without audio devices
+    ///
+    /// Runs the engine on generated audio and fake outputs, then reports how
+    /// much audio got through, the longest render pass, underruns and CPU
+    /// usage, followed by the ring buffer's raw throughput.
+    Bench {
+        /// Number of fake outputs
+        #[arg(short, long, default_value = "4")]
+        outputs: usize,
+
+        /// Buffer size in milliseconds, as for start (10 to 500ms)
+        #[arg(short, long, default_value = "50")]
+        buffer: u32,
+
+        /// How long to run the engine, in seconds
+        #[arg(long, default_value = "10")]
+        seconds: u64,
+    },
+
+    /// Measure how late each output plays and the delays that line them up
+    ///
+    /// Plays a click pattern on every output while a microphone records the
+    /// room. Without a microphone, play the pattern with --play, record it on
+    /// a phone and analyze the WAV file with --file.
+    Calibrate {
+        /// Devices to calibrate, in this order (IDs, names or aliases, comma-separated; default: all HDMI devices)
+        #[arg(short, long, value_delimiter = ',')]
+        devices: Option<Vec<String>>,
+
+        /// Microphone to record with (ID or part of its name; default: the default input)
+        #[arg(long, conflicts_with_all = ["play", "file"])]
+        input: Option<String>,
+
+        /// Only play the pattern, for recording it on another device
+        #[arg(long, conflicts_with = "file")]
+        play: bool,
+
+        /// Analyze a WAV recording of the pattern played with --play
+        #[arg(long)]
+        file: Option<String>,
+
+        /// Write the delays to the configuration file
+        #[arg(long, conflicts_with = "play")]
+        save: bool,
+    },
+
+    /// Play a test tone on outputs to check they work and play in step
+    ///
+    /// Sends beeps straight to the devices, without capturing anything: one
+    /// channel at a time, front left first, each channel at a higher pitch.
+    /// On several devices the beeps start together, so any echo between them
+    /// is a delay one of them adds.
+    Test {
+        /// Devices to play on (IDs, names or aliases)
+        #[arg(required = true, value_delimiter = ',')]
+        devices: Vec<String>,
+
+        /// How long to play, in seconds
+        #[arg(long, default_value = "6")]
+        seconds: u64,
+    },
+
+    /// Run unattended: restart the engine after any failure, forever
+    ///
+    /// Takes its devices from the service configuration file and needs no
+    /// desktop session, for signage and other kiosk setups.
+    Kiosk {
+        /// Configuration file (default: the file the service would use)
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Heartbeat file updated with the supervisor's state
+        /// (default: %LOCALAPPDATA%\wemux\heartbeat.json)
+        #[arg(long)]
+        heartbeat: Option<String>,
+
+        /// Seconds between heartbeat updates
+        #[arg(long, default_value = "10")]
+        heartbeat_interval: u64,
+
+        /// Longest wait between restart attempts in seconds
+        #[arg(long, default_value = "60")]
+        max_backoff: u64,
+    },
+
+    /// Pause or resume an output of the running service, daemon or tray
+    Device {
+        /// Device action to perform
+        #[command(subcommand)]
+        action: DeviceAction,
+    },
+
+    /// Show or set the volume of the outputs of the running service, daemon or tray,
+    /// apart from the volume of the main output
+    Volume {
+        /// Mirror volume in percent (0-100)
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        percent: Option<u8>,
+
+        /// Follow the source's volume, play at the mirror volume (fixed) or
+        /// follow the source's volume scaled by it
+        #[arg(long, value_enum)]
+        mode: Option<VolumeMode>,
+    },
+
+    /// Change the log level of the running service, daemon and tray without restarting them
+    LogLevel {
+        /// New log level
+        #[arg(value_enum)]
+        level: LogLevel,
+    },
+
+    /// Configuration file management
+    Config {
+        /// Configuration action to perform
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
     /// Windows Service management
     Service {
         /// Service action to perform
@@ -79,13 +328,35 @@ pub enum Command {
 #[derive(Subcommand, Debug)]
 pub enum ServiceAction {
     /// Install the Windows service
-    Install,
+    Install {
+        /// Run it as LocalSystem in session 0 or as a per-user service in the
+        /// signed-in user's session (default: service_mode from the configuration)
+        #[arg(long, value_enum)]
+        mode: Option<ServiceMode>,
+
+        /// Install another instance under this name, e.g. one per zone
+        #[arg(long, default_value = SERVICE_NAME)]
+        name: String,
+
+        /// Configuration file of the instance (default: the file the
+        /// `wemux` service reads)
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
 
     /// Uninstall the Windows service
-    Uninstall,
+    Uninstall {
+        /// Name the instance was installed under
+        #[arg(long, default_value = SERVICE_NAME)]
+        name: String,
+    },
 
     /// Show service status
-    Status,
+    Status {
+        /// Name the instance was installed under
+        #[arg(long, default_value = SERVICE_NAME)]
+        name: String,
+    },
 
     /// Generate a sample configuration file
     Config {
@@ -95,7 +366,72 @@ pub enum ServiceAction {
     },
 }
 
+/// Actions on an output of the running engine
+#[derive(Subcommand, Debug)]
+pub enum DeviceAction {
+    /// List the outputs with their device period, latency class and buffer
+    Status,
+
+    /// Stop playing to a device
+    Pause {
+        /// Device ID, name or alias
+        device: String,
+    },
+
+    /// Play to a paused device again
+    Resume {
+        /// Device ID, name or alias
+        device: String,
+    },
+
+    /// Pause a playing device or resume a paused one
+    Toggle {
+        /// Device ID, name or alias
+        device: String,
+    },
+}
+
+/// Configuration file actions
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Write a commented configuration file with default values
+    Init {
+        /// Output path (default: %LOCALAPPDATA%\wemux\config.toml)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Also document advanced sections (aliases, profiles, schedule)
+        #[arg(long)]
+        full: bool,
+
+        /// Language of the comments
+        #[arg(long, value_enum, default_value_t)]
+        lang: ConfigLanguage,
+
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Remove tray settings of devices not seen for a while
+    #[cfg(feature = "tray")]
+    Prune {
+        /// Remove devices not seen for this many days
+        #[arg(long, default_value_t = crate::tray::PRUNE_AFTER_DAYS)]
+        days: u64,
+    },
+}
+
 impl Args {
+    /// Rotation of the `--log` file
+    pub fn log_policy(&self) -> RotationPolicy {
+        RotationPolicy {
+            rotation: self.log_rotation,
+            max_size: self.log_max_size_mb * 1024 * 1024,
+            keep: self.log_keep,
+        }
+    }
+
     /// Get the log level based on verbose/quiet flags
     pub fn log_level(&self) -> tracing::Level {
         if self.quiet {
@@ -118,6 +454,16 @@ impl Default for Command {
             exclude: None,
             buffer: 50,
             source: None,
+            source_type: SourceType::Loopback,
+            exclude_apps: Vec::new(),
+            mix: Vec::new(),
+            fallback: None,
+            announce: None,
+            follow_source: false,
+            performance: false,
+            dry_run: false,
+            daemon: false,
+            detached: false,
         }
     }
 }
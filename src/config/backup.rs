@@ -0,0 +1,236 @@
+//! Timestamped backups of settings and configuration files
+//!
+//! Every save first copies the file it replaces to a `backups` directory next
+//! to it, keeping the most recent few. A bad edit or an interrupted save can
+//! then be undone instead of falling back to defaults.
+//!
+//! [`write_atomic`] saves through a temporary file renamed over the old one,
+//! so a crash or power loss mid-save leaves either version complete, and keeps
+//! the replaced version as `<name>.bak` to recover from if the new one is
+//! still found damaged.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Backups kept per file
+pub const BACKUP_COUNT: usize = 10;
+
+/// Name of the directory holding backups, next to the backed-up file
+const BACKUP_DIR: &str = "backups";
+
+/// Backups of one file, named `<stem>.<YYYYMMDD-HHMMSS-mmm>.<ext>`
+#[derive(Debug, Clone)]
+pub struct Backups {
+    path: PathBuf,
+    keep: usize,
+}
+
+impl Backups {
+    /// Backups of `path`, keeping the last [`BACKUP_COUNT`]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            keep: BACKUP_COUNT,
+        }
+    }
+
+    /// Keep the last `keep` backups instead
+    pub fn with_keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        self
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(BACKUP_DIR)
+    }
+
+    /// File name prefix shared by all backups of this file
+    fn prefix(&self) -> String {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        format!("{}.", stem)
+    }
+
+    fn backup_name(&self, timestamp: &str) -> String {
+        match self.path.extension() {
+            Some(ext) => format!("{}{}.{}", self.prefix(), timestamp, ext.to_string_lossy()),
+            None => format!("{}{}", self.prefix(), timestamp),
+        }
+    }
+
+    /// Copy the current file into a new backup and drop the oldest ones
+    ///
+    /// Returns the backup path, or None if there is no file to back up.
+    pub fn create(&self) -> io::Result<Option<PathBuf>> {
+        self.create_at(&timestamp())
+    }
+
+    fn create_at(&self, timestamp: &str) -> io::Result<Option<PathBuf>> {
+        if !self.path.is_file() {
+            return Ok(None);
+        }
+
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let backup = dir.join(self.backup_name(timestamp));
+        fs::copy(&self.path, &backup)?;
+        debug!("Backed up {:?} to {:?}", self.path, backup);
+
+        for old in self.list()?.into_iter().skip(self.keep) {
+            if let Err(e) = fs::remove_file(&old) {
+                warn!("Failed to remove old backup {:?}: {}", old, e);
+            }
+        }
+        Ok(Some(backup))
+    }
+
+    /// Existing backups, newest first
+    pub fn list(&self) -> io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(self.dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let prefix = self.prefix();
+        let extension = self.path.extension();
+        let mut backups: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension() == extension
+                    && path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+            })
+            .collect();
+
+        // Timestamps sort chronologically by name
+        backups.sort();
+        backups.reverse();
+        Ok(backups)
+    }
+
+    /// Replace the file with its newest backup, consuming that backup
+    ///
+    /// Restoring again steps further back. Returns the restored backup, or
+    /// None if there is none.
+    pub fn restore_latest(&self) -> io::Result<Option<PathBuf>> {
+        let Some(latest) = self.list()?.into_iter().next() else {
+            return Ok(None);
+        };
+
+        fs::copy(&latest, &self.path)?;
+        fs::remove_file(&latest)?;
+        info!("Restored {:?} from {:?}", self.path, latest);
+        Ok(Some(latest))
+    }
+}
+
+/// `<name>.bak` next to `path`, holding the version [`write_atomic`] replaced
+pub fn bak_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Replace `path` with `contents` so it is never left half-written
+///
+/// The contents are written and flushed to disk in a temporary file next to
+/// `path`, the current file is copied to [`bak_path`], and the temporary
+/// file is renamed over it.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let temp = with_suffix(path, ".tmp");
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        if path.is_file() {
+            fs::copy(path, bak_path(path))?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Local time as `YYYYMMDD-HHMMSS-mmm`, which sorts chronologically
+fn timestamp() -> String {
+    let now = unsafe { windows::Win32::System::SystemInformation::GetLocalTime() };
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        now.wYear, now.wMonth, now.wDay, now.wHour, now.wMinute, now.wSecond, now.wMilliseconds
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        let dir = std::env::temp_dir().join(format!("wemux-backup-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+        let backups = Backups::new(&path).with_keep(2);
+
+        assert!(backups.create_at("20260101-000000-000").unwrap().is_none());
+        for version in 1..=3 {
+            fs::write(&path, format!("version = {}", version)).unwrap();
+            let stamp = format!("20260101-00000{}-000", version);
+            assert!(backups.create_at(&stamp).unwrap().is_some());
+        }
+        // Unrelated files in the backup directory are left alone
+        fs::write(
+            dir.join(BACKUP_DIR).join("other.20260101-000000-000.toml"),
+            "",
+        )
+        .unwrap();
+
+        let list = backups.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list[0].ends_with("settings.20260101-000003-000.toml"));
+
+        fs::write(&path, "broken").unwrap();
+        backups.restore_latest().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version = 3");
+        backups.restore_latest().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version = 2");
+        assert!(backups.restore_latest().unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_previous_version() {
+        let dir = std::env::temp_dir().join(format!("wemux-atomic-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+
+        write_atomic(&path, "version = 1").unwrap();
+        assert!(!bak_path(&path).exists());
+        write_atomic(&path, "version = 2").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version = 2");
+        assert_eq!(fs::read_to_string(bak_path(&path)).unwrap(), "version = 1");
+        assert!(bak_path(&path).ends_with("settings.toml.bak"));
+
+        // Only the file, its .bak and nothing left over from writing
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
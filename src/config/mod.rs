@@ -1,5 +1,11 @@
 //! Configuration and CLI argument handling
 
+#[cfg(feature = "cli")]
 mod args;
+mod backup;
+mod watch;
 
-pub use args::{Args, Command, ServiceAction};
+#[cfg(feature = "cli")]
+pub use args::{Args, Command, ConfigAction, DeviceAction, ServiceAction};
+pub use backup::{bak_path, write_atomic, Backups, BACKUP_COUNT};
+pub use watch::ConfigWatcher;
@@ -0,0 +1,145 @@
+//! Change detection for configuration and settings files
+//!
+//! Files are polled by modification time and size instead of using directory
+//! notifications: editors often save by replacing the file, and the file the
+//! service loads can itself change when a higher-priority location is created
+//! or removed.
+//!
+//! Editors may save in several writes, so a change can be held back until
+//! the file stopped changing for a moment ([`ConfigWatcher::check_settled`]).
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// What identifies one version of the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Remembers which config file was loaded and in what version
+#[derive(Debug, Clone, Default)]
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    stamp: Option<FileStamp>,
+    /// Changed version not reported yet, and since when it is unchanged
+    pending: Option<(Option<PathBuf>, Option<FileStamp>, Instant)>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `path` as it is now (None = no config file, defaults in use)
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let stamp = path.as_deref().and_then(FileStamp::read);
+        Self {
+            path,
+            stamp,
+            pending: None,
+        }
+    }
+
+    /// Path of the watched file
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Check whether the config changed since the last call
+    ///
+    /// `current` is the file that would be loaded now; a different path counts
+    /// as a change, as does a new modification time or size of the same path.
+    pub fn check(&mut self, current: Option<PathBuf>) -> bool {
+        self.check_settled(current, Duration::ZERO)
+    }
+
+    /// Like [`check`](Self::check), but report a change only once the file
+    /// stayed the same for `quiet`
+    ///
+    /// Meant to be called periodically; until the change settles it returns
+    /// false.
+    pub fn check_settled(&mut self, current: Option<PathBuf>, quiet: Duration) -> bool {
+        let stamp = current.as_deref().and_then(FileStamp::read);
+        if current == self.path && stamp == self.stamp {
+            self.pending = None;
+            return false;
+        }
+
+        let since = match &self.pending {
+            Some((path, pending, since)) if *path == current && *pending == stamp => *since,
+            _ => Instant::now(),
+        };
+        if since.elapsed() < quiet {
+            self.pending = Some((current, stamp, since));
+            return false;
+        }
+
+        self.pending = None;
+        self.path = current;
+        self.stamp = stamp;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_changes() {
+        let dir = std::env::temp_dir().join(format!("wemux-watch-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+
+        let mut watcher = ConfigWatcher::new(None);
+        assert!(!watcher.check(None));
+
+        std::fs::write(&path, "buffer_ms = 50").unwrap();
+        assert!(watcher.check(Some(path.clone())));
+        assert!(!watcher.check(Some(path.clone())));
+
+        // Same modification time granularity can hide quick edits; size still differs
+        std::fs::write(&path, "buffer_ms = 100").unwrap();
+        assert!(watcher.check(Some(path.clone())));
+        assert_eq!(watcher.path(), Some(path.as_path()));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.check(None));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_waits_for_changes_to_settle() {
+        let dir = std::env::temp_dir().join(format!("wemux-settle-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+        let quiet = Duration::from_millis(50);
+
+        std::fs::write(&path, "enabled = true").unwrap();
+        let mut watcher = ConfigWatcher::new(Some(path.clone()));
+
+        std::fs::write(&path, "[devices").unwrap();
+        assert!(!watcher.check_settled(Some(path.clone()), quiet));
+        // Still being written: the wait starts over
+        std::thread::sleep(quiet / 2);
+        std::fs::write(&path, "[devices]\nenabled = false").unwrap();
+        assert!(!watcher.check_settled(Some(path.clone()), quiet));
+
+        std::thread::sleep(quiet);
+        assert!(watcher.check_settled(Some(path.clone()), quiet));
+        assert!(!watcher.check_settled(Some(path.clone()), quiet));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,121 @@
+//! Background operation without the service or the tray (`wemux start --daemon`)
+//!
+//! `wemux start --daemon` starts a copy of itself detached from the console
+//! and returns. The copy writes its process ID to a PID file, answers the
+//! same requests as the service on [`DAEMON_PIPE_NAME`], and runs until its
+//! engine stops - on a Stop request or because it failed. Closing the console
+//! it was started from doesn't affect it.
+
+use crate::audio::AudioEngine;
+use crate::error::Result;
+use crate::ipc::{answer_request, PipeServer, DAEMON_PIPE_NAME};
+use crossbeam_channel::bounded;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::{info, warn};
+use windows::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+/// How often the daemon checks the stop flag and the engine between requests
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Argument telling `wemux start` it is the detached copy
+pub const DETACHED_ARG: &str = "--detached";
+
+/// File holding the process ID of a running daemon, removed when it exits
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current process ID to `path`
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, format!("{}\n", std::process::id()))?;
+        Ok(Self { path })
+    }
+
+    /// Process ID in the file at `path`, if it holds one
+    pub fn read(path: &Path) -> Option<u32> {
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// %LOCALAPPDATA%\wemux\daemon.pid
+pub fn default_pid_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("wemux").join("daemon.pid"))
+}
+
+/// %LOCALAPPDATA%\wemux\daemon.log, where a daemon logs unless `--log` is given
+pub fn default_log_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("wemux").join("daemon.log"))
+}
+
+/// Start the current executable with `args`, without a console and out of
+/// reach of the console's Ctrl+C
+pub fn spawn_detached(args: impl IntoIterator<Item = OsString>) -> io::Result<Child> {
+    Command::new(std::env::current_exe()?)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(DETACHED_PROCESS.0 | CREATE_NEW_PROCESS_GROUP.0)
+        .spawn()
+}
+
+/// Answer requests on [`DAEMON_PIPE_NAME`] until the engine stops or `stop`
+/// is set, then stop the engine
+pub fn run(engine: &mut AudioEngine, stop: &AtomicBool) -> Result<()> {
+    let (ipc_tx, ipc_rx) = bounded(16);
+    let _ipc_server = PipeServer::start(DAEMON_PIPE_NAME, ipc_tx)?;
+    if !engine.is_running() {
+        engine.start()?;
+    }
+    info!("Daemon running (PID {})", std::process::id());
+
+    while !stop.load(Ordering::SeqCst) && engine.is_running() {
+        if let Ok(message) = ipc_rx.recv_timeout(POLL_INTERVAL) {
+            let response = answer_request(engine, message.request);
+            let _ = message.reply.send(response);
+        }
+    }
+
+    info!("Daemon exiting");
+    engine.stop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_is_removed_on_drop() {
+        let dir = std::env::temp_dir().join(format!("wemux-daemon-test-{}", std::process::id()));
+        let path = dir.join("daemon.pid");
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(PidFile::read(&path), Some(std::process::id()));
+        drop(pid_file);
+        assert!(!path.exists());
+        assert_eq!(PidFile::read(&path), None);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,182 @@
+//! User-assigned device aliases
+//!
+//! WASAPI endpoint IDs change when a GPU driver is reinstalled, which would
+//! otherwise orphan every setting keyed by ID. An alias records the ID it was
+//! assigned to together with the device name and hardware container ID, and
+//! falls back to those when the ID is no longer present.
+
+use crate::device::DeviceInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Identity of an aliased device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceAlias {
+    /// Device ID the alias was last seen with (empty = match by name/hardware only)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// Device friendly name
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Hardware container ID, stable across driver reinstalls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+}
+
+impl DeviceAlias {
+    /// Record the identity of a device
+    pub fn from_device(device: &DeviceInfo) -> Self {
+        Self {
+            id: device.id.clone(),
+            name: device.name.clone(),
+            container_id: device.container_id.clone(),
+        }
+    }
+
+    /// Find the device this alias refers to
+    ///
+    /// Tries the recorded ID first, then name and container ID together,
+    /// then each of them alone. Fallbacks only match when they are unambiguous.
+    pub fn resolve<'a>(&self, devices: &'a [DeviceInfo]) -> Option<&'a DeviceInfo> {
+        if !self.id.is_empty() {
+            if let Some(device) = devices.iter().find(|d| d.id == self.id) {
+                return Some(device);
+            }
+        }
+
+        let same_name =
+            |d: &&DeviceInfo| !self.name.is_empty() && d.name.eq_ignore_ascii_case(&self.name);
+        let same_container = |d: &&DeviceInfo| {
+            self.container_id.is_some() && d.container_id.as_ref() == self.container_id.as_ref()
+        };
+
+        unique(devices.iter().filter(|d| same_name(d) && same_container(d)))
+            .or_else(|| unique(devices.iter().filter(same_container)))
+            .or_else(|| unique(devices.iter().filter(same_name)))
+    }
+}
+
+/// Return the only item of an iterator, or None if it has zero or several
+fn unique<'a>(mut iter: impl Iterator<Item = &'a DeviceInfo>) -> Option<&'a DeviceInfo> {
+    let first = iter.next()?;
+    iter.next().is_none().then_some(first)
+}
+
+/// Aliases keyed by their user-assigned name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DeviceAliases(BTreeMap<String, DeviceAlias>);
+
+impl DeviceAliases {
+    /// Whether no aliases are defined
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get an alias by name (case-insensitive)
+    pub fn get(&self, alias: &str) -> Option<&DeviceAlias> {
+        self.0
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(alias))
+            .map(|(_, entry)| entry)
+    }
+
+    /// Assign an alias to a device, replacing any previous assignment
+    pub fn assign(&mut self, alias: &str, device: &DeviceInfo) {
+        self.0.retain(|name, _| !name.eq_ignore_ascii_case(alias));
+        self.0
+            .insert(alias.to_string(), DeviceAlias::from_device(device));
+    }
+
+    /// Iterate over (alias, identity) pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &DeviceAlias)> {
+        self.0.iter().map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    /// Resolve an alias name to a device
+    pub fn resolve<'a>(&self, alias: &str, devices: &'a [DeviceInfo]) -> Option<&'a DeviceInfo> {
+        self.get(alias)?.resolve(devices)
+    }
+
+    /// Map device IDs to the alias that resolves to them
+    pub fn assignments(&self, devices: &[DeviceInfo]) -> HashMap<String, String> {
+        self.iter()
+            .filter_map(|(name, entry)| {
+                entry
+                    .resolve(devices)
+                    .map(|device| (device.id.clone(), name.to_string()))
+            })
+            .collect()
+    }
+
+    /// Record the current IDs of aliased devices whose IDs changed
+    ///
+    /// Returns (old ID, new ID) for each alias that moved, so settings keyed
+    /// by the old ID can follow it.
+    pub fn refresh_ids(&mut self, devices: &[DeviceInfo]) -> Vec<(String, String)> {
+        let mut moved = Vec::new();
+        for entry in self.0.values_mut() {
+            if let Some(device) = entry.resolve(devices) {
+                if device.id != entry.id {
+                    moved.push((std::mem::take(&mut entry.id), device.id.clone()));
+                    *entry = DeviceAlias::from_device(device);
+                }
+            }
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str, container: Option<&str>) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_hdmi: true,
+            is_default: false,
+            container_id: container.map(str::to_string),
+            is_bluetooth: false,
+        }
+    }
+
+    #[test]
+    fn test_resolve_survives_id_change() {
+        let mut aliases = DeviceAliases::default();
+        aliases.assign("tv", &device("old", "LG TV (NVIDIA)", Some("{c1}")));
+
+        // Driver reinstall: new ID, same name and container
+        let devices = vec![
+            device("new", "LG TV (NVIDIA)", Some("{c1}")),
+            device("other", "Monitor (NVIDIA)", Some("{c2}")),
+        ];
+        assert_eq!(aliases.resolve("TV", &devices).unwrap().id, "new");
+
+        // Renamed endpoint: container ID still identifies it
+        let devices = vec![device("new", "Living Room", Some("{c1}"))];
+        assert_eq!(aliases.resolve("tv", &devices).unwrap().id, "new");
+
+        assert_eq!(
+            aliases.refresh_ids(&devices),
+            vec![("old".to_string(), "new".to_string())]
+        );
+        assert_eq!(aliases.get("tv").unwrap().id, "new");
+    }
+
+    #[test]
+    fn test_ambiguous_fallback_does_not_match() {
+        let alias = DeviceAlias {
+            id: String::new(),
+            name: "HDMI Output".to_string(),
+            container_id: None,
+        };
+        let devices = vec![
+            device("a", "HDMI Output", None),
+            device("b", "HDMI Output", None),
+        ];
+        assert!(alias.resolve(&devices).is_none());
+        assert_eq!(alias.resolve(&devices[..1]).unwrap().id, "a");
+    }
+}
@@ -0,0 +1,75 @@
+//! Outputs that end at the same physical sink
+//!
+//! An AV receiver can show up as two endpoints, e.g. "NVIDIA Output" and
+//! "Digital Audio (HDMI)", that both reach the same speakers; feeding both
+//! plays everything twice. Windows groups the endpoints of one physical
+//! device under a shared hardware container ID, which is what gives them away.
+
+use crate::device::DeviceInfo;
+use std::collections::HashMap;
+
+/// Container ID Windows gives every device built into the computer
+const BUILT_IN_CONTAINER: &str = "00000000-0000-0000-ffff-ffffffffffff";
+
+/// Outputs that reach the same sink as an earlier output, each paired with it
+///
+/// Devices without a container ID, and those built into the computer (whose
+/// speakers and headphone jack share one), are never duplicates.
+pub fn find_duplicates<'a>(
+    devices: impl IntoIterator<Item = &'a DeviceInfo>,
+) -> Vec<(&'a DeviceInfo, &'a DeviceInfo)> {
+    let mut first_by_container: HashMap<String, &DeviceInfo> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for device in devices {
+        let Some(container) = device.container_id.as_deref() else {
+            continue;
+        };
+        let container = container
+            .trim_matches(|c| c == '{' || c == '}')
+            .to_lowercase();
+        if container == BUILT_IN_CONTAINER {
+            continue;
+        }
+        match first_by_container.get(&container) {
+            Some(first) => duplicates.push((device, *first)),
+            None => {
+                first_by_container.insert(container, device);
+            }
+        }
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, container: Option<&str>) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            is_hdmi: true,
+            is_default: false,
+            container_id: container.map(str::to_string),
+            is_bluetooth: false,
+        }
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let devices = [
+            device("nvidia-output", Some("{AAAA}")),
+            device("tv", Some("{BBBB}")),
+            device("receiver-hdmi", Some("{aaaa}")),
+            device("speakers", Some("{00000000-0000-0000-FFFF-FFFFFFFFFFFF}")),
+            device("headphones", Some("{00000000-0000-0000-FFFF-FFFFFFFFFFFF}")),
+            device("unknown", None),
+        ];
+
+        let duplicates: Vec<(&str, &str)> = find_duplicates(&devices)
+            .into_iter()
+            .map(|(duplicate, first)| (duplicate.id.as_str(), first.id.as_str()))
+            .collect();
+        assert_eq!(duplicates, [("receiver-hdmi", "nvidia-output")]);
+    }
+}
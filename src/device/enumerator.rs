@@ -1,16 +1,20 @@
 //! Audio device enumeration using Windows Core Audio API
 
-use crate::device::filter::HdmiFilter;
+use crate::audio::{AudioFormat, HardwareCapabilities};
+use crate::device::filter::{DeviceKind, HdmiFilter};
+use crate::device::{find_device, DeviceAliases, DeviceState};
 use crate::error::{Result, WemuxError};
 use std::fmt;
 use tracing::{debug, info};
 use windows::{
-    core::{PCWSTR, PROPVARIANT},
+    core::{BSTR, PCWSTR, PROPVARIANT},
     Win32::{
-        Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+        Devices::FunctionDiscovery::{
+            PKEY_Device_ContainerId, PKEY_Device_EnumeratorName, PKEY_Device_FriendlyName,
+        },
         Media::Audio::{
-            eConsole, eRender, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator,
-            DEVICE_STATE_ACTIVE,
+            eCapture, eConsole, eRender, EDataFlow, IAudioClient, IMMDevice, IMMDeviceEnumerator,
+            MMDeviceEnumerator, DEVICE_STATE_ACTIVE,
         },
         System::Com::{
             CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ,
@@ -32,6 +36,20 @@ pub struct DeviceInfo {
     pub is_hdmi: bool,
     /// Whether this is the default render device
     pub is_default: bool,
+    /// Hardware container ID, shared by all endpoints of a physical device
+    pub container_id: Option<String>,
+    /// Whether the device is connected over Bluetooth
+    pub is_bluetooth: bool,
+}
+
+impl DeviceInfo {
+    /// Kind of device (HDMI, Bluetooth, other)
+    pub fn kind(&self) -> DeviceKind {
+        if self.is_bluetooth && !self.is_hdmi {
+            return DeviceKind::Bluetooth;
+        }
+        DeviceKind::detect(&self.name, self.is_hdmi)
+    }
 }
 
 impl fmt::Display for DeviceInfo {
@@ -49,10 +67,25 @@ impl fmt::Debug for DeviceInfo {
             .field("name", &self.name)
             .field("is_hdmi", &self.is_hdmi)
             .field("is_default", &self.is_default)
+            .field("container_id", &self.container_id)
+            .field("is_bluetooth", &self.is_bluetooth)
             .finish()
     }
 }
 
+/// Format a device mixes in and how fast it can be fed
+///
+/// Not part of [`DeviceInfo`]: finding it out activates an audio client on
+/// the endpoint, which is too slow to do for every enumeration. Ask
+/// [`DeviceEnumerator::device_format`] for the devices that need it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceFormat {
+    /// Shared mode mix format
+    pub format: AudioFormat,
+    /// Device periods and latency class
+    pub capabilities: HardwareCapabilities,
+}
+
 /// Audio device enumerator wrapping Windows MMDevice API
 pub struct DeviceEnumerator {
     enumerator: IMMDeviceEnumerator,
@@ -123,15 +156,66 @@ impl DeviceEnumerator {
         }
     }
 
+    /// Get a device by its ID, failing with [`WemuxError::DeviceInactive`]
+    /// if it is disabled or unplugged
+    pub fn get_active_device_by_id(&self, device_id: &str) -> Result<IMMDevice> {
+        let device = self.get_device_by_id(device_id)?;
+        let state = DeviceState::from(unsafe { device.GetState()? });
+        if !state.is_active() {
+            return Err(WemuxError::DeviceInactive {
+                device_id: device_id.to_string(),
+                state,
+            });
+        }
+        Ok(device)
+    }
+
+    /// Query the mix format and latency class of a device
+    pub fn device_format(&self, device: &DeviceInfo) -> Result<DeviceFormat> {
+        let endpoint = self.get_device_by_id(&device.id)?;
+        unsafe {
+            let client: IAudioClient = endpoint.Activate(CLSCTX_ALL, None)?;
+            let format_ptr = client.GetMixFormat()?;
+            let format = AudioFormat::from_wave_format(format_ptr);
+            windows::Win32::System::Com::CoTaskMemFree(Some(format_ptr as *const _));
+            Ok(DeviceFormat {
+                format,
+                capabilities: HardwareCapabilities::query(&client)?,
+            })
+        }
+    }
+
+    /// Get the default audio input (microphone) device
+    pub fn get_default_input_device(&self) -> Result<IMMDevice> {
+        unsafe {
+            self.enumerator
+                .GetDefaultAudioEndpoint(eCapture, eConsole)
+                .map_err(|e| WemuxError::DeviceError {
+                    device_id: "default".into(),
+                    message: format!("Failed to get default input device: {}", e),
+                })
+        }
+    }
+
     /// Enumerate all active render devices
     pub fn enumerate_all_devices(&self) -> Result<Vec<DeviceInfo>> {
+        self.enumerate(eRender)
+    }
+
+    /// Enumerate all active input devices (microphones, line-in)
+    pub fn enumerate_input_devices(&self) -> Result<Vec<DeviceInfo>> {
+        self.enumerate(eCapture)
+    }
+
+    fn enumerate(&self, flow: EDataFlow) -> Result<Vec<DeviceInfo>> {
         unsafe {
             let collection = self
                 .enumerator
-                .EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+                .EnumAudioEndpoints(flow, DEVICE_STATE_ACTIVE)?;
 
             let count = collection.GetCount()?;
-            debug!("Found {} render devices", count);
+            let kind = if flow == eRender { "render" } else { "input" };
+            debug!("Found {} {} devices", count, kind);
 
             let mut devices = Vec::with_capacity(count as usize);
 
@@ -147,6 +231,12 @@ impl DeviceEnumerator {
         }
     }
 
+    /// Find the output device `query` refers to (see [`find_device`])
+    pub fn find(&self, query: &str, aliases: &DeviceAliases) -> Result<DeviceInfo> {
+        let devices = self.enumerate_all_devices()?;
+        find_device(query, &devices, aliases).cloned()
+    }
+
     /// Enumerate only HDMI devices
     pub fn enumerate_hdmi_devices(&self) -> Result<Vec<DeviceInfo>> {
         let all_devices = self.enumerate_all_devices()?;
@@ -168,6 +258,22 @@ impl DeviceEnumerator {
         Ok(info.name)
     }
 
+    /// Check whether a device is connected over Bluetooth
+    ///
+    /// The endpoint form factor (headphones, speakers) is the same for wired
+    /// devices; the bus the driver was enumerated on (BTHENUM, BTHHFENUM,
+    /// BTHLEDEVICE) tells them apart.
+    pub fn is_bluetooth(device: &IMMDevice) -> bool {
+        unsafe {
+            device
+                .OpenPropertyStore(STGM_READ)
+                .and_then(|store| store.GetValue(&PKEY_Device_EnumeratorName))
+                .ok()
+                .and_then(|prop| prop_variant_to_string(&prop))
+                .is_some_and(|bus| bus.to_uppercase().starts_with("BTH"))
+        }
+    }
+
     /// Get device information from an IMMDevice
     fn get_device_info(&self, device: &IMMDevice) -> Result<DeviceInfo> {
         unsafe {
@@ -185,6 +291,14 @@ impl DeviceEnumerator {
             let name =
                 prop_variant_to_string(&name_prop).unwrap_or_else(|| "Unknown Device".to_string());
 
+            // Container ID survives driver reinstalls, unlike the endpoint ID
+            let container_id = store
+                .GetValue(&PKEY_Device_ContainerId)
+                .ok()
+                .and_then(|prop| BSTR::try_from(&prop).ok())
+                .map(|guid| guid.to_string())
+                .filter(|guid| !guid.is_empty());
+
             // Check if HDMI
             let is_hdmi = HdmiFilter::is_hdmi_device(&name) || HdmiFilter::is_hdmi_device_id(&id);
 
@@ -196,6 +310,8 @@ impl DeviceEnumerator {
                 name,
                 is_hdmi,
                 is_default,
+                container_id,
+                is_bluetooth: Self::is_bluetooth(device),
             })
         }
     }
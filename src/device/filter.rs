@@ -1,4 +1,4 @@
-//! HDMI device filtering logic
+//! HDMI device filtering and device kind detection
 
 /// Keywords that identify HDMI audio devices
 const HDMI_KEYWORDS: &[&str] = &[
@@ -9,6 +9,9 @@ const HDMI_KEYWORDS: &[&str] = &[
     "display audio",
 ];
 
+/// Keywords that identify Bluetooth audio devices
+const BLUETOOTH_KEYWORDS: &[&str] = &["bluetooth", "hands-free", "a2dp"];
+
 /// Filter for identifying HDMI audio devices
 pub struct HdmiFilter;
 
@@ -29,6 +32,31 @@ impl HdmiFilter {
     }
 }
 
+/// Kind of output device, as far as its name tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Hdmi,
+    Bluetooth,
+    Other,
+}
+
+impl DeviceKind {
+    /// Kind of a device given its name and whether it was identified as HDMI
+    pub fn detect(name: &str, is_hdmi: bool) -> Self {
+        let name_lower = name.to_lowercase();
+        if is_hdmi {
+            DeviceKind::Hdmi
+        } else if BLUETOOTH_KEYWORDS
+            .iter()
+            .any(|keyword| name_lower.contains(keyword))
+        {
+            DeviceKind::Bluetooth
+        } else {
+            DeviceKind::Other
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,5 +71,11 @@ mod tests {
         assert!(HdmiFilter::is_hdmi_device("HDMI Output"));
         assert!(!HdmiFilter::is_hdmi_device("Realtek Audio"));
         assert!(!HdmiFilter::is_hdmi_device("Speakers"));
+
+        assert_eq!(
+            DeviceKind::detect("Headset (Bluetooth Hands-Free)", false),
+            DeviceKind::Bluetooth
+        );
+        assert_eq!(DeviceKind::detect("Speakers", false), DeviceKind::Other);
     }
 }
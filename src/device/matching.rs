@@ -0,0 +1,141 @@
+//! Finding the device a user means
+//!
+//! The CLI, the configuration and remote commands all accept a device as an
+//! ID, an alias or (part of) its name. Matches are ranked so that a full ID
+//! or name wins over a device that merely contains the text, and a query
+//! that fits several devices equally well is an error instead of a guess.
+
+use crate::device::{DeviceAliases, DeviceInfo};
+use crate::error::{Result, WemuxError};
+
+/// How well a query matches a device, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchRank {
+    /// The query is the device ID
+    Id,
+    /// The query is an alias of the device
+    Alias,
+    /// The query is the device name, ignoring case
+    Name,
+    /// The query is part of the device ID or name, ignoring case
+    Substring,
+}
+
+impl MatchRank {
+    /// Rank a device with `id`, `name` and optionally the alias it is known by
+    pub fn of(query: &str, id: &str, name: &str, alias: Option<&str>) -> Option<Self> {
+        let query_lower = query.to_lowercase();
+        if id.eq_ignore_ascii_case(query) {
+            Some(MatchRank::Id)
+        } else if alias.is_some_and(|alias| alias.eq_ignore_ascii_case(query)) {
+            Some(MatchRank::Alias)
+        } else if name.to_lowercase() == query_lower {
+            Some(MatchRank::Name)
+        } else if id.to_lowercase().contains(&query_lower)
+            || name.to_lowercase().contains(&query_lower)
+        {
+            Some(MatchRank::Substring)
+        } else {
+            None
+        }
+    }
+}
+
+/// The candidate `query` matches best
+///
+/// Fails with `DeviceNotFound` if nothing matches and with `AmbiguousDevice`,
+/// listing the candidates by `label`, if several share the best rank.
+pub fn best_match<'a, T>(
+    query: &str,
+    candidates: &'a [T],
+    rank: impl Fn(&T) -> Option<MatchRank>,
+    label: impl Fn(&T) -> String,
+) -> Result<&'a T> {
+    let ranked: Vec<(MatchRank, &T)> = candidates
+        .iter()
+        .filter_map(|candidate| rank(candidate).map(|r| (r, candidate)))
+        .collect();
+    let Some(best) = ranked.iter().map(|(rank, _)| *rank).min() else {
+        return Err(WemuxError::DeviceNotFound(query.to_string()));
+    };
+
+    let mut found = ranked.into_iter().filter(|(rank, _)| *rank == best);
+    let (_, first) = found.next().expect("the best rank has a candidate");
+    let others: Vec<&T> = found.map(|(_, candidate)| candidate).collect();
+    if others.is_empty() {
+        Ok(first)
+    } else {
+        Err(WemuxError::AmbiguousDevice {
+            query: query.to_string(),
+            matches: std::iter::once(first).chain(others).map(label).collect(),
+        })
+    }
+}
+
+/// The output device `query` refers to: its ID, an alias, its name or part of either
+pub fn find_device<'a>(
+    query: &str,
+    devices: &'a [DeviceInfo],
+    aliases: &DeviceAliases,
+) -> Result<&'a DeviceInfo> {
+    let aliased = aliases
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(query))
+        .and_then(|(_, alias)| alias.resolve(devices))
+        .map(|d| d.id.as_str());
+    best_match(
+        query,
+        devices,
+        |d| {
+            MatchRank::of(
+                query,
+                &d.id,
+                &d.name,
+                (aliased == Some(&d.id)).then_some(query),
+            )
+        },
+        |d| format!("{} [{}]", d.name, d.id),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_hdmi: true,
+            is_default: false,
+            container_id: None,
+            is_bluetooth: false,
+        }
+    }
+
+    #[test]
+    fn test_find_device_ranks_matches() {
+        let devices = [
+            device("{a}", "LG TV (NVIDIA High Definition Audio)"),
+            device("{b}", "Sony TV (NVIDIA High Definition Audio)"),
+            device("{c}", "Sony TV"),
+        ];
+        let mut aliases = DeviceAliases::default();
+        aliases.assign("lounge", &devices[0]);
+        let find = |query| find_device(query, &devices, &aliases).map(|d| d.id.as_str());
+
+        assert_eq!(find("{b}").unwrap(), "{b}");
+        assert_eq!(find("Lounge").unwrap(), "{a}");
+        // A full name wins over names containing it
+        assert_eq!(find("sony tv").unwrap(), "{c}");
+        assert_eq!(find("lg").unwrap(), "{a}");
+        assert!(matches!(
+            find("nvidia"),
+            Err(WemuxError::AmbiguousDevice { matches, .. }) if matches.len() == 2
+        ));
+        assert!(matches!(
+            find("Samsung"),
+            Err(WemuxError::DeviceNotFound(_))
+        ));
+    }
+}
@@ -1,9 +1,17 @@
 //! Device enumeration and management
 
+mod alias;
+mod duplicate;
 mod enumerator;
 mod filter;
+mod matching;
 mod monitor;
+mod watch;
 
-pub use enumerator::{DeviceEnumerator, DeviceInfo};
-pub use filter::HdmiFilter;
-pub use monitor::{DeviceEvent, DeviceMonitor};
+pub use alias::{DeviceAlias, DeviceAliases};
+pub use duplicate::find_duplicates;
+pub use enumerator::{DeviceEnumerator, DeviceFormat, DeviceInfo};
+pub use filter::{DeviceKind, HdmiFilter};
+pub use matching::{best_match, find_device, MatchRank};
+pub use monitor::{DeviceEvent, DeviceMonitor, DeviceState};
+pub use watch::{device_changes, DeviceChange};
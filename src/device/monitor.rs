@@ -3,6 +3,8 @@
 use crate::error::Result;
 use crossbeam_channel::Sender;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 use windows::{
@@ -10,7 +12,8 @@ use windows::{
     Win32::{
         Media::Audio::{
             EDataFlow, ERole, IMMDeviceEnumerator, IMMNotificationClient,
-            IMMNotificationClient_Impl, MMDeviceEnumerator, DEVICE_STATE,
+            IMMNotificationClient_Impl, MMDeviceEnumerator, DEVICE_STATE, DEVICE_STATE_DISABLED,
+            DEVICE_STATE_NOTPRESENT, DEVICE_STATE_UNPLUGGED,
         },
         System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED},
     },
@@ -30,11 +33,61 @@ pub enum DeviceEvent {
         device_id: String,
     },
     /// Device state changed
-    StateChanged { device_id: String, new_state: u32 },
+    StateChanged {
+        device_id: String,
+        new_state: DeviceState,
+    },
     /// Device property changed
     PropertyChanged { device_id: String },
 }
 
+/// State of an endpoint, as Windows reports it
+///
+/// Only an active device can be opened; the others stay known to Windows
+/// (and to a running engine) until they come back or are removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceState {
+    #[default]
+    Active,
+    /// Disabled in the Sound control panel or Device Manager
+    Disabled,
+    /// The adapter or driver is gone
+    NotPresent,
+    /// The jack or HDMI cable is unplugged, e.g. the display is off
+    Unplugged,
+}
+
+impl DeviceState {
+    /// Whether the device can be opened and played to
+    pub fn is_active(self) -> bool {
+        self == Self::Active
+    }
+}
+
+impl From<DEVICE_STATE> for DeviceState {
+    fn from(state: DEVICE_STATE) -> Self {
+        match state {
+            DEVICE_STATE_DISABLED => Self::Disabled,
+            DEVICE_STATE_NOTPRESENT => Self::NotPresent,
+            DEVICE_STATE_UNPLUGGED => Self::Unplugged,
+            _ => Self::Active,
+        }
+    }
+}
+
+impl fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Active => "active",
+            Self::Disabled => "disabled",
+            Self::NotPresent => "not present",
+            Self::Unplugged => "unplugged",
+        };
+        f.write_str(name)
+    }
+}
+
 /// Device monitor for hot-plug detection
 pub struct DeviceMonitor {
     enumerator: IMMDeviceEnumerator,
@@ -101,7 +154,7 @@ impl IMMNotificationClient_Impl for NotificationCallback_Impl {
             debug!("Device state changed: {} -> {}", device_id, dwnewstate.0);
             let event = DeviceEvent::StateChanged {
                 device_id,
-                new_state: dwnewstate.0,
+                new_state: dwnewstate.into(),
             };
             self.send_event(event);
         }
@@ -0,0 +1,96 @@
+//! Changes between two device lists, for `wemux list --watch`
+//!
+//! The device monitor only says that something happened to an endpoint ID;
+//! comparing the lists before and after tells what the user sees: a device
+//! that appeared or went away, a new default output or a new name (Windows
+//! often renames an HDMI endpoint once the display reports its own name).
+
+use crate::device::DeviceInfo;
+
+/// What changed about one device
+#[derive(Clone)]
+pub enum DeviceChange {
+    /// The device appeared
+    Added(DeviceInfo),
+    /// The device went away
+    Removed(DeviceInfo),
+    /// The device was renamed from the given name
+    Renamed { from: String, device: DeviceInfo },
+    /// The device became the default output
+    DefaultChanged(DeviceInfo),
+}
+
+/// Changes from `before` to `after`: removals first, then additions, renames
+/// and a new default, each in list order
+pub fn device_changes(before: &[DeviceInfo], after: &[DeviceInfo]) -> Vec<DeviceChange> {
+    let find = |list: &[DeviceInfo], id: &str| list.iter().position(|d| d.id == id);
+    let mut changes: Vec<DeviceChange> = before
+        .iter()
+        .filter(|d| find(after, &d.id).is_none())
+        .map(|d| DeviceChange::Removed(d.clone()))
+        .collect();
+
+    for device in after {
+        match find(before, &device.id) {
+            None => changes.push(DeviceChange::Added(device.clone())),
+            Some(i) if before[i].name != device.name => changes.push(DeviceChange::Renamed {
+                from: before[i].name.clone(),
+                device: device.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    let default = |list: &[DeviceInfo]| list.iter().find(|d| d.is_default).map(|d| d.id.clone());
+    if let Some(id) = default(after).filter(|id| default(before).as_ref() != Some(id)) {
+        if let Some(device) = after.iter().find(|d| d.id == id) {
+            changes.push(DeviceChange::DefaultChanged(device.clone()));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str, is_default: bool) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_hdmi: true,
+            is_default,
+            container_id: None,
+            is_bluetooth: false,
+        }
+    }
+
+    #[test]
+    fn test_device_changes() {
+        let before = [
+            device("speakers", "Speakers", true),
+            device("tv", "Digital Audio (HDMI)", false),
+            device("monitor", "DELL U2720Q", false),
+        ];
+        let after = [
+            device("speakers", "Speakers", false),
+            device("tv", "LG TV (NVIDIA High Definition Audio)", true),
+            device("receiver", "AVR-X1700H", false),
+        ];
+
+        let changes: Vec<String> = device_changes(&before, &after)
+            .iter()
+            .map(|change| match change {
+                DeviceChange::Added(d) => format!("+{}", d.id),
+                DeviceChange::Removed(d) => format!("-{}", d.id),
+                DeviceChange::Renamed { from, device } => format!("{}>{}", from, device.id),
+                DeviceChange::DefaultChanged(d) => format!("*{}", d.id),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            ["-monitor", "Digital Audio (HDMI)>tv", "+receiver", "*tv"]
+        );
+        assert!(device_changes(&after, &after).is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Environment and device diagnostics (`wemux doctor`)
+//!
+//! Runs the steps the engine depends on one at a time - COM, endpoint
+//! enumeration, loopback capture and rendering to each HDMI output - and
+//! records the outcome of each, so a bug report can show the whole picture
+//! instead of the first error the engine ran into.
+
+use crate::audio::{HdmiRenderer, LoopbackCapture};
+use crate::device::{DeviceEnumerator, DeviceFormat, DeviceInfo};
+use crate::error::Result;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+
+/// How long the loopback capture is given to deliver audio
+const CAPTURE_WAIT: Duration = Duration::from_secs(1);
+
+/// Silence written to each HDMI output
+const SILENCE_MS: u64 = 1000;
+
+/// Time after which an output that hasn't taken all the silence counts as stuck
+const RENDER_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    /// Works, but something looks off
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        f.write_str(label)
+    }
+}
+
+/// Result of one diagnostic step
+#[derive(Debug, Clone)]
+pub struct Check {
+    /// What was checked
+    pub name: String,
+    pub status: Status,
+    /// What was found, or why the check failed
+    pub detail: String,
+}
+
+/// Results of all checks, in the order they ran
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn record(&mut self, name: impl Into<String>, result: Result<(Status, String)>) {
+        let (status, detail) = result.unwrap_or_else(|e| (Status::Fail, e.to_string()));
+        self.checks.push(Check {
+            name: name.into(),
+            status,
+            detail,
+        });
+    }
+
+    /// Number of checks that ended with `status`
+    pub fn count(&self, status: Status) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+}
+
+/// Run all checks
+///
+/// Writes a second of silence to every HDMI output, which is inaudible but
+/// briefly opens each device.
+pub fn run() -> Report {
+    let mut report = Report::default();
+
+    report.record("COM initialization", check_com());
+
+    let enumerator = match DeviceEnumerator::new() {
+        Ok(enumerator) => enumerator,
+        Err(e) => {
+            report.record("Device enumeration", Err(e));
+            return report;
+        }
+    };
+    let devices = match enumerator.enumerate_all_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            report.record("Device enumeration", Err(e));
+            return report;
+        }
+    };
+    let hdmi_count = devices.iter().filter(|d| d.is_hdmi).count();
+    let status = if hdmi_count == 0 {
+        Status::Warn
+    } else {
+        Status::Pass
+    };
+    report.record(
+        "Device enumeration",
+        Ok((
+            status,
+            format!("{} output devices, {} HDMI", devices.len(), hdmi_count),
+        )),
+    );
+
+    for device in &devices {
+        report.record(
+            format!("Endpoint {}", device.name),
+            describe_endpoint(&enumerator, device),
+        );
+    }
+
+    report.record("Loopback capture", check_loopback());
+
+    for device in devices.iter().filter(|d| d.is_hdmi) {
+        report.record(
+            format!("Render to {}", device.name),
+            check_render(&enumerator, device),
+        );
+    }
+
+    report
+}
+
+fn check_com() -> Result<(Status, String)> {
+    unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.ok()?;
+    Ok((Status::Pass, "multithreaded apartment".to_string()))
+}
+
+/// Mix format and device periods of an endpoint
+fn describe_endpoint(
+    enumerator: &DeviceEnumerator,
+    device: &DeviceInfo,
+) -> Result<(Status, String)> {
+    let DeviceFormat {
+        format,
+        capabilities: caps,
+    } = enumerator.device_format(device)?;
+
+    let mut detail = format!(
+        "{}, period {:.2}ms (min {:.2}ms)",
+        format,
+        caps.default_period_ms(),
+        caps.min_period_ms()
+    );
+    if device.is_default {
+        detail.push_str(", default output");
+    }
+    Ok((Status::Pass, detail))
+}
+
+/// Capture the default output for a moment
+fn check_loopback() -> Result<(Status, String)> {
+    let mut capture = LoopbackCapture::from_default_device()?;
+    let format = capture.format().clone();
+    capture.start()?;
+
+    let deadline = Instant::now() + CAPTURE_WAIT;
+    let mut frames = 0u64;
+    let mut audible = false;
+    while Instant::now() < deadline {
+        let packet = capture.read_frames(100)?;
+        frames += packet.num_frames() as u64;
+        audible |= !packet.is_empty() && !packet.is_silent();
+    }
+    capture.stop()?;
+
+    if frames == 0 {
+        // Windows delivers nothing while no application plays audio
+        return Ok((
+            Status::Warn,
+            format!(
+                "{}, no audio within {}s (is anything playing?)",
+                format,
+                CAPTURE_WAIT.as_secs()
+            ),
+        ));
+    }
+    Ok((
+        Status::Pass,
+        format!(
+            "{}, {}ms captured{}",
+            format,
+            frames * 1000 / format.sample_rate as u64,
+            if audible { "" } else { " (silent)" }
+        ),
+    ))
+}
+
+/// Write a second of silence to an output
+fn check_render(enumerator: &DeviceEnumerator, device: &DeviceInfo) -> Result<(Status, String)> {
+    let endpoint = enumerator.get_device_by_id(&device.id)?;
+    let mut renderer = HdmiRenderer::new(&endpoint)?;
+    let format = renderer.format().clone();
+    let total = (format.sample_rate as u64 * SILENCE_MS / 1000) as u32;
+
+    renderer.start()?;
+    let started = Instant::now();
+    let mut written = 0;
+    while written < total {
+        if started.elapsed() > RENDER_TIMEOUT {
+            renderer.stop()?;
+            return Ok((
+                Status::Fail,
+                format!(
+                    "{}, device took only {}ms of {}ms silence",
+                    format,
+                    written as u64 * 1000 / format.sample_rate as u64,
+                    SILENCE_MS
+                ),
+            ));
+        }
+
+        let space = renderer.buffer_frames() - renderer.padding_frames()?;
+        let frames = space.min(total - written);
+        renderer.write_silence(frames)?;
+        written += frames;
+        thread::sleep(Duration::from_millis(5));
+    }
+    renderer.stop()?;
+
+    Ok((
+        Status::Pass,
+        format!("{}, {}ms of silence written", format, SILENCE_MS),
+    ))
+}
@@ -1,5 +1,6 @@
 //! Unified error types for wemux
 
+use crate::device::DeviceState;
 use thiserror::Error;
 
 /// Main error type for wemux operations
@@ -13,6 +14,17 @@ pub enum WemuxError {
     #[error("Device not found: {0}")]
     DeviceNotFound(String),
 
+    /// A device query matches several devices equally well
+    #[error("'{query}' matches several devices: {}", .matches.join(", "))]
+    AmbiguousDevice { query: String, matches: Vec<String> },
+
+    /// The device is disabled or unplugged, so it can't be opened
+    #[error("Device '{device_id}' is {state}")]
+    DeviceInactive {
+        device_id: String,
+        state: DeviceState,
+    },
+
     /// Device operation error
     #[error("Device '{device_id}' error: {message}")]
     DeviceError { device_id: String, message: String },
@@ -41,6 +53,10 @@ pub enum WemuxError {
     #[error("Audio engine already running")]
     AlreadyRunning,
 
+    /// Another process (service, tray or console) already runs an engine
+    #[error("Another wemux engine is already running on this machine")]
+    EngineInUse,
+
     /// Invalid configuration
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
@@ -48,6 +64,26 @@ pub enum WemuxError {
     /// Thread communication error
     #[error("Thread communication error: {0}")]
     ChannelError(String),
+
+    /// Inter-process communication error
+    #[error("IPC error: {0}")]
+    Ipc(String),
+
+    /// Sending a notification failed
+    #[error("Notification error: {0}")]
+    Notification(String),
+
+    /// Measuring output delays failed
+    #[error("Calibration error: {0}")]
+    Calibration(String),
+
+    /// Changing the log failed
+    #[error("Logging error: {0}")]
+    Logging(String),
+
+    /// Threads still running when the engine gave up waiting for them
+    #[error("Threads did not stop in time: {}", .0.join(", "))]
+    ShutdownTimeout(Vec<String>),
 }
 
 /// Result type alias for wemux operations
@@ -0,0 +1,556 @@
+//! C API for embedding the engine (`ffi` feature)
+//!
+//! HTPC frontends written in C, C++ or C# can run the sync engine in their
+//! own process through these functions, declared in `include/wemux.h`; build
+//! a DLL with `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//!
+//! Functions returning `int32_t` return `WEMUX_OK` or `WEMUX_ERROR`; after an
+//! error, `wemux_last_error` describes it. A panic inside the library is
+//! reported the same way instead of unwinding into the caller. Strings the
+//! library returns must be released with `wemux_string_free`. Calls on one
+//! engine must not overlap, and must not be made from its event callback.
+//!
+//! Every engine runs on a thread of its own that joins the multithreaded COM
+//! apartment, so the caller's thread may be in any apartment or none.
+
+use crate::audio::{AudioEngine, DeviceStatus, EngineConfig, EngineEvent};
+use crate::device::DeviceEnumerator;
+use crate::error::{Result, WemuxError};
+use crate::service::config::ServiceConfig;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
+use parking_lot::Mutex;
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+/// The call succeeded
+pub const WEMUX_OK: i32 = 0;
+
+/// The call failed; `wemux_last_error` says why
+pub const WEMUX_ERROR: i32 = -1;
+
+/// What an event passed to a `WemuxEventCallback` is about
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WemuxEventKind {
+    /// The default output changed
+    DefaultDeviceChanged = 0,
+    /// A device was added, removed, enabled or disabled
+    DeviceListChanged = 1,
+    /// An output kept underrunning and got a larger buffer
+    BufferTuned = 2,
+    /// An output receives a Dolby/DTS bitstream and passes it on untouched
+    Passthrough = 3,
+    /// An output stopped responding and was reopened
+    Recovered = 4,
+    /// An output reaches the same speakers as another one and was paused
+    DuplicateSuppressed = 5,
+    /// An output failed to start or to take audio
+    RendererFailed = 6,
+    /// An output fell behind and skipped audio
+    BufferOverrun = 7,
+    /// The fallback source started (message "fallback") or stopped ("source")
+    SourceSwitched = 8,
+    /// An announcement started (message "active") or ended ("inactive")
+    Ducking = 9,
+    /// Capturing the source failed
+    CaptureFailed = 10,
+    /// The engine can't continue and should be restarted
+    Fatal = 11,
+    /// The clock master's device was removed; message is the output that
+    /// took over, NULL if none is left
+    MasterChanged = 12,
+    /// An output that failed (`RendererFailed`) plays again
+    RendererWorking = 13,
+    /// Capturing the source works again after `CaptureFailed`
+    CaptureWorking = 14,
+}
+
+/// Called on a thread of the library for every engine event
+///
+/// `device_id` and `message` may be NULL and are only valid during the call.
+pub type WemuxEventCallback = Option<
+    unsafe extern "C" fn(
+        kind: WemuxEventKind,
+        device_id: *const c_char,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ),
+>;
+
+/// An engine, the thread it runs on and the callback its events go to
+pub struct WemuxEngine {
+    tasks: Option<Sender<Task>>,
+    worker: Option<JoinHandle<()>>,
+    callback: Arc<Mutex<Option<Callback>>>,
+}
+
+/// Work for the engine's thread
+type Task = Box<dyn FnOnce(&mut AudioEngine) + Send>;
+
+impl WemuxEngine {
+    /// Run `task` on the engine's thread and wait for its result
+    fn call<T: Send + 'static>(
+        &self,
+        task: impl FnOnce(&mut AudioEngine) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let (reply_tx, reply_rx) = bounded(1);
+        let task: Task = Box::new(move |engine| {
+            let _ = reply_tx.send(panic::catch_unwind(AssertUnwindSafe(|| task(engine))));
+        });
+        if let Some(tasks) = &self.tasks {
+            let _ = tasks.send(task);
+        }
+        match reply_rx.recv() {
+            Ok(Ok(result)) => result,
+            Ok(Err(payload)) => Err(WemuxError::ChannelError(format!(
+                "engine thread panicked: {}",
+                panic_message(&*payload)
+            ))),
+            Err(_) => Err(WemuxError::ChannelError("engine thread has ended".into())),
+        }
+    }
+}
+
+impl Drop for WemuxEngine {
+    fn drop(&mut self) {
+        // The thread stops the engine once it runs out of tasks
+        self.tasks = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Owns the engine and runs the tasks sent to it
+fn engine_thread(config: EngineConfig, events: Sender<EngineEvent>, tasks: Receiver<Task>) {
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    }
+    let mut engine = AudioEngine::new(config);
+    engine.set_event_channel(events);
+    for task in tasks {
+        task(&mut engine);
+    }
+    if engine.is_running() {
+        let _ = engine.stop();
+    }
+    drop(engine);
+    unsafe { CoUninitialize() };
+}
+
+/// Callback with its user data, called from the event thread
+struct Callback {
+    function: unsafe extern "C" fn(WemuxEventKind, *const c_char, *const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The caller vouches for `user_data` when registering the callback
+unsafe impl Send for Callback {}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// `WEMUX_OK`, or `WEMUX_ERROR` with the error recorded
+fn status(result: Result<()>) -> i32 {
+    match result {
+        Ok(()) => WEMUX_OK,
+        Err(e) => {
+            set_last_error(e);
+            WEMUX_ERROR
+        }
+    }
+}
+
+/// Text of a panic's payload
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Run the body of an API function, returning `fallback` with the error
+/// recorded if it panics
+fn guard<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        set_last_error(format!("wemux panicked: {}", panic_message(&*payload)));
+        fallback
+    })
+}
+
+/// `WEMUX_ERROR` for a NULL engine
+fn null_engine() -> i32 {
+    set_last_error("engine is NULL");
+    WEMUX_ERROR
+}
+
+/// Borrow a C string argument
+unsafe fn text<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+    if value.is_null() {
+        return Err(WemuxError::InvalidConfig(format!("{} is NULL", name)));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| WemuxError::InvalidConfig(format!("{} is not UTF-8", name)))
+}
+
+/// Hand a string to the caller, to be released with `wemux_string_free`
+fn into_c_string(value: String) -> *mut c_char {
+    CString::new(value).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Kind, device ID and message of an engine event
+fn describe(event: EngineEvent) -> (WemuxEventKind, Option<String>, Option<String>) {
+    match event {
+        EngineEvent::DefaultDeviceChanged => (WemuxEventKind::DefaultDeviceChanged, None, None),
+        EngineEvent::DeviceListChanged => (WemuxEventKind::DeviceListChanged, None, None),
+        EngineEvent::BufferTuned {
+            device_id,
+            extra_buffer_ms,
+            prefill_ms,
+        } => (
+            WemuxEventKind::BufferTuned,
+            Some(device_id),
+            Some(format!("+{}ms, {}ms pre-fill", extra_buffer_ms, prefill_ms)),
+        ),
+        EngineEvent::Passthrough { device_id } => {
+            (WemuxEventKind::Passthrough, Some(device_id), None)
+        }
+        EngineEvent::Recovered { device_id, reason } => {
+            (WemuxEventKind::Recovered, Some(device_id), Some(reason))
+        }
+        EngineEvent::DuplicateSuppressed {
+            device_id,
+            duplicate_of,
+        } => (
+            WemuxEventKind::DuplicateSuppressed,
+            Some(device_id),
+            Some(duplicate_of),
+        ),
+        EngineEvent::RendererFailed { device_id, error } => {
+            (WemuxEventKind::RendererFailed, Some(device_id), Some(error))
+        }
+        EngineEvent::BufferOverrun { device_id } => {
+            (WemuxEventKind::BufferOverrun, Some(device_id), None)
+        }
+        EngineEvent::SourceSwitched { fallback } => (
+            WemuxEventKind::SourceSwitched,
+            None,
+            Some(if fallback { "fallback" } else { "source" }.to_string()),
+        ),
+        EngineEvent::Ducking { active } => (
+            WemuxEventKind::Ducking,
+            None,
+            Some(if active { "active" } else { "inactive" }.to_string()),
+        ),
+        EngineEvent::RendererWorking { device_id } => {
+            (WemuxEventKind::RendererWorking, Some(device_id), None)
+        }
+        EngineEvent::CaptureFailed { error } => (WemuxEventKind::CaptureFailed, None, Some(error)),
+        EngineEvent::CaptureWorking => (WemuxEventKind::CaptureWorking, None, None),
+        EngineEvent::Fatal(reason) => (WemuxEventKind::Fatal, None, Some(reason)),
+        EngineEvent::MasterChanged { removed, master } => {
+            (WemuxEventKind::MasterChanged, Some(removed), master)
+        }
+    }
+}
+
+/// Version of the library, e.g. "0.2.1"
+#[no_mangle]
+pub extern "C" fn wemux_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Description of the last error on the calling thread, or NULL
+///
+/// Valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn wemux_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Release a string returned by the library
+///
+/// # Safety
+///
+/// `value` must be NULL or a string returned by the library, not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_string_free(value: *mut c_char) {
+    guard((), || {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+    })
+}
+
+/// Create an engine from a configuration in the service config's TOML
+/// format (NULL = defaults: the default output to every HDMI output)
+///
+/// Returns NULL on error. Release the engine with `wemux_engine_free`.
+///
+/// # Safety
+///
+/// `config` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_new(config: *const c_char) -> *mut WemuxEngine {
+    guard(ptr::null_mut(), || {
+        let config = if config.is_null() {
+            EngineConfig::default()
+        } else {
+            let parsed = text(config, "config").and_then(|config| {
+                toml::from_str::<ServiceConfig>(config)
+                    .map_err(|e| WemuxError::InvalidConfig(e.to_string()))
+            });
+            match parsed {
+                Ok(config) => config.to_engine_config(),
+                Err(e) => {
+                    set_last_error(e);
+                    return ptr::null_mut();
+                }
+            }
+        };
+
+        let callback: Arc<Mutex<Option<Callback>>> = Arc::new(Mutex::new(None));
+        let (event_tx, event_rx) = unbounded();
+        let (tasks, task_rx) = unbounded();
+        let worker = match thread::Builder::new()
+            .name("wemux-ffi-engine".into())
+            .spawn(move || engine_thread(config, event_tx, task_rx))
+        {
+            Ok(worker) => worker,
+            Err(e) => {
+                set_last_error(e);
+                return ptr::null_mut();
+            }
+        };
+
+        // Ends once the engine and its threads have dropped their senders
+        let dispatch = callback.clone();
+        thread::spawn(move || {
+            for event in event_rx {
+                let callback = dispatch.lock();
+                let Some(callback) = callback.as_ref() else {
+                    continue;
+                };
+                let (kind, device_id, message) = describe(event);
+                let device_id = device_id.and_then(|id| CString::new(id).ok());
+                let message = message.and_then(|m| CString::new(m).ok());
+                let as_ptr =
+                    |value: &Option<CString>| value.as_ref().map_or(ptr::null(), |v| v.as_ptr());
+                unsafe {
+                    (callback.function)(
+                        kind,
+                        as_ptr(&device_id),
+                        as_ptr(&message),
+                        callback.user_data,
+                    )
+                };
+            }
+        });
+
+        Box::into_raw(Box::new(WemuxEngine {
+            tasks: Some(tasks),
+            worker: Some(worker),
+            callback,
+        }))
+    })
+}
+
+/// Stop and release an engine
+///
+/// # Safety
+///
+/// `engine` must be NULL or an engine from `wemux_engine_new`, not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_free(engine: *mut WemuxEngine) {
+    guard((), || {
+        if engine.is_null() {
+            return;
+        }
+        let engine = Box::from_raw(engine);
+        // Waits for a callback in progress; none is made afterwards
+        *engine.callback.lock() = None;
+        drop(engine);
+    })
+}
+
+/// Send the engine's events to `callback` (NULL = stop sending them)
+///
+/// # Safety
+///
+/// `engine` must be a live engine. `callback` must be safe to call from
+/// another thread with `user_data` until it is replaced or the engine released.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_set_event_callback(
+    engine: *mut WemuxEngine,
+    callback: WemuxEventCallback,
+    user_data: *mut c_void,
+) -> i32 {
+    guard(WEMUX_ERROR, || {
+        let Some(engine) = engine.as_mut() else {
+            return null_engine();
+        };
+        *engine.callback.lock() = callback.map(|function| Callback {
+            function,
+            user_data,
+        });
+        WEMUX_OK
+    })
+}
+
+/// Start capturing and playing to the outputs
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_start(engine: *mut WemuxEngine) -> i32 {
+    guard(WEMUX_ERROR, || match engine.as_ref() {
+        Some(engine) => status(engine.call(|engine| engine.start())),
+        None => null_engine(),
+    })
+}
+
+/// Stop the engine; it can be started again
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_stop(engine: *mut WemuxEngine) -> i32 {
+    guard(WEMUX_ERROR, || match engine.as_ref() {
+        Some(engine) => status(engine.call(|engine| engine.stop())),
+        None => null_engine(),
+    })
+}
+
+/// Whether the engine is running
+///
+/// # Safety
+///
+/// `engine` must be NULL or a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_is_running(engine: *const WemuxEngine) -> bool {
+    guard(false, || {
+        engine
+            .as_ref()
+            .is_some_and(|e| e.call(|engine| Ok(engine.is_running())).unwrap_or(false))
+    })
+}
+
+/// Pause or resume one output of the running engine
+///
+/// # Safety
+///
+/// `engine` must be a live engine and `device_id` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_set_device_paused(
+    engine: *mut WemuxEngine,
+    device_id: *const c_char,
+    paused: bool,
+) -> i32 {
+    guard(WEMUX_ERROR, || {
+        let Some(engine) = engine.as_ref() else {
+            return null_engine();
+        };
+        status(text(device_id, "device_id").and_then(|id| {
+            let id = id.to_string();
+            engine.call(move |engine| {
+                if paused {
+                    engine.pause_renderer(&id)
+                } else {
+                    engine.resume_renderer(&id)
+                }
+            })
+        }))
+    })
+}
+
+/// The outputs as a JSON array of objects with `id`, `name`, `is_enabled`,
+/// `is_paused`, `is_system_default` and, if set, `alias`
+///
+/// While running these are the engine's outputs; while stopped, every
+/// output device, none enabled. Returns NULL on error; release the string
+/// with `wemux_string_free`.
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn wemux_engine_list_devices(engine: *const WemuxEngine) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let Some(engine) = engine.as_ref() else {
+            set_last_error("engine is NULL");
+            return ptr::null_mut();
+        };
+        match engine.call(list_devices) {
+            Ok(devices) => into_c_string(serde_json::to_string(&devices).unwrap_or_default()),
+            Err(e) => {
+                set_last_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// The engine's outputs, or every output device while it is stopped
+fn list_devices(engine: &mut AudioEngine) -> Result<Vec<DeviceStatus>> {
+    if engine.is_running() {
+        return Ok(engine.get_device_statuses());
+    }
+    let devices = DeviceEnumerator::new()?.enumerate_all_devices()?;
+    Ok(devices
+        .into_iter()
+        .map(|device| DeviceStatus {
+            is_system_default: device.is_default,
+            is_capture_source: false,
+            id: device.id,
+            name: device.name,
+            is_enabled: false,
+            is_paused: false,
+            alias: None,
+            latency: None,
+            duplicate_of: None,
+            receiving_audio: false,
+            end_to_end_ms: None,
+            inactive: None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_config_reports_error() {
+        let config = CString::new("buffer_ms = \"fast\"").unwrap();
+        let engine = unsafe { wemux_engine_new(config.as_ptr()) };
+        assert!(engine.is_null());
+        let error = unsafe { CStr::from_ptr(wemux_last_error()) };
+        assert!(error.to_str().unwrap().contains("buffer_ms"));
+
+        assert_eq!(unsafe { wemux_engine_start(ptr::null_mut()) }, WEMUX_ERROR);
+        assert!(!unsafe { wemux_engine_is_running(ptr::null()) });
+    }
+
+    #[test]
+    fn test_panic_becomes_error() {
+        assert_eq!(guard(WEMUX_ERROR, || panic!("boom")), WEMUX_ERROR);
+        let error = unsafe { CStr::from_ptr(wemux_last_error()) };
+        assert_eq!(error.to_str().unwrap(), "wemux panicked: boom");
+    }
+}
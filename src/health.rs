@@ -0,0 +1,416 @@
+//! Per-device health history kept on disk (`wemux stats`)
+//!
+//! The service samples the engine's statistics every [`SAMPLE_INTERVAL`] and
+//! appends a summary per output to a JSON-lines file every [`FLUSH_INTERVAL`]:
+//! its underruns, reconnects - its renderer starting over after the device
+//! went away or failed - and average drift. Records older than the configured
+//! number of days are dropped once a day. `wemux stats` adds the records up
+//! per device, to point at the HDMI cable or receiver that keeps dropping out.
+
+use crate::audio::{EngineStats, ThreadState};
+use crate::config::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// How often the engine's statistics are sampled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the samples are summarized into the file
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often records past the retention are removed from the file
+const COMPACT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Days of history the service keeps unless configured otherwise
+pub const DEFAULT_HISTORY_DAYS: u32 = 7;
+
+/// %PROGRAMDATA%\wemux\health.jsonl, where the service keeps its history
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("PROGRAMDATA").map(|dir| PathBuf::from(dir).join("wemux").join("health.jsonl"))
+}
+
+/// How one output fared over a period, one line of the history file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthRecord {
+    /// End of the period, in seconds since the Unix epoch
+    pub time: u64,
+    /// Device ID
+    pub device: String,
+    /// Device name
+    pub name: String,
+    /// User-assigned alias
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Seconds the output played in the period
+    pub seconds: u64,
+    /// Underruns in the period
+    pub underruns: u64,
+    /// Times the output's renderer started over in the period
+    pub reconnects: u64,
+    /// Average drift from the clock master in milliseconds, ignoring its sign
+    pub drift_ms: f64,
+}
+
+/// Samples of one output since the last flush
+#[derive(Debug, Default)]
+struct Tally {
+    name: String,
+    alias: Option<String>,
+    /// Counters of the last sample, to tell a renderer that started over
+    last_underruns: u64,
+    last_frames: u64,
+    /// Whether the output was in the last sample
+    present: bool,
+    seconds: f64,
+    underruns: u64,
+    reconnects: u64,
+    drift_sum: f64,
+    drift_samples: u64,
+}
+
+/// Collects engine statistics and appends them to the history file
+#[derive(Debug)]
+pub struct HealthRecorder {
+    path: PathBuf,
+    keep: Duration,
+    devices: HashMap<String, Tally>,
+    /// Engine starts reported by the last sample
+    restarts: Option<u64>,
+    last_sample: Option<Instant>,
+    last_flush: Instant,
+    last_compact: Option<Instant>,
+}
+
+impl HealthRecorder {
+    /// Record to `path`, keeping `keep_days` days of history
+    pub fn new(path: impl Into<PathBuf>, keep_days: u32) -> Self {
+        Self {
+            path: path.into(),
+            keep: Duration::from_secs(keep_days as u64 * 24 * 60 * 60),
+            devices: HashMap::new(),
+            restarts: None,
+            last_sample: None,
+            last_flush: Instant::now(),
+            last_compact: None,
+        }
+    }
+
+    /// Sample the engine if [`SAMPLE_INTERVAL`] passed and write the history
+    /// if [`FLUSH_INTERVAL`] did; meant to be called from the host's loop
+    pub fn update(&mut self, now: Instant, stats: impl FnOnce() -> EngineStats) -> io::Result<()> {
+        let elapsed = match self.last_sample {
+            Some(last) if now.duration_since(last) < SAMPLE_INTERVAL => return Ok(()),
+            Some(last) => now.duration_since(last),
+            None => Duration::ZERO,
+        };
+        self.last_sample = Some(now);
+        self.sample(&stats(), elapsed);
+
+        if now.duration_since(self.last_flush) >= FLUSH_INTERVAL {
+            self.last_flush = now;
+            self.flush()?;
+        }
+        if self
+            .last_compact
+            .is_none_or(|last| now.duration_since(last) >= COMPACT_INTERVAL)
+        {
+            self.last_compact = Some(now);
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Write what was sampled since the last flush, e.g. as the service stops
+    pub fn flush(&mut self) -> io::Result<()> {
+        let records = self.take_records(unix_now());
+        if records.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for record in &records {
+            let line = serde_json::to_string(record).map_err(io::Error::other)?;
+            writeln!(file, "{}", line)?;
+        }
+        debug!("Wrote {} health records", records.len());
+        Ok(())
+    }
+
+    fn sample(&mut self, stats: &EngineStats, elapsed: Duration) {
+        // A new engine starts all renderers over; that's no reconnect
+        let engine_restarted = self.restarts.is_some_and(|r| r != stats.restarts);
+        self.restarts = Some(stats.restarts);
+
+        let mut seen = Vec::with_capacity(stats.renderers.len());
+        for renderer in &stats.renderers {
+            let tally = self.devices.entry(renderer.id.clone()).or_default();
+            let started_over = renderer.underruns < tally.last_underruns
+                || renderer.frames_written < tally.last_frames;
+            if started_over {
+                tally.underruns += renderer.underruns;
+                if !engine_restarted {
+                    tally.reconnects += 1;
+                }
+            } else {
+                tally.underruns += renderer.underruns - tally.last_underruns;
+            }
+            if tally.present {
+                tally.seconds += elapsed.as_secs_f64();
+            }
+            if renderer.state == ThreadState::Running && !renderer.is_master {
+                tally.drift_sum += renderer.drift_ms.abs();
+                tally.drift_samples += 1;
+            }
+            tally.name = renderer.name.clone();
+            tally.alias = renderer.alias.clone();
+            tally.last_underruns = renderer.underruns;
+            tally.last_frames = renderer.frames_written;
+            seen.push(renderer.id.as_str());
+        }
+
+        for (id, tally) in &mut self.devices {
+            let present = seen.contains(&id.as_str());
+            if !present {
+                // Counters of a renderer that comes back start from zero
+                tally.last_underruns = 0;
+                tally.last_frames = 0;
+            }
+            tally.present = present;
+        }
+    }
+
+    /// Records of the samples since the last call, ending at `time`
+    fn take_records(&mut self, time: u64) -> Vec<HealthRecord> {
+        let mut records: Vec<HealthRecord> = self
+            .devices
+            .iter_mut()
+            .filter(|(_, t)| t.seconds > 0.0 || t.underruns > 0 || t.reconnects > 0)
+            .map(|(id, tally)| {
+                let record = HealthRecord {
+                    time,
+                    device: id.clone(),
+                    name: tally.name.clone(),
+                    alias: tally.alias.clone(),
+                    seconds: tally.seconds.round() as u64,
+                    underruns: tally.underruns,
+                    reconnects: tally.reconnects,
+                    drift_ms: if tally.drift_samples > 0 {
+                        tally.drift_sum / tally.drift_samples as f64
+                    } else {
+                        0.0
+                    },
+                };
+                tally.seconds = 0.0;
+                tally.underruns = 0;
+                tally.reconnects = 0;
+                tally.drift_sum = 0.0;
+                tally.drift_samples = 0;
+                record
+            })
+            .collect();
+        records.sort_by(|a, b| a.device.cmp(&b.device));
+        records
+    }
+
+    /// Rewrite the file without the records past the retention
+    fn compact(&self) -> io::Result<()> {
+        let records = match read_records(&self.path) {
+            Ok(records) => records,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let oldest = unix_now().saturating_sub(self.keep.as_secs());
+        let kept: Vec<&HealthRecord> = records.iter().filter(|r| r.time >= oldest).collect();
+        if kept.len() == records.len() {
+            return Ok(());
+        }
+
+        let mut content = String::new();
+        for record in kept {
+            content.push_str(&serde_json::to_string(record).map_err(io::Error::other)?);
+            content.push('\n');
+        }
+        write_atomic(&self.path, &content)
+    }
+}
+
+/// Records in the history file at `path`; lines that don't parse are skipped
+pub fn read_records(path: &Path) -> io::Result<Vec<HealthRecord>> {
+    let file = fs::File::open(path)?;
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(record) = serde_json::from_str(&line?) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// An output's history added up over a period
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceHealth {
+    pub device: String,
+    pub name: String,
+    pub alias: Option<String>,
+    /// Seconds the output played
+    pub seconds: u64,
+    pub underruns: u64,
+    pub reconnects: u64,
+    /// Average drift in milliseconds, weighted by playing time
+    pub drift_ms: f64,
+}
+
+impl DeviceHealth {
+    /// Name to show the user: the alias if one is assigned, else the device name
+    pub fn display_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Underruns per hour of playing
+    pub fn underruns_per_hour(&self) -> f64 {
+        if self.seconds == 0 {
+            return 0.0;
+        }
+        self.underruns as f64 * 3600.0 / self.seconds as f64
+    }
+}
+
+/// Add up the records from `since` (seconds since the Unix epoch) on per
+/// device, the outputs with the most underruns per hour first
+pub fn summarize(records: &[HealthRecord], since: u64) -> Vec<DeviceHealth> {
+    let mut devices: HashMap<&str, DeviceHealth> = HashMap::new();
+    for record in records.iter().filter(|r| r.time >= since) {
+        let health = devices
+            .entry(record.device.as_str())
+            .or_insert_with(|| DeviceHealth {
+                device: record.device.clone(),
+                name: String::new(),
+                alias: None,
+                seconds: 0,
+                underruns: 0,
+                reconnects: 0,
+                drift_ms: 0.0,
+            });
+        // The newest record names the device
+        health.name = record.name.clone();
+        health.alias = record.alias.clone();
+        let seconds = health.seconds + record.seconds;
+        if seconds > 0 {
+            health.drift_ms = (health.drift_ms * health.seconds as f64
+                + record.drift_ms * record.seconds as f64)
+                / seconds as f64;
+        }
+        health.seconds = seconds;
+        health.underruns += record.underruns;
+        health.reconnects += record.reconnects;
+    }
+
+    let mut summary: Vec<DeviceHealth> = devices.into_values().collect();
+    summary.sort_by(|a, b| {
+        b.underruns_per_hour()
+            .total_cmp(&a.underruns_per_hour())
+            .then(b.reconnects.cmp(&a.reconnects))
+            .then_with(|| a.device.cmp(&b.device))
+    });
+    summary
+}
+
+/// Parse a period such as "90s", "30m", "24h" or "7d"
+pub fn parse_period(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a period like 30m, 24h or 7d", text))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("'{}' is not a period like 30m, 24h or 7d", text)),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("'{}' is too long a period", text))
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::RendererStats;
+
+    fn renderer(id: &str, underruns: u64, frames_written: u64, drift_ms: f64) -> RendererStats {
+        RendererStats {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            alias: None,
+            state: ThreadState::Running,
+            is_master: false,
+            drift_ms,
+            buffered_ms: 0.0,
+            underruns,
+            frames_written,
+            extra_buffer_ms: 0,
+            longest_pass_ms: 0.0,
+            heartbeat_age_ms: 0,
+            stalled: false,
+            end_to_end_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_recorder_counts_underruns_and_reconnects() {
+        let mut recorder = HealthRecorder::new("unused.jsonl", DEFAULT_HISTORY_DAYS);
+        let stats = |renderers| EngineStats {
+            running: true,
+            renderers,
+            ..EngineStats::default()
+        };
+        recorder.sample(&stats(vec![renderer("tv", 2, 1000, 1.0)]), Duration::ZERO);
+        recorder.sample(&stats(vec![renderer("tv", 5, 2000, -3.0)]), SAMPLE_INTERVAL);
+        // The TV's renderer started over after the device came back
+        recorder.sample(&stats(vec![renderer("tv", 1, 100, 2.0)]), SAMPLE_INTERVAL);
+
+        let records = recorder.take_records(3600);
+        assert_eq!(records.len(), 1);
+        let tv = &records[0];
+        assert_eq!((tv.seconds, tv.underruns, tv.reconnects), (20, 6, 1));
+        assert!((tv.drift_ms - 2.0).abs() < 1e-9);
+        assert!(recorder.take_records(3700).is_empty());
+
+        let older = HealthRecord {
+            time: 100,
+            seconds: 3580,
+            underruns: 0,
+            drift_ms: 0.0,
+            ..tv.clone()
+        };
+        let summary = summarize(&[older.clone(), tv.clone()], 0);
+        assert_eq!(summary[0].seconds, 3600);
+        assert!((summary[0].underruns_per_hour() - 6.0).abs() < 1e-9);
+        assert!(summarize(&[older], 200).is_empty());
+
+        assert_eq!(parse_period("24h"), Ok(Duration::from_secs(86400)));
+        assert!(parse_period("soon").is_err());
+        assert!(parse_period(&format!("{}d", u64::MAX / 60)).is_err());
+    }
+}
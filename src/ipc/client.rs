@@ -0,0 +1,159 @@
+//! Client for an engine hosted by another process
+
+use crate::audio::{EngineStats, VolumeMode};
+use crate::error::{Result, WemuxError};
+use crate::ipc::{send_request, Request, Response, StatusReport};
+use crate::logging::LogLevel;
+use crate::remote;
+
+/// How requests reach the engine host
+#[derive(Clone)]
+enum Transport {
+    /// Named pipe on this machine
+    Pipe(String),
+    /// Remote administration endpoint of another machine
+    Remote { addr: String, token: String },
+}
+
+impl std::fmt::Debug for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Pipe(name) => f.debug_tuple("Pipe").field(name).finish(),
+            // Keep the token out of logs
+            Transport::Remote { addr, .. } => f
+                .debug_struct("Remote")
+                .field("addr", addr)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Remote control for an engine running in another process (e.g. the service)
+///
+/// Mirrors the control surface of [`AudioEngine`](crate::audio::AudioEngine);
+/// every call is a single request/response round trip over the named pipe, or
+/// over HTTP for a client created with [`remote`](Self::remote).
+#[derive(Debug, Clone)]
+pub struct EngineClient {
+    transport: Transport,
+}
+
+impl EngineClient {
+    /// Create a client for the instance listening on `pipe_name`
+    pub fn new(pipe_name: impl Into<String>) -> Self {
+        Self {
+            transport: Transport::Pipe(pipe_name.into()),
+        }
+    }
+
+    /// Create a client for the service's remote administration endpoint at
+    /// `addr` (e.g. "htpc:9465")
+    pub fn remote(addr: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            transport: Transport::Remote {
+                addr: addr.into(),
+                token: token.into(),
+            },
+        }
+    }
+
+    /// Query engine state and device statuses
+    pub fn status(&self) -> Result<StatusReport> {
+        match self.send(&Request::Status)? {
+            Response::Status(report) => Ok(report),
+            other => Err(Self::unexpected(other)),
+        }
+    }
+
+    /// Query live engine statistics
+    pub fn stats(&self) -> Result<EngineStats> {
+        match self.send(&Request::Stats)? {
+            Response::Stats(stats) => Ok(stats),
+            other => Err(Self::unexpected(other)),
+        }
+    }
+
+    /// Start the remote engine (no-op if it is already running)
+    pub fn start(&self) -> Result<()> {
+        self.call(Request::Start)
+    }
+
+    /// Stop the remote engine
+    pub fn stop(&self) -> Result<()> {
+        self.call(Request::Stop)
+    }
+
+    /// Stop the remote engine if it runs and start it again
+    pub fn restart(&self) -> Result<()> {
+        self.call(Request::Restart)
+    }
+
+    /// Pause output to a device
+    pub fn pause_device(&self, device_id: &str) -> Result<()> {
+        self.call(Request::PauseDevice {
+            device_id: device_id.to_string(),
+        })
+    }
+
+    /// Resume output to a device
+    pub fn resume_device(&self, device_id: &str) -> Result<()> {
+        self.call(Request::ResumeDevice {
+            device_id: device_id.to_string(),
+        })
+    }
+
+    /// Mute or unmute all outputs
+    pub fn set_muted(&self, muted: bool) -> Result<()> {
+        self.call(Request::SetMuted { muted })
+    }
+
+    /// Pause or resume all outputs
+    pub fn set_paused_all(&self, paused: bool) -> Result<()> {
+        self.call(Request::SetPausedAll { paused })
+    }
+
+    /// Set wemux's own volume of all outputs (0.0 - 1.0)
+    pub fn set_mirror_volume(&self, volume: f32) -> Result<()> {
+        self.call(Request::SetMirrorVolume { volume })
+    }
+
+    /// Choose how the volume of all outputs is set
+    pub fn set_volume_mode(&self, mode: VolumeMode) -> Result<()> {
+        self.call(Request::SetVolumeMode { mode })
+    }
+
+    /// Pause or resume a zone
+    pub fn set_zone_paused(&self, zone: &str, paused: bool) -> Result<()> {
+        self.call(Request::SetZonePaused {
+            zone: zone.to_string(),
+            paused,
+        })
+    }
+
+    /// Change the log level of the remote instance
+    pub fn set_log_level(&self, level: LogLevel) -> Result<()> {
+        self.call(Request::SetLogLevel { level })
+    }
+
+    /// Send a request that is answered with a plain acknowledgement
+    fn call(&self, request: Request) -> Result<()> {
+        match self.send(&request)? {
+            Response::Ok => Ok(()),
+            other => Err(Self::unexpected(other)),
+        }
+    }
+
+    fn send(&self, request: &Request) -> Result<Response> {
+        match &self.transport {
+            Transport::Pipe(name) => send_request(name, request),
+            Transport::Remote { addr, token } => remote::send_request(addr, token, request),
+        }
+    }
+
+    fn unexpected(response: Response) -> WemuxError {
+        match response {
+            Response::Error { message } => WemuxError::Ipc(message),
+            other => WemuxError::Ipc(format!("Unexpected response: {:?}", other)),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Answering requests against an engine hosted by this process
+//!
+//! The service and the daemon own their engine directly, so they answer
+//! requests the same way; the tray goes through its engine controller.
+
+use crate::audio::AudioEngine;
+use crate::ipc::{Request, Response, StatusReport};
+use crate::logging;
+use tracing::info;
+
+/// Answer a remote control request against an engine this process hosts
+pub(crate) fn answer_request(engine: &mut AudioEngine, request: Request) -> Response {
+    let result = match request {
+        // Nothing to bring to the foreground without a window
+        Request::Activate => Ok(()),
+        Request::Status => {
+            return Response::Status(StatusReport {
+                running: engine.is_running(),
+                devices: engine.get_device_statuses(),
+                muted: engine.is_muted(),
+                paused_all: engine.is_paused_all(),
+                zones: engine.zone_statuses(),
+            })
+        }
+        Request::Stats => return Response::Stats(engine.get_stats()),
+        Request::Start => {
+            if engine.is_running() {
+                Ok(())
+            } else {
+                info!("Engine start requested over IPC");
+                engine.start()
+            }
+        }
+        Request::Stop => {
+            info!("Engine stop requested over IPC");
+            engine.stop()
+        }
+        Request::Restart => {
+            info!("Engine restart requested over IPC");
+            engine.restart()
+        }
+        Request::PauseDevice { device_id } => engine.pause_renderer(&device_id),
+        Request::ResumeDevice { device_id } => engine.resume_renderer(&device_id),
+        Request::SetMuted { muted } => {
+            engine.set_muted(muted);
+            Ok(())
+        }
+        Request::SetPausedAll { paused } => {
+            if paused {
+                engine.pause_all();
+            } else {
+                engine.resume_all();
+            }
+            Ok(())
+        }
+        Request::SetMirrorVolume { volume } => {
+            engine.set_mirror_volume(volume);
+            Ok(())
+        }
+        Request::SetVolumeMode { mode } => {
+            engine.set_volume_mode(mode);
+            Ok(())
+        }
+        Request::SetZonePaused { zone, paused } => engine.set_zone_paused(&zone, paused),
+        Request::SetLogLevel { level } => logging::set_log_level(level),
+    };
+
+    match result {
+        Ok(()) => Response::Ok,
+        Err(e) => Response::Error {
+            message: e.to_string(),
+        },
+    }
+}
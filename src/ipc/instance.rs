@@ -0,0 +1,57 @@
+//! Named mutex used to detect an already running instance
+
+use crate::error::Result;
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{
+            CloseHandle, GetLastError, ERROR_ACCESS_DENIED, ERROR_ALREADY_EXISTS, HANDLE,
+        },
+        System::Threading::CreateMutexW,
+    },
+};
+
+/// Lock held for the lifetime of an instance
+///
+/// The named mutex exists as long as any process holds a handle to it, so a
+/// second instance can detect the first one even if it crashed mid-startup.
+pub struct InstanceLock {
+    handle: HANDLE,
+}
+
+// SAFETY: The mutex is never waited on or released, the handle only keeps the
+// named object alive and can be closed from any thread
+unsafe impl Send for InstanceLock {}
+
+impl InstanceLock {
+    /// Try to acquire the named lock
+    ///
+    /// Returns `None` if another instance already holds it.
+    pub fn acquire(name: &str) -> Result<Option<Self>> {
+        let name_wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+
+        unsafe {
+            let handle = match CreateMutexW(None, false, PCWSTR(name_wide.as_ptr())) {
+                Ok(handle) => handle,
+                // Held by a process of another account (e.g. the service as SYSTEM)
+                Err(e) if e.code() == ERROR_ACCESS_DENIED.to_hresult() => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+            if GetLastError() == ERROR_ALREADY_EXISTS {
+                let _ = CloseHandle(handle);
+                return Ok(None);
+            }
+
+            Ok(Some(Self { handle }))
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
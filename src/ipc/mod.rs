@@ -0,0 +1,34 @@
+//! Inter-process communication between wemux instances
+//!
+//! A running instance listens on a named pipe and answers line-delimited JSON
+//! requests. This is used to hand off to an already running instance instead
+//! of starting a second one, and by the tray to remote-control the engine
+//! hosted by the wemux service.
+
+mod client;
+mod host;
+mod instance;
+mod pipe;
+mod protocol;
+
+pub use client::EngineClient;
+pub(crate) use host::answer_request;
+pub use instance::InstanceLock;
+pub(crate) use pipe::dispatch;
+pub use pipe::{send_request, IpcMessage, PipeAccess, PipeServer};
+pub use protocol::{Request, Response, StatusReport};
+
+/// Named pipe the tray application listens on
+pub const TRAY_PIPE_NAME: &str = r"\\.\pipe\wemux-tray";
+
+/// Named mutex guarding a single tray instance per user session
+pub const TRAY_INSTANCE_NAME: &str = r"Local\wemux-tray";
+
+/// Named mutex held by whichever process runs an audio engine, machine-wide
+pub const ENGINE_INSTANCE_NAME: &str = r"Global\wemux-engine";
+
+/// Named pipe the wemux service listens on (shared with all local users)
+pub const SERVICE_PIPE_NAME: &str = r"\\.\pipe\wemux-service";
+
+/// Named pipe a `wemux start --daemon` instance listens on
+pub const DAEMON_PIPE_NAME: &str = r"\\.\pipe\wemux-daemon";
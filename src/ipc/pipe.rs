@@ -0,0 +1,465 @@
+//! Named pipe server and client
+
+use crate::error::{Result, WemuxError};
+use crate::ipc::{Request, Response};
+use crossbeam_channel::{bounded, Sender};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::windows::io::{AsRawHandle, FromRawHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{
+            CloseHandle, LocalFree, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, FALSE, HANDLE, HLOCAL,
+        },
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+                SDDL_REVISION_1,
+            },
+            GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY,
+            TOKEN_USER,
+        },
+        Storage::FileSystem::{FlushFileBuffers, PIPE_ACCESS_DUPLEX},
+        System::{
+            Pipes::{
+                ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE,
+                PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+            },
+            RemoteDesktop::{WTSGetActiveConsoleSessionId, WTSQueryUserToken},
+            Threading::{GetCurrentProcess, OpenProcessToken},
+            IO::CancelSynchronousIo,
+        },
+    },
+};
+
+/// Pipe buffer size in bytes
+const PIPE_BUFFER_SIZE: u32 = 4096;
+
+/// How long the server waits for the owner to answer a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a client retries while all pipe instances are busy
+const BUSY_RETRIES: u32 = 20;
+
+/// How long dropping the server waits for its thread, which may be waiting
+/// for the owner to answer a request
+const STOP_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Access rules for pipes shared across user accounts
+///
+/// SYSTEM and administrators get full control.
+const SHARED_PIPE_SDDL: &str = "D:(A;;GA;;;SY)(A;;GA;;;BA)";
+
+/// Read and write access for a client of a shared pipe (FILE_GENERIC_READ |
+/// FILE_GENERIC_WRITE without FILE_CREATE_PIPE_INSTANCE, so it cannot squat on
+/// the pipe name)
+const CLIENT_ACCESS: &str = "0x12019b";
+
+/// No console session, as returned by WTSGetActiveConsoleSessionId
+const NO_CONSOLE_SESSION: u32 = 0xFFFF_FFFF;
+
+/// Who besides SYSTEM and administrators may use a shared pipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeAccess {
+    /// The user signed in at the console, and the account the process runs as
+    ConsoleUser,
+    /// Every signed-in user, including remote desktop sessions
+    AllUsers,
+}
+
+/// Pipe handle moved to the server thread
+struct PipeHandle(HANDLE);
+
+// SAFETY: pipe handles are kernel handles usable from any thread; only the
+// server thread uses it after the move
+unsafe impl Send for PipeHandle {}
+
+/// Security descriptor of one instance of a shared pipe
+struct PipeSecurity(PSECURITY_DESCRIPTOR);
+
+impl PipeSecurity {
+    /// Build the descriptor of a shared pipe open to `access`
+    ///
+    /// The console user is looked up anew for every pipe instance, so a user
+    /// switch is picked up by the next connection.
+    fn shared(access: PipeAccess) -> Result<Self> {
+        let mut sddl = SHARED_PIPE_SDDL.to_string();
+        match access {
+            PipeAccess::AllUsers => sddl.push_str(&format!("(A;;{};;;AU)", CLIENT_ACCESS)),
+            PipeAccess::ConsoleUser => {
+                let users = [console_user_sid(), process_user_sid()];
+                let mut sids: Vec<String> = users.into_iter().flatten().collect();
+                sids.dedup();
+                for sid in sids {
+                    sddl.push_str(&format!("(A;;{};;;{})", CLIENT_ACCESS, sid));
+                }
+            }
+        }
+        let sddl: Vec<u16> = sddl.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut descriptor = PSECURITY_DESCRIPTOR(std::ptr::null_mut());
+
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                PCWSTR(sddl.as_ptr()),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                None,
+            )
+        }
+        .map_err(|e| WemuxError::Ipc(format!("Failed to build pipe security: {}", e)))?;
+
+        Ok(Self(descriptor))
+    }
+
+    fn attributes(&self) -> SECURITY_ATTRIBUTES {
+        SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.0 .0,
+            bInheritHandle: FALSE,
+        }
+    }
+}
+
+impl Drop for PipeSecurity {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = LocalFree(HLOCAL(self.0 .0));
+        }
+    }
+}
+
+/// SID of the user signed in at the console, if anyone is
+///
+/// Only works for a process running as SYSTEM, like the service.
+fn console_user_sid() -> Option<String> {
+    let session = unsafe { WTSGetActiveConsoleSessionId() };
+    if session == NO_CONSOLE_SESSION {
+        return None;
+    }
+    let mut token = HANDLE::default();
+    unsafe { WTSQueryUserToken(session, &mut token) }.ok()?;
+    let sid = token_user_sid(token);
+    unsafe {
+        let _ = CloseHandle(token);
+    }
+    sid
+}
+
+/// SID of the account this process runs as
+fn process_user_sid() -> Option<String> {
+    let mut token = HANDLE::default();
+    unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) }.ok()?;
+    let sid = token_user_sid(token);
+    unsafe {
+        let _ = CloseHandle(token);
+    }
+    sid
+}
+
+/// SID of the user `token` belongs to, in string form
+fn token_user_sid(token: HANDLE) -> Option<String> {
+    let mut size = 0;
+    // The first call fails and reports the size needed
+    let _ = unsafe { GetTokenInformation(token, TokenUser, None, 0, &mut size) };
+    // u64 keeps the buffer aligned for TOKEN_USER
+    let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+    unsafe {
+        GetTokenInformation(
+            token,
+            TokenUser,
+            Some(buffer.as_mut_ptr().cast()),
+            size,
+            &mut size,
+        )
+    }
+    .ok()?;
+    let user = unsafe { &*(buffer.as_ptr() as *const TOKEN_USER) };
+
+    let mut string = windows::core::PWSTR::null();
+    unsafe { ConvertSidToStringSidW(user.User.Sid, &mut string) }.ok()?;
+    let sid = unsafe { string.to_string() }.ok();
+    unsafe {
+        let _ = LocalFree(HLOCAL(string.0.cast()));
+    }
+    sid
+}
+
+/// Request received by a [`PipeServer`] together with a channel for its response
+pub struct IpcMessage {
+    /// The decoded request
+    pub request: Request,
+    /// Channel the owner must answer on
+    pub reply: Sender<Response>,
+}
+
+/// Named pipe server forwarding requests to the owning thread
+///
+/// Requests are delivered as [`IpcMessage`]s so the owner can answer them from
+/// its own loop (e.g. the tray message loop) without sharing state with the
+/// pipe thread.
+pub struct PipeServer {
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PipeServer {
+    /// Start listening on the named pipe, accessible to the current user only
+    pub fn start(name: &str, message_tx: Sender<IpcMessage>) -> Result<Self> {
+        Self::start_with_security(name, message_tx, None)
+    }
+
+    /// Start listening on a named pipe that other local users may use too
+    ///
+    /// Used by the service, whose pipe must be reachable from unelevated
+    /// processes in the interactive session.
+    pub fn start_shared(
+        name: &str,
+        access: PipeAccess,
+        message_tx: Sender<IpcMessage>,
+    ) -> Result<Self> {
+        Self::start_with_security(name, message_tx, Some(access))
+    }
+
+    fn start_with_security(
+        name: &str,
+        message_tx: Sender<IpcMessage>,
+        access: Option<PipeAccess>,
+    ) -> Result<Self> {
+        // Create the first instance here so failures are reported to the caller
+        let pipe = create_pipe_instance(name, access)?;
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop_flag.clone();
+        let thread_name = name.to_string();
+
+        let pipe = PipeHandle(pipe);
+        let thread = thread::spawn(move || {
+            server_thread(pipe, thread_name, access, message_tx, thread_stop);
+        });
+
+        info!("IPC server listening on {}", name);
+
+        Ok(Self {
+            stop_flag,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for PipeServer {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        let Some(handle) = self.thread.take() else {
+            return;
+        };
+
+        // Abort whatever pipe call the server thread is blocked in, waiting
+        // for a client or for one to send or read; it may only enter the call
+        // after a cancel, so keep cancelling until it exits
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                warn!("IPC server thread did not stop in time, leaving it behind");
+                return;
+            }
+            unsafe {
+                let _ = CancelSynchronousIo(HANDLE(handle.as_raw_handle()));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = handle.join();
+    }
+}
+
+/// Send a request to the instance listening on `name` and wait for its response
+pub fn send_request(name: &str, request: &Request) -> Result<Response> {
+    let file = open_client(name)?;
+
+    let mut line = serde_json::to_string(request)
+        .map_err(|e| WemuxError::Ipc(format!("Failed to encode request: {}", e)))?;
+    line.push('\n');
+    (&file)
+        .write_all(line.as_bytes())
+        .map_err(|e| WemuxError::Ipc(format!("Failed to send request: {}", e)))?;
+
+    let mut response = String::new();
+    BufReader::new(&file)
+        .read_line(&mut response)
+        .map_err(|e| WemuxError::Ipc(format!("Failed to read response: {}", e)))?;
+
+    serde_json::from_str(response.trim())
+        .map_err(|e| WemuxError::Ipc(format!("Invalid response: {}", e)))
+}
+
+/// Open a client connection, retrying while all pipe instances are busy
+fn open_client(name: &str) -> Result<File> {
+    let mut attempts = 0;
+    loop {
+        match OpenOptions::new().read(true).write(true).open(name) {
+            Ok(file) => return Ok(file),
+            Err(e)
+                if e.raw_os_error() == Some(ERROR_PIPE_BUSY.0 as i32)
+                    && attempts < BUSY_RETRIES =>
+            {
+                attempts += 1;
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(WemuxError::Ipc(format!(
+                    "No instance listening on {}",
+                    name
+                )));
+            }
+            Err(e) => {
+                return Err(WemuxError::Ipc(format!(
+                    "Failed to connect to {}: {}",
+                    name, e
+                )))
+            }
+        }
+    }
+}
+
+/// Create a new instance of the named pipe, shared with `access` if given
+fn create_pipe_instance(name: &str, access: Option<PipeAccess>) -> Result<HANDLE> {
+    let name_wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    let security = access.map(PipeSecurity::shared).transpose()?;
+    let attributes = security.as_ref().map(PipeSecurity::attributes);
+
+    unsafe {
+        let pipe = CreateNamedPipeW(
+            PCWSTR(name_wide.as_ptr()),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            PIPE_BUFFER_SIZE,
+            PIPE_BUFFER_SIZE,
+            0,
+            attributes.as_ref().map(|a| a as *const SECURITY_ATTRIBUTES),
+        );
+
+        if pipe.is_invalid() {
+            return Err(WemuxError::Ipc(format!(
+                "Failed to create pipe {}: {}",
+                name,
+                windows::core::Error::from_win32()
+            )));
+        }
+
+        Ok(pipe)
+    }
+}
+
+/// Server thread function - accepts one client at a time
+fn server_thread(
+    pipe: PipeHandle,
+    name: String,
+    access: Option<PipeAccess>,
+    message_tx: Sender<IpcMessage>,
+    stop_flag: Arc<AtomicBool>,
+) {
+    let mut pipe = pipe.0;
+
+    loop {
+        let connected = match unsafe { ConnectNamedPipe(pipe, None) } {
+            Ok(()) => true,
+            // Client connected between CreateNamedPipe and ConnectNamedPipe
+            Err(e) => e.code() == ERROR_PIPE_CONNECTED.to_hresult(),
+        };
+
+        if stop_flag.load(Ordering::SeqCst) {
+            unsafe {
+                let _ = CloseHandle(pipe);
+            }
+            break;
+        }
+
+        if connected {
+            handle_connection(pipe, &message_tx);
+        } else {
+            unsafe {
+                let _ = CloseHandle(pipe);
+            }
+        }
+        if stop_flag.load(Ordering::SeqCst) {
+            break;
+        }
+
+        pipe = match create_pipe_instance(&name, access) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("IPC server stopped: {}", e);
+                break;
+            }
+        };
+    }
+
+    debug!("IPC server thread stopped");
+}
+
+/// Read one request, forward it to the owner and write back the response
+fn handle_connection(pipe: HANDLE, message_tx: &Sender<IpcMessage>) {
+    // SAFETY: the file takes ownership of the pipe handle and closes it on drop
+    let file = unsafe { File::from_raw_handle(pipe.0) };
+
+    let mut line = String::new();
+    if let Err(e) = BufReader::new(&file).read_line(&mut line) {
+        warn!("Failed to read IPC request: {}", e);
+        return;
+    }
+
+    let response = match serde_json::from_str::<Request>(line.trim()) {
+        Ok(request) => {
+            debug!("IPC request: {:?}", request);
+            dispatch(request, message_tx)
+        }
+        Err(e) => Response::Error {
+            message: format!("Invalid request: {}", e),
+        },
+    };
+
+    match serde_json::to_string(&response) {
+        Ok(mut out) => {
+            out.push('\n');
+            if let Err(e) = (&file).write_all(out.as_bytes()) {
+                warn!("Failed to write IPC response: {}", e);
+            }
+        }
+        Err(e) => warn!("Failed to encode IPC response: {}", e),
+    }
+
+    unsafe {
+        // Make sure the client has read the response before disconnecting
+        let _ = FlushFileBuffers(pipe);
+        let _ = DisconnectNamedPipe(pipe);
+    }
+}
+
+/// Hand a request to the owner and wait for its answer
+pub(crate) fn dispatch(request: Request, message_tx: &Sender<IpcMessage>) -> Response {
+    let (reply_tx, reply_rx) = bounded(1);
+
+    if message_tx
+        .send(IpcMessage {
+            request,
+            reply: reply_tx,
+        })
+        .is_err()
+    {
+        return Response::Error {
+            message: "Instance is shutting down".to_string(),
+        };
+    }
+
+    reply_rx
+        .recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| Response::Error {
+            message: "Timed out waiting for instance".to_string(),
+        })
+}
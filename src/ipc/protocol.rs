@@ -0,0 +1,156 @@
+//! Request/response messages exchanged over the IPC pipe
+
+use crate::audio::{DeviceStatus, EngineStats, VolumeMode, ZoneStatus};
+use crate::logging::LogLevel;
+use serde::{Deserialize, Serialize};
+
+/// Request sent to a running instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// A second instance was launched - bring the running one to the user's attention
+    Activate,
+    /// Query engine state and device statuses
+    Status,
+    /// Query live engine statistics (drift, buffer fill, volume)
+    Stats,
+    /// Start the audio engine
+    Start,
+    /// Stop the audio engine
+    Stop,
+    /// Stop the audio engine if it runs and start it again
+    Restart,
+    /// Pause output to a device
+    PauseDevice { device_id: String },
+    /// Resume output to a device
+    ResumeDevice { device_id: String },
+    /// Mute or unmute all outputs
+    SetMuted { muted: bool },
+    /// Pause or resume all outputs, keeping the engine running
+    SetPausedAll { paused: bool },
+    /// Set wemux's own volume of the outputs (0.0 - 1.0)
+    SetMirrorVolume { volume: f32 },
+    /// Choose whether the outputs follow the source's volume, the mirror
+    /// volume or both
+    SetVolumeMode { mode: VolumeMode },
+    /// Pause or resume a zone
+    SetZonePaused { zone: String, paused: bool },
+    /// Change the log level of the running instance
+    SetLogLevel { level: LogLevel },
+}
+
+/// Response returned by a running instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// Request handled successfully
+    Ok,
+    /// Engine state, answering [`Request::Status`]
+    Status(StatusReport),
+    /// Live statistics, answering [`Request::Stats`]
+    Stats(EngineStats),
+    /// Request failed
+    Error { message: String },
+}
+
+/// Engine state reported by the instance hosting the engine
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// Whether the engine is running
+    pub running: bool,
+    /// Status of each active renderer
+    pub devices: Vec<DeviceStatus>,
+    /// Whether all outputs are muted
+    #[serde(default)]
+    pub muted: bool,
+    /// Whether all outputs are paused
+    #[serde(default)]
+    pub paused_all: bool,
+    /// Zones of the engine and whether each plays (empty without zones)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub zones: Vec<ZoneStatus>,
+}
+
+impl StatusReport {
+    /// Playing output with the largest buffer, which sets the latency of all of them
+    pub fn limiting_device(&self) -> Option<&DeviceStatus> {
+        self.devices
+            .iter()
+            .filter(|d| !d.is_paused)
+            .filter_map(|d| Some((d, d.latency.as_ref()?.buffer_ms)))
+            .max_by_key(|(_, buffer_ms)| *buffer_ms)
+            .map(|(d, _)| d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{DeviceLatency, HardwareCapabilities};
+
+    #[test]
+    fn test_wire_format() {
+        let json = serde_json::to_string(&Request::Activate).unwrap();
+        assert_eq!(json, r#"{"type":"activate"}"#);
+        assert!(matches!(
+            serde_json::from_str::<Request>(&json).unwrap(),
+            Request::Activate
+        ));
+
+        let json = serde_json::to_string(&Request::PauseDevice {
+            device_id: "hdmi".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"type":"pause_device","device_id":"hdmi"}"#);
+        assert_eq!(
+            serde_json::to_string(&Request::Restart).unwrap(),
+            r#"{"type":"restart"}"#
+        );
+
+        let json = serde_json::to_string(&Response::Error {
+            message: "boom".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"type":"error","message":"boom"}"#);
+    }
+
+    #[test]
+    fn test_status_roundtrip() {
+        let report = StatusReport {
+            running: true,
+            devices: vec![DeviceStatus {
+                id: "dev1".to_string(),
+                name: "HDMI".to_string(),
+                is_enabled: true,
+                is_paused: false,
+                is_system_default: false,
+                is_capture_source: false,
+                alias: Some("tv".to_string()),
+                latency: Some(DeviceLatency {
+                    capabilities: HardwareCapabilities::default(),
+                    buffer_ms: 35,
+                }),
+                duplicate_of: None,
+                receiving_audio: true,
+                end_to_end_ms: Some(87),
+                inactive: None,
+            }],
+            muted: false,
+            paused_all: true,
+            zones: vec![ZoneStatus {
+                name: "Office".to_string(),
+                paused: true,
+            }],
+        };
+
+        let json = serde_json::to_string(&Response::Status(report.clone())).unwrap();
+        assert!(json.starts_with(r#"{"type":"status","running":true"#));
+        assert!(json.contains(r#""latency_class":"standard""#));
+        assert_eq!(report.limiting_device().unwrap().id, "dev1");
+
+        match serde_json::from_str::<Response>(&json).unwrap() {
+            Response::Status(decoded) => assert_eq!(decoded, report),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+}
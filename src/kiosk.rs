@@ -0,0 +1,286 @@
+//! Unattended operation for signage and other kiosk setups (`wemux kiosk`)
+//!
+//! The supervisor keeps an engine running for as long as the process lives:
+//! a start that fails, a fatal engine event, an engine that stops on its own
+//! and even a panic all lead to a new engine after an exponential backoff,
+//! capped so a device that comes back is picked up within a bounded time.
+//! Nothing here needs a desktop session - no tray, no prompts, no console
+//! input - so it can run from a scheduled task or a shell replacement.
+//!
+//! Progress is written to a heartbeat file that an external monitor can
+//! watch: a stale timestamp means the process is gone or hung, a fresh one
+//! with `"state": "restarting"` means wemux is alive but has no audio. A
+//! [`FailureNotifier`] can also push a message once restarting goes on for
+//! too long.
+
+use crate::audio::{AudioEngine, EngineConfig, EngineEvent};
+use crate::notify::FailureNotifier;
+use crossbeam_channel::{unbounded, Receiver};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+/// Delay before the first restart; doubles with every failure in a row
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// A run this long means the next failure is a new incident
+const RESTART_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// How often the supervisor checks the stop flag and the engine
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Supervisor settings
+#[derive(Debug, Clone)]
+pub struct KioskOptions {
+    /// File rewritten with the supervisor's state every `heartbeat_interval`
+    pub heartbeat_path: Option<PathBuf>,
+    pub heartbeat_interval: Duration,
+    /// Longest wait between restart attempts
+    pub max_backoff: Duration,
+    /// Told whether the supervisor is restarting, to report long failures
+    pub notifier: Option<FailureNotifier>,
+}
+
+impl Default for KioskOptions {
+    fn default() -> Self {
+        Self {
+            heartbeat_path: None,
+            heartbeat_interval: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+            notifier: None,
+        }
+    }
+}
+
+/// What the supervisor is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KioskState {
+    /// The engine is running
+    Running,
+    /// Waiting to start a new engine after a failure
+    Restarting,
+    /// Shutting down on request
+    Stopped,
+}
+
+/// Contents of the heartbeat file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    /// When the heartbeat was written, in seconds since the Unix epoch
+    pub time: u64,
+    pub pid: u32,
+    pub state: KioskState,
+    /// Restart attempts since the supervisor started
+    pub restarts: u64,
+    /// Why the engine last failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Delay before restart attempt `attempt` (1-based), doubling up to `max`
+pub(crate) fn backoff(attempt: u32, max: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    RESTART_BASE_DELAY.saturating_mul(factor).min(max)
+}
+
+/// Restarting supervisor around one engine at a time
+struct Supervisor {
+    options: KioskOptions,
+    state: KioskState,
+    restarts: u64,
+    last_error: Option<String>,
+    last_heartbeat: Option<Instant>,
+}
+
+impl Supervisor {
+    /// Rewrite the heartbeat file if it is due, or right away with `force`
+    fn heartbeat(&mut self, force: bool) {
+        let Some(path) = &self.options.heartbeat_path else {
+            return;
+        };
+        if !force
+            && self
+                .last_heartbeat
+                .is_some_and(|t| t.elapsed() < self.options.heartbeat_interval)
+        {
+            return;
+        }
+        self.last_heartbeat = Some(Instant::now());
+
+        let heartbeat = Heartbeat {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            pid: std::process::id(),
+            state: self.state,
+            restarts: self.restarts,
+            last_error: self.last_error.clone(),
+        };
+        if let Err(e) = write_heartbeat(path, &heartbeat) {
+            warn!("Failed to write heartbeat {}: {}", path.display(), e);
+        }
+    }
+
+    fn set_state(&mut self, state: KioskState) {
+        self.state = state;
+        self.heartbeat(true);
+        self.notify();
+    }
+
+    /// Tell the notifier whether the engine is failing
+    fn notify(&mut self) {
+        if let Some(notifier) = &mut self.options.notifier {
+            notifier.update(
+                self.state == KioskState::Restarting,
+                self.last_error.as_deref(),
+            );
+        }
+    }
+
+    /// Sleep for `delay` unless asked to stop, keeping the heartbeat going
+    ///
+    /// Returns false if the stop flag was set.
+    fn wait(&mut self, delay: Duration, stop_signal: &AtomicBool) -> bool {
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            if stop_signal.load(Ordering::SeqCst) {
+                return false;
+            }
+            self.heartbeat(false);
+            self.notify();
+            thread::sleep(POLL_INTERVAL.min(deadline - Instant::now()));
+        }
+        !stop_signal.load(Ordering::SeqCst)
+    }
+
+    /// Run one engine until it fails or the stop flag is set
+    ///
+    /// Returns why the engine failed, or None after a requested stop.
+    fn run_engine(&mut self, config: EngineConfig, stop_signal: &AtomicBool) -> Option<String> {
+        let (event_tx, event_rx) = unbounded();
+        let mut engine = AudioEngine::new(config);
+        engine.set_event_channel(event_tx);
+
+        if let Err(e) = engine.start() {
+            return Some(format!("Failed to start engine: {}", e));
+        }
+        info!("Engine running");
+        self.set_state(KioskState::Running);
+
+        let failure = loop {
+            if stop_signal.load(Ordering::SeqCst) {
+                break None;
+            }
+            if let Some(reason) = fatal_event(&event_rx) {
+                break Some(reason);
+            }
+            if !engine.is_running() {
+                break Some("Engine stopped unexpectedly".to_string());
+            }
+            self.heartbeat(false);
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        if let Err(e) = engine.stop() {
+            warn!("Error stopping engine: {}", e);
+        }
+        failure
+    }
+}
+
+/// Reason of the first fatal event waiting in `events`, if any
+fn fatal_event(events: &Receiver<EngineEvent>) -> Option<String> {
+    events.try_iter().find_map(|event| match event {
+        EngineEvent::Fatal(reason) => Some(reason),
+        _ => None,
+    })
+}
+
+/// Write the heartbeat through a temporary file, so readers never see half of it
+fn write_heartbeat(path: &Path, heartbeat: &Heartbeat) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string_pretty(heartbeat)?;
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, content)?;
+    fs::rename(&temp, path)
+}
+
+/// Keep an engine running until `stop_signal` is set
+///
+/// `engine_config` is called before every start, so a fixed configuration
+/// file is picked up by the next restart. Only a set stop flag ends the loop.
+pub fn run(
+    mut engine_config: impl FnMut() -> EngineConfig,
+    options: KioskOptions,
+    stop_signal: &AtomicBool,
+) {
+    let mut supervisor = Supervisor {
+        options,
+        state: KioskState::Restarting,
+        restarts: 0,
+        last_error: None,
+        last_heartbeat: None,
+    };
+    let mut attempts = 0;
+
+    loop {
+        let config = engine_config();
+        let started = Instant::now();
+        // A panicking engine is a failure like any other
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            supervisor.run_engine(config, stop_signal)
+        }))
+        .unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Some(format!("Engine panicked: {}", message))
+        });
+
+        let Some(reason) = outcome else {
+            break;
+        };
+        error!("{}", reason);
+
+        if started.elapsed() >= RESTART_RESET_AFTER {
+            attempts = 0;
+        }
+        attempts += 1;
+        let delay = backoff(attempts, supervisor.options.max_backoff);
+        info!("Restarting engine in {:?} (attempt {})", delay, attempts);
+
+        supervisor.last_error = Some(reason);
+        supervisor.set_state(KioskState::Restarting);
+        if !supervisor.wait(delay, stop_signal) {
+            break;
+        }
+        supervisor.restarts += 1;
+    }
+
+    info!("Kiosk supervisor stopped");
+    supervisor.set_state(KioskState::Stopped);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let max = Duration::from_secs(30);
+        assert_eq!(backoff(1, max), Duration::from_secs(1));
+        assert_eq!(backoff(3, max), Duration::from_secs(4));
+        assert_eq!(backoff(6, max), Duration::from_secs(30));
+        assert_eq!(backoff(1000, max), Duration::from_secs(30));
+    }
+}
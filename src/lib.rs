@@ -18,11 +18,45 @@
 //!
 //! engine.stop().expect("Failed to stop engine");
 //! ```
+//!
+//! Hosts without a loop of their own can use
+//! [`AudioEngine::run_blocking`](audio::AudioEngine::run_blocking), which runs
+//! the engine until a stop flag is set (see `examples/embed.rs`).
+//!
+//! # Features
+//!
+//! - `cli` (default) - the `wemux` command line tool and its terminal UI
+//! - `tray` - the `wemux-tray` system tray app
+//! - `service` - the `wemux-service` Windows service
+//! - `metrics` - Prometheus metrics endpoint
+//! - `ffi` - C API and header
+//!
+//! Embedding the engine only needs the library:
+//! `wemux = { version = "0.2", default-features = false }` leaves out the
+//! command line, tray and service dependencies. The file source is part of
+//! the engine, so the library still decodes FLAC, MP3, Ogg Vorbis and WAV
+//! (with `symphonia`).
 
 pub mod audio;
+pub mod bench;
+pub mod calibrate;
 pub mod config;
+pub mod daemon;
 pub mod device;
+pub mod doctor;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod health;
+pub mod ipc;
+pub mod kiosk;
+pub mod logging;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "cli")]
+pub mod monitor;
+pub mod notify;
+pub mod remote;
 pub mod service;
 pub mod sync;
 
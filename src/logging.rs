@@ -0,0 +1,353 @@
+//! Log files that rotate instead of growing forever
+//!
+//! The current log is always written to the configured path. On rotation it
+//! is renamed to `<path>.1`, an older `<path>.1` becomes `<path>.2` and so on;
+//! files beyond the retention count are deleted. An HTPC that runs wemux for
+//! months then keeps a bounded amount of logs.
+//!
+//! Processes that take requests (the service and the tray) also keep a handle
+//! on their log filter, so the level can be raised to capture a glitch as it
+//! happens instead of after a restart. The tray also logs into a [`LogRing`]
+//! of the last lines, which it shows without reading the file back.
+
+use crate::error::{Result, WemuxError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::info;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Rotated log files kept by default
+pub const DEFAULT_LOG_KEEP: usize = 7;
+
+/// Size a log file may reach with [`LogRotation::Size`] by default
+pub const DEFAULT_LOG_MAX_SIZE_MB: u64 = 10;
+
+/// Lines [`LogRing::global`] keeps
+pub const LOG_RING_LINES: usize = 500;
+
+/// Filter of this process's log, once set up with [`reloadable_filter`]
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Least severe messages written to the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Failures only
+    Error,
+    /// Failures and problems wemux worked around
+    Warn,
+    /// Also what the engine and its devices are doing
+    Info,
+    /// Also details for tracking down a glitch
+    Debug,
+    /// Everything, down to individual buffer waits
+    Trace,
+}
+
+impl LogLevel {
+    /// Name used in configuration and filter directives
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Log filter layer whose level [`set_log_level`] can change later
+///
+/// Must be the first layer on `tracing_subscriber::registry()`. Only the
+/// first filter set up in a process can be changed.
+pub fn reloadable_filter(filter: EnvFilter) -> reload::Layer<EnvFilter, Registry> {
+    let (layer, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(handle);
+    layer
+}
+
+/// Current level of this process's log, if it can be changed
+pub fn log_level() -> Option<LogLevel> {
+    let hint = FILTER
+        .get()?
+        .with_current(|filter| filter.max_level_hint())
+        .ok()?;
+    Some(match hint? {
+        LevelFilter::OFF | LevelFilter::ERROR => LogLevel::Error,
+        LevelFilter::WARN => LogLevel::Warn,
+        LevelFilter::INFO => LogLevel::Info,
+        LevelFilter::DEBUG => LogLevel::Debug,
+        _ => LogLevel::Trace,
+    })
+}
+
+/// Log messages from `level` up from now on, replacing any `RUST_LOG` directives
+pub fn set_log_level(level: LogLevel) -> Result<()> {
+    let handle = FILTER
+        .get()
+        .ok_or_else(|| WemuxError::Logging("this process keeps no log".to_string()))?;
+    handle
+        .reload(EnvFilter::new(level.as_str()))
+        .map_err(|e| WemuxError::Logging(e.to_string()))?;
+    info!("Log level set to {}", level);
+    Ok(())
+}
+
+/// When the log file is rotated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Append to the same file forever
+    Never,
+    /// Start a new file after local midnight
+    #[default]
+    Daily,
+    /// Start a new file when the current one would exceed the size limit
+    Size,
+}
+
+/// How a log file is rotated and how many old files are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// When to rotate
+    pub rotation: LogRotation,
+    /// Size limit in bytes for [`LogRotation::Size`]
+    pub max_size: u64,
+    /// Rotated files kept next to the current one (0 = discard on rotation)
+    pub keep: usize,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            rotation: LogRotation::default(),
+            max_size: DEFAULT_LOG_MAX_SIZE_MB * 1024 * 1024,
+            keep: DEFAULT_LOG_KEEP,
+        }
+    }
+}
+
+/// Log file writer that rotates according to a [`RotationPolicy`]
+///
+/// Rotation only happens between writes, and tracing writes one line at a
+/// time, so a line is never split across files.
+pub struct RollingFile {
+    path: PathBuf,
+    policy: RotationPolicy,
+    file: File,
+    /// Bytes in the current file
+    size: u64,
+    /// Local date the current file was started on
+    day: u32,
+}
+
+impl RollingFile {
+    /// Open the log file at `path`, appending to an existing one
+    pub fn open(path: impl Into<PathBuf>, policy: RotationPolicy) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            policy,
+            file,
+            size,
+            day: today(),
+        })
+    }
+
+    /// Path of the `n`th rotated file
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        match self.policy.rotation {
+            LogRotation::Never => false,
+            LogRotation::Daily => today() != self.day,
+            LogRotation::Size => self.size + incoming as u64 > self.policy.max_size,
+        }
+    }
+
+    /// Move the current file to `<path>.1`, shifting older ones, and start a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.policy.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            match fs::remove_file(self.rotated(self.policy.keep)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            for n in (1..self.policy.keep).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        self.day = today();
+        Ok(())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.needs_rotation(buf.len()) {
+            // Logging goes on in the current file if rotation fails (e.g. a
+            // viewer holds an old file open); the next line tries again
+            let _ = self.rotate();
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// The last lines of a log, kept in memory
+///
+/// A writer for a tracing layer: `fmt::layer().with_writer(LogRing::global)`.
+/// Each write is split into lines; the oldest are dropped beyond the capacity.
+pub struct LogRing {
+    capacity: usize,
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl LogRing {
+    /// Ring keeping the last `capacity` lines
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Ring of this process, keeping the last [`LOG_RING_LINES`] lines
+    pub fn global() -> &'static LogRing {
+        static RING: OnceLock<LogRing> = OnceLock::new();
+        RING.get_or_init(|| LogRing::new(LOG_RING_LINES))
+    }
+
+    /// Lines kept, oldest first
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().iter().cloned().collect()
+    }
+}
+
+impl Write for &LogRing {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        let mut lines = self.lines.lock();
+        for line in text.lines() {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Local date as `YYYYMMDD`
+fn today() -> u32 {
+    let now = unsafe { windows::Win32::System::SystemInformation::GetLocalTime() };
+    now.wYear as u32 * 10_000 + now.wMonth as u32 * 100 + now.wDay as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_log_level() {
+        use tracing_subscriber::prelude::*;
+
+        let subscriber =
+            tracing_subscriber::registry().with(reloadable_filter(EnvFilter::new("info")));
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(log_level(), Some(LogLevel::Info));
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+
+            set_log_level(LogLevel::Debug).unwrap();
+            assert_eq!(log_level(), Some(LogLevel::Debug));
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+        });
+    }
+
+    #[test]
+    fn test_ring_keeps_last_lines() {
+        let ring = LogRing::new(3);
+        for line in ["one\n", "two\nthree\n", "four\n"] {
+            (&ring).write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(ring.lines(), ["two", "three", "four"]);
+    }
+
+    #[test]
+    fn test_size_rotation_keeps_newest_files() {
+        let dir = std::env::temp_dir().join(format!("wemux-log-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wemux.log");
+        let policy = RotationPolicy {
+            rotation: LogRotation::Size,
+            max_size: 12,
+            keep: 2,
+        };
+
+        let mut log = RollingFile::open(&path, policy).unwrap();
+        for line in ["line 1\n", "line 2\n", "line 3\n", "line 4\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        log.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line 4\n");
+        assert_eq!(fs::read_to_string(log.rotated(1)).unwrap(), "line 3\n");
+        assert_eq!(fs::read_to_string(log.rotated(2)).unwrap(), "line 2\n");
+        assert!(!log.rotated(3).exists());
+
+        // Reopening appends to the current file
+        drop(log);
+        let mut log = RollingFile::open(&path, RotationPolicy::default()).unwrap();
+        log.write_all(b"line 5\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line 4\nline 5\n");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
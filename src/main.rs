@@ -2,37 +2,126 @@
 
 use anyhow::Result;
 use clap::Parser;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tracing::error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, warn};
 use tracing_subscriber::EnvFilter;
 
-use wemux::audio::{AudioEngine, EngineConfig};
-use wemux::config::{Args, Command, ServiceAction};
-use wemux::device::DeviceEnumerator;
+use wemux::audio::{
+    internal_format, Announcement, AudioEngine, AudioFormat, EngineConfig, FallbackSource,
+    MixSource, SampleEncoding, SourceType, StartPlan, VolumeMode,
+};
+use wemux::config::{Args, Backups, Command, ConfigAction, DeviceAction, ServiceAction};
+use wemux::daemon::{self, PidFile};
+use wemux::device::{
+    best_match, device_changes, find_device, DeviceAliases, DeviceChange, DeviceEnumerator,
+    DeviceFormat, DeviceInfo, DeviceMonitor, MatchRank,
+};
+use wemux::doctor::Status;
+use wemux::health::{self, DeviceHealth};
+use wemux::ipc::{EngineClient, StatusReport, DAEMON_PIPE_NAME, SERVICE_PIPE_NAME, TRAY_PIPE_NAME};
+use wemux::kiosk::KioskOptions;
+use wemux::logging::{LogLevel, RollingFile};
 use wemux::service::{
-    config::ServiceConfig, SERVICE_DESCRIPTION, SERVICE_DISPLAY_NAME, SERVICE_NAME,
+    config::{ServiceConfig, ServiceMode},
+    schedule::LocalTime,
+    template, ServiceInstance, SERVICE_DESCRIPTION,
 };
+use wemux::WemuxError;
+
+/// Time `start --daemon` waits for the background copy to answer
+const DAEMON_START_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Time `wemux list --watch` lets a burst of device events settle
+const WATCH_SETTLE: Duration = Duration::from_millis(500);
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
     // Initialize logging
     init_logging(&args)?;
+    let log_given = args.log.is_some();
 
     // Execute command
     match args.command.unwrap_or_default() {
         Command::List {
             hdmi_only,
             show_ids,
-        } => cmd_list(hdmi_only, show_ids),
+            detailed,
+            watch,
+        } => cmd_list(hdmi_only, show_ids, detailed, watch),
+        Command::Start { daemon: true, .. } => cmd_daemon(log_given),
         Command::Start {
             devices,
             exclude,
             buffer,
             source,
-        } => cmd_start(devices, exclude, buffer, source),
+            source_type,
+            exclude_apps,
+            mix,
+            fallback,
+            announce,
+            follow_source,
+            performance,
+            dry_run,
+            detached,
+            ..
+        } => cmd_start(
+            devices,
+            exclude,
+            buffer,
+            source,
+            source_type,
+            exclude_apps,
+            mix,
+            fallback,
+            announce,
+            follow_source,
+            performance,
+            dry_run,
+            detached,
+        ),
+        Command::Stop => cmd_stop(false),
+        Command::Restart => cmd_stop(true),
         Command::Info { device_id } => cmd_info(&device_id),
+        Command::Monitor {
+            interval,
+            host,
+            token,
+        } => cmd_monitor(interval, host, token),
+        Command::Stats {
+            device,
+            since,
+            name,
+        } => cmd_stats(device, since, &name),
+        Command::Doctor => cmd_doctor(),
+        Command::Bench {
+            outputs,
+            buffer,
+            seconds,
+        } => cmd_bench(outputs, buffer, seconds),
+        Command::Calibrate {
+            devices,
+            input,
+            play,
+            file,
+            save,
+        } => cmd_calibrate(devices, input, play, file, save),
+        Command::Test { devices, seconds } => cmd_test(devices, seconds),
+        Command::Kiosk {
+            config,
+            heartbeat,
+            heartbeat_interval,
+            max_backoff,
+        } => cmd_kiosk(config, heartbeat, heartbeat_interval, max_backoff),
+        Command::Device { action } => cmd_device(action),
+        Command::Volume { percent, mode } => cmd_volume(percent, mode),
+        Command::LogLevel { level } => cmd_log_level(level),
+        Command::Config { action } => cmd_config(action),
         Command::Service { action } => cmd_service(action),
     }
 }
@@ -48,8 +137,11 @@ fn init_logging(args: &Args) -> Result<()> {
         .with_target(false);
 
     if let Some(log_file) = &args.log {
-        let file = std::fs::File::create(log_file)?;
-        subscriber.with_writer(file).init();
+        let file = RollingFile::open(log_file, args.log_policy())?;
+        subscriber
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .init();
     } else {
         subscriber.init();
     }
@@ -58,31 +150,86 @@ fn init_logging(args: &Args) -> Result<()> {
 }
 
 /// List available audio devices
-fn cmd_list(hdmi_only: bool, show_ids: bool) -> Result<()> {
+/// Print what `start` would do
+fn print_start_plan(plan: &StartPlan) {
+    if let Some(zone) = &plan.zone {
+        println!("Zone {}:", zone);
+    }
+    println!("Source: {}", plan.source);
+    println!(
+        "Buffer: {}ms ring buffer, {}ms chunks, {}ms prefill",
+        plan.buffers.ring_buffer_ms, plan.buffers.chunk_ms, plan.buffers.prefill_ms
+    );
+    println!("Outputs:");
+    for (i, output) in plan.outputs.iter().enumerate() {
+        print!("  {}. {}", i + 1, output.name);
+        if output.master {
+            print!(" (master)");
+        }
+        if output.delay_ms > 0 {
+            print!(" +{}ms", output.delay_ms);
+        }
+        if let Some(reason) = &output.paused {
+            print!(" [paused: {}]", reason);
+        }
+        println!("\n     ID: {}", output.id);
+    }
+    println!();
+}
+
+fn cmd_list(hdmi_only: bool, show_ids: bool, detailed: bool, watch: bool) -> Result<()> {
     let enumerator = DeviceEnumerator::new()?;
+    let devices = list_devices(&enumerator, hdmi_only)?;
+    print_device_list(&enumerator, &devices, hdmi_only, show_ids, detailed);
+    if watch {
+        watch_devices(&enumerator, devices, hdmi_only)?;
+    }
+    Ok(())
+}
 
-    let devices = if hdmi_only {
-        enumerator.enumerate_hdmi_devices().unwrap_or_default()
-    } else {
-        enumerator.enumerate_all_devices()?
-    };
+/// Devices `wemux list` shows
+fn list_devices(enumerator: &DeviceEnumerator, hdmi_only: bool) -> Result<Vec<DeviceInfo>> {
+    if hdmi_only {
+        return Ok(enumerator.enumerate_hdmi_devices().unwrap_or_default());
+    }
+    Ok(enumerator.enumerate_all_devices()?)
+}
 
+fn print_device_list(
+    enumerator: &DeviceEnumerator,
+    devices: &[DeviceInfo],
+    hdmi_only: bool,
+    show_ids: bool,
+    detailed: bool,
+) {
     if devices.is_empty() {
         if hdmi_only {
             println!("No HDMI audio devices found.");
         } else {
             println!("No audio devices found.");
         }
-        return Ok(());
+        return;
     }
 
     println!("Available audio devices:\n");
 
+    let all_devices = enumerator.enumerate_all_devices().unwrap_or_default();
+    let aliases = load_aliases().assignments(&all_devices);
+
+    if detailed {
+        print_device_table(enumerator, devices, &all_devices, &aliases, show_ids);
+        return;
+    }
+
     for (i, device) in devices.iter().enumerate() {
         let hdmi_tag = if device.is_hdmi { " [HDMI]" } else { "" };
         let default_tag = if device.is_default { " (default)" } else { "" };
 
-        print!("  {}. {}{}{}", i + 1, device.name, hdmi_tag, default_tag);
+        match aliases.get(&device.id) {
+            Some(alias) => print!("  {}. {} = {}", i + 1, alias, device.name),
+            None => print!("  {}. {}", i + 1, device.name),
+        }
+        print!("{}{}", hdmi_tag, default_tag);
 
         if show_ids {
             println!("\n     ID: {}", device.id);
@@ -92,38 +239,225 @@ fn cmd_list(hdmi_only: bool, show_ids: bool) -> Result<()> {
     }
 
     println!();
+}
+
+/// Print devices appearing, going away, renamed or becoming the default as
+/// the device monitor reports them, until Ctrl+C
+fn watch_devices(
+    enumerator: &DeviceEnumerator,
+    mut known: Vec<DeviceInfo>,
+    hdmi_only: bool,
+) -> Result<()> {
+    let (event_tx, event_rx) = crossbeam_channel::unbounded();
+    let _monitor = DeviceMonitor::new(event_tx)?;
+    println!("Watching for device changes. Press Ctrl+C to stop.\n");
+
+    while event_rx.recv().is_ok() {
+        // Plugging in a display sends a burst of events; list once it settles
+        std::thread::sleep(WATCH_SETTLE);
+        while event_rx.try_recv().is_ok() {}
+
+        let current = list_devices(enumerator, hdmi_only)?;
+        let time = LocalTime::now().time;
+        for change in device_changes(&known, &current) {
+            let tag = |d: &DeviceInfo| if d.is_hdmi { " [HDMI]" } else { "" };
+            match change {
+                DeviceChange::Added(d) => {
+                    println!("[{}] + {}{}", time, d.name, tag(&d));
+                    println!("        ID: {}", d.id);
+                }
+                DeviceChange::Removed(d) => println!("[{}] - {}{}", time, d.name, tag(&d)),
+                DeviceChange::Renamed { from, device } => {
+                    println!("[{}] ~ {} is now {}", time, from, device.name)
+                }
+                DeviceChange::DefaultChanged(d) => println!("[{}] * Default: {}", time, d.name),
+            }
+        }
+        known = current;
+    }
     Ok(())
 }
 
+/// Print devices as a table with their mix format and latency class
+///
+/// Formats that differ from the one the default output's audio is passed on
+/// in, in a way wemux can't convert, are marked: only float audio is
+/// resampled and mapped to each output's speakers.
+fn print_device_table(
+    enumerator: &DeviceEnumerator,
+    devices: &[DeviceInfo],
+    all_devices: &[DeviceInfo],
+    aliases: &HashMap<String, String>,
+    show_ids: bool,
+) {
+    let labels: Vec<String> = devices
+        .iter()
+        .map(|device| {
+            let mut label = match aliases.get(&device.id) {
+                Some(alias) => format!("{} = {}", alias, device.name),
+                None => device.name.clone(),
+            };
+            if device.is_hdmi {
+                label.push_str(" [HDMI]");
+            }
+            if device.is_default {
+                label.push_str(" (default)");
+            }
+            label
+        })
+        .collect();
+    let width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .chain(std::iter::once("Device".len()))
+        .max()
+        .unwrap_or_default();
+
+    let formats: Vec<Option<DeviceFormat>> = devices
+        .iter()
+        .map(|device| enumerator.device_format(device).ok())
+        .collect();
+    let source_format = match devices.iter().position(|d| d.is_default) {
+        Some(i) => formats[i].clone(),
+        None => all_devices
+            .iter()
+            .find(|d| d.is_default)
+            .and_then(|d| enumerator.device_format(d).ok()),
+    }
+    .map(|f| internal_format(&f.format));
+
+    println!(
+        "  {:>3}  {:<width$}  {:>9}  {:>2}  {:>4}  {:<12}  {:>7}",
+        "#", "Device", "Rate", "Ch", "Bits", "Latency", "Period"
+    );
+    let converts = |f: &AudioFormat| f.encoding == SampleEncoding::Float && f.bits_per_sample == 32;
+    let mut mismatch = false;
+    for (i, (label, format)) in labels.iter().zip(&formats).enumerate() {
+        match format {
+            Some(DeviceFormat {
+                format,
+                capabilities,
+            }) => {
+                let differs = source_format.as_ref().is_some_and(|source| {
+                    !(converts(source) && converts(format))
+                        && (source.sample_rate, source.channels, source.bits_per_sample)
+                            != (format.sample_rate, format.channels, format.bits_per_sample)
+                });
+                mismatch |= differs;
+                println!(
+                    "  {:>3}  {:<width$}  {:>9}  {:>2}  {:>4}  {:<12}  {:>5.2}ms{}",
+                    format!("{}.", i + 1),
+                    label,
+                    format!("{}Hz", format.sample_rate),
+                    format.channels,
+                    format.bits_per_sample,
+                    capabilities.latency_class.to_string(),
+                    capabilities.default_period_ms(),
+                    if differs { " *" } else { "" }
+                );
+            }
+            None => println!(
+                "  {:>3}  {:<width$}  (format unavailable)",
+                format!("{}.", i + 1),
+                label
+            ),
+        }
+        if show_ids {
+            println!("       ID: {}", devices[i].id);
+        }
+    }
+
+    if let (true, Some(source)) = (mismatch, &source_format) {
+        println!(
+            "\n  * Differs from the format wemux passes the default output's audio on in ({}),\n    and only float audio can be converted.",
+            source
+        );
+        println!("    Set the device to the same format in the Windows sound settings.");
+    }
+    println!();
+}
+
 /// Start audio synchronization
+#[allow(clippy::too_many_arguments)]
 fn cmd_start(
     devices: Option<Vec<String>>,
     exclude: Option<Vec<String>>,
     buffer_ms: u32,
     source: Option<String>,
+    source_type: SourceType,
+    exclude_apps: Vec<String>,
+    mix: Vec<MixSource>,
+    fallback: Option<FallbackSource>,
+    announce: Option<Announcement>,
+    follow_source: bool,
+    performance: bool,
+    dry_run: bool,
+    detached: bool,
 ) -> Result<()> {
     println!("wemux - Windows Multi-HDMI Audio Sync\n");
 
+    let file_config = ServiceConfig::load_default().unwrap_or_default();
+    // Devices given on the command line replace the configured zones
+    let zones = if devices.is_none() {
+        file_config.zones
+    } else {
+        Vec::new()
+    };
     let config = EngineConfig {
         buffer_ms,
+        device_buffer_ms: file_config.device_buffer_ms.into_iter().collect(),
         device_ids: devices,
         exclude_ids: exclude,
         source_device_id: source,
+        source_type,
+        exclude_apps: if exclude_apps.is_empty() {
+            file_config.exclude_apps
+        } else {
+            exclude_apps
+        },
         paused_device_ids: None,
         use_all_devices: false, // CLI uses HDMI devices only
+        exclusive: true,
+        aliases: file_config.aliases,
+        mix_sources: mix,
+        fallback_source: fallback,
+        announcement: announce,
+        follow_source,
+        drift_threshold_ms: file_config.drift_threshold_ms,
+        max_correction_ms: file_config.max_correction_ms,
+        performance_capture: performance,
+        render_priority: file_config.render_priority,
+        render_cores: file_config.render_cores.into_iter().collect(),
+        equalizers: HashMap::new(),
+        delays: file_config.delays.into_iter().collect(),
+        dsp: file_config.dsp.into_iter().collect(),
+        bluetooth_latency_ms: file_config.bluetooth_latency_ms,
+        pause_fade_ms: file_config.pause_fade_ms,
+        volume_mode: file_config.volume_mode,
+        mirror_volume: file_config.mirror_volume,
+        zones,
+        suppress_duplicates: file_config.suppress_duplicates,
+        allow_duplicates: file_config.allow_duplicates,
     };
 
     let mut engine = AudioEngine::new(config);
 
+    if dry_run {
+        for plan in engine.plan()? {
+            print_start_plan(&plan);
+        }
+        return Ok(());
+    }
+
     // Setup Ctrl+C handler
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
+    let stopping = Arc::new(AtomicBool::new(false));
+    let s = stopping.clone();
 
     #[cfg(windows)]
     {
         let _ = ctrlc::set_handler(move || {
             println!("\nReceived Ctrl+C, stopping...");
-            r.store(false, Ordering::SeqCst);
+            s.store(true, Ordering::SeqCst);
         });
     }
 
@@ -137,55 +471,768 @@ fn cmd_start(
         }
         Err(e) => {
             error!("Failed to start engine: {}", e);
+            if matches!(e, WemuxError::EngineInUse) {
+                // A user service runs as a per-user copy of the template
+                let service = ServiceInstance::default()
+                    .user_instances()
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| ServiceInstance::default().name);
+                println!("Control the running engine from the tray, or stop it first");
+                println!(
+                    "(run 'net stop {}' as Administrator if it is the wemux service).",
+                    service
+                );
+            }
             return Err(e.into());
         }
     }
 
-    // Wait for Ctrl+C
-    while running.load(Ordering::SeqCst) && engine.is_running() {
-        std::thread::sleep(std::time::Duration::from_millis(100));
+    if detached {
+        let _pid_file = daemon::default_pid_path()
+            .map(PidFile::create)
+            .transpose()?;
+        daemon::run(&mut engine, &stopping)?;
+        return Ok(());
     }
 
-    // Stop the engine
-    engine.stop()?;
+    // Run until Ctrl+C, then stop the engine
+    engine.run_blocking(&stopping)?;
     println!("Stopped.");
 
     Ok(())
 }
 
+/// Start a copy of this command in the background and wait until it runs
+fn cmd_daemon(log_given: bool) -> Result<()> {
+    let client = EngineClient::new(DAEMON_PIPE_NAME);
+    if client.status().is_ok() {
+        let pid = daemon::default_pid_path().and_then(|path| PidFile::read(&path));
+        return Err(anyhow::anyhow!(
+            "A wemux daemon is already running{}",
+            pid.map(|pid| format!(" (PID {})", pid)).unwrap_or_default()
+        ));
+    }
+
+    // Same arguments, but as the background copy
+    let mut child_args: Vec<OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--daemon")
+        .collect();
+    child_args.push(daemon::DETACHED_ARG.into());
+    let log_path = if log_given {
+        None
+    } else {
+        daemon::default_log_path()
+    };
+    if let Some(path) = &log_path {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        child_args.push("--log".into());
+        child_args.push(path.into());
+    }
+
+    let mut child = daemon::spawn_detached(child_args)?;
+    let started = std::time::Instant::now();
+    while started.elapsed() < DAEMON_START_TIMEOUT {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow::anyhow!(
+                "The daemon exited during startup ({}){}",
+                status,
+                log_path
+                    .as_ref()
+                    .map(|path| format!(", see {}", path.display()))
+                    .unwrap_or_default()
+            ));
+        }
+        if client.status().is_ok() {
+            println!("wemux daemon running (PID {})", child.id());
+            if let Some(path) = &log_path {
+                println!("Log: {}", path.display());
+            }
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Err(anyhow::anyhow!(
+        "The daemon (PID {}) did not answer within {} seconds",
+        child.id(),
+        DAEMON_START_TIMEOUT.as_secs()
+    ))
+}
+
 /// Show detailed device information
 fn cmd_info(device_id: &str) -> Result<()> {
     let enumerator = DeviceEnumerator::new()?;
+    let aliases = load_aliases();
+
+    let dev = match enumerator.find(device_id, &aliases) {
+        Ok(dev) => dev,
+        Err(WemuxError::DeviceNotFound(_)) => {
+            println!("Device not found: {}", device_id);
+            println!("\nUse 'wemux list --show-ids' to see available devices.");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
     let devices = enumerator.enumerate_all_devices()?;
+    let alias = aliases.assignments(&devices).remove(&dev.id);
+    let container_id = dev.container_id.as_deref().unwrap_or("");
+
+    println!("Device Information:\n");
+    println!("  Name:     {}", dev.name);
+    if let Some(alias) = &alias {
+        println!("  Alias:    {}", alias);
+    }
+    println!("  ID:       {}", dev.id);
+    println!("  Hardware: {}", container_id);
+    println!("  HDMI:     {}", if dev.is_hdmi { "Yes" } else { "No" });
+    println!("  Default:  {}", if dev.is_default { "Yes" } else { "No" });
+
+    println!("\nTo alias this device, add to the config file:\n");
+    println!("  [aliases.{}]", alias.as_deref().unwrap_or("my-device"));
+    println!("  id = \"{}\"", dev.id);
+    println!("  name = \"{}\"", dev.name);
+    if !container_id.is_empty() {
+        println!("  container_id = \"{}\"", container_id);
+    }
+
+    Ok(())
+}
+
+/// Show a live dashboard of the service's engine, local or on `host`
+fn cmd_monitor(interval_ms: u64, host: Option<String>, token: Option<String>) -> Result<()> {
+    let interval = std::time::Duration::from_millis(interval_ms.max(50));
+    let client = match host {
+        Some(host) => EngineClient::remote(host, token.unwrap_or_default()),
+        None => EngineClient::new(SERVICE_PIPE_NAME),
+    };
+    wemux::monitor::run(client, interval)?;
+    Ok(())
+}
+
+/// Print the health history the service `name` recorded over `since`
+fn cmd_stats(device: Option<String>, since: Duration, name: &str) -> Result<()> {
+    let instance = ServiceInstance::new(name, None)?;
+    let path = instance
+        .health_path()
+        .ok_or_else(|| anyhow::anyhow!("PROGRAMDATA is not set"))?;
+    let records = match health::read_records(&path) {
+        Ok(records) => records,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("No health history at {}", path.display());
+            println!("\nThe wemux service records it while running (health_history_days).");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let summary = health::summarize(&records, health::unix_now().saturating_sub(since.as_secs()));
+
+    let shown: Vec<&DeviceHealth> = match &device {
+        Some(query) => {
+            let found = best_match(
+                query,
+                &summary,
+                |d| MatchRank::of(query, &d.device, &d.name, d.alias.as_deref()),
+                |d| d.display_name().to_string(),
+            )
+            .map_err(|e| match e {
+                WemuxError::DeviceNotFound(_) => anyhow::anyhow!(
+                    "No health history for {} in the last {}",
+                    query,
+                    format_period(since)
+                ),
+                e => e.into(),
+            })?;
+            vec![found]
+        }
+        None => summary.iter().collect(),
+    };
+    if shown.is_empty() {
+        println!("No health history in the last {}", format_period(since));
+        return Ok(());
+    }
+
+    println!("Output health over the last {}:\n", format_period(since));
+    println!(
+        "  {:<32} {:>8} {:>12} {:>11} {:>10}",
+        "Output", "Played", "Underruns/h", "Reconnects", "Avg drift"
+    );
+    for health in shown {
+        println!(
+            "  {:<32} {:>7.1}h {:>12.1} {:>11} {:>8.1}ms",
+            health.display_name(),
+            health.seconds as f64 / 3600.0,
+            health.underruns_per_hour(),
+            health.reconnects,
+            health.drift_ms
+        );
+        if device.is_some() {
+            println!("\n  ID:        {}", health.device);
+            println!("  Underruns: {}", health.underruns);
+        }
+    }
+    Ok(())
+}
+
+/// A period as the largest whole unit, e.g. "24h" or "90m"
+fn format_period(period: Duration) -> String {
+    let secs = period.as_secs();
+    match secs {
+        s if s >= 86400 && s % 86400 == 0 => format!("{}d", s / 86400),
+        s if s >= 3600 && s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s >= 60 && s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Processes that can host an engine and their pipes; the service hosts the
+/// engine whenever it runs, so it comes first
+const ENGINE_HOSTS: [(&str, &str); 3] = [
+    ("service", SERVICE_PIPE_NAME),
+    ("daemon", DAEMON_PIPE_NAME),
+    ("tray", TRAY_PIPE_NAME),
+];
+
+/// The first of the service, daemon and tray that answers, with a client
+/// for it and its status
+fn find_running_engine() -> Result<(&'static str, EngineClient, StatusReport)> {
+    ENGINE_HOSTS
+        .into_iter()
+        .find_map(|(name, pipe)| {
+            let client = EngineClient::new(pipe);
+            let report = client.status().ok()?;
+            Some((name, client, report))
+        })
+        .ok_or_else(|| anyhow::anyhow!("No running wemux service, daemon or tray found"))
+}
+
+/// Stop or restart the engine of the running service, daemon or tray
+fn cmd_stop(restart: bool) -> Result<()> {
+    let (name, client, _) = find_running_engine()?;
+
+    if restart {
+        client.restart()?;
+        println!("Restarted the engine of the {}", name);
+    } else {
+        client.stop()?;
+        // The daemon exits once its engine stopped
+        if name == "daemon" {
+            println!("Stopped the daemon");
+        } else {
+            println!("Stopped the engine of the {}", name);
+        }
+    }
+    Ok(())
+}
+
+/// Pause, resume or toggle an output of the running service, daemon or tray
+fn cmd_device(action: DeviceAction) -> Result<()> {
+    let (_, client, report) = find_running_engine()?;
+
+    let (DeviceAction::Pause { device: query }
+    | DeviceAction::Resume { device: query }
+    | DeviceAction::Toggle { device: query }) = &action
+    else {
+        print_device_status(&report);
+        return Ok(());
+    };
+    let device = best_match(
+        query,
+        &report.devices,
+        |d| MatchRank::of(query, &d.id, &d.name, d.alias.as_deref()),
+        |d| d.alias.clone().unwrap_or_else(|| d.name.clone()),
+    )
+    .map_err(|e| match e {
+        WemuxError::DeviceNotFound(_) => anyhow::anyhow!(
+            "Device not found: {} (not an output of the running engine)",
+            query
+        ),
+        e => e.into(),
+    })?;
+
+    let pause = match action {
+        DeviceAction::Pause { .. } => true,
+        DeviceAction::Resume { .. } => false,
+        DeviceAction::Toggle { .. } => !device.is_paused,
+        DeviceAction::Status => unreachable!("status is printed above"),
+    };
+    if pause {
+        client.pause_device(&device.id)?;
+        println!("Paused {}", device.display_name());
+    } else {
+        client.resume_device(&device.id)?;
+        println!("Resumed {}", device.display_name());
+    }
+    Ok(())
+}
+
+/// Show or set the mirror volume and volume mode of the running service, daemon or tray
+fn cmd_volume(percent: Option<u8>, mode: Option<VolumeMode>) -> Result<()> {
+    let (name, client, _) = find_running_engine()?;
+
+    if percent.is_none() && mode.is_none() {
+        // The tray doesn't answer stats; its settings hold the volume
+        let stats = client.stats().map_err(|_| {
+            anyhow::anyhow!(
+                "The {} doesn't report its volume; see its Volume menu",
+                name
+            )
+        })?;
+        println!(
+            "Volume mode: {}\nMirror volume: {:.0}%{}",
+            stats.volume_mode,
+            stats.mirror_volume * 100.0,
+            if stats.muted { " (muted)" } else { "" }
+        );
+        return Ok(());
+    }
+
+    if let Some(mode) = mode {
+        client.set_volume_mode(mode)?;
+        println!("Volume mode of the {} set to {}", name, mode);
+    }
+    if let Some(percent) = percent {
+        client.set_mirror_volume(percent as f32 / 100.0)?;
+        println!("Mirror volume of the {} set to {}%", name, percent);
+    }
+    Ok(())
+}
+
+/// Change the log level of the running service, daemon and tray
+fn cmd_log_level(level: LogLevel) -> Result<()> {
+    let mut reached = false;
+    for (name, pipe) in ENGINE_HOSTS {
+        let client = EngineClient::new(pipe);
+        if client.status().is_err() {
+            continue;
+        }
+        reached = true;
+        match client.set_log_level(level) {
+            Ok(()) => println!("Log level of the {} set to {}", name, level),
+            Err(e) => println!("Log level of the {} unchanged: {}", name, e),
+        }
+    }
+    if !reached {
+        return Err(anyhow::anyhow!(
+            "No running wemux service, daemon or tray found"
+        ));
+    }
+    Ok(())
+}
+
+/// Print the outputs of a running engine with their timing
+fn print_device_status(report: &StatusReport) {
+    println!(
+        "Engine: {}{}{}\n",
+        if report.running { "running" } else { "stopped" },
+        if report.paused_all { " (paused)" } else { "" },
+        if report.muted { " (muted)" } else { "" }
+    );
+    if report.devices.is_empty() {
+        println!("No outputs");
+        return;
+    }
+
+    let limiting = report.limiting_device().map(|d| d.id.as_str());
+    for device in &report.devices {
+        let inactive = device.inactive.map(|state| state.to_string());
+        let state = if let Some(inactive) = &inactive {
+            inactive.as_str()
+        } else if device.is_system_default {
+            "default output"
+        } else if device.is_capture_source {
+            "capture source"
+        } else if device.is_paused && device.duplicate_of.is_some() {
+            "paused, duplicate output"
+        } else if device.is_paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        println!("{} [{}]", device.display_name(), state);
+        match &device.latency {
+            Some(latency) => {
+                let caps = &latency.capabilities;
+                println!(
+                    "  Buffer: {}ms, period {:.1}ms (min {:.1}ms), {}{}",
+                    latency.buffer_ms,
+                    caps.default_period_ms(),
+                    caps.min_period_ms(),
+                    caps.latency_class,
+                    if limiting == Some(device.id.as_str()) {
+                        " - sets the latency"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            None => println!("  Not opened"),
+        }
+        // What the display's lip-sync setting should delay the picture by
+        if let Some(ms) = device.end_to_end_ms {
+            println!("  End-to-end latency: {}ms (capture to playback)", ms);
+        }
+    }
+}
+
+/// Run environment and device diagnostics
+fn cmd_doctor() -> Result<()> {
+    println!("wemux {} diagnostics\n", wemux::VERSION);
+
+    let report = wemux::doctor::run();
+    for check in &report.checks {
+        println!("[{}] {}", check.status, check.name);
+        println!("       {}", check.detail);
+    }
+
+    let failed = report.count(Status::Fail);
+    println!(
+        "\n{} passed, {} warnings, {} failed",
+        report.count(Status::Pass),
+        report.count(Status::Warn),
+        failed
+    );
 
-    let device = devices
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} check(s) failed", failed));
+    }
+    Ok(())
+}
+
+/// Run the engine on fake outputs and the ring buffer on its own
+fn cmd_bench(outputs: usize, buffer_ms: u32, seconds: u64) -> Result<()> {
+    if outputs == 0 {
+        return Err(anyhow::anyhow!("At least one output is needed"));
+    }
+    println!(
+        "Running {} fake outputs with a {}ms buffer for {}s...\n",
+        outputs, buffer_ms, seconds
+    );
+
+    let result = wemux::bench::run_pipeline(outputs, buffer_ms, Duration::from_secs(seconds))?;
+    println!(
+        "Delivered:     {:.1}% of real time (slowest output)",
+        result.delivered * 100.0
+    );
+    println!("Longest pass:  {:.2}ms", result.longest_pass_ms);
+    println!("Underruns:     {}", result.underruns);
+    match result.cpu_usage {
+        Some(usage) => println!("CPU usage:     {:.1}% of all cores", usage * 100.0),
+        None => println!("CPU usage:     unavailable"),
+    }
+    if result.longest_pass_ms > buffer_ms as f64 / 2.0 {
+        println!("\nA render pass took over half the buffer; a larger buffer may avoid dropouts.");
+    }
+
+    println!("\nRing buffer ({} readers):", outputs);
+    let rate = wemux::bench::ring_buffer_throughput(outputs, Duration::from_secs(1)) / 1_000_000.0;
+    println!(
+        "  {:.0} MB/s total, {:.0} MB/s per reader",
+        rate,
+        rate / outputs as f64
+    );
+    Ok(())
+}
+
+/// Measure how late each output plays and the delays that line them up
+fn cmd_test(devices: Vec<String>, seconds: u64) -> Result<()> {
+    let enumerator = DeviceEnumerator::new()?;
+    let all_devices = enumerator.enumerate_all_devices()?;
+    let aliases = load_aliases();
+    let targets: Vec<&DeviceInfo> = devices
         .iter()
-        .find(|d| d.id.contains(device_id) || d.name.contains(device_id));
+        .map(|s| find_device(s, &all_devices, &aliases))
+        .collect::<wemux::Result<_>>()?;
+    let names = aliases.assignments(&all_devices);
 
-    match device {
-        Some(dev) => {
-            println!("Device Information:\n");
-            println!("  Name:     {}", dev.name);
-            println!("  ID:       {}", dev.id);
-            println!("  HDMI:     {}", if dev.is_hdmi { "Yes" } else { "No" });
-            println!("  Default:  {}", if dev.is_default { "Yes" } else { "No" });
+    println!("Playing a test tone for {}s on:", seconds);
+    for device in &targets {
+        println!("  {}", names.get(&device.id).unwrap_or(&device.name));
+    }
+    println!(
+        "
+Beeps go to front left, front right, center... in turn, each higher."
+    );
+    if targets.len() > 1 {
+        println!("An echo on each beep means the outputs are out of step.");
+    }
+
+    let ids: Vec<String> = targets.iter().map(|d| d.id.clone()).collect();
+    wemux::calibrate::play_test_tone(&ids, seconds as f64)?;
+    println!(
+        "
+Done."
+    );
+    Ok(())
+}
+
+fn cmd_calibrate(
+    devices: Option<Vec<String>>,
+    input: Option<String>,
+    play: bool,
+    file: Option<String>,
+    save: bool,
+) -> Result<()> {
+    let enumerator = DeviceEnumerator::new()?;
+    let all_devices = enumerator.enumerate_all_devices()?;
+    let aliases = load_aliases();
+    let targets: Vec<&DeviceInfo> = match &devices {
+        Some(selectors) => selectors
+            .iter()
+            .map(|s| find_device(s, &all_devices, &aliases))
+            .collect::<wemux::Result<_>>()?,
+        None => all_devices.iter().filter(|d| d.is_hdmi).collect(),
+    };
+    if targets.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No HDMI audio devices found, choose devices with --devices"
+        ));
+    }
+    let ids: Vec<String> = targets.iter().map(|d| d.id.clone()).collect();
+    let names = aliases.assignments(&all_devices);
+    let label = |d: &DeviceInfo| names.get(&d.id).unwrap_or(&d.name).clone();
+
+    println!("Devices, in pattern order:");
+    for (i, device) in targets.iter().enumerate() {
+        let clicks = if i == 0 { "click" } else { "clicks" };
+        println!("  {}. {} ({} {})", i + 1, label(device), i + 1, clicks);
+    }
+
+    let latencies = if play {
+        println!(
+            "\nPlaying the pattern for {:.0}s, record it now...",
+            wemux::calibrate::duration_secs(ids.len())
+        );
+        wemux::calibrate::play(&ids)?;
+        let devices_arg = devices
+            .map(|d| format!(" --devices {}", d.join(",")))
+            .unwrap_or_default();
+        println!(
+            "\nDone. Analyze the recording with:\n  wemux calibrate --file <recording.wav>{}",
+            devices_arg
+        );
+        return Ok(());
+    } else if let Some(file) = file {
+        let (samples, rate) = wemux::calibrate::read_wav(Path::new(&file))?;
+        wemux::calibrate::analyze(&samples, rate, ids.len())?
+    } else {
+        let input_id = match input {
+            Some(selector) => {
+                let inputs = enumerator.enumerate_input_devices()?;
+                let device = find_device(&selector, &inputs, &DeviceAliases::default())
+                    .map_err(|e| anyhow::anyhow!("Input device: {}", e))?;
+                Some(device.id.clone())
+            }
+            None => None,
+        };
+        println!(
+            "\nPlaying and recording for {:.0}s, keep the room quiet...",
+            wemux::calibrate::duration_secs(ids.len())
+        );
+        wemux::calibrate::measure(&ids, input_id.as_deref())?
+    };
+
+    let delays = wemux::calibrate::delays(&latencies);
+    println!("\nMeasured:");
+    for (device, (latency, delay)) in targets.iter().zip(latencies.iter().zip(&delays)) {
+        match (latency, delay) {
+            (Some(latency), Some(delay)) => println!(
+                "  {:<40} {:>6.1} ms late, delay by {} ms",
+                label(device),
+                latency,
+                delay
+            ),
+            _ => println!("  {:<40} not heard", label(device)),
         }
-        None => {
-            println!("Device not found: {}", device_id);
-            println!("\nUse 'wemux list --show-ids' to see available devices.");
+    }
+
+    if !save {
+        println!("\nRun again with --save to write the delays to the configuration file.");
+        return Ok(());
+    }
+
+    let path = ServiceConfig::find_default()
+        .or_else(ServiceConfig::get_user_config_path)
+        .ok_or_else(|| anyhow::anyhow!("No configuration file location found"))?;
+    let mut config = if path.exists() {
+        ServiceConfig::load(&path)?
+    } else {
+        ServiceConfig::default()
+    };
+    for (device, delay) in targets.iter().zip(&delays) {
+        let Some(delay) = *delay else {
+            continue;
+        };
+        // An entry under the ID would win over the alias
+        config.delays.remove(&device.id);
+        let key = names.get(&device.id).unwrap_or(&device.id).clone();
+        if delay == 0 {
+            config.delays.remove(&key);
+        } else {
+            config.delays.insert(key, delay);
         }
     }
+    config.save(&path)?;
+    println!("\nDelays saved to {}", path.display());
+    Ok(())
+}
+
+/// Keep the engine running unattended until Ctrl+C
+fn cmd_kiosk(
+    config_path: Option<String>,
+    heartbeat: Option<String>,
+    heartbeat_interval: u64,
+    max_backoff: u64,
+) -> Result<()> {
+    let config_path = config_path
+        .map(PathBuf::from)
+        .or_else(ServiceConfig::find_default);
+    let mut last_good = match &config_path {
+        Some(path) => ServiceConfig::load(path).unwrap_or_else(|e| {
+            warn!("Using default configuration: {}", e);
+            ServiceConfig::default()
+        }),
+        None => ServiceConfig::default(),
+    };
+    let options = KioskOptions {
+        heartbeat_path: heartbeat
+            .map(PathBuf::from)
+            .or_else(|| dirs::data_local_dir().map(|d| d.join("wemux").join("heartbeat.json"))),
+        heartbeat_interval: Duration::from_secs(heartbeat_interval.max(1)),
+        max_backoff: Duration::from_secs(max_backoff.max(1)),
+        notifier: last_good.failure_notifier(),
+    };
+    if let Some(path) = &options.heartbeat_path {
+        println!("Heartbeat: {}", path.display());
+    }
+
+    let stopping = Arc::new(AtomicBool::new(false));
+    let s = stopping.clone();
 
+    #[cfg(windows)]
+    {
+        let _ = ctrlc::set_handler(move || {
+            println!("\nReceived Ctrl+C, stopping...");
+            s.store(true, Ordering::SeqCst);
+        });
+    }
+
+    // Read the file again before every start, keeping the last good version
+    let engine_config = || {
+        if let Some(path) = &config_path {
+            match ServiceConfig::load(path) {
+                Ok(config) => last_good = config,
+                Err(e) => warn!("Using previous configuration: {}", e),
+            }
+        }
+        last_good.to_engine_config_for(last_good.current_profile(LocalTime::now()))
+    };
+
+    wemux::kiosk::run(engine_config, options, &stopping);
     Ok(())
 }
 
+/// Configuration file management
+fn cmd_config(action: ConfigAction) -> Result<()> {
+    match action {
+        ConfigAction::Init {
+            output,
+            full,
+            lang,
+            force,
+        } => {
+            let path = match output {
+                Some(output) => PathBuf::from(output),
+                None => ServiceConfig::get_user_config_path()
+                    .ok_or_else(|| anyhow::anyhow!("Cannot determine the user data directory"))?,
+            };
+
+            if path.exists() && !force {
+                eprintln!("Configuration file already exists: {}", path.display());
+                eprintln!("\nUse --force to overwrite it.");
+                return Err(anyhow::anyhow!("Configuration file already exists"));
+            }
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if let Some(backup) = Backups::new(&path).create()? {
+                println!("Previous configuration backed up to {}", backup.display());
+            }
+            std::fs::write(&path, template::generate(lang, full))?;
+
+            println!("Configuration file created: {}", path.display());
+            if !full {
+                println!("\nRun with --full to also document aliases, profiles and schedules.");
+            }
+        }
+        #[cfg(feature = "tray")]
+        ConfigAction::Prune { days } => {
+            // Present devices count as seen, however long ago the tray last ran
+            let devices = DeviceEnumerator::new()?.enumerate_all_devices()?;
+            let mut settings = wemux::tray::TraySettings::load();
+            let mut removed = Vec::new();
+            settings.update(|settings| {
+                settings.update_from_devices(&devices);
+                removed = settings.prune(days);
+            })?;
+
+            if removed.is_empty() {
+                println!("No devices unseen for more than {} days", days);
+            } else {
+                println!("Removed {} old device(s):", removed.len());
+                for name in &removed {
+                    println!("  {}", name);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Load device aliases from the service configuration file, if any
+fn load_aliases() -> DeviceAliases {
+    ServiceConfig::load_default()
+        .map(|config| config.aliases)
+        .unwrap_or_default()
+}
+
+/// Print the name, type, state and process of an `sc query` answer
+fn print_service_query(stdout: &[u8]) {
+    for line in String::from_utf8_lossy(stdout).lines() {
+        let line = line.trim();
+        if line.starts_with("STATE")
+            || line.starts_with("SERVICE_NAME")
+            || line.starts_with("TYPE")
+            || line.starts_with("PID")
+        {
+            println!("  {}", line);
+        }
+    }
+}
+
 /// Windows Service management
 fn cmd_service(action: ServiceAction) -> Result<()> {
     use std::process::Command as ProcessCommand;
 
     match action {
-        ServiceAction::Install => {
-            println!("Installing {} service...\n", SERVICE_DISPLAY_NAME);
+        ServiceAction::Install { mode, name, config } => {
+            // The service doesn't run in this directory
+            let config = config.map(std::path::absolute).transpose()?;
+            let instance = ServiceInstance::new(&name, config)?;
+            if let Some(config) = instance.config.as_ref().filter(|c| !c.exists()) {
+                println!(
+                    "Note: {} doesn't exist yet; the service uses defaults until it does.\n",
+                    config.display()
+                );
+            }
+            let service_config = instance.load_config().unwrap_or_default();
+            let mode = mode.unwrap_or(service_config.service_mode);
+            println!("Installing {} service...\n", instance.display_name());
 
             // Get path to service executable
             let exe_path = std::env::current_exe()?;
@@ -199,13 +1246,24 @@ fn cmd_service(action: ServiceAction) -> Result<()> {
                 return Err(anyhow::anyhow!("Service executable not found"));
             }
 
-            // Use sc.exe to install the service
+            // Use sc.exe to install the service; a user service is a template
+            // Windows starts an instance of for each user signing in
+            let service_type = match mode {
+                ServiceMode::System => "type=own",
+                ServiceMode::User => "type=userown",
+            };
+            // A named instance finds its name and configuration in its arguments
+            let mut bin_path = format!("\"{}\"", service_exe.display());
+            for arg in instance.to_args() {
+                bin_path.push_str(&format!(" \"{}\"", arg.to_string_lossy()));
+            }
             let output = ProcessCommand::new("sc")
                 .args([
                     "create",
-                    SERVICE_NAME,
-                    &format!("binPath={}", service_exe.display()),
-                    &format!("DisplayName={}", SERVICE_DISPLAY_NAME),
+                    &instance.name,
+                    &format!("binPath={}", bin_path),
+                    &format!("DisplayName={}", instance.display_name()),
+                    service_type,
                     "start=auto",
                 ])
                 .output()?;
@@ -215,12 +1273,53 @@ fn cmd_service(action: ServiceAction) -> Result<()> {
 
                 // Set description
                 let _ = ProcessCommand::new("sc")
-                    .args(["description", SERVICE_NAME, SERVICE_DESCRIPTION])
+                    .args(["description", &instance.name, SERVICE_DESCRIPTION])
                     .output();
 
-                println!("\nTo start the service:");
-                println!("  net start {}", SERVICE_NAME);
-                println!("\nOr use Services (services.msc) to manage the service.");
+                // Let Windows restart the service when it crashes, or stops
+                // with an error (failure flag)
+                let mut failure = vec!["failure".to_string(), instance.name.clone()];
+                failure.extend(service_config.failure_actions());
+                let failure_flag = ["failureflag", &instance.name, "1"].map(String::from);
+                let recovery = [&failure[..], &failure_flag[..]].iter().all(|args| {
+                    ProcessCommand::new("sc")
+                        .args(*args)
+                        .output()
+                        .is_ok_and(|output| output.status.success())
+                });
+                if !recovery {
+                    eprintln!("Warning: Failed to set up restarts after a failure");
+                } else if service_config.restart_delay_secs > 0 {
+                    let limit = match service_config.restart_attempts {
+                        0 => String::new(),
+                        n => format!(
+                            ", up to {} times in {}h",
+                            n, service_config.restart_reset_hours
+                        ),
+                    };
+                    println!(
+                        "Windows restarts it {}s after a failure{}.",
+                        service_config.restart_delay_secs, limit
+                    );
+                }
+
+                match mode {
+                    ServiceMode::System => {
+                        println!("\nTo start the service:");
+                        println!("  net start {}", instance.name);
+                        println!("\nOr use Services (services.msc) to manage the service.");
+                    }
+                    ServiceMode::User => {
+                        println!("\nIt starts in your session the next time you sign in,");
+                        println!(
+                            "listed in Services (services.msc) as {}_<id>.",
+                            instance.name
+                        );
+                    }
+                }
+                if !instance.is_default() {
+                    println!("\nIt answers 'wemux' commands on {}", instance.pipe_name());
+                }
             } else {
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 eprintln!("Failed to install service: {}", stderr);
@@ -229,20 +1328,24 @@ fn cmd_service(action: ServiceAction) -> Result<()> {
             }
         }
 
-        ServiceAction::Uninstall => {
-            println!("Uninstalling {} service...\n", SERVICE_DISPLAY_NAME);
+        ServiceAction::Uninstall { name } => {
+            let instance = ServiceInstance::new(&name, None)?;
+            println!("Uninstalling {} service...\n", instance.display_name());
 
-            // Stop the service first (ignore errors if not running)
-            let _ = ProcessCommand::new("sc")
-                .args(["stop", SERVICE_NAME])
-                .output();
+            // Stop the service first (ignore errors if not running); a user
+            // service runs as a copy per signed-in user
+            let mut running = instance.user_instances();
+            running.push(instance.name.clone());
+            for name in &running {
+                let _ = ProcessCommand::new("sc").args(["stop", name]).output();
+            }
 
             // Give it a moment to stop
             std::thread::sleep(std::time::Duration::from_secs(1));
 
             // Delete the service
             let output = ProcessCommand::new("sc")
-                .args(["delete", SERVICE_NAME])
+                .args(["delete", &instance.name])
                 .output()?;
 
             if output.status.success() {
@@ -255,28 +1358,24 @@ fn cmd_service(action: ServiceAction) -> Result<()> {
             }
         }
 
-        ServiceAction::Status => {
+        ServiceAction::Status { name } => {
+            let instance = ServiceInstance::new(&name, None)?;
             let output = ProcessCommand::new("sc")
-                .args(["query", SERVICE_NAME])
+                .args(["query", &instance.name])
                 .output()?;
 
             if output.status.success() {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                println!("{} Service Status:\n", SERVICE_DISPLAY_NAME);
-
-                // Parse and display status
-                for line in stdout.lines() {
-                    let line = line.trim();
-                    if line.starts_with("STATE")
-                        || line.starts_with("SERVICE_NAME")
-                        || line.starts_with("TYPE")
-                        || line.starts_with("PID")
-                    {
-                        println!("  {}", line);
-                    }
+                println!("{} Service Status:\n", instance.display_name());
+                print_service_query(&output.stdout);
+
+                // A user service's template never runs; its copies do
+                for name in instance.user_instances() {
+                    let output = ProcessCommand::new("sc").args(["query", &name]).output()?;
+                    println!();
+                    print_service_query(&output.stdout);
                 }
             } else {
-                println!("Service '{}' is not installed.", SERVICE_NAME);
+                println!("Service '{}' is not installed.", instance.name);
                 println!("\nTo install the service:");
                 println!("  wemux service install");
             }
@@ -286,6 +1385,9 @@ fn cmd_service(action: ServiceAction) -> Result<()> {
             println!("Generating sample configuration file: {}\n", output);
 
             let config_content = ServiceConfig::sample_config();
+            if let Some(backup) = Backups::new(&output).create()? {
+                println!("Previous configuration backed up to {}", backup.display());
+            }
             std::fs::write(&output, config_content)?;
 
             println!("Configuration file created: {}", output);
@@ -0,0 +1,141 @@
+//! Prometheus metrics endpoint (`metrics` feature)
+//!
+//! Serves `GET /metrics` over plain HTTP. Each scrape asks the owner of the
+//! engine for a stats snapshot through the same [`IpcMessage`] channel the
+//! pipe server uses, so the engine stays on its owner's thread.
+
+mod prometheus;
+
+pub use prometheus::encode;
+
+use crate::audio::EngineStats;
+use crate::error::{Result, WemuxError};
+use crate::ipc::{IpcMessage, Request, Response};
+use crate::remote::http::{read_head, request_reader, Workers, WRITE_TIMEOUT};
+use crossbeam_channel::{bounded, Sender};
+use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How long a scrape waits for the engine owner to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Scrapes served at the same time
+const WORKERS: usize = 2;
+
+/// HTTP server answering metrics scrapes
+pub struct MetricsServer {
+    addr: SocketAddr,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl MetricsServer {
+    /// Start serving on `addr`, e.g. "127.0.0.1:9464" or "0.0.0.0:9464"
+    pub fn start(addr: &str, message_tx: Sender<IpcMessage>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| WemuxError::Ipc(format!("Failed to listen on {}: {}", addr, e)))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| WemuxError::Ipc(format!("Failed to listen on {}: {}", addr, e)))?;
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop_flag.clone();
+        let workers = Workers::spawn("wemux-metrics", WORKERS, move |stream, peer| {
+            if let Err(e) = handle_connection(stream, &message_tx) {
+                debug!("Metrics request from {} failed: {}", peer, e);
+            }
+        })
+        .map_err(|e| WemuxError::Ipc(format!("Failed to start metrics workers: {}", e)))?;
+        thread::spawn(move || server_thread(listener, workers, thread_stop));
+
+        info!("Metrics available at http://{}/metrics", addr);
+        Ok(Self { addr, stop_flag })
+    }
+
+    /// Address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+
+        // Wake the server thread blocked in accept; it exits on its own
+        let mut addr = self.addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+    }
+}
+
+/// Server thread function - hands each connection to a worker
+fn server_thread(listener: TcpListener, workers: Workers, stop_flag: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stop_flag.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Metrics connection failed: {}", e);
+                continue;
+            }
+        };
+        if let Ok(peer) = stream.peer_addr() {
+            workers.serve(stream, peer);
+        }
+    }
+    debug!("Metrics server stopped");
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    message_tx: &Sender<IpcMessage>,
+) -> std::io::Result<()> {
+    // Headers are not needed, but the client expects them to be read
+    let head = read_head(&mut request_reader(&stream))?;
+    let (status, body) = match (head.method.as_str(), head.path.as_str()) {
+        ("GET", "/metrics") => match fetch_stats(message_tx) {
+            Some(stats) => ("200 OK", encode(&stats)),
+            None => (
+                "503 Service Unavailable",
+                "The engine did not answer\n".to_string(),
+            ),
+        },
+        _ => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+    };
+
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Ask the engine owner for a stats snapshot
+fn fetch_stats(message_tx: &Sender<IpcMessage>) -> Option<EngineStats> {
+    let (reply_tx, reply_rx) = bounded(1);
+    message_tx
+        .send(IpcMessage {
+            request: Request::Stats,
+            reply: reply_tx,
+        })
+        .ok()?;
+    match reply_rx.recv_timeout(REPLY_TIMEOUT).ok()? {
+        Response::Stats(stats) => Some(stats),
+        _ => None,
+    }
+}
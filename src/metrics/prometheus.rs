@@ -0,0 +1,189 @@
+//! Prometheus text exposition format
+
+use crate::audio::{EngineStats, RendererStats};
+use std::fmt::Write;
+
+/// Metric name, type, help text and how to read it from a renderer
+type RendererMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&RendererStats) -> f64,
+);
+
+const RENDERER_METRICS: [RendererMetric; 8] = [
+    (
+        "wemux_renderer_frames_total",
+        "counter",
+        "Audio frames written to the device",
+        |r| r.frames_written as f64,
+    ),
+    (
+        "wemux_renderer_underruns_total",
+        "counter",
+        "Times the device played everything it had queued",
+        |r| r.underruns as f64,
+    ),
+    (
+        "wemux_renderer_drift_ms",
+        "gauge",
+        "Drift from the clock sync reference in milliseconds (positive = ahead)",
+        |r| r.drift_ms,
+    ),
+    (
+        "wemux_renderer_buffered_ms",
+        "gauge",
+        "Captured audio waiting in the ring buffer in milliseconds",
+        |r| r.buffered_ms,
+    ),
+    (
+        "wemux_renderer_extra_buffer_ms",
+        "gauge",
+        "Device buffer added by auto-tuning in milliseconds",
+        |r| r.extra_buffer_ms as f64,
+    ),
+    (
+        "wemux_renderer_longest_pass_ms",
+        "gauge",
+        "Longest render pass in milliseconds",
+        |r| r.longest_pass_ms,
+    ),
+    (
+        "wemux_renderer_heartbeat_age_seconds",
+        "gauge",
+        "Time since the render thread last made progress in seconds",
+        |r| r.heartbeat_age_ms as f64 / 1000.0,
+    ),
+    (
+        "wemux_renderer_master",
+        "gauge",
+        "Whether the device is the clock sync master",
+        |r| r.is_master as u8 as f64,
+    ),
+];
+
+/// Render a stats snapshot as Prometheus metrics
+pub fn encode(stats: &EngineStats) -> String {
+    let mut out = String::new();
+
+    let engine_metrics = [
+        (
+            "wemux_engine_running",
+            "gauge",
+            "Whether the audio engine is running",
+            stats.running as u8 as f64,
+        ),
+        (
+            "wemux_engine_restarts_total",
+            "counter",
+            "Times the engine was started again",
+            stats.restarts as f64,
+        ),
+        (
+            "wemux_volume",
+            "gauge",
+            "Effective source volume (0 - 1)",
+            stats.volume as f64,
+        ),
+        (
+            "wemux_muted",
+            "gauge",
+            "Whether wemux output is muted",
+            stats.muted as u8 as f64,
+        ),
+        (
+            "wemux_mirror_volume",
+            "gauge",
+            "wemux's own volume of the outputs (0 - 1)",
+            stats.mirror_volume as f64,
+        ),
+        (
+            "wemux_ring_buffer_ms",
+            "gauge",
+            "Ring buffer capacity in milliseconds",
+            stats.ring_buffer_ms as f64,
+        ),
+        (
+            "wemux_signal_peak_dbfs",
+            "gauge",
+            "Peak level of the audio passed to the outputs in dBFS",
+            stats.level.peak_db() as f64,
+        ),
+        (
+            "wemux_signal_rms_dbfs",
+            "gauge",
+            "RMS level of the audio passed to the outputs in dBFS",
+            stats.level.rms_db() as f64,
+        ),
+    ];
+    for (name, kind, help, value) in engine_metrics {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    for (name, kind, help, value) in RENDERER_METRICS {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for renderer in &stats.renderers {
+            let _ = writeln!(
+                out,
+                "{}{{device=\"{}\",name=\"{}\"}} {}",
+                name,
+                escape_label(&renderer.id),
+                escape_label(renderer.display_name()),
+                value(renderer)
+            );
+        }
+    }
+
+    out
+}
+
+/// Escape a label value (backslash, double quote and newline)
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::ThreadState;
+
+    #[test]
+    fn test_encode_stats() {
+        let stats = EngineStats {
+            running: true,
+            restarts: 2,
+            renderers: vec![RendererStats {
+                id: "{0.0.0}.{abc}".to_string(),
+                name: "LG TV".to_string(),
+                alias: Some("Living \"room\"".to_string()),
+                state: ThreadState::Running,
+                is_master: true,
+                drift_ms: -1.5,
+                buffered_ms: 12.0,
+                underruns: 3,
+                frames_written: 48000,
+                extra_buffer_ms: 0,
+                longest_pass_ms: 0.25,
+                heartbeat_age_ms: 10,
+                stalled: false,
+                end_to_end_ms: None,
+            }],
+            ..Default::default()
+        };
+
+        let text = encode(&stats);
+        assert!(text.contains("# TYPE wemux_engine_restarts_total counter\n"));
+        assert!(text.contains("\nwemux_engine_restarts_total 2\n"));
+        assert!(text.contains(
+            "\nwemux_renderer_frames_total{device=\"{0.0.0}.{abc}\",name=\"Living \\\"room\\\"\"} 48000\n"
+        ));
+        assert!(text.contains("\"} -1.5\n"));
+        assert!(text.ends_with('\n'));
+    }
+}
@@ -0,0 +1,114 @@
+//! Rolling drift history for the monitor graphs
+
+use crate::audio::EngineStats;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Smallest drift range shown, so a perfectly synced device is not a flat line at the edge
+const MIN_DRIFT_RANGE_MS: f64 = 1.0;
+
+/// Recent drift samples of each renderer
+#[derive(Debug)]
+pub struct DriftHistory {
+    capacity: usize,
+    samples: BTreeMap<String, VecDeque<f64>>,
+}
+
+impl DriftHistory {
+    /// Create a history keeping the last `capacity` samples per device
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: BTreeMap::new(),
+        }
+    }
+
+    /// Number of samples kept per device
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Append one sample per renderer; devices that are gone are forgotten
+    pub fn push(&mut self, stats: &EngineStats) {
+        self.samples
+            .retain(|id, _| stats.renderers.iter().any(|r| &r.id == id));
+
+        for renderer in &stats.renderers {
+            let samples = self.samples.entry(renderer.id.clone()).or_default();
+            if samples.len() == self.capacity {
+                samples.pop_front();
+            }
+            samples.push_back(renderer.drift_ms);
+        }
+    }
+
+    /// Chart points for a device, right-aligned so the newest sample is at `capacity`
+    pub fn points(&self, device_id: &str) -> Vec<(f64, f64)> {
+        let Some(samples) = self.samples.get(device_id) else {
+            return Vec::new();
+        };
+        let offset = self.capacity - samples.len();
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, drift)| ((offset + i + 1) as f64, *drift))
+            .collect()
+    }
+
+    /// Symmetric y-axis range covering every sample
+    pub fn range(&self) -> f64 {
+        self.samples
+            .values()
+            .flatten()
+            .fold(MIN_DRIFT_RANGE_MS, |max, drift| max.max(drift.abs()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{RendererStats, ThreadState};
+
+    fn stats(drifts: &[(&str, f64)]) -> EngineStats {
+        EngineStats {
+            running: true,
+            renderers: drifts
+                .iter()
+                .map(|(id, drift_ms)| RendererStats {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    alias: None,
+                    state: ThreadState::Running,
+                    is_master: false,
+                    drift_ms: *drift_ms,
+                    buffered_ms: 0.0,
+                    underruns: 0,
+                    frames_written: 0,
+                    extra_buffer_ms: 0,
+                    longest_pass_ms: 0.0,
+                    heartbeat_age_ms: 0,
+                    stalled: false,
+                    end_to_end_ms: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_history_rolls_and_forgets_devices() {
+        let mut history = DriftHistory::new(3);
+        for drift in [1.0, 2.0, -4.0, 3.0] {
+            history.push(&stats(&[("a", drift), ("b", 0.0)]));
+        }
+
+        assert_eq!(
+            history.points("a"),
+            vec![(1.0, 2.0), (2.0, -4.0), (3.0, 3.0)]
+        );
+        assert_eq!(history.range(), 4.0);
+
+        history.push(&stats(&[("b", 0.5)]));
+        assert!(history.points("a").is_empty());
+        assert_eq!(history.range(), MIN_DRIFT_RANGE_MS);
+    }
+}
@@ -0,0 +1,130 @@
+//! Live terminal dashboard for a running engine (`wemux monitor`)
+//!
+//! Polls [`EngineStats`] from the engine host over IPC and draws per-device
+//! drift graphs, ring buffer fill, volume and render thread state. Losing the
+//! connection is not fatal: the dashboard keeps polling until the host returns.
+
+mod history;
+mod ui;
+
+pub use history::DriftHistory;
+
+use crate::audio::{EngineStats, ThreadState};
+use crate::ipc::EngineClient;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::DefaultTerminal;
+use std::time::{Duration, Instant};
+
+/// Seconds of drift history shown in the graph
+const HISTORY_SECONDS: u64 = 30;
+
+/// Dashboard state between redraws
+struct Monitor {
+    client: EngineClient,
+    /// Latest statistics, None until the engine host answers
+    stats: Option<EngineStats>,
+    /// Last IPC error, cleared by the next successful poll
+    error: Option<String>,
+    drift: DriftHistory,
+    /// Index of the highlighted renderer
+    selected: usize,
+}
+
+impl Monitor {
+    fn poll(&mut self) {
+        match self.client.stats() {
+            Ok(stats) => {
+                self.drift.push(&stats);
+                self.selected = self.selected.min(stats.renderers.len().saturating_sub(1));
+                self.stats = Some(stats);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn select(&mut self, delta: isize) {
+        let count = self.stats.as_ref().map_or(0, |s| s.renderers.len());
+        if count > 0 {
+            self.selected = self.selected.saturating_add_signed(delta).min(count - 1);
+        }
+    }
+
+    /// Pause the highlighted renderer, or resume it if it is paused
+    fn toggle_pause(&mut self) {
+        let Some(renderer) = self
+            .stats
+            .as_ref()
+            .and_then(|s| s.renderers.get(self.selected))
+        else {
+            return;
+        };
+
+        let result = if renderer.state == ThreadState::Paused {
+            self.client.resume_device(&renderer.id)
+        } else {
+            self.client.pause_device(&renderer.id)
+        };
+        if let Err(e) = result {
+            self.error = Some(e.to_string());
+        }
+    }
+}
+
+/// Run the dashboard until the user quits (q or Esc)
+///
+/// `interval` is the time between polls of the engine host.
+pub fn run(client: EngineClient, interval: Duration) -> std::io::Result<()> {
+    let samples = (Duration::from_secs(HISTORY_SECONDS).as_millis() / interval.as_millis().max(1))
+        .max(2) as usize;
+    let mut monitor = Monitor {
+        client,
+        stats: None,
+        error: None,
+        drift: DriftHistory::new(samples),
+        selected: 0,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut monitor, interval);
+    ratatui::restore();
+
+    result
+}
+
+/// Poll, redraw and handle keys until the user quits
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    monitor: &mut Monitor,
+    interval: Duration,
+) -> std::io::Result<()> {
+    let mut next_poll = Instant::now();
+    loop {
+        if Instant::now() >= next_poll {
+            monitor.poll();
+            next_poll = Instant::now() + interval;
+        }
+        terminal.draw(|frame| ui::draw(frame, monitor))?;
+
+        if !event::poll(next_poll.saturating_duration_since(Instant::now()))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Up => monitor.select(-1),
+            KeyCode::Down => monitor.select(1),
+            KeyCode::Char(' ') => {
+                monitor.toggle_pause();
+                // Show the new state right away
+                next_poll = Instant::now();
+            }
+            _ => {}
+        }
+    }
+}
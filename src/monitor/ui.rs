@@ -0,0 +1,254 @@
+//! Dashboard layout and widgets
+
+use super::Monitor;
+use crate::audio::{EngineStats, RendererStats, ThreadState, VolumeMode};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::symbols::Marker;
+use ratatui::text::Line;
+use ratatui::widgets::{
+    Axis, Block, Cell, Chart, Dataset, Gauge, GraphType, Paragraph, Row, Table, TableState,
+};
+use ratatui::Frame;
+
+/// Colors assigned to devices in table order, shared by table and graph
+const DEVICE_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+/// Width of the ring buffer fill bar in characters
+const FILL_BAR_WIDTH: usize = 12;
+
+/// Lowest level the signal meter shows, in dBFS
+const METER_FLOOR_DB: f32 = -60.0;
+
+fn device_color(index: usize) -> Color {
+    DEVICE_COLORS[index % DEVICE_COLORS.len()]
+}
+
+/// Draw the whole dashboard
+pub(super) fn draw(frame: &mut Frame, monitor: &Monitor) {
+    let device_rows = monitor
+        .stats
+        .as_ref()
+        .map_or(1, |s| s.renderers.len().max(1));
+    let [header, table, chart, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(device_rows as u16 + 3),
+        Constraint::Min(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_header(frame, header, monitor.stats.as_ref());
+    match &monitor.stats {
+        Some(stats) => {
+            draw_table(frame, table, stats, monitor.selected);
+            draw_chart(frame, chart, stats, monitor);
+        }
+        None => frame.render_widget(
+            Paragraph::new("Waiting for the engine host (is the wemux service running?)")
+                .block(Block::bordered().title(" Devices ")),
+            table,
+        ),
+    }
+    draw_footer(frame, footer, monitor.error.as_deref());
+}
+
+fn draw_header(frame: &mut Frame, area: Rect, stats: Option<&EngineStats>) {
+    let [status, signal, volume] = Layout::horizontal([
+        Constraint::Min(30),
+        Constraint::Length(24),
+        Constraint::Length(30),
+    ])
+    .areas(area);
+
+    let text = match stats {
+        Some(stats) if stats.running => Line::from(vec![
+            "Engine ".into(),
+            "running".green().bold(),
+            format!("  |  ring buffer {}ms", stats.ring_buffer_ms).into(),
+        ]),
+        Some(_) => Line::from(vec!["Engine ".into(), "stopped".yellow().bold()]),
+        None => Line::from("Not connected".red().bold()),
+    };
+    frame.render_widget(
+        Paragraph::new(text).block(Block::bordered().title(" wemux monitor ")),
+        status,
+    );
+
+    // Signal meter: peak level from the floor up to full scale
+    let (ratio, label) = match stats.map(|s| s.level).filter(|l| !l.is_silent()) {
+        Some(level) => {
+            let db = level.peak_db();
+            (
+                ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0) as f64,
+                format!("{:.0} dB", db),
+            )
+        }
+        None => (0.0, "silent".to_string()),
+    };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::bordered().title(" Signal "))
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(ratio)
+            .label(label),
+        signal,
+    );
+
+    let level = stats.map_or(0.0, |s| s.volume.clamp(0.0, 1.0) as f64);
+    let title = match stats {
+        Some(s) if s.muted => " Volume (muted) ".to_string(),
+        Some(s) if s.volume_mode != VolumeMode::Follow => format!(
+            " Volume ({}, mirror {:.0}%) ",
+            s.volume_mode,
+            s.mirror_volume * 100.0
+        ),
+        _ => " Volume ".to_string(),
+    };
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::bordered().title(title))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(level),
+        volume,
+    );
+}
+
+fn draw_table(frame: &mut Frame, area: Rect, stats: &EngineStats, selected: usize) {
+    let header = Row::new([
+        "Device",
+        "Role",
+        "State",
+        "Drift",
+        "Buffered",
+        "",
+        "Underruns",
+        "Extra",
+        "Latency",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows = stats.renderers.iter().enumerate().map(|(i, renderer)| {
+        Row::new([
+            Cell::from(renderer.display_name().to_string()).fg(device_color(i)),
+            Cell::from(if renderer.is_master {
+                "master"
+            } else {
+                "slave"
+            }),
+            state_cell(renderer),
+            Cell::from(if renderer.is_master {
+                "-".to_string()
+            } else {
+                format!("{:+.2}ms", renderer.drift_ms)
+            }),
+            Cell::from(fill_bar(renderer, stats.ring_buffer_ms)),
+            Cell::from(format!("{:.0}ms", renderer.buffered_ms)),
+            Cell::from(renderer.underruns.to_string()),
+            Cell::from(format!("+{}ms", renderer.extra_buffer_ms)),
+            Cell::from(match renderer.end_to_end_ms {
+                Some(ms) => format!("{:.0}ms", ms),
+                None => "-".to_string(),
+            }),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(FILL_BAR_WIDTH as u16),
+            Constraint::Length(7),
+            Constraint::Length(9),
+            Constraint::Length(7),
+            Constraint::Length(7),
+        ],
+    )
+    .header(header)
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .block(Block::bordered().title(" Devices "));
+
+    let mut state = TableState::default().with_selected(Some(selected));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn state_cell(renderer: &RendererStats) -> Cell<'static> {
+    if renderer.stalled {
+        return Cell::from("stalled").fg(Color::Red);
+    }
+    let color = match renderer.state {
+        ThreadState::Running => Color::Green,
+        ThreadState::Paused => Color::Yellow,
+        ThreadState::Starting => Color::Gray,
+        ThreadState::Stopped => Color::Red,
+    };
+    Cell::from(renderer.state.to_string()).fg(color)
+}
+
+/// Text bar of how much of the ring buffer is waiting for a renderer
+fn fill_bar(renderer: &RendererStats, ring_buffer_ms: u32) -> String {
+    let ratio = if ring_buffer_ms == 0 {
+        0.0
+    } else {
+        (renderer.buffered_ms / ring_buffer_ms as f64).clamp(0.0, 1.0)
+    };
+    let filled = (ratio * FILL_BAR_WIDTH as f64).round() as usize;
+    format!(
+        "{}{}",
+        "█".repeat(filled),
+        "░".repeat(FILL_BAR_WIDTH - filled)
+    )
+}
+
+fn draw_chart(frame: &mut Frame, area: Rect, stats: &EngineStats, monitor: &Monitor) {
+    let points: Vec<Vec<(f64, f64)>> = stats
+        .renderers
+        .iter()
+        .map(|renderer| monitor.drift.points(&renderer.id))
+        .collect();
+
+    let datasets = stats
+        .renderers
+        .iter()
+        .zip(&points)
+        .enumerate()
+        .filter(|(_, (renderer, _))| !renderer.is_master)
+        .map(|(i, (renderer, points))| {
+            Dataset::default()
+                .name(renderer.display_name().to_string())
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(device_color(i)))
+                .data(points)
+        })
+        .collect();
+
+    let range = monitor.drift.range();
+    let chart = Chart::new(datasets)
+        .block(Block::bordered().title(" Drift from master (ms) "))
+        .x_axis(Axis::default().bounds([1.0, monitor.drift.capacity() as f64]))
+        .y_axis(Axis::default().bounds([-range, range]).labels([
+            format!("{:+.1}", -range),
+            "0".to_string(),
+            format!("{:+.1}", range),
+        ]));
+    frame.render_widget(chart, area);
+}
+
+fn draw_footer(frame: &mut Frame, area: Rect, error: Option<&str>) {
+    let line = match error {
+        Some(error) => Line::from(error.to_string().red()),
+        None => Line::from("q: quit   ↑/↓: select   space: pause/resume device".dark_gray()),
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
@@ -0,0 +1,286 @@
+//! Notifications about sustained engine failures (`notify_url`)
+//!
+//! Nobody watches a tray icon on a headless box. Once the engine has been
+//! failing for longer than a configured time, wemux POSTs a short text to a
+//! webhook, and again when audio plays once more. An ntfy topic URL
+//! (`https://ntfy.sh/<topic>`) works as is and shows up on a phone; the title
+//! goes in a `Title` header, the message is the plain-text body. Requests go
+//! through WinHTTP, so HTTPS and the system proxy work without extra
+//! dependencies.
+
+use crate::error::{Result, WemuxError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use windows::core::{w, PCWSTR};
+use windows::Win32::Networking::WinHttp::{
+    WinHttpCloseHandle, WinHttpConnect, WinHttpCrackUrl, WinHttpOpen, WinHttpOpenRequest,
+    WinHttpQueryHeaders, WinHttpReceiveResponse, WinHttpSendRequest, WinHttpSetTimeouts,
+    URL_COMPONENTS, WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY, WINHTTP_FLAG_SECURE,
+    WINHTTP_INTERNET_SCHEME_HTTPS, WINHTTP_OPEN_REQUEST_FLAGS, WINHTTP_QUERY_FLAG_NUMBER,
+    WINHTTP_QUERY_STATUS_CODE,
+};
+
+/// Resolve, connect, send and receive timeout in milliseconds
+const REQUEST_TIMEOUT_MS: i32 = 15_000;
+
+/// WinHTTP handle closed on drop
+struct Handle(*mut core::ffi::c_void);
+
+impl Handle {
+    fn new(handle: *mut core::ffi::c_void, what: &str) -> Result<Self> {
+        if handle.is_null() {
+            return Err(WemuxError::Notification(format!(
+                "{} failed: {}",
+                what,
+                windows::core::Error::from_win32()
+            )));
+        }
+        Ok(Self(handle))
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = WinHttpCloseHandle(self.0);
+        }
+    }
+}
+
+/// Webhook notifications are posted to
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+}
+
+impl Webhook {
+    /// Webhook at an http:// or https:// URL
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+
+    /// POST `message` with `title`, failing unless the server answers 2xx
+    pub fn send(&self, title: &str, message: &str) -> Result<()> {
+        let fail = |what: &str, e: windows::core::Error| {
+            WemuxError::Notification(format!("{} failed: {}", what, e))
+        };
+        let url: Vec<u16> = self.url.encode_utf16().collect();
+
+        unsafe {
+            // Lengths of -1 ask WinHttpCrackUrl for pointers into `url`
+            let mut parts = URL_COMPONENTS {
+                dwStructSize: std::mem::size_of::<URL_COMPONENTS>() as u32,
+                dwSchemeLength: u32::MAX,
+                dwHostNameLength: u32::MAX,
+                dwUrlPathLength: u32::MAX,
+                dwExtraInfoLength: u32::MAX,
+                ..Default::default()
+            };
+            WinHttpCrackUrl(&url, 0, &mut parts)
+                .map_err(|_| WemuxError::InvalidConfig(format!("Invalid URL '{}'", self.url)))?;
+            let host: Vec<u16> =
+                std::slice::from_raw_parts(parts.lpszHostName.0, parts.dwHostNameLength as usize)
+                    .iter()
+                    .copied()
+                    .chain(Some(0))
+                    .collect();
+            // Path and query string are adjacent in the URL
+            let path: Vec<u16> = std::slice::from_raw_parts(
+                parts.lpszUrlPath.0,
+                (parts.dwUrlPathLength + parts.dwExtraInfoLength) as usize,
+            )
+            .iter()
+            .copied()
+            .chain(Some(0))
+            .collect();
+            let flags = if parts.nScheme == WINHTTP_INTERNET_SCHEME_HTTPS {
+                WINHTTP_FLAG_SECURE
+            } else {
+                WINHTTP_OPEN_REQUEST_FLAGS(0)
+            };
+
+            let session = Handle::new(
+                WinHttpOpen(
+                    w!("wemux"),
+                    WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+                    PCWSTR::null(),
+                    PCWSTR::null(),
+                    0,
+                ),
+                "WinHttpOpen",
+            )?;
+            WinHttpSetTimeouts(
+                session.0,
+                REQUEST_TIMEOUT_MS,
+                REQUEST_TIMEOUT_MS,
+                REQUEST_TIMEOUT_MS,
+                REQUEST_TIMEOUT_MS,
+            )
+            .map_err(|e| fail("WinHttpSetTimeouts", e))?;
+            let connection = Handle::new(
+                WinHttpConnect(session.0, PCWSTR(host.as_ptr()), parts.nPort, 0),
+                "WinHttpConnect",
+            )?;
+            let request = Handle::new(
+                WinHttpOpenRequest(
+                    connection.0,
+                    w!("POST"),
+                    PCWSTR(path.as_ptr()),
+                    PCWSTR::null(),
+                    PCWSTR::null(),
+                    std::ptr::null(),
+                    flags,
+                ),
+                "WinHttpOpenRequest",
+            )?;
+
+            let title = title.replace(['\r', '\n'], " ");
+            let headers: Vec<u16> = format!(
+                "Content-Type: text/plain; charset=utf-8\r\nTitle: {}\r\n",
+                title
+            )
+            .encode_utf16()
+            .collect();
+            WinHttpSendRequest(
+                request.0,
+                Some(&headers),
+                Some(message.as_ptr().cast()),
+                message.len() as u32,
+                message.len() as u32,
+                0,
+            )
+            .map_err(|e| fail("Sending the notification", e))?;
+            WinHttpReceiveResponse(request.0, std::ptr::null_mut())
+                .map_err(|e| fail("Receiving the answer", e))?;
+
+            let mut status = 0u32;
+            let mut size = std::mem::size_of::<u32>() as u32;
+            WinHttpQueryHeaders(
+                request.0,
+                WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+                PCWSTR::null(),
+                Some((&mut status as *mut u32).cast()),
+                &mut size,
+                std::ptr::null_mut(),
+            )
+            .map_err(|e| fail("Reading the status", e))?;
+            if !(200..300).contains(&status) {
+                return Err(WemuxError::Notification(format!(
+                    "{} answered with status {}",
+                    self.url, status
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Notification due after an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alert {
+    /// Failing for longer than the threshold
+    Failing,
+    /// Working again after a failure that was reported
+    Recovered,
+}
+
+/// Posts to a webhook once the engine has been failing for a while
+///
+/// Each failure is reported once, followed by one message when it is over.
+/// Failures shorter than the threshold stay silent.
+#[derive(Debug, Clone)]
+pub struct FailureNotifier {
+    webhook: Webhook,
+    after: Duration,
+    failing_since: Option<Instant>,
+    notified: bool,
+}
+
+impl FailureNotifier {
+    /// Notify through `webhook` about failures lasting longer than `after`
+    pub fn new(webhook: Webhook, after: Duration) -> Self {
+        Self {
+            webhook,
+            after,
+            failing_since: None,
+            notified: false,
+        }
+    }
+
+    /// Report whether the engine is failing right now; call this regularly
+    ///
+    /// Notifications are sent from a background thread.
+    pub fn update(&mut self, failing: bool, reason: Option<&str>) {
+        let Some(alert) = self.transition(failing, Instant::now()) else {
+            return;
+        };
+
+        let host = std::env::var("COMPUTERNAME").unwrap_or_else(|_| "this machine".to_string());
+        let (title, message) = match alert {
+            Alert::Failing => (
+                format!("wemux on {} is failing", host),
+                format!(
+                    "No audio for {} minutes{}",
+                    self.after.as_secs().div_ceil(60),
+                    reason.map(|r| format!(": {}", r)).unwrap_or_default()
+                ),
+            ),
+            Alert::Recovered => (
+                format!("wemux on {} recovered", host),
+                "Audio is playing again".to_string(),
+            ),
+        };
+
+        info!("Sending notification: {}", title);
+        let webhook = self.webhook.clone();
+        thread::spawn(move || {
+            if let Err(e) = webhook.send(&title, &message) {
+                warn!("Failed to send notification: {}", e);
+            }
+        });
+    }
+
+    fn transition(&mut self, failing: bool, now: Instant) -> Option<Alert> {
+        if !failing {
+            self.failing_since = None;
+            return std::mem::take(&mut self.notified).then_some(Alert::Recovered);
+        }
+
+        let since = *self.failing_since.get_or_insert(now);
+        if !self.notified && now.duration_since(since) >= self.after {
+            self.notified = true;
+            return Some(Alert::Failing);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_reported_once() {
+        let minute = Duration::from_secs(60);
+        let mut notifier = FailureNotifier::new(Webhook::new("https://ntfy.sh/x"), 5 * minute);
+        let start = Instant::now();
+
+        // A short failure stays silent
+        assert_eq!(notifier.transition(true, start), None);
+        assert_eq!(notifier.transition(false, start + minute), None);
+
+        assert_eq!(notifier.transition(true, start + 2 * minute), None);
+        assert_eq!(notifier.transition(true, start + 6 * minute), None);
+        assert_eq!(
+            notifier.transition(true, start + 7 * minute),
+            Some(Alert::Failing)
+        );
+        assert_eq!(notifier.transition(true, start + 30 * minute), None);
+        assert_eq!(
+            notifier.transition(false, start + 31 * minute),
+            Some(Alert::Recovered)
+        );
+        assert_eq!(notifier.transition(false, start + 32 * minute), None);
+    }
+}
@@ -0,0 +1,113 @@
+//! Client address allowlist
+
+use crate::error::{Result, WemuxError};
+use std::net::IpAddr;
+
+/// One allowed address or network, e.g. "192.168.1.0/24"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn parse(entry: &str) -> Result<Self> {
+        let invalid = || WemuxError::InvalidConfig(format!("Invalid allowlist entry '{}'", entry));
+
+        let (addr, prefix) = match entry.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (entry, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(net) as u128,
+                u32::from(ip) as u128,
+                32,
+                self.prefix,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(net), u128::from(ip), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the top `prefix` of `bits` bits agree
+fn prefix_matches(net: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+    let shift = bits - prefix;
+    shift == bits || (net >> shift) == (ip >> shift)
+}
+
+/// Addresses that may use remote administration
+///
+/// An empty list admits this machine only. Loopback clients are always
+/// admitted, since they could use the local pipe anyway.
+#[derive(Debug, Clone, Default)]
+pub struct AllowList {
+    networks: Vec<Network>,
+}
+
+impl AllowList {
+    /// Parse addresses ("10.0.0.5") and networks in CIDR notation ("192.168.1.0/24")
+    pub fn parse(entries: &[String]) -> Result<Self> {
+        let networks = entries
+            .iter()
+            .map(|entry| Network::parse(entry))
+            .collect::<Result<_>>()?;
+        Ok(Self { networks })
+    }
+
+    /// Whether no address besides this machine is admitted
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    /// Whether a client connecting from `ip` is admitted
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_loopback() || self.networks.iter().any(|network| network.contains(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_list() {
+        let list = AllowList::parse(&[
+            "192.168.1.0/24".to_string(),
+            "10.0.0.5".to_string(),
+            "fd00::/8".to_string(),
+        ])
+        .unwrap();
+
+        assert!(list.allows("192.168.1.77".parse().unwrap()));
+        assert!(list.allows("10.0.0.5".parse().unwrap()));
+        assert!(list.allows("::ffff:192.168.1.2".parse().unwrap()));
+        assert!(list.allows("fd12::1".parse().unwrap()));
+        assert!(list.allows("127.0.0.1".parse().unwrap()));
+        assert!(!list.allows("192.168.2.1".parse().unwrap()));
+        assert!(!list.allows("10.0.0.6".parse().unwrap()));
+
+        assert!(!AllowList::default().allows("8.8.8.8".parse().unwrap()));
+        assert!(AllowList::default().allows("::1".parse().unwrap()));
+        assert!(AllowList::parse(&["0.0.0.0/0".to_string()]).is_ok());
+        assert!(AllowList::parse(&["10.0.0.0/33".to_string()]).is_err());
+        assert!(AllowList::parse(&["living-room".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Minimal HTTP/1.1 plumbing shared by the remote and metrics servers
+//!
+//! Both servers face the network, so a client gets a bounded amount of
+//! everything: [`MAX_HEADER_BYTES`] of request head, [`REQUEST_DEADLINE`] to
+//! send the whole request and [`WRITE_TIMEOUT`] per write of the answer.
+//! Connections are served by a small [`Workers`] pool; when all of its
+//! workers are busy and its queue is full, new connections are dropped
+//! instead of piling up.
+
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use std::io::{self, BufRead, BufReader, Read, Take};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Largest request line plus headers accepted
+pub(crate) const MAX_HEADER_BYTES: u64 = 8 * 1024;
+
+/// How long a client may take to send its whole request
+pub(crate) const REQUEST_DEADLINE: Duration = Duration::from_secs(5);
+
+/// How long a single write of the answer may block
+pub(crate) const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections waiting for a worker before new ones are dropped
+const QUEUE_SIZE: usize = 16;
+
+/// Reads from a stream until an overall deadline
+///
+/// A socket read timeout only limits each read, so a client trickling one
+/// byte at a time could otherwise hold a worker forever.
+pub(crate) struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> DeadlineReader<'a> {
+    pub(crate) fn new(stream: &'a TcpStream, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Request not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Request line and headers of an HTTP request
+pub(crate) struct RequestHead {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// Value of the header `name`, compared case-insensitively
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reader for a request on `stream`, limited to [`MAX_HEADER_BYTES`] and
+/// [`REQUEST_DEADLINE`]; raise the limit with `set_limit` to read a body
+pub(crate) fn request_reader(stream: &TcpStream) -> Take<BufReader<DeadlineReader<'_>>> {
+    BufReader::new(DeadlineReader::new(stream, REQUEST_DEADLINE)).take(MAX_HEADER_BYTES)
+}
+
+/// Read the request line and headers, failing if they exceed the reader's limit
+pub(crate) fn read_head(reader: &mut impl BufRead) -> io::Result<RequestHead> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, "Request header too large");
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    if !request_line.ends_with('\n') {
+        return Err(too_large());
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts
+        .next()
+        .and_then(|p| p.split('?').next())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut headers = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        if !line.ends_with('\n') {
+            return Err(too_large());
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    Ok(RequestHead {
+        method,
+        path,
+        headers,
+    })
+}
+
+/// Fixed set of threads serving accepted connections
+///
+/// The threads exit once the pool is dropped and the queue is drained.
+pub(crate) struct Workers {
+    queue: Sender<(TcpStream, SocketAddr)>,
+}
+
+impl Workers {
+    /// Start `count` threads named `name` calling `serve` for each connection
+    pub(crate) fn spawn<F>(name: &str, count: usize, serve: F) -> io::Result<Self>
+    where
+        F: Fn(TcpStream, SocketAddr) + Clone + Send + 'static,
+    {
+        let (queue, connections) = bounded::<(TcpStream, SocketAddr)>(QUEUE_SIZE);
+        for i in 0..count {
+            let connections = connections.clone();
+            let serve = serve.clone();
+            thread::Builder::new()
+                .name(format!("{}-{}", name, i))
+                .spawn(move || {
+                    for (stream, peer) in connections {
+                        serve(stream, peer);
+                    }
+                })?;
+        }
+        Ok(Self { queue })
+    }
+
+    /// Hand a connection to a worker, or drop it if they are all busy
+    pub(crate) fn serve(&self, stream: TcpStream, peer: SocketAddr) {
+        if let Err(TrySendError::Full(_)) = self.queue.try_send((stream, peer)) {
+            debug!("Dropped connection from {}: all workers busy", peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_head_limits_header_size() {
+        let request =
+            b"POST /api?x=1 HTTP/1.1\r\nAuthorization: Bearer abc\r\nContent-Length: 2\r\n\r\n{}";
+        let mut reader = (&request[..]).take(MAX_HEADER_BYTES);
+        let head = read_head(&mut reader).unwrap();
+        assert_eq!(head.method, "POST");
+        assert_eq!(head.path, "/api");
+        assert_eq!(head.header("authorization"), Some("Bearer abc"));
+        assert_eq!(head.header("content-length"), Some("2"));
+
+        let mut huge = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        huge.resize(MAX_HEADER_BYTES as usize * 2, b'a');
+        let mut reader = (&huge[..]).take(MAX_HEADER_BYTES);
+        assert!(read_head(&mut reader).is_err());
+    }
+}
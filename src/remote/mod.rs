@@ -0,0 +1,301 @@
+//! Remote administration over the network
+//!
+//! The service can take the same [`Request`]s as its named pipe over HTTP, so
+//! one machine can control wemux on several others. Each call is a
+//! `POST /api` whose body is the JSON request and whose `Authorization:
+//! Bearer` header carries the shared token; the body of the answer is the
+//! JSON [`Response`]. Clients outside the [`AllowList`] are turned away before
+//! their request is read; without one, only this machine may connect.
+//!
+//! `GET /` serves a small dashboard built on the same API, so a phone's
+//! browser can mute or pause outputs. The page holds no secrets and asks for
+//! the token itself.
+
+mod allow;
+pub(crate) mod http;
+
+pub use allow::AllowList;
+
+use crate::error::{Result, WemuxError};
+use crate::ipc::{dispatch, IpcMessage, Request, Response};
+use crossbeam_channel::Sender;
+use http::{read_head, request_reader, Workers, WRITE_TIMEOUT};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Web dashboard served at `/`
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Largest request body accepted
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Connections served at the same time
+const WORKERS: usize = 4;
+
+/// How long a client waits to connect and for the answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP server answering remote control requests
+pub struct RemoteServer {
+    addr: SocketAddr,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl RemoteServer {
+    /// Start serving on `addr` (e.g. "0.0.0.0:9465") for clients presenting `token`
+    ///
+    /// Refuses to start without a token.
+    pub fn start(
+        addr: &str,
+        token: &str,
+        allow: AllowList,
+        message_tx: Sender<IpcMessage>,
+    ) -> Result<Self> {
+        if token.is_empty() {
+            return Err(WemuxError::InvalidConfig(
+                "Remote administration needs a token".to_string(),
+            ));
+        }
+
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| WemuxError::Ipc(format!("Failed to listen on {}: {}", addr, e)))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| WemuxError::Ipc(format!("Failed to listen on {}: {}", addr, e)))?;
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop_flag.clone();
+        let token = token.to_string();
+        let allow_empty = allow.is_empty();
+        let workers = Workers::spawn("wemux-remote", WORKERS, move |stream, peer| {
+            serve(stream, peer, &token, &allow, &message_tx)
+        })
+        .map_err(|e| WemuxError::Ipc(format!("Failed to start remote workers: {}", e)))?;
+        thread::spawn(move || server_thread(listener, workers, thread_stop));
+
+        info!("Remote administration listening on {}", addr);
+        if allow_empty && !addr.ip().is_loopback() {
+            warn!("remote_allow is empty, so only this machine may connect");
+        }
+        Ok(Self { addr, stop_flag })
+    }
+
+    /// Address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+
+        // Wake the server thread blocked in accept; it exits on its own
+        let mut addr = self.addr;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&addr, Duration::from_secs(1));
+    }
+}
+
+/// Server thread function - hands each connection to a worker
+fn server_thread(listener: TcpListener, workers: Workers, stop_flag: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stop_flag.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Remote connection failed: {}", e);
+                continue;
+            }
+        };
+        if let Ok(peer) = stream.peer_addr() {
+            workers.serve(stream, peer);
+        }
+    }
+    debug!("Remote server stopped");
+}
+
+/// Answer one connection, turning away clients outside `allow`
+fn serve(
+    stream: TcpStream,
+    peer: SocketAddr,
+    token: &str,
+    allow: &AllowList,
+    message_tx: &Sender<IpcMessage>,
+) {
+    let result = if allow.allows(peer.ip()) {
+        handle_connection(stream, peer, token, message_tx)
+    } else {
+        warn!("Rejected remote connection from {} (not allowed)", peer);
+        write_json(
+            stream,
+            "403 Forbidden",
+            &Response::Error {
+                message: "Address not allowed".to_string(),
+            },
+        )
+    };
+    if let Err(e) = result {
+        debug!("Remote request from {} failed: {}", peer, e);
+    }
+}
+
+/// Parts of an HTTP request the server looks at
+struct HttpRequest {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+fn read_request(stream: &TcpStream) -> std::io::Result<HttpRequest> {
+    let mut reader = request_reader(stream);
+    let head = read_head(&mut reader)?;
+    let token = head
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let content_length: usize = head
+        .header("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+
+    let mut body = Vec::new();
+    reader.set_limit(content_length.min(MAX_BODY_SIZE) as u64);
+    reader.read_to_end(&mut body)?;
+
+    Ok(HttpRequest {
+        method: head.method,
+        path: head.path,
+        token,
+        body,
+    })
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    token: &str,
+    message_tx: &Sender<IpcMessage>,
+) -> std::io::Result<()> {
+    let request = read_request(&stream)?;
+
+    if request.method == "GET" && (request.path == "/" || request.path == "/index.html") {
+        return write_response(stream, "200 OK", "text/html; charset=utf-8", DASHBOARD_HTML);
+    }
+
+    let error = |message: &str| Response::Error {
+        message: message.to_string(),
+    };
+    let (status, response) = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/api") => {
+            if !request
+                .token
+                .as_deref()
+                .is_some_and(|t| token_matches(t, token))
+            {
+                warn!("Rejected remote request from {} (bad token)", peer);
+                ("401 Unauthorized", error("Invalid token"))
+            } else {
+                match serde_json::from_slice::<Request>(&request.body) {
+                    Ok(request) => {
+                        debug!("Remote request from {}: {:?}", peer, request);
+                        ("200 OK", dispatch(request, message_tx))
+                    }
+                    Err(e) => ("400 Bad Request", error(&format!("Invalid request: {}", e))),
+                }
+            }
+        }
+        (_, "/api") => ("405 Method Not Allowed", error("Use POST")),
+        _ => ("404 Not Found", error("Not found, try / or POST /api")),
+    };
+
+    write_json(stream, status, &response)
+}
+
+fn write_json(stream: TcpStream, status: &str, response: &Response) -> std::io::Result<()> {
+    let body = serde_json::to_string(response)?;
+    write_response(stream, status, "application/json", &body)
+}
+
+fn write_response(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Compare tokens without revealing through timing how much of them matched
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Send a request to the remote instance at `addr` and wait for its response
+pub fn send_request(addr: &str, token: &str, request: &Request) -> Result<Response> {
+    let fail = |what: &str, e: &dyn std::fmt::Display| {
+        WemuxError::Ipc(format!("Failed to {} {}: {}", what, addr, e))
+    };
+
+    let socket_addr = addr
+        .to_socket_addrs()
+        .map_err(|e| fail("resolve", &e))?
+        .next()
+        .ok_or_else(|| WemuxError::Ipc(format!("No address found for {}", addr)))?;
+    let mut stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)
+        .map_err(|e| fail("connect to", &e))?;
+    stream
+        .set_read_timeout(Some(RESPONSE_TIMEOUT))
+        .map_err(|e| fail("connect to", &e))?;
+
+    let body = serde_json::to_string(request)
+        .map_err(|e| WemuxError::Ipc(format!("Failed to encode request: {}", e)))?;
+    write!(
+        stream,
+        "POST /api HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        addr,
+        token,
+        body.len(),
+        body
+    )
+    .map_err(|e| fail("send request to", &e))?;
+
+    // The server closes the connection after its answer
+    let mut answer = String::new();
+    stream
+        .read_to_string(&mut answer)
+        .map_err(|e| fail("read response from", &e))?;
+    let (head, body) = answer.split_once("\r\n\r\n").unwrap_or((&answer, ""));
+
+    serde_json::from_str(body).map_err(|_| {
+        let status = head.lines().next().unwrap_or_default();
+        WemuxError::Ipc(format!("Unexpected answer from {}: {}", addr, status))
+    })
+}
@@ -1,14 +1,44 @@
 //! Service configuration file support
 
-use crate::audio::EngineConfig;
+use crate::audio::{
+    Announcement, DspStageConfig, EngineConfig, FallbackSource, MixSource, SourceType,
+    ThreadPriority, VolumeMode, Zone, BLUETOOTH_LATENCY_MS, DEFAULT_DUCK_DB,
+    DEFAULT_FALLBACK_AFTER_SECS, PAUSE_FADE_MS,
+};
+use crate::config::Backups;
+use crate::device::DeviceAliases;
+use crate::health::DEFAULT_HISTORY_DAYS;
+use crate::logging::{LogRotation, RotationPolicy, DEFAULT_LOG_KEEP, DEFAULT_LOG_MAX_SIZE_MB};
+use crate::notify::{FailureNotifier, Webhook};
+use crate::service::displays::{self, DisplayRule};
+use crate::service::schedule::{self, ActiveHours, LocalTime, Profile, ScheduleEntry};
+use crate::service::template::{self, ConfigLanguage};
+use crate::sync::{DEFAULT_DRIFT_THRESHOLD_MS, DEFAULT_MAX_CORRECTION_MS};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+/// Where the installed service runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceMode {
+    /// As LocalSystem in session 0, running before anyone signs in
+    #[default]
+    System,
+    /// As a per-user service Windows starts in the session of whoever signs
+    /// in, with that user's audio endpoints (for drivers that don't expose
+    /// them to session 0 reliably)
+    User,
+}
 
 /// Service configuration loaded from TOML file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ServiceConfig {
-    /// Audio buffer size in milliseconds
+    /// Audio buffer size in milliseconds (`EngineConfig::buffer_ms`)
     pub buffer_ms: u32,
 
     /// Specific device IDs to use (empty = auto-detect all HDMI)
@@ -23,12 +53,180 @@ pub struct ServiceConfig {
     #[serde(default)]
     pub source_device_id: String,
 
+    /// Whether the source is an output to loop back or an input to record
+    #[serde(default)]
+    pub source_type: SourceType,
+
+    /// Apps (executable names) left out of a loopback source
+    #[serde(default)]
+    pub exclude_apps: Vec<String>,
+
+    /// Pace renderers by the audio the source delivers instead of a master
+    #[serde(default)]
+    pub follow_source: bool,
+
+    /// Drift in milliseconds before an output is corrected
+    pub drift_threshold_ms: f64,
+
+    /// Most audio in milliseconds skipped per correction
+    pub max_correction_ms: f64,
+
+    /// Capture on a dedicated core with MMCSS, polling instead of sleeping
+    #[serde(default)]
+    pub performance_capture: bool,
+
+    /// Scheduling priority of the render threads
+    #[serde(default)]
+    pub render_priority: ThreadPriority,
+
+    /// Start outputs paused that reach the same physical sink as another
+    /// output, e.g. an AV receiver with two HDMI endpoints
+    #[serde(default)]
+    pub suppress_duplicates: bool,
+
+    /// Device IDs or aliases that play even when they duplicate another output
+    #[serde(default)]
+    pub allow_duplicates: Vec<String>,
+
+    /// Latency assumed for Bluetooth outputs, by which the other outputs are
+    /// delayed while one plays (0 = off)
+    pub bluetooth_latency_ms: u32,
+
+    /// Time in milliseconds an output fades out when paused and in when
+    /// resumed (0 = switch at once)
+    pub pause_fade_ms: u32,
+
+    /// Whether the outputs follow the source's volume (follow), play at
+    /// `mirror_volume` (fixed) or follow it scaled by `mirror_volume` (scaled)
+    pub volume_mode: VolumeMode,
+
+    /// Volume of the outputs in the fixed and scaled modes (0.0 - 1.0)
+    pub mirror_volume: f32,
+
+    /// Extra sources mixed into the captured audio
+    #[serde(default)]
+    pub mix_sources: Vec<MixSource>,
+
+    /// Device played instead of the source while that is silent (empty = off)
+    #[serde(default)]
+    pub fallback_device: String,
+
+    /// Seconds the source must be silent before `fallback_device` takes over
+    pub fallback_after_secs: u32,
+
+    /// Device played over the source, e.g. a paging microphone (empty = off)
+    #[serde(default)]
+    pub announcement_device: String,
+
+    /// Decibels the source is turned down by while `announcement_device` is audible
+    pub announcement_duck_db: f32,
+
     /// Log level (trace, debug, info, warn, error)
     pub log_level: String,
 
     /// Log file path (empty = no file logging)
     #[serde(default)]
     pub log_file: String,
+
+    /// When the log file is rotated (never, daily, size)
+    pub log_rotation: LogRotation,
+
+    /// Size limit of a log file in MB with `log_rotation = "size"`
+    pub log_max_size_mb: u64,
+
+    /// Rotated log files kept besides the current one
+    pub log_keep: usize,
+
+    /// Let every signed-in user control the service, not just the console
+    /// user and administrators
+    pub control_all_users: bool,
+
+    /// Address serving Prometheus metrics, e.g. "127.0.0.1:9464" (empty = off,
+    /// needs the `metrics` feature)
+    #[serde(default)]
+    pub metrics_listen: String,
+
+    /// Address accepting remote administration over HTTP, e.g. "0.0.0.0:9465"
+    /// (empty = off)
+    #[serde(default)]
+    pub remote_listen: String,
+
+    /// Token remote clients must present (required for remote administration)
+    #[serde(default)]
+    pub remote_token: String,
+
+    /// Client addresses or CIDR networks allowed to connect (empty = this
+    /// machine only)
+    #[serde(default)]
+    pub remote_allow: Vec<String>,
+
+    /// Webhook notified when the engine keeps failing, e.g. an ntfy topic
+    /// URL (empty = off)
+    #[serde(default)]
+    pub notify_url: String,
+
+    /// Minutes the engine must be failing before `notify_url` is notified
+    pub notify_after_minutes: u32,
+
+    /// Days of per-device health history kept for `wemux stats` (0 = off)
+    pub health_history_days: u32,
+
+    /// Account and session `wemux service install` sets the service up to run in
+    pub service_mode: ServiceMode,
+
+    /// Seconds Windows waits before restarting the service after it fails
+    /// (0 = don't restart; set up by `wemux service install`)
+    pub restart_delay_secs: u32,
+
+    /// Restarts within `restart_reset_hours` before Windows gives up (0 = no limit)
+    pub restart_attempts: u32,
+
+    /// Hours without a failure after which the restart count starts over
+    pub restart_reset_hours: u32,
+
+    /// Device aliases, usable in place of IDs in `device_ids`/`exclude_ids`
+    #[serde(default)]
+    pub aliases: DeviceAliases,
+
+    /// Extra delay in milliseconds per device ID or alias (`wemux calibrate --save`)
+    #[serde(default)]
+    pub delays: BTreeMap<String, u32>,
+
+    /// Buffer in milliseconds per device ID or alias, in place of `buffer_ms`
+    #[serde(default)]
+    pub device_buffer_ms: BTreeMap<String, u32>,
+
+    /// Processing stages per device ID or alias: gain, eq, delay, limiter, channels
+    #[serde(default)]
+    pub dsp: BTreeMap<String, Vec<DspStageConfig>>,
+
+    /// Core to pin the render thread to per device ID or alias
+    #[serde(default)]
+    pub render_cores: BTreeMap<String, usize>,
+
+    /// Groups of outputs with their own source, played instead of
+    /// `device_ids`
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+
+    /// Named device selections that the schedule can switch between
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Time-based profile activation; outside all entries the top-level
+    /// `device_ids`/`exclude_ids` apply
+    #[serde(default)]
+    pub schedule: Vec<ScheduleEntry>,
+
+    /// Profiles picked by the number of connected HDMI outputs, used when no
+    /// schedule entry is active
+    #[serde(default)]
+    pub displays: Vec<DisplayRule>,
+
+    /// Times the engine plays; it is stopped outside all of them (empty =
+    /// always)
+    #[serde(default)]
+    pub active_hours: Vec<ActiveHours>,
 }
 
 impl Default for ServiceConfig {
@@ -38,8 +236,51 @@ impl Default for ServiceConfig {
             device_ids: Vec::new(),
             exclude_ids: Vec::new(),
             source_device_id: String::new(),
+            source_type: SourceType::default(),
+            exclude_apps: Vec::new(),
+            follow_source: false,
+            drift_threshold_ms: DEFAULT_DRIFT_THRESHOLD_MS,
+            max_correction_ms: DEFAULT_MAX_CORRECTION_MS,
+            performance_capture: false,
+            render_priority: ThreadPriority::default(),
+            suppress_duplicates: false,
+            allow_duplicates: Vec::new(),
+            bluetooth_latency_ms: BLUETOOTH_LATENCY_MS,
+            pause_fade_ms: PAUSE_FADE_MS,
+            volume_mode: VolumeMode::default(),
+            mirror_volume: 1.0,
+            mix_sources: Vec::new(),
+            fallback_device: String::new(),
+            fallback_after_secs: DEFAULT_FALLBACK_AFTER_SECS,
+            announcement_device: String::new(),
+            announcement_duck_db: DEFAULT_DUCK_DB,
             log_level: "info".to_string(),
             log_file: String::new(),
+            log_rotation: LogRotation::default(),
+            log_max_size_mb: DEFAULT_LOG_MAX_SIZE_MB,
+            log_keep: DEFAULT_LOG_KEEP,
+            control_all_users: false,
+            metrics_listen: String::new(),
+            remote_listen: String::new(),
+            remote_token: String::new(),
+            remote_allow: Vec::new(),
+            notify_url: String::new(),
+            notify_after_minutes: 5,
+            health_history_days: DEFAULT_HISTORY_DAYS,
+            service_mode: ServiceMode::default(),
+            restart_delay_secs: 5,
+            restart_attempts: 3,
+            restart_reset_hours: 24,
+            aliases: DeviceAliases::default(),
+            delays: BTreeMap::new(),
+            device_buffer_ms: BTreeMap::new(),
+            dsp: BTreeMap::new(),
+            render_cores: BTreeMap::new(),
+            zones: Vec::new(),
+            profiles: BTreeMap::new(),
+            schedule: Vec::new(),
+            displays: Vec::new(),
+            active_hours: Vec::new(),
         }
     }
 }
@@ -65,12 +306,21 @@ impl ServiceConfig {
     /// 2. %LOCALAPPDATA%\wemux\config.toml (MSIX-compatible user data)
     /// 3. %PROGRAMDATA%\wemux\config.toml
     pub fn load_default() -> Result<Self, ConfigError> {
+        match Self::find_default() {
+            Some(config_path) => Self::load(&config_path),
+            // Return default config if no file found
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Path of the file [`load_default`](Self::load_default) reads, if any exists
+    pub fn find_default() -> Option<PathBuf> {
         // Try executable directory first
         if let Ok(exe_path) = std::env::current_exe() {
             if let Some(exe_dir) = exe_path.parent() {
                 let config_path = exe_dir.join("wemux.toml");
                 if config_path.exists() {
-                    return Self::load(&config_path);
+                    return Some(config_path);
                 }
             }
         }
@@ -79,7 +329,7 @@ impl ServiceConfig {
         if let Some(local_data) = dirs::data_local_dir() {
             let config_path = local_data.join("wemux").join("config.toml");
             if config_path.exists() {
-                return Self::load(&config_path);
+                return Some(config_path);
             }
         }
 
@@ -87,22 +337,21 @@ impl ServiceConfig {
         if let Ok(program_data) = std::env::var("PROGRAMDATA") {
             let config_path = Path::new(&program_data).join("wemux").join("config.toml");
             if config_path.exists() {
-                return Self::load(&config_path);
+                return Some(config_path);
             }
         }
 
-        // Return default config if no file found
-        Ok(Self::default())
+        None
     }
 
     /// Get the recommended configuration file path for user data
     ///
     /// Returns %LOCALAPPDATA%\wemux\config.toml (MSIX-compatible)
-    pub fn get_user_config_path() -> Option<std::path::PathBuf> {
+    pub fn get_user_config_path() -> Option<PathBuf> {
         dirs::data_local_dir().map(|d| d.join("wemux").join("config.toml"))
     }
 
-    /// Save configuration to a TOML file
+    /// Save configuration to a TOML file, backing up the file it replaces
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
         let content = toml::to_string_pretty(self).map_err(ConfigError::Serialize)?;
 
@@ -114,63 +363,166 @@ impl ServiceConfig {
             })?;
         }
 
+        if let Err(e) = Backups::new(path.as_ref()).create() {
+            warn!("Failed to back up {}: {}", path.as_ref().display(), e);
+        }
+
         std::fs::write(path.as_ref(), content).map_err(|e| ConfigError::Io {
             path: path.as_ref().to_string_lossy().to_string(),
             source: e,
         })
     }
 
+    /// Rotation of the log file
+    pub fn log_policy(&self) -> RotationPolicy {
+        RotationPolicy {
+            rotation: self.log_rotation,
+            max_size: self.log_max_size_mb * 1024 * 1024,
+            keep: self.log_keep,
+        }
+    }
+
+    /// Arguments of `sc failure` after the service name: restart after
+    /// `restart_delay_secs`, `restart_attempts` times, then do nothing
+    pub fn failure_actions(&self) -> Vec<String> {
+        let reset = format!("reset={}", self.restart_reset_hours as u64 * 3600);
+        if self.restart_delay_secs == 0 {
+            return vec![reset, "actions=".to_string()];
+        }
+        let restart = format!("restart/{}", self.restart_delay_secs as u64 * 1000);
+        // Windows repeats the last action for any further failure
+        let actions: Vec<String> = match self.restart_attempts {
+            0 => vec![restart],
+            n => (0..n)
+                .map(|_| restart.clone())
+                .chain(["none/0".to_string()])
+                .collect(),
+        };
+        vec![reset, format!("actions={}", actions.join("/"))]
+    }
+
     /// Convert to EngineConfig
     pub fn to_engine_config(&self) -> EngineConfig {
+        self.to_engine_config_for(None)
+    }
+
+    /// Name of the profile the schedule selects at `now` (None = top-level devices)
+    pub fn scheduled_profile(&self, now: LocalTime) -> Option<&str> {
+        schedule::active_profile(&self.schedule, now)
+    }
+
+    /// Whether the engine should play at `now` according to `[[active_hours]]`
+    pub fn within_active_hours(&self, now: LocalTime) -> bool {
+        schedule::within_active_hours(&self.active_hours, now)
+    }
+
+    /// Name of the profile to use at `now`: the schedule's, otherwise the
+    /// first `[[displays]]` rule matching the connected HDMI outputs
+    pub fn current_profile(&self, now: LocalTime) -> Option<&str> {
+        self.scheduled_profile(now).or_else(|| {
+            if self.displays.is_empty() {
+                return None;
+            }
+            match displays::hdmi_output_count() {
+                Ok(outputs) => displays::display_profile(&self.displays, outputs),
+                Err(e) => {
+                    warn!("Failed to count HDMI outputs for display rules: {}", e);
+                    None
+                }
+            }
+        })
+    }
+
+    /// Convert to EngineConfig using a profile's device selection
+    pub fn to_engine_config_for(&self, profile: Option<&str>) -> EngineConfig {
+        let profile = profile.and_then(|name| {
+            let found = self.profiles.get(name);
+            if found.is_none() {
+                warn!(
+                    "Unknown profile '{}' in schedule or display rules, using default devices",
+                    name
+                );
+            }
+            found
+        });
+        let (device_ids, exclude_ids) = match profile {
+            Some(profile) => (&profile.device_ids, &profile.exclude_ids),
+            None => (&self.device_ids, &self.exclude_ids),
+        };
+        let zones = match profile {
+            Some(profile) if !profile.zones.is_empty() => &profile.zones,
+            _ => &self.zones,
+        };
+
         EngineConfig {
             buffer_ms: self.buffer_ms,
-            device_ids: if self.device_ids.is_empty() {
+            device_buffer_ms: self.device_buffer_ms.clone().into_iter().collect(),
+            device_ids: if device_ids.is_empty() {
                 None
             } else {
-                Some(self.device_ids.clone())
+                Some(device_ids.clone())
             },
-            exclude_ids: if self.exclude_ids.is_empty() {
+            exclude_ids: if exclude_ids.is_empty() {
                 None
             } else {
-                Some(self.exclude_ids.clone())
+                Some(exclude_ids.clone())
             },
             source_device_id: if self.source_device_id.is_empty() {
                 None
             } else {
                 Some(self.source_device_id.clone())
             },
+            source_type: self.source_type,
+            exclude_apps: self.exclude_apps.clone(),
             paused_device_ids: None, // Service doesn't support per-device pause settings
             use_all_devices: false,  // Service uses HDMI devices only (legacy behavior)
+            exclusive: true,
+            aliases: self.aliases.clone(),
+            mix_sources: self.mix_sources.clone(),
+            fallback_source: (!self.fallback_device.is_empty()).then(|| FallbackSource {
+                device: self.fallback_device.clone(),
+                after_secs: self.fallback_after_secs,
+            }),
+            announcement: (!self.announcement_device.is_empty()).then(|| Announcement {
+                device: self.announcement_device.clone(),
+                duck_db: self.announcement_duck_db,
+            }),
+            follow_source: self.follow_source,
+            drift_threshold_ms: self.drift_threshold_ms,
+            max_correction_ms: self.max_correction_ms,
+            performance_capture: self.performance_capture,
+            render_priority: self.render_priority,
+            render_cores: self.render_cores.clone().into_iter().collect(),
+            equalizers: HashMap::new(),
+            delays: self.delays.clone().into_iter().collect(),
+            dsp: self.dsp.clone().into_iter().collect(),
+            bluetooth_latency_ms: self.bluetooth_latency_ms,
+            pause_fade_ms: self.pause_fade_ms,
+            volume_mode: self.volume_mode,
+            mirror_volume: self.mirror_volume,
+            zones: zones.clone(),
+            suppress_duplicates: self.suppress_duplicates,
+            allow_duplicates: self.allow_duplicates.clone(),
         }
     }
 
+    /// Notifier for sustained failures, if `notify_url` is set
+    pub fn failure_notifier(&self) -> Option<FailureNotifier> {
+        if self.notify_url.is_empty() {
+            return None;
+        }
+        Some(FailureNotifier::new(
+            Webhook::new(&self.notify_url),
+            Duration::from_secs(self.notify_after_minutes.max(1) as u64 * 60),
+        ))
+    }
+
     /// Generate a sample configuration file content
+    ///
+    /// Documents every field in English; see [`template::generate`] for
+    /// other languages and the short form.
     pub fn sample_config() -> String {
-        r#"# Wemux Service Configuration
-# This file configures the wemux audio sync service
-
-# Audio buffer size in milliseconds (default: 50)
-buffer_ms = 50
-
-# Specific device IDs to use (empty = auto-detect all HDMI)
-# Example: device_ids = ["Device1", "Device2"]
-device_ids = []
-
-# Device IDs to exclude from auto-detection
-# Example: exclude_ids = ["SomeDevice"]
-exclude_ids = []
-
-# Source device ID for loopback capture (empty = system default)
-source_device_id = ""
-
-# Log level: trace, debug, info, warn, error (default: info)
-log_level = "info"
-
-# Log file path (empty = no file logging)
-# Example: log_file = "C:\\Users\\<username>\\AppData\\Local\\wemux\\wemux.log"
-log_file = ""
-"#
-        .to_string()
+        template::generate(ConfigLanguage::En, true)
     }
 }
 
@@ -214,3 +566,64 @@ impl std::error::Error for ConfigError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_routes_replace_zones() {
+        let config: ServiceConfig = toml::from_str(
+            r#"
+            [[zones]]
+            name = "House"
+            devices = ["living-room", "kitchen"]
+
+            [[profiles.party.zones]]
+            name = "House"
+            devices = ["living-room", "kitchen", "patio"]
+            source = "CABLE Input"
+
+            [[profiles.party.zones]]
+            name = "Office"
+            devices = ["office"]
+
+            [profiles.quiet]
+            device_ids = ["office"]
+            "#,
+        )
+        .unwrap();
+
+        let zones = config.to_engine_config_for(Some("party")).zones;
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[0].source.as_deref(), Some("CABLE Input"));
+        // A profile without routes keeps the top-level ones
+        assert_eq!(
+            config.to_engine_config_for(Some("quiet")).zones,
+            config.zones
+        );
+    }
+
+    #[test]
+    fn test_failure_actions() {
+        let config = ServiceConfig {
+            restart_attempts: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.failure_actions(),
+            ["reset=86400", "actions=restart/5000/restart/5000/none/0"]
+        );
+
+        let config = ServiceConfig {
+            restart_delay_secs: 30,
+            restart_attempts: 0,
+            restart_reset_hours: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.failure_actions(),
+            ["reset=3600", "actions=restart/30000"]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Profile selection by connected displays
+//!
+//! A laptop that is sometimes docked to three TVs wants different outputs
+//! than the same laptop on its own. Display rules pick a profile from the
+//! number of connected HDMI outputs; the service checks them again whenever
+//! a device comes or goes.
+
+use crate::device::DeviceEnumerator;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// Activates a profile while the number of HDMI outputs is in a range
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayRule {
+    /// Name of the profile in `[profiles]`
+    pub profile: String,
+
+    /// Fewest HDMI outputs the rule applies to
+    #[serde(default)]
+    pub min_hdmi: usize,
+
+    /// Most HDMI outputs the rule applies to (None = no limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_hdmi: Option<usize>,
+}
+
+impl DisplayRule {
+    /// Check whether the rule applies to a number of HDMI outputs
+    pub fn matches(&self, hdmi_outputs: usize) -> bool {
+        hdmi_outputs >= self.min_hdmi && self.max_hdmi.is_none_or(|max| hdmi_outputs <= max)
+    }
+}
+
+/// Name of the profile for `hdmi_outputs` connected outputs: the first matching rule wins
+pub fn display_profile(rules: &[DisplayRule], hdmi_outputs: usize) -> Option<&str> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(hdmi_outputs))
+        .map(|rule| rule.profile.as_str())
+}
+
+/// Number of active HDMI outputs
+pub fn hdmi_output_count() -> Result<usize> {
+    let devices = DeviceEnumerator::new()?.enumerate_all_devices()?;
+    Ok(devices.iter().filter(|device| device.is_hdmi).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_profile() {
+        #[derive(Deserialize)]
+        struct Config {
+            displays: Vec<DisplayRule>,
+        }
+
+        let Config { displays } = toml::from_str(
+            r#"
+            [[displays]]
+            profile = "docked"
+            min_hdmi = 3
+
+            [[displays]]
+            profile = "single"
+            min_hdmi = 1
+            max_hdmi = 1
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(display_profile(&displays, 4), Some("docked"));
+        assert_eq!(display_profile(&displays, 3), Some("docked"));
+        assert_eq!(display_profile(&displays, 2), None);
+        assert_eq!(display_profile(&displays, 1), Some("single"));
+        assert_eq!(display_profile(&displays, 0), None);
+    }
+}
@@ -0,0 +1,248 @@
+//! Named service instances
+//!
+//! The service can be installed several times under different names, each
+//! with a configuration file of its own, e.g. one service per zone
+//! (`wemux service install --name wemux-patio --config C:\cfg\patio.toml`).
+//! `wemux service install` passes the name and file to `wemux-service.exe`
+//! on its command line, which [`ServiceInstance::from_args`] reads back.
+//!
+//! Installed as a user service, the registered name is only a template:
+//! Windows starts a copy named `<name>_<LUID>` in each user's session, which
+//! [`ServiceInstance::user_instances`] finds.
+
+use crate::error::{Result, WemuxError};
+use crate::health;
+use crate::ipc::SERVICE_PIPE_NAME;
+use crate::service::config::{ConfigError, ServiceConfig};
+use crate::service::{SERVICE_DISPLAY_NAME, SERVICE_NAME};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::ERROR_MORE_DATA;
+use windows::Win32::System::Services::{
+    CloseServiceHandle, EnumServicesStatusExW, OpenSCManagerW, ENUM_SERVICE_STATUS_PROCESSW,
+    ENUM_SERVICE_TYPE, SC_ENUM_PROCESS_INFO, SC_MANAGER_ENUMERATE_SERVICE, SERVICE_STATE_ALL,
+    SERVICE_USER_OWN_PROCESS,
+};
+
+/// Service type bit of a per-user copy of a user service template
+const SERVICE_USERSERVICE_INSTANCE: u32 = 0x80;
+
+/// Name and configuration file of one installed service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceInstance {
+    /// Name registered with the Service Control Manager
+    pub name: String,
+    /// Configuration file (None = the default locations)
+    pub config: Option<PathBuf>,
+}
+
+impl Default for ServiceInstance {
+    fn default() -> Self {
+        Self {
+            name: SERVICE_NAME.to_string(),
+            config: None,
+        }
+    }
+}
+
+impl ServiceInstance {
+    /// Instance named `name`; letters, digits, '-' and '_' only
+    pub fn new(name: &str, config: Option<PathBuf>) -> Result<Self> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(WemuxError::InvalidConfig(format!(
+                "Invalid service name '{}': use letters, digits, '-' and '_'",
+                name
+            )));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            config,
+        })
+    }
+
+    /// Read `--name` and `--config` from the service executable's arguments
+    /// (without the program name); both are optional
+    pub fn from_args(args: impl IntoIterator<Item = OsString>) -> Result<Self> {
+        let mut name = SERVICE_NAME.to_string();
+        let mut config = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next().ok_or_else(|| {
+                    WemuxError::InvalidConfig(format!("{} needs a value", arg.to_string_lossy()))
+                })
+            };
+            match arg.to_str() {
+                Some("--name") => name = value()?.to_string_lossy().into_owned(),
+                Some("--config") => config = Some(PathBuf::from(value()?)),
+                _ => {
+                    return Err(WemuxError::InvalidConfig(format!(
+                        "Unknown service argument: {}",
+                        arg.to_string_lossy()
+                    )))
+                }
+            }
+        }
+        Self::new(&name, config)
+    }
+
+    /// Arguments the service executable is registered with to run this instance
+    pub fn to_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if !self.is_default() {
+            args.extend(["--name".into(), self.name.clone().into()]);
+        }
+        if let Some(config) = &self.config {
+            args.extend(["--config".into(), config.clone().into_os_string()]);
+        }
+        args
+    }
+
+    /// Whether this is the service installed without `--name`
+    pub fn is_default(&self) -> bool {
+        self.name == SERVICE_NAME
+    }
+
+    /// Name shown in services.msc
+    pub fn display_name(&self) -> String {
+        if self.is_default() {
+            SERVICE_DISPLAY_NAME.to_string()
+        } else {
+            format!("{} ({})", SERVICE_DISPLAY_NAME, self.name)
+        }
+    }
+
+    /// Pipe the instance answers requests on; [`SERVICE_PIPE_NAME`] for the
+    /// default instance
+    pub fn pipe_name(&self) -> String {
+        if self.is_default() {
+            SERVICE_PIPE_NAME.to_string()
+        } else {
+            format!("{}-{}", SERVICE_PIPE_NAME, self.name)
+        }
+    }
+
+    /// File the instance keeps its per-device health history in
+    pub fn health_path(&self) -> Option<PathBuf> {
+        let path = health::default_path()?;
+        if self.is_default() {
+            return Some(path);
+        }
+        Some(path.with_file_name(format!("health-{}.jsonl", self.name)))
+    }
+
+    /// Configuration file the instance reads, if one exists
+    pub fn config_path(&self) -> Option<PathBuf> {
+        match &self.config {
+            Some(path) => Some(path.clone()),
+            None => ServiceConfig::find_default(),
+        }
+    }
+
+    /// Per-user copies Windows started of this service installed as a user
+    /// service, e.g. `wemux_4a7f2`; these are what run, stop and report a
+    /// state, not the template
+    pub fn user_instances(&self) -> Vec<String> {
+        let service_type =
+            ENUM_SERVICE_TYPE(SERVICE_USER_OWN_PROCESS.0 | SERVICE_USERSERVICE_INSTANCE);
+        let mut names = Vec::new();
+        unsafe {
+            let Ok(manager) =
+                OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_ENUMERATE_SERVICE)
+            else {
+                return names;
+            };
+            // u64 keeps the entries' pointers aligned
+            let mut buffer = vec![0u64; 4096];
+            let mut resume = 0u32;
+            loop {
+                let mut needed = 0u32;
+                let mut count = 0u32;
+                let bytes = std::slice::from_raw_parts_mut(
+                    buffer.as_mut_ptr() as *mut u8,
+                    buffer.len() * 8,
+                );
+                let result = EnumServicesStatusExW(
+                    manager,
+                    SC_ENUM_PROCESS_INFO,
+                    service_type,
+                    SERVICE_STATE_ALL,
+                    Some(bytes),
+                    &mut needed,
+                    &mut count,
+                    Some(&mut resume),
+                    PCWSTR::null(),
+                );
+                let entries = std::slice::from_raw_parts(
+                    buffer.as_ptr() as *const ENUM_SERVICE_STATUS_PROCESSW,
+                    count as usize,
+                );
+                for entry in entries {
+                    let service_type = entry.ServiceStatusProcess.dwServiceType.0;
+                    let name = entry.lpServiceName.to_string().unwrap_or_default();
+                    if service_type & SERVICE_USERSERVICE_INSTANCE != 0
+                        && is_user_instance(&name, &self.name)
+                    {
+                        names.push(name);
+                    }
+                }
+                match result {
+                    Err(e) if e.code() == ERROR_MORE_DATA.to_hresult() => {
+                        if count == 0 {
+                            buffer.resize((needed as usize).div_ceil(8), 0);
+                        }
+                    }
+                    _ => break,
+                }
+            }
+            let _ = CloseServiceHandle(manager);
+        }
+        names
+    }
+
+    /// Load the instance's configuration; defaults if it has no file
+    pub fn load_config(&self) -> std::result::Result<ServiceConfig, ConfigError> {
+        match &self.config {
+            Some(path) => ServiceConfig::load(path),
+            None => ServiceConfig::load_default(),
+        }
+    }
+}
+
+/// Whether `name` is a per-user copy of the template `template`:
+/// `<template>_<LUID in hex>`
+fn is_user_instance(name: &str, template: &str) -> bool {
+    name.strip_prefix(template)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .is_some_and(|luid| !luid.is_empty() && luid.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_args_roundtrip() {
+        let default = ServiceInstance::from_args(Vec::new()).unwrap();
+        assert_eq!(default, ServiceInstance::default());
+        assert!(default.to_args().is_empty());
+        assert_eq!(default.pipe_name(), SERVICE_PIPE_NAME);
+
+        let patio =
+            ServiceInstance::new("wemux-patio", Some(PathBuf::from(r"C:\cfg\patio.toml"))).unwrap();
+        assert_eq!(ServiceInstance::from_args(patio.to_args()).unwrap(), patio);
+        assert_eq!(patio.display_name(), "Wemux Audio Sync (wemux-patio)");
+        assert_eq!(patio.pipe_name(), r"\\.\pipe\wemux-service-wemux-patio");
+
+        assert!(ServiceInstance::new("patio zone", None).is_err());
+        assert!(is_user_instance("wemux_4a7f2", "wemux"));
+        assert!(!is_user_instance("wemux_patio", "wemux"));
+        assert!(!is_user_instance("wemux-patio_4a7f2", "wemux"));
+        assert!(ServiceInstance::from_args(vec!["--name".into()]).is_err());
+    }
+}
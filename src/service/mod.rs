@@ -7,10 +7,18 @@
 mod runner;
 
 pub mod config;
+pub mod displays;
+pub mod instance;
+pub mod power;
+pub mod recovery;
+pub mod schedule;
+pub mod template;
 
 #[cfg(all(windows, feature = "service"))]
 pub use runner::run_service;
 
+pub use instance::ServiceInstance;
+
 /// Service name used for registration
 pub const SERVICE_NAME: &str = "wemux";
 
@@ -0,0 +1,92 @@
+//! Restarting the engine around sleep
+//!
+//! Audio clients don't survive S3 sleep: after a resume every client the
+//! engine holds is invalidated and it never plays again. The service and the
+//! tray stop the engine when the computer suspends and start a new one once
+//! it resumed, giving the audio endpoints [`RESUME_SETTLE`] to come back
+//! first.
+
+use std::time::{Duration, Instant};
+
+/// Time the audio endpoints get to come back after a resume
+pub const RESUME_SETTLE: Duration = Duration::from_secs(3);
+
+/// Starts tried after a resume before giving up
+const RESUME_ATTEMPTS: u32 = 5;
+
+/// Pending start of the engine after a resume
+#[derive(Debug, Default)]
+pub struct ResumeRestart {
+    due: Option<Instant>,
+    attempts: u32,
+}
+
+impl ResumeRestart {
+    /// The computer resumed at `now`: start the engine [`RESUME_SETTLE`] later
+    pub fn resume(&mut self, now: Instant) {
+        self.due = Some(now + RESUME_SETTLE);
+        self.attempts = 0;
+    }
+
+    /// Forget a pending start, e.g. as the computer suspends again
+    pub fn cancel(&mut self) {
+        self.due = None;
+    }
+
+    /// Whether a start is scheduled
+    pub fn is_pending(&self) -> bool {
+        self.due.is_some()
+    }
+
+    /// Whether a start is due at `now`; true once per scheduled start
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        if self.due.is_some_and(|due| now >= due) {
+            self.due = None;
+            self.attempts += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The start failed at `now`: try again [`RESUME_SETTLE`] later, unless
+    /// that was the last attempt. Returns whether it will.
+    pub fn failed(&mut self, now: Instant) -> bool {
+        if self.attempts >= RESUME_ATTEMPTS {
+            return false;
+        }
+        self.due = Some(now + RESUME_SETTLE);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_restart_retries_then_gives_up() {
+        let start = Instant::now();
+        let mut restart = ResumeRestart::default();
+        assert!(!restart.is_due(start));
+
+        restart.resume(start);
+        assert!(!restart.is_due(start + RESUME_SETTLE / 2));
+        let mut now = start + RESUME_SETTLE;
+        assert!(restart.is_due(now));
+        assert!(!restart.is_due(now));
+
+        for _ in 1..RESUME_ATTEMPTS {
+            assert!(restart.failed(now));
+            now += RESUME_SETTLE;
+            assert!(restart.is_due(now));
+        }
+        assert!(!restart.failed(now));
+        assert!(!restart.is_due(now + RESUME_SETTLE));
+
+        // Suspending again drops the pending start
+        restart.resume(now);
+        restart.cancel();
+        assert!(!restart.is_due(now + RESUME_SETTLE));
+    }
+}
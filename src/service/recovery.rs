@@ -0,0 +1,125 @@
+//! Restarting an engine that died while the service runs
+//!
+//! An engine can stop on its own: a fatal engine event, or a start after a
+//! profile switch that failed. The service starts it again after an
+//! exponential backoff; once [`RECOVERY_ATTEMPTS`] starts in a row failed
+//! it gives up and exits with an error, so the recovery actions Windows has
+//! for the service (`wemux service install` sets them up) take over.
+
+use crate::kiosk::backoff;
+use std::time::{Duration, Instant};
+
+/// Starts tried in a row before the service gives up
+const RECOVERY_ATTEMPTS: u32 = 5;
+
+/// Longest wait between two starts
+const RECOVERY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A run this long means the next failure is a new incident
+const RECOVERY_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// What the service should do about its engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Nothing to do
+    Idle,
+    /// The engine died; it is started again after this delay
+    Scheduled(Duration),
+    /// Start the engine now
+    Start,
+    /// Starting failed too often; the service should exit with an error
+    GiveUp,
+}
+
+/// Restarts of a dead engine with backoff
+#[derive(Debug, Default)]
+pub struct EngineRecovery {
+    due: Option<Instant>,
+    attempts: u32,
+    running_since: Option<Instant>,
+}
+
+impl EngineRecovery {
+    /// Decide at `now` what to do about an engine that should be running
+    /// (`wanted`) and is or isn't (`running`)
+    pub fn poll(&mut self, now: Instant, wanted: bool, running: bool) -> Recovery {
+        if running {
+            let since = *self.running_since.get_or_insert(now);
+            if now.duration_since(since) >= RECOVERY_RESET_AFTER {
+                self.attempts = 0;
+            }
+            self.due = None;
+            return Recovery::Idle;
+        }
+        self.running_since = None;
+
+        if !wanted {
+            // Stopped on purpose
+            self.due = None;
+            self.attempts = 0;
+            return Recovery::Idle;
+        }
+        match self.due {
+            None if self.attempts >= RECOVERY_ATTEMPTS => Recovery::GiveUp,
+            None => {
+                self.attempts += 1;
+                let delay = backoff(self.attempts, RECOVERY_MAX_DELAY);
+                self.due = Some(now + delay);
+                Recovery::Scheduled(delay)
+            }
+            Some(due) if now >= due => {
+                self.due = None;
+                Recovery::Start
+            }
+            Some(_) => Recovery::Idle,
+        }
+    }
+
+    /// Forget a pending start, e.g. while the computer sleeps
+    pub fn cancel(&mut self) {
+        self.due = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_engine_is_restarted_then_given_up() {
+        let mut now = Instant::now();
+        let mut recovery = EngineRecovery::default();
+        assert_eq!(recovery.poll(now, true, true), Recovery::Idle);
+        assert_eq!(recovery.poll(now, false, false), Recovery::Idle);
+
+        for attempt in 1..=RECOVERY_ATTEMPTS {
+            let delay = backoff(attempt, RECOVERY_MAX_DELAY);
+            assert_eq!(recovery.poll(now, true, false), Recovery::Scheduled(delay));
+            assert_eq!(recovery.poll(now, true, false), Recovery::Idle);
+            now += delay;
+            assert_eq!(recovery.poll(now, true, false), Recovery::Start);
+        }
+        assert_eq!(recovery.poll(now, true, false), Recovery::GiveUp);
+    }
+
+    #[test]
+    fn test_long_run_resets_the_attempts() {
+        let mut now = Instant::now();
+        let mut recovery = EngineRecovery::default();
+        for _ in 0..RECOVERY_ATTEMPTS - 1 {
+            let Recovery::Scheduled(delay) = recovery.poll(now, true, false) else {
+                panic!("expected a scheduled start");
+            };
+            now += delay;
+            assert_eq!(recovery.poll(now, true, false), Recovery::Start);
+        }
+
+        recovery.poll(now, true, true);
+        recovery.poll(now + RECOVERY_RESET_AFTER, true, true);
+        now += RECOVERY_RESET_AFTER;
+        assert_eq!(
+            recovery.poll(now, true, false),
+            Recovery::Scheduled(backoff(1, RECOVERY_MAX_DELAY))
+        );
+    }
+}
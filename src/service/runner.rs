@@ -1,18 +1,33 @@
 //! Windows Service runner implementation
 
 use crate::audio::AudioEngine;
+use crate::audio::{EngineConfig, EngineEvent};
+use crate::config::ConfigWatcher;
+use crate::device::{DeviceEvent, DeviceMonitor};
+use crate::error::WemuxError;
+use crate::health::HealthRecorder;
+use crate::ipc::{answer_request, IpcMessage, PipeAccess, PipeServer, Request};
+use crate::logging::{self, RollingFile};
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsServer;
+use crate::remote::{AllowList, RemoteServer};
 use crate::service::config::ServiceConfig;
-use crate::service::{SERVICE_DISPLAY_NAME, SERVICE_NAME};
+use crate::service::power::ResumeRestart;
+use crate::service::recovery::{EngineRecovery, Recovery};
+use crate::service::schedule::{ActiveHoursTracker, LocalTime};
+use crate::service::ServiceInstance;
+use crossbeam_channel::{bounded, unbounded, Sender};
 use std::ffi::OsString;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use tracing::{error, info, warn};
 use windows_service::{
     define_windows_service,
     service::{
-        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
-        ServiceType,
+        PowerEventParam, ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState,
+        ServiceStatus, ServiceType,
     },
     service_control_handler::{self, ServiceControlHandlerResult},
     service_dispatcher,
@@ -20,12 +35,26 @@ use windows_service::{
 
 const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
 
+/// Instance this process runs, set before the dispatcher starts
+static INSTANCE: OnceLock<ServiceInstance> = OnceLock::new();
+
+/// How often the profile schedule and active hours are re-evaluated
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time to let a burst of hotplug events settle before applying display rules
+const HOTPLUG_SETTLE: Duration = Duration::from_secs(2);
+
+/// How often the configuration file is checked for changes
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
 /// Run the Windows service
 ///
 /// This is the main entry point called from the service binary.
 /// It registers with the Service Control Manager and starts the service dispatcher.
-pub fn run_service() -> Result<(), windows_service::Error> {
-    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+pub fn run_service(instance: ServiceInstance) -> Result<(), windows_service::Error> {
+    let name = instance.name.clone();
+    let _ = INSTANCE.set(instance);
+    service_dispatcher::start(name, ffi_service_main)
 }
 
 // Generate the Windows service entry point
@@ -39,8 +68,11 @@ fn service_main(arguments: Vec<OsString>) {
 }
 
 fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error::Error>> {
-    // Load configuration
-    let config = ServiceConfig::load_default().unwrap_or_else(|e| {
+    let instance = INSTANCE.get_or_init(ServiceInstance::default);
+
+    // Load configuration, remembering which file so edits can be picked up
+    let mut config_watcher = ConfigWatcher::new(instance.config_path());
+    let mut config = instance.load_config().unwrap_or_else(|e| {
         eprintln!("Warning: Failed to load config: {}, using defaults", e);
         ServiceConfig::default()
     });
@@ -48,15 +80,20 @@ fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error:
     // Initialize logging
     init_logging(&config);
 
-    info!("Starting {} service", SERVICE_DISPLAY_NAME);
+    info!("Starting {} service", instance.display_name());
 
     // Create stop flag
     let stop_flag = Arc::new(AtomicBool::new(false));
     let stop_flag_handler = stop_flag.clone();
+    // Set when the computer suspends or resumes, for the service loop to act on
+    let suspending = Arc::new(AtomicBool::new(false));
+    let suspending_handler = suspending.clone();
+    let resumed = Arc::new(AtomicBool::new(false));
+    let resumed_handler = resumed.clone();
 
     // Register service control handler
     let status_handle = service_control_handler::register(
-        SERVICE_NAME,
+        &instance.name,
         move |control_event| -> ServiceControlHandlerResult {
             match control_event {
                 ServiceControl::Stop | ServiceControl::Shutdown => {
@@ -64,6 +101,15 @@ fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error:
                     stop_flag_handler.store(true, Ordering::SeqCst);
                     ServiceControlHandlerResult::NoError
                 }
+                ServiceControl::PowerEvent(PowerEventParam::Suspend) => {
+                    suspending_handler.store(true, Ordering::SeqCst);
+                    ServiceControlHandlerResult::NoError
+                }
+                ServiceControl::PowerEvent(PowerEventParam::ResumeAutomatic) => {
+                    resumed_handler.store(true, Ordering::SeqCst);
+                    ServiceControlHandlerResult::NoError
+                }
+                ServiceControl::PowerEvent(_) => ServiceControlHandlerResult::NoError,
                 ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
                 _ => ServiceControlHandlerResult::NotImplemented,
             }
@@ -81,30 +127,255 @@ fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error:
         process_id: None,
     })?;
 
-    // Create and start the audio engine
-    let engine_config = config.to_engine_config();
+    // Create and start the audio engine with the profile the schedule or the
+    // connected displays select
+    let mut active_profile = config.current_profile(LocalTime::now()).map(str::to_string);
+    if let Some(profile) = &active_profile {
+        info!("Active profile: {}", profile);
+    }
+    let engine_config = engine_config(instance, &config, active_profile.as_deref());
     let mut engine = AudioEngine::new(engine_config);
+    let (event_tx, event_rx) = unbounded();
+    engine.set_event_channel(event_tx);
+
+    // Outside the active hours the service runs with the engine stopped
+    let mut active_hours = ActiveHoursTracker::new(&config.active_hours, LocalTime::now());
+    let started = if active_hours.within() {
+        engine.start()
+    } else {
+        info!("Outside active hours, audio engine not started");
+        Ok(())
+    };
 
-    match engine.start() {
+    let mut exit_code = ServiceExitCode::Win32(0);
+    match started {
         Ok(()) => {
-            info!("Audio engine started successfully");
+            if engine.is_running() {
+                info!("Audio engine started successfully");
+            }
 
             // Report service running
             status_handle.set_service_status(ServiceStatus {
                 service_type: SERVICE_TYPE,
                 current_state: ServiceState::Running,
-                controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+                controls_accepted: ServiceControlAccept::STOP
+                    | ServiceControlAccept::SHUTDOWN
+                    | ServiceControlAccept::POWER_EVENT,
                 exit_code: ServiceExitCode::Win32(0),
                 checkpoint: 0,
                 wait_hint: Duration::default(),
                 process_id: None,
             })?;
 
-            // Main service loop
-            while !stop_flag.load(Ordering::SeqCst) && engine.is_running() {
+            // Accept remote control requests (e.g. from the tray)
+            let (ipc_tx, ipc_rx) = bounded(16);
+            let access = if config.control_all_users {
+                PipeAccess::AllUsers
+            } else {
+                PipeAccess::ConsoleUser
+            };
+            let ipc_server =
+                match PipeServer::start_shared(&instance.pipe_name(), access, ipc_tx.clone()) {
+                    Ok(server) => Some(server),
+                    Err(e) => {
+                        warn!("IPC server unavailable: {}", e);
+                        None
+                    }
+                };
+            // Remote administration and metrics scrapes use the same channel
+            let _remote_server = start_remote(&config, ipc_tx.clone());
+            #[cfg(feature = "metrics")]
+            let _metrics_server = start_metrics(&config, ipc_tx);
+            #[cfg(not(feature = "metrics"))]
+            if !config.metrics_listen.is_empty() {
+                warn!("metrics_listen is set, but this build lacks the 'metrics' feature");
+            }
+
+            // Whether the engine should run; only remote Start/Stop and the active
+            // hours change it, so an engine that dies or fails to start after a
+            // profile switch is started again
+            let mut keep_running = active_hours.within();
+            let mut recovery = EngineRecovery::default();
+            let mut asleep = false;
+            let mut gave_up = false;
+            let mut last_schedule_check = Instant::now();
+            let mut hotplug_at: Option<Instant> = None;
+            let mut last_config_check = Instant::now();
+            let mut notifier = config.failure_notifier();
+            let mut resume_restart = ResumeRestart::default();
+            let mut health = start_health(instance, &config);
+
+            // Outputs coming and going can change which display rule applies
+            let (device_tx, device_rx) = unbounded();
+            let _device_monitor = DeviceMonitor::new(device_tx)
+                .map_err(|e| warn!("Display rules won't follow hotplug: {}", e))
+                .ok();
+
+            // Main service loop - the engine may be stopped and restarted remotely,
+            // the service itself only exits on an SCM stop
+            while !stop_flag.load(Ordering::SeqCst) {
+                while let Ok(message) = ipc_rx.try_recv() {
+                    match message.request {
+                        Request::Start | Request::Restart => keep_running = true,
+                        Request::Stop => keep_running = false,
+                        _ => {}
+                    }
+                    let response = answer_request(&mut engine, message.request);
+                    let _ = message.reply.send(response);
+                }
+
+                while let Ok(event) = device_rx.try_recv() {
+                    if matches!(
+                        event,
+                        DeviceEvent::Added(_)
+                            | DeviceEvent::Removed(_)
+                            | DeviceEvent::StateChanged { .. }
+                    ) && !config.displays.is_empty()
+                    {
+                        hotplug_at = Some(Instant::now());
+                    }
+                }
+
+                // Audio clients don't survive sleep: stop before, start anew after
+                if suspending.swap(false, Ordering::SeqCst) {
+                    asleep = true;
+                    resume_restart.cancel();
+                    recovery.cancel();
+                    if engine.is_running() {
+                        info!("Computer suspending, stopping audio engine");
+                        if let Err(e) = engine.stop() {
+                            warn!("Error stopping engine: {}", e);
+                        }
+                    }
+                }
+                if resumed.swap(false, Ordering::SeqCst) {
+                    asleep = false;
+                    info!("Computer resumed");
+                    resume_restart.resume(Instant::now());
+                }
+                if resume_restart.is_due(Instant::now()) && keep_running {
+                    info!("Restarting audio engine after resume");
+                    // A suspend that wasn't reported leaves a stale engine behind
+                    if let Err(e) = engine.restart() {
+                        if resume_restart.failed(Instant::now()) {
+                            warn!("Failed to start audio engine after resume, retrying: {}", e);
+                        } else {
+                            error!("Failed to start audio engine after resume: {}", e);
+                        }
+                    }
+                }
+
+                let hotplug_settled = hotplug_at.is_some_and(|at| at.elapsed() >= HOTPLUG_SETTLE);
+                if hotplug_settled
+                    || ((!config.schedule.is_empty()
+                        || !config.displays.is_empty()
+                        || !config.active_hours.is_empty())
+                        && last_schedule_check.elapsed() >= SCHEDULE_CHECK_INTERVAL)
+                {
+                    last_schedule_check = Instant::now();
+                    hotplug_at = None;
+                    if let Some(within) =
+                        active_hours.update(&config.active_hours, LocalTime::now())
+                    {
+                        keep_running = within;
+                        let result = if within {
+                            info!("Active hours began, starting audio engine");
+                            if engine.is_running() {
+                                Ok(())
+                            } else {
+                                engine.start()
+                            }
+                        } else {
+                            info!("Active hours ended, stopping audio engine");
+                            engine.stop()
+                        };
+                        if let Err(e) = result {
+                            error!("Failed to follow active hours: {}", e);
+                        }
+                    }
+                    let current = config.current_profile(LocalTime::now());
+                    if current != active_profile.as_deref() {
+                        active_profile = current.map(str::to_string);
+                        info!(
+                            "Switching to profile: {}",
+                            active_profile.as_deref().unwrap_or("(default devices)")
+                        );
+                        apply_profile(
+                            &mut engine,
+                            instance,
+                            &config,
+                            active_profile.as_deref(),
+                            keep_running,
+                        );
+                    }
+                }
+
+                if last_config_check.elapsed() >= CONFIG_CHECK_INTERVAL {
+                    last_config_check = Instant::now();
+                    if config_watcher.check(instance.config_path()) {
+                        reload_config(
+                            &mut engine,
+                            instance,
+                            &mut config,
+                            &mut active_profile,
+                            config_watcher.path(),
+                            keep_running,
+                        );
+                    }
+                }
+
+                // A fatal error leaves the engine without its threads
+                for event in event_rx.try_iter() {
+                    if let EngineEvent::Fatal(reason) = event {
+                        if engine.is_running() {
+                            error!("Audio engine failed: {}", reason);
+                            if let Err(e) = engine.stop() {
+                                warn!("Error stopping engine: {}", e);
+                            }
+                        }
+                    }
+                }
+
+                // Start an engine that should run but doesn't, unless the
+                // computer is sleeping or the start after a resume is pending
+                let wanted = keep_running && !asleep && !resume_restart.is_pending();
+                match recovery.poll(Instant::now(), wanted, engine.is_running()) {
+                    Recovery::Idle => {}
+                    Recovery::Scheduled(delay) => {
+                        warn!("Audio engine not running, starting it in {:?}", delay);
+                    }
+                    Recovery::Start => match engine.start() {
+                        Ok(()) => info!("Audio engine started again"),
+                        Err(e) => warn!("Failed to start audio engine: {}", e),
+                    },
+                    Recovery::GiveUp => {
+                        error!("Audio engine keeps failing, stopping the service");
+                        gave_up = true;
+                        break;
+                    }
+                }
+
+                // An engine that should run but doesn't is failing
+                if let Some(notifier) = &mut notifier {
+                    notifier.update(keep_running && !engine.is_running(), None);
+                }
+
+                if let Some(recorder) = &mut health {
+                    if let Err(e) = recorder.update(Instant::now(), || engine.get_stats()) {
+                        warn!("Failed to record device health: {}", e);
+                    }
+                }
+
                 std::thread::sleep(Duration::from_millis(100));
             }
 
+            drop(ipc_server);
+
+            // Exiting with an error lets Windows restart the service
+            if gave_up {
+                exit_code = ServiceExitCode::Win32(1);
+            }
+
             // Report service stopping
             status_handle.set_service_status(ServiceStatus {
                 service_type: SERVICE_TYPE,
@@ -120,9 +391,17 @@ fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error:
             if let Err(e) = engine.stop() {
                 warn!("Error stopping engine: {}", e);
             }
+            if let Some(recorder) = &mut health {
+                if let Err(e) = recorder.flush() {
+                    warn!("Failed to record device health: {}", e);
+                }
+            }
         }
         Err(e) => {
             error!("Failed to start audio engine: {}", e);
+            if matches!(e, WemuxError::EngineInUse) {
+                error!("Exit the wemux tray or 'wemux start' so the service can run the engine");
+            }
 
             // Report service stopped with error
             status_handle.set_service_status(ServiceStatus {
@@ -144,7 +423,7 @@ fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error:
         service_type: SERVICE_TYPE,
         current_state: ServiceState::Stopped,
         controls_accepted: ServiceControlAccept::empty(),
-        exit_code: ServiceExitCode::Win32(0),
+        exit_code,
         checkpoint: 0,
         wait_hint: Duration::default(),
         process_id: None,
@@ -154,25 +433,185 @@ fn run_service_main(_arguments: Vec<OsString>) -> Result<(), Box<dyn std::error:
     Ok(())
 }
 
+/// Engine configuration of `instance` for a profile of `config`
+///
+/// Only the default instance holds the machine-wide engine lock, so named
+/// instances playing other outputs run side by side.
+fn engine_config(
+    instance: &ServiceInstance,
+    config: &ServiceConfig,
+    profile: Option<&str>,
+) -> EngineConfig {
+    EngineConfig {
+        exclusive: instance.is_default(),
+        ..config.to_engine_config_for(profile)
+    }
+}
+
+/// Apply a profile of `config` to the engine
+///
+/// A running engine keeps playing on the outputs the change doesn't touch;
+/// one that should run but doesn't (e.g. after a failed switch) is started.
+fn apply_profile(
+    engine: &mut AudioEngine,
+    instance: &ServiceInstance,
+    config: &ServiceConfig,
+    profile: Option<&str>,
+    keep_running: bool,
+) {
+    let result = engine
+        .reconfigure(engine_config(instance, config, profile))
+        .and_then(|()| {
+            if keep_running && !engine.is_running() {
+                engine.start()
+            } else {
+                Ok(())
+            }
+        });
+    if let Err(e) = result {
+        error!("Failed to restart engine: {}", e);
+    }
+}
+
+/// Apply an edited configuration file
+///
+/// An invalid file is ignored so a half-finished edit doesn't stop playback.
+/// The engine is only touched if its effective configuration changed.
+fn reload_config(
+    engine: &mut AudioEngine,
+    instance: &ServiceInstance,
+    config: &mut ServiceConfig,
+    active_profile: &mut Option<String>,
+    path: Option<&Path>,
+    keep_running: bool,
+) {
+    let new_config = match path {
+        Some(path) => match ServiceConfig::load(path) {
+            Ok(new_config) => new_config,
+            Err(e) => {
+                warn!("Ignoring changed configuration: {}", e);
+                return;
+            }
+        },
+        None => ServiceConfig::default(),
+    };
+    info!(
+        "Configuration reloaded from {}",
+        path.map_or("(defaults)".into(), |p| p.display().to_string())
+    );
+
+    if new_config.log_level != config.log_level
+        || new_config.log_file != config.log_file
+        || new_config.log_policy() != config.log_policy()
+    {
+        warn!("Logging changes take effect when the service restarts; 'wemux log-level' changes the level now");
+    }
+    if new_config.metrics_listen != config.metrics_listen {
+        warn!("Metrics address changes take effect when the service restarts");
+    }
+    if new_config.control_all_users != config.control_all_users {
+        warn!("control_all_users takes effect when the service restarts");
+    }
+    if new_config.remote_listen != config.remote_listen
+        || new_config.remote_token != config.remote_token
+        || new_config.remote_allow != config.remote_allow
+    {
+        warn!("Remote administration changes take effect when the service restarts");
+    }
+    if new_config.notify_url != config.notify_url
+        || new_config.notify_after_minutes != config.notify_after_minutes
+    {
+        warn!("Notification changes take effect when the service restarts");
+    }
+
+    let new_profile = new_config
+        .current_profile(LocalTime::now())
+        .map(str::to_string);
+    let engine_changed = new_config.to_engine_config_for(new_profile.as_deref())
+        != config.to_engine_config_for(active_profile.as_deref());
+
+    *config = new_config;
+    *active_profile = new_profile;
+    if engine_changed {
+        info!(
+            "Applying profile: {}",
+            active_profile.as_deref().unwrap_or("(default devices)")
+        );
+        apply_profile(
+            engine,
+            instance,
+            config,
+            active_profile.as_deref(),
+            keep_running,
+        );
+    }
+}
+
+/// Start the remote administration endpoint if one is configured
+fn start_remote(config: &ServiceConfig, message_tx: Sender<IpcMessage>) -> Option<RemoteServer> {
+    if config.remote_listen.is_empty() {
+        return None;
+    }
+    let server = AllowList::parse(&config.remote_allow).and_then(|allow| {
+        RemoteServer::start(
+            &config.remote_listen,
+            &config.remote_token,
+            allow,
+            message_tx,
+        )
+    });
+    match server {
+        Ok(server) => Some(server),
+        Err(e) => {
+            warn!("Remote administration unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// Record per-device health unless the history is turned off
+fn start_health(instance: &ServiceInstance, config: &ServiceConfig) -> Option<HealthRecorder> {
+    if config.health_history_days == 0 {
+        return None;
+    }
+    let Some(path) = instance.health_path() else {
+        warn!("PROGRAMDATA is not set, device health won't be recorded");
+        return None;
+    };
+    info!("Recording device health to {}", path.display());
+    Some(HealthRecorder::new(path, config.health_history_days))
+}
+
+/// Start the Prometheus endpoint if one is configured
+#[cfg(feature = "metrics")]
+fn start_metrics(config: &ServiceConfig, message_tx: Sender<IpcMessage>) -> Option<MetricsServer> {
+    if config.metrics_listen.is_empty() {
+        return None;
+    }
+    match MetricsServer::start(&config.metrics_listen, message_tx) {
+        Ok(server) => Some(server),
+        Err(e) => {
+            warn!("Metrics endpoint unavailable: {}", e);
+            None
+        }
+    }
+}
+
 /// Initialize logging for service mode
 fn init_logging(config: &ServiceConfig) {
     use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-    let filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level));
+    let filter = logging::reloadable_filter(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level)),
+    );
 
     if !config.log_file.is_empty() {
         // Log to file
-        let file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&config.log_file);
-
-        match file {
+        match RollingFile::open(&config.log_file, config.log_policy()) {
             Ok(file) => {
                 tracing_subscriber::registry()
                     .with(filter)
-                    .with(fmt::layer().with_writer(file).with_ansi(false))
+                    .with(fmt::layer().with_writer(Mutex::new(file)).with_ansi(false))
                     .init();
                 return;
             }
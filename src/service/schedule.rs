@@ -0,0 +1,319 @@
+//! Time-based profile switching and active hours
+//!
+//! A schedule maps days and time ranges to named output profiles, e.g. the
+//! shop floor displays during opening hours and only the entrance after hours.
+//! Active hours are the times audio plays at all, e.g. outdoor speakers from
+//! 9am to 10pm; the service and the tray stop the engine outside them.
+
+use crate::audio::Zone;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Output device selection activated by the schedule
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Device IDs or aliases to use (empty = auto-detect all HDMI)
+    pub device_ids: Vec<String>,
+
+    /// Device IDs or aliases to exclude
+    pub exclude_ids: Vec<String>,
+
+    /// Which source plays on which outputs while the profile is active,
+    /// replacing the top-level `[[zones]]` (empty = keep those)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub zones: Vec<Zone>,
+}
+
+/// Days a schedule entry applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Day {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+    /// Monday to Friday
+    Weekdays,
+    /// Saturday and Sunday
+    Weekend,
+    /// Every day
+    Daily,
+}
+
+impl Day {
+    /// Check whether this day set includes a weekday (0 = Sunday, as in `SYSTEMTIME`)
+    fn includes(self, weekday: u8) -> bool {
+        match self {
+            Day::Sun => weekday == 0,
+            Day::Mon => weekday == 1,
+            Day::Tue => weekday == 2,
+            Day::Wed => weekday == 3,
+            Day::Thu => weekday == 4,
+            Day::Fri => weekday == 5,
+            Day::Sat => weekday == 6,
+            Day::Weekdays => (1..=5).contains(&weekday),
+            Day::Weekend => weekday == 0 || weekday == 6,
+            Day::Daily => true,
+        }
+    }
+}
+
+/// Time of day as "HH:MM"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    /// Minutes since midnight
+    minutes: u16,
+}
+
+impl TimeOfDay {
+    /// Create a time of day, None if out of range
+    pub fn new(hour: u16, minute: u16) -> Option<Self> {
+        // 24:00 is allowed as the end of the day
+        if minute >= 60 || hour > 24 || (hour == 24 && minute != 0) {
+            return None;
+        }
+        Some(Self {
+            minutes: hour * 60 + minute,
+        })
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .split_once(':')
+            .and_then(|(h, m)| Self::new(h.trim().parse().ok()?, m.trim().parse().ok()?))
+            .ok_or_else(|| format!("invalid time '{}', expected HH:MM", value))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+/// Activates a profile on the given days between `start` and `end`
+///
+/// A range whose end is before its start runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// Name of the profile in `[profiles]`
+    pub profile: String,
+
+    /// Days the entry starts on (empty = every day)
+    #[serde(default)]
+    pub days: Vec<Day>,
+
+    /// Start time (inclusive)
+    pub start: TimeOfDay,
+
+    /// End time (exclusive)
+    pub end: TimeOfDay,
+}
+
+impl ScheduleEntry {
+    /// Check whether the entry is active at a local time
+    pub fn is_active(&self, now: LocalTime) -> bool {
+        in_range(&self.days, self.start, self.end, now)
+    }
+}
+
+/// Whether `now` falls between `start` and `end` of a range starting on `days`
+fn in_range(days: &[Day], start: TimeOfDay, end: TimeOfDay, now: LocalTime) -> bool {
+    let starts_on = |weekday: u8| days.is_empty() || days.iter().any(|day| day.includes(weekday));
+    let time = now.time;
+    if start <= end {
+        starts_on(now.weekday) && start <= time && time < end
+    } else {
+        let yesterday = (now.weekday + 6) % 7;
+        (starts_on(now.weekday) && time >= start) || (starts_on(yesterday) && time < end)
+    }
+}
+
+/// Days and times the engine plays
+///
+/// Like a schedule entry without a profile; an end before the start runs
+/// past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveHours {
+    /// Days the range starts on (empty = every day)
+    #[serde(default)]
+    pub days: Vec<Day>,
+
+    /// Start time (inclusive)
+    pub start: TimeOfDay,
+
+    /// End time (exclusive)
+    pub end: TimeOfDay,
+}
+
+impl ActiveHours {
+    /// Check whether the range is active at a local time
+    pub fn is_active(&self, now: LocalTime) -> bool {
+        in_range(&self.days, self.start, self.end, now)
+    }
+}
+
+/// Whether the engine should play at `now`: always without active hours,
+/// otherwise within any of them
+pub fn within_active_hours(hours: &[ActiveHours], now: LocalTime) -> bool {
+    hours.is_empty() || hours.iter().any(|range| range.is_active(now))
+}
+
+/// Turns active hours into start and stop decisions
+///
+/// Only the edges of the active hours start or stop the engine, so an engine
+/// started or stopped by hand stays that way until the next edge.
+#[derive(Debug, Clone)]
+pub struct ActiveHoursTracker {
+    within: bool,
+}
+
+impl ActiveHoursTracker {
+    /// Track `hours`, starting from whether `now` is within them
+    pub fn new(hours: &[ActiveHours], now: LocalTime) -> Self {
+        Self {
+            within: within_active_hours(hours, now),
+        }
+    }
+
+    /// Whether the last check was within the active hours
+    pub fn within(&self) -> bool {
+        self.within
+    }
+
+    /// Check `hours` at `now`: Some(true) when they began since the last
+    /// check, Some(false) when they ended
+    pub fn update(&mut self, hours: &[ActiveHours], now: LocalTime) -> Option<bool> {
+        let within = within_active_hours(hours, now);
+        if within == self.within {
+            return None;
+        }
+        self.within = within;
+        Some(within)
+    }
+}
+
+/// Local wall-clock time used to evaluate schedules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    /// Day of week (0 = Sunday)
+    pub weekday: u8,
+    /// Time of day
+    pub time: TimeOfDay,
+}
+
+impl LocalTime {
+    /// Current local time
+    pub fn now() -> Self {
+        let now = unsafe { windows::Win32::System::SystemInformation::GetLocalTime() };
+        Self {
+            weekday: now.wDayOfWeek as u8,
+            time: TimeOfDay {
+                minutes: now.wHour * 60 + now.wMinute,
+            },
+        }
+    }
+}
+
+/// Name of the profile active at `now`: the first matching entry wins
+pub fn active_profile(schedule: &[ScheduleEntry], now: LocalTime) -> Option<&str> {
+    schedule
+        .iter()
+        .find(|entry| entry.is_active(now))
+        .map(|entry| entry.profile.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(weekday: u8, time: &str) -> LocalTime {
+        LocalTime {
+            weekday,
+            time: TimeOfDay::try_from(time.to_string()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        #[derive(Deserialize)]
+        struct Config {
+            schedule: Vec<ScheduleEntry>,
+        }
+
+        let Config { schedule } = toml::from_str(
+            r#"
+            [[schedule]]
+            profile = "open"
+            days = ["weekdays", "sat"]
+            start = "09:00"
+            end = "18:30"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(schedule[0].days, [Day::Weekdays, Day::Sat]);
+        assert_eq!(schedule[0].end.to_string(), "18:30");
+        assert!(TimeOfDay::try_from("25:00".to_string()).is_err());
+        assert!(TimeOfDay::try_from("9".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_active_profile() {
+        let entry = |profile: &str, days: Vec<Day>, start: &str, end: &str| ScheduleEntry {
+            profile: profile.to_string(),
+            days,
+            start: TimeOfDay::try_from(start.to_string()).unwrap(),
+            end: TimeOfDay::try_from(end.to_string()).unwrap(),
+        };
+        let schedule = vec![
+            entry("open", vec![Day::Weekdays], "09:00", "18:00"),
+            entry("night", vec![Day::Fri], "22:00", "02:00"),
+            entry("weekend", vec![Day::Weekend], "00:00", "24:00"),
+        ];
+
+        // Monday
+        assert_eq!(active_profile(&schedule, at(1, "09:00")), Some("open"));
+        assert_eq!(active_profile(&schedule, at(1, "18:00")), None);
+        // Friday night runs into Saturday
+        assert_eq!(active_profile(&schedule, at(5, "23:00")), Some("night"));
+        assert_eq!(active_profile(&schedule, at(6, "01:59")), Some("night"));
+        assert_eq!(active_profile(&schedule, at(6, "02:00")), Some("weekend"));
+        // Sunday
+        assert_eq!(active_profile(&schedule, at(0, "12:00")), Some("weekend"));
+    }
+
+    #[test]
+    fn test_active_hours_edges() {
+        let hours = vec![ActiveHours {
+            days: Vec::new(),
+            start: TimeOfDay::new(9, 0).unwrap(),
+            end: TimeOfDay::new(22, 0).unwrap(),
+        }];
+        assert!(within_active_hours(&[], at(1, "03:00")));
+
+        let mut tracker = ActiveHoursTracker::new(&hours, at(1, "08:00"));
+        assert!(!tracker.within());
+        assert_eq!(tracker.update(&hours, at(1, "08:59")), None);
+        assert_eq!(tracker.update(&hours, at(1, "09:00")), Some(true));
+        assert_eq!(tracker.update(&hours, at(1, "21:00")), None);
+        assert_eq!(tracker.update(&hours, at(1, "22:00")), Some(false));
+        // Removing the hours plays again
+        assert_eq!(tracker.update(&[], at(1, "23:00")), Some(true));
+    }
+}
@@ -0,0 +1,807 @@
+//! Commented configuration file generator
+//!
+//! Values come from [`ServiceConfig::default`] and comments from [`FIELDS`],
+//! so the generated file follows the actual schema; a test fails when a field
+//! is added without documentation or an example stops parsing.
+
+use crate::service::config::ServiceConfig;
+
+/// Language of the generated comments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ConfigLanguage {
+    /// English
+    #[default]
+    En,
+    /// Traditional Chinese
+    #[cfg_attr(feature = "clap", value(name = "zh-tw"))]
+    ZhTw,
+}
+
+/// Text in each supported language
+struct Text {
+    en: &'static str,
+    zh_tw: &'static str,
+}
+
+impl Text {
+    fn get(&self, language: ConfigLanguage) -> &'static str {
+        match language {
+            ConfigLanguage::En => self.en,
+            ConfigLanguage::ZhTw => self.zh_tw,
+        }
+    }
+}
+
+/// Documentation of one top-level configuration key
+struct FieldDoc {
+    key: &'static str,
+    /// Comment above the key, one line per comment line
+    comment: Text,
+    /// Commented-out TOML snippet; must parse as a configuration on its own
+    example: Option<&'static str>,
+    /// Table or array of tables, only shown through its example
+    section: bool,
+    /// Only emitted in full mode
+    advanced: bool,
+}
+
+const HEADER: Text = Text {
+    en: "Wemux Service Configuration\n\
+         This file configures the wemux audio sync service",
+    zh_tw: "Wemux 服務設定\n\
+            此檔案設定 wemux 音訊同步服務",
+};
+
+const EXAMPLE: Text = Text {
+    en: "Example:",
+    zh_tw: "範例:",
+};
+
+const FIELDS: &[FieldDoc] = &[
+    FieldDoc {
+        key: "buffer_ms",
+        comment: Text {
+            en: "Audio buffer size in milliseconds (default: 50).\n\
+                 Each output is fed in chunks of this size; the ring buffer and the silence\n\
+                 written before playback starts grow with it. Raise it if outputs crackle\n\
+                 (10 to 500)",
+            zh_tw: "音訊緩衝大小，單位為毫秒（預設：50）。\n\
+                    每個輸出以此大小分段寫入；環形緩衝與播放前預先寫入的靜音會隨之增加。\n\
+                    輸出有爆音時請調高（10 到 500）",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "device_ids",
+        comment: Text {
+            en: "Specific device IDs or aliases to use (empty = auto-detect all HDMI)",
+            zh_tw: "指定使用的裝置 ID 或別名（空白 = 自動偵測所有 HDMI 裝置）",
+        },
+        example: Some(r#"device_ids = ["Device1", "living-room"]"#),
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "exclude_ids",
+        comment: Text {
+            en: "Device IDs or aliases to exclude from auto-detection",
+            zh_tw: "自動偵測時要排除的裝置 ID 或別名",
+        },
+        example: Some(r#"exclude_ids = ["SomeDevice"]"#),
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "source_device_id",
+        comment: Text {
+            en: "Source device ID for loopback capture (empty = system default)",
+            zh_tw: "迴路擷取的來源裝置 ID（空白 = 系統預設）",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "source_type",
+        comment: Text {
+            en: "What source_device_id names: \"loopback\" captures what an output plays,\n\
+                 \"input\" records an input such as line-in (empty source = default input),\n\
+                 \"file\" plays an audio file in a loop (source_device_id is its path;\n\
+                 WAV, FLAC, MP3 or Ogg Vorbis)",
+            zh_tw: "source_device_id 的類型：\"loopback\" 擷取輸出裝置播放的聲音，\n\
+                    \"input\" 錄製線路輸入等輸入裝置（來源空白 = 預設輸入裝置），\n\
+                    \"file\" 循環播放音訊檔（source_device_id 為檔案路徑；\n\
+                    WAV、FLAC、MP3 或 Ogg Vorbis）",
+        },
+        example: Some(r#"source_type = "input""#),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "exclude_apps",
+        comment: Text {
+            en: "Apps whose sound is not mirrored, by executable name (\".exe\" optional),\n\
+                 e.g. a voice chat. Every other app playing on the source is captured on\n\
+                 its own; Windows' own sounds are left out too. Loopback sources only,\n\
+                 needs Windows 10 version 2004 or later (empty = capture everything)",
+            zh_tw: "不要鏡像輸出聲音的應用程式，以執行檔名稱指定（可省略 \".exe\"），例如語音聊天。\n\
+                    來源上其他播放中的應用程式會個別擷取；Windows 系統音效也會一併排除。\n\
+                    僅適用於迴路來源，需要 Windows 10 2004 版或更新版本（空白 = 擷取全部）",
+        },
+        example: Some(r#"exclude_apps = ["Discord", "Teams.exe"]"#),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "fallback_device",
+        comment: Text {
+            en: "Output or input device played instead of the source while that is silent,\n\
+                 e.g. a network receiver or a microphone; it yields as soon as the source is\n\
+                 audible again. Converted to the format of the source (empty = off)",
+            zh_tw: "來源無聲時改為播放的輸出或輸入裝置，例如網路接收器或麥克風；\n\
+                    來源一有聲音就立即交還。會轉換為來源的格式（空白 = 停用）",
+        },
+        example: Some(r#"fallback_device = "Line In""#),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "fallback_after_secs",
+        comment: Text {
+            en: "Seconds the source must be silent before fallback_device takes over (default: 5)",
+            zh_tw: "來源需靜音多少秒後才由 fallback_device 接手（預設：5）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "announcement_device",
+        comment: Text {
+            en: "Output or input played over the source, e.g. a paging microphone. While it is\n\
+                 audible the source is turned down on every output (empty = off)",
+            zh_tw: "疊加在來源上播放的輸出或輸入裝置，例如廣播用麥克風。\n\
+                    有聲音時，所有輸出上的來源音量會自動降低（空白 = 關閉）",
+        },
+        example: Some(r#"announcement_device = "Microphone""#),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "announcement_duck_db",
+        comment: Text {
+            en: "Decibels the source is turned down by during an announcement (default: 15)",
+            zh_tw: "廣播期間來源降低的分貝數（預設：15）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "follow_source",
+        comment: Text {
+            en: "Pace outputs by the audio the source delivers instead of the first output's clock.\n\
+                 Helps with variable-rate sources such as virtual audio cables (default: false)",
+            zh_tw: "依來源實際送出的音訊調整輸出速度，而非以第一個輸出裝置的時脈為準。\n\
+                    適用於 VB-Cable 等速率不固定的虛擬音訊來源（預設：false）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "drift_threshold_ms",
+        comment: Text {
+            en: "Drift in milliseconds before an output is brought back in step with the others.\n\
+                 Higher values correct less often; lower values keep outputs tighter (default: 5)",
+            zh_tw: "輸出與其他輸出偏差超過這麼多毫秒時才校正。\n\
+                    數值越高校正越少；越低則輸出越同步（預設：5）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "max_correction_ms",
+        comment: Text {
+            en: "Most audio in milliseconds skipped per correction. Smaller steps are less audible\n\
+                 but take longer to catch up (default: 1)",
+            zh_tw: "每次校正最多略過的音訊毫秒數。步幅越小越不易察覺，\n\
+                    但需要更久才能追上（預設：1）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "performance_capture",
+        comment: Text {
+            en: "Capture as a critical-priority MMCSS task pinned to its own core, polling for audio\n\
+                 instead of sleeping. Shaves wake-up delays for rhythm games, but keeps one core\n\
+                 fully busy (default: false)",
+            zh_tw: "以最高優先權的 MMCSS 工作在專屬核心上擷取，持續輪詢音訊而不休眠。\n\
+                    可減少節奏遊戲等情境的喚醒延遲，但會讓一個核心保持滿載（預設：false）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "render_priority",
+        comment: Text {
+            en: "Priority of the output threads, if background tasks cause dropouts:\n\
+                 \"normal\", \"high\" or \"time_critical\" (default: \"normal\")",
+            zh_tw: "輸出執行緒的優先權，背景工作造成斷音時可調高：\n\
+                    \"normal\"、\"high\" 或 \"time_critical\"（預設：\"normal\"）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "suppress_duplicates",
+        comment: Text {
+            en: "Start outputs paused that reach the same physical device as another output,\n\
+                 e.g. an AV receiver that appears as two HDMI endpoints and would play twice",
+            zh_tw: "與其他輸出連到同一實體裝置的輸出以暫停狀態啟動，\n\
+                    例如顯示為兩個 HDMI 端點、會重複播放的 AV 擴大機",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "allow_duplicates",
+        comment: Text {
+            en: "Device IDs or aliases that play even when they duplicate another output",
+            zh_tw: "即使與其他輸出重複也照常播放的裝置 ID 或別名",
+        },
+        example: Some(r#"allow_duplicates = ["receiver-zone-2"]"#),
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "bluetooth_latency_ms",
+        comment: Text {
+            en: "Bluetooth outputs play 100-300ms behind wired ones. While one plays, the other\n\
+                 outputs without an entry under [delays] wait this long (0 = off, default: 200)",
+            zh_tw: "藍牙輸出比有線輸出慢 100-300 毫秒。藍牙輸出播放時，\n\
+                    未在 [delays] 設定的其他輸出會延遲這麼久（0 = 關閉，預設：200）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "pause_fade_ms",
+        comment: Text {
+            en: "Fade in milliseconds when an output is paused or resumed, against clicks on\n\
+                 some receivers (0 = switch at once, default: 20)",
+            zh_tw: "輸出暫停或恢復時的淡入淡出毫秒數，避免部分接收器發出爆音\n\
+                    （0 = 立即切換，預設：20）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "volume_mode",
+        comment: Text {
+            en: "Volume of the outputs: \"follow\" the captured device's volume, play at\n\
+                 mirror_volume whatever that is (\"fixed\"), or follow it \"scaled\" by\n\
+                 mirror_volume, to turn the outputs down without the main output\n\
+                 (default: \"follow\")",
+            zh_tw: "輸出音量：\"follow\" 跟隨擷取裝置的音量，\"fixed\" 不論其音量一律以\n\
+                    mirror_volume 播放，\"scaled\" 跟隨其音量再乘上 mirror_volume，\n\
+                    可在不影響主要輸出的情況下調低輸出音量（預設：\"follow\"）",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "mirror_volume",
+        comment: Text {
+            en: "Volume of the outputs with volume_mode \"fixed\" or \"scaled\", 0.0 to 1.0\n\
+                 (default: 1.0); `wemux volume` changes it while running",
+            zh_tw: "volume_mode 為 \"fixed\" 或 \"scaled\" 時的輸出音量，0.0 至 1.0\n\
+                    （預設：1.0）；執行中可用 `wemux volume` 變更",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "mix_sources",
+        comment: Text {
+            en: "Extra output devices whose loopback is mixed into the source.\n\
+                 device is an ID, alias or name; gain scales the source (default: 1.0).\n\
+                 Sources are converted to the format of the main source.",
+            zh_tw: "額外混入來源的輸出裝置迴路。\n\
+                    device 為裝置 ID、別名或名稱；gain 調整該來源音量（預設：1.0）。\n\
+                    來源會轉換為主要來源的格式。",
+        },
+        example: Some(
+            "[[mix_sources]]\n\
+             device = \"Speakers\"\n\
+             gain = 0.5",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "log_level",
+        comment: Text {
+            en: "Log level: trace, debug, info, warn, error (default: info)",
+            zh_tw: "記錄層級：trace、debug、info、warn、error（預設：info）",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "log_file",
+        comment: Text {
+            en: "Log file path (empty = no file logging)",
+            zh_tw: "記錄檔路徑（空白 = 不寫入檔案）",
+        },
+        example: Some(r#"log_file = 'C:\Users\<username>\AppData\Local\wemux\wemux.log'"#),
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "log_rotation",
+        comment: Text {
+            en: "When to start a new log file: never, daily (after local midnight) or size.\n\
+                 The previous file is renamed to <log_file>.1, older ones to .2, .3, ...",
+            zh_tw: "何時開始新的記錄檔：never（永不）、daily（每日午夜後）或 size（依大小）。\n\
+                    先前的檔案會改名為 <log_file>.1，更舊的依序為 .2、.3……",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "log_max_size_mb",
+        comment: Text {
+            en: "Size limit of a log file in MB with log_rotation = \"size\"",
+            zh_tw: "log_rotation = \"size\" 時單一記錄檔的大小上限（MB）",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "log_keep",
+        comment: Text {
+            en: "Rotated log files to keep; older ones are deleted",
+            zh_tw: "保留的舊記錄檔數量，更舊的會被刪除",
+        },
+        example: None,
+        section: false,
+        advanced: false,
+    },
+    FieldDoc {
+        key: "control_all_users",
+        comment: Text {
+            en: "Let every signed-in user, e.g. in a remote desktop session, control the service.\n\
+                 Otherwise only the user at the console and administrators may",
+            zh_tw: "允許所有已登入的使用者（例如遠端桌面工作階段）控制服務。\n\
+                    否則僅限主控台使用者與系統管理員",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "metrics_listen",
+        comment: Text {
+            en: "Serve Prometheus metrics at http://<address>/metrics (empty = off).\n\
+                 Requires a build with the 'metrics' feature; use 0.0.0.0 to allow other machines",
+            zh_tw: "在 http://<位址>/metrics 提供 Prometheus 監控數據（空白 = 關閉）。\n\
+                    需以 'metrics' 功能編譯；使用 0.0.0.0 允許其他電腦存取",
+        },
+        example: Some("metrics_listen = '127.0.0.1:9464'"),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "remote_listen",
+        comment: Text {
+            en: "Accept remote administration over HTTP at this address (empty = off).\n\
+                 Use 0.0.0.0 to allow other machines; 'wemux monitor --host' connects to it",
+            zh_tw: "在此位址透過 HTTP 接受遠端管理（空白 = 關閉）。\n\
+                    使用 0.0.0.0 允許其他電腦連線；'wemux monitor --host' 可連線至此",
+        },
+        example: Some("remote_listen = '0.0.0.0:9465'"),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "remote_token",
+        comment: Text {
+            en: "Secret that remote clients must present; remote administration stays off without it",
+            zh_tw: "遠端用戶端必須提供的密鑰；未設定時不啟用遠端管理",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "remote_allow",
+        comment: Text {
+            en: "Client addresses or networks allowed to connect (empty = this machine only; it always may)",
+            zh_tw: "允許連線的用戶端位址或網段（空白 = 僅限本機；本機一律允許）",
+        },
+        example: Some(r#"remote_allow = ["192.168.1.0/24", "10.0.0.5"]"#),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "notify_url",
+        comment: Text {
+            en: "Webhook that gets a POST when audio has been failing for notify_after_minutes,\n\
+                 and again once it recovers; an ntfy topic URL shows up on your phone (empty = off)",
+            zh_tw: "音訊持續失敗達 notify_after_minutes 分鐘時以 POST 通知的 Webhook，\n\
+                    恢復後會再通知一次；使用 ntfy 主題網址即可在手機上收到（空白 = 關閉）",
+        },
+        example: Some("notify_url = 'https://ntfy.sh/my-wemux-alerts'"),
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "notify_after_minutes",
+        comment: Text {
+            en: "Minutes of failure before notify_url is notified (default: 5)",
+            zh_tw: "失敗持續多少分鐘後通知 notify_url（預設：5）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "health_history_days",
+        comment: Text {
+            en: "Days of underruns, reconnects and drift per output kept for `wemux stats`\n\
+                 (0 = off, default: 7)",
+            zh_tw: "保留多少天各輸出的欠載、重新連線與漂移紀錄供 `wemux stats` 查詢\n\
+                    （0 = 關閉，預設：7）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "service_mode",
+        comment: Text {
+            en: "How `wemux service install` sets up the service: \"system\" runs it in session 0\n\
+                 before anyone signs in; \"user\" starts it in the session of whoever signs in, for\n\
+                 audio drivers that only work there. Reinstall after changing (default: system)",
+            zh_tw: "`wemux service install` 安裝服務的方式：\"system\" 在登入前於工作階段 0 執行；\n\
+                    \"user\" 在登入使用者的工作階段中啟動，適用於只能在該處運作的音訊驅動程式。\n\
+                    變更後需重新安裝（預設：system）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "restart_delay_secs",
+        comment: Text {
+            en: "Seconds Windows waits before restarting the service after it crashes or stops\n\
+                 with an error (0 = don't restart, default: 5). Reinstall after changing",
+            zh_tw: "服務當機或因錯誤停止後，Windows 等待多少秒再重新啟動\n\
+                    （0 = 不重新啟動，預設：5）。變更後需重新安裝",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "restart_attempts",
+        comment: Text {
+            en: "Restarts within restart_reset_hours before Windows gives up (0 = no limit, default: 3)",
+            zh_tw: "在 restart_reset_hours 內最多重新啟動幾次（0 = 不限，預設：3）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "restart_reset_hours",
+        comment: Text {
+            en: "Hours without a failure after which the restart count starts over (default: 24)",
+            zh_tw: "多少小時內未再失敗後重新計算重新啟動次數（預設：24）",
+        },
+        example: None,
+        section: false,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "aliases",
+        comment: Text {
+            en: "Device aliases survive driver reinstalls that change device IDs.\n\
+                 A device is matched by ID first, then by name and container ID.\n\
+                 'wemux info <device>' prints a ready-made entry.",
+            zh_tw: "裝置別名在驅動程式重新安裝導致裝置 ID 改變後仍然有效。\n\
+                    先以 ID 比對裝置，再以名稱與容器 ID 比對。\n\
+                    'wemux info <裝置>' 會輸出可直接使用的設定。",
+        },
+        example: Some(
+            "[aliases.living-room]\n\
+             id = \"{0.0.0.00000000}.{...}\"\n\
+             name = \"LG TV (NVIDIA High Definition Audio)\"\n\
+             container_id = \"{...}\"",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "delays",
+        comment: Text {
+            en: "Extra delay in milliseconds per device ID or alias, to line up TVs that play\n\
+                 sooner than others. 'wemux calibrate --save' measures and writes them.",
+            zh_tw: "每個裝置 ID 或別名額外延遲的毫秒數，用來對齊播放較早的電視。\n\
+                    'wemux calibrate --save' 會量測並寫入這些值。",
+        },
+        example: Some(
+            "[delays]\n\
+             living-room = 120\n\
+             bedroom = 0",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "device_buffer_ms",
+        comment: Text {
+            en: "Buffer in milliseconds per device ID or alias, for TVs that need a bigger one\n\
+                 than buffer_ms (10 to 500). What it exceeds buffer_ms by is added to the\n\
+                 device's own buffer.",
+            zh_tw: "每個裝置 ID 或別名的緩衝毫秒數，供需要比 buffer_ms 更大緩衝的電視使用\n\
+                    （10 到 500）。超出 buffer_ms 的部分會加到該裝置本身的緩衝。",
+        },
+        example: Some(
+            "[device_buffer_ms]\n\
+             living-room = 120",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "dsp",
+        comment: Text {
+            en: "Processing per device ID or alias, run in order after the equalizer and before\n\
+                 the delay: \"gain\" (db), \"eq\" (gains_db, 31Hz to 16kHz), \"delay\" (ms),\n\
+                 \"limiter\" (threshold_db, release_ms) and \"channels\" (map: the source channel\n\
+                 played on each channel)",
+            zh_tw: "每個裝置 ID 或別名的音訊處理，依序在等化器之後、延遲之前執行：\n\
+                    \"gain\"（db）、\"eq\"（gains_db，31Hz 至 16kHz）、\"delay\"（ms）、\n\
+                    \"limiter\"（threshold_db、release_ms）與 \"channels\"（map：每個聲道播放的來源聲道）",
+        },
+        example: Some(
+            "[dsp]\n\
+             patio = [{ type = \"gain\", db = -6.0 }, { type = \"limiter\", threshold_db = -3.0 }]\n\
+             bedroom = [{ type = \"channels\", map = [1, 0] }]",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "render_cores",
+        comment: Text {
+            en: "CPU core to run an output's thread on, per device ID or alias (cores count\n\
+                 from 0). Core 0 handles most interrupts; performance capture takes the last ones.",
+            zh_tw: "每個裝置 ID 或別名的輸出執行緒所使用的 CPU 核心（從 0 起算）。\n\
+                    核心 0 處理大部分中斷；效能擷取會使用最後面的核心。",
+        },
+        example: Some(
+            "[render_cores]\n\
+             living-room = 2\n\
+             bedroom = 3",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "zones",
+        comment: Text {
+            en: "Zones are groups of outputs, each with its own source and sync. When set, only\n\
+                 the zones' devices play and device_ids is not used. source defaults to the\n\
+                 one above; a paused zone stays silent until resumed from the tray.",
+            zh_tw: "區域是一組輸出裝置，各自擁有來源與同步。設定後只播放區域內的裝置，\n\
+                    不使用 device_ids。source 預設為上方的來源；\n\
+                    paused 的區域會保持靜音，直到從系統匣恢復。",
+        },
+        example: Some(
+            "[[zones]]\n\
+             name = \"Downstairs\"\n\
+             devices = [\"living-room\", \"kitchen\"]\n\
+             \n\
+             [[zones]]\n\
+             name = \"Office\"\n\
+             devices = [\"office\"]\n\
+             source = \"Speakers\"\n\
+             paused = true",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "profiles",
+        comment: Text {
+            en: "Profiles are alternative device selections, activated by the schedule or\n\
+                 the display rules. Otherwise the device_ids/exclude_ids above apply.\n\
+                 A profile with zones routes each zone's source to its outputs instead.",
+            zh_tw: "設定檔是由排程或顯示器規則啟用的另一組裝置選擇。\n\
+                    兩者皆未啟用時，使用上方的 device_ids/exclude_ids。\n\
+                    設定檔中有 zones 時，改為將各區域的來源送到其輸出裝置。",
+        },
+        example: Some(
+            "[profiles.open]\n\
+             device_ids = [\"shop-floor\", \"entrance\"]\n\
+             \n\
+             [profiles.closed]\n\
+             device_ids = [\"entrance\"]\n\
+             \n\
+             [[profiles.evening.zones]]\n\
+             name = \"Music\"\n\
+             devices = [\"shop-floor\", \"entrance\"]\n\
+             source = \"CABLE Input\"",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "schedule",
+        comment: Text {
+            en: "Schedule entries: days (mon..sun, weekdays, weekend, daily; empty = every day),\n\
+                 start/end as HH:MM local time. An end before the start runs past midnight.\n\
+                 The first matching entry wins.",
+            zh_tw: "排程項目：days（mon..sun、weekdays、weekend、daily；空白 = 每天），\n\
+                    start/end 為 HH:MM 本地時間。結束早於開始時表示跨越午夜。\n\
+                    以第一個符合的項目為準。",
+        },
+        example: Some(
+            "[[schedule]]\n\
+             profile = \"open\"\n\
+             days = [\"weekdays\"]\n\
+             start = \"09:00\"\n\
+             end = \"18:00\"",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "displays",
+        comment: Text {
+            en: "Display rules pick a profile by the number of connected HDMI outputs\n\
+                 (min_hdmi..=max_hdmi), checked again when outputs are plugged in or removed.\n\
+                 They apply when no schedule entry is active; the first matching rule wins.",
+            zh_tw: "顯示器規則依已連接的 HDMI 輸出數量（min_hdmi..=max_hdmi）選擇設定檔，\n\
+                    插入或移除輸出時會重新檢查。\n\
+                    僅在沒有排程時段生效時套用；以第一個符合的規則為準。",
+        },
+        example: Some(
+            "[[displays]]\n\
+             profile = \"docked\"\n\
+             min_hdmi = 3\n\
+             \n\
+             [[displays]]\n\
+             profile = \"laptop\"\n\
+             max_hdmi = 2",
+        ),
+        section: true,
+        advanced: true,
+    },
+    FieldDoc {
+        key: "active_hours",
+        comment: Text {
+            en: "Times audio plays: days and start/end as in [[schedule]]. The engine starts\n\
+                 when a range begins and stops when the last one ends; starting or stopping\n\
+                 it by hand holds until the next change. No entries = always.",
+            zh_tw: "播放音訊的時段：days 與 start/end 的格式同 [[schedule]]。時段開始時啟動引擎，\n\
+                    所有時段結束時停止；手動啟動或停止會維持到下一次時段變化。\n\
+                    沒有項目 = 隨時播放。",
+        },
+        example: Some(
+            "[[active_hours]]\n\
+             days = [\"daily\"]\n\
+             start = \"09:00\"\n\
+             end = \"22:00\"",
+        ),
+        section: true,
+        advanced: true,
+    },
+];
+
+/// Append text as TOML comment lines
+fn push_comment(out: &mut String, text: &str) {
+    for line in text.lines() {
+        if line.is_empty() {
+            out.push_str("#\n");
+        } else {
+            out.push_str("# ");
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+}
+
+/// Generate a commented configuration file with default values
+///
+/// `full` also documents the advanced sections (aliases, profiles, schedule,
+/// display rules).
+pub fn generate(language: ConfigLanguage, full: bool) -> String {
+    let defaults =
+        toml::Table::try_from(ServiceConfig::default()).expect("default config is serializable");
+
+    let mut out = String::new();
+    push_comment(&mut out, HEADER.get(language));
+
+    for field in FIELDS.iter().filter(|field| full || !field.advanced) {
+        out.push('\n');
+        push_comment(&mut out, field.comment.get(language));
+
+        if let Some(example) = field.example {
+            if example.contains('\n') {
+                push_comment(&mut out, EXAMPLE.get(language));
+                push_comment(&mut out, example);
+            } else {
+                push_comment(&mut out, &format!("{} {}", EXAMPLE.get(language), example));
+            }
+        }
+
+        if !field.section {
+            if let Some(value) = defaults.get(field.key) {
+                out.push_str(&format!("{} = {}\n", field.key, value));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_field_documented() {
+        let defaults = toml::Table::try_from(ServiceConfig::default()).unwrap();
+        let mut keys: Vec<&str> = defaults.keys().map(String::as_str).collect();
+        let mut documented: Vec<&str> = FIELDS.iter().map(|field| field.key).collect();
+        keys.sort();
+        documented.sort();
+        assert_eq!(keys, documented);
+    }
+
+    #[test]
+    fn test_generated_config_parses() {
+        let defaults = toml::to_string(&ServiceConfig::default()).unwrap();
+        for language in [ConfigLanguage::En, ConfigLanguage::ZhTw] {
+            for full in [false, true] {
+                let text = generate(language, full);
+                let parsed: ServiceConfig = toml::from_str(&text).unwrap();
+                assert_eq!(toml::to_string(&parsed).unwrap(), defaults);
+            }
+        }
+
+        let full = generate(ConfigLanguage::En, true);
+        assert!(full.contains("# [[schedule]]\n"));
+        assert!(!generate(ConfigLanguage::En, false).contains("[[schedule]]"));
+    }
+
+    #[test]
+    fn test_examples_parse() {
+        for field in FIELDS {
+            if let Some(example) = field.example {
+                let parsed = toml::from_str::<ServiceConfig>(example);
+                assert!(parsed.is_ok(), "example for {}: {:?}", field.key, parsed);
+            }
+        }
+    }
+}
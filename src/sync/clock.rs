@@ -1,57 +1,178 @@
 //! Master-slave clock synchronization for multiple renderers
-
+//!
+//! By default each renderer reports the frames its device has played with
+//! the time they were read (`IAudioClock::GetPosition` with its QPC
+//! timestamp). How far a device's clock runs ahead of or behind that time is
+//! measured for the master and each slave, and a slave is corrected by the
+//! difference to the master. When following the source, there is no master
+//! and no clock: each renderer keeps the amount of captured audio queued for
+//! it, so it consumes at whatever rate the capture delivers (useful for
+//! variable-rate virtual devices).
+
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::Instant;
+use std::time::Duration;
 use tracing::{debug, trace};
 
-/// Threshold in samples before applying drift correction
-const DRIFT_THRESHOLD_SAMPLES: i64 = 240; // ~5ms at 48kHz (tighter sync)
+/// Drift in milliseconds before a renderer is corrected, unless configured
+pub const DEFAULT_DRIFT_THRESHOLD_MS: f64 = 5.0;
+
+/// Most audio in milliseconds skipped per correction (to avoid audible
+/// glitches), unless configured
+pub const DEFAULT_MAX_CORRECTION_MS: f64 = 1.0;
+
+/// How tightly renderers are kept in step
+///
+/// Looser sync lets outputs drift further apart before correcting them, and
+/// corrects in smaller steps, so corrections are rarer and less audible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStrictness {
+    /// Few, small corrections; outputs may drift up to 15ms apart
+    Loose,
+    /// The defaults: corrections beyond 5ms of drift, 1ms at a time
+    #[default]
+    Normal,
+    /// Outputs within 2ms of each other, corrected in larger steps
+    Tight,
+}
+
+impl SyncStrictness {
+    /// Drift threshold and largest correction, in milliseconds
+    pub fn drift_limits(self) -> (f64, f64) {
+        match self {
+            SyncStrictness::Loose => (15.0, 0.5),
+            SyncStrictness::Normal => (DEFAULT_DRIFT_THRESHOLD_MS, DEFAULT_MAX_CORRECTION_MS),
+            SyncStrictness::Tight => (2.0, 2.0),
+        }
+    }
+}
+
+/// Frames a device has played, and when that was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DevicePosition {
+    /// Frames played since the stream started
+    pub frames: u64,
+    /// Time of the reading on a clock shared by all devices (the
+    /// performance counter on Windows)
+    pub time: Duration,
+}
 
-/// Maximum correction per update (to avoid audible glitches)
-const MAX_CORRECTION_SAMPLES: i64 = 48; // ~1ms at 48kHz
+/// How far a device's clock has run from the shared clock since an anchor
+#[derive(Debug, Clone, Copy, Default)]
+struct ClockOffset {
+    anchor: Option<DevicePosition>,
+    last: Option<DevicePosition>,
+    /// Offset measured before the stream last started over
+    base: f64,
+    /// Frames played beyond (positive) or short of what the shared clock
+    /// expects
+    frames: f64,
+}
+
+impl ClockOffset {
+    /// Take a new reading; a stream that started over is anchored again
+    fn update(&mut self, position: DevicePosition, sample_rate: u32) {
+        if self
+            .last
+            .is_some_and(|last| position.frames < last.frames || position.time < last.time)
+        {
+            // Keep the offset measured so far
+            self.base = self.frames;
+            self.anchor = None;
+        }
+        let anchor = *self.anchor.get_or_insert(position);
+        self.last = Some(position);
+
+        let elapsed = position.time.saturating_sub(anchor.time);
+        let expected = elapsed.as_secs_f64() * sample_rate as f64;
+        let played = (position.frames - anchor.frames) as f64;
+        self.frames = self.base + played - expected;
+    }
+}
+
+/// What renderers are kept in step with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClockReference {
+    /// Slaves follow the master renderer's device clock
+    #[default]
+    WallClock,
+    /// Every renderer follows the capture's own sample clock
+    Source,
+}
 
 /// Clock synchronization state for master-slave model
 pub struct ClockSync {
+    /// What renderers are synchronized to
+    reference: ClockReference,
     /// Master device ID
     master_id: Option<String>,
-    /// Master's reference position
-    master_position: u64,
-    /// Last update time
-    last_update: Instant,
+    /// Master's clock against the shared clock
+    master_clock: ClockOffset,
     /// Per-slave state
     slaves: HashMap<String, SlaveState>,
     /// Sample rate for calculations
     sample_rate: u32,
+    /// Drift in samples before a correction is applied
+    drift_threshold: i64,
+    /// Maximum correction per update in samples
+    max_correction: i64,
 }
 
+#[derive(Default)]
 struct SlaveState {
-    /// Position at last sync
-    last_position: u64,
-    /// Accumulated drift in samples (positive = ahead of master, negative = behind)
+    /// Slave's clock against the shared clock
+    clock: ClockOffset,
+    /// Frames skipped so far to make up for the drift
+    corrected: i64,
+    /// Drift in samples (positive = behind the master, negative = ahead)
     drift_samples: i64,
-    /// Last sync time
-    last_sync: Instant,
     /// Pending correction to apply
     pending_correction: i64,
+    /// Queued frames to hold when following the source (set by the first update)
+    target_backlog: Option<u64>,
 }
 
 impl ClockSync {
     /// Create a new clock sync instance
     pub fn new(sample_rate: u32) -> Self {
+        Self::with_reference(sample_rate, ClockReference::WallClock)
+    }
+
+    /// Create a clock sync instance synchronizing to `reference`
+    pub fn with_reference(sample_rate: u32, reference: ClockReference) -> Self {
         Self {
+            reference,
             master_id: None,
-            master_position: 0,
-            last_update: Instant::now(),
+            master_clock: ClockOffset::default(),
             slaves: HashMap::new(),
             sample_rate,
+            drift_threshold: 0,
+            max_correction: 0,
         }
+        .with_drift_limits(DEFAULT_DRIFT_THRESHOLD_MS, DEFAULT_MAX_CORRECTION_MS)
+    }
+
+    /// Correct drift beyond `threshold_ms`, at most `max_correction_ms` per update
+    ///
+    /// A correction is always at least one sample.
+    pub fn with_drift_limits(mut self, threshold_ms: f64, max_correction_ms: f64) -> Self {
+        let samples = |ms: f64| (ms.max(0.0) * self.sample_rate as f64 / 1000.0).round() as i64;
+        self.drift_threshold = samples(threshold_ms);
+        self.max_correction = samples(max_correction_ms).max(1);
+        self
     }
 
     /// Set the master device
+    ///
+    /// Slaves are measured against the new master from now on.
     pub fn set_master(&mut self, device_id: &str) {
         self.master_id = Some(device_id.to_string());
-        self.master_position = 0;
-        self.last_update = Instant::now();
+        self.master_clock = ClockOffset::default();
+        for slave in self.slaves.values_mut() {
+            *slave = SlaveState::default();
+        }
         debug!("Clock sync master set to: {}", device_id);
     }
 
@@ -61,15 +182,8 @@ impl ClockSync {
             return; // Don't register master as slave
         }
 
-        self.slaves.insert(
-            device_id.to_string(),
-            SlaveState {
-                last_position: 0,
-                drift_samples: 0,
-                last_sync: Instant::now(),
-                pending_correction: 0,
-            },
-        );
+        self.slaves
+            .insert(device_id.to_string(), SlaveState::default());
         debug!("Registered clock sync slave: {}", device_id);
     }
 
@@ -78,33 +192,51 @@ impl ClockSync {
         self.slaves.remove(device_id);
     }
 
+    /// Hand the master role to the slave `device_id`, e.g. as the master's
+    /// device was removed
+    ///
+    /// The new master's clock keeps its history, shifted by the audio skipped
+    /// to correct it, and the other slaves' drift is rebased onto it, so
+    /// outputs already in step with each other stay that way. Returns false
+    /// if `device_id` isn't a slave.
+    pub fn promote(&mut self, device_id: &str) -> bool {
+        let Some(master) = self.slaves.remove(device_id) else {
+            return false;
+        };
+        let skipped = master.corrected as f64;
+        self.master_clock = ClockOffset {
+            base: master.clock.base + skipped,
+            frames: master.clock.frames + skipped,
+            ..master.clock
+        };
+        for slave in self.slaves.values_mut() {
+            slave.drift_samples -= master.drift_samples;
+            slave.pending_correction = 0;
+        }
+        self.master_id = Some(device_id.to_string());
+        debug!("Clock sync master handed to: {}", device_id);
+        true
+    }
+
+    /// Current master device, if any
+    pub fn master_id(&self) -> Option<&str> {
+        self.master_id.as_deref()
+    }
+
     /// Update master position
-    pub fn update_master(&mut self, position: u64) {
-        self.master_position = position;
-        self.last_update = Instant::now();
+    pub fn update_master(&mut self, position: DevicePosition) {
+        self.master_clock.update(position, self.sample_rate);
     }
 
     /// Update slave position and calculate drift
-    pub fn update_slave(&mut self, device_id: &str, position: u64) {
+    ///
+    /// A slave whose device played fewer frames than the master's in the
+    /// same time falls behind and is corrected by skipping audio.
+    pub fn update_slave(&mut self, device_id: &str, position: DevicePosition) {
         if let Some(slave) = self.slaves.get_mut(device_id) {
-            let now = Instant::now();
-            let elapsed = now.duration_since(slave.last_sync);
-
-            // Calculate expected position based on elapsed time
-            let elapsed_samples = (elapsed.as_secs_f64() * self.sample_rate as f64) as i64;
-
-            // Calculate actual movement
-            let actual_movement = position.wrapping_sub(slave.last_position) as i64;
-
-            // Drift is difference between actual and expected
-            // Positive drift = slave is ahead, negative = slave is behind
-            let drift_delta = actual_movement - elapsed_samples;
-
-            // Accumulate drift with some smoothing
-            slave.drift_samples = (slave.drift_samples * 7 + drift_delta) / 8;
-
-            slave.last_position = position;
-            slave.last_sync = now;
+            slave.clock.update(position, self.sample_rate);
+            let behind = (self.master_clock.frames - slave.clock.frames).round() as i64;
+            slave.drift_samples = behind - slave.corrected;
 
             trace!(
                 "Slave {} drift: {} samples ({:.2}ms)",
@@ -114,9 +246,9 @@ impl ClockSync {
             );
 
             // Calculate correction if drift exceeds threshold
-            if slave.drift_samples.abs() > DRIFT_THRESHOLD_SAMPLES {
+            if slave.drift_samples.abs() > self.drift_threshold {
                 let correction = slave.drift_samples.signum()
-                    * slave.drift_samples.abs().min(MAX_CORRECTION_SAMPLES);
+                    * slave.drift_samples.abs().min(self.max_correction);
                 slave.pending_correction = correction;
 
                 debug!(
@@ -129,6 +261,38 @@ impl ClockSync {
         }
     }
 
+    /// Record the captured frames queued for a renderer when following the source
+    ///
+    /// `backlog` counts frames captured but not yet played (waiting in the ring
+    /// buffer plus queued on the device). The first backlog is the renderer's
+    /// target; once it grows beyond that, the renderer consumes slower than the
+    /// source delivers and the excess is scheduled to be skipped. A shrinking
+    /// backlog is left to underrun handling, since audio can't be invented.
+    pub fn update_backlog(&mut self, device_id: &str, backlog: u64) {
+        let Some(slave) = self.slaves.get_mut(device_id) else {
+            return;
+        };
+
+        let target = *slave.target_backlog.get_or_insert(backlog);
+        let excess = backlog as i64 - target as i64;
+        slave.drift_samples = (slave.drift_samples * 7 + excess) / 8;
+
+        slave.pending_correction = if slave.drift_samples > self.drift_threshold {
+            debug!(
+                "Renderer {} is {} frames behind the source",
+                device_id, slave.drift_samples
+            );
+            slave.drift_samples.min(self.max_correction)
+        } else {
+            0
+        };
+    }
+
+    /// Whether renderers follow the capture instead of a master
+    pub fn follows_source(&self) -> bool {
+        self.reference == ClockReference::Source
+    }
+
     /// Get the pending correction for a slave (read-only, does not modify state)
     ///
     /// Returns number of samples to skip (positive) or duplicate (negative)
@@ -147,6 +311,7 @@ impl ClockSync {
         if let Some(slave) = self.slaves.get_mut(device_id) {
             if slave.pending_correction != 0 {
                 slave.drift_samples -= slave.pending_correction;
+                slave.corrected += slave.pending_correction;
                 slave.pending_correction = 0;
             }
         }
@@ -162,6 +327,7 @@ impl ClockSync {
             if correction != 0 {
                 // Apply correction to drift tracking
                 slave.drift_samples -= correction;
+                slave.corrected += correction;
                 slave.pending_correction = 0;
             }
             correction
@@ -199,8 +365,13 @@ impl ClockSync {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread::sleep;
-    use std::time::Duration;
+
+    fn at(frames: u64, millis: u64) -> DevicePosition {
+        DevicePosition {
+            frames,
+            time: Duration::from_millis(millis),
+        }
+    }
 
     #[test]
     fn test_master_slave_basic() {
@@ -219,12 +390,113 @@ mod tests {
         sync.register_slave("slave1");
 
         // Update slave with matching rate - should have no drift
-        sync.update_slave("slave1", 0);
-        sleep(Duration::from_millis(10));
-        sync.update_slave("slave1", 480); // 10ms worth of samples
+        sync.update_master(at(1000, 0));
+        sync.update_slave("slave1", at(0, 0));
+        sync.update_master(at(1480, 10));
+        sync.update_slave("slave1", at(480, 10)); // 10ms worth of samples
 
         let drift = sync.get_drift_ms("slave1").unwrap();
-        // Should be close to 0 (within tolerance for timing)
-        assert!(drift.abs() < 5.0, "Drift was {}", drift);
+        assert_eq!(drift, 0.0);
+    }
+
+    #[test]
+    fn test_slow_slave_is_corrected() {
+        let mut sync = ClockSync::new(48000);
+        sync.set_master("master");
+        sync.register_slave("slave1");
+
+        // Over 10s the slave plays 300 frames (6.25ms) fewer than the
+        // master, whose own clock runs fast
+        for second in 0..=10 {
+            sync.update_master(at(48_005 * second, 1000 * second));
+            sync.update_slave("slave1", at(47_975 * second + 100, 1000 * second + 3));
+        }
+        assert_eq!(sync.get_drift_ms("slave1"), Some(6.25));
+        assert_eq!(sync.get_correction_readonly("slave1"), 48);
+
+        // Skipped audio counts towards the drift
+        sync.apply_correction("slave1");
+        sync.update_slave("slave1", at(47_975 * 10 + 100, 10_003));
+        assert_eq!(sync.get_drift_ms("slave1"), Some(5.25));
+
+        // A restarted stream keeps the drift measured so far
+        sync.update_slave("slave1", at(0, 10_500));
+        sync.update_slave("slave1", at(24_000, 11_000));
+        assert_eq!(sync.get_drift_ms("slave1"), Some(5.25));
+    }
+
+    #[test]
+    fn test_promoted_slave_keeps_drift_between_slaves() {
+        let mut sync = ClockSync::new(48000);
+        sync.set_master("master");
+        sync.register_slave("a");
+        sync.register_slave("b");
+
+        // Against the master, a is 480 frames (10ms) behind and b 960
+        sync.update_master(at(0, 0));
+        sync.update_slave("a", at(0, 0));
+        sync.update_slave("b", at(0, 0));
+        sync.update_master(at(48_000, 1000));
+        sync.update_slave("a", at(47_520, 1000));
+        sync.update_slave("b", at(47_040, 1000));
+        assert_eq!(sync.get_drift_ms("b"), Some(20.0));
+
+        assert!(sync.promote("a"));
+        assert!(!sync.promote("master"));
+        assert_eq!(sync.master_id(), Some("a"));
+        assert_eq!(sync.get_drift_ms("a"), None);
+        // Rebased onto a, b is 10ms behind, now and on the next reading
+        assert_eq!(sync.get_drift_ms("b"), Some(10.0));
+        sync.update_master(at(95_040, 2000));
+        sync.update_slave("b", at(94_080, 2000));
+        assert_eq!(sync.get_drift_ms("b"), Some(20.0));
+    }
+
+    #[test]
+    fn test_follow_source_backlog() {
+        let mut sync = ClockSync::with_reference(48000, ClockReference::Source);
+        sync.register_slave("hdmi");
+        assert!(sync.follows_source());
+
+        // A steady backlog needs no correction, however much time passes
+        for _ in 0..20 {
+            sync.update_backlog("hdmi", 2400);
+        }
+        assert_eq!(sync.get_correction_readonly("hdmi"), 0);
+
+        // A renderer falling behind skips frames, at most 1ms per update
+        for _ in 0..20 {
+            sync.update_backlog("hdmi", 4800);
+        }
+        assert_eq!(sync.get_correction_readonly("hdmi"), 48);
+
+        // One running ahead is left alone
+        for _ in 0..40 {
+            sync.update_backlog("hdmi", 0);
+        }
+        assert_eq!(sync.get_correction_readonly("hdmi"), 0);
+    }
+
+    #[test]
+    fn test_drift_limits() {
+        let (threshold_ms, max_correction_ms) = SyncStrictness::Loose.drift_limits();
+        let mut sync = ClockSync::with_reference(48000, ClockReference::Source)
+            .with_drift_limits(threshold_ms, max_correction_ms);
+        sync.register_slave("hdmi");
+
+        // 10ms behind is within loose sync
+        for _ in 0..40 {
+            sync.update_backlog("hdmi", 2400);
+        }
+        for _ in 0..40 {
+            sync.update_backlog("hdmi", 2880);
+        }
+        assert_eq!(sync.get_correction_readonly("hdmi"), 0);
+
+        // Beyond 15ms it is corrected half a millisecond at a time
+        for _ in 0..40 {
+            sync.update_backlog("hdmi", 3600);
+        }
+        assert_eq!(sync.get_correction_readonly("hdmi"), 24);
     }
 }
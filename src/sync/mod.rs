@@ -2,4 +2,7 @@
 
 mod clock;
 
-pub use clock::ClockSync;
+pub use clock::{
+    ClockReference, ClockSync, DevicePosition, SyncStrictness, DEFAULT_DRIFT_THRESHOLD_MS,
+    DEFAULT_MAX_CORRECTION_MS,
+};
@@ -1,22 +1,39 @@
 //! Main tray application
 
-use crate::audio::EngineState;
+use crate::audio::{DeviceStatus, EngineState, ZoneStatus};
+use crate::ipc::{IpcMessage, PipeServer, Request, Response, StatusReport, TRAY_PIPE_NAME};
+use crate::logging;
+use crate::service::schedule::{self, LocalTime};
+use crate::tray::autostart;
 use crate::tray::controller::{EngineController, EngineStatus, TrayCommand};
-use crate::tray::icon::IconManager;
+use crate::tray::error::{ErrorLog, Result, TrayError};
+use crate::tray::hotkey::{HotkeyAction, HotkeyManager};
+use crate::tray::i18n::{self, Language};
+use crate::tray::icon::{IconManager, ThemeWatcher};
+use crate::tray::log_viewer::{self, LogViewer};
 use crate::tray::menu::{MenuAction, MenuManager};
-use anyhow::Result;
+use crate::tray::notification::Notifier;
+use crate::tray::power::PowerWatcher;
+use crate::tray::settings::TraySettings;
+use crate::tray::shell;
 use crossbeam_channel::{bounded, Receiver, Sender};
 use muda::MenuEvent;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
-use std::time::Duration;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 use tray_icon::{MouseButton, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use windows::core::HSTRING;
 use windows::Win32::UI::WindowsAndMessaging::{
-    DispatchMessageW, PeekMessageW, PostQuitMessage, TranslateMessage, MSG, PM_REMOVE, WM_QUIT,
+    DispatchMessageW, MessageBoxW, PeekMessageW, PostQuitMessage, SetProcessDPIAware,
+    TranslateMessage, IDYES, MB_ICONINFORMATION, MB_ICONQUESTION, MB_OK, MB_SETFOREGROUND,
+    MB_YESNO, MSG, PM_REMOVE, WM_HOTKEY, WM_QUIT,
 };
 
+/// Time Exit waits for the controller to stop the engine before exiting anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Configuration for tray application
 #[derive(Debug, Clone)]
 pub struct TrayConfig {
@@ -35,6 +52,13 @@ impl Default for TrayConfig {
     }
 }
 
+/// What the tooltip's warning is about, so it goes once that works again
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Failing {
+    Device(String),
+    Capture,
+}
+
 /// Main tray application
 pub struct TrayApp {
     config: TrayConfig,
@@ -45,6 +69,33 @@ pub struct TrayApp {
     status_rx: Receiver<EngineStatus>,
     controller_handle: Option<JoinHandle<()>>,
     exit_flag: Arc<AtomicBool>,
+    ipc_server: Option<PipeServer>,
+    ipc_rx: Receiver<IpcMessage>,
+    attached_to_service: bool,
+    muted: bool,
+    paused_all: bool,
+    /// Last engine state, device list and zones, for status requests over IPC
+    running: bool,
+    devices: Vec<DeviceStatus>,
+    zones: Vec<ZoneStatus>,
+    /// Warning shown in the tooltip until the engine stops, or until what
+    /// `failing` names works again
+    warning: Option<String>,
+    failing: Option<Failing>,
+    /// Peak level of the captured audio in dBFS for the tooltip, None while silent
+    level: Option<i32>,
+    hotkeys: Option<HotkeyManager>,
+    theme_watcher: Option<ThemeWatcher>,
+    power_watcher: Option<PowerWatcher>,
+    /// The error icon is shown, until the engine state changes
+    showing_error: bool,
+    /// Error shown in the tooltip until the engine state changes
+    error: Option<TrayError>,
+    /// Errors listed by "Recent Errors"
+    errors: ErrorLog,
+    notifier: Option<Notifier>,
+    /// "View Log" window, once opened
+    log_viewer: Option<LogViewer>,
 }
 
 impl TrayApp {
@@ -56,10 +107,25 @@ impl TrayApp {
         // Start engine controller in background and keep handle
         let controller_handle = EngineController::start(command_rx, status_tx);
 
+        // Icons and dialogs at the display's DPI rather than scaled up
+        unsafe {
+            let _ = SetProcessDPIAware();
+        }
         let icon_manager = IconManager::new()?;
+        apply_language(TraySettings::load().language);
         let menu_manager = MenuManager::new();
         let exit_flag = Arc::new(AtomicBool::new(false));
 
+        // Listen for requests from other instances (e.g. activation handoff)
+        let (ipc_tx, ipc_rx) = bounded(16);
+        let ipc_server = match PipeServer::start(TRAY_PIPE_NAME, ipc_tx) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warn!("IPC server unavailable: {}", e);
+                None
+            }
+        };
+
         Ok(Self {
             config,
             tray_icon: None,
@@ -69,6 +135,25 @@ impl TrayApp {
             status_rx,
             controller_handle: Some(controller_handle),
             exit_flag,
+            ipc_server,
+            ipc_rx,
+            attached_to_service: false,
+            muted: false,
+            paused_all: false,
+            running: false,
+            devices: Vec::new(),
+            zones: Vec::new(),
+            warning: None,
+            failing: None,
+            level: None,
+            hotkeys: None,
+            theme_watcher: None,
+            power_watcher: None,
+            showing_error: false,
+            error: None,
+            errors: ErrorLog::default(),
+            notifier: None,
+            log_viewer: None,
         })
     }
 
@@ -81,16 +166,62 @@ impl TrayApp {
         let icon = self.icon_manager.get_idle_icon()?;
         let tray_icon = TrayIconBuilder::new()
             .with_menu(Box::new(menu))
-            .with_tooltip("wemux - Audio Sync")
+            .with_tooltip(self.tooltip())
             .with_icon(icon)
             .build()?;
 
         self.tray_icon = Some(tray_icon);
 
+        // Bound to this thread like the hotkeys
+        self.theme_watcher = match ThemeWatcher::new() {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Icons won't follow theme changes: {}", e);
+                None
+            }
+        };
+        self.power_watcher = match PowerWatcher::new() {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Audio won't restart on its own after sleep: {}", e);
+                None
+            }
+        };
+        if self.config.show_notifications {
+            self.notifier = match Notifier::new() {
+                Ok(notifier) => Some(notifier),
+                Err(e) => {
+                    warn!("Errors won't be notified: {}", e);
+                    None
+                }
+            };
+        }
+
+        let settings = TraySettings::load();
+        if TraySettings::take_recovered() {
+            if let Some(notifier) = &mut self.notifier {
+                if let Err(e) = notifier.show_warning("wemux", i18n::text().settings_recovered) {
+                    warn!("Failed to show notification: {}", e);
+                }
+            }
+        }
+        // Hotkeys are bound to this thread, which runs the message loop
+        self.hotkeys = Some(HotkeyManager::register(&settings.hotkeys));
+        self.sync_start_with_windows(settings.start_with_windows);
+        self.menu_manager
+            .update_sync_strictness(settings.sync_strictness);
+        self.menu_manager.update_volume_mode(settings.volume_mode);
+        self.menu_manager
+            .update_mirror_volume(settings.mirror_volume.unwrap_or(1.0));
+
         // Auto-start engine if configured
         if self.config.auto_start {
-            info!("Auto-starting engine");
-            self.command_tx.send(TrayCommand::Start)?;
+            if schedule::within_active_hours(&settings.active_hours, LocalTime::now()) {
+                info!("Auto-starting engine");
+                self.command_tx.send(TrayCommand::Start)?;
+            } else {
+                info!("Outside active hours, engine not auto-started");
+            }
         }
 
         // Request initial device list
@@ -121,6 +252,10 @@ impl TrayApp {
                         info!("Received WM_QUIT, exiting");
                         return Ok(());
                     }
+                    if msg.message == WM_HOTKEY {
+                        self.handle_hotkey(msg.wParam.0 as i32);
+                        continue;
+                    }
                     let _ = TranslateMessage(&msg);
                     DispatchMessageW(&msg);
                 }
@@ -129,24 +264,44 @@ impl TrayApp {
             // Process tray icon events
             if let Ok(event) = TrayIconEvent::receiver().try_recv() {
                 if let Err(e) = self.handle_tray_event(event) {
-                    error!("Error handling tray event: {}", e);
+                    self.report_error(e);
                 }
             }
 
             // Process menu events
             if let Ok(event) = MenuEvent::receiver().try_recv() {
                 if let Err(e) = self.handle_menu_event(event) {
-                    error!("Error handling menu event: {}", e);
+                    self.report_error(e);
                 }
             }
 
             // Process status updates from engine
             while let Ok(status) = self.status_rx.try_recv() {
                 if let Err(e) = self.handle_status_update(status) {
-                    error!("Error handling status update: {}", e);
+                    self.report_error(e);
                 }
             }
 
+            // Process requests from other instances
+            while let Ok(message) = self.ipc_rx.try_recv() {
+                let response = self.handle_ipc_request(message.request);
+                let _ = message.reply.send(response);
+            }
+
+            if self.theme_watcher.as_ref().is_some_and(|w| w.changed()) {
+                if let Err(e) = self.refresh_icon() {
+                    error!("Failed to update icon for the new theme: {}", e);
+                }
+            }
+            if let Some(watcher) = &self.power_watcher {
+                for command in watcher.commands() {
+                    self.command_tx.send(command)?;
+                }
+            }
+            if let Some(notifier) = &mut self.notifier {
+                notifier.expire();
+            }
+
             // Small sleep to avoid busy-waiting
             std::thread::sleep(Duration::from_millis(10));
         }
@@ -160,14 +315,28 @@ impl TrayApp {
     fn shutdown(&mut self) {
         info!("Performing clean shutdown...");
 
+        // Stop accepting requests from other instances
+        self.ipc_server = None;
+        self.hotkeys = None;
+
         // Send shutdown command to controller
         let _ = self.command_tx.send(TrayCommand::Shutdown);
 
-        // Wait for controller thread to finish
+        // Wait for controller thread to finish. The engine leaves threads
+        // stuck in a driver call behind; if even that hangs, exit anyway:
+        // ending the process takes every thread with it.
         if let Some(handle) = self.controller_handle.take() {
             info!("Waiting for controller thread to finish...");
-            let _ = handle.join();
-            info!("Controller thread finished");
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            while !handle.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            if handle.is_finished() {
+                let _ = handle.join();
+                info!("Controller thread finished");
+            } else {
+                error!("Controller thread did not finish in time, exiting without it");
+            }
         }
 
         // Drop tray icon to remove from system tray
@@ -204,10 +373,69 @@ impl TrayApp {
                     self.command_tx
                         .send(TrayCommand::ToggleDevice { device_id })?;
                 }
+                MenuAction::ToggleZone(name) => {
+                    info!("Toggle zone: {}", name);
+                    self.command_tx.send(TrayCommand::ToggleZone { name })?;
+                }
                 MenuAction::RefreshDevices => {
                     info!("Refresh devices");
                     self.command_tx.send(TrayCommand::RefreshDevices)?;
                 }
+                MenuAction::RestoreSettings => {
+                    info!("Restore previous settings");
+                    self.command_tx.send(TrayCommand::RestoreSettings)?;
+                }
+                MenuAction::PruneDevices => {
+                    info!("Clean up old devices");
+                    self.command_tx.send(TrayCommand::PruneDevices)?;
+                }
+                MenuAction::MeasureDelays => {
+                    info!("Measure audio delay");
+                    self.command_tx.send(TrayCommand::MeasureDelays)?;
+                }
+                MenuAction::OpenSoundSettings => shell::open_sound_settings()?,
+                MenuAction::OpenDeviceProperties(device_id) => {
+                    shell::open_device_properties(&device_id)?;
+                }
+                MenuAction::ToggleStartWithWindows => {
+                    let enabled = !self.menu_manager.start_with_windows();
+                    info!("Start with Windows: {}", enabled);
+                    if let Err(e) = self.set_start_with_windows(enabled) {
+                        // Undo the check mark muda already toggled
+                        self.menu_manager.update_start_with_windows(!enabled);
+                        return Err(TrayError::Ui(format!(
+                            "Failed to change Windows startup: {}",
+                            e
+                        )));
+                    }
+                }
+                MenuAction::SetLogLevel(level) => {
+                    info!("Log level: {}", level);
+                    // muda toggled the clicked item; a level stays checked
+                    self.menu_manager.update_log_level(level);
+                    self.command_tx.send(TrayCommand::SetLogLevel(level))?;
+                }
+                MenuAction::ViewLog => LogViewer::show(&mut self.log_viewer)?,
+                MenuAction::OpenLogFolder => log_viewer::open_log_folder()?,
+                MenuAction::SetSyncStrictness(strictness) => {
+                    info!("Sync strictness: {:?}", strictness);
+                    // A strictness stays checked, like a log level
+                    self.menu_manager.update_sync_strictness(strictness);
+                    self.command_tx
+                        .send(TrayCommand::SetSyncStrictness(strictness))?;
+                }
+                MenuAction::SetVolumeMode(mode) => {
+                    info!("Volume mode: {}", mode);
+                    // A mode stays checked, like a log level
+                    self.menu_manager.update_volume_mode(mode);
+                    self.command_tx.send(TrayCommand::SetVolumeMode(mode))?;
+                }
+                MenuAction::SetMirrorVolume(percent) => {
+                    info!("Mirror volume: {}%", percent);
+                    let volume = percent as f32 / 100.0;
+                    self.menu_manager.update_mirror_volume(volume);
+                    self.command_tx.send(TrayCommand::SetMirrorVolume(volume))?;
+                }
                 MenuAction::StartEngine => {
                     info!("Start engine");
                     self.command_tx.send(TrayCommand::Start)?;
@@ -216,6 +444,13 @@ impl TrayApp {
                     info!("Stop engine");
                     self.command_tx.send(TrayCommand::Stop)?;
                 }
+                MenuAction::TogglePauseAll => {
+                    // muda toggled the check mark; the engine's answer sets it
+                    self.menu_manager.update_paused_all(self.paused_all);
+                    self.command_tx.send(TrayCommand::PauseAll)?;
+                }
+                MenuAction::ShowErrors => self.show_errors(),
+                MenuAction::ShowLastError => self.show_last_error(),
                 MenuAction::Exit => {
                     info!("Exit application requested");
                     // Set exit flag to break event loop
@@ -231,11 +466,297 @@ impl TrayApp {
         Ok(())
     }
 
+    fn handle_hotkey(&mut self, id: i32) {
+        let command = match HotkeyAction::from_id(id) {
+            Some(HotkeyAction::ToggleEngine) => TrayCommand::ToggleEngine,
+            Some(HotkeyAction::ToggleMute) => TrayCommand::ToggleMute,
+            None => return,
+        };
+        info!("Hotkey pressed: {:?}", command);
+        if let Err(e) = self.command_tx.send(command) {
+            error!("Error handling hotkey: {}", e);
+        }
+    }
+
+    fn handle_ipc_request(&mut self, request: Request) -> Response {
+        match request {
+            Request::Activate => {
+                info!("Activation requested by another instance, opening settings");
+                match Self::open_settings() {
+                    Ok(()) => Response::Ok,
+                    Err(e) => Response::Error {
+                        message: e.to_string(),
+                    },
+                }
+            }
+            Request::Status => Response::Status(StatusReport {
+                running: self.running,
+                devices: self.devices.clone(),
+                muted: self.muted,
+                paused_all: self.paused_all,
+                zones: self.zones.clone(),
+            }),
+            Request::Start => self.forward(TrayCommand::Start),
+            Request::Stop => self.forward(TrayCommand::Stop),
+            Request::Restart => self.forward(TrayCommand::Restart),
+            Request::PauseDevice { device_id } => self.set_device_enabled(device_id, false),
+            Request::ResumeDevice { device_id } => self.set_device_enabled(device_id, true),
+            Request::SetLogLevel { level } => match logging::set_log_level(level) {
+                Ok(()) => {
+                    self.menu_manager.update_log_level(level);
+                    Response::Ok
+                }
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            },
+            // Saved in the settings like a choice from the Volume menu
+            Request::SetVolumeMode { mode } => {
+                self.menu_manager.update_volume_mode(mode);
+                self.forward(TrayCommand::SetVolumeMode(mode))
+            }
+            Request::SetMirrorVolume { volume } => {
+                self.menu_manager.update_mirror_volume(volume);
+                self.forward(TrayCommand::SetMirrorVolume(volume))
+            }
+            _ => Response::Error {
+                message: "Engine control is not available on the tray pipe".to_string(),
+            },
+        }
+    }
+
+    /// Pass a command on to the engine controller on behalf of another process
+    fn forward(&self, command: TrayCommand) -> Response {
+        match self.command_tx.send(command) {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error {
+                message: e.to_string(),
+            },
+        }
+    }
+
+    /// Pause or resume a device on behalf of another process, like its menu item
+    fn set_device_enabled(&self, device_id: String, enabled: bool) -> Response {
+        let Some(device) = self.devices.iter().find(|d| d.id == device_id) else {
+            return Response::Error {
+                message: format!("Unknown device: {}", device_id),
+            };
+        };
+        if device.is_auto_paused() {
+            return Response::Error {
+                message: format!(
+                    "{} is the {} and cannot be controlled",
+                    device.display_name(),
+                    if device.is_system_default {
+                        "system default output"
+                    } else {
+                        "capture source"
+                    }
+                ),
+            };
+        }
+
+        match self
+            .command_tx
+            .send(TrayCommand::SetDeviceEnabled { device_id, enabled })
+        {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error {
+                message: e.to_string(),
+            },
+        }
+    }
+
+    /// Render the icons again for a changed theme or DPI and show the new one
+    fn refresh_icon(&mut self) -> Result<()> {
+        if !self.icon_manager.refresh()? {
+            return Ok(());
+        }
+        info!("Taskbar theme or DPI changed, icons updated");
+        let icon = if self.showing_error {
+            self.icon_manager.get_error_icon()?
+        } else if self.running {
+            self.icon_manager.get_active_icon()?
+        } else {
+            self.icon_manager.get_idle_icon()?
+        };
+        if let Some(ref tray) = self.tray_icon {
+            tray.set_icon(Some(icon))?;
+        }
+        Ok(())
+    }
+
+    /// Remove the tooltip's warning if it is about `recovered`
+    fn clear_warning(&mut self, recovered: Failing) -> Result<()> {
+        if self.failing.as_ref() != Some(&recovered) {
+            return Ok(());
+        }
+        self.warning = None;
+        self.failing = None;
+        if let Some(ref tray) = self.tray_icon {
+            tray.set_tooltip(Some(self.tooltip()))?;
+        }
+        Ok(())
+    }
+
+    /// Tooltip for the current engine host and mute state
+    fn tooltip(&self) -> String {
+        let text = i18n::text();
+        let mut tooltip = if self.attached_to_service {
+            text.service_tooltip
+        } else {
+            text.tooltip
+        }
+        .to_string();
+        if self.paused_all {
+            tooltip.push_str(" - ");
+            tooltip.push_str(text.paused);
+        }
+        if self.muted {
+            tooltip.push_str(" - ");
+            tooltip.push_str(text.muted);
+        }
+        if self.running {
+            match self.level {
+                Some(db) => tooltip.push_str(&format!("\n{}: {} dB", text.signal, db)),
+                None => tooltip.push_str(&format!("\n{}: {}", text.signal, text.silent)),
+            }
+        }
+        if let Some(warning) = &self.warning {
+            tooltip.push('\n');
+            tooltip.push_str(warning);
+        }
+        if let Some(error) = &self.error {
+            tooltip.push('\n');
+            tooltip.push_str(&error.to_string());
+        }
+        tooltip
+    }
+
+    /// Show an error with the error icon, in the tooltip and in a
+    /// notification, and keep it for "Recent Errors"
+    fn report_error(&mut self, error: TrayError) {
+        error!("{}", error);
+        // A failure that keeps repeating is shown once
+        if self.error.as_ref() == Some(&error) {
+            return;
+        }
+        self.errors
+            .push(LocalTime::now().time.to_string(), error.clone());
+        self.showing_error = true;
+
+        if let Some(notifier) = &mut self.notifier {
+            if let Err(e) = notifier.show_error("wemux", &error.to_string()) {
+                warn!("Failed to show notification: {}", e);
+            }
+        }
+        self.error = Some(error);
+
+        if let Err(e) = self.show_error_icon() {
+            warn!("Failed to show the error in the tray: {}", e);
+        }
+    }
+
+    fn show_error_icon(&self) -> Result<()> {
+        if let Some(ref tray) = self.tray_icon {
+            tray.set_icon(Some(self.icon_manager.get_error_icon()?))?;
+            tray.set_tooltip(Some(self.tooltip()))?;
+        }
+        Ok(())
+    }
+
+    /// List the recent errors in a message box
+    fn show_errors(&self) {
+        let text = i18n::text();
+        let message = if self.errors.is_empty() {
+            text.no_errors.to_string()
+        } else {
+            self.errors.lines()
+        };
+        // Blocks the message loop like the menu does while open
+        unsafe {
+            MessageBoxW(
+                None,
+                &HSTRING::from(message),
+                &HSTRING::from(text.errors_title),
+                MB_OK | MB_ICONINFORMATION | MB_SETFOREGROUND,
+            );
+        }
+    }
+
+    /// Ask whether to control the engine the service runs instead of the
+    /// tray's own, and tell the controller if so
+    fn offer_service_engine(&mut self) -> Result<()> {
+        let answer = unsafe {
+            MessageBoxW(
+                None,
+                &HSTRING::from(i18n::text().service_running_prompt),
+                &HSTRING::from("wemux"),
+                MB_YESNO | MB_ICONQUESTION | MB_SETFOREGROUND,
+            )
+        };
+        if answer == IDYES {
+            self.command_tx.send(TrayCommand::AttachToService)?;
+        } else {
+            self.report_error(TrayError::Service(
+                "Already running the audio engine".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Show the newest error in full, with the diagnosis of a failed start
+    fn show_last_error(&self) {
+        let text = i18n::text();
+        let message = match self.errors.last() {
+            Some((time, error)) => format!("{}\n\n{}", time, error.report()),
+            None => text.no_errors.to_string(),
+        };
+        unsafe {
+            MessageBoxW(
+                None,
+                &HSTRING::from(message),
+                &HSTRING::from(text.last_error_title),
+                MB_OK | MB_ICONINFORMATION | MB_SETFOREGROUND,
+            );
+        }
+    }
+
+    /// Register or unregister the tray at logon and remember the choice
+    fn set_start_with_windows(&mut self, enabled: bool) -> Result<()> {
+        autostart::apply(enabled)?;
+        TraySettings::load().update(|settings| settings.start_with_windows = enabled)?;
+        self.menu_manager.update_start_with_windows(enabled);
+        Ok(())
+    }
+
+    /// Bring the Run registry key in line with the settings
+    fn sync_start_with_windows(&mut self, enabled: bool) {
+        if let Err(e) = autostart::apply(enabled) {
+            warn!("Failed to update Windows startup: {}", e);
+        }
+        self.menu_manager.update_start_with_windows(enabled);
+    }
+
+    /// Open the settings file in Notepad, creating it first if needed
+    fn open_settings() -> Result<()> {
+        let path = TraySettings::settings_path();
+        if !path.exists() {
+            TraySettings::load().save()?;
+        }
+
+        std::process::Command::new("notepad.exe")
+            .arg(&path)
+            .spawn()?;
+        Ok(())
+    }
+
     fn handle_status_update(&mut self, status: EngineStatus) -> Result<()> {
         match status {
             EngineStatus::DevicesUpdated(devices) => {
                 info!("Devices updated: {} devices", devices.len());
                 self.menu_manager.update_device_menu(&devices)?;
+                self.devices = devices;
 
                 // Rebuild complete menu with updated devices
                 let menu = self.menu_manager.build_initial_menu()?;
@@ -244,6 +765,20 @@ impl TrayApp {
                     tray.set_menu(Some(Box::new(menu)));
                 }
             }
+            EngineStatus::ActivityChanged(receiving) => {
+                self.menu_manager.update_activity(&receiving);
+            }
+            EngineStatus::LevelChanged(level) => {
+                self.level = level;
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::ZonesUpdated(zones) => {
+                // Shown with the menu rebuild that follows the device update
+                self.menu_manager.update_zones(&zones);
+                self.zones = zones;
+            }
             EngineStatus::DefaultDeviceChanged(device_name) => {
                 info!("Default device changed to: {}", device_name);
                 // Update system output display
@@ -251,6 +786,13 @@ impl TrayApp {
             }
             EngineStatus::EngineStateChanged(state) => {
                 info!("Engine state changed: {:?}", state);
+                if state == EngineState::Stopped {
+                    self.warning = None;
+                    self.failing = None;
+                }
+                self.running = state == EngineState::Running;
+                self.showing_error = false;
+                self.error = None;
                 self.menu_manager
                     .update_engine_state(state == EngineState::Running)?;
 
@@ -262,27 +804,142 @@ impl TrayApp {
 
                 if let Some(ref tray) = self.tray_icon {
                     tray.set_icon(Some(icon))?;
+                    tray.set_tooltip(Some(self.tooltip()))?;
                 }
             }
-            EngineStatus::Error(msg) => {
-                error!("Engine error: {}", msg);
-
-                if self.config.show_notifications {
-                    // Could show Windows toast notification here
-                    // For now, just log the error
-                }
+            EngineStatus::Recovering {
+                attempt,
+                max_attempts,
+                reason,
+            } => {
+                warn!(
+                    "Engine recovering (attempt {}/{}): {}",
+                    attempt, max_attempts, reason
+                );
+                self.running = false;
+                self.showing_error = true;
+                self.menu_manager.update_engine_state(false)?;
 
                 if let Some(ref tray) = self.tray_icon {
                     let icon = self.icon_manager.get_error_icon()?;
                     tray.set_icon(Some(icon))?;
+                    tray.set_tooltip(Some(format!(
+                        "wemux - {} ({}/{})",
+                        i18n::text().restarting,
+                        attempt,
+                        max_attempts
+                    )))?;
                 }
             }
+            EngineStatus::MuteChanged(muted) => {
+                info!("Output {}", if muted { "muted" } else { "unmuted" });
+                self.muted = muted;
+
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::PausedAllChanged(paused) => {
+                info!("Mirroring {}", if paused { "paused" } else { "resumed" });
+                self.paused_all = paused;
+                self.menu_manager.update_paused_all(paused);
+
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::ServiceAttached(attached) => {
+                info!(
+                    "{} wemux service",
+                    if attached {
+                        "Attached to"
+                    } else {
+                        "Detached from"
+                    }
+                );
+                self.attached_to_service = attached;
+
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::ServiceRunning { ask: true } => self.offer_service_engine()?,
+            EngineStatus::ServiceRunning { ask: false } => {
+                info!("The wemux service runs the engine");
+                if let Some(notifier) = &mut self.notifier {
+                    let notice = i18n::text().service_running_notice;
+                    if let Err(e) = notifier.show_warning("wemux", notice) {
+                        warn!("Failed to show notification: {}", e);
+                    }
+                }
+            }
+            EngineStatus::SettingsReloaded => {
+                let settings = TraySettings::load();
+                // Unregister first so unchanged bindings can be registered again
+                self.hotkeys = None;
+                self.hotkeys = Some(HotkeyManager::register(&settings.hotkeys));
+                self.sync_start_with_windows(settings.start_with_windows);
+                self.menu_manager
+                    .update_sync_strictness(settings.sync_strictness);
+                self.menu_manager.update_volume_mode(settings.volume_mode);
+                self.menu_manager
+                    .update_mirror_volume(settings.mirror_volume.unwrap_or(1.0));
+
+                // Show a changed language right away
+                apply_language(settings.language);
+                let menu = self.menu_manager.build_initial_menu()?;
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_menu(Some(Box::new(menu)));
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::DeviceFailing {
+                device_id,
+                name,
+                reason,
+            } => {
+                warn!("Output {} ({}) failing: {}", name, device_id, reason);
+                self.warning = Some(format!("{}: {}", name, reason));
+                self.failing = Some(Failing::Device(device_id));
+
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::DeviceWorking { device_id } => {
+                self.clear_warning(Failing::Device(device_id))?;
+            }
+            EngineStatus::CaptureFailing(error) => {
+                warn!("Capture failing: {}", error);
+                self.warning = Some(format!("Capture failing: {}", error));
+                self.failing = Some(Failing::Capture);
+
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::CaptureWorking => self.clear_warning(Failing::Capture)?,
+            EngineStatus::Warning(msg) => {
+                warn!("Engine warning: {}", msg);
+                self.warning = Some(msg);
+                self.failing = None;
+
+                if let Some(ref tray) = self.tray_icon {
+                    tray.set_tooltip(Some(self.tooltip()))?;
+                }
+            }
+            EngineStatus::Error(error) => self.report_error(error),
         }
 
         Ok(())
     }
 }
 
+/// Show the tray in the configured language, or the Windows locale's
+fn apply_language(setting: Option<Language>) {
+    i18n::set_language(setting.unwrap_or_else(Language::detect));
+}
+
 impl Drop for TrayApp {
     fn drop(&mut self) {
         // Ensure clean shutdown when TrayApp is dropped
@@ -0,0 +1,110 @@
+//! "Start with Windows" through the per-user Run registry key
+//!
+//! The tray registers itself under `HKCU\Software\Microsoft\Windows\CurrentVersion\Run`,
+//! which needs no admin rights. `TraySettings::start_with_windows` is the
+//! source of truth: the tray brings the registry in line with it at startup
+//! and whenever the setting changes, which also repoints the entry at the
+//! current executable after wemux was moved.
+
+use crate::tray::error::Result;
+use crate::tray::TraySettings;
+use tracing::info;
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+use windows::Win32::System::Registry::{
+    RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_SZ,
+};
+
+const RUN_KEY: PCWSTR = w!(r"Software\Microsoft\Windows\CurrentVersion\Run");
+
+const VALUE_NAME: PCWSTR = w!("wemux");
+
+/// Command line that starts this executable, quoted for paths with spaces
+///
+/// Keeps a settings file given on the command line, so the tray started at
+/// logon uses the same settings.
+fn command() -> Result<String> {
+    let exe = std::env::current_exe()?;
+    Ok(match TraySettings::custom_path() {
+        Some(settings) => format!(
+            "\"{}\" --settings \"{}\"",
+            exe.display(),
+            settings.display()
+        ),
+        None => format!("\"{}\"", exe.display()),
+    })
+}
+
+/// Command registered to run at logon, if any
+pub fn registered_command() -> Option<String> {
+    unsafe {
+        let mut size = 0u32;
+        let status = RegGetValueW(
+            HKEY_CURRENT_USER,
+            RUN_KEY,
+            VALUE_NAME,
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            Some(&mut size),
+        );
+        if status != ERROR_SUCCESS || size == 0 {
+            return None;
+        }
+
+        let mut data = vec![0u16; (size as usize).div_ceil(2)];
+        let status = RegGetValueW(
+            HKEY_CURRENT_USER,
+            RUN_KEY,
+            VALUE_NAME,
+            RRF_RT_REG_SZ,
+            None,
+            Some(data.as_mut_ptr().cast()),
+            Some(&mut size),
+        );
+        if status != ERROR_SUCCESS {
+            return None;
+        }
+
+        let len = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+        Some(String::from_utf16_lossy(&data[..len]))
+    }
+}
+
+/// Register this executable to start at logon, or remove the registration
+///
+/// Does nothing if the registry already matches.
+pub fn apply(enabled: bool) -> Result<()> {
+    let registered = registered_command();
+
+    if !enabled {
+        if registered.is_some() {
+            let status = unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, RUN_KEY, VALUE_NAME) };
+            if status != ERROR_FILE_NOT_FOUND {
+                status.ok()?;
+            }
+            info!("Removed wemux from Windows startup");
+        }
+        return Ok(());
+    }
+
+    let command = command()?;
+    if registered.as_deref() == Some(command.as_str()) {
+        return Ok(());
+    }
+
+    let data: Vec<u16> = command.encode_utf16().chain(Some(0)).collect();
+    unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            RUN_KEY,
+            VALUE_NAME,
+            REG_SZ.0,
+            Some(data.as_ptr().cast()),
+            (data.len() * 2) as u32,
+        )
+    }
+    .ok()?;
+    info!("Registered {} to start with Windows", command);
+    Ok(())
+}
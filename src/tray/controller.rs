@@ -1,14 +1,54 @@
 //! Bridge between UI and AudioEngine
 
-use crate::audio::{AudioEngine, DeviceStatus, EngineConfig, EngineEvent, EngineState};
+use crate::audio::{
+    AudioEngine, DeviceStatus, EngineConfig, EngineEvent, EngineState, SignalLevel, SourceType,
+    ThreadPriority, VolumeMode, ZoneStatus, BLUETOOTH_LATENCY_MS, PAUSE_FADE_MS,
+};
+use crate::calibrate;
 use crate::device::DeviceEnumerator;
-use crate::tray::settings::TraySettings;
+use crate::error::WemuxError;
+use crate::ipc::{EngineClient, StatusReport, SERVICE_PIPE_NAME};
+use crate::logging::{self, LogLevel};
+use crate::service::power::RESUME_SETTLE;
+use crate::service::schedule::{ActiveHoursTracker, LocalTime};
+use crate::sync::SyncStrictness;
+use crate::tray::error::{Diagnosis, TrayError};
+use crate::tray::settings::{TraySettings, PRUNE_AFTER_DAYS};
 use crossbeam_channel::{bounded, Receiver, Sender};
 use parking_lot::Mutex;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
-use tracing::{error, info, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+/// Maximum automatic restart attempts after a fatal engine error
+const MAX_RESTART_ATTEMPTS: u32 = 3;
+
+/// Delay before the first restart attempt, doubled for each following attempt
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Engine uptime after which a new fatal error starts a fresh series of attempts
+const RESTART_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// How often the service's engine state is polled while attached to it
+const REMOTE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Consecutive failed polls before the service is considered gone
+const REMOTE_MAX_POLL_FAILURES: u32 = 3;
+
+/// How often the settings file is checked for changes made outside the tray
+const SETTINGS_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the menu's marks for outputs receiving audio are brought up to date
+const ACTIVITY_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Least time between level updates within one bucket, each of which
+/// rewrites the tooltip
+const LEVEL_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Width of a level bucket in dB; entering another one (or silence) is
+/// shown right away
+const LEVEL_BUCKET_DB: i32 = 10;
 
 /// Commands sent from UI to Engine
 #[derive(Debug, Clone)]
@@ -17,12 +57,43 @@ pub enum TrayCommand {
     Start,
     /// Stop the audio engine
     Stop,
+    /// Stop the engine if running and start it again
+    Restart,
+    /// Start the engine if stopped, stop it if running
+    ToggleEngine,
+    /// Mute or unmute all outputs
+    ToggleMute,
+    /// Pause or resume all outputs, keeping the engine running
+    PauseAll,
     /// Toggle device enabled/paused state
     ToggleDevice { device_id: String },
     /// Set device enabled state explicitly
     SetDeviceEnabled { device_id: String, enabled: bool },
+    /// Pause a playing zone or resume a paused one
+    ToggleZone { name: String },
     /// Refresh device list
     RefreshDevices,
+    /// Replace the settings with their newest backup
+    RestoreSettings,
+    /// Remove settings of devices not seen for a while
+    PruneDevices,
+    /// Measure the delay of each enabled output with the default microphone
+    MeasureDelays,
+    /// Change the log level of the tray, and of the service while attached to it
+    SetLogLevel(LogLevel),
+    /// Keep outputs in step more loosely or tightly
+    SetSyncStrictness(SyncStrictness),
+    /// Follow the system volume, play at the mirror volume or both
+    SetVolumeMode(VolumeMode),
+    /// Volume of the outputs apart from the system volume (0.0 - 1.0)
+    SetMirrorVolume(f32),
+    /// The computer is about to sleep; stop the engine until it resumes
+    Suspend,
+    /// The computer woke up; start the engine again if it ran before
+    Resume,
+    /// Control the engine hosted by the wemux service, the user's answer to
+    /// [`EngineStatus::ServiceRunning`]
+    AttachToService,
     /// Shutdown the controller
     Shutdown,
 }
@@ -32,12 +103,112 @@ pub enum TrayCommand {
 pub enum EngineStatus {
     /// Device list updated
     DevicesUpdated(Vec<DeviceStatus>),
+    /// Zones and whether each plays; sent before `DevicesUpdated`
+    ZonesUpdated(Vec<ZoneStatus>),
+    /// Outputs receiving audio changed; the IDs of those that do
+    ActivityChanged(Vec<String>),
+    /// Peak level of the audio passed to the outputs changed, in whole dBFS;
+    /// None while silent
+    LevelChanged(Option<i32>),
     /// Default device changed
     DefaultDeviceChanged(String),
     /// Engine state changed
     EngineStateChanged(EngineState),
-    /// Error occurred
-    Error(String),
+    /// Outputs were muted (`true`) or unmuted (`false`)
+    MuteChanged(bool),
+    /// All outputs were paused (`true`) or resumed (`false`)
+    PausedAllChanged(bool),
+    /// Engine hit a fatal error and an automatic restart is scheduled
+    Recovering {
+        attempt: u32,
+        max_attempts: u32,
+        reason: String,
+    },
+    /// Controller attached to (`true`) or detached from (`false`) the engine
+    /// hosted by the wemux service
+    ServiceAttached(bool),
+    /// The local engine can't start as the wemux service runs one; with
+    /// `ask`, the UI asks whether to control it and answers with
+    /// [`TrayCommand::AttachToService`], otherwise it just tells the user
+    ServiceRunning { ask: bool },
+    /// The settings file was changed outside the tray and has been reloaded
+    SettingsReloaded,
+    /// An output is failing; the engine keeps trying it
+    DeviceFailing {
+        device_id: String,
+        name: String,
+        reason: String,
+    },
+    /// An output reported by `DeviceFailing` plays again
+    DeviceWorking { device_id: String },
+    /// Capturing the source is failing; the engine keeps trying it
+    CaptureFailing(String),
+    /// Capturing works again after `CaptureFailing`
+    CaptureWorking,
+    /// Something the user should know about that doesn't stop the engine
+    Warning(String),
+    /// An operation failed; shown with the error icon and kept for the
+    /// "Recent Errors" dialog
+    Error(TrayError),
+}
+
+/// Why the local control loop ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoopExit {
+    /// The tray is shutting down
+    Shutdown,
+    /// The user chose to control the service's engine instead
+    AttachToService,
+}
+
+/// Result of trying to start the local engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StartOutcome {
+    /// Engine is running
+    Started,
+    /// Engine did not start, the error has been reported
+    Failed,
+    /// The service already runs an engine, nothing has been reported
+    ServiceRunning,
+}
+
+/// Bookkeeping for automatic restarts after fatal engine errors
+#[derive(Debug, Default)]
+struct RecoveryState {
+    /// Restart attempts made in the current series
+    attempts: u32,
+    /// When the next restart attempt is due
+    next_attempt: Option<Instant>,
+    /// When the engine last started successfully
+    started_at: Option<Instant>,
+    /// The engine was stopped for sleep and starts again on resume
+    suspended: bool,
+}
+
+/// The level last sent to the UI and when
+#[derive(Debug, Default)]
+struct LevelState {
+    /// Peak in whole dBFS, None while silent
+    db: Option<i32>,
+    sent_at: Option<Instant>,
+}
+
+impl LevelState {
+    /// Whether `db` differs enough from the level sent last, or long
+    /// enough after it, to be sent; records it if so
+    fn update(&mut self, db: Option<i32>, now: Instant) -> bool {
+        let bucket = |db: Option<i32>| db.map(|db| db.div_euclid(LEVEL_BUCKET_DB));
+        let due = bucket(db) != bucket(self.db)
+            || (db != self.db
+                && self
+                    .sent_at
+                    .is_none_or(|at| now.duration_since(at) >= LEVEL_UPDATE_INTERVAL));
+        if due {
+            self.db = db;
+            self.sent_at = Some(now);
+        }
+        due
+    }
 }
 
 /// Controller that bridges UI and AudioEngine
@@ -66,14 +237,41 @@ impl EngineController {
 
             // Create engine inside the thread to avoid Send issues with COM objects
             let mut engine: Option<AudioEngine> = None;
-            Self::run_loop(
-                command_rx,
-                status_tx,
-                &mut engine,
-                &engine_event_rx,
-                engine_event_tx,
-                &settings,
-            );
+            let service = EngineClient::new(SERVICE_PIPE_NAME);
+
+            loop {
+                // Act as a remote control if the service already hosts an engine,
+                // so two engines never fight over the same endpoints
+                match service.status() {
+                    Ok(report) => {
+                        info!("wemux service detected, controlling its engine remotely");
+                        if Self::run_remote_loop(
+                            &service,
+                            report,
+                            &command_rx,
+                            &status_tx,
+                            &settings,
+                        ) {
+                            break;
+                        }
+                        // Replace the service's device list with the local one
+                        Self::refresh_devices(&status_tx, &mut engine, &settings);
+                    }
+                    Err(e) => debug!("No wemux service to attach to: {}", e),
+                }
+
+                let exit = Self::run_loop(
+                    &command_rx,
+                    &status_tx,
+                    &mut engine,
+                    &engine_event_rx,
+                    &engine_event_tx,
+                    &settings,
+                );
+                if exit == LoopExit::Shutdown {
+                    break;
+                }
+            }
 
             // Uninitialize COM when thread exits
             unsafe {
@@ -83,25 +281,34 @@ impl EngineController {
     }
 
     fn run_loop(
-        command_rx: Receiver<TrayCommand>,
-        status_tx: Sender<EngineStatus>,
+        command_rx: &Receiver<TrayCommand>,
+        status_tx: &Sender<EngineStatus>,
         engine: &mut Option<AudioEngine>,
         engine_event_rx: &Receiver<EngineEvent>,
-        engine_event_tx: Sender<EngineEvent>,
+        engine_event_tx: &Sender<EngineEvent>,
         settings: &Arc<Mutex<TraySettings>>,
-    ) {
-        loop {
+    ) -> LoopExit {
+        let mut recovery = RecoveryState::default();
+        let mut next_settings_check = Instant::now() + SETTINGS_CHECK_INTERVAL;
+        let mut next_activity_check = Instant::now() + ACTIVITY_CHECK_INTERVAL;
+        let mut receiving = Vec::new();
+        let mut level = LevelState::default();
+        let mut active_hours =
+            ActiveHoursTracker::new(&settings.lock().active_hours, LocalTime::now());
+
+        let exit = loop {
             // Check for commands (non-blocking with timeout)
             match command_rx.recv_timeout(Duration::from_millis(50)) {
                 Ok(command) => {
-                    if !Self::handle_command(
+                    if let Some(exit) = Self::handle_command(
                         command,
-                        &status_tx,
+                        status_tx,
                         engine,
-                        &engine_event_tx,
+                        engine_event_tx,
                         settings,
+                        &mut recovery,
                     ) {
-                        break;
+                        break exit;
                     }
                 }
                 Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
@@ -109,7 +316,7 @@ impl EngineController {
                 }
                 Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
                     info!("Command channel disconnected");
-                    break;
+                    break LoopExit::Shutdown;
                 }
             }
 
@@ -118,16 +325,399 @@ impl EngineController {
                 match event {
                     EngineEvent::DefaultDeviceChanged => {
                         info!("Default device changed, refreshing device list");
-                        Self::refresh_devices(&status_tx, engine, settings);
+                        Self::refresh_devices(status_tx, engine, settings);
+                    }
+                    EngineEvent::DeviceListChanged => {
+                        info!("Device list changed, refreshing device list");
+                        Self::refresh_devices(status_tx, engine, settings);
+                    }
+                    EngineEvent::BufferTuned {
+                        device_id,
+                        extra_buffer_ms,
+                        prefill_ms,
+                    } => {
+                        info!(
+                            "Buffer of {} auto-tuned: +{}ms, {}ms pre-fill",
+                            device_id, extra_buffer_ms, prefill_ms
+                        );
+                    }
+                    EngineEvent::Passthrough { device_id } => {
+                        let name = Self::device_name(engine, device_id);
+                        let _ = status_tx.send(EngineStatus::Warning(format!(
+                            "Dolby/DTS bitstream on {}: volume and sync off",
+                            name
+                        )));
+                    }
+                    EngineEvent::Recovered { device_id, reason } => {
+                        let name = Self::device_name(engine, device_id);
+                        warn!("Output {} recovered: {}", name, reason);
+                        let _ = status_tx.send(EngineStatus::Warning(format!(
+                            "{} stopped responding and was restarted",
+                            name
+                        )));
+                    }
+                    EngineEvent::DuplicateSuppressed {
+                        device_id,
+                        duplicate_of,
+                    } => {
+                        let name = Self::device_name(engine, device_id);
+                        let other = Self::device_name(engine, duplicate_of);
+                        let _ = status_tx.send(EngineStatus::Warning(format!(
+                            "{} reaches the same speakers as {} and was paused; enable it to play both",
+                            name, other
+                        )));
+                    }
+                    EngineEvent::RendererFailed { device_id, error } => {
+                        let name = Self::device_name(engine, device_id.clone());
+                        let _ = status_tx.send(EngineStatus::DeviceFailing {
+                            device_id,
+                            name,
+                            reason: error,
+                        });
+                    }
+                    EngineEvent::BufferOverrun { device_id } => {
+                        let name = Self::device_name(engine, device_id.clone());
+                        let _ = status_tx.send(EngineStatus::DeviceFailing {
+                            device_id,
+                            name,
+                            reason: "fell behind and skipped audio".to_string(),
+                        });
+                    }
+                    EngineEvent::MasterChanged { removed, master } => match master {
+                        Some(master) => info!(
+                            "Clock master {} was removed, {} took over",
+                            removed,
+                            Self::device_name(engine, master)
+                        ),
+                        None => info!("Clock master {} was removed, no output left", removed),
+                    },
+                    EngineEvent::SourceSwitched { fallback } => {
+                        if fallback {
+                            info!("Source silent, playing the fallback source");
+                        } else {
+                            info!("Source audible again, fallback source stopped");
+                        }
+                    }
+                    EngineEvent::Ducking { active } => {
+                        if active {
+                            info!("Announcement playing, source ducked");
+                        } else {
+                            info!("Announcement over, source restored");
+                        }
+                    }
+                    EngineEvent::RendererWorking { device_id } => {
+                        info!("Output {} plays again", device_id);
+                        let _ = status_tx.send(EngineStatus::DeviceWorking { device_id });
+                    }
+                    EngineEvent::CaptureFailed { error } => {
+                        let _ = status_tx.send(EngineStatus::CaptureFailing(error));
+                    }
+                    EngineEvent::CaptureWorking => {
+                        info!("Capture works again");
+                        let _ = status_tx.send(EngineStatus::CaptureWorking);
+                    }
+                    EngineEvent::Fatal(reason) => {
+                        Self::handle_fatal(&reason, status_tx, engine, &mut recovery);
                     }
                 }
             }
-        }
+
+            if Instant::now() >= next_activity_check {
+                next_activity_check = Instant::now() + ACTIVITY_CHECK_INTERVAL;
+                Self::publish_activity(status_tx, engine, &mut receiving);
+                let signal = engine.as_ref().map(|eng| eng.signal_level());
+                Self::publish_level(status_tx, signal.unwrap_or_default(), &mut level);
+            }
+
+            if Instant::now() >= next_settings_check {
+                next_settings_check = Instant::now() + SETTINGS_CHECK_INTERVAL;
+                let reloaded = settings.lock().reload_if_changed();
+                if reloaded {
+                    Self::apply_reloaded_settings(status_tx, engine, settings);
+                }
+
+                let edge = active_hours.update(&settings.lock().active_hours, LocalTime::now());
+                if let Some(within) = edge {
+                    info!(
+                        "Active hours {}",
+                        if within {
+                            "began, starting engine"
+                        } else {
+                            "ended, stopping engine"
+                        }
+                    );
+                    let command = if within {
+                        TrayCommand::Start
+                    } else {
+                        TrayCommand::Stop
+                    };
+                    if engine.is_some() != within {
+                        if let Some(exit) = Self::handle_command(
+                            command,
+                            status_tx,
+                            engine,
+                            engine_event_tx,
+                            settings,
+                            &mut recovery,
+                        ) {
+                            break exit;
+                        }
+                    }
+                }
+            }
+
+            // Perform a scheduled restart once its backoff has elapsed
+            if recovery
+                .next_attempt
+                .is_some_and(|due| Instant::now() >= due)
+            {
+                recovery.next_attempt = None;
+                info!(
+                    "Attempting engine restart ({}/{})",
+                    recovery.attempts, MAX_RESTART_ATTEMPTS
+                );
+                match Self::start_engine(status_tx, engine, engine_event_tx, settings) {
+                    StartOutcome::Started => recovery.started_at = Some(Instant::now()),
+                    StartOutcome::Failed => {
+                        Self::schedule_restart("restart attempt failed", status_tx, &mut recovery);
+                    }
+                    // Nobody asked for this start, so there is no one to ask
+                    // either; the user can still choose Start
+                    StartOutcome::ServiceRunning => {
+                        recovery = RecoveryState::default();
+                        let _ = status_tx.send(EngineStatus::ServiceRunning { ask: false });
+                    }
+                }
+            }
+        };
 
         // Cleanup
         if let Some(ref mut eng) = engine {
             let _ = eng.stop();
         }
+        *engine = None;
+
+        exit
+    }
+
+    /// Forward commands to the engine hosted by the wemux service
+    ///
+    /// Device toggles only affect the running service engine; tray settings are
+    /// left untouched apart from picking up hotkey edits. Returns `true` when
+    /// the tray is shutting down and `false` when the service became
+    /// unreachable and a local engine should take over.
+    fn run_remote_loop(
+        service: &EngineClient,
+        report: StatusReport,
+        command_rx: &Receiver<TrayCommand>,
+        status_tx: &Sender<EngineStatus>,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) -> bool {
+        let _ = status_tx.send(EngineStatus::ServiceAttached(true));
+
+        let mut last = None;
+        Self::publish_remote_status(report, &mut last, status_tx);
+
+        let mut next_poll = Instant::now() + REMOTE_POLL_INTERVAL;
+        let mut poll_failures = 0;
+        let mut level = LevelState::default();
+
+        loop {
+            match command_rx.recv_timeout(Duration::from_millis(50)) {
+                Ok(TrayCommand::Shutdown) => return true,
+                // Tray settings are pruned even though the service runs the engine
+                Ok(TrayCommand::PruneDevices) => {
+                    Self::prune_devices(status_tx, settings);
+                    last = None;
+                    next_poll = Instant::now();
+                }
+                Ok(command) => {
+                    if matches!(command, TrayCommand::RefreshDevices) {
+                        // Force the next poll to republish everything
+                        last = None;
+                    }
+                    if let Err(e) = Self::handle_remote_command(command, service, last.as_ref()) {
+                        Self::report(status_tx, TrayError::Service(e.to_string()));
+                    }
+                    // Reflect the outcome right away
+                    next_poll = Instant::now();
+                }
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                    info!("Command channel disconnected");
+                    return true;
+                }
+            }
+
+            if Instant::now() < next_poll {
+                continue;
+            }
+            next_poll = Instant::now() + REMOTE_POLL_INTERVAL;
+
+            let reloaded = settings.lock().reload_if_changed();
+            if reloaded {
+                let _ = status_tx.send(EngineStatus::SettingsReloaded);
+            }
+
+            match service.status() {
+                Ok(report) => {
+                    poll_failures = 0;
+                    Self::publish_remote_status(report, &mut last, status_tx);
+                    if let Ok(stats) = service.stats() {
+                        Self::publish_level(status_tx, stats.level, &mut level);
+                    }
+                }
+                Err(e) => {
+                    poll_failures += 1;
+                    debug!("Service status poll failed ({}): {}", poll_failures, e);
+                    if poll_failures >= REMOTE_MAX_POLL_FAILURES {
+                        warn!("Lost connection to wemux service: {}", e);
+                        let _ = status_tx.send(EngineStatus::ServiceAttached(false));
+                        let _ =
+                            status_tx.send(EngineStatus::EngineStateChanged(EngineState::Stopped));
+                        Self::report(
+                            status_tx,
+                            TrayError::Service(format!("Lost connection: {}", e)),
+                        );
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_remote_command(
+        command: TrayCommand,
+        service: &EngineClient,
+        last: Option<&StatusReport>,
+    ) -> crate::Result<()> {
+        match command {
+            TrayCommand::Start => service.start(),
+            TrayCommand::Stop => service.stop(),
+            TrayCommand::Restart => service.restart(),
+            TrayCommand::ToggleEngine => {
+                if last.is_some_and(|report| report.running) {
+                    service.stop()
+                } else {
+                    service.start()
+                }
+            }
+            TrayCommand::ToggleMute => service.set_muted(!last.is_some_and(|report| report.muted)),
+            TrayCommand::PauseAll => {
+                service.set_paused_all(!last.is_some_and(|report| report.paused_all))
+            }
+            TrayCommand::ToggleDevice { device_id } => {
+                let Some(status) =
+                    last.and_then(|report| report.devices.iter().find(|s| s.id == device_id))
+                else {
+                    return Ok(());
+                };
+
+                // Don't allow toggling system default or capture source devices
+                if status.is_auto_paused() {
+                    info!("Cannot toggle auto-paused device: {}", device_id);
+                    return Ok(());
+                }
+
+                if status.is_paused {
+                    service.resume_device(&device_id)
+                } else {
+                    service.pause_device(&device_id)
+                }
+            }
+            TrayCommand::SetDeviceEnabled { device_id, enabled } => {
+                if enabled {
+                    service.resume_device(&device_id)
+                } else {
+                    service.pause_device(&device_id)
+                }
+            }
+            TrayCommand::ToggleZone { name } => {
+                match last.and_then(|report| report.zones.iter().find(|z| z.name == name)) {
+                    Some(zone) => service.set_zone_paused(&name, !zone.paused),
+                    None => Ok(()),
+                }
+            }
+            TrayCommand::SetLogLevel(level) => {
+                // The tray's own log, if it keeps one, follows along
+                let _ = logging::set_log_level(level);
+                service.set_log_level(level)
+            }
+            // The service's own volume changes, its configuration doesn't
+            TrayCommand::SetVolumeMode(mode) => service.set_volume_mode(mode),
+            TrayCommand::SetMirrorVolume(volume) => service.set_mirror_volume(volume),
+            TrayCommand::RestoreSettings
+            | TrayCommand::MeasureDelays
+            | TrayCommand::SetSyncStrictness(_) => Err(WemuxError::InvalidConfig(
+                "Tray settings are not used while the wemux service runs the engine".to_string(),
+            )),
+            // The service follows sleep and resume on its own
+            TrayCommand::Suspend | TrayCommand::Resume => Ok(()),
+            TrayCommand::RefreshDevices
+            | TrayCommand::PruneDevices
+            | TrayCommand::AttachToService
+            | TrayCommand::Shutdown => Ok(()),
+        }
+    }
+
+    /// Send the parts of a service status report that changed to the UI
+    fn publish_remote_status(
+        report: StatusReport,
+        last: &mut Option<StatusReport>,
+        status_tx: &Sender<EngineStatus>,
+    ) {
+        if last.as_ref() == Some(&report) {
+            return;
+        }
+
+        // Audio starting or stopping on an output only changes its mark
+        let without_activity = |report: &StatusReport| {
+            let mut report = report.clone();
+            for device in &mut report.devices {
+                device.receiving_audio = false;
+            }
+            report
+        };
+        if last
+            .as_ref()
+            .is_some_and(|l| without_activity(l) == without_activity(&report))
+        {
+            let receiving = report
+                .devices
+                .iter()
+                .filter(|d| d.receiving_audio)
+                .map(|d| d.id.clone())
+                .collect();
+            let _ = status_tx.send(EngineStatus::ActivityChanged(receiving));
+            *last = Some(report);
+            return;
+        }
+
+        if last.as_ref().map(|l| l.muted) != Some(report.muted) {
+            let _ = status_tx.send(EngineStatus::MuteChanged(report.muted));
+        }
+        if last.as_ref().map(|l| l.paused_all) != Some(report.paused_all) {
+            let _ = status_tx.send(EngineStatus::PausedAllChanged(report.paused_all));
+        }
+
+        if last.as_ref().map(|l| l.running) != Some(report.running) {
+            let state = if report.running {
+                EngineState::Running
+            } else {
+                EngineState::Stopped
+            };
+            let _ = status_tx.send(EngineStatus::EngineStateChanged(state));
+        }
+
+        if let Ok(enumerator) = DeviceEnumerator::new() {
+            if let Ok(default_name) = enumerator.get_default_device_name() {
+                let _ = status_tx.send(EngineStatus::DefaultDeviceChanged(default_name));
+            }
+        }
+
+        let _ = status_tx.send(EngineStatus::ZonesUpdated(report.zones.clone()));
+        let _ = status_tx.send(EngineStatus::DevicesUpdated(report.devices.clone()));
+        *last = Some(report);
     }
 
     fn handle_command(
@@ -136,40 +726,319 @@ impl EngineController {
         engine: &mut Option<AudioEngine>,
         engine_event_tx: &Sender<EngineEvent>,
         settings: &Arc<Mutex<TraySettings>>,
-    ) -> bool {
+        recovery: &mut RecoveryState,
+    ) -> Option<LoopExit> {
         match command {
             TrayCommand::Start => {
-                Self::start_engine(status_tx, engine, engine_event_tx, settings);
+                // An explicit start cancels any pending automatic restart
+                *recovery = RecoveryState::default();
+                match Self::start_engine(status_tx, engine, engine_event_tx, settings) {
+                    StartOutcome::Started => recovery.started_at = Some(Instant::now()),
+                    StartOutcome::Failed => {}
+                    StartOutcome::ServiceRunning => {
+                        let _ = status_tx.send(EngineStatus::ServiceRunning { ask: true });
+                    }
+                }
             }
             TrayCommand::Stop => {
+                *recovery = RecoveryState::default();
                 Self::stop_engine(status_tx, engine, settings);
             }
+            TrayCommand::Restart => {
+                for command in [TrayCommand::Stop, TrayCommand::Start] {
+                    let exit = Self::handle_command(
+                        command,
+                        status_tx,
+                        engine,
+                        engine_event_tx,
+                        settings,
+                        recovery,
+                    );
+                    if exit.is_some() {
+                        return exit;
+                    }
+                }
+            }
+            TrayCommand::ToggleEngine => {
+                let command = if engine.is_some() {
+                    TrayCommand::Stop
+                } else {
+                    TrayCommand::Start
+                };
+                return Self::handle_command(
+                    command,
+                    status_tx,
+                    engine,
+                    engine_event_tx,
+                    settings,
+                    recovery,
+                );
+            }
+            TrayCommand::ToggleMute => match engine {
+                Some(eng) => {
+                    let muted = !eng.is_muted();
+                    eng.set_muted(muted);
+                    let _ = status_tx.send(EngineStatus::MuteChanged(muted));
+                }
+                None => info!("Engine not running, nothing to mute"),
+            },
+            TrayCommand::PauseAll => match engine {
+                Some(eng) => {
+                    let paused = !eng.is_paused_all();
+                    if paused {
+                        eng.pause_all();
+                    } else {
+                        eng.resume_all();
+                    }
+                    let _ = status_tx.send(EngineStatus::PausedAllChanged(paused));
+                }
+                None => info!("Engine not running, nothing to pause"),
+            },
             TrayCommand::ToggleDevice { device_id } => {
                 Self::toggle_device(&device_id, status_tx, engine, settings);
             }
             TrayCommand::SetDeviceEnabled { device_id, enabled } => {
                 Self::set_device_enabled(&device_id, enabled, status_tx, engine, settings);
             }
+            TrayCommand::ToggleZone { name } => {
+                Self::toggle_zone(&name, status_tx, engine, settings);
+            }
             TrayCommand::RefreshDevices => {
                 Self::refresh_devices(status_tx, engine, settings);
             }
+            TrayCommand::RestoreSettings => {
+                Self::restore_settings(status_tx, engine, settings);
+            }
+            TrayCommand::PruneDevices => {
+                Self::prune_devices(status_tx, settings);
+                Self::refresh_devices(status_tx, engine, settings);
+            }
+            TrayCommand::MeasureDelays => {
+                Self::measure_delays(status_tx, engine, engine_event_tx, settings);
+            }
+            TrayCommand::SetLogLevel(level) => {
+                if let Err(e) = logging::set_log_level(level) {
+                    Self::report(
+                        status_tx,
+                        TrayError::Ui(format!(
+                            "{}; start wemux-tray with --debug to see its log",
+                            e
+                        )),
+                    );
+                }
+            }
+            TrayCommand::SetSyncStrictness(strictness) => {
+                let result = settings
+                    .lock()
+                    .update(|settings| settings.sync_strictness = strictness);
+                Self::report_save(status_tx, result);
+                Self::reconfigure_engine(status_tx, engine, settings);
+            }
+            TrayCommand::SetVolumeMode(mode) => {
+                let result = settings
+                    .lock()
+                    .update(|settings| settings.volume_mode = mode);
+                Self::report_save(status_tx, result);
+                Self::reconfigure_engine(status_tx, engine, settings);
+            }
+            TrayCommand::SetMirrorVolume(volume) => {
+                let volume = volume.clamp(0.0, 1.0);
+                let result = settings
+                    .lock()
+                    .update(|settings| settings.mirror_volume = Some(volume));
+                Self::report_save(status_tx, result);
+                Self::reconfigure_engine(status_tx, engine, settings);
+            }
+            TrayCommand::Suspend => {
+                let running = engine.is_some();
+                *recovery = RecoveryState {
+                    suspended: running || recovery.suspended,
+                    ..Default::default()
+                };
+                if running {
+                    info!("Computer suspending, stopping engine");
+                    Self::stop_engine(status_tx, engine, settings);
+                }
+            }
+            TrayCommand::Resume => {
+                // A suspend that wasn't reported leaves a stale engine behind
+                if recovery.suspended || engine.is_some() {
+                    info!("Computer resumed, restarting engine");
+                    if let Some(ref mut eng) = engine {
+                        let _ = eng.stop();
+                    }
+                    *engine = None;
+                    *recovery = RecoveryState {
+                        next_attempt: Some(Instant::now() + RESUME_SETTLE),
+                        ..Default::default()
+                    };
+                }
+            }
+            TrayCommand::AttachToService => {
+                info!("Attaching to the engine hosted by the wemux service");
+                return Some(LoopExit::AttachToService);
+            }
             TrayCommand::Shutdown => {
-                return false; // Signal to exit loop
+                return Some(LoopExit::Shutdown); // Signal to exit loop
             }
         }
-        true
+        None
     }
 
-    fn start_engine(
+    /// Restore the newest settings backup and apply it to a running engine
+    fn restore_settings(
+        status_tx: &Sender<EngineStatus>,
+        engine: &mut Option<AudioEngine>,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) {
+        let restored = match TraySettings::restore_previous() {
+            Ok(Some(restored)) => restored,
+            Ok(None) => {
+                Self::report(
+                    status_tx,
+                    TrayError::Settings("No backup to restore".to_string()),
+                );
+                return;
+            }
+            Err(e) => {
+                Self::report(
+                    status_tx,
+                    TrayError::Settings(format!("Failed to restore: {}", e)),
+                );
+                return;
+            }
+        };
+        *settings.lock() = restored;
+        info!("Previous settings restored");
+
+        Self::reconfigure_engine(status_tx, engine, settings);
+        Self::refresh_devices(status_tx, engine, settings);
+    }
+
+    /// Measure and save the delays of the enabled outputs
+    ///
+    /// The engine is stopped while the click pattern plays and started again
+    /// with the new delays.
+    fn measure_delays(
         status_tx: &Sender<EngineStatus>,
         engine: &mut Option<AudioEngine>,
         engine_event_tx: &Sender<EngineEvent>,
         settings: &Arc<Mutex<TraySettings>>,
     ) {
-        if engine.is_some() {
+        let devices = match DeviceEnumerator::new().and_then(|e| e.enumerate_all_devices()) {
+            Ok(devices) => devices,
+            Err(e) => {
+                Self::report(
+                    status_tx,
+                    TrayError::Calibration(format!("Failed to enumerate devices: {}", e)),
+                );
+                return;
+            }
+        };
+        // The default output plays the system's own audio and is never rendered to
+        let targets: Vec<_> = {
+            let guard = settings.lock();
+            devices
+                .into_iter()
+                .filter(|d| !d.is_default && guard.devices.get(&d.id).is_some_and(|s| s.enabled))
+                .take(calibrate::MAX_DEVICES)
+                .collect()
+        };
+        if targets.is_empty() {
+            Self::report(
+                status_tx,
+                TrayError::Calibration("No enabled outputs to measure".to_string()),
+            );
             return;
         }
 
+        let was_running = engine.is_some();
+        Self::stop_engine(status_tx, engine, settings);
+
+        info!("Measuring the audio delay of {} devices", targets.len());
+        let ids: Vec<String> = targets.iter().map(|d| d.id.clone()).collect();
+        match calibrate::measure(&ids, None) {
+            Ok(latencies) => {
+                let delays = calibrate::delays(&latencies);
+                let mut missed = Vec::new();
+                let result = settings.lock().update(|settings| {
+                    for (device, delay) in targets.iter().zip(delays) {
+                        match (settings.devices.get_mut(&device.id), delay) {
+                            (Some(setting), Some(delay)) => {
+                                info!("Delay of {}: {}ms", device.name, delay);
+                                setting.delay_ms = delay;
+                            }
+                            _ => missed.push(device.name.clone()),
+                        }
+                    }
+                });
+                Self::report_save(status_tx, result);
+                if !missed.is_empty() {
+                    let _ = status_tx.send(EngineStatus::Warning(format!(
+                        "Not heard while measuring the delay: {}",
+                        missed.join(", ")
+                    )));
+                }
+            }
+            Err(e) => Self::report(status_tx, TrayError::Calibration(e.to_string())),
+        }
+
+        if was_running
+            && Self::start_engine(status_tx, engine, engine_event_tx, settings)
+                == StartOutcome::ServiceRunning
+        {
+            let _ = status_tx.send(EngineStatus::ServiceRunning { ask: true });
+        }
+    }
+
+    /// Remove settings of devices not seen for [`PRUNE_AFTER_DAYS`] days
+    ///
+    /// Present devices are marked as seen first, so a long-running engine
+    /// doesn't make them look stale.
+    fn prune_devices(status_tx: &Sender<EngineStatus>, settings: &Arc<Mutex<TraySettings>>) {
+        let devices = match DeviceEnumerator::new().and_then(|e| e.enumerate_all_devices()) {
+            Ok(devices) => devices,
+            Err(e) => {
+                Self::report(
+                    status_tx,
+                    TrayError::Settings(format!("Failed to enumerate devices: {}", e)),
+                );
+                return;
+            }
+        };
+
+        let mut removed = Vec::new();
+        let result = settings.lock().update(|settings| {
+            settings.update_from_devices(&devices);
+            removed = settings.prune(PRUNE_AFTER_DAYS);
+        });
+        match result {
+            Ok(()) if removed.is_empty() => info!("No old devices to clean up"),
+            Ok(()) => info!("Removed old devices: {}", removed.join(", ")),
+            Err(e) => Self::report_save(status_tx, Err(e)),
+        }
+    }
+
+    /// Start the local engine unless another process already runs one
+    fn start_engine(
+        status_tx: &Sender<EngineStatus>,
+        engine: &mut Option<AudioEngine>,
+        engine_event_tx: &Sender<EngineEvent>,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) -> StartOutcome {
+        if engine.is_some() {
+            return StartOutcome::Started;
+        }
+
+        // Devices plugged in since the last refresh get their new-device default
+        // instead of being rendered to just because settings don't know them
+        if let Ok(devices) = DeviceEnumerator::new().and_then(|e| e.enumerate_all_devices()) {
+            let result = settings
+                .lock()
+                .update(|settings| settings.update_from_devices(&devices));
+            Self::report_save(status_tx, result);
+        }
+
         // Build config from settings
         let config = Self::build_engine_config(settings);
         let mut eng = AudioEngine::new(config);
@@ -181,16 +1050,126 @@ impl EngineController {
             Ok(()) => {
                 info!("Engine started from tray controller");
                 let _ = status_tx.send(EngineStatus::EngineStateChanged(EngineState::Running));
+                // A new engine always starts unmuted and playing
+                let _ = status_tx.send(EngineStatus::MuteChanged(false));
+                let _ = status_tx.send(EngineStatus::PausedAllChanged(false));
                 *engine = Some(eng);
                 Self::refresh_devices(status_tx, engine, settings);
+                StartOutcome::Started
             }
+            Err(WemuxError::EngineInUse) => Self::handle_engine_in_use(status_tx),
             Err(e) => {
-                error!("Failed to start engine: {}", e);
-                let _ = status_tx.send(EngineStatus::Error(e.to_string()));
+                let mut diagnosis = Diagnosis::of_start_failure(&e);
+                // Name the device the way the menu does
+                if let Some(device) = &mut diagnosis.device {
+                    if let Some(setting) = settings.lock().devices.get(device.as_str()) {
+                        *device = format!("{} ({})", setting.name, device);
+                    }
+                }
+                Self::report(status_tx, TrayError::Start(diagnosis));
+                StartOutcome::Failed
             }
         }
     }
 
+    /// Another process holds the engine lock: the service's engine, left to
+    /// the caller to offer, or one it explains how to stop
+    fn handle_engine_in_use(status_tx: &Sender<EngineStatus>) -> StartOutcome {
+        if EngineClient::new(SERVICE_PIPE_NAME).status().is_err() {
+            Self::report(
+                status_tx,
+                TrayError::Engine(
+                    "Another wemux engine is already running (e.g. 'wemux start' in a console). \
+                     Stop it before starting the engine from the tray."
+                        .to_string(),
+                ),
+            );
+            return StartOutcome::Failed;
+        }
+        StartOutcome::ServiceRunning
+    }
+
+    /// Name of a running renderer's device, or its ID if the engine doesn't know it
+    fn device_name(engine: &Option<AudioEngine>, device_id: String) -> String {
+        engine
+            .as_ref()
+            .and_then(|eng| {
+                eng.get_device_statuses()
+                    .into_iter()
+                    .find(|s| s.id == device_id)
+            })
+            .map_or(device_id, |s| s.name)
+    }
+
+    /// Tear down an engine that reported a fatal error and schedule a restart
+    fn handle_fatal(
+        reason: &str,
+        status_tx: &Sender<EngineStatus>,
+        engine: &mut Option<AudioEngine>,
+        recovery: &mut RecoveryState,
+    ) {
+        if engine.is_none() {
+            // Stale event from an engine that was already torn down
+            debug!("Ignoring fatal event without running engine: {}", reason);
+            return;
+        }
+
+        error!("Engine reported fatal error: {}", reason);
+        if let Some(ref mut eng) = engine {
+            let _ = eng.stop();
+        }
+        *engine = None;
+
+        Self::schedule_restart(reason, status_tx, recovery);
+    }
+
+    /// Schedule the next restart attempt with exponential backoff, or give up
+    fn schedule_restart(
+        reason: &str,
+        status_tx: &Sender<EngineStatus>,
+        recovery: &mut RecoveryState,
+    ) {
+        // A long stable run means this is a new incident rather than a restart loop
+        if recovery
+            .started_at
+            .is_some_and(|t| t.elapsed() >= RESTART_RESET_AFTER)
+        {
+            recovery.attempts = 0;
+        }
+        recovery.started_at = None;
+
+        if recovery.attempts >= MAX_RESTART_ATTEMPTS {
+            warn!(
+                "Giving up on engine restart after {} attempts",
+                recovery.attempts
+            );
+            recovery.next_attempt = None;
+            let _ = status_tx.send(EngineStatus::EngineStateChanged(EngineState::Stopped));
+            Self::report(
+                status_tx,
+                TrayError::Engine(format!(
+                    "Stopped after {} failed restart attempts: {}",
+                    recovery.attempts, reason
+                )),
+            );
+            return;
+        }
+
+        recovery.attempts += 1;
+        let delay = RESTART_BASE_DELAY * 2u32.pow(recovery.attempts - 1);
+        recovery.next_attempt = Some(Instant::now() + delay);
+
+        info!(
+            "Engine restart {}/{} scheduled in {:?}",
+            recovery.attempts, MAX_RESTART_ATTEMPTS, delay
+        );
+        let _ = status_tx.send(EngineStatus::Recovering {
+            attempt: recovery.attempts,
+            max_attempts: MAX_RESTART_ATTEMPTS,
+            reason: reason.to_string(),
+        });
+    }
+
     fn stop_engine(
         status_tx: &Sender<EngineStatus>,
         engine: &mut Option<AudioEngine>,
@@ -216,31 +1195,75 @@ impl EngineController {
             // Engine is running, toggle renderer state
             let statuses = eng.get_device_statuses();
             if let Some(status) = statuses.iter().find(|s| s.id == device_id) {
-                // Don't allow toggling system default devices
-                if status.is_system_default {
-                    info!("Cannot toggle system default device: {}", device_id);
+                // Don't allow toggling system default or capture source devices
+                if status.is_auto_paused() {
+                    info!("Cannot toggle auto-paused device: {}", device_id);
                     return;
                 }
 
                 let new_enabled = status.is_paused;
-                if new_enabled {
-                    let _ = eng.resume_renderer(device_id);
-                } else {
-                    let _ = eng.pause_renderer(device_id);
+                Self::switch_renderer(status_tx, eng, device_id, &status.name, new_enabled);
+                // Resuming a suppressed duplicate keeps it playing from now on
+                if new_enabled && status.duplicate_of.is_some() {
+                    Self::allow_duplicate(status_tx, device_id, settings);
                 }
 
                 // Also save to settings
-                Self::save_device_setting(device_id, &status.name, new_enabled, settings);
+                Self::save_device_setting(
+                    status_tx,
+                    device_id,
+                    &status.name,
+                    new_enabled,
+                    settings,
+                );
 
                 Self::refresh_devices(status_tx, engine, settings);
             }
         } else {
             // Engine not running, just toggle setting
-            Self::toggle_device_setting(device_id, settings);
+            Self::toggle_device_setting(status_tx, device_id, settings);
             Self::refresh_devices(status_tx, engine, settings);
         }
     }
 
+    /// Stop suppressing a device as the duplicate of another output
+    fn allow_duplicate(
+        status_tx: &Sender<EngineStatus>,
+        device_id: &str,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) {
+        let result = settings.lock().update(|settings| {
+            if !settings.allow_duplicates.iter().any(|id| id == device_id) {
+                settings.allow_duplicates.push(device_id.to_string());
+            }
+        });
+        Self::report_save(status_tx, result);
+    }
+
+    /// Pause or resume a running engine's renderer, reporting a failure
+    fn switch_renderer(
+        status_tx: &Sender<EngineStatus>,
+        engine: &AudioEngine,
+        device_id: &str,
+        name: &str,
+        enabled: bool,
+    ) {
+        let (result, action) = if enabled {
+            (engine.resume_renderer(device_id), "resume")
+        } else {
+            (engine.pause_renderer(device_id), "pause")
+        };
+        if let Err(e) = result {
+            Self::report(
+                status_tx,
+                TrayError::Device {
+                    device: name.to_string(),
+                    message: format!("Failed to {}: {}", action, e),
+                },
+            );
+        }
+    }
+
     fn set_device_enabled(
         device_id: &str,
         enabled: bool,
@@ -248,14 +1271,6 @@ impl EngineController {
         engine: &mut Option<AudioEngine>,
         settings: &Arc<Mutex<TraySettings>>,
     ) {
-        if let Some(ref eng) = engine {
-            if enabled {
-                let _ = eng.resume_renderer(device_id);
-            } else {
-                let _ = eng.pause_renderer(device_id);
-            }
-        }
-
         // Get device name from settings
         let name = settings
             .lock()
@@ -264,15 +1279,87 @@ impl EngineController {
             .map(|s| s.name.clone())
             .unwrap_or_else(|| device_id.to_string());
 
-        Self::save_device_setting(device_id, &name, enabled, settings);
+        if let Some(ref eng) = engine {
+            Self::switch_renderer(status_tx, eng, device_id, &name, enabled);
+        }
+
+        Self::save_device_setting(status_tx, device_id, &name, enabled, settings);
+        Self::refresh_devices(status_tx, engine, settings);
+    }
+
+    /// Pause or resume a zone, remembering the choice for the next start
+    fn toggle_zone(
+        name: &str,
+        status_tx: &Sender<EngineStatus>,
+        engine: &mut Option<AudioEngine>,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) {
+        let zones = match engine {
+            Some(eng) => eng.zone_statuses(),
+            None => settings.lock().zone_statuses(),
+        };
+        let Some(paused) = zones.iter().find(|z| z.name == name).map(|z| !z.paused) else {
+            return;
+        };
+
+        if let Some(eng) = engine {
+            if let Err(e) = eng.set_zone_paused(name, paused) {
+                Self::report(
+                    status_tx,
+                    TrayError::Engine(format!("Failed to switch zone {}: {}", name, e)),
+                );
+            }
+        }
+
+        let result = settings.lock().update(|settings| {
+            if let Some(zone) = settings.zones.iter_mut().find(|z| z.name == name) {
+                zone.paused = paused;
+            }
+        });
+        Self::report_save(status_tx, result);
+
         Self::refresh_devices(status_tx, engine, settings);
     }
 
+    /// Tell the tray which outputs receive audio, if that changed since `last`
+    fn publish_activity(
+        status_tx: &Sender<EngineStatus>,
+        engine: &Option<AudioEngine>,
+        last: &mut Vec<String>,
+    ) {
+        let mut receiving: Vec<String> = engine
+            .iter()
+            .flat_map(|eng| eng.get_device_statuses())
+            .filter(|d| d.receiving_audio)
+            .map(|d| d.id)
+            .collect();
+        receiving.sort();
+        if receiving != *last {
+            *last = receiving.clone();
+            let _ = status_tx.send(EngineStatus::ActivityChanged(receiving));
+        }
+    }
+
+    /// Send the peak level in whole dB if it moved to another bucket, or
+    /// changed and the last update is a while ago
+    fn publish_level(status_tx: &Sender<EngineStatus>, level: SignalLevel, last: &mut LevelState) {
+        let db = (!level.is_silent()).then(|| level.peak_db().round() as i32);
+        if last.update(db, Instant::now()) {
+            let _ = status_tx.send(EngineStatus::LevelChanged(db));
+        }
+    }
+
     fn refresh_devices(
         status_tx: &Sender<EngineStatus>,
         engine: &mut Option<AudioEngine>,
         settings: &Arc<Mutex<TraySettings>>,
     ) {
+        let zones = match engine {
+            Some(eng) => eng.zone_statuses(),
+            None => settings.lock().zone_statuses(),
+        };
+        let _ = status_tx.send(EngineStatus::ZonesUpdated(zones));
+
         // Get default device info first
         if let Ok(enumerator) = DeviceEnumerator::new() {
             if let Ok(default_name) = enumerator.get_default_device_name() {
@@ -291,12 +1378,11 @@ impl EngineController {
                         // Load settings and update with new devices
                         let mut settings_guard = settings.lock();
 
-                        // Update settings with device list
-                        let device_list: Vec<(String, String)> = devices
-                            .iter()
-                            .map(|d| (d.id.clone(), d.name.clone()))
-                            .collect();
-                        settings_guard.update_from_devices(&device_list);
+                        // Update settings with device list and save them
+                        let result = settings_guard
+                            .update(|settings| settings.update_from_devices(&devices));
+                        Self::report_save(status_tx, result);
+                        let aliases = settings_guard.aliases.assignments(&devices);
 
                         // Create device statuses based on settings
                         // System default devices are always paused (disabled)
@@ -313,15 +1399,17 @@ impl EngineController {
                                     is_enabled: !is_paused,
                                     is_paused,
                                     is_system_default: d.is_default,
+                                    is_capture_source: false,
+                                    alias: aliases.get(&d.id).cloned(),
+                                    latency: None,
+                                    duplicate_of: None,
+                                    receiving_audio: false,
+                                    end_to_end_ms: None,
+                                    inactive: None,
                                 }
                             })
                             .collect();
 
-                        // Save settings
-                        if let Err(e) = settings_guard.save() {
-                            warn!("Failed to save settings: {}", e);
-                        }
-
                         let _ = status_tx.send(EngineStatus::DevicesUpdated(statuses));
                     }
                     Err(_) => {
@@ -333,26 +1421,26 @@ impl EngineController {
         }
     }
 
-    fn toggle_device_setting(device_id: &str, settings: &Arc<Mutex<TraySettings>>) {
-        let mut settings_guard = settings.lock();
-
-        // Get current state and toggle
-        let current_enabled = settings_guard.is_device_enabled(device_id);
-        let new_enabled = !current_enabled;
-
-        // Get name from existing setting or use device_id as fallback
-        let name = settings_guard
-            .devices
-            .get(device_id)
-            .map(|s| s.name.clone())
-            .unwrap_or_else(|| device_id.to_string());
+    fn toggle_device_setting(
+        status_tx: &Sender<EngineStatus>,
+        device_id: &str,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) {
+        let mut new_enabled = false;
+        let result = settings.lock().update(|settings| {
+            // Get current state and toggle
+            new_enabled = !settings.is_device_enabled(device_id);
 
-        settings_guard.set_device_enabled(device_id, &name, new_enabled);
+            // Get name from existing setting or use device_id as fallback
+            let name = settings
+                .devices
+                .get(device_id)
+                .map(|s| s.name.clone())
+                .unwrap_or_else(|| device_id.to_string());
 
-        // Save settings
-        if let Err(e) = settings_guard.save() {
-            warn!("Failed to save settings: {}", e);
-        }
+            settings.set_device_enabled(device_id, &name, new_enabled);
+        });
+        Self::report_save(status_tx, result);
 
         info!(
             "Toggled device {} to {}",
@@ -362,16 +1450,63 @@ impl EngineController {
     }
 
     fn save_device_setting(
+        status_tx: &Sender<EngineStatus>,
         device_id: &str,
         name: &str,
         enabled: bool,
         settings: &Arc<Mutex<TraySettings>>,
     ) {
-        let mut settings_guard = settings.lock();
-        settings_guard.set_device_enabled(device_id, name, enabled);
+        let result = settings
+            .lock()
+            .update(|settings| settings.set_device_enabled(device_id, name, enabled));
+        Self::report_save(status_tx, result);
+    }
+
+    /// Log an error and show it in the tray
+    fn report(status_tx: &Sender<EngineStatus>, error: TrayError) {
+        error!("{}", error);
+        let _ = status_tx.send(EngineStatus::Error(error));
+    }
 
-        if let Err(e) = settings_guard.save() {
-            warn!("Failed to save settings: {}", e);
+    /// Report a failure to write the settings file
+    fn report_save(status_tx: &Sender<EngineStatus>, result: Result<(), std::io::Error>) {
+        if let Err(e) = result {
+            Self::report(
+                status_tx,
+                TrayError::Settings(format!("Failed to save: {}", e)),
+            );
+        }
+    }
+
+    /// Bring a running engine and the UI in line with settings edited outside the tray
+    fn apply_reloaded_settings(
+        status_tx: &Sender<EngineStatus>,
+        engine: &mut Option<AudioEngine>,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) {
+        Self::reconfigure_engine(status_tx, engine, settings);
+        let _ = status_tx.send(EngineStatus::SettingsReloaded);
+        Self::refresh_devices(status_tx, engine, settings);
+    }
+
+    /// Apply the settings to a running engine, keeping unaffected outputs playing
+    fn reconfigure_engine(
+        status_tx: &Sender<EngineStatus>,
+        engine: &mut Option<AudioEngine>,
+        settings: &Arc<Mutex<TraySettings>>,
+    ) {
+        let Some(eng) = engine else {
+            return;
+        };
+        if let Err(e) = eng.reconfigure(Self::build_engine_config(settings)) {
+            Self::report(
+                status_tx,
+                TrayError::Engine(format!("Failed to apply settings: {}", e)),
+            );
+        }
+        if !eng.is_running() {
+            *engine = None;
+            let _ = status_tx.send(EngineStatus::EngineStateChanged(EngineState::Stopped));
         }
     }
 
@@ -392,17 +1527,45 @@ impl EngineController {
             paused_ids.len()
         );
 
+        let (drift_threshold_ms, max_correction_ms) = settings_guard.sync_strictness.drift_limits();
+
         EngineConfig {
             buffer_ms: 50,
+            device_buffer_ms: settings_guard.buffer_ms(),
             device_ids: None,
             exclude_ids: None,
             source_device_id: None,
+            source_type: SourceType::Loopback,
+            exclude_apps: settings_guard.exclude_apps.clone(),
             paused_device_ids: if paused_ids.is_empty() {
                 None
             } else {
                 Some(paused_ids)
             },
             use_all_devices: true, // Use all output devices, not just HDMI
+            exclusive: true,
+            aliases: settings_guard.aliases.clone(),
+            mix_sources: Vec::new(),
+            fallback_source: None,
+            announcement: None,
+            follow_source: false,
+            drift_threshold_ms,
+            max_correction_ms,
+            performance_capture: settings_guard.performance_capture,
+            render_priority: ThreadPriority::default(),
+            render_cores: Default::default(),
+            equalizers: settings_guard.equalizers(),
+            delays: settings_guard.delays(),
+            dsp: settings_guard.dsp(),
+            bluetooth_latency_ms: settings_guard
+                .bluetooth_latency_ms
+                .unwrap_or(BLUETOOTH_LATENCY_MS),
+            pause_fade_ms: settings_guard.pause_fade_ms.unwrap_or(PAUSE_FADE_MS),
+            volume_mode: settings_guard.volume_mode,
+            mirror_volume: settings_guard.mirror_volume.unwrap_or(1.0),
+            zones: settings_guard.zones.clone(),
+            suppress_duplicates: settings_guard.suppress_duplicates,
+            allow_duplicates: settings_guard.allow_duplicates.clone(),
         }
     }
 }
@@ -0,0 +1,235 @@
+//! Error types of the tray application
+//!
+//! Failures are kept as text so they can travel from the controller thread
+//! to the UI in an [`EngineStatus`](crate::tray::EngineStatus) and be listed
+//! again later in the "Recent Errors" dialog. A failed engine start carries
+//! a [`Diagnosis`] naming the step that failed and what to try, shown by
+//! "Show Last Error".
+
+use crate::error::WemuxError;
+use crate::tray::i18n;
+use std::collections::VecDeque;
+use thiserror::Error;
+
+/// Errors kept for the "Recent Errors" dialog
+const MAX_RECENT_ERRORS: usize = 10;
+
+/// Error of the tray application
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TrayError {
+    /// The audio engine failed to start, stop or apply a change
+    #[error("Audio engine: {0}")]
+    Engine(String),
+
+    /// The audio engine failed to start
+    #[error("Audio engine: {}", .0.cause)]
+    Start(Diagnosis),
+
+    /// An output couldn't be paused or resumed
+    #[error("{device}: {message}")]
+    Device { device: String, message: String },
+
+    /// The settings file couldn't be written or restored
+    #[error("Settings: {0}")]
+    Settings(String),
+
+    /// A request to the wemux service failed
+    #[error("wemux service: {0}")]
+    Service(String),
+
+    /// Measuring the audio delay failed
+    #[error("Delay measurement: {0}")]
+    Calibration(String),
+
+    /// The tray icon, its menu or windows, or a program it opens failed
+    #[error("Tray: {0}")]
+    Ui(String),
+
+    /// The engine controller thread is gone
+    #[error("The engine controller stopped")]
+    ControllerGone,
+}
+
+/// Result type for the tray application
+pub type Result<T> = std::result::Result<T, TrayError>;
+
+impl From<WemuxError> for TrayError {
+    fn from(e: WemuxError) -> Self {
+        Self::Engine(e.to_string())
+    }
+}
+
+impl<T> From<crossbeam_channel::SendError<T>> for TrayError {
+    fn from(_: crossbeam_channel::SendError<T>) -> Self {
+        Self::ControllerGone
+    }
+}
+
+/// Failures of the tray's own UI
+macro_rules! ui_error_from {
+    ($($source:ty),*) => {
+        $(impl From<$source> for TrayError {
+            fn from(e: $source) -> Self {
+                Self::Ui(e.to_string())
+            }
+        })*
+    };
+}
+
+ui_error_from!(
+    muda::Error,
+    tray_icon::Error,
+    tray_icon::BadIcon,
+    image::ImageError,
+    windows::core::Error,
+    std::io::Error
+);
+
+impl TrayError {
+    /// Full description for "Show Last Error", with the diagnosis of a
+    /// failed start
+    pub(crate) fn report(&self) -> String {
+        match self {
+            Self::Start(diagnosis) => diagnosis.report(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// What went wrong starting the engine and what the user can do about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// Step of the start that failed
+    pub step: &'static str,
+    /// Device involved, if the failure concerns one
+    pub device: Option<String>,
+    /// The error itself
+    pub cause: String,
+    /// What to try
+    pub fix: &'static str,
+}
+
+impl Diagnosis {
+    /// Diagnose an error returned by `AudioEngine::start`, in the tray's
+    /// language
+    pub fn of_start_failure(error: &WemuxError) -> Self {
+        let text = i18n::text();
+        let (step, device, fix) = match error {
+            WemuxError::ComInit(_) => (text.step_open_audio, None, text.fix_restart_audio),
+            WemuxError::DeviceNotFound(device) => (
+                text.step_find_devices,
+                Some(device.clone()),
+                text.fix_device_not_found,
+            ),
+            WemuxError::AmbiguousDevice { query, .. } => (
+                text.step_find_devices,
+                Some(query.clone()),
+                text.fix_ambiguous_device,
+            ),
+            WemuxError::NoHdmiDevices => (text.step_find_outputs, None, text.fix_no_outputs),
+            WemuxError::DeviceError { device_id, .. } => (
+                text.step_open_output,
+                Some(device_id.clone()),
+                text.fix_exclusive_mode,
+            ),
+            WemuxError::FormatMismatch { .. } => {
+                (text.step_match_formats, None, text.fix_format_mismatch)
+            }
+            WemuxError::InvalidConfig(_) => {
+                (text.step_read_settings, None, text.fix_invalid_config)
+            }
+            _ => (text.step_start_engine, None, text.fix_unknown),
+        };
+        Self {
+            step,
+            device,
+            cause: error.to_string(),
+            fix,
+        }
+    }
+
+    /// Multi-line report for a dialog
+    pub fn report(&self) -> String {
+        let text = i18n::text();
+        let mut report = format!("{}: {}\n", text.failed_step, self.step);
+        if let Some(device) = &self.device {
+            report.push_str(&format!("{}: {}\n", text.failed_device, device));
+        }
+        report.push_str(&format!(
+            "{}: {}\n\n{}: {}",
+            text.failed_error, self.cause, text.suggested_fix, self.fix
+        ));
+        report
+    }
+}
+
+/// The last [`MAX_RECENT_ERRORS`] errors with the time they happened
+#[derive(Debug, Default)]
+pub(crate) struct ErrorLog {
+    entries: VecDeque<(String, TrayError)>,
+}
+
+impl ErrorLog {
+    /// Remember `error`, forgetting the oldest one if the log is full
+    pub(crate) fn push(&mut self, time: String, error: TrayError) {
+        if self.entries.len() == MAX_RECENT_ERRORS {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((time, error));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The newest error with its time
+    pub(crate) fn last(&self) -> Option<&(String, TrayError)> {
+        self.entries.back()
+    }
+
+    /// One line per error, newest first
+    pub(crate) fn lines(&self) -> String {
+        self.entries
+            .iter()
+            .rev()
+            .map(|(time, error)| format!("{}  {}", time, error))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_log_keeps_the_newest() {
+        let mut log = ErrorLog::default();
+        assert!(log.is_empty());
+        for n in 0..=MAX_RECENT_ERRORS {
+            log.push(format!("10:{:02}", n), TrayError::Engine(n.to_string()));
+        }
+
+        let lines = log.lines();
+        assert_eq!(lines.lines().count(), MAX_RECENT_ERRORS);
+        assert!(lines.starts_with("10:10  Audio engine: 10\n"));
+        // The first error was forgotten
+        assert!(lines.ends_with("10:01  Audio engine: 1"));
+    }
+
+    #[test]
+    fn test_start_failure_diagnosis() {
+        let error = TrayError::Start(Diagnosis::of_start_failure(&WemuxError::device_error(
+            "hdmi-1",
+            "exclusive mode",
+        )));
+        assert_eq!(
+            error.to_string(),
+            "Audio engine: Device 'hdmi-1' error: exclusive mode"
+        );
+
+        let report = error.report();
+        assert!(report.starts_with("Failed step: Opening an output\nDevice: hdmi-1\n"));
+        assert!(report.contains("Suggested fix: Close apps"));
+    }
+}
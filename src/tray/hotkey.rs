@@ -0,0 +1,198 @@
+//! Global hotkeys registered with `RegisterHotKey`
+//!
+//! Hotkeys are registered on the tray's UI thread without a window, so
+//! `WM_HOTKEY` arrives in its message loop with the hotkey ID in `wParam`.
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT,
+    MOD_SHIFT, MOD_WIN,
+};
+
+/// Hotkey bindings stored in the tray settings (empty = disabled)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeySettings {
+    /// Start the engine if stopped, stop it if running
+    pub toggle_engine: String,
+    /// Mute or unmute all outputs
+    pub mute: String,
+}
+
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        Self {
+            toggle_engine: "Ctrl+Alt+W".to_string(),
+            mute: "Ctrl+Alt+M".to_string(),
+        }
+    }
+}
+
+/// Action triggered by a hotkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    ToggleEngine,
+    ToggleMute,
+}
+
+impl HotkeyAction {
+    /// Hotkey ID passed to `RegisterHotKey`
+    fn id(self) -> i32 {
+        match self {
+            HotkeyAction::ToggleEngine => 1,
+            HotkeyAction::ToggleMute => 2,
+        }
+    }
+
+    /// Action for a `WM_HOTKEY` ID
+    pub fn from_id(id: i32) -> Option<Self> {
+        [HotkeyAction::ToggleEngine, HotkeyAction::ToggleMute]
+            .into_iter()
+            .find(|action| action.id() == id)
+    }
+}
+
+/// Key combination such as "Ctrl+Alt+W"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    modifiers: HOT_KEY_MODIFIERS,
+    /// Virtual-key code
+    key: u32,
+}
+
+impl Hotkey {
+    /// Parse "Modifier+...+Key"; modifiers are Ctrl, Alt, Shift and Win,
+    /// keys are letters, digits, F1-F24 and a few named keys (Space, Pause, ...)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = HOT_KEY_MODIFIERS(0);
+        let mut key = None;
+
+        for part in text.split('+').map(str::trim) {
+            let modifier = match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Some(MOD_CONTROL),
+                "alt" => Some(MOD_ALT),
+                "shift" => Some(MOD_SHIFT),
+                "win" => Some(MOD_WIN),
+                _ => None,
+            };
+            match modifier {
+                Some(modifier) => modifiers |= modifier,
+                None if key.is_none() => {
+                    key = Some(virtual_key(part).ok_or_else(|| format!("unknown key '{}'", part))?)
+                }
+                None => return Err(format!("more than one key in '{}'", text)),
+            }
+        }
+
+        let key = key.ok_or_else(|| format!("no key in '{}'", text))?;
+        Ok(Self { modifiers, key })
+    }
+}
+
+/// Virtual-key code for a key name
+fn virtual_key(name: &str) -> Option<u32> {
+    let upper = name.to_ascii_uppercase();
+
+    if let [c] = upper.as_bytes() {
+        if c.is_ascii_alphanumeric() {
+            // VK codes of letters and digits are their ASCII codes
+            return Some(*c as u32);
+        }
+    }
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        return (1..=24).contains(&n).then_some(0x70 + n - 1);
+    }
+
+    let code = match upper.as_str() {
+        "SPACE" => 0x20,
+        "PAUSE" => 0x13,
+        "INSERT" => 0x2D,
+        "DELETE" => 0x2E,
+        "HOME" => 0x24,
+        "END" => 0x23,
+        "PAGEUP" => 0x21,
+        "PAGEDOWN" => 0x22,
+        "VOLUMEMUTE" => 0xAD,
+        "MEDIAPLAYPAUSE" => 0xB3,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Registered hotkeys, unregistered on drop
+///
+/// Must be created and dropped on the thread that runs the message loop.
+pub struct HotkeyManager {
+    registered: Vec<HotkeyAction>,
+}
+
+impl HotkeyManager {
+    /// Register the configured hotkeys; invalid or taken bindings are skipped with a warning
+    pub fn register(settings: &HotkeySettings) -> Self {
+        let mut registered = Vec::new();
+
+        for (action, binding) in [
+            (HotkeyAction::ToggleEngine, &settings.toggle_engine),
+            (HotkeyAction::ToggleMute, &settings.mute),
+        ] {
+            if binding.trim().is_empty() {
+                continue;
+            }
+            let hotkey = match Hotkey::parse(binding) {
+                Ok(hotkey) => hotkey,
+                Err(e) => {
+                    warn!("Invalid hotkey for {:?}: {}", action, e);
+                    continue;
+                }
+            };
+
+            let result = unsafe {
+                RegisterHotKey(
+                    None,
+                    action.id(),
+                    hotkey.modifiers | MOD_NOREPEAT,
+                    hotkey.key,
+                )
+            };
+            match result {
+                Ok(()) => {
+                    info!("Registered hotkey {} for {:?}", binding, action);
+                    registered.push(action);
+                }
+                // Usually another application owns the combination
+                Err(e) => warn!("Failed to register hotkey {}: {}", binding, e),
+            }
+        }
+
+        Self { registered }
+    }
+}
+
+impl Drop for HotkeyManager {
+    fn drop(&mut self) {
+        for action in &self.registered {
+            let _ = unsafe { UnregisterHotKey(None, action.id()) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hotkeys() {
+        let hotkey = Hotkey::parse("Ctrl+Alt+W").unwrap();
+        assert_eq!(hotkey.modifiers, MOD_CONTROL | MOD_ALT);
+        assert_eq!(hotkey.key, 'W' as u32);
+
+        assert_eq!(Hotkey::parse("shift + f12").unwrap().key, 0x7B);
+        assert_eq!(Hotkey::parse("MediaPlayPause").unwrap().key, 0xB3);
+
+        assert!(Hotkey::parse("Ctrl+Alt").is_err());
+        assert!(Hotkey::parse("Ctrl+W+M").is_err());
+        assert!(Hotkey::parse("Ctrl+F25").is_err());
+        assert!(Hotkey::parse("Hyper+W").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+//! Localized text of the tray menu, tooltips and dialogs
+//!
+//! Each language is one table of strings, so a translation missing a string
+//! doesn't compile. The tray follows the Windows locale unless `language` is
+//! set in its settings; locales without a translation get English.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
+use windows::Win32::Globalization::GetUserDefaultLocaleName;
+
+/// Longest locale name in UTF-16 units, with the null (LOCALE_NAME_MAX_LENGTH)
+const LOCALE_NAME_MAX_LENGTH: usize = 85;
+
+/// Language of the tray's text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    /// English
+    #[default]
+    #[serde(rename = "en")]
+    En,
+    /// Traditional Chinese
+    #[serde(rename = "zh-tw")]
+    ZhTw,
+}
+
+impl Language {
+    /// Language of the user's Windows locale
+    pub fn detect() -> Self {
+        let mut name = [0u16; LOCALE_NAME_MAX_LENGTH];
+        let len = unsafe { GetUserDefaultLocaleName(&mut name) };
+        if len <= 1 {
+            return Self::default();
+        }
+        // The length includes the terminating null
+        Self::from_locale(&String::from_utf16_lossy(&name[..len as usize - 1]))
+    }
+
+    /// Language for a locale name like "zh-TW"
+    fn from_locale(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if ["zh-tw", "zh-hk", "zh-mo", "zh-hant"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            Self::ZhTw
+        } else {
+            Self::En
+        }
+    }
+
+    fn strings(self) -> &'static Strings {
+        match self {
+            Self::En => &EN,
+            Self::ZhTw => &ZH_TW,
+        }
+    }
+}
+
+/// Language in use, as an index into the tables
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+/// Show the tray's text in `language` from now on
+pub(crate) fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// Text in the language in use
+pub(crate) fn text() -> &'static Strings {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::ZhTw.strings(),
+        _ => Language::En.strings(),
+    }
+}
+
+/// Every string the tray shows
+pub(crate) struct Strings {
+    pub system_output: &'static str,
+    pub unknown: &'static str,
+    pub output_devices: &'static str,
+    pub no_devices: &'static str,
+    pub device_properties: &'static str,
+    pub sound_settings: &'static str,
+    pub zones: &'static str,
+    pub start: &'static str,
+    pub stop: &'static str,
+    pub pause_all: &'static str,
+    pub refresh_devices: &'static str,
+    pub restore_settings: &'static str,
+    pub prune_devices: &'static str,
+    pub measure_delays: &'static str,
+    pub start_with_windows: &'static str,
+    pub sync: &'static str,
+    pub sync_loose: &'static str,
+    pub sync_normal: &'static str,
+    pub sync_tight: &'static str,
+    pub volume: &'static str,
+    pub volume_follow: &'static str,
+    pub volume_fixed: &'static str,
+    pub volume_scaled: &'static str,
+    pub mirror_volume: &'static str,
+    pub logging: &'static str,
+    pub log_debug: &'static str,
+    pub log_info: &'static str,
+    pub log_warn: &'static str,
+    pub view_log: &'static str,
+    pub open_log_folder: &'static str,
+    pub recent_errors: &'static str,
+    pub last_error: &'static str,
+    pub exit: &'static str,
+    /// Device label suffixes
+    pub system_default: &'static str,
+    pub capture_source: &'static str,
+    pub device_disabled: &'static str,
+    pub device_not_present: &'static str,
+    pub device_unplugged: &'static str,
+    pub disabled: &'static str,
+    pub active: &'static str,
+    /// Engine states
+    pub running: &'static str,
+    pub stopped: &'static str,
+    /// Tooltips
+    pub tooltip: &'static str,
+    pub service_tooltip: &'static str,
+    pub muted: &'static str,
+    pub paused: &'static str,
+    pub signal: &'static str,
+    pub silent: &'static str,
+    pub restarting: &'static str,
+    /// Notified when the settings file was damaged and recovered
+    pub settings_recovered: &'static str,
+    /// Asked when the service already runs the engine
+    pub service_running_prompt: &'static str,
+    /// Notified when an automatic restart finds the service running the engine
+    pub service_running_notice: &'static str,
+    /// "Recent Errors" dialog
+    pub errors_title: &'static str,
+    pub no_errors: &'static str,
+    /// "Show Last Error" dialog
+    pub last_error_title: &'static str,
+    /// Diagnosis of a failed start: its labels, the step that failed and
+    /// what to try
+    pub failed_step: &'static str,
+    pub failed_device: &'static str,
+    pub failed_error: &'static str,
+    pub suggested_fix: &'static str,
+    pub step_open_audio: &'static str,
+    pub step_find_devices: &'static str,
+    pub step_find_outputs: &'static str,
+    pub step_open_output: &'static str,
+    pub step_match_formats: &'static str,
+    pub step_read_settings: &'static str,
+    pub step_start_engine: &'static str,
+    pub fix_restart_audio: &'static str,
+    pub fix_device_not_found: &'static str,
+    pub fix_ambiguous_device: &'static str,
+    pub fix_no_outputs: &'static str,
+    pub fix_exclusive_mode: &'static str,
+    pub fix_format_mismatch: &'static str,
+    pub fix_invalid_config: &'static str,
+    pub fix_unknown: &'static str,
+    /// "View Log" window
+    pub log_title: &'static str,
+    pub log_empty: &'static str,
+}
+
+const EN: Strings = Strings {
+    system_output: "System Output",
+    unknown: "Unknown",
+    output_devices: "Output Devices",
+    no_devices: "Not found",
+    device_properties: "Device Properties",
+    sound_settings: "Windows Sound Settings",
+    zones: "Zones",
+    start: "Start",
+    stop: "Stop",
+    pause_all: "Pause Mirroring",
+    refresh_devices: "Refresh Devices",
+    restore_settings: "Restore Previous Settings",
+    prune_devices: "Clean Up Old Devices",
+    measure_delays: "Measure Audio Delay",
+    start_with_windows: "Start with Windows",
+    sync: "Sync",
+    sync_loose: "Loose (fewer glitches)",
+    sync_normal: "Normal",
+    sync_tight: "Tight (closer sync)",
+    volume: "Volume",
+    volume_follow: "Follow System Volume",
+    volume_fixed: "Fixed Mirror Volume",
+    volume_scaled: "System Volume x Mirror Volume",
+    mirror_volume: "Mirror volume",
+    logging: "Logging",
+    log_debug: "Debug",
+    log_info: "Info",
+    log_warn: "Warn",
+    view_log: "View Log...",
+    open_log_folder: "Open Log Folder",
+    recent_errors: "Recent Errors...",
+    last_error: "Show Last Error...",
+    exit: "Exit",
+    system_default: "(System Default)",
+    capture_source: "(Capture Source)",
+    device_disabled: "(Device Disabled)",
+    device_not_present: "(Not Present)",
+    device_unplugged: "(Unplugged)",
+    disabled: "[Disabled]",
+    active: "[Active]",
+    running: "Running",
+    stopped: "Stopped",
+    tooltip: "wemux - Audio Sync",
+    service_tooltip: "wemux - Audio Sync (service)",
+    muted: "muted",
+    paused: "paused",
+    signal: "Signal",
+    silent: "silent",
+    restarting: "Restarting",
+    settings_recovered:
+        "The settings file was damaged and has been restored from its last good version.",
+    service_running_prompt: "The wemux service is already running the audio engine.\n\n\
+        Control the service's engine from the tray instead?\n\n\
+        To run the engine in the tray, stop the service first \
+        ('net stop wemux' as Administrator).",
+    service_running_notice: "The wemux service took over the audio engine. \
+        Choose Start to control it from the tray.",
+    errors_title: "wemux - Recent Errors",
+    no_errors: "No errors since wemux started.",
+    last_error_title: "wemux - Last Error",
+    failed_step: "Failed step",
+    failed_device: "Device",
+    failed_error: "Error",
+    suggested_fix: "Suggested fix",
+    step_open_audio: "Opening Windows audio",
+    step_find_devices: "Finding the devices",
+    step_find_outputs: "Finding the outputs",
+    step_open_output: "Opening an output",
+    step_match_formats: "Matching the audio formats",
+    step_read_settings: "Reading the settings",
+    step_start_engine: "Starting the audio engine",
+    fix_restart_audio: "Restart the Windows Audio service, or sign out and back in.",
+    fix_device_not_found:
+        "Check the device is connected and enabled in Sound settings, or choose another one.",
+    fix_ambiguous_device: "Name the device by its full name or ID in wemux-tray.toml.",
+    fix_no_outputs: "Turn on the TV or receiver and check its cable, \
+        or enable an output in the Devices menu.",
+    fix_exclusive_mode: "Close apps using the device in exclusive mode, \
+        or turn off \"Allow applications to take exclusive control\" in its Sound properties.",
+    fix_format_mismatch:
+        "Set the same sample rate on the source and the outputs in Sound settings.",
+    fix_invalid_config: "Correct the setting in wemux-tray.toml; the tray picks up the change.",
+    fix_unknown: "Raise the log level to Debug in the Logging menu and start again for details.",
+    log_title: "wemux - Log",
+    log_empty: "Nothing logged yet.",
+};
+
+const ZH_TW: Strings = Strings {
+    system_output: "系統輸出",
+    unknown: "未知",
+    output_devices: "輸出裝置",
+    no_devices: "找不到裝置",
+    device_properties: "裝置內容",
+    sound_settings: "Windows 音效設定",
+    zones: "區域",
+    start: "開始",
+    stop: "停止",
+    pause_all: "暫停鏡像",
+    refresh_devices: "重新整理裝置",
+    restore_settings: "還原先前的設定",
+    prune_devices: "清除舊裝置",
+    measure_delays: "測量音訊延遲",
+    start_with_windows: "隨 Windows 啟動",
+    sync: "同步",
+    sync_loose: "寬鬆（較少斷音）",
+    sync_normal: "一般",
+    sync_tight: "嚴格（同步更精準）",
+    volume: "音量",
+    volume_follow: "跟隨系統音量",
+    volume_fixed: "固定鏡像音量",
+    volume_scaled: "系統音量 x 鏡像音量",
+    mirror_volume: "鏡像音量",
+    logging: "記錄",
+    log_debug: "偵錯",
+    log_info: "資訊",
+    log_warn: "警告",
+    view_log: "檢視記錄...",
+    open_log_folder: "開啟記錄資料夾",
+    recent_errors: "最近的錯誤...",
+    last_error: "顯示上一個錯誤...",
+    exit: "結束",
+    system_default: "（系統預設）",
+    capture_source: "（擷取來源）",
+    device_disabled: "（裝置已停用）",
+    device_not_present: "（裝置不存在）",
+    device_unplugged: "（已拔除）",
+    disabled: "[已停用]",
+    active: "[使用中]",
+    running: "執行中",
+    stopped: "已停止",
+    tooltip: "wemux - 音訊同步",
+    service_tooltip: "wemux - 音訊同步（服務）",
+    muted: "已靜音",
+    paused: "已暫停",
+    signal: "訊號",
+    silent: "無聲",
+    restarting: "重新啟動中",
+    settings_recovered: "設定檔已損毀，已從上一個正常版本還原。",
+    service_running_prompt: "wemux 服務已在執行音訊引擎。\n\n\
+        要改由系統匣控制服務的引擎嗎？\n\n\
+        若要在系統匣執行引擎，請先停止服務\
+        （以系統管理員身分執行 'net stop wemux'）。",
+    service_running_notice: "wemux 服務已接手音訊引擎。選擇「啟動」即可由系統匣控制它。",
+    errors_title: "wemux - 最近的錯誤",
+    no_errors: "wemux 啟動後沒有發生錯誤。",
+    last_error_title: "wemux - 上一個錯誤",
+    failed_step: "失敗的步驟",
+    failed_device: "裝置",
+    failed_error: "錯誤",
+    suggested_fix: "建議的解決方法",
+    step_open_audio: "開啟 Windows 音訊",
+    step_find_devices: "尋找裝置",
+    step_find_outputs: "尋找輸出裝置",
+    step_open_output: "開啟輸出裝置",
+    step_match_formats: "比對音訊格式",
+    step_read_settings: "讀取設定",
+    step_start_engine: "啟動音訊引擎",
+    fix_restart_audio: "請重新啟動 Windows Audio 服務，或登出後再登入。",
+    fix_device_not_found: "請確認裝置已連接並在音效設定中啟用，或選擇其他裝置。",
+    fix_ambiguous_device: "請在 wemux-tray.toml 中以完整名稱或 ID 指定裝置。",
+    fix_no_outputs: "請開啟電視或擴大機並檢查連接線，或在「輸出裝置」選單中啟用輸出裝置。",
+    fix_exclusive_mode: "請關閉以獨佔模式使用該裝置的應用程式，\
+        或在其音效內容中取消勾選「允許應用程式取得此裝置的獨佔控制權」。",
+    fix_format_mismatch: "請在音效設定中將來源與輸出裝置設為相同的取樣率。",
+    fix_invalid_config: "請修正 wemux-tray.toml 中的設定；系統匣會自動套用變更。",
+    fix_unknown: "請在「記錄」選單中將記錄層級設為「偵錯」，然後再次啟動以查看詳細資訊。",
+    log_title: "wemux - 記錄",
+    log_empty: "尚無記錄。",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_locale() {
+        assert_eq!(Language::from_locale("zh-TW"), Language::ZhTw);
+        assert_eq!(Language::from_locale("zh-Hant-HK"), Language::ZhTw);
+        assert_eq!(Language::from_locale("en-US"), Language::En);
+        assert_eq!(Language::from_locale("zh-CN"), Language::En);
+
+        let settings: toml::Table = toml::from_str("language = \"zh-tw\"").unwrap();
+        let language: Language = settings["language"].clone().try_into().unwrap();
+        assert_eq!(language.strings().exit, "結束");
+    }
+}
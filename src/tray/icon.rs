@@ -1,31 +1,171 @@
 //! Icon management for tray application
+//!
+//! The PNGs are scaled to the small icon size of the display's DPI, and on a
+//! light taskbar their grey parts are darkened so the idle icon stays
+//! visible. A hidden window picks up theme, DPI and display changes
+//! (`WM_SETTINGCHANGE`, `WM_DPICHANGED`, `WM_DISPLAYCHANGE`), after which the
+//! icons are rendered again.
 
-use anyhow::{Context, Result};
+use crate::tray::error::{Result, TrayError};
+use crate::tray::window::hidden_window;
+use image::imageops::FilterType;
 use image::GenericImageView;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tray_icon::Icon;
+use windows::core::w;
+use windows::Win32::Foundation::{ERROR_SUCCESS, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+use windows::Win32::UI::WindowsAndMessaging::{
+    DefWindowProcW, DestroyWindow, GetSystemMetrics, SM_CXSMICON, WM_DISPLAYCHANGE, WM_DPICHANGED,
+    WM_SETTINGCHANGE,
+};
+
+/// Icon size at 100% scaling, used if the system metric is unavailable
+const DEFAULT_ICON_SIZE: u32 = 16;
+
+/// Pixels whose channels differ less than this are grey
+const GREY_TOLERANCE: u8 = 40;
+
+/// Set by the hidden window when the theme, DPI or displays changed
+static THEME_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// What the icons are rendered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconTheme {
+    /// The taskbar uses the light theme
+    pub light_taskbar: bool,
+    /// Width and height of a tray icon in pixels
+    pub size: u32,
+}
+
+impl IconTheme {
+    /// Taskbar theme and icon size in effect now
+    pub fn current() -> Self {
+        let size = unsafe { GetSystemMetrics(SM_CXSMICON) };
+        Self {
+            light_taskbar: system_uses_light_theme(),
+            size: if size > 0 {
+                size as u32
+            } else {
+                DEFAULT_ICON_SIZE
+            },
+        }
+    }
+}
+
+/// Whether the taskbar is light (`SystemUsesLightTheme`; absent before
+/// Windows 10 1903, where it is always dark)
+fn system_uses_light_theme() -> bool {
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize"),
+            w!("SystemUsesLightTheme"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some((&mut value as *mut u32).cast()),
+            Some(&mut size),
+        )
+    };
+    status == ERROR_SUCCESS && value != 0
+}
+
+/// Invert the lightness of grey pixels in RGBA data, leaving coloured ones
+fn darken_greys(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let max = pixel[..3].iter().max().copied().unwrap_or(0);
+        let min = pixel[..3].iter().min().copied().unwrap_or(0);
+        if max - min < GREY_TOLERANCE && min > 127 {
+            for channel in &mut pixel[..3] {
+                *channel = 255 - *channel;
+            }
+        }
+    }
+}
+
+/// Hidden top-level window receiving the broadcasts about theme and DPI
+/// changes, which reach no thread without a window
+pub struct ThemeWatcher {
+    hwnd: HWND,
+}
+
+impl ThemeWatcher {
+    /// Create the window on the current thread, whose message loop must
+    /// dispatch its messages
+    pub fn new() -> Result<Self> {
+        let hwnd = hidden_window(w!("wemux-theme-watcher"), Some(theme_window_proc))?;
+        Ok(Self { hwnd })
+    }
+
+    /// Whether the theme, DPI or displays changed since the last call
+    pub fn changed(&self) -> bool {
+        THEME_CHANGED.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Drop for ThemeWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+unsafe extern "system" fn theme_window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if matches!(msg, WM_SETTINGCHANGE | WM_DPICHANGED | WM_DISPLAYCHANGE) {
+        THEME_CHANGED.store(true, Ordering::Relaxed);
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
 
 /// Icon manager for different application states
 pub struct IconManager {
     idle_icon: Icon,
     active_icon: Icon,
     error_icon: Icon,
+    theme: IconTheme,
 }
 
 impl IconManager {
     /// Create a new icon manager
     pub fn new() -> Result<Self> {
-        let idle_icon = Self::load_icon_from_file("assets/icons/tray/idle.png")?;
-        let active_icon = Self::load_icon_from_file("assets/icons/tray/active.png")?;
-        let error_icon = Self::load_icon_from_file("assets/icons/tray/error.png")?;
+        Self::with_theme(IconTheme::current())
+    }
+
+    /// Icons rendered for `theme`
+    fn with_theme(theme: IconTheme) -> Result<Self> {
+        let idle_icon = Self::load_icon_from_file("assets/icons/tray/idle.png", theme)?;
+        let active_icon = Self::load_icon_from_file("assets/icons/tray/active.png", theme)?;
+        let error_icon = Self::load_icon_from_file("assets/icons/tray/error.png", theme)?;
 
         Ok(Self {
             idle_icon,
             active_icon,
             error_icon,
+            theme,
         })
     }
 
+    /// Render the icons again if the taskbar theme or icon size changed
+    ///
+    /// Returns true if they did, so the shown icon needs replacing.
+    pub fn refresh(&mut self) -> Result<bool> {
+        let theme = IconTheme::current();
+        if theme == self.theme {
+            return Ok(false);
+        }
+        *self = Self::with_theme(theme)?;
+        Ok(true)
+    }
+
     /// Get asset path relative to executable
     ///
     /// Searches in order:
@@ -44,26 +184,30 @@ impl IconManager {
 
         // Fall back to current working directory (development)
         let cwd_path = std::env::current_dir()
-            .context("Failed to get current directory")?
+            .map_err(|e| TrayError::Ui(format!("Failed to get current directory: {}", e)))?
             .join(relative_path);
 
         if cwd_path.exists() {
             return Ok(cwd_path);
         }
 
-        anyhow::bail!(
+        Err(TrayError::Ui(format!(
             "Asset not found: {} (searched in exe dir and current dir)",
             relative_path
-        )
+        )))
     }
 
-    /// Load icon from PNG file
-    fn load_icon_from_file(path: &str) -> Result<Icon> {
+    /// Load icon from PNG file, rendered for `theme`
+    fn load_icon_from_file(path: &str, theme: IconTheme) -> Result<Icon> {
         let full_path = Self::get_asset_path(path)?;
         let img = image::open(&full_path)
-            .with_context(|| format!("Failed to load icon: {:?}", full_path))?;
+            .map_err(|e| TrayError::Ui(format!("Failed to load icon {:?}: {}", full_path, e)))?
+            .resize(theme.size, theme.size, FilterType::Lanczos3);
         let (width, height) = img.dimensions();
-        let rgba = img.into_rgba8().into_raw();
+        let mut rgba = img.into_rgba8().into_raw();
+        if theme.light_taskbar {
+            darken_greys(&mut rgba);
+        }
         Ok(Icon::from_rgba(rgba, width, height)?)
     }
 
@@ -88,3 +232,19 @@ impl Default for IconManager {
         Self::new().expect("Failed to create icon manager")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_darken_greys_keeps_colours() {
+        let mut rgba = vec![
+            200, 200, 200, 255, // light grey
+            64, 160, 32, 255, // green
+            40, 40, 40, 255, // dark grey
+        ];
+        darken_greys(&mut rgba);
+        assert_eq!(rgba, [55, 55, 55, 255, 64, 160, 32, 255, 40, 40, 40, 255]);
+    }
+}
@@ -0,0 +1,179 @@
+//! The tray's log file and a window showing its last lines
+//!
+//! The tray logs to %LOCALAPPDATA%\wemux\tray.log and into the process's
+//! [`LogRing`], whose last lines "View Log..." shows in a plain window with
+//! a read-only text box. "Open Log Folder" opens the folder in Explorer, so
+//! the files can be sent along with a problem report.
+
+use crate::logging::LogRing;
+use crate::tray::error::{Result, TrayError};
+use crate::tray::i18n;
+use crate::tray::shell;
+use crate::tray::window::register_class;
+use std::fs;
+use std::path::PathBuf;
+use windows::core::{w, HSTRING, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Graphics::Gdi::{
+    CreateFontW, DeleteObject, CLEARTYPE_QUALITY, CLIP_DEFAULT_PRECIS, DEFAULT_CHARSET, FF_MODERN,
+    FIXED_PITCH, FW_NORMAL, HFONT, OUT_DEFAULT_PRECIS,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, GetClientRect, GetWindow, IsWindow, MoveWindow,
+    SendMessageW, SetForegroundWindow, SetWindowTextW, ShowWindow, CW_USEDEFAULT, ES_AUTOHSCROLL,
+    ES_AUTOVSCROLL, ES_MULTILINE, ES_READONLY, GW_CHILD, SB_BOTTOM, SW_SHOWNORMAL, WINDOW_STYLE,
+    WM_SETFONT, WM_SIZE, WM_VSCROLL, WS_CHILD, WS_EX_CLIENTEDGE, WS_EX_LEFT, WS_HSCROLL,
+    WS_OVERLAPPEDWINDOW, WS_VISIBLE, WS_VSCROLL,
+};
+
+/// Size of the log window when it opens
+const WINDOW_WIDTH: i32 = 1000;
+const WINDOW_HEIGHT: i32 = 600;
+
+/// Height of the log window's font in pixels
+const FONT_HEIGHT: i32 = 16;
+
+/// %LOCALAPPDATA%\wemux\tray.log, where the tray logs
+pub fn default_log_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("wemux").join("tray.log"))
+}
+
+/// Open the folder of the tray's log in Explorer
+pub fn open_log_folder() -> Result<()> {
+    let Some(folder) = default_log_path().and_then(|p| p.parent().map(PathBuf::from)) else {
+        return Err(TrayError::Ui("The log folder is unknown".to_string()));
+    };
+    fs::create_dir_all(&folder)?;
+    shell::open_folder(&folder)
+}
+
+/// Window listing the last lines of the log
+///
+/// Created on the UI thread, whose message loop dispatches its messages;
+/// closing it destroys the window, and [`LogViewer::show`] opens a new one.
+pub struct LogViewer {
+    hwnd: HWND,
+    font: HFONT,
+}
+
+impl LogViewer {
+    /// Show the lines in the log ring, opening the window if it is closed
+    pub fn show(viewer: &mut Option<LogViewer>) -> Result<()> {
+        let open = viewer
+            .as_ref()
+            .is_some_and(|v| unsafe { IsWindow(v.hwnd).as_bool() });
+        if !open {
+            *viewer = Some(Self::create()?);
+        }
+        if let Some(viewer) = viewer {
+            viewer.refresh()?;
+        }
+        Ok(())
+    }
+
+    fn create() -> Result<Self> {
+        unsafe {
+            let instance = register_class(w!("wemux-log"), Some(log_window_proc))?;
+            let hwnd = CreateWindowExW(
+                WS_EX_LEFT,
+                w!("wemux-log"),
+                &HSTRING::from(i18n::text().log_title),
+                WS_OVERLAPPEDWINDOW,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                WINDOW_WIDTH,
+                WINDOW_HEIGHT,
+                None,
+                None,
+                instance,
+                None,
+            )?;
+            let edit_style = (ES_MULTILINE | ES_READONLY | ES_AUTOVSCROLL | ES_AUTOHSCROLL) as u32;
+            let edit = CreateWindowExW(
+                WS_EX_CLIENTEDGE,
+                w!("EDIT"),
+                PCWSTR::null(),
+                WS_CHILD | WS_VISIBLE | WS_VSCROLL | WS_HSCROLL | WINDOW_STYLE(edit_style),
+                0,
+                0,
+                0,
+                0,
+                hwnd,
+                None,
+                instance,
+                None,
+            )?;
+            fit_to_window(hwnd);
+
+            // Monospaced, so columns of the log line up
+            let font = CreateFontW(
+                FONT_HEIGHT,
+                0,
+                0,
+                0,
+                FW_NORMAL.0 as i32,
+                0,
+                0,
+                0,
+                DEFAULT_CHARSET.0 as u32,
+                OUT_DEFAULT_PRECIS.0 as u32,
+                CLIP_DEFAULT_PRECIS.0 as u32,
+                CLEARTYPE_QUALITY.0 as u32,
+                (FIXED_PITCH.0 | FF_MODERN.0) as u32,
+                w!("Consolas"),
+            );
+            SendMessageW(edit, WM_SETFONT, WPARAM(font.0 as usize), LPARAM(0));
+            Ok(Self { hwnd, font })
+        }
+    }
+
+    /// Fill the text box with the current lines, scrolled to the newest
+    fn refresh(&self) -> Result<()> {
+        let lines = LogRing::global().lines();
+        let text = if lines.is_empty() {
+            i18n::text().log_empty.to_string()
+        } else {
+            lines.join("\r\n")
+        };
+        unsafe {
+            let edit = GetWindow(self.hwnd, GW_CHILD)?;
+            SetWindowTextW(edit, &HSTRING::from(text))?;
+            SendMessageW(edit, WM_VSCROLL, WPARAM(SB_BOTTOM.0 as usize), LPARAM(0));
+            let _ = ShowWindow(self.hwnd, SW_SHOWNORMAL);
+            let _ = SetForegroundWindow(self.hwnd);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LogViewer {
+    fn drop(&mut self) {
+        unsafe {
+            if IsWindow(self.hwnd).as_bool() {
+                let _ = DestroyWindow(self.hwnd);
+            }
+            let _ = DeleteObject(self.font);
+        }
+    }
+}
+
+/// Size the text box to the window's client area
+unsafe fn fit_to_window(hwnd: HWND) {
+    let mut rect = RECT::default();
+    if let (Ok(edit), Ok(())) = (GetWindow(hwnd, GW_CHILD), GetClientRect(hwnd, &mut rect)) {
+        let _ = MoveWindow(edit, 0, 0, rect.right, rect.bottom, true);
+    }
+}
+
+unsafe extern "system" fn log_window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_SIZE {
+        fit_to_window(hwnd);
+        return LRESULT(0);
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
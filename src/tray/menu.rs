@@ -1,16 +1,40 @@
 //! Menu management for tray application
 
-use crate::audio::DeviceStatus;
+use crate::audio::{DeviceStatus, VolumeMode, ZoneStatus};
+use crate::device::DeviceState;
+use crate::logging::{self, LogLevel};
+use crate::sync::SyncStrictness;
+use crate::tray::i18n;
 use muda::{CheckMenuItem, Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu};
 use std::collections::HashMap;
 
+/// Mirror volumes offered in the Volume submenu, in percent
+const MIRROR_VOLUMES: [u8; 4] = [100, 75, 50, 25];
+
 /// Menu actions
 #[derive(Debug, Clone)]
 pub enum MenuAction {
     ToggleDevice(String),
+    ToggleZone(String),
     RefreshDevices,
+    RestoreSettings,
+    PruneDevices,
+    MeasureDelays,
+    OpenSoundSettings,
+    OpenDeviceProperties(String),
+    ToggleStartWithWindows,
+    SetLogLevel(LogLevel),
+    ViewLog,
+    OpenLogFolder,
+    SetSyncStrictness(SyncStrictness),
+    SetVolumeMode(VolumeMode),
+    /// Mirror volume in percent
+    SetMirrorVolume(u8),
     StartEngine,
     StopEngine,
+    TogglePauseAll,
+    ShowErrors,
+    ShowLastError,
     Exit,
 }
 
@@ -18,29 +42,50 @@ pub enum MenuAction {
 pub struct MenuManager {
     menu: Menu,
     device_submenu: Submenu,
-    device_items: HashMap<MenuId, String>, // MenuId -> device_id
+    device_items: HashMap<String, CheckMenuItem>, // device_id -> item
     actions: HashMap<MenuId, MenuAction>,
     default_output_item: MenuItem,
     status_item: MenuItem,
     start_item: MenuItem,
     stop_item: MenuItem,
+    pause_all_item: CheckMenuItem,
+    start_with_windows_item: CheckMenuItem,
+    log_level_items: Vec<(LogLevel, CheckMenuItem)>,
+    sync_items: Vec<(SyncStrictness, CheckMenuItem)>,
+    volume_mode_items: Vec<(VolumeMode, CheckMenuItem)>,
+    mirror_volume_items: Vec<(u8, CheckMenuItem)>,
     // Cached state for menu rebuilds
-    cached_default_output: String,
+    cached_default_output: Option<String>,
     cached_devices: Vec<DeviceStatus>,
+    cached_zones: Vec<ZoneStatus>,
     cached_engine_running: bool,
+    cached_paused_all: bool,
+    cached_start_with_windows: bool,
+    cached_log_level: LogLevel,
+    cached_sync_strictness: SyncStrictness,
+    cached_volume_mode: VolumeMode,
+    cached_mirror_volume: u8,
 }
 
 impl MenuManager {
     /// Create a new menu manager
     pub fn new() -> Self {
+        let text = i18n::text();
         let menu = Menu::new();
-        let device_submenu = Submenu::new("Output Devices", true);
+        let device_submenu = Submenu::new(text.output_devices, true);
 
         // Create placeholder items
-        let default_output_item = MenuItem::new("System Output: Unknown", false, None);
-        let status_item = MenuItem::new("wemux: Stopped", false, None);
-        let start_item = MenuItem::new("Start", true, None);
-        let stop_item = MenuItem::new("Stop", false, None);
+        let default_output_item = MenuItem::new(
+            format!("{}: {}", text.system_output, text.unknown),
+            false,
+            None,
+        );
+        let status_item = MenuItem::new(format!("wemux: {}", text.stopped), false, None);
+        let start_item = MenuItem::new(text.start, true, None);
+        let stop_item = MenuItem::new(text.stop, false, None);
+        let pause_all_item = CheckMenuItem::new(text.pause_all, false, false, None);
+        let start_with_windows_item =
+            CheckMenuItem::new(text.start_with_windows, true, false, None);
 
         Self {
             menu,
@@ -51,9 +96,22 @@ impl MenuManager {
             status_item,
             start_item,
             stop_item,
-            cached_default_output: "Unknown".to_string(),
+            pause_all_item,
+            start_with_windows_item,
+            log_level_items: Vec::new(),
+            sync_items: Vec::new(),
+            volume_mode_items: Vec::new(),
+            mirror_volume_items: Vec::new(),
+            cached_default_output: None,
             cached_devices: Vec::new(),
+            cached_zones: Vec::new(),
             cached_engine_running: false,
+            cached_paused_all: false,
+            cached_start_with_windows: false,
+            cached_log_level: logging::log_level().unwrap_or(LogLevel::Info),
+            cached_sync_strictness: SyncStrictness::default(),
+            cached_volume_mode: VolumeMode::default(),
+            cached_mirror_volume: 100,
         }
     }
 
@@ -63,55 +121,216 @@ impl MenuManager {
         self.device_items.clear();
         self.actions.clear();
 
+        let text = i18n::text();
         let menu = Menu::new();
 
         // System Output display (non-clickable) - use cached value
-        let output_text = format!("System Output: {}", self.cached_default_output);
+        let output_text = format!(
+            "{}: {}",
+            text.system_output,
+            self.cached_default_output
+                .as_deref()
+                .unwrap_or(text.unknown)
+        );
         self.default_output_item = MenuItem::new(&output_text, false, None);
         menu.append(&self.default_output_item)?;
 
         menu.append(&PredefinedMenuItem::separator())?;
 
         // Output Devices submenu - use cached devices
-        self.device_submenu = Submenu::new("Output Devices", true);
+        self.device_submenu = Submenu::new(text.output_devices, true);
         if self.cached_devices.is_empty() {
-            let no_devices = MenuItem::new("Not found", false, None);
+            let no_devices = MenuItem::new(text.no_devices, false, None);
             self.device_submenu.append(&no_devices)?;
         } else {
             for device in &self.cached_devices {
                 let label = self.format_device_label(device);
-                // System default devices are greyed out (disabled) and cannot be toggled
+                // System default and capture source devices are greyed out
+                // (disabled) and cannot be toggled, as are devices Windows
+                // reports disabled or unplugged
                 // Other devices can be toggled between Active and Disabled
-                let can_toggle = !device.is_system_default;
-                let is_active = !device.is_paused && !device.is_system_default;
+                let can_toggle = !device.is_auto_paused() && device.inactive.is_none();
+                let is_active = can_toggle && !device.is_paused;
                 let item = CheckMenuItem::new(&label, can_toggle, is_active, None);
-                let item_id = item.id().clone();
-                self.device_items.insert(item_id.clone(), device.id.clone());
-                self.actions
-                    .insert(item_id, MenuAction::ToggleDevice(device.id.clone()));
+                self.actions.insert(
+                    item.id().clone(),
+                    MenuAction::ToggleDevice(device.id.clone()),
+                );
                 self.device_submenu.append(&item)?;
+                self.device_items.insert(device.id.clone(), item);
             }
         }
         menu.append(&self.device_submenu)?;
 
+        // Windows' own pages for the devices
+        let properties_submenu =
+            Submenu::new(text.device_properties, !self.cached_devices.is_empty());
+        for device in &self.cached_devices {
+            let label = device.alias.as_deref().unwrap_or(&device.name);
+            let item = MenuItem::new(label, true, None);
+            self.actions.insert(
+                item.id().clone(),
+                MenuAction::OpenDeviceProperties(device.id.clone()),
+            );
+            properties_submenu.append(&item)?;
+        }
+        menu.append(&properties_submenu)?;
+
+        let sound_settings_item = MenuItem::new(text.sound_settings, true, None);
+        let sound_settings_id = sound_settings_item.id().clone();
+        self.actions
+            .insert(sound_settings_id, MenuAction::OpenSoundSettings);
+        menu.append(&sound_settings_item)?;
+
+        // Zones submenu, only when zones are configured
+        if !self.cached_zones.is_empty() {
+            let zone_submenu = Submenu::new(text.zones, true);
+            for zone in &self.cached_zones {
+                let item = CheckMenuItem::new(&zone.name, true, !zone.paused, None);
+                self.actions
+                    .insert(item.id().clone(), MenuAction::ToggleZone(zone.name.clone()));
+                zone_submenu.append(&item)?;
+            }
+            menu.append(&zone_submenu)?;
+        }
+
         menu.append(&PredefinedMenuItem::separator())?;
 
         // Control items - use cached engine state
-        self.start_item = MenuItem::new("Start", !self.cached_engine_running, None);
+        self.start_item = MenuItem::new(text.start, !self.cached_engine_running, None);
         let start_id = self.start_item.id().clone();
         self.actions.insert(start_id, MenuAction::StartEngine);
         menu.append(&self.start_item)?;
 
-        self.stop_item = MenuItem::new("Stop", self.cached_engine_running, None);
+        self.stop_item = MenuItem::new(text.stop, self.cached_engine_running, None);
         let stop_id = self.stop_item.id().clone();
         self.actions.insert(stop_id, MenuAction::StopEngine);
         menu.append(&self.stop_item)?;
 
-        let refresh_item = MenuItem::new("Refresh Devices", true, None);
+        // Silences the outputs without closing them, unlike Stop
+        self.pause_all_item = CheckMenuItem::new(
+            text.pause_all,
+            self.cached_engine_running,
+            self.cached_paused_all,
+            None,
+        );
+        self.actions
+            .insert(self.pause_all_item.id().clone(), MenuAction::TogglePauseAll);
+        menu.append(&self.pause_all_item)?;
+
+        let refresh_item = MenuItem::new(text.refresh_devices, true, None);
         let refresh_id = refresh_item.id().clone();
         self.actions.insert(refresh_id, MenuAction::RefreshDevices);
         menu.append(&refresh_item)?;
 
+        let restore_item = MenuItem::new(text.restore_settings, true, None);
+        let restore_id = restore_item.id().clone();
+        self.actions.insert(restore_id, MenuAction::RestoreSettings);
+        menu.append(&restore_item)?;
+
+        let prune_item = MenuItem::new(text.prune_devices, true, None);
+        let prune_id = prune_item.id().clone();
+        self.actions.insert(prune_id, MenuAction::PruneDevices);
+        menu.append(&prune_item)?;
+
+        let measure_item = MenuItem::new(text.measure_delays, true, None);
+        let measure_id = measure_item.id().clone();
+        self.actions.insert(measure_id, MenuAction::MeasureDelays);
+        menu.append(&measure_item)?;
+
+        self.start_with_windows_item = CheckMenuItem::new(
+            text.start_with_windows,
+            true,
+            self.cached_start_with_windows,
+            None,
+        );
+        let start_with_windows_id = self.start_with_windows_item.id().clone();
+        self.actions
+            .insert(start_with_windows_id, MenuAction::ToggleStartWithWindows);
+        menu.append(&self.start_with_windows_item)?;
+
+        // Drift allowed between outputs before they are corrected
+        let sync_submenu = Submenu::new(text.sync, true);
+        self.sync_items.clear();
+        for (label, strictness) in [
+            (text.sync_loose, SyncStrictness::Loose),
+            (text.sync_normal, SyncStrictness::Normal),
+            (text.sync_tight, SyncStrictness::Tight),
+        ] {
+            let item =
+                CheckMenuItem::new(label, true, self.cached_sync_strictness == strictness, None);
+            self.actions
+                .insert(item.id().clone(), MenuAction::SetSyncStrictness(strictness));
+            sync_submenu.append(&item)?;
+            self.sync_items.push((strictness, item));
+        }
+        menu.append(&sync_submenu)?;
+
+        // Volume of the outputs apart from the system volume
+        let volume_submenu = Submenu::new(text.volume, true);
+        self.volume_mode_items.clear();
+        for (label, mode) in [
+            (text.volume_follow, VolumeMode::Follow),
+            (text.volume_fixed, VolumeMode::Fixed),
+            (text.volume_scaled, VolumeMode::Scaled),
+        ] {
+            let item = CheckMenuItem::new(label, true, self.cached_volume_mode == mode, None);
+            self.actions
+                .insert(item.id().clone(), MenuAction::SetVolumeMode(mode));
+            volume_submenu.append(&item)?;
+            self.volume_mode_items.push((mode, item));
+        }
+        volume_submenu.append(&PredefinedMenuItem::separator())?;
+        self.mirror_volume_items.clear();
+        for percent in MIRROR_VOLUMES {
+            let item = CheckMenuItem::new(
+                format!("{}: {}%", text.mirror_volume, percent),
+                true,
+                self.cached_mirror_volume == percent,
+                None,
+            );
+            self.actions
+                .insert(item.id().clone(), MenuAction::SetMirrorVolume(percent));
+            volume_submenu.append(&item)?;
+            self.mirror_volume_items.push((percent, item));
+        }
+        menu.append(&volume_submenu)?;
+
+        // Log level of the tray and, while attached, of the service
+        let logging_submenu = Submenu::new(text.logging, true);
+        self.log_level_items.clear();
+        for (label, level) in [
+            (text.log_debug, LogLevel::Debug),
+            (text.log_info, LogLevel::Info),
+            (text.log_warn, LogLevel::Warn),
+        ] {
+            let item = CheckMenuItem::new(label, true, self.cached_log_level == level, None);
+            self.actions
+                .insert(item.id().clone(), MenuAction::SetLogLevel(level));
+            logging_submenu.append(&item)?;
+            self.log_level_items.push((level, item));
+        }
+        logging_submenu.append(&PredefinedMenuItem::separator())?;
+        let view_log_item = MenuItem::new(text.view_log, true, None);
+        self.actions
+            .insert(view_log_item.id().clone(), MenuAction::ViewLog);
+        logging_submenu.append(&view_log_item)?;
+        let log_folder_item = MenuItem::new(text.open_log_folder, true, None);
+        self.actions
+            .insert(log_folder_item.id().clone(), MenuAction::OpenLogFolder);
+        logging_submenu.append(&log_folder_item)?;
+        menu.append(&logging_submenu)?;
+
+        let errors_item = MenuItem::new(text.recent_errors, true, None);
+        let errors_id = errors_item.id().clone();
+        self.actions.insert(errors_id, MenuAction::ShowErrors);
+        menu.append(&errors_item)?;
+
+        let last_error_item = MenuItem::new(text.last_error, true, None);
+        self.actions
+            .insert(last_error_item.id().clone(), MenuAction::ShowLastError);
+        menu.append(&last_error_item)?;
+
         menu.append(&PredefinedMenuItem::separator())?;
 
         // Version info (non-clickable)
@@ -119,7 +338,7 @@ impl MenuManager {
         menu.append(&self.status_item)?;
 
         // Exit
-        let exit_item = MenuItem::new("Exit", true, None);
+        let exit_item = MenuItem::new(text.exit, true, None);
         let exit_id = exit_item.id().clone();
         self.actions.insert(exit_id, MenuAction::Exit);
         menu.append(&exit_item)?;
@@ -135,18 +354,57 @@ impl MenuManager {
         Ok(())
     }
 
+    /// Mark the outputs receiving audio, without rebuilding the menu
+    pub fn update_activity(&mut self, receiving: &[String]) {
+        for device in &mut self.cached_devices {
+            device.receiving_audio = receiving.contains(&device.id);
+        }
+        for device in &self.cached_devices {
+            if let Some(item) = self.device_items.get(&device.id) {
+                item.set_text(self.format_device_label(device));
+            }
+        }
+    }
+
+    /// Cache the zones shown on the next menu rebuild
+    pub fn update_zones(&mut self, zones: &[ZoneStatus]) {
+        self.cached_zones = zones.to_vec();
+    }
+
     fn format_device_label(&self, device: &DeviceStatus) -> String {
-        let mut label = device.name.clone();
+        let text = i18n::text();
+        let mut label = match &device.alias {
+            Some(alias) => format!("{} ({})", alias, device.name),
+            None => device.name.clone(),
+        };
+        if device.receiving_audio {
+            label.insert_str(0, "● ");
+        }
 
-        if device.is_system_default {
+        if let Some(state) = device.inactive {
+            // Windows can't play to the device until it is back
+            label.push(' ');
+            label.push_str(match state {
+                DeviceState::Disabled => text.device_disabled,
+                DeviceState::NotPresent => text.device_not_present,
+                _ => text.device_unplugged,
+            });
+        } else if device.is_system_default {
             // System default device - auto-paused to prevent feedback
-            label.push_str(" (System Default)");
+            label.push(' ');
+            label.push_str(text.system_default);
+        } else if device.is_capture_source {
+            // Captured output - auto-paused to prevent feedback
+            label.push(' ');
+            label.push_str(text.capture_source);
         } else if device.is_paused {
             // User manually disabled this device
-            label.push_str(" [Disabled]");
+            label.push(' ');
+            label.push_str(text.disabled);
         } else if device.is_enabled {
             // Active and outputting audio
-            label.push_str(" [Active]");
+            label.push(' ');
+            label.push_str(text.active);
         }
 
         label
@@ -157,11 +415,15 @@ impl MenuManager {
         // Cache engine state for menu rebuilds
         self.cached_engine_running = running;
 
-        let text = if running {
-            "wemux: Running"
-        } else {
-            "wemux: Stopped"
-        };
+        let strings = i18n::text();
+        let text = format!(
+            "wemux: {}",
+            if running {
+                strings.running
+            } else {
+                strings.stopped
+            }
+        );
 
         // Update status item text
         self.status_item.set_text(text);
@@ -169,10 +431,61 @@ impl MenuManager {
         // Update Start/Stop button states
         self.start_item.set_enabled(!running);
         self.stop_item.set_enabled(running);
+        self.pause_all_item.set_enabled(running);
 
         Ok(())
     }
 
+    /// Update the "Pause Mirroring" check mark
+    pub fn update_paused_all(&mut self, paused: bool) {
+        self.cached_paused_all = paused;
+        self.pause_all_item.set_checked(paused);
+    }
+
+    /// Update the "Start with Windows" check mark
+    pub fn update_start_with_windows(&mut self, enabled: bool) {
+        self.cached_start_with_windows = enabled;
+        self.start_with_windows_item.set_checked(enabled);
+    }
+
+    /// Whether "Start with Windows" is checked
+    pub fn start_with_windows(&self) -> bool {
+        self.cached_start_with_windows
+    }
+
+    /// Check the chosen log level in the Logging submenu
+    pub fn update_log_level(&mut self, level: LogLevel) {
+        self.cached_log_level = level;
+        for (item_level, item) in &self.log_level_items {
+            item.set_checked(*item_level == level);
+        }
+    }
+
+    /// Check the chosen strictness in the Sync submenu
+    pub fn update_sync_strictness(&mut self, strictness: SyncStrictness) {
+        self.cached_sync_strictness = strictness;
+        for (item_strictness, item) in &self.sync_items {
+            item.set_checked(*item_strictness == strictness);
+        }
+    }
+
+    /// Check the chosen mode in the Volume submenu
+    pub fn update_volume_mode(&mut self, mode: VolumeMode) {
+        self.cached_volume_mode = mode;
+        for (item_mode, item) in &self.volume_mode_items {
+            item.set_checked(*item_mode == mode);
+        }
+    }
+
+    /// Check the mirror volume in the Volume submenu; a volume not offered
+    /// there leaves all levels unchecked
+    pub fn update_mirror_volume(&mut self, volume: f32) {
+        self.cached_mirror_volume = (volume.clamp(0.0, 1.0) * 100.0).round() as u8;
+        for (percent, item) in &self.mirror_volume_items {
+            item.set_checked(*percent == self.cached_mirror_volume);
+        }
+    }
+
     /// Get action for a menu ID
     pub fn get_action(&self, id: &MenuId) -> Option<&MenuAction> {
         self.actions.get(id)
@@ -193,9 +506,9 @@ impl MenuManager {
     /// Update the system default output device display
     pub fn update_default_output(&mut self, device_name: &str) -> Result<(), muda::Error> {
         // Cache the default output for menu rebuilds
-        self.cached_default_output = device_name.to_string();
+        self.cached_default_output = Some(device_name.to_string());
         // Also update current menu item
-        let text = format!("System Output: {}", device_name);
+        let text = format!("{}: {}", i18n::text().system_output, device_name);
         self.default_output_item.set_text(&text);
         Ok(())
     }
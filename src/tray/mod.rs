@@ -6,17 +6,43 @@
 #[cfg(feature = "tray")]
 mod app;
 #[cfg(feature = "tray")]
+mod autostart;
+#[cfg(feature = "tray")]
 mod controller;
 #[cfg(feature = "tray")]
+mod error;
+#[cfg(feature = "tray")]
+mod hotkey;
+#[cfg(feature = "tray")]
+mod i18n;
+#[cfg(feature = "tray")]
 mod icon;
 #[cfg(feature = "tray")]
+mod log_viewer;
+#[cfg(feature = "tray")]
 mod menu;
 #[cfg(feature = "tray")]
+mod notification;
+#[cfg(feature = "tray")]
+mod power;
+#[cfg(feature = "tray")]
 mod settings;
+#[cfg(feature = "tray")]
+mod shell;
+#[cfg(feature = "tray")]
+mod window;
 
 #[cfg(feature = "tray")]
 pub use app::{TrayApp, TrayConfig};
 #[cfg(feature = "tray")]
 pub use controller::{EngineController, EngineStatus, TrayCommand};
 #[cfg(feature = "tray")]
-pub use settings::TraySettings;
+pub use error::{Diagnosis, TrayError};
+#[cfg(feature = "tray")]
+pub use hotkey::{HotkeyAction, HotkeySettings};
+#[cfg(feature = "tray")]
+pub use i18n::Language;
+#[cfg(feature = "tray")]
+pub use log_viewer::default_log_path;
+#[cfg(feature = "tray")]
+pub use settings::{NewDeviceDefaults, TraySettings, PRUNE_AFTER_DAYS};
@@ -0,0 +1,128 @@
+//! Balloon notifications about errors and warnings
+//!
+//! tray-icon doesn't expose the window of its icon, so the balloon comes from
+//! a second notification icon owned by a hidden window of our own. It is
+//! added with the balloon and removed once the balloon had time to show; on
+//! Windows 10 and later the balloon is shown as a toast and stays in the
+//! notification center.
+
+use crate::tray::error::Result;
+use crate::tray::window::hidden_window;
+use std::time::{Duration, Instant};
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::UI::Shell::{
+    Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_TIP, NIIF_ERROR, NIIF_WARNING, NIM_ADD, NIM_DELETE,
+    NOTIFYICONDATAW, NOTIFY_ICON_INFOTIP_FLAGS,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    DefWindowProcW, DestroyWindow, LoadIconW, IDI_ERROR, IDI_WARNING,
+};
+
+/// Time the notification icon stays after showing a balloon
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(10);
+
+/// ID of the notification icon within the hidden window
+const ICON_ID: u32 = 1;
+
+/// Shows errors and warnings as balloon notifications
+pub struct Notifier {
+    hwnd: HWND,
+    /// When the notification icon was added, if it is shown
+    shown_at: Option<Instant>,
+}
+
+impl Notifier {
+    /// Create the hidden window on the current thread, whose message loop
+    /// must dispatch its messages
+    pub fn new() -> Result<Self> {
+        let hwnd = hidden_window(w!("wemux-notifier"), Some(notifier_window_proc))?;
+        Ok(Self {
+            hwnd,
+            shown_at: None,
+        })
+    }
+
+    /// Show `message` in an error balloon, replacing one still shown
+    pub fn show_error(&mut self, title: &str, message: &str) -> Result<()> {
+        self.show(title, message, IDI_ERROR, NIIF_ERROR)
+    }
+
+    /// Show `message` in a warning balloon, replacing one still shown
+    pub fn show_warning(&mut self, title: &str, message: &str) -> Result<()> {
+        self.show(title, message, IDI_WARNING, NIIF_WARNING)
+    }
+
+    fn show(
+        &mut self,
+        title: &str,
+        message: &str,
+        icon: PCWSTR,
+        flags: NOTIFY_ICON_INFOTIP_FLAGS,
+    ) -> Result<()> {
+        self.remove();
+        let mut data = self.icon_data();
+        data.uFlags |= NIF_ICON | NIF_TIP | NIF_INFO;
+        data.hIcon = unsafe { LoadIconW(None, icon)? };
+        data.dwInfoFlags = flags;
+        copy_wide(&mut data.szTip, title);
+        copy_wide(&mut data.szInfoTitle, title);
+        copy_wide(&mut data.szInfo, message);
+        unsafe { Shell_NotifyIconW(NIM_ADD, &data).ok()? };
+        self.shown_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Remove the notification icon once its balloon had time to show
+    pub fn expire(&mut self) {
+        if self
+            .shown_at
+            .is_some_and(|shown| shown.elapsed() >= NOTIFICATION_DURATION)
+        {
+            self.remove();
+        }
+    }
+
+    fn remove(&mut self) {
+        if self.shown_at.take().is_some() {
+            unsafe {
+                let _ = Shell_NotifyIconW(NIM_DELETE, &self.icon_data());
+            }
+        }
+    }
+
+    fn icon_data(&self) -> NOTIFYICONDATAW {
+        NOTIFYICONDATAW {
+            cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: self.hwnd,
+            uID: ICON_ID,
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.remove();
+        unsafe {
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+unsafe extern "system" fn notifier_window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Copy `text` into a null-terminated UTF-16 field, cut to fit
+fn copy_wide(field: &mut [u16], text: &str) {
+    let len = field.len() - 1;
+    for (dest, unit) in field[..len].iter_mut().zip(text.encode_utf16()) {
+        *dest = unit;
+    }
+}
@@ -0,0 +1,72 @@
+//! Sleep and resume notifications for the tray
+//!
+//! `WM_POWERBROADCAST` only reaches top-level windows, so a hidden one
+//! records suspends and resumes for the message loop to pass on to the
+//! engine controller, which stops the engine before sleep and starts it
+//! again after resume.
+
+use crate::tray::error::Result;
+use crate::tray::window::hidden_window;
+use crate::tray::TrayCommand;
+use std::sync::atomic::{AtomicBool, Ordering};
+use windows::core::w;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::UI::WindowsAndMessaging::{
+    DefWindowProcW, DestroyWindow, PBT_APMRESUMEAUTOMATIC, PBT_APMSUSPEND, WM_POWERBROADCAST,
+};
+
+/// Set by the hidden window when the computer is about to sleep
+static SUSPENDING: AtomicBool = AtomicBool::new(false);
+
+/// Set by the hidden window when the computer woke up
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+/// Hidden top-level window receiving power broadcasts
+pub struct PowerWatcher {
+    hwnd: HWND,
+}
+
+impl PowerWatcher {
+    /// Create the window on the current thread, whose message loop must
+    /// dispatch its messages
+    pub fn new() -> Result<Self> {
+        let hwnd = hidden_window(w!("wemux-power-watcher"), Some(power_window_proc))?;
+        Ok(Self { hwnd })
+    }
+
+    /// Commands for the suspends and resumes since the last call, in order
+    pub fn commands(&self) -> Vec<TrayCommand> {
+        let mut commands = Vec::new();
+        if SUSPENDING.swap(false, Ordering::Relaxed) {
+            commands.push(TrayCommand::Suspend);
+        }
+        if RESUMED.swap(false, Ordering::Relaxed) {
+            commands.push(TrayCommand::Resume);
+        }
+        commands
+    }
+}
+
+impl Drop for PowerWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+unsafe extern "system" fn power_window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_POWERBROADCAST {
+        match wparam.0 as u32 {
+            PBT_APMSUSPEND => SUSPENDING.store(true, Ordering::Relaxed),
+            PBT_APMRESUMEAUTOMATIC => RESUMED.store(true, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
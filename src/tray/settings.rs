@@ -1,11 +1,37 @@
 //! Device settings persistence using TOML format
 
+use crate::audio::{DspStageConfig, EqSettings, VolumeMode, Zone, ZoneStatus};
+use crate::config::{bak_path, write_atomic, Backups, ConfigWatcher};
+use crate::device::{DeviceAliases, DeviceInfo, DeviceKind};
+use crate::service::schedule::ActiveHours;
+use crate::sync::SyncStrictness;
+use crate::tray::hotkey::HotkeySettings;
+use crate::tray::i18n::Language;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
+/// Devices not seen for this many days are removed by "Clean Up Old Devices"
+pub const PRUNE_AFTER_DAYS: u64 = 30;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Time the settings file must stay unchanged before an outside edit is
+/// reloaded, so an editor saving in several writes is read once, complete
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Settings file given on the command line, used instead of the default one
+static CUSTOM_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set when a damaged settings file was replaced by its `.bak`, until the
+/// tray has told the user
+static RECOVERED: AtomicBool = AtomicBool::new(false);
+
 /// Device setting entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceSetting {
@@ -13,6 +39,65 @@ pub struct DeviceSetting {
     pub name: String,
     /// Whether the device is enabled
     pub enabled: bool,
+    /// When the device was last present, in seconds since the Unix epoch
+    ///
+    /// Updated at most once a day so device refreshes don't rewrite the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+    /// Equalizer gains from 31Hz to 16kHz in dB (omitted when flat)
+    #[serde(default, skip_serializing_if = "EqSettings::is_flat")]
+    pub equalizer: EqSettings,
+    /// Extra delay in milliseconds, measured by "Measure Audio Delay" (omitted when 0)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay_ms: u32,
+    /// Buffer in milliseconds, in place of the engine's, for a device that
+    /// needs a bigger one (edited in the file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer_ms: Option<u32>,
+    /// Processing stages, edited in the file (see `dsp` in the service config)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dsp: Vec<DspStageConfig>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Whether devices the tray sees for the first time start enabled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NewDeviceDefaults {
+    /// Default for kinds without their own setting
+    pub enabled: bool,
+    /// HDMI and DisplayPort outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hdmi: Option<bool>,
+    /// Bluetooth headsets and speakers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bluetooth: Option<bool>,
+}
+
+impl Default for NewDeviceDefaults {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hdmi: None,
+            // A headset turning on shouldn't start playing the duplicated audio
+            bluetooth: Some(false),
+        }
+    }
+}
+
+impl NewDeviceDefaults {
+    /// Whether a new device of this kind starts enabled
+    pub fn enabled_for(&self, kind: DeviceKind) -> bool {
+        let specific = match kind {
+            DeviceKind::Hdmi => self.hdmi,
+            DeviceKind::Bluetooth => self.bluetooth,
+            DeviceKind::Other => None,
+        };
+        specific.unwrap_or(self.enabled)
+    }
 }
 
 /// Settings structure for persistence
@@ -21,38 +106,182 @@ pub struct TraySettings {
     /// Device settings keyed by device ID
     #[serde(default)]
     pub devices: HashMap<String, DeviceSetting>,
+    /// User-assigned device aliases
+    #[serde(default)]
+    pub aliases: DeviceAliases,
+    /// Global hotkey bindings
+    #[serde(default)]
+    pub hotkeys: HotkeySettings,
+    /// Enabled state given to newly discovered devices
+    #[serde(default)]
+    pub new_devices: NewDeviceDefaults,
+    /// Language of the menu and tooltips (None = the Windows locale's)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    /// Start the tray at logon (kept in sync with the Run registry key)
+    #[serde(default)]
+    pub start_with_windows: bool,
+    /// Latency assumed for Bluetooth outputs (None = `BLUETOOTH_LATENCY_MS`, 0 = no compensation)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bluetooth_latency_ms: Option<u32>,
+    /// Fade when an output is paused or resumed (None = `PAUSE_FADE_MS`, 0 = none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause_fade_ms: Option<u32>,
+    /// Capture on a dedicated core, polling instead of sleeping
+    #[serde(default)]
+    pub performance_capture: bool,
+    /// How tightly outputs are kept in step, set from the Sync menu
+    #[serde(default)]
+    pub sync_strictness: SyncStrictness,
+    /// Whether the outputs follow the system volume, set from the Volume menu
+    #[serde(default)]
+    pub volume_mode: VolumeMode,
+    /// Volume of the outputs in the fixed and scaled modes (None = full)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_volume: Option<f32>,
+    /// Apps (executable names) whose sound is not mirrored
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_apps: Vec<String>,
+    /// Groups of outputs with their own source; when set, only these play
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub zones: Vec<Zone>,
+    /// Times audio sync runs; the tray starts and stops it as they begin and
+    /// end (empty = no automatic start or stop)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub active_hours: Vec<ActiveHours>,
+    /// Start outputs paused that reach the same sink as another output
+    #[serde(default)]
+    pub suppress_duplicates: bool,
+    /// Device IDs that play even when they duplicate another output; a
+    /// suppressed duplicate resumed from the menu is added here
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_duplicates: Vec<String>,
+    /// Version of the settings file these settings were read from or written to
+    #[serde(skip)]
+    file: ConfigWatcher,
 }
 
 impl TraySettings {
     /// Load settings from file, returns default if file doesn't exist
+    ///
+    /// A file that can't be read is recovered from its `.bak` when that one
+    /// can; see [`take_recovered`](Self::take_recovered).
     pub fn load() -> Self {
         let path = Self::settings_path();
+        // Taken before reading so an edit made meanwhile is seen as a change
+        let mut file = ConfigWatcher::new(Some(path.clone()));
 
         if !path.exists() {
             debug!("Settings file not found, using defaults");
-            return Self::default();
+            return Self {
+                file,
+                ..Self::default()
+            };
         }
 
-        match fs::read_to_string(&path) {
-            Ok(content) => match toml::from_str(&content) {
-                Ok(settings) => {
-                    info!("Loaded settings from {:?}", path);
-                    settings
-                }
-                Err(e) => {
-                    warn!("Failed to parse settings file: {}", e);
-                    Self::default()
+        let settings = match Self::read(&path) {
+            Ok(settings) => {
+                info!("Loaded settings from {:?}", path);
+                settings
+            }
+            Err(e) => {
+                warn!("Failed to load settings file: {}", e);
+                match Self::recover(&path) {
+                    Some(settings) => {
+                        // Our own write is not an external change
+                        file.check(Some(path));
+                        settings
+                    }
+                    None => Self::default(),
                 }
-            },
+            }
+        };
+        Self { file, ..settings }
+    }
+
+    /// Settings from the `.bak` of a damaged file, put back in its place
+    ///
+    /// The damaged file is kept among the backups.
+    fn recover(path: &Path) -> Option<Self> {
+        let bak = bak_path(path);
+        let settings = match Self::read(&bak) {
+            Ok(settings) => settings,
+            Err(e) => {
+                debug!("No usable settings backup {:?}: {}", bak, e);
+                return None;
+            }
+        };
+        if let Err(e) = Backups::new(path).create() {
+            warn!("Failed to back up damaged settings: {}", e);
+        }
+        if let Err(e) = fs::copy(&bak, path) {
+            warn!("Failed to restore settings from {:?}: {}", bak, e);
+        }
+        warn!("Recovered settings from {:?}", bak);
+        RECOVERED.store(true, Ordering::SeqCst);
+        Some(settings)
+    }
+
+    /// Whether settings were recovered from their `.bak` since the last call
+    pub fn take_recovered() -> bool {
+        RECOVERED.swap(false, Ordering::SeqCst)
+    }
+
+    fn read(path: &Path) -> Result<Self, std::io::Error> {
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Pick up changes another process (the CLI, an editor) made to the file
+    ///
+    /// Returns true if the settings were replaced. A change is picked up once
+    /// the file stayed the same for [`RELOAD_DEBOUNCE`], so this is meant to
+    /// be called periodically. A file that no longer parses is ignored so a
+    /// half-finished edit doesn't reset everything.
+    pub fn reload_if_changed(&mut self) -> bool {
+        self.reload(RELOAD_DEBOUNCE)
+    }
+
+    /// Reload the file if it changed and stayed the same for `debounce`
+    fn reload(&mut self, debounce: Duration) -> bool {
+        let path = Self::settings_path();
+        if !self.file.check_settled(Some(path.clone()), debounce) || !path.exists() {
+            return false;
+        }
+
+        match Self::read(&path) {
+            Ok(settings) => {
+                info!("Settings file changed, reloaded {:?}", path);
+                *self = Self {
+                    file: self.file.clone(),
+                    ..settings
+                };
+                true
+            }
             Err(e) => {
-                warn!("Failed to read settings file: {}", e);
-                Self::default()
+                warn!("Ignoring changed settings file: {}", e);
+                false
             }
         }
     }
 
-    /// Save settings to file
-    pub fn save(&self) -> Result<(), std::io::Error> {
+    /// Apply a change on top of the latest settings file and save the result
+    ///
+    /// Reloading first keeps edits made outside the tray from being
+    /// overwritten by stale in-memory settings.
+    pub fn update(&mut self, change: impl FnOnce(&mut Self)) -> Result<(), std::io::Error> {
+        // Not debounced: the change is about to be written over the file
+        self.reload(Duration::ZERO);
+        change(self);
+        self.save()
+    }
+
+    /// Save settings to file, backing up the previous version
+    ///
+    /// The file is replaced atomically, keeping the previous version as its
+    /// `.bak`.
+    pub fn save(&mut self) -> Result<(), std::io::Error> {
         let path = Self::settings_path();
 
         // Ensure directory exists
@@ -63,18 +292,70 @@ impl TraySettings {
         let content = toml::to_string_pretty(self)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
 
-        fs::write(&path, content)?;
-        info!("Saved settings to {:?}", path);
+        // Device refreshes save often; only real changes deserve a backup
+        if fs::read_to_string(&path).is_ok_and(|current| current == content) {
+            debug!("Settings unchanged, not saving");
+        } else {
+            if let Err(e) = Backups::new(&path).create() {
+                warn!("Failed to back up settings: {}", e);
+            }
+            write_atomic(&path, &content)?;
+            info!("Saved settings to {:?}", path);
+        }
+
+        // Our own write is not an external change
+        self.file.check(Some(path));
         Ok(())
     }
 
-    /// Get settings file path (same directory as executable)
-    fn settings_path() -> PathBuf {
+    /// Replace the settings file with its newest backup and load it
+    ///
+    /// Returns None if there is no backup to restore.
+    pub fn restore_previous() -> Result<Option<Self>, std::io::Error> {
+        match Backups::new(Self::settings_path()).restore_latest()? {
+            Some(_) => Ok(Some(Self::load())),
+            None => Ok(None),
+        }
+    }
+
+    /// Read and write settings at `path` for the rest of this process
+    ///
+    /// Only the first call has an effect.
+    pub fn use_path(path: PathBuf) {
+        let _ = CUSTOM_PATH.set(path);
+    }
+
+    /// Settings file set with [`use_path`](Self::use_path), if any
+    pub fn custom_path() -> Option<&'static Path> {
+        CUSTOM_PATH.get().map(PathBuf::as_path)
+    }
+
+    /// Get settings file path (same directory as executable, unless set with `use_path`)
+    pub fn settings_path() -> PathBuf {
+        if let Some(path) = CUSTOM_PATH.get() {
+            return path.clone();
+        }
+        Self::default_dir().join("wemux-tray.toml")
+    }
+
+    /// Settings file of a named profile, `wemux-tray-<name>.toml` next to
+    /// the executable
+    ///
+    /// Returns None for a name that isn't a plain file name part.
+    pub fn profile_path(name: &str) -> Option<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '));
+        valid.then(|| Self::default_dir().join(format!("wemux-tray-{}.toml", name)))
+    }
+
+    /// Directory of the executable, where settings files are kept
+    fn default_dir() -> PathBuf {
         std::env::current_exe()
             .ok()
             .and_then(|p| p.parent().map(|p| p.to_path_buf()))
             .unwrap_or_else(|| PathBuf::from("."))
-            .join("wemux-tray.toml")
     }
 
     /// Check if a device is enabled in settings
@@ -88,33 +369,273 @@ impl TraySettings {
 
     /// Set device enabled state
     pub fn set_device_enabled(&mut self, device_id: &str, name: &str, enabled: bool) {
-        self.devices.insert(
-            device_id.to_string(),
-            DeviceSetting {
-                name: name.to_string(),
+        let setting = self
+            .devices
+            .entry(device_id.to_string())
+            .or_insert_with(|| DeviceSetting {
+                name: String::new(),
                 enabled,
-            },
-        );
+                last_seen: None,
+                equalizer: EqSettings::default(),
+                delay_ms: 0,
+                buffer_ms: None,
+                dsp: Vec::new(),
+            });
+        setting.name = name.to_string();
+        setting.enabled = enabled;
+    }
+
+    /// Equalizer settings of every device that isn't flat
+    pub fn equalizers(&self) -> HashMap<String, EqSettings> {
+        self.devices
+            .iter()
+            .filter(|(_, setting)| !setting.equalizer.is_flat())
+            .map(|(id, setting)| (id.clone(), setting.equalizer))
+            .collect()
+    }
+
+    /// Delays of every device that has one
+    pub fn delays(&self) -> HashMap<String, u32> {
+        self.devices
+            .iter()
+            .filter(|(_, setting)| setting.delay_ms > 0)
+            .map(|(id, setting)| (id.clone(), setting.delay_ms))
+            .collect()
+    }
+
+    /// Buffers of every device that has its own
+    pub fn buffer_ms(&self) -> HashMap<String, u32> {
+        self.devices
+            .iter()
+            .filter_map(|(id, setting)| Some((id.clone(), setting.buffer_ms?)))
+            .collect()
+    }
+
+    /// Processing stages of every device that has some
+    pub fn dsp(&self) -> HashMap<String, Vec<DspStageConfig>> {
+        self.devices
+            .iter()
+            .filter(|(_, setting)| !setting.dsp.is_empty())
+            .map(|(id, setting)| (id.clone(), setting.dsp.clone()))
+            .collect()
+    }
+
+    /// Whether each zone starts paused, for the menu while the engine is stopped
+    pub fn zone_statuses(&self) -> Vec<ZoneStatus> {
+        self.zones
+            .iter()
+            .map(|zone| ZoneStatus {
+                name: zone.name.clone(),
+                paused: zone.paused,
+            })
+            .collect()
     }
 
     /// Update settings from device list, adding new devices as enabled
-    pub fn update_from_devices(&mut self, devices: &[(String, String)]) {
-        for (id, name) in devices {
+    ///
+    /// Settings of aliased devices whose ID changed (e.g. after a driver
+    /// reinstall) move to the new ID instead of starting over.
+    pub fn update_from_devices(&mut self, devices: &[DeviceInfo]) {
+        self.update_from_devices_at(devices, unix_now());
+    }
+
+    fn update_from_devices_at(&mut self, devices: &[DeviceInfo], now: u64) {
+        for (old_id, new_id) in self.aliases.refresh_ids(devices) {
+            if let Some(setting) = self.devices.remove(&old_id) {
+                info!(
+                    "Device ID changed, keeping settings: {} -> {}",
+                    old_id, new_id
+                );
+                self.devices.insert(new_id, setting);
+            }
+        }
+
+        for device @ DeviceInfo { id, name, .. } in devices {
             if !self.devices.contains_key(id) {
-                // New device, add as enabled by default
+                // New device, enabled according to the defaults for its kind
+                let enabled = self.new_devices.enabled_for(device.kind());
+                if !enabled {
+                    info!("New device {} added disabled", name);
+                }
                 self.devices.insert(
                     id.clone(),
                     DeviceSetting {
                         name: name.clone(),
-                        enabled: true,
+                        enabled,
+                        last_seen: Some(now),
+                        equalizer: EqSettings::default(),
+                        delay_ms: 0,
+                        buffer_ms: None,
+                        dsp: Vec::new(),
                     },
                 );
             } else {
                 // Update name in case it changed
                 if let Some(setting) = self.devices.get_mut(id) {
                     setting.name = name.clone();
+                    if setting
+                        .last_seen
+                        .is_some_and(|seen| now.saturating_sub(seen) >= SECONDS_PER_DAY)
+                    {
+                        setting.last_seen = Some(now);
+                    }
                 }
             }
         }
+
+        // Entries written before last-seen tracking start counting from now
+        for setting in self.devices.values_mut() {
+            setting.last_seen.get_or_insert(now);
+        }
+    }
+
+    /// Remove devices not seen for `days` days, returning their names
+    pub fn prune(&mut self, days: u64) -> Vec<String> {
+        self.prune_at(days, unix_now())
+    }
+
+    fn prune_at(&mut self, days: u64, now: u64) -> Vec<String> {
+        let max_age = days.saturating_mul(SECONDS_PER_DAY);
+        let mut removed = Vec::new();
+        self.devices.retain(|_, setting| {
+            let stale = setting
+                .last_seen
+                .is_some_and(|seen| now.saturating_sub(seen) > max_age);
+            if stale {
+                removed.push(setting.name.clone());
+            }
+            !stale
+        });
+
+        removed.sort();
+        removed
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str) -> DeviceInfo {
+        DeviceInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            is_hdmi: true,
+            is_default: false,
+            container_id: None,
+            is_bluetooth: false,
+        }
+    }
+
+    #[test]
+    fn test_profile_path() {
+        let path = TraySettings::profile_path("Living Room").unwrap();
+        assert_eq!(
+            path.file_name().unwrap().to_str(),
+            Some("wemux-tray-Living Room.toml")
+        );
+        assert_eq!(path.parent(), TraySettings::settings_path().parent());
+
+        assert_eq!(TraySettings::profile_path(""), None);
+        assert_eq!(TraySettings::profile_path("..\\other"), None);
+        assert_eq!(TraySettings::profile_path("a/b"), None);
+    }
+
+    #[test]
+    fn test_prune_devices_not_seen() {
+        let mut settings: TraySettings = toml::from_str(
+            r#"
+            [devices.old]
+            name = "Old TV"
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        let day = SECONDS_PER_DAY;
+
+        // Entries without a timestamp start their grace period at the first refresh
+        settings.update_from_devices_at(&[device("tv", "TV")], 10 * day);
+        assert_eq!(settings.devices["old"].last_seen, Some(10 * day));
+
+        // Timestamps only move once a day
+        settings.update_from_devices_at(&[device("tv", "TV")], 10 * day + 60);
+        assert_eq!(settings.devices["tv"].last_seen, Some(10 * day));
+        settings.update_from_devices_at(&[device("tv", "TV")], 45 * day);
+        assert_eq!(settings.devices["tv"].last_seen, Some(45 * day));
+
+        assert!(settings.prune_at(PRUNE_AFTER_DAYS, 40 * day).is_empty());
+        assert_eq!(settings.prune_at(PRUNE_AFTER_DAYS, 45 * day), ["Old TV"]);
+        assert!(settings.devices.contains_key("tv"));
+    }
+
+    #[test]
+    fn test_new_device_defaults() {
+        let not_hdmi = |id, name| DeviceInfo {
+            is_hdmi: false,
+            ..device(id, name)
+        };
+        let devices = [
+            device("tv", "TV"),
+            not_hdmi("bt", "Headset (Bluetooth Hands-Free)"),
+            not_hdmi("usb", "USB DAC"),
+        ];
+
+        let mut settings = TraySettings::default();
+        settings.update_from_devices(&devices);
+        assert!(settings.devices["tv"].enabled);
+        assert!(!settings.devices["bt"].enabled);
+        assert!(settings.devices["usb"].enabled);
+
+        let mut settings: TraySettings = toml::from_str(
+            r#"
+            [new_devices]
+            enabled = false
+            hdmi = true
+            "#,
+        )
+        .unwrap();
+        settings.update_from_devices(&devices);
+        assert!(settings.devices["tv"].enabled);
+        assert!(!settings.devices["bt"].enabled);
+        assert!(!settings.devices["usb"].enabled);
+    }
+
+    #[test]
+    fn test_device_equalizer() {
+        let mut settings: TraySettings = toml::from_str(
+            r#"
+            [devices.ceiling]
+            name = "Ceiling Speakers"
+            enabled = true
+            equalizer = { gains_db = [-9, -6, -3, 0, 0, 0, 0, 0, 0, 0] }
+
+            [devices.tv]
+            name = "TV"
+            enabled = true
+            buffer_ms = 120
+            "#,
+        )
+        .unwrap();
+
+        let equalizers = settings.equalizers();
+        assert_eq!(equalizers.len(), 1);
+        assert_eq!(equalizers["ceiling"].gains_db[0], -9.0);
+        assert_eq!(
+            settings.buffer_ms(),
+            HashMap::from([("tv".to_string(), 120)])
+        );
+
+        // Toggling a device keeps its equalizer; flat ones aren't written
+        settings.set_device_enabled("ceiling", "Ceiling Speakers", false);
+        let text = toml::to_string(&settings).unwrap();
+        assert!(text.contains("gains_db = [-9.0, -6.0, -3.0"));
+        assert_eq!(text.matches("gains_db").count(), 1);
     }
 }
@@ -0,0 +1,60 @@
+//! Opening the Windows sound settings from the tray
+//!
+//! Output devices are set up in Windows itself (spatial sound, format,
+//! enhancements), so the tray links there instead of duplicating it. Pages
+//! are opened through the shell like any `ms-settings:` link, folders in
+//! Explorer the same way.
+
+use crate::tray::error::{Result, TrayError};
+use std::path::Path;
+use tracing::info;
+use windows::core::{w, HSTRING, PCWSTR};
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+/// Sound page of the Settings app
+const SOUND_SETTINGS: &str = "ms-settings:sound";
+
+/// Open the Windows sound settings
+pub fn open_sound_settings() -> Result<()> {
+    open(SOUND_SETTINGS)
+}
+
+/// Open the properties page of an output device
+///
+/// Windows 11 shows the device's own page; Windows 10 doesn't know the
+/// link's `endpointId` and shows the sound settings instead.
+pub fn open_device_properties(device_id: &str) -> Result<()> {
+    open(&format!(
+        "ms-settings:sound-properties?endpointId={}",
+        device_id
+    ))
+}
+
+/// Open a folder in Explorer
+pub fn open_folder(path: &Path) -> Result<()> {
+    open(&path.to_string_lossy())
+}
+
+/// Let the shell open a link or file
+fn open(target: &str) -> Result<()> {
+    info!("Opening {}", target);
+    let result = unsafe {
+        ShellExecuteW(
+            None,
+            w!("open"),
+            &HSTRING::from(target),
+            PCWSTR::null(),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are error codes
+    if result.0 as usize <= 32 {
+        return Err(TrayError::Ui(format!(
+            "Failed to open {} (error {})",
+            target, result.0 as usize
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Windows of the tray's own classes
+//!
+//! Theme, power and notification messages only reach top-level windows, so
+//! each is received by a hidden window on the tray's thread.
+
+use crate::tray::error::Result;
+use windows::core::{w, PCWSTR};
+use windows::Win32::Foundation::{HINSTANCE, HWND};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, RegisterClassW, WINDOW_EX_STYLE, WINDOW_STYLE, WNDCLASSW, WNDPROC,
+};
+
+/// Register the window class `class` handled by `wndproc`
+///
+/// Registering again fails harmlessly; creating a window reports errors.
+/// Returns the module instance the class belongs to.
+pub(crate) fn register_class(class: PCWSTR, wndproc: WNDPROC) -> Result<HINSTANCE> {
+    unsafe {
+        let instance = GetModuleHandleW(None)?;
+        RegisterClassW(&WNDCLASSW {
+            lpfnWndProc: wndproc,
+            hInstance: instance.into(),
+            lpszClassName: class,
+            ..Default::default()
+        });
+        Ok(instance.into())
+    }
+}
+
+/// Create a hidden top-level window of the class `class` handled by
+/// `wndproc`, on the current thread, whose message loop must dispatch its
+/// messages
+pub(crate) fn hidden_window(class: PCWSTR, wndproc: WNDPROC) -> Result<HWND> {
+    let instance = register_class(class, wndproc)?;
+    unsafe {
+        Ok(CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            class,
+            w!("wemux"),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            instance,
+            None,
+        )?)
+    }
+}